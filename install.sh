@@ -57,9 +57,9 @@ chmod +x "${INSTALL_DIR}/${BARTENDER_BINARY}"
 chmod +x "${INSTALL_DIR}/${CLI_BINARY}"
 
 # Copy the configuration file
-if [[ -f "${SCRIPT_DIR}/sketchybartenderrc" ]]; then
-    cp -f "${SCRIPT_DIR}/sketchybartenderrc" "${CONFIG_DIR}/sketchybartenderrc"
-    echo "✓ Configuration installed to ${CONFIG_DIR}/sketchybartenderrc"
+if [[ -f "${SCRIPT_DIR}/sketchybartender.toml" ]]; then
+    cp -f "${SCRIPT_DIR}/sketchybartender.toml" "${CONFIG_DIR}/sketchybartender.toml"
+    echo "✓ Configuration installed to ${CONFIG_DIR}/sketchybartender.toml"
 fi
 
 echo "✓ sketchybartender installed to ${INSTALL_DIR}/${BARTENDER_BINARY}"
@@ -89,6 +89,6 @@ echo "  - ${INSTALL_DIR}/${BARTENDER_BINARY}"
 echo "  - ${INSTALL_DIR}/${CLI_BINARY}"
 echo ""
 echo "Configuration:"
-echo "  - ${CONFIG_DIR}/sketchybartenderrc"
+echo "  - ${CONFIG_DIR}/sketchybartender.toml"
 echo "  - ${CONFIG_DIR}/sketchybarrc"
 echo ""
\ No newline at end of file
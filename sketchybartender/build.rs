@@ -1,16 +1,100 @@
 use serde::Deserialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::path::Path;
 
+#[path = "src/normalize.rs"]
+mod normalize;
+
+// Only the parser is needed at build time
+#[path = "src/glob.rs"]
+#[allow(dead_code)]
+mod glob;
+
+use glob::Token;
+use normalize::{normalize_name, normalize_prefix};
+
+/// Render compiled glob tokens as a Rust slice expression
+fn tokens_source(tokens: &[Token]) -> String {
+    let items: Vec<String> = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Literal(text) => format!("Token::Literal(std::borrow::Cow::Borrowed({:?}))", text),
+            Token::AnyChar => "Token::AnyChar".to_string(),
+            Token::AnyString => "Token::AnyString".to_string(),
+            Token::Class { negated, ranges } => {
+                let ranges: Vec<String> = ranges
+                    .iter()
+                    .map(|(lo, hi)| format!("({:?}, {:?})", lo, hi))
+                    .collect();
+                format!(
+                    "Token::Class {{ negated: {}, ranges: std::borrow::Cow::Borrowed(&[{}]) }}",
+                    negated,
+                    ranges.join(", ")
+                )
+            }
+        })
+        .collect();
+    format!("&[{}]", items.join(", "))
+}
+
 #[derive(Deserialize)]
 struct IconEntry {
     #[serde(rename = "iconName")]
     icon_name: String,
     #[serde(rename = "appNames")]
     app_names: Vec<String>,
+    #[serde(rename = "bundleIds", default)]
+    bundle_ids: Vec<String>,
+    /// Glyphs for the alternative icon themes, keyed by theme name
+    #[serde(default)]
+    icons: HashMap<String, String>,
+}
+
+/// Alternative icon themes and the generated map holding each one's glyphs
+const THEMES: &[(&str, &str)] = &[("nerdfont", "NERDFONT_ICONS"), ("emoji", "EMOJI_ICONS")];
+
+/// Record a mapping key, returning whether it is new
+///
+/// A repeated key with the same icon is harmless and skipped; one with a
+/// different icon is reported, since only the first would ever be used.
+fn check_duplicate(
+    seen: &mut HashMap<String, (String, String)>,
+    key: &str,
+    name: &str,
+    icon: &str,
+    errors: &mut Vec<String>,
+) -> bool {
+    match seen.get(key) {
+        None => {
+            seen.insert(key.to_string(), (icon.to_string(), name.to_string()));
+            true
+        }
+        Some((first_icon, first_name)) => {
+            if first_icon != icon {
+                errors.push(format!(
+                    "\"{}\" maps to {} but \"{}\" already maps to {}",
+                    name, icon, first_name, first_icon
+                ));
+            }
+            false
+        }
+    }
+}
+
+/// Describe what is wrong with a theme glyph, if anything
+fn glyph_problem(glyph: &str) -> Option<&'static str> {
+    if glyph.trim().is_empty() {
+        Some("is empty")
+    } else if glyph.contains('\u{FFFD}') {
+        Some("contains a U+FFFD replacement character (broken encoding)")
+    } else if glyph.chars().any(char::is_control) {
+        Some("contains control characters")
+    } else {
+        None
+    }
 }
 
 fn main() {
@@ -19,46 +103,111 @@ fn main() {
 
     // Tell Cargo to rerun if the JSON changes
     println!("cargo:rerun-if-changed=src/icon_map.json");
+    println!("cargo:rerun-if-changed=src/normalize.rs");
+    println!("cargo:rerun-if-changed=src/glob.rs");
 
     // Read and parse the JSON file
     let json_path = Path::new("src/icon_map.json");
-    let file = File::open(json_path).expect("Failed to open icon_map.json");
-    let reader = BufReader::new(file);
+    let bytes = fs::read(json_path).expect("Failed to open icon_map.json");
+    let contents = String::from_utf8(bytes).unwrap_or_else(|e| {
+        let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+        let line = valid.iter().filter(|b| **b == b'\n').count() + 1;
+        panic!("icon_map.json: invalid UTF-8 on line {}", line)
+    });
     let entries: Vec<IconEntry> =
-        serde_json::from_reader(reader).expect("Failed to parse icon_map.json");
+        serde_json::from_str(&contents).expect("Failed to parse icon_map.json");
 
-    // Track seen app names to avoid duplicates (first occurrence wins)
-    let mut seen: HashSet<String> = HashSet::new();
+    // Every problem found, reported together so one build shows them all
+    let mut errors: Vec<String> = Vec::new();
+
+    // Keys are stored normalized so lookups ignore case, spacing and accents.
+    // Track seen keys (with the icon and the name as written) to reject conflicting duplicates
+    let mut seen: HashMap<String, (String, String)> = HashMap::new();
+    let mut seen_bundles: HashMap<String, (String, String)> = HashMap::new();
 
     // Separate exact matches from prefix patterns
     let mut exact_matches: Vec<(String, String)> = Vec::new();
     let mut prefix_patterns: Vec<(String, String)> = Vec::new();
+    let mut glob_patterns: Vec<(String, Vec<Token>, String)> = Vec::new();
+    let mut bundle_matches: Vec<(String, String)> = Vec::new();
+    // Prefixes as written, for overlap reporting
+    let mut prefix_names: Vec<String> = Vec::new();
 
     for entry in &entries {
+        let icon = &entry.icon_name;
+        if icon.trim().is_empty() || icon == "::" {
+            errors.push(format!("empty iconName for {:?}", entry.app_names));
+        }
+
+        for (theme, glyph) in &entry.icons {
+            if !THEMES.iter().any(|(name, _)| name == theme) {
+                errors.push(format!("unknown icon theme \"{}\" for {}", theme, icon));
+            }
+            if let Some(problem) = glyph_problem(glyph) {
+                errors.push(format!("{} glyph for {} {}", theme, icon, problem));
+            }
+        }
+
+        for bundle_id in &entry.bundle_ids {
+            let key = normalize_name(bundle_id);
+            if check_duplicate(&mut seen_bundles, &key, bundle_id, icon, &mut errors) {
+                bundle_matches.push((key, icon.clone()));
+            }
+        }
+
         for app_name in &entry.app_names {
-            if app_name.ends_with('*') {
+            let is_prefix = app_name.ends_with('*') && !glob::is_glob(&app_name[..app_name.len() - 1]);
+            if !is_prefix && glob::is_glob(app_name) {
+                // General glob (e.g. "*Helper*", "Office 20[0-9][0-9]")
+                let tokens = match glob::parse(app_name) {
+                    Ok(tokens) => tokens,
+                    Err(e) => {
+                        errors.push(format!("invalid pattern \"{}\" for {}: {}", app_name, icon, e));
+                        continue;
+                    }
+                };
+                if check_duplicate(&mut seen, app_name, app_name, icon, &mut errors) {
+                    glob_patterns.push((app_name.clone(), tokens, icon.clone()));
+                }
+            } else if is_prefix {
                 // Wildcard pattern - store without the asterisk
-                let prefix = app_name[..app_name.len() - 1].to_string();
-                if seen.insert(prefix.clone()) {
-                    prefix_patterns.push((prefix, entry.icon_name.clone()));
+                let prefix = normalize_prefix(&app_name[..app_name.len() - 1]);
+                if check_duplicate(&mut seen, &format!("{}*", prefix), app_name, icon, &mut errors) {
+                    prefix_patterns.push((prefix, icon.clone()));
+                    prefix_names.push(app_name.clone());
                 }
-            } else if app_name.contains('|') {
+            } else {
                 // Handle pipe-separated names (e.g., "MATLAB |MATLABWindow")
                 for name in app_name.split('|') {
-                    let name = name.trim().to_string();
-                    if !name.is_empty() && seen.insert(name.clone()) {
-                        exact_matches.push((name, entry.icon_name.clone()));
+                    let key = normalize_name(name);
+                    if !key.is_empty() && check_duplicate(&mut seen, &key, name, icon, &mut errors) {
+                        exact_matches.push((key, icon.clone()));
                     }
                 }
-            } else {
-                let name = app_name.clone();
-                if seen.insert(name.clone()) {
-                    exact_matches.push((name, entry.icon_name.clone()));
-                }
             }
         }
     }
 
+    // Prefixes are tried in file order, so a shorter prefix hides any longer one it covers
+    for (i, (prefix, icon)) in prefix_patterns.iter().enumerate() {
+        for (j, (other, other_icon)) in prefix_patterns.iter().enumerate() {
+            if i != j && icon != other_icon && other.starts_with(prefix.as_str()) {
+                errors.push(format!(
+                    "prefix \"{}\" ({}) overlaps \"{}\" ({})",
+                    prefix_names[i], icon, prefix_names[j], other_icon
+                ));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        panic!(
+            "icon_map.json has {} problem(s):\n  - {}",
+            errors.len(),
+            errors.join("\n  - ")
+        );
+    }
+
     // Generate the output file
     let mut out_file = BufWriter::new(File::create(&dest_path).unwrap());
 
@@ -70,6 +219,31 @@ fn main() {
     }
     writeln!(out_file, "{};", builder.build()).unwrap();
 
+    // Generate the PHF map for bundle identifiers
+    writeln!(out_file).unwrap();
+    writeln!(out_file, "static BUNDLE_ID_MAP: phf::Map<&'static str, &'static str> = ").unwrap();
+    let mut builder = phf_codegen::Map::new();
+    for (bundle_id, icon_name) in &bundle_matches {
+        builder.entry(&**bundle_id, &format!("\"{}\"", icon_name));
+    }
+    writeln!(out_file, "{};", builder.build()).unwrap();
+
+    // Generate one PHF map per alternative theme (icon name -> glyph)
+    for (theme, map_name) in THEMES {
+        writeln!(out_file).unwrap();
+        writeln!(out_file, "static {}: phf::Map<&'static str, &'static str> = ", map_name).unwrap();
+        let mut builder = phf_codegen::Map::new();
+        let mut seen_icons: HashSet<&str> = HashSet::new();
+        for entry in &entries {
+            if let Some(glyph) = entry.icons.get(*theme) {
+                if seen_icons.insert(&entry.icon_name) {
+                    builder.entry(entry.icon_name.as_str(), &format!("{:?}", glyph));
+                }
+            }
+        }
+        writeln!(out_file, "{};", builder.build()).unwrap();
+    }
+
     // Generate the prefix patterns array
     writeln!(out_file).unwrap();
     writeln!(
@@ -81,4 +255,17 @@ fn main() {
         writeln!(out_file, "    (\"{}\", \"{}\"),", prefix, icon_name).unwrap();
     }
     writeln!(out_file, "];").unwrap();
+
+    // Generate the glob patterns array (checked after exact and prefix matches)
+    writeln!(out_file).unwrap();
+    writeln!(
+        out_file,
+        "static GLOB_PATTERNS: &[(&[Token], &str)] = &["
+    )
+    .unwrap();
+    for (pattern, tokens, icon_name) in &glob_patterns {
+        writeln!(out_file, "    // {}", pattern).unwrap();
+        writeln!(out_file, "    ({}, {:?}),", tokens_source(tokens), icon_name).unwrap();
+    }
+    writeln!(out_file, "];").unwrap();
 }
@@ -1,13 +1,27 @@
 use std::collections::{HashMap, HashSet};
-use std::process::Command;
-use crate::icon_map::get_icon;
+use std::process::{Command, Output};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use sketchybartender::config::{Config, IconDedup, IconOrder};
+use sketchybartender::icon_map::{get_icon, is_mapped};
+use sketchybartender::normalize::normalize_name;
+use sketchybartender::{error, warn};
+
+use crate::bundles;
+use crate::processes;
+use crate::runtime;
+
+/// How long an aerospace query may take; it hangs while aerospace itself is stuck
+const TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Information about a workspace
 #[derive(Debug, Clone, Default)]
 pub struct WorkspaceInfo {
     #[allow(dead_code)] // Used in tests
     pub id: String,
-    pub apps: Vec<String>,
+    /// One entry per window
+    pub apps: Vec<App>,
     pub icons: String,
     #[allow(dead_code)] // Used in tests
     pub is_focused: bool,
@@ -15,17 +29,183 @@ pub struct WorkspaceInfo {
     pub monitor_id: u32,
 }
 
+/// An application as reported by aerospace
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct App {
+    /// Localized display name (e.g. "Visual Studio Code")
+    pub name: String,
+    /// Bundle identifier (e.g. "com.microsoft.VSCode") from aerospace or NSRunningApplication,
+    /// or read from the app's bundle when they have none
+    pub bundle_id: Option<String>,
+    /// Process ID, used to find the owner of helper processes
+    pub pid: Option<u32>,
+    /// App owning this helper process (e.g. VS Code for "Code Helper (Renderer)")
+    pub parent: Option<Box<App>>,
+    /// Unlocalized bundle name, when the display name has no icon mapping (e.g. "Preview" for "Aperçu")
+    pub canonical_name: Option<String>,
+}
+
+impl App {
+    /// Parse a `%{app-name}|%{app-bundle-id}` pair
+    fn parse(name: &str, bundle_id: &str) -> Self {
+        let bundle_id = bundle_id.trim();
+        Self {
+            name: name.to_string(),
+            bundle_id: (!bundle_id.is_empty()).then(|| bundle_id.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// An app reported by the system rather than aerospace, e.g. on activation
+    pub fn running(name: String, bundle_id: Option<String>, pid: Option<u32>) -> Self {
+        Self { name, bundle_id, pid, ..Self::default() }.resolved()
+    }
+
+    /// Resolve a missing bundle ID, the owning app of helper processes and the canonical name of localized apps
+    fn resolved(mut self) -> Self {
+        if self.bundle_id.is_none() {
+            self.bundle_id = self.pid.and_then(processes::bundle_id);
+        }
+        if let Some(pid) = self.pid.filter(|_| processes::is_helper(&self)) {
+            self.parent = processes::parent_app(pid).map(|parent| Box::new(parent.with_canonical_name()));
+        }
+        self.with_canonical_name()
+    }
+
+    /// Look up the bundle's unlocalized name if the display name isn't mapped
+    fn with_canonical_name(mut self) -> Self {
+        if let Some(bundle_id) = self.bundle_id.as_deref() {
+            if !is_mapped(&self.name, Some(bundle_id)) {
+                self.canonical_name = bundles::canonical_name(bundle_id).filter(|name| *name != self.name);
+            }
+        }
+        self
+    }
+
+    /// The name used for icon matching: the canonical name when known, otherwise the display name
+    pub fn lookup_name(&self) -> &str {
+        self.canonical_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// The app whose icon represents this one: the owning app for helpers, otherwise itself
+    pub fn icon_source(&self) -> &App {
+        self.parent.as_deref().unwrap_or(self)
+    }
+
+    /// Get the icon for this app
+    pub fn icon(&self) -> String {
+        let app = self.icon_source();
+        get_icon(app.lookup_name(), app.bundle_id.as_deref())
+    }
+}
+
+/// A window as reported by `aerospace list-windows`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Window {
+    pub id: u32,
+    pub workspace: String,
+    pub app: App,
+}
+
+/// How app icons are assembled into a workspace item
+#[derive(Debug, Clone, Default)]
+pub struct IconRules {
+    pub order: IconOrder,
+    pub dedup: IconDedup,
+    /// Normalized names of apps shown first, in this order
+    pub important: Vec<String>,
+    /// Maximum number of icons, 0 for no limit
+    pub max: usize,
+}
+
+impl IconRules {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            order: config.workspace_icon_order,
+            dedup: config.workspace_icon_dedup,
+            important: config.important_apps.iter().map(|name| normalize_name(name)).collect(),
+            max: config.workspace_icon_max,
+        }
+    }
+
+    /// Order, deduplicate and cap a workspace's windows, returning the apps to show
+    ///
+    /// `dedup_auto` is what `IconDedup::Auto` resolves to. Windows are given in
+    /// layout order; ties are broken by window ID so refreshes don't jitter.
+    pub fn assemble<'a>(&self, windows: &'a [Window], dedup_auto: bool) -> Vec<&'a App> {
+        let mut windows: Vec<(String, &Window)> = windows
+            .iter()
+            .map(|window| (normalize_name(&window.app.name), window))
+            .collect();
+
+        if self.order == IconOrder::Alphabetical {
+            windows.sort_by(|(a, wa), (b, wb)| a.cmp(b).then(wa.id.cmp(&wb.id)));
+        }
+
+        // Important apps first, in configured order; the stable sort keeps the rest as they are
+        windows.sort_by_key(|(name, _)| {
+            self.important
+                .iter()
+                .position(|important| important == name)
+                .unwrap_or(self.important.len())
+        });
+
+        let dedup = match self.dedup {
+            IconDedup::Auto => dedup_auto,
+            IconDedup::On => true,
+            IconDedup::Off => false,
+        };
+        if dedup {
+            let mut seen = HashSet::new();
+            windows.retain(|(name, _)| seen.insert(name.clone()));
+        }
+
+        if self.max > 0 {
+            windows.truncate(self.max);
+        }
+
+        windows.into_iter().map(|(_, window)| &window.app).collect()
+    }
+}
+
+fn rules_lock() -> &'static RwLock<IconRules> {
+    static RULES: OnceLock<RwLock<IconRules>> = OnceLock::new();
+    RULES.get_or_init(|| RwLock::new(IconRules::default()))
+}
+
+/// Set the rules used to assemble workspace icons
+pub fn set_icon_rules(rules: IconRules) {
+    if let Ok(mut r) = rules_lock().write() {
+        *r = rules;
+    }
+}
+
+/// Run an aerospace query
+fn query(args: &[&str]) -> Option<Output> {
+    let mut command = Command::new("aerospace");
+    command.args(args);
+    match runtime::output(command, TIMEOUT) {
+        Ok(output) => Some(output),
+        Err(e) => {
+            error!("Failed to run aerospace {}: {}", args.first().unwrap_or(&""), e);
+            None
+        }
+    }
+}
+
 /// Get the currently focused app
-pub fn get_focused_app() -> Option<String> {
-    let output = Command::new("aerospace")
-        .args(["list-windows", "--focused", "--format", "%{app-name}"])
-        .output()
-        .ok()?;
+pub fn get_focused_app() -> Option<App> {
+    let output = query(&["list-windows", "--focused", "--format", "%{app-pid}|%{app-bundle-id}|%{app-name}"])?;
 
     if output.status.success() {
-        let app = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !app.is_empty() {
-            return Some(app);
+        let line = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        // App name goes last since it is the only field that may contain '|'
+        let parts: Vec<&str> = line.splitn(3, '|').collect();
+        if let [pid, bundle_id, name] = parts.as_slice() {
+            if !name.is_empty() {
+                let app = App { pid: pid.trim().parse().ok(), ..App::parse(name, bundle_id) };
+                return Some(app.resolved());
+            }
         }
     }
     None
@@ -33,10 +213,7 @@ pub fn get_focused_app() -> Option<String> {
 
 /// Get the currently focused workspace
 pub fn get_focused_workspace() -> Option<String> {
-    let output = Command::new("aerospace")
-        .args(["list-workspaces", "--focused"])
-        .output()
-        .ok()?;
+    let output = query(&["list-workspaces", "--focused"])?;
 
     if output.status.success() {
         let ws = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -47,14 +224,11 @@ pub fn get_focused_workspace() -> Option<String> {
     None
 }
 
-/// Get all windows with their workspace and app name
-pub fn get_all_windows() -> Vec<(String, String)> {
-    let output = match Command::new("aerospace")
-        .args(["list-windows", "--all", "--format", "%{workspace}|%{app-name}"])
-        .output()
-    {
-        Ok(o) => o,
-        Err(_) => return Vec::new(),
+/// Get all windows with their workspace and app, in layout order
+pub fn get_all_windows() -> Vec<Window> {
+    let format = "%{workspace}|%{window-id}|%{app-pid}|%{app-bundle-id}|%{app-name}";
+    let Some(output) = query(&["list-windows", "--all", "--format", format]) else {
+        return Vec::new();
     };
 
     if !output.status.success() {
@@ -63,27 +237,35 @@ pub fn get_all_windows() -> Vec<(String, String)> {
 
     String::from_utf8_lossy(&output.stdout)
         .lines()
-        .filter_map(|line| {
-            let parts: Vec<&str> = line.splitn(2, '|').collect();
-            if parts.len() == 2 {
-                Some((parts[0].to_string(), parts[1].to_string()))
-            } else {
-                None
-            }
-        })
+        .filter_map(parse_window)
+        .map(|window| Window { app: window.app.resolved(), ..window })
         .collect()
 }
 
+/// Parse a `%{workspace}|%{window-id}|%{app-pid}|%{app-bundle-id}|%{app-name}` line
+fn parse_window(line: &str) -> Option<Window> {
+    // App name goes last since it is the only field that may contain '|'
+    let parts: Vec<&str> = line.splitn(5, '|').collect();
+    if parts.len() != 5 {
+        return None;
+    }
+
+    Some(Window {
+        id: parts[1].trim().parse().ok()?,
+        workspace: parts[0].to_string(),
+        app: App {
+            pid: parts[2].trim().parse().ok(),
+            ..App::parse(parts[4], parts[3])
+        },
+    })
+}
+
 /// Get the monitor ID for each workspace
 pub fn get_workspace_monitors() -> HashMap<String, u32> {
     let mut result = HashMap::new();
 
-    let output = match Command::new("aerospace")
-        .args(["list-workspaces", "--all", "--format", "%{workspace}|%{monitor-id}"])
-        .output()
-    {
-        Ok(o) => o,
-        Err(_) => return result,
+    let Some(output) = query(&["list-workspaces", "--all", "--format", "%{workspace}|%{monitor-id}"]) else {
+        return result;
     };
 
     if !output.status.success() {
@@ -105,8 +287,8 @@ pub fn get_workspace_monitors() -> HashMap<String, u32> {
 /// Get workspace information for all workspaces
 ///
 /// # Arguments
-/// * `show_all_windows` - If true, show an icon for each window. If false, show one icon per app.
-pub fn get_workspace_infos(show_all_windows: bool) -> HashMap<String, WorkspaceInfo> {
+/// * `dedup_auto` - Whether `workspace_icon_dedup = auto` shows one icon per app rather than per window
+pub fn get_workspace_infos(dedup_auto: bool) -> HashMap<String, WorkspaceInfo> {
     // Query all aerospace state at once to get consistent snapshot
     // Retry if focused workspace is empty or if window list seems stale
     let mut focused = get_focused_workspace().unwrap_or_default();
@@ -122,11 +304,11 @@ pub fn get_workspace_infos(show_all_windows: bool) -> HashMap<String, WorkspaceI
     while retry_count < max_retries {
         let needs_retry = if focused.is_empty() {
             // Focused workspace is empty - definitely need to retry
-            eprintln!("[AEROSPACE] Warning: focused workspace empty (retry {}/{})", retry_count + 1, max_retries);
+            warn!("[AEROSPACE] focused workspace empty (retry {}/{})", retry_count + 1, max_retries);
             true
         } else if retry_count == 0 && initial_window_count > 0 && windows.is_empty() {
             // We had windows before but now have none - might be mid-update
-            eprintln!("[AEROSPACE] Warning: all windows disappeared, possible stale data (retry {}/{})", retry_count + 1, max_retries);
+            warn!("[AEROSPACE] all windows disappeared, possible stale data (retry {}/{})", retry_count + 1, max_retries);
             true
         } else {
             false
@@ -146,47 +328,39 @@ pub fn get_workspace_infos(show_all_windows: bool) -> HashMap<String, WorkspaceI
         retry_count += 1;
     }
 
-    // Group apps by workspace, keeping all windows (including multiple windows of the same app)
-    let mut workspace_apps: HashMap<String, Vec<String>> = HashMap::new();
+    // Group windows by workspace, keeping all windows (including multiple windows of the same app)
+    let mut workspace_windows: HashMap<String, Vec<Window>> = HashMap::new();
 
-    for (ws, app) in windows {
-        workspace_apps
-            .entry(ws)
+    for window in windows {
+        workspace_windows
+            .entry(window.workspace.clone())
             .or_default()
-            .push(app);
+            .push(window);
     }
 
+    let rules = rules_lock().read().map(|r| r.clone()).unwrap_or_default();
+
     // Build workspace infos for all workspaces found
     let mut result = HashMap::new();
 
-    // Get all workspace IDs from the monitors map and workspace_apps
+    // Get all workspace IDs from the monitors map and workspace_windows
     let mut all_workspace_ids: HashSet<String> = monitors.keys().cloned().collect();
-    all_workspace_ids.extend(workspace_apps.keys().cloned());
+    all_workspace_ids.extend(workspace_windows.keys().cloned());
 
     for id in all_workspace_ids {
-        // Get all apps for this workspace (including duplicates for multiple windows)
-        let apps: Vec<String> = workspace_apps
-            .get(&id)
-            .cloned()
+        // Get all windows for this workspace (including several of the same app)
+        let windows: Vec<Window> = workspace_windows
+            .remove(&id)
             .unwrap_or_default();
 
         // Build icons string
-        let icons: String = if show_all_windows {
-            // Show an icon for each window
-            apps
-                .iter()
-                .map(|app| format!("{}", get_icon(app)))
-                .collect()
-        } else {
-            // Show one icon per unique app
-            let mut unique_apps: Vec<String> = apps.clone();
-            unique_apps.sort();
-            unique_apps.dedup();
-            unique_apps
-                .iter()
-                .map(|app| format!("{}", get_icon(app)))
-                .collect()
-        };
+        let icons: String = rules
+            .assemble(&windows, dedup_auto)
+            .into_iter()
+            .map(App::icon)
+            .collect();
+
+        let apps: Vec<App> = windows.into_iter().map(|window| window.app).collect();
 
         result.insert(
             id.clone(),
@@ -212,11 +386,72 @@ mod tests {
         // This test verifies the structure without requiring aerospace
         let mut info = WorkspaceInfo::default();
         info.id = "1".to_string();
-        info.apps = vec!["Safari".to_string(), "Cursor".to_string()];
+        info.apps = vec![App::parse("Safari", ""), App::parse("Cursor", "")];
         info.icons = ":safari: :cursor:".to_string();
         info.is_focused = true;
 
         assert_eq!(info.apps.len(), 2);
         assert!(info.is_focused);
     }
+
+    fn window(id: u32, name: &str) -> Window {
+        Window { id, workspace: "1".to_string(), app: App::parse(name, "") }
+    }
+
+    fn names(apps: Vec<&App>) -> Vec<&str> {
+        apps.into_iter().map(|app| app.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_window() {
+        let window = parse_window("2|42|123|com.example.app|My|App").unwrap();
+        assert_eq!(window.id, 42);
+        assert_eq!(window.app.pid, Some(123));
+        assert_eq!(window.workspace, "2");
+        assert_eq!(window.app.name, "My|App");
+        assert_eq!(window.app.bundle_id.as_deref(), Some("com.example.app"));
+        assert!(parse_window("2|x|123|com.example.app|App").is_none());
+    }
+
+    #[test]
+    fn test_helper_uses_parent_icon() {
+        let helper = App {
+            parent: Some(Box::new(App::parse("Visual Studio Code", "com.microsoft.VSCode"))),
+            ..App::parse("Code Helper (Renderer)", "com.microsoft.VSCode.helper.Renderer")
+        };
+        assert_eq!(helper.icon_source().name, "Visual Studio Code");
+        assert_eq!(helper.icon(), ":code:");
+    }
+
+    #[test]
+    fn test_canonical_name_lookup() {
+        let app = App {
+            canonical_name: Some("Preview".to_string()),
+            ..App::parse("Aperçu", "com.apple.Preview")
+        };
+        assert_eq!(app.lookup_name(), "Preview");
+        assert_eq!(app.icon(), get_icon("Preview", None));
+    }
+
+    #[test]
+    fn test_assemble_icons() {
+        let windows = vec![window(3, "Safari"), window(1, "Cursor"), window(2, "Safari"), window(4, "Slack")];
+
+        let rules = IconRules::default();
+        assert_eq!(names(rules.assemble(&windows, true)), vec!["Cursor", "Safari", "Slack"]);
+        assert_eq!(names(rules.assemble(&windows, false)), vec!["Cursor", "Safari", "Safari", "Slack"]);
+
+        // Input order doesn't matter for alphabetical ordering
+        let mut reversed = windows.clone();
+        reversed.reverse();
+        assert_eq!(names(rules.assemble(&reversed, false)), names(rules.assemble(&windows, false)));
+
+        let rules = IconRules {
+            order: IconOrder::Position,
+            dedup: IconDedup::On,
+            important: vec!["slack".to_string()],
+            max: 2,
+        };
+        assert_eq!(names(rules.assemble(&windows, false)), vec!["Slack", "Safari"]);
+    }
 }
@@ -0,0 +1,158 @@
+//! Real app icons extracted from app bundles
+//!
+//! The bundle's .icns file is converted to a PNG in the cache directory once
+//! and reused afterwards; apps whose icon can't be extracted keep their glyph.
+//! Extraction runs `mdfind` and `sips`, so it happens on the blocking pool and
+//! apps show their glyph until the PNG is cached.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use sketchybartender::config::AppIconImages;
+use sketchybartender::warn;
+
+use crate::aerospace::App;
+use crate::bundles;
+use crate::daemon::get_cache_dir;
+use crate::runtime;
+
+/// Size of the extracted PNGs in pixels (rendered at half size for Retina displays)
+const IMAGE_SIZE: u32 = 64;
+
+fn mode_lock() -> &'static RwLock<AppIconImages> {
+    static MODE: OnceLock<RwLock<AppIconImages>> = OnceLock::new();
+    MODE.get_or_init(|| RwLock::new(AppIconImages::default()))
+}
+
+/// Select where app icon images are shown
+pub fn set_mode(mode: AppIconImages) {
+    if let Ok(mut m) = mode_lock().write() {
+        *m = mode;
+    }
+}
+
+/// Where app icon images are shown
+pub fn mode() -> AppIconImages {
+    mode_lock().read().map(|m| *m).unwrap_or_default()
+}
+
+/// Where an app's icon extraction stands
+enum Extraction {
+    Running,
+    Done(Option<PathBuf>),
+}
+
+/// Extraction results by bundle ID, so failures aren't retried on every refresh
+fn extracted() -> &'static Mutex<HashMap<String, Extraction>> {
+    static EXTRACTED: OnceLock<Mutex<HashMap<String, Extraction>>> = OnceLock::new();
+    EXTRACTED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Path of a PNG of the app's icon, once it has been extracted
+///
+/// The first request starts the extraction in the background and returns `None`;
+/// `on_extracted` runs when the PNG is cached, so the caller can draw it then.
+pub fn image_for(app: &App, on_extracted: impl FnOnce() + Send + 'static) -> Option<PathBuf> {
+    let bundle_id = app.bundle_id.clone()?;
+    let dest = get_cache_dir().join("app_icons").join(format!("{}.png", bundle_id));
+
+    {
+        let mut cache = extracted().lock().ok()?;
+        match cache.get(&bundle_id) {
+            Some(Extraction::Done(result)) => return result.clone(),
+            Some(Extraction::Running) => return None,
+            None if dest.exists() => {
+                cache.insert(bundle_id, Extraction::Done(Some(dest.clone())));
+                return Some(dest);
+            }
+            None => {
+                cache.insert(bundle_id.clone(), Extraction::Running);
+            }
+        }
+    }
+
+    runtime::background(move || {
+        let result = match extract(&bundle_id, &dest) {
+            Ok(()) => Some(dest),
+            Err(e) => {
+                warn!("Failed to extract icon for {}: {}", bundle_id, e);
+                None
+            }
+        };
+
+        let found = result.is_some();
+        if let Ok(mut cache) = extracted().lock() {
+            cache.insert(bundle_id, Extraction::Done(result));
+        }
+        if found {
+            on_extracted();
+        }
+    });
+    None
+}
+
+/// Convert the app's .icns icon into a PNG at `dest`
+fn extract(bundle_id: &str, dest: &Path) -> Result<(), String> {
+    if !bundles::is_valid_bundle_id(bundle_id) {
+        return Err("unsupported bundle identifier".to_string());
+    }
+
+    let app = bundles::find_app(bundle_id)?;
+    let icns = icon_file(&app)?;
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    }
+
+    let size = IMAGE_SIZE.to_string();
+    let output = Command::new("sips")
+        .args(["-s", "format", "png", "-Z", &size])
+        .arg(&icns)
+        .arg("--out")
+        .arg(dest)
+        .output()
+        .map_err(|e| format!("Failed to run sips: {}", e))?;
+
+    if output.status.success() && dest.exists() {
+        Ok(())
+    } else {
+        Err(format!("sips failed: {}", String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// The .icns file named by the bundle's CFBundleIconFile
+fn icon_file(app: &Path) -> Result<PathBuf, String> {
+    let name = bundles::info(app, "CFBundleIconFile")
+        .ok_or_else(|| "bundle has no CFBundleIconFile".to_string())?;
+
+    let file = icns_file_name(&name);
+    let path = app.join("Contents").join("Resources").join(file);
+    if path.exists() {
+        Ok(path)
+    } else {
+        Err(format!("{} not found", path.display()))
+    }
+}
+
+/// CFBundleIconFile may omit the .icns extension
+fn icns_file_name(name: &str) -> String {
+    if name.ends_with(".icns") {
+        name.to_string()
+    } else {
+        format!("{}.icns", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icns_file_name() {
+        assert_eq!(icns_file_name("AppIcon"), "AppIcon.icns");
+        assert_eq!(icns_file_name("AppIcon.icns"), "AppIcon.icns");
+    }
+}
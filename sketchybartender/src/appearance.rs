@@ -0,0 +1,130 @@
+//! Light and dark appearance from the global defaults
+//!
+//! `AppleInterfaceStyle` in the global domain reads `Dark` while the dark
+//! appearance is on and doesn't exist otherwise, also when it's switched by
+//! the automatic schedule. Every switch posts a distributed notification,
+//! which is delivered on the main thread's run loop, so the observer is
+//! registered from `main` like the front app's. It only sends on a channel;
+//! another thread runs the Rust callback.
+//!
+//! Switching goes through System Events' appearance preferences, which needs
+//! the Automation permission the first time.
+
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use sketchybartender::warn;
+
+/// Sending end of the event channel, set by `listen`
+static EVENTS: OnceLock<Mutex<Sender<()>>> = OnceLock::new();
+
+const TOGGLE_SCRIPT: &str =
+    "tell application \"System Events\" to tell appearance preferences to set dark mode to not dark mode";
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::c_void;
+
+    use sketchybartender::error;
+
+    use super::EVENTS;
+    use crate::objc::{self, send, Id, Sel};
+
+    /// `themeChanged:` of the observer class
+    extern "C" fn theme_changed(_this: Id, _cmd: Sel, _notification: Id) {
+        if let Some(Ok(events)) = EVENTS.get().map(|events| events.lock()) {
+            let _ = events.send(());
+        }
+    }
+
+    /// Observe appearance switches; call from the main thread, whose run loop delivers them
+    pub fn register() {
+        let methods: [objc::Method; 1] = [(c"themeChanged:", theme_changed as *const c_void, c"v@:@")];
+        let Some(class) = objc::define_class(c"SketchybartenderAppearanceObserver", &methods) else {
+            error!("Failed to create the appearance observer class");
+            return;
+        };
+
+        // The name is autoreleased, and main has no pool until the run loop starts
+        objc::autoreleasing(|| unsafe {
+            let observer = send(send(class, c"alloc"), c"init");
+            let center = send(objc::class(c"NSDistributedNotificationCenter"), c"defaultCenter");
+            objc::msg_send::<unsafe extern "C" fn(Id, Sel, Id, Sel, Id, Id)>()(
+                center,
+                objc::selector(c"addObserver:selector:name:object:"),
+                observer,
+                objc::selector(c"themeChanged:"),
+                objc::nsstring("AppleInterfaceThemeChangedNotification"),
+                std::ptr::null_mut(),
+            );
+        });
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn register() {}
+}
+
+/// Whether `defaults read -g AppleInterfaceStyle` found the dark appearance
+fn parse_style(found: bool, stdout: &str) -> bool {
+    found && stdout.trim() == "Dark"
+}
+
+/// Whether the dark appearance is on
+pub fn dark() -> Result<bool, String> {
+    let output = Command::new("defaults")
+        .args(["read", "-g", "AppleInterfaceStyle"])
+        .output()
+        .map_err(|e| format!("Failed to run defaults: {}", e))?;
+    // The key doesn't exist in the light appearance, which makes defaults fail
+    Ok(parse_style(output.status.success(), &String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Switch between the light and dark appearance; the listener picks up the change
+pub fn toggle() -> Result<(), String> {
+    let output = Command::new("osascript")
+        .args(["-e", TOGGLE_SCRIPT])
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to switch the appearance: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Run `callback` on a background thread whenever the appearance switches
+///
+/// Must be called from the main thread, which then runs the run loop.
+pub fn listen(callback: impl Fn() + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+    if EVENTS.set(Mutex::new(sender)).is_err() {
+        warn!("Appearance listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            callback();
+        }
+    });
+
+    native::register();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_style() {
+        assert!(parse_style(true, "Dark\n"));
+        assert!(!parse_style(false, ""));
+        assert!(!parse_style(true, "Light\n"));
+    }
+}
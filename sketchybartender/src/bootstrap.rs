@@ -0,0 +1,264 @@
+//! Creating the bar and the daemon's items, so sketchybarrc only has to start the daemon
+//!
+//! Items are removed before they're added, so bootstrapping again after sketchybarrc or an
+//! earlier run set them up replaces them instead of failing on duplicates. The providers and
+//! workspace handlers fill the items in afterwards.
+
+use sketchybartender::config::Config;
+use sketchybartender::{error, info};
+
+use crate::sketchybar::{remove_items, SketchybarBatch, VOLUME_SLIDER};
+
+/// Aerospace workspaces that get an item, hidden until they have windows
+const WORKSPACES: &str = "123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Items added at the right end, rightmost first
+const RIGHT_ITEMS: &[&str] = &[
+    "clock", "calendar", "zoom", "weather", "sun", "moon", "crypto", "exchange", "feeds", "reviews", "gitlab", "jira",
+    "linear", "battery", "wifi", "network", "cpu", "top_process", "ram", "disk", "load", "thermal", "fan", "focus_mode",
+    "appearance", "brightness", "volume", "audio_output", "brew", "teams",
+];
+
+const LABEL_FONT: &str = "JetbrainsMono Nerd Font:Bold:14.0";
+
+/// Hidden item whose script passes sketchybar's events on to the daemon
+const LISTENER: &str = "event_listener";
+
+/// Events the listener subscribes to, and the message each becomes; the daemon follows most of
+/// these natively as well, so the subscriptions are a backstop rather than the only trigger
+const EVENTS: &[(&str, &str)] = &[
+    ("space_windows_change", "on-workspace-changed"),
+    ("front_app_switched", "on-focus-changed"),
+    ("volume_change", "on-volume-changed"),
+    ("power_source_change", "on-power-source-changed"),
+    ("display_change", "on-display-configuration-changed"),
+    ("system_woke", "on-system-wake"),
+];
+
+/// Events sketchybar doesn't trigger by itself until they're declared
+const CUSTOM_EVENTS: &[&str] = &["space_windows_change"];
+
+/// Items earlier versions of sketchybarrc added, which the listener replaces
+const LEGACY_ITEMS: &[&str] = &["workspace_listener"];
+
+/// The daemon message for a sketchybar event the listener passed on
+pub fn message_for(event: &str) -> Option<&'static str> {
+    EVENTS.iter().find(|(name, _)| *name == event).map(|(_, message)| *message)
+}
+
+/// Every item the bootstrap adds
+fn items() -> Vec<String> {
+    let mut items: Vec<String> = WORKSPACES.chars().map(|workspace| format!("workspace.{}", workspace)).collect();
+    items.extend([LISTENER, "front_app"].iter().chain(RIGHT_ITEMS).map(|item| item.to_string()));
+    items.push(VOLUME_SLIDER.to_string());
+    items
+}
+
+/// The commands that set up the bar, with click scripts calling `sketchycli`
+fn batch(config: &Config, sketchycli: &str) -> SketchybarBatch {
+    let mut batch = SketchybarBatch::new();
+    batch
+        .bar(&[("position", "top"), ("height", "25"), ("blur_radius", "0"), ("color", "0xff000000"), ("margin", "0")])
+        .defaults(&[
+            ("padding_left", "0"),
+            ("padding_right", "0"),
+            ("icon.font", "JetbrainsMono Nerd Font:Bold:15.0"),
+            ("label.font", LABEL_FONT),
+            ("icon.color", "0xffffffff"),
+            ("label.color", "0xffffffff"),
+            ("icon.padding_left", "5"),
+            ("icon.padding_right", "3"),
+            ("label.padding_left", "3"),
+            ("label.padding_right", "8"),
+        ]);
+
+    // Workspace items stay hidden until the workspace handler shows the ones with windows
+    for workspace in WORKSPACES.chars() {
+        let item = format!("workspace.{}", workspace);
+        let click_script = format!("aerospace workspace {}", workspace);
+        batch.add(&item, "left").set(&item, &[
+            ("icon.font", config.icon_theme.font()),
+            ("label.font", LABEL_FONT),
+            ("background.corner_radius", "6"),
+            ("background.height", "22"),
+            ("background.drawing", "off"),
+            ("icon.padding_left", "5"),
+            ("icon.padding_right", "0"),
+            ("label.padding_left", "2"),
+            ("label.padding_right", "8"),
+            ("label.y_offset", "0.5"),
+            ("padding_right", "3"),
+            ("drawing", "off"),
+            ("click_script", &click_script),
+        ]);
+    }
+
+    // One script for every event; $SENDER names the event and $INFO carries its details
+    for event in CUSTOM_EVENTS {
+        batch.add_event(event);
+    }
+    let listener_script = format!("\"{}\" on-event \"$SENDER\" \"$INFO\"", sketchycli);
+    let events: Vec<&str> = EVENTS.iter().map(|(event, _)| *event).collect();
+    batch
+        .add(LISTENER, "left")
+        .set(LISTENER, &[("drawing", "off"), ("updates", "on"), ("script", &listener_script)])
+        .subscribe(LISTENER, &events);
+
+    batch.add("front_app", "left").set("front_app", &[
+        ("label.font", "Hack Nerd Font:Bold:13.0"),
+        ("icon.drawing", "off"),
+        ("padding_left", "0"),
+    ]);
+
+    for item in RIGHT_ITEMS {
+        batch.add(item, "right");
+    }
+    let brew_script = format!("\"{}\" on-brew-clicked", sketchycli);
+    let battery_script = format!("\"{}\" on-battery-clicked $MODIFIER", sketchycli);
+    let volume_script = format!("\"{}\" on-volume-clicked $MODIFIER", sketchycli);
+    let audio_output_script = format!("\"{}\" on-audio-output-clicked", sketchycli);
+    let teams_script = format!("\"{}\" on-teams-clicked", sketchycli);
+    let focus_mode_script = format!("\"{}\" on-focus-mode-clicked", sketchycli);
+    let appearance_script = format!("\"{}\" on-appearance-clicked", sketchycli);
+    let calendar_script = format!("\"{}\" on-calendar-clicked", sketchycli);
+    let zoom_script = format!("\"{}\" on-zoom-clicked", sketchycli);
+    batch
+        .set("clock", &[
+            ("padding_left", "0"),
+            ("padding_right", "0"),
+            ("click_script", clock_script(config)),
+            ("popup.align", "right"),
+        ])
+        .set("battery", &[("click_script", &battery_script), ("popup.align", "center")])
+        .set("volume", &[("click_script", &volume_script), ("popup.align", "center")])
+        .set("audio_output", &[("click_script", &audio_output_script), ("popup.align", "center")])
+        .set("brew", &[("click_script", &brew_script)])
+        .set("top_process", &[("click_script", "open -a 'Activity Monitor'")])
+        .set("calendar", &[("click_script", &calendar_script)])
+        .set("zoom", &[("click_script", &zoom_script), ("label.drawing", "off"), ("drawing", "off")])
+        .set("weather", &[
+            ("click_script", "sketchybar --set weather popup.drawing=toggle"),
+            ("popup.align", "right"),
+        ])
+        .set("sun", &[("drawing", "off")])
+        .set("feeds", &[
+            ("click_script", "sketchybar --set feeds popup.drawing=toggle"),
+            ("popup.align", "right"),
+            ("drawing", "off"),
+        ])
+        .set("reviews", &[
+            ("click_script", "sketchybar --set reviews popup.drawing=toggle"),
+            ("popup.align", "right"),
+            ("drawing", "off"),
+        ])
+        .set("gitlab", &[
+            ("click_script", "sketchybar --set gitlab popup.drawing=toggle"),
+            ("popup.align", "right"),
+            ("drawing", "off"),
+        ])
+        .set("jira", &[
+            ("click_script", "sketchybar --set jira popup.drawing=toggle"),
+            ("popup.align", "right"),
+            ("drawing", "off"),
+        ])
+        .set("linear", &[("click_script", "open -a Linear || open https://linear.app"), ("drawing", "off")])
+        .set("focus_mode", &[("click_script", &focus_mode_script)])
+        .set("appearance", &[("click_script", &appearance_script), ("label.drawing", "off")])
+        .set("teams", &[
+            ("icon", "󰊻"),
+            ("icon.color", "0xffffffff"),
+            ("icon.font", "Hack Nerd Font:Regular:18.0"),
+            ("click_script", &teams_script),
+        ]);
+
+    // The scripts also run on sketchybar's forced update, which has no $SCROLL_DELTA
+    for (item, message) in [("brightness", "on-brightness-scroll"), ("volume", "on-volume-scroll")] {
+        let script = format!("[ \"$SENDER\" = mouse.scrolled ] && \"{}\" {} \"$SCROLL_DELTA\"", sketchycli, message);
+        batch.set(item, &[("script", &script)]).subscribe(item, &["mouse.scrolled"]);
+    }
+
+    // Dragging the slider reports where it was let go as $PERCENTAGE
+    let slider_script = format!("\"{}\" volume \"$PERCENTAGE\"", sketchycli);
+    batch
+        .add_slider(VOLUME_SLIDER, "popup.volume", 100)
+        .set(VOLUME_SLIDER, &[
+            ("slider.highlight_color", "0xffffffff"),
+            ("slider.background.height", "5"),
+            ("slider.background.corner_radius", "3"),
+            ("slider.background.color", "0xff555555"),
+            ("slider.knob", "\u{f111}"),
+            ("slider.knob.drawing", "on"),
+            ("icon.drawing", "off"),
+            ("label.drawing", "off"),
+            ("script", &slider_script),
+        ])
+        .subscribe(VOLUME_SLIDER, &["mouse.clicked"]);
+
+    // Disabled items are added hidden, so turning them on later only has to draw them
+    for item in ["front_app"].iter().chain(RIGHT_ITEMS) {
+        if !config.is_enabled(item) {
+            batch.set(item, &[("drawing", "off")]);
+        }
+    }
+    batch
+}
+
+/// What clicking the clock runs: opening the other time zones, if it has a popup of them
+pub fn clock_script(config: &Config) -> &'static str {
+    if config.clock.has_popup() {
+        "sketchybar --set clock popup.drawing=toggle"
+    } else {
+        ""
+    }
+}
+
+/// Create the bar and its items, replacing any that already exist
+pub fn run(config: &Config, sketchycli: &str) {
+    let items = items();
+    let removed: Vec<&str> = items.iter().map(String::as_str).chain(LEGACY_ITEMS.iter().copied()).collect();
+    remove_items(&removed);
+    match batch(config, sketchycli).execute() {
+        Ok(()) => info!("[BOOTSTRAP] Added {} items", items.len()),
+        Err(e) => error!("Failed to add the bar's items: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sketchybartender::config::ExtraClocks;
+
+    #[test]
+    fn test_batch() {
+        let config = Config { disabled_items: vec!["teams".to_string()], ..Config::default() };
+        let batch = format!("{:?}", batch(&config, "/usr/local/bin/sketchycli"));
+
+        for item in items().iter().filter(|item| *item != VOLUME_SLIDER) {
+            assert!(batch.contains(&format!("\"--add\", \"item\", \"{}\"", item)), "{} isn't added", item);
+        }
+        assert!(batch.contains("\"--add\", \"item\", \"workspace.z\", \"left\""));
+        assert!(batch.contains("\"click_script=\\\"/usr/local/bin/sketchycli\\\" on-brew-clicked\""));
+        assert!(batch.contains("\"--set\", \"teams\", \"drawing=off\""));
+        assert!(batch.contains("\"--add\", \"slider\", \"volume.slider\", \"popup.volume\", \"100\""));
+        assert!(!batch.contains("\"--set\", \"clock\", \"drawing=off\""));
+        assert!(batch.contains("\"click_script=\""));
+        assert!(batch.contains("\"--subscribe\", \"event_listener\", \"space_windows_change\""));
+    }
+
+    #[test]
+    fn test_clock_script() {
+        let mut config = Config::default();
+        assert_eq!(clock_script(&config), "");
+        config.clock.extra = vec!["Asia/Tokyo".to_string()];
+        assert_eq!(clock_script(&config), "sketchybar --set clock popup.drawing=toggle");
+        config.clock.extra_display = ExtraClocks::Inline;
+        assert_eq!(clock_script(&config), "");
+    }
+
+    #[test]
+    fn test_message_for() {
+        assert_eq!(message_for("system_woke"), Some("on-system-wake"));
+        assert_eq!(message_for("power_source_change"), Some("on-power-source-changed"));
+        assert_eq!(message_for("mouse.clicked"), None);
+    }
+}
@@ -0,0 +1,138 @@
+//! Main display brightness through DisplayServices
+//!
+//! DisplayServices is a private framework, so instead of linking it the
+//! functions are looked up with dlopen the first time they're needed. It
+//! controls the built-in display and Apple's external ones. Where it can't,
+//! the `brightness` command from Homebrew is tried instead.
+
+use std::process::Command;
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    pub const RTLD_LAZY: c_int = 1;
+
+    pub type GetBrightness = unsafe extern "C" fn(display: u32, brightness: *mut f32) -> i32;
+    pub type SetBrightness = unsafe extern "C" fn(display: u32, brightness: f32) -> i32;
+
+    extern "C" {
+        pub fn dlopen(path: *const c_char, mode: c_int) -> *mut c_void;
+        pub fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        pub fn CGMainDisplayID() -> u32;
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::c_void;
+    use std::sync::OnceLock;
+
+    use super::ffi;
+
+    struct DisplayServices {
+        get: ffi::GetBrightness,
+        set: ffi::SetBrightness,
+    }
+
+    /// The framework's functions, or None where it can't be loaded
+    fn display_services() -> Option<&'static DisplayServices> {
+        static SERVICES: OnceLock<Option<DisplayServices>> = OnceLock::new();
+        SERVICES
+            .get_or_init(|| unsafe {
+                let path = c"/System/Library/PrivateFrameworks/DisplayServices.framework/DisplayServices";
+                let handle = ffi::dlopen(path.as_ptr(), ffi::RTLD_LAZY);
+                if handle.is_null() {
+                    return None;
+                }
+                let get = ffi::dlsym(handle, c"DisplayServicesGetBrightness".as_ptr());
+                let set = ffi::dlsym(handle, c"DisplayServicesSetBrightness".as_ptr());
+                if get.is_null() || set.is_null() {
+                    return None;
+                }
+                Some(DisplayServices {
+                    get: std::mem::transmute::<*mut c_void, ffi::GetBrightness>(get),
+                    set: std::mem::transmute::<*mut c_void, ffi::SetBrightness>(set),
+                })
+            })
+            .as_ref()
+    }
+
+    pub fn level() -> Option<f32> {
+        let services = display_services()?;
+        let mut level = 0.0;
+        let result = unsafe { (services.get)(ffi::CGMainDisplayID(), &mut level) };
+        (result == 0).then_some(level)
+    }
+
+    pub fn set(level: f32) -> bool {
+        display_services().is_some_and(|services| unsafe { (services.set)(ffi::CGMainDisplayID(), level) } == 0)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn level() -> Option<f32> {
+        None
+    }
+
+    pub fn set(_level: f32) -> bool {
+        false
+    }
+}
+
+/// Level of the first display in `brightness -l` output, from 0 to 100
+///
+/// ```text
+/// display 0: main, active, awake, online, built-in, ID 0x1
+/// display 0: brightness 0.687500
+/// ```
+fn parse_cli(stdout: &str) -> Option<u8> {
+    stdout.lines().find_map(|line| {
+        let level: f32 = line.split_once(": brightness ")?.1.trim().parse().ok()?;
+        Some((level.clamp(0.0, 1.0) * 100.0).round() as u8)
+    })
+}
+
+/// The main display's brightness from 0 to 100, or None if neither DisplayServices nor `brightness` can read it
+pub fn percentage() -> Option<u8> {
+    if let Some(level) = native::level() {
+        return Some((level.clamp(0.0, 1.0) * 100.0).round() as u8);
+    }
+    let output = Command::new("brightness").arg("-l").output().ok()?;
+    parse_cli(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Set the main display's brightness from 0 to 100
+pub fn set_percentage(percentage: u8) -> Result<(), String> {
+    let level = f32::from(percentage.min(100)) / 100.0;
+    if native::set(level) {
+        return Ok(());
+    }
+    let output = Command::new("brightness")
+        .arg(format!("{:.2}", level))
+        .output()
+        .map_err(|e| format!("Failed to run brightness: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("brightness failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cli() {
+        let stdout = "display 0: main, active, awake, online, built-in, ID 0x1\ndisplay 0: brightness 0.687500\n";
+        assert_eq!(parse_cli(stdout), Some(69));
+        assert_eq!(parse_cli("display 0: brightness 1.000000\n"), Some(100));
+        assert_eq!(parse_cli("display 0: main, active, awake, online, external, ID 0x2\n"), None);
+        assert_eq!(parse_cli(""), None);
+    }
+}
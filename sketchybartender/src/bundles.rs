@@ -0,0 +1,90 @@
+//! Reading app bundles on disk
+//!
+//! Results of the slower lookups are cached by bundle ID for the daemon's
+//! lifetime, since installed apps rarely move or get renamed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// Locate the app bundle for a bundle identifier via Spotlight
+pub fn find_app(bundle_id: &str) -> Result<PathBuf, String> {
+    if !is_valid_bundle_id(bundle_id) {
+        return Err("unsupported bundle identifier".to_string());
+    }
+
+    let output = Command::new("mdfind")
+        .arg(format!("kMDItemCFBundleIdentifier == '{}'", bundle_id))
+        .output()
+        .map_err(|e| format!("Failed to run mdfind: {}", e))?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.ends_with(".app"))
+        .map(PathBuf::from)
+        .ok_or_else(|| "app bundle not found".to_string())
+}
+
+/// Whether a bundle ID is safe to use in an mdfind query and a file name
+pub fn is_valid_bundle_id(bundle_id: &str) -> bool {
+    !bundle_id.is_empty()
+        && !bundle_id.starts_with('.')
+        && bundle_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// Read a key from the bundle's Info.plist
+pub fn info(bundle: &Path, key: &str) -> Option<String> {
+    let output = Command::new("defaults")
+        .arg("read")
+        .arg(bundle.join("Contents").join("Info"))
+        .arg(key)
+        .output()
+        .ok()?;
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+/// Canonical names by bundle ID
+fn canonical_names() -> &'static Mutex<HashMap<String, Option<String>>> {
+    static NAMES: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+    NAMES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The app's unlocalized name (CFBundleName), e.g. "Preview" for "Aperçu"
+///
+/// Display names come from the bundle's localized InfoPlist.strings, while
+/// Info.plist itself holds the name in the app's development language.
+pub fn canonical_name(bundle_id: &str) -> Option<String> {
+    if let Ok(cache) = canonical_names().lock() {
+        if let Some(name) = cache.get(bundle_id) {
+            return name.clone();
+        }
+    }
+
+    let name = find_app(bundle_id)
+        .ok()
+        .and_then(|bundle| info(&bundle, "CFBundleName"));
+
+    if let Ok(mut cache) = canonical_names().lock() {
+        cache.insert(bundle_id.to_string(), name.clone());
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_id_validation() {
+        assert!(is_valid_bundle_id("com.microsoft.VSCode"));
+        assert!(is_valid_bundle_id("org.mozilla.firefox-dev_edition"));
+        assert!(!is_valid_bundle_id(""));
+        assert!(!is_valid_bundle_id("../etc"));
+        assert!(!is_valid_bundle_id("com.example' || true"));
+    }
+}
@@ -0,0 +1,222 @@
+//! The next calendar event from icalBuddy
+//!
+//! icalBuddy reads the same calendars as the Calendar app through EventKit and
+//! asks for the Calendars permission itself, which a daemon without an app
+//! bundle can't do. Times come back as local wall-clock `YYYY-MM-DD HH:MM`, and
+//! so does `date` for the current time, so no time zone handling is needed
+//! beyond a daylight saving switch being off by an hour.
+//!
+//! Video call links are looked for in the event's URL, location and notes,
+//! where Zoom, Teams and Google Calendar put them.
+
+use std::process::Command;
+
+/// An event that hasn't ended yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub title: String,
+    /// Local start time as `YYYY-MM-DD HH:MM`
+    pub start: String,
+    /// Zoom, Teams or Google Meet link to join it
+    pub link: Option<String>,
+}
+
+/// Separates the properties in icalBuddy's output
+const SEPARATOR: &str = " @ ";
+
+/// Hosts, or the domains under them, and the paths on them of links that make an event joinable
+const MEETING_LINKS: &[(&str, &str)] = &[
+    // Zoom gives every company a subdomain, e.g. acme.zoom.us
+    ("zoom.us", "/j/"),
+    ("zoom.us", "/my/"),
+    ("zoom.us", "/w/"),
+    ("teams.microsoft.com", "/l/meetup-join/"),
+    ("teams.live.com", "/meet/"),
+    ("meet.google.com", "/"),
+];
+
+/// icalBuddy arguments for the first timed event from now until the end of tomorrow
+fn arguments(calendars: &[String]) -> Vec<String> {
+    let properties = "title,datetime,url,location,notes";
+    let mut arguments: Vec<String> = [
+        "-n", "-ea", "-nc", "-npn", "-nrd", "-eed", "-b", "", "-li", "1", "-iep", properties, "-po", properties,
+        "-ps", "| @ |", "-df", "%Y-%m-%d", "-tf", "%H:%M",
+    ]
+    .iter()
+    .map(|argument| argument.to_string())
+    .collect();
+    if !calendars.is_empty() {
+        arguments.push("-ic".to_string());
+        arguments.push(calendars.join(","));
+    }
+    arguments.push("eventsToday+1".to_string());
+    arguments
+}
+
+/// `YYYY-MM-DD HH:MM` from an icalBuddy date and time, e.g. `2026-10-16 at 09:30`
+fn start_time(datetime: &str) -> Option<String> {
+    // The word between date and time is localized, e.g. `at` or `um`
+    let mut words = datetime.split_whitespace();
+    let date = words.next()?;
+    let time = words.last()?;
+    let start = format!("{} {}", date, time);
+    minutes(&start).map(|_| start)
+}
+
+/// The first video call link in an event's URL, location or notes
+fn meeting_link(text: &str) -> Option<String> {
+    text.split(|c: char| c.is_whitespace() || "<>\"'()[]".contains(c))
+        .filter(|word| word.starts_with("https://"))
+        .find(|url| {
+            let address = &url["https://".len()..];
+            // The authority ends at the path, query or fragment, whichever comes first
+            let (authority, path) = address.split_at(address.find(['/', '?', '#']).unwrap_or(address.len()));
+            // Past any user name and before any port
+            let host = authority.rsplit('@').next().unwrap_or_default();
+            let host = host.split(':').next().unwrap_or_default().to_ascii_lowercase();
+            MEETING_LINKS.iter().any(|(domain, prefix)| {
+                let on_domain = host == *domain || host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.'));
+                on_domain && path.starts_with(prefix)
+            })
+        })
+        .map(str::to_string)
+}
+
+/// The event in icalBuddy's output, e.g. `Standup @ 2026-10-16 at 09:30 @ https://meet.google.com/abc-defg-hij`
+fn parse(stdout: &str) -> Option<Event> {
+    let parts: Vec<&str> = stdout.trim().split(SEPARATOR).collect();
+    // Titles may have the separator in them too, so the start is the first part that reads as one
+    let (index, start) = parts.iter().enumerate().skip(1).find_map(|(i, part)| Some((i, start_time(part)?)))?;
+    Some(Event {
+        title: parts[..index].join(SEPARATOR).trim().to_string(),
+        start,
+        link: meeting_link(&parts[index + 1..].join(" ")),
+    })
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Minutes since 1970-01-01 00:00 of a `YYYY-MM-DD HH:MM` time
+fn minutes(stamp: &str) -> Option<i64> {
+    let (date, time) = stamp.split_once(' ')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (hour, minute) = time.split_once(':')?;
+    let (hour, minute) = (hour.parse::<i64>().ok()?, minute.parse::<i64>().ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    Some((days_from_civil(year, month, day) * 24 + hour) * 60 + minute)
+}
+
+/// Whole minutes from `now` until `start`, negative once it has started
+pub fn minutes_until(now: &str, start: &str) -> Option<i64> {
+    Some(minutes(start)? - minutes(now)?)
+}
+
+/// The next event in the named calendars, or in all of them when none are named
+pub fn next(calendars: &[String]) -> Result<Option<Event>, String> {
+    let output = Command::new("icalBuddy")
+        .args(arguments(calendars))
+        .output()
+        .map_err(|e| format!("Failed to run icalBuddy: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("icalBuddy failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Open a meeting link, which hands it to the Zoom, Teams or Meet app if installed
+pub fn join(link: &str) -> Result<(), String> {
+    open(&[link])
+}
+
+/// Open the Calendar app
+pub fn open_calendar() -> Result<(), String> {
+    open(&["-a", "Calendar"])
+}
+
+fn open(arguments: &[&str]) -> Result<(), String> {
+    let output = Command::new("open")
+        .args(arguments)
+        .output()
+        .map_err(|e| format!("Failed to run open: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("open failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// The current local time as `YYYY-MM-DD HH:MM`
+pub fn now() -> Result<String, String> {
+    let output = Command::new("date")
+        .arg("+%Y-%m-%d %H:%M")
+        .output()
+        .map_err(|e| format!("Failed to run date: {}", e))?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let event = parse("Standup @ 2026-10-16 at 09:30\n").unwrap();
+        assert_eq!(event.title, "Standup");
+        assert_eq!(event.start, "2026-10-16 09:30");
+        assert_eq!(parse("Q4 @ planning @ 2026-10-17 um 14:00").unwrap().title, "Q4 @ planning");
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("Standup @ tomorrow"), None);
+        assert_eq!(event.link, None);
+    }
+
+    #[test]
+    fn test_meeting_link() {
+        let stdout = "Design review @ 2026-10-16 at 14:00 @ Room 4 @ Join Zoom Meeting\n    \
+                      https://acme.zoom.us/j/123456789?pwd=abc\n    Meeting ID: 123 456 789";
+        let event = parse(stdout).unwrap();
+        assert_eq!(event.title, "Design review");
+        assert_eq!(event.link.as_deref(), Some("https://acme.zoom.us/j/123456789?pwd=abc"));
+
+        let teams = "Join on your computer <https://teams.microsoft.com/l/meetup-join/19%3ameeting>";
+        assert_eq!(meeting_link(teams).as_deref(), Some("https://teams.microsoft.com/l/meetup-join/19%3ameeting"));
+        assert_eq!(
+            meeting_link("https://meet.google.com/abc-defg-hij").as_deref(),
+            Some("https://meet.google.com/abc-defg-hij")
+        );
+        assert_eq!(meeting_link("https://example.com/?next=https://zoom.us/j/1 https://notzoom.us/j/1"), None);
+        assert_eq!(meeting_link("https://example.com/a.zoom.us/j/1"), None);
+        assert_eq!(meeting_link("https://evil.com?x=.zoom.us/j/1"), None);
+        assert_eq!(meeting_link("https://evil.com#.zoom.us/j/1"), None);
+        assert_eq!(meeting_link("https://zoom.us@evil.com/j/1"), None);
+        assert_eq!(meeting_link("https://evilzoom.us/j/1"), None);
+        assert_eq!(meeting_link("https://zoom.us/u/1"), None);
+        assert_eq!(meeting_link("https://Acme.Zoom.us:443/j/1").as_deref(), Some("https://Acme.Zoom.us:443/j/1"));
+    }
+
+    #[test]
+    fn test_minutes_until() {
+        assert_eq!(minutes_until("2026-10-16 09:20", "2026-10-16 09:30"), Some(10));
+        assert_eq!(minutes_until("2026-10-16 23:50", "2026-10-17 00:15"), Some(25));
+        assert_eq!(minutes_until("2026-12-31 23:00", "2027-01-01 01:00"), Some(120));
+        assert_eq!(minutes_until("2028-02-28 12:00", "2028-02-29 12:00"), Some(1440));
+        assert_eq!(minutes_until("2026-10-16 09:35", "2026-10-16 09:30"), Some(-5));
+        assert_eq!(minutes_until("16/10 09:30", "2026-10-16 09:30"), None);
+    }
+
+    #[test]
+    fn test_arguments() {
+        let arguments = arguments(&["Work".to_string(), "Family".to_string()]);
+        assert_eq!(arguments[arguments.len() - 3..], ["-ic", "Work,Family", "eventsToday+1"]);
+        assert_eq!(super::arguments(&[]).last().map(String::as_str), Some("eventsToday+1"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Clamshell (lid-closed) mode
+//!
+//! Closing the lid takes the built-in display away, and with it any items
+//! pinned to it in sketchybarrc. Those items are moved to the main display
+//! while the lid is closed and put back when it reopens.
+
+use std::process::Command;
+use std::sync::{OnceLock, RwLock};
+
+use sketchybartender::monitor_map::Mappings;
+
+fn items_lock() -> &'static RwLock<Vec<String>> {
+    static ITEMS: OnceLock<RwLock<Vec<String>>> = OnceLock::new();
+    ITEMS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Set the items that may be moved off the built-in display
+pub fn set_items(items: Vec<String>) {
+    if let Ok(mut i) = items_lock().write() {
+        *i = items;
+    }
+}
+
+/// Items that may be moved off the built-in display
+pub fn items() -> Vec<String> {
+    items_lock().read().map(|i| i.clone()).unwrap_or_default()
+}
+
+/// Whether the laptop lid is closed, according to IOKit
+pub fn lid_closed() -> bool {
+    match Command::new("ioreg").args(["-r", "-k", "AppleClamshellState", "-d", "1"]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains("\"AppleClamshellState\" = Yes"),
+        Err(_) => false,
+    }
+}
+
+/// Get the displays an item is pinned to; empty if it is shown on all of them
+pub fn pinned_displays(item: &str) -> Vec<u32> {
+    match Command::new("sketchybar").args(["--query", item]).output() {
+        Ok(output) if output.status.success() => parse_pinned_displays(&String::from_utf8_lossy(&output.stdout)),
+        _ => Vec::new(),
+    }
+}
+
+/// Parse the display mask from `sketchybar --query <item>`, where bit N is arrangement ID N
+fn parse_pinned_displays(json: &str) -> Vec<u32> {
+    let value: serde_json::Value = match serde_json::from_str(json) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mask = value["geometry"]["associated_display_mask"].as_u64().unwrap_or(0);
+    (1..64).filter(|display| mask & (1 << display) != 0).collect()
+}
+
+/// Items moved off the built-in display, and where to put them back
+#[derive(Debug, Default)]
+pub struct Clamshell {
+    /// Sketchybar display of the built-in screen, last time it was active
+    builtin_display: Option<u32>,
+    /// Moved items with their original `display` value
+    rehomed: Vec<(String, String)>,
+}
+
+impl Clamshell {
+    /// Work out which items to move after a display change
+    ///
+    /// Returns `(item, display)` pairs to apply: moves to the main display when
+    /// the built-in display went away with the lid closed (or was never there
+    /// because the daemon started that way), and the original values once it
+    /// is back.
+    pub fn update(
+        &mut self,
+        mappings: &Mappings,
+        items: &[String],
+        lid_closed: impl FnOnce() -> bool,
+        pinned_displays: impl Fn(&str) -> Vec<u32>,
+    ) -> Vec<(String, String)> {
+        if let Some(builtin) = mappings.builtin_display {
+            self.builtin_display = Some(builtin);
+            return std::mem::take(&mut self.rehomed);
+        }
+
+        let Some(main) = mappings.main_display else {
+            return Vec::new();
+        };
+
+        // Already moved; follow the main display if another one took over
+        if !self.rehomed.is_empty() {
+            return self
+                .rehomed
+                .iter()
+                .map(|(item, _)| (item.clone(), main.to_string()))
+                .collect();
+        }
+
+        if items.is_empty() || !lid_closed() {
+            return Vec::new();
+        }
+
+        // With the lid already closed at startup the built-in display was never seen,
+        // so the items it took away are those pinned only to displays that aren't there
+        let present: Vec<u32> = mappings.displays.iter().map(|d| d.arrangement_id).collect();
+        let builtin = self.builtin_display;
+        let on_builtin = |displays: &[u32]| match builtin {
+            Some(builtin) => displays.contains(&builtin),
+            None => !displays.is_empty() && displays.iter().all(|d| !present.contains(d)),
+        };
+
+        for item in items {
+            let displays = pinned_displays(item);
+            if on_builtin(&displays) {
+                let original = displays.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",");
+                self.rehomed.push((item.clone(), original));
+            }
+        }
+
+        self.rehomed
+            .iter()
+            .map(|(item, _)| (item.clone(), main.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sketchybartender::monitor_map::SketchybarDisplay;
+
+    fn mappings(main: u32, builtin: Option<u32>) -> Mappings {
+        Mappings { main_display: Some(main), builtin_display: builtin, ..Mappings::default() }
+    }
+
+    #[test]
+    fn test_parse_pinned_displays() {
+        let json = r#"{"name":"battery","geometry":{"drawing":"on","associated_display_mask":4}}"#;
+        assert_eq!(parse_pinned_displays(json), vec![2]);
+        assert_eq!(parse_pinned_displays(r#"{"geometry":{"associated_display_mask":6}}"#), vec![1, 2]);
+        assert!(parse_pinned_displays("not json").is_empty());
+    }
+
+    #[test]
+    fn test_rehome_and_restore() {
+        let items = vec!["battery".to_string(), "clock".to_string()];
+        let pinned = |item: &str| if item == "battery" { vec![2] } else { Vec::new() };
+        let mut clamshell = Clamshell::default();
+
+        // Lid open: the built-in display is sketchybar display 2
+        assert!(clamshell.update(&mappings(1, Some(2)), &items, || false, pinned).is_empty());
+
+        // Lid closed: only the item pinned to the built-in display moves
+        let moves = clamshell.update(&mappings(1, None), &items, || true, pinned);
+        assert_eq!(moves, vec![("battery".to_string(), "1".to_string())]);
+
+        // Lid reopened: it goes back
+        let moves = clamshell.update(&mappings(1, Some(2)), &items, || false, pinned);
+        assert_eq!(moves, vec![("battery".to_string(), "2".to_string())]);
+        assert!(clamshell.update(&mappings(1, Some(2)), &items, || false, pinned).is_empty());
+    }
+
+    #[test]
+    fn test_lid_closed_at_startup() {
+        let items = vec!["battery".to_string(), "clock".to_string()];
+        let pinned = |item: &str| if item == "battery" { vec![2] } else { vec![1] };
+        let mut external = mappings(1, None);
+        external.displays = vec![SketchybarDisplay { arrangement_id: 1, direct_display_id: 5, uuid: None }];
+        let mut clamshell = Clamshell::default();
+
+        // Display 2 isn't there, so the item pinned to it moves; the one on display 1 stays
+        let moves = clamshell.update(&external, &items, || true, pinned);
+        assert_eq!(moves, vec![("battery".to_string(), "1".to_string())]);
+
+        // Opening the lid brings the built-in display and the item back
+        let moves = clamshell.update(&mappings(1, Some(2)), &items, || false, pinned);
+        assert_eq!(moves, vec![("battery".to_string(), "2".to_string())]);
+    }
+
+    #[test]
+    fn test_disabled_builtin_with_lid_open() {
+        let items = vec!["battery".to_string()];
+        let mut clamshell = Clamshell::default();
+        clamshell.update(&mappings(1, Some(2)), &items, || false, |_| vec![2]);
+
+        // The built-in display was turned off some other way; leave items alone
+        assert!(clamshell.update(&mappings(1, None), &items, || false, |_| vec![2]).is_empty());
+    }
+}
@@ -1,61 +1,1027 @@
 //! Configuration module for sketchybartender update intervals
+//!
+//! The config lives in `sketchybartender.toml`. The flat key=value
+//! `sketchybartenderrc` of earlier versions is still read, and converted to
+//! TOML the first time it is found without a TOML file next to it.
 
+mod alias;
+mod badge;
+mod battery;
+mod brew;
+mod calendar;
+mod crypto;
+mod custom;
+mod exchange;
+mod expand;
+mod feeds;
+mod focus_mode;
+mod gitlab;
+mod history;
+mod jira;
+mod level;
+mod linear;
+mod hooks;
+mod load;
+mod notifications;
+mod quiet;
+mod ram;
+mod reviews;
+mod rules;
+mod style;
+mod sun;
+mod thermal;
+mod toml_file;
+mod weather;
+mod zoom;
+
+pub use alias::AliasItem;
+pub use badge::BadgeAppConfig;
+pub use battery::{BatteryConfig, BatteryLevel, ClickModifier};
+pub use brew::{BrewClick, BrewConfig};
+pub use calendar::CalendarConfig;
+pub use crypto::{CryptoAlert, CryptoConfig};
+pub use custom::{CustomItem, ItemPosition, BUILTIN_ITEMS};
+pub use exchange::CurrencyPair;
+pub use feeds::FeedsConfig;
+pub use focus_mode::FocusModeConfig;
+pub use gitlab::GitlabConfig;
+pub use jira::JiraConfig;
+pub use linear::LinearConfig;
+pub use history::{HistoryConfig, GRAPH_METRICS};
+pub use hooks::{Hook, HooksConfig};
+pub use level::{Level, LevelColors};
+pub use load::LoadConfig;
+pub use notifications::NotificationsConfig;
+pub use quiet::QuietConfig;
+pub use ram::RamConfig;
+pub use reviews::ReviewsConfig;
+pub use rules::{hidden_items, PowerSource, Rule, RuleContext, RuleNeeds};
+pub use style::ItemStyle;
+pub use sun::SunConfig;
+pub use thermal::{ThermalConfig, ThermalState};
+pub use weather::{parse_coordinates, WeatherConfig, WeatherSource, WeatherUnits};
+pub use zoom::ZoomConfig;
+
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Known keys of the legacy config format and the TOML key paths they moved to, for
+/// unknown-key reporting and suggestions
+const KNOWN_KEYS: &[(&str, &str)] = &[
+    ("clock_interval", "items.clock.interval"),
+    ("battery_interval", "items.battery.interval"),
+    ("brew_interval", "items.brew.interval"),
+    ("teams_interval", "items.teams.interval"),
+    ("icon_theme", "theme.icon_theme"),
+    ("app_icon_images", "theme.app_icon_images"),
+    ("workspace_icon_order", "workspaces.icon_order"),
+    ("workspace_icon_dedup", "workspaces.icon_dedup"),
+    ("workspace_icon_max", "workspaces.icon_max"),
+    ("important_apps", "workspaces.important_apps"),
+    ("clamshell_items", "displays.clamshell_items"),
+    ("front_app_display", "items.front_app.display"),
+    ("workspace_display", "workspaces.display"),
+];
+
+/// Names of the configuration file, the legacy one and the selected profile's, in the config directory
+const CONFIG_FILE: &str = "sketchybartender.toml";
+const LEGACY_CONFIG_FILE: &str = "sketchybartenderrc";
+const PROFILE_FILE: &str = "sketchybartender.profile";
+/// Environment variable that turns on strict mode like `strict = true`, when set to 1
+const STRICT_VAR: &str = "SKETCHYBARTENDER_STRICT";
+
+/// Name of the profile that applies no overrides
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Glyph set used for app icons in workspaces and front_app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IconTheme {
+    /// sketchybar-app-font ligatures such as ":safari:" (default)
+    #[default]
+    AppFont,
+    /// Nerd Font codepoints
+    NerdFont,
+    /// Plain emoji
+    Emoji,
+}
+
+impl IconTheme {
+    /// Font sketchybar needs to render this theme's glyphs
+    pub fn font(&self) -> &'static str {
+        match self {
+            IconTheme::AppFont => "sketchybar-app-font:Regular:13.0",
+            IconTheme::NerdFont => "JetbrainsMono Nerd Font:Regular:14.0",
+            IconTheme::Emoji => "Apple Color Emoji:Regular:12.0",
+        }
+    }
+}
+
+impl FromStr for IconTheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "app-font" | "sketchybar-app-font" => Ok(IconTheme::AppFont),
+            "nerdfont" | "nerd-font" => Ok(IconTheme::NerdFont),
+            "emoji" => Ok(IconTheme::Emoji),
+            _ => Err(format!(
+                "Invalid value for icon_theme: {} (expected app-font, nerdfont or emoji)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IconTheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IconTheme::AppFont => "app-font",
+            IconTheme::NerdFont => "nerdfont",
+            IconTheme::Emoji => "emoji",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Where real app icons (extracted from app bundles) replace glyphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AppIconImages {
+    /// Glyphs only (default)
+    #[default]
+    Off,
+    /// The front_app item
+    FrontApp,
+    /// The front_app item and workspaces showing a single app
+    All,
+}
+
+impl FromStr for AppIconImages {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(AppIconImages::Off),
+            "front-app" | "front_app" => Ok(AppIconImages::FrontApp),
+            "all" => Ok(AppIconImages::All),
+            _ => Err(format!(
+                "Invalid value for app_icon_images: {} (expected off, front-app or all)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AppIconImages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AppIconImages::Off => "off",
+            AppIconImages::FrontApp => "front-app",
+            AppIconImages::All => "all",
+        };
+        f.write_str(name)
+    }
+}
+
+/// When the daemon shows its own menu bar item in place of sketchybar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MenubarFallback {
+    /// Never (default)
+    #[default]
+    Off,
+    /// While sketchybar isn't running
+    Auto,
+    /// Always, alongside sketchybar
+    On,
+}
+
+impl FromStr for MenubarFallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(MenubarFallback::Off),
+            "auto" => Ok(MenubarFallback::Auto),
+            "on" => Ok(MenubarFallback::On),
+            _ => Err(format!("Invalid value for menubar_fallback: {} (expected off, auto or on)", s)),
+        }
+    }
+}
+
+impl fmt::Display for MenubarFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MenubarFallback::Off => "off",
+            MenubarFallback::Auto => "auto",
+            MenubarFallback::On => "on",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Interfaces the network item counts traffic on
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum NetworkInterface {
+    /// The interface of the default route, following VPNs and Wi-Fi/Ethernet switches (default)
+    #[default]
+    Auto,
+    /// An interface like `en0`, or a family like `utun` for every interface named `utun<n>`
+    Named(String),
+}
+
+impl NetworkInterface {
+    /// Whether a named interface counts, on its own or as part of a family
+    pub fn matches(&self, interface: &str) -> bool {
+        match self {
+            NetworkInterface::Auto => false,
+            NetworkInterface::Named(name) => interface
+                .strip_prefix(name.as_str())
+                .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit())),
+        }
+    }
+}
+
+impl FromStr for NetworkInterface {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(NetworkInterface::Auto),
+            "" => Err("network_interface can't be empty (expected auto or a name like en0 or utun)".to_string()),
+            _ if !s.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                Err(format!("Invalid value for network_interface: {} (expected auto or a name like en0 or utun)", s))
+            }
+            _ => Ok(NetworkInterface::Named(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for NetworkInterface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkInterface::Auto => f.write_str("auto"),
+            NetworkInterface::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+/// How much the daemon logs; each level includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    /// What the daemon does, e.g. profile switches and items hidden by rules (default)
+    #[default]
+    Info,
+    /// Every message received and command run, for tracking down a problem
+    Debug,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(format!("Invalid value for log_level: {} (expected error, warn, info or debug)", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Order of app icons within a workspace item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IconOrder {
+    /// By app name (default)
+    #[default]
+    Alphabetical,
+    /// By window position in the workspace layout
+    Position,
+}
+
+impl FromStr for IconOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alphabetical" => Ok(IconOrder::Alphabetical),
+            "position" => Ok(IconOrder::Position),
+            _ => Err(format!(
+                "Invalid value for workspace_icon_order: {} (expected alphabetical or position)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IconOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IconOrder::Alphabetical => "alphabetical",
+            IconOrder::Position => "position",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Whether a workspace item shows one icon per app or one per window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IconDedup {
+    /// One icon per app on a single display, one per window with several displays (default)
+    #[default]
+    Auto,
+    /// Always one icon per app
+    On,
+    /// Always one icon per window
+    Off,
+}
+
+impl FromStr for IconDedup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(IconDedup::Auto),
+            "on" => Ok(IconDedup::On),
+            "off" => Ok(IconDedup::Off),
+            _ => Err(format!(
+                "Invalid value for workspace_icon_dedup: {} (expected auto, on or off)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IconDedup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IconDedup::Auto => "auto",
+            IconDedup::On => "on",
+            IconDedup::Off => "off",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Where the clock item shows the time in other time zones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtraClocks {
+    /// In a popup that clicking the clock opens
+    #[default]
+    Popup,
+    /// After the local time in the label
+    Inline,
+}
+
+impl FromStr for ExtraClocks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "popup" => Ok(ExtraClocks::Popup),
+            "inline" => Ok(ExtraClocks::Inline),
+            _ => Err(format!("Invalid value for clock_extra_display: {} (expected popup or inline)", s)),
+        }
+    }
+}
+
+impl fmt::Display for ExtraClocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ExtraClocks::Popup => "popup",
+            ExtraClocks::Inline => "inline",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the clock item formats the time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockFormat {
+    /// strftime format used as is; `None` builds one from the options below
+    pub format: Option<String>,
+    /// 12 or 24 hour time
+    pub hours: u8,
+    /// Prefix the abbreviated day of the week
+    pub weekday: bool,
+    /// Show seconds, ticking on every second
+    pub seconds: bool,
+    /// Locale for day and month names and AM/PM (`LC_TIME`); `None` uses the system's
+    pub locale: Option<String>,
+    /// Tick on whole multiples of the interval (:00, :15, :30, :45 for 15 seconds), so the minute changes on time
+    pub align: bool,
+    /// Time zones also shown, e.g. `America/New_York`
+    pub extra: Vec<String>,
+    /// Where the other time zones are shown
+    pub extra_display: ExtraClocks,
+}
+
+impl Default for ClockFormat {
+    fn default() -> Self {
+        Self {
+            format: None,
+            hours: 24,
+            weekday: false,
+            seconds: false,
+            locale: None,
+            align: true,
+            extra: Vec::new(),
+            extra_display: ExtraClocks::default(),
+        }
+    }
+}
+
+impl ClockFormat {
+    /// Whether clicking the clock opens a popup with the other time zones
+    pub fn has_popup(&self) -> bool {
+        !self.extra.is_empty() && self.extra_display == ExtraClocks::Popup
+    }
+
+    /// The strftime format to pass to `date`
+    pub fn strftime(&self) -> String {
+        if let Some(format) = &self.format {
+            return format.clone();
+        }
+
+        let weekday = if self.weekday { "%a " } else { "" };
+        format!("{}%d/%m {}", weekday, self.time_strftime())
+    }
+
+    /// The strftime format of just the time, for the other time zones
+    pub fn time_strftime(&self) -> &'static str {
+        match (self.hours, self.seconds) {
+            (12, false) => "%I:%M %p",
+            (12, true) => "%I:%M:%S %p",
+            (_, false) => "%H:%M",
+            (_, true) => "%H:%M:%S",
+        }
+    }
+
+    /// Whether the label changes every second, so ticks should land on second boundaries
+    pub fn shows_seconds(&self) -> bool {
+        match &self.format {
+            Some(format) => ["%S", "%T", "%r", "%s"].iter().any(|spec| format.contains(spec)),
+            None => self.seconds,
+        }
+    }
+}
+
+/// Parse a color, as sketchybar's 0xAARRGGBB
+fn parse_color(key: &str, value: &str) -> Result<String, String> {
+    let valid = value
+        .strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == 8 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!("Invalid color for {}: {} (expected 0xAARRGGBB)", key, value))
+    }
+}
+
+/// Parse the clock's hour mode
+fn parse_hours(key: &str, value: u64) -> Result<u8, String> {
+    match value {
+        12 => Ok(12),
+        24 => Ok(24),
+        _ => Err(format!("Invalid value for {}: {} (expected 12 or 24)", key, value)),
+    }
+}
+
+/// Where time zone files live, on macOS as on Linux
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+/// Parse a list of time zones, which `date` would silently replace with UTC if they didn't exist
+fn parse_time_zones(key: &str, zones: Vec<String>) -> Result<Vec<String>, String> {
+    for zone in &zones {
+        let safe = !zone.starts_with('/') && !zone.split('/').any(|part| part == "..");
+        if !safe || !Path::new(ZONEINFO).join(zone).is_file() {
+            return Err(format!("Unknown time zone for {}: {} (expected e.g. America/New_York)", key, zone));
+        }
+    }
+    Ok(zones)
+}
+
+/// Parse an optional string, where an empty value means unset
+fn parse_optional(value: &str) -> Option<String> {
+    Some(value.to_string()).filter(|value| !value.is_empty())
+}
+
+/// Parse the address of a web service, like `https://gitlab.example.com`, without a trailing slash
+fn parse_url(key: &str, value: &str) -> Result<String, String> {
+    let url = value.trim().trim_end_matches('/');
+    let host = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"));
+    if host.is_some_and(|host| !host.is_empty() && !host.contains(char::is_whitespace) && !host.contains('"')) {
+        Ok(url.to_string())
+    } else {
+        Err(format!("Invalid URL for {}: {} (expected e.g. https://example.com)", key, value))
+    }
+}
+
+/// Parse a socket address, with an empty value for none
+fn parse_address(key: &str, value: &str) -> Result<Option<SocketAddr>, String> {
+    parse_optional(value)
+        .map(|address| {
+            address
+                .parse()
+                .map_err(|_| format!("Invalid address for {}: {} (expected e.g. 127.0.0.1:9273)", key, address))
+        })
+        .transpose()
+}
+
+/// Displays an item is shown on, as one of sketchybar's display selectors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplaySpec {
+    /// The display with the focused window, following focus
+    Active,
+    /// The display with the menu bar
+    Main,
+    /// Every display
+    All,
+    /// Sketchybar display (arrangement) IDs
+    Ids(Vec<u32>),
+}
+
+impl FromStr for DisplaySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(DisplaySpec::Active),
+            "main" => Ok(DisplaySpec::Main),
+            "all" => Ok(DisplaySpec::All),
+            _ => s
+                .split(',')
+                .map(|id| id.trim().parse::<u32>().ok().filter(|id| *id > 0))
+                .collect::<Option<Vec<u32>>>()
+                .map(DisplaySpec::Ids)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid display: {} (expected active, main, all or display numbers like 1,2)",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for DisplaySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplaySpec::Active => f.write_str("active"),
+            DisplaySpec::Main => f.write_str("main"),
+            DisplaySpec::All => f.write_str("all"),
+            DisplaySpec::Ids(ids) => {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                f.write_str(&ids.join(","))
+            }
+        }
+    }
+}
+
+/// Parse a display selector, where `auto` leaves the daemon's default placement
+fn parse_display(key: &str, value: &str) -> Result<Option<DisplaySpec>, String> {
+    match value {
+        "auto" => Ok(None),
+        _ => value.parse().map(Some).map_err(|e| format!("{} for {}", e, key)),
+    }
+}
+
+/// Format an optional display selector for the config file
+fn display_or_auto(spec: &Option<DisplaySpec>) -> String {
+    spec.as_ref().map_or_else(|| "auto".to_string(), |spec| spec.to_string())
+}
+
+/// Number of single-character edits turning `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// The candidate closest to a misspelled key, if it is close enough to be a likely typo
+fn suggest<'a>(unknown: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (unknown.len() / 4).clamp(1, 3);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(unknown, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Message for an unknown key, with a suggestion when one is close
+fn unknown_key_message(key: &str, suggestion: Option<&str>) -> String {
+    match suggestion {
+        Some(suggestion) => format!("unknown config key `{}`; did you mean `{}`?", key, suggestion),
+        None => format!("unknown config key `{}`", key),
+    }
+}
+
+/// Parse a whole number, like an icon count
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| {
+        format!("Invalid value for {}: {} (expected a whole number)", key, value)
+    })
+}
+
+/// Parse a comma-separated list of app or item names
+fn parse_app_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Parse a number of seconds that may be 0
+fn parse_seconds(key: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid value for {}: {} (expected whole seconds)", key, value))
+}
+
+/// Parse a rate in MB/s, which must be at least 1
+fn parse_rate(key: &str, rate: u64) -> Result<u64, String> {
+    match rate {
+        0 => Err(format!("Invalid value for {}: {} (expected whole MB/s from 1)", key, rate)),
+        _ => Ok(rate),
+    }
+}
+
+/// Parse an update interval, which must be at least 1 second
+fn parse_interval(key: &str, seconds: u64) -> Result<u64, String> {
+    match seconds {
+        0 => Err(format!("{} must be at least 1 second", key)),
+        _ => Ok(seconds),
+    }
+}
 
 /// Configuration for update intervals (in seconds)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Config {
-    /// Clock update interval (default: 15 seconds)
+    /// Clock update interval (default: 15 seconds); with seconds shown the clock ticks every second
     pub clock_interval: u64,
-    /// Battery update interval (default: 120 seconds)
+    /// Clock label format (default: DD/MM HH:MM)
+    pub clock: ClockFormat,
+    /// Battery refresh interval, a fallback to power notifications (default: 600 seconds)
     pub battery_interval: u64,
+    /// Battery icon breakpoints, alert levels and colors
+    pub battery: BatteryConfig,
     /// Brew outdated check interval (default: 3600 seconds / 1 hour)
     pub brew_interval: u64,
+    /// Brew binary, outdated check and click behavior
+    pub brew: BrewConfig,
     /// Teams notification check interval (default: 30 seconds)
     pub teams_interval: u64,
+    /// App, Dock label, colors and click refresh schedule of the Teams badge item
+    pub teams: BadgeAppConfig,
+    /// Wi-Fi signal check interval (default: 30 seconds)
+    pub wifi_interval: u64,
+    /// Network throughput sample interval (default: 2 seconds)
+    pub network_interval: u64,
+    /// Interfaces the network item counts (default: auto)
+    pub network_interface: NetworkInterface,
+    /// CPU usage sample interval (default: 5 seconds)
+    pub cpu_interval: u64,
+    /// Memory usage sample interval (default: 10 seconds)
+    pub ram_interval: u64,
+    /// Memory pressure thresholds and colors of the ram item
+    pub ram: RamConfig,
+    /// Disk throughput sample interval (default: 2 seconds)
+    pub disk_interval: u64,
+    /// Combined read and write rate in MB/s at the top of the disk graph (default: 500)
+    pub disk_graph_max: u64,
+    /// Load average sample interval (default: 5 seconds)
+    pub load_interval: u64,
+    /// Load thresholds and colors of the load item
+    pub load: LoadConfig,
+    /// Heaviest process sample interval (default: 5 seconds)
+    pub top_process_interval: u64,
+    /// Thermal state and temperature check interval (default: 30 seconds)
+    pub thermal_interval: u64,
+    /// Temperature readout and state colors of the thermal item
+    pub thermal: ThermalConfig,
+    /// Fallback Focus check interval; turning a Focus on or off is picked up within a second (default: 60 seconds)
+    pub focus_mode_interval: u64,
+    /// Toggle shortcut and colors of the focus_mode item
+    pub focus_mode: FocusModeConfig,
+    /// Fallback appearance check interval; switches show as they happen (default: 60 seconds)
+    pub appearance_interval: u64,
+    /// Display brightness check interval, which catches the brightness keys (default: 5 seconds)
+    pub brightness_interval: u64,
+    /// Brightness change in percent per line scrolled over the brightness item, 0 to ignore scrolling (default: 5)
+    pub brightness_scroll_step: u8,
+    /// Volume change in percent per line scrolled over the volume item, 0 to ignore scrolling (default: 5)
+    pub volume_scroll_step: u8,
+    /// Modifier key that makes a click on the volume item open its slider instead of muting (default: alt)
+    pub volume_slider_modifier: ClickModifier,
+    /// Next calendar event check interval (default: 60 seconds)
+    pub calendar_interval: u64,
+    /// Calendars and countdown colors of the calendar item
+    pub calendar: CalendarConfig,
+    /// Zoom meeting and microphone check interval (default: 3 seconds)
+    pub zoom_interval: u64,
+    /// Microphone colors of the zoom item
+    pub zoom: ZoomConfig,
+    /// Weather fetch interval (default: 900 seconds)
+    pub weather_interval: u64,
+    /// Location, service and units of the weather item
+    pub weather: WeatherConfig,
+    /// Next sunrise or sunset check interval (default: 60 seconds)
+    pub sun_interval: u64,
+    /// Location and golden hour of the sun item
+    pub sun: SunConfig,
+    /// Name the moon's phase next to its icon (default: off)
+    pub moon_label: bool,
+    /// Crypto price fetch interval (default: 300 seconds)
+    pub crypto_interval: u64,
+    /// Coins, currency and price alerts of the crypto item
+    pub crypto: CryptoConfig,
+    /// Exchange rate fetch interval (default: 21600 seconds)
+    pub exchange_interval: u64,
+    /// Currency pair of the exchange item; `None` (the default) leaves the item off
+    pub exchange_pair: Option<CurrencyPair>,
+    /// Feed check interval (default: 900 seconds)
+    pub feeds_interval: u64,
+    /// Feed URLs and popup length of the feeds item
+    pub feeds: FeedsConfig,
+    /// GitHub review queue check interval (default: 300 seconds)
+    pub reviews_interval: u64,
+    /// GitHub token and search of the reviews item
+    pub reviews: ReviewsConfig,
+    /// GitLab merge request and pipeline check interval (default: 300 seconds)
+    pub gitlab_interval: u64,
+    /// Instance, token and failure color of the gitlab item
+    pub gitlab: GitlabConfig,
+    /// Jira assigned issues check interval (default: 300 seconds)
+    pub jira_interval: u64,
+    /// Site, credentials, projects and priority colors of the jira item
+    pub jira: JiraConfig,
+    /// Linear cycle issue count check interval (default: 300 seconds)
+    pub linear_interval: u64,
+    /// API key and teams of the linear item
+    pub linear: LinearConfig,
+    /// Icon set for app icons (default: app-font)
+    pub icon_theme: IconTheme,
+    /// Real app icon images instead of glyphs (default: off)
+    pub app_icon_images: AppIconImages,
+    /// Order of icons in workspace items (default: alphabetical)
+    pub workspace_icon_order: IconOrder,
+    /// One icon per app or per window in workspace items (default: auto)
+    pub workspace_icon_dedup: IconDedup,
+    /// Maximum icons per workspace item, 0 for no limit (default: 0)
+    pub workspace_icon_max: usize,
+    /// Apps whose icons come first in workspace items, in this order (default: none)
+    pub important_apps: Vec<String>,
+    /// Items moved off the built-in display while the lid is closed (default: battery, clock)
+    pub clamshell_items: Vec<String>,
+    /// Items the daemon hides and stops updating (default: none)
+    pub disabled_items: Vec<String>,
+    /// Displays front_app is shown on; `None` keeps sketchybarrc's placement (default: auto)
+    pub front_app_display: Option<DisplaySpec>,
+    /// Displays all workspace items are shown on; `None` follows each workspace's monitor (default: auto)
+    pub workspace_display: Option<DisplaySpec>,
+    /// Per-item style overrides from `[items.<name>.style]`, by item name (default: none)
+    pub styles: BTreeMap<String, ItemStyle>,
+    /// User-defined items from `[custom.<name>]`, by item name (default: none)
+    pub custom_items: BTreeMap<String, CustomItem>,
+    /// Menu bar items mirrored into the bar from `[aliases.<name>]`, by item name (default: none)
+    pub aliases: BTreeMap<String, AliasItem>,
+    /// Rules hiding and showing items by time, power source, network and focused app (default: none)
+    pub rules: Vec<Rule>,
+    /// Items hidden in quiet mode, and whether screen sharing turns it on
+    pub quiet: QuietConfig,
+    /// Events that raise a macOS notification
+    pub notifications: NotificationsConfig,
+    /// Show a menu bar item with the clock, battery and brew when sketchybar is down (default: off)
+    pub menubar_fallback: MenubarFallback,
+    /// Shell commands run on daemon events (default: none)
+    pub hooks: HooksConfig,
+    /// Address serving metrics over HTTP, e.g. 127.0.0.1:9273 (default: none)
+    pub metrics_listen: Option<SocketAddr>,
+    /// Battery and CPU graph items and how many samples they keep
+    pub history: HistoryConfig,
+    /// Least severe messages the daemon logs (default: info)
+    pub log_level: LogLevel,
+    /// Also log to a rotating file in the cache directory (default: false)
+    pub log_file: bool,
+    /// Remove the custom, alias and graph items the daemon added when it exits (default: false)
+    pub clear_on_exit: bool,
+    /// Create the bar and its items when the daemon starts, instead of sketchybarrc
+    /// (default: false, so configs from before it keep their bar; on in the config created on first run)
+    pub bootstrap_items: bool,
+    /// Refuse to start the daemon when the config has errors (default: false)
+    pub strict: bool,
+    /// Named overlays from `[profiles.<name>]`, applied with [`Config::with_profile`]
+    pub profiles: BTreeMap<String, toml::Table>,
+    /// Settings under `[plugins]`, passed through untouched
+    pub plugins: toml::Table,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             clock_interval: 15,
-            battery_interval: 120,
+            clock: ClockFormat::default(),
+            battery_interval: 600,
+            battery: BatteryConfig::default(),
             brew_interval: 3600,
+            brew: BrewConfig::default(),
             teams_interval: 30,
+            teams: BadgeAppConfig::default(),
+            wifi_interval: 30,
+            network_interval: 2,
+            network_interface: NetworkInterface::default(),
+            cpu_interval: 5,
+            ram_interval: 10,
+            ram: RamConfig::default(),
+            disk_interval: 2,
+            disk_graph_max: 500,
+            load_interval: 5,
+            load: LoadConfig::default(),
+            top_process_interval: 5,
+            thermal_interval: 30,
+            thermal: ThermalConfig::default(),
+            focus_mode_interval: 60,
+            focus_mode: FocusModeConfig::default(),
+            appearance_interval: 60,
+            brightness_interval: 5,
+            brightness_scroll_step: 5,
+            volume_scroll_step: 5,
+            volume_slider_modifier: ClickModifier::Alt,
+            calendar_interval: 60,
+            calendar: CalendarConfig::default(),
+            zoom_interval: 3,
+            zoom: ZoomConfig::default(),
+            weather_interval: 900,
+            weather: WeatherConfig::default(),
+            sun_interval: 60,
+            sun: SunConfig::default(),
+            moon_label: false,
+            crypto_interval: 300,
+            crypto: CryptoConfig::default(),
+            exchange_interval: 6 * 60 * 60,
+            exchange_pair: None,
+            feeds_interval: 900,
+            feeds: FeedsConfig::default(),
+            reviews_interval: 300,
+            reviews: ReviewsConfig::default(),
+            gitlab_interval: 300,
+            gitlab: GitlabConfig::default(),
+            jira_interval: 300,
+            jira: JiraConfig::default(),
+            linear_interval: 300,
+            linear: LinearConfig::default(),
+            icon_theme: IconTheme::default(),
+            app_icon_images: AppIconImages::default(),
+            workspace_icon_order: IconOrder::default(),
+            workspace_icon_dedup: IconDedup::default(),
+            workspace_icon_max: 0,
+            important_apps: Vec::new(),
+            clamshell_items: vec!["battery".to_string(), "clock".to_string()],
+            disabled_items: Vec::new(),
+            front_app_display: None,
+            workspace_display: None,
+            styles: BTreeMap::new(),
+            custom_items: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            rules: Vec::new(),
+            quiet: QuietConfig::default(),
+            notifications: NotificationsConfig::default(),
+            menubar_fallback: MenubarFallback::default(),
+            hooks: HooksConfig::default(),
+            metrics_listen: None,
+            history: HistoryConfig::default(),
+            log_level: LogLevel::default(),
+            log_file: false,
+            clear_on_exit: false,
+            bootstrap_items: false,
+            strict: false,
+            profiles: BTreeMap::new(),
+            plugins: toml::Table::new(),
         }
     }
 }
 
+/// How serious a configuration problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The value is ignored or overridden, but the config still loads
+    Warning,
+    /// The config cannot be loaded as written
+    Error,
+}
+
+/// A problem found while parsing the configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based line number in the config file
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "line {}: {}: {}", self.line, level, self.message)
+    }
+}
+
 impl Config {
     /// Load configuration from file or use defaults
+    ///
+    /// Problems are printed; settings with errors keep their defaults, and a
+    /// file that can't be read or parsed gives the defaults.
     pub fn load() -> Self {
-        let config_path = Self::get_config_path();
+        Self::load_counting_errors(&Self::get_config_dir(), false).map_or_else(
+            |e| {
+                eprintln!("{}", e);
+                eprintln!("Using default configuration");
+                Self::default()
+            },
+            |(config, _)| config,
+        )
+    }
+
+    /// Load configuration from the files in `dir` like [`Config::load`], also returning the number of errors found
+    ///
+    /// A file that can't be read is an error of its own, and so is TOML that
+    /// doesn't parse unless `recover` is set; see [`Config::load_from_file`].
+    fn load_counting_errors(dir: &Path, recover: bool) -> Result<(Self, usize), String> {
+        let config_path = dir.join(CONFIG_FILE);
+        let legacy_path = dir.join(LEGACY_CONFIG_FILE);
 
         if config_path.exists() {
-            match Self::load_from_file(&config_path) {
-                Ok(config) => config,
-                Err(e) => {
-                    eprintln!("Failed to load config from {:?}: {}", config_path, e);
-                    eprintln!("Using default configuration");
-                    Self::default()
+            Self::load_from_file(&config_path, recover)
+                .map_err(|e| format!("Failed to load config from {:?}: {}", config_path, e))
+        } else if legacy_path.exists() {
+            // Convert the legacy file once it is free of errors; it is left in place
+            match Self::load_from_file(&legacy_path, recover) {
+                Ok((config, 0)) => {
+                    match config.save_to_file(&config_path) {
+                        Ok(()) => eprintln!("Converted {:?} to {:?}", legacy_path, config_path),
+                        Err(e) => eprintln!("Failed to convert legacy config: {}", e),
+                    }
+                    Ok((config, 0))
+                }
+                Ok(loaded) => {
+                    eprintln!("Not converting {:?} until its errors are fixed", legacy_path);
+                    Ok(loaded)
                 }
+                Err(e) => Err(format!("Failed to load config from {:?}: {}", legacy_path, e)),
             }
         } else {
-            // Create default config file
-            let config = Self::default();
+            // Create a config file for the bar the shipped sketchybarrc expects, one the daemon sets up
+            let config = Self::first_run();
             if let Err(e) = config.save_to_file(&config_path) {
                 eprintln!("Failed to save default config: {}", e);
             } else {
                 eprintln!("Created default config at {:?}", config_path);
             }
-            config
+            Ok((config, 0))
         }
     }
 
-    /// Get the configuration file path
-    fn get_config_path() -> PathBuf {
+    /// The configuration written when there is no config file yet, which the repository's
+    /// `sketchybartender.toml` matches
+    fn first_run() -> Self {
+        Self { bootstrap_items: true, ..Self::default() }
+    }
+
+    /// Get the sketchybar configuration directory
+    pub fn get_config_dir() -> PathBuf {
         let config_dir = env::var("XDG_CONFIG_HOME")
             .map(PathBuf::from)
             .unwrap_or_else(|_| {
@@ -63,17 +1029,153 @@ impl Config {
                 PathBuf::from(home).join(".config")
             });
 
-        config_dir.join("sketchybar").join("sketchybartenderrc")
+        config_dir.join("sketchybar")
     }
 
-    /// Load configuration from a file
-    fn load_from_file(path: &PathBuf) -> Result<Self, String> {
+    /// Get the configuration file path
+    pub fn get_config_path() -> PathBuf {
+        Self::get_config_dir().join(CONFIG_FILE)
+    }
+
+    /// Get the path of the legacy key=value configuration file
+    pub fn get_legacy_config_path() -> PathBuf {
+        Self::get_config_dir().join(LEGACY_CONFIG_FILE)
+    }
+
+    /// Get the file recording the profile selected with `sketchycli profile`
+    pub fn get_profile_path() -> PathBuf {
+        Self::get_config_dir().join(PROFILE_FILE)
+    }
+
+    /// The selected profile, or [`DEFAULT_PROFILE`] if none was selected
+    pub fn selected_profile() -> String {
+        Self::selected_profile_in(&Self::get_config_dir())
+    }
+
+    fn selected_profile_in(dir: &Path) -> String {
+        fs::read_to_string(dir.join(PROFILE_FILE))
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+    }
+
+    /// Record the selected profile so it is used on the next start
+    pub fn save_selected_profile(name: &str) -> Result<(), String> {
+        fs::write(Self::get_profile_path(), format!("{}\n", name))
+            .map_err(|e| format!("Failed to save selected profile: {}", e))
+    }
+
+    /// Load the configuration with the selected profile applied, as the daemon starts
+    ///
+    /// A file that can't be read is returned as an error. Other errors fall
+    /// back to defaults unless strict mode is on, through `strict` (the
+    /// daemon's `--strict` flag), `SKETCHYBARTENDER_STRICT=1` or `strict = true`
+    /// in the config; then they are returned so the daemon can refuse to start.
+    /// That includes TOML that doesn't parse, whose `strict` line is read on its own.
+    pub fn load_selected(strict: bool) -> Result<Self, String> {
+        Self::load_selected_in(&Self::get_config_dir(), strict || Self::strict_from_env(), true)
+    }
+
+    /// Whether `SKETCHYBARTENDER_STRICT=1` turns on strict mode
+    pub fn strict_from_env() -> bool {
+        env::var(STRICT_VAR).is_ok_and(|value| value == "1")
+    }
+
+    /// Load the configuration in `dir` like [`Config::load_selected`]
+    ///
+    /// Without `recover`, TOML that doesn't parse is always an error, e.g. so a
+    /// reload keeps the running settings rather than go back to the defaults.
+    pub fn load_selected_in(dir: &Path, strict: bool, recover: bool) -> Result<Self, String> {
+        let (config, mut errors) = Self::load_counting_errors(dir, recover)?;
+        let profile = Self::selected_profile_in(dir);
+
+        let profiled = match config.with_profile(&profile) {
+            Ok(profiled) => profiled,
+            Err(e) => {
+                eprintln!("Failed to apply profile {}: {}", profile, e);
+                errors += 1;
+                config
+            }
+        };
+
+        if (profiled.strict || strict) && errors > 0 {
+            return Err(format!("{} error(s) in the configuration, and strict mode is on", errors));
+        }
+        Ok(profiled)
+    }
+
+    /// Whether the daemon should show and update an item
+    pub fn is_enabled(&self, item: &str) -> bool {
+        !self.disabled_items.iter().any(|disabled| disabled == item)
+    }
+
+    /// Parse a file in the format its extension indicates
+    fn parse_file(path: &Path) -> Result<(Self, Vec<Diagnostic>), String> {
         let contents = fs::read_to_string(path)
             .map_err(|e| format!("Failed to read file: {}", e))?;
+        Ok(Self::parse_contents(path, &contents))
+    }
+
+    /// Parse the contents of a file in the format its extension indicates
+    fn parse_contents(path: &Path, contents: &str) -> (Self, Vec<Diagnostic>) {
+        if Self::is_toml(path) {
+            Self::parse_toml_in(contents, path.parent().unwrap_or(Path::new(".")))
+        } else {
+            Self::parse(contents)
+        }
+    }
 
+    fn is_toml(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "toml")
+    }
+
+    /// Validate a configuration file, returning every problem found
+    pub fn validate_file(path: &Path) -> Result<Vec<Diagnostic>, String> {
+        Ok(Self::parse_file(path)?.1)
+    }
+
+    /// Load configuration from a file, printing every problem and returning the number of errors
+    ///
+    /// TOML that doesn't parse is returned as an error rather than counted,
+    /// since none of the file's settings could be applied. With `recover` it
+    /// is printed and counted as one error on the defaults instead, unless a
+    /// top-level `strict = true` line can be read on its own.
+    fn load_from_file(path: &Path, recover: bool) -> Result<(Self, usize), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        if Self::is_toml(path) {
+            if let Some(syntax) = Self::toml_syntax_error(&contents) {
+                if !recover || Self::recovered_strict(&contents) {
+                    return Err(format!("Failed to parse: {}", syntax));
+                }
+                eprintln!("{}: failed to parse: {}", path.display(), syntax);
+                eprintln!("Using default configuration");
+                return Ok((Self::default(), 1));
+            }
+        }
+        let (config, diagnostics) = Self::parse_contents(path, &contents);
+
+        for diagnostic in &diagnostics {
+            eprintln!("{}: {}", path.display(), diagnostic);
+        }
+
+        let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+        if errors > 0 {
+            eprintln!("{} error(s) in {}; those settings keep their defaults", errors, path.display());
+        }
+        Ok((config, errors))
+    }
+
+    /// Parse legacy key=value contents, collecting diagnostics instead of stopping at the first problem
+    pub fn parse(contents: &str) -> (Self, Vec<Diagnostic>) {
         let mut config = Self::default();
+        let mut diagnostics = Vec::new();
+        // Line on which each key was first set, for duplicate detection
+        let mut seen: Vec<(&str, usize)> = Vec::new();
 
-        for line in contents.lines() {
+        for (index, line) in contents.lines().enumerate() {
+            let line_no = index + 1;
             let line = line.trim();
 
             // Skip empty lines and comments
@@ -82,65 +1184,82 @@ impl Config {
             }
 
             // Parse key=value pairs
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim();
-                let value = value.trim();
-
-                match key {
-                    "clock_interval" => {
-                        config.clock_interval = value.parse()
-                            .map_err(|_| format!("Invalid value for clock_interval: {}", value))?;
-                    }
-                    "battery_interval" => {
-                        config.battery_interval = value.parse()
-                            .map_err(|_| format!("Invalid value for battery_interval: {}", value))?;
-                    }
-                    "brew_interval" => {
-                        config.brew_interval = value.parse()
-                            .map_err(|_| format!("Invalid value for brew_interval: {}", value))?;
-                    }
-                    "teams_interval" => {
-                        config.teams_interval = value.parse()
-                            .map_err(|_| format!("Invalid value for teams_interval: {}", value))?;
-                    }
-                    _ => {
-                        eprintln!("Warning: Unknown config key: {}", key);
-                    }
-                }
+            let Some((key, value)) = line.split_once('=') else {
+                diagnostics.push(Diagnostic {
+                    line: line_no,
+                    severity: Severity::Error,
+                    message: format!("expected `key = value`, found `{}`", line),
+                });
+                continue;
+            };
+
+            let key = key.trim();
+            let value = value.trim();
+
+            let Some(&(known, _)) = KNOWN_KEYS.iter().find(|(k, _)| *k == key) else {
+                diagnostics.push(Diagnostic {
+                    line: line_no,
+                    severity: Severity::Warning,
+                    message: unknown_key_message(key, suggest(key, KNOWN_KEYS.iter().map(|(k, _)| *k))),
+                });
+                continue;
+            };
+
+            if let Some((_, first_line)) = seen.iter().find(|(k, _)| *k == known) {
+                diagnostics.push(Diagnostic {
+                    line: line_no,
+                    severity: Severity::Warning,
+                    message: format!(
+                        "`{}` is already set on line {}; this later value wins",
+                        key, first_line
+                    ),
+                });
+            } else {
+                seen.push((known, line_no));
+            }
+
+            if let Err(message) = config.apply(key, value) {
+                diagnostics.push(Diagnostic {
+                    line: line_no,
+                    severity: Severity::Error,
+                    message,
+                });
             }
         }
 
-        Ok(config)
+        (config, diagnostics)
+    }
+
+    /// Set a single known key, validating its value
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "clock_interval" => self.clock_interval = parse_interval(key, parse_seconds(key, value)?)?,
+            "battery_interval" => self.battery_interval = parse_interval(key, parse_seconds(key, value)?)?,
+            "brew_interval" => self.brew_interval = parse_interval(key, parse_seconds(key, value)?)?,
+            "teams_interval" => self.teams_interval = parse_interval(key, parse_seconds(key, value)?)?,
+            "icon_theme" => self.icon_theme = value.parse()?,
+            "app_icon_images" => self.app_icon_images = value.parse()?,
+            "workspace_icon_order" => self.workspace_icon_order = value.parse()?,
+            "workspace_icon_dedup" => self.workspace_icon_dedup = value.parse()?,
+            "workspace_icon_max" => self.workspace_icon_max = parse_number(key, value)?,
+            "important_apps" => self.important_apps = parse_app_list(value),
+            "clamshell_items" => self.clamshell_items = parse_app_list(value),
+            "front_app_display" => self.front_app_display = parse_display(key, value)?,
+            "workspace_display" => self.workspace_display = parse_display(key, value)?,
+            _ => unreachable!("key checked against KNOWN_KEYS"),
+        }
+        Ok(())
     }
 
     /// Save configuration to a file
-    fn save_to_file(&self, path: &PathBuf) -> Result<(), String> {
+    fn save_to_file(&self, path: &Path) -> Result<(), String> {
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create config directory: {}", e))?;
         }
 
-        let contents = format!(
-            "# Sketchybartender Configuration\n\
-             # Update intervals in seconds\n\
-             \n\
-             # Clock update interval (default: 15)\n\
-             clock_interval = {}\n\
-             \n\
-             # Battery update interval (default: 120)\n\
-             battery_interval = {}\n\
-             \n\
-             # Brew outdated check interval (default: 3600)\n\
-             brew_interval = {}\n\
-             \n\
-             # Teams notification check interval (default: 30)\n\
-             teams_interval = {}\n",
-            self.clock_interval,
-            self.battery_interval,
-            self.brew_interval,
-            self.teams_interval,
-        );
+        let contents = self.to_toml();
 
         fs::write(path, contents)
             .map_err(|e| format!("Failed to write config file: {}", e))?;
@@ -157,8 +1276,258 @@ mod tests {
     fn test_default_config() {
         let config = Config::default();
         assert_eq!(config.clock_interval, 15);
-        assert_eq!(config.battery_interval, 120);
+        assert_eq!(config.battery_interval, 600);
+        assert!(config.battery.time_remaining);
+        assert_eq!(config.battery.low_power_modifier, ClickModifier::Alt);
         assert_eq!(config.brew_interval, 3600);
         assert_eq!(config.teams_interval, 30);
+        assert_eq!(config.wifi_interval, 30);
+        assert_eq!(config.network_interval, 2);
+        assert_eq!(config.network_interface, NetworkInterface::Auto);
+        assert_eq!(config.cpu_interval, 5);
+        assert_eq!(config.ram_interval, 10);
+        assert_eq!(config.disk_interval, 2);
+        assert_eq!(config.disk_graph_max, 500);
+        assert_eq!(config.load_interval, 5);
+        assert_eq!(config.load, LoadConfig::default());
+        assert_eq!(config.top_process_interval, 5);
+        assert_eq!(config.thermal_interval, 30);
+        assert!(config.thermal.temperature);
+        assert_eq!(config.focus_mode_interval, 60);
+        assert_eq!(config.focus_mode.shortcut, "Toggle Do Not Disturb");
+        assert_eq!(config.appearance_interval, 60);
+        assert_eq!(config.brightness_interval, 5);
+        assert_eq!(config.brightness_scroll_step, 5);
+        assert_eq!(config.volume_scroll_step, 5);
+        assert_eq!(config.volume_slider_modifier, ClickModifier::Alt);
+        assert_eq!(config.calendar_interval, 60);
+        assert!(config.calendar.names.is_empty());
+        assert_eq!(config.zoom_interval, 3);
+        assert_eq!(config.weather_interval, 900);
+        assert_eq!(config.weather.source, WeatherSource::Wttr);
+        assert_eq!(config.sun_interval, 60);
+        assert!(!config.sun.golden_hour);
+        assert!(!config.moon_label);
+        assert_eq!(config.crypto_interval, 300);
+        assert!(config.crypto.coins.is_empty());
+        assert_eq!(config.crypto.currency, "usd");
+        assert!(config.crypto.alerts.is_empty());
+        assert_eq!(config.exchange_interval, 21600);
+        assert_eq!(config.exchange_pair, None);
+        assert_eq!(config.feeds_interval, 900);
+        assert!(config.feeds.urls.is_empty());
+        assert_eq!(config.feeds.recent, 10);
+        assert_eq!(config.reviews_interval, 300);
+        assert_eq!(config.reviews.token, None);
+        assert_eq!(config.gitlab_interval, 300);
+        assert_eq!(config.gitlab.url, "https://gitlab.com");
+        assert_eq!(config.gitlab.token, None);
+        assert_eq!(config.jira_interval, 300);
+        assert_eq!(config.jira.url, None);
+        assert!(config.jira.projects.is_empty());
+        assert_eq!(config.linear_interval, 300);
+        assert_eq!(config.linear.api_key, None);
+        assert_eq!(config.icon_theme, IconTheme::AppFont);
+        assert_eq!(config.app_icon_images, AppIconImages::Off);
+        assert!(!config.bootstrap_items);
+        assert!(Config::first_run().bootstrap_items);
+    }
+
+    #[test]
+    fn test_parse_icon_theme() {
+        let (config, diagnostics) = Config::parse("icon_theme = emoji\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.icon_theme, IconTheme::Emoji);
+
+        let (_, diagnostics) = Config::parse("icon_theme = wingdings\n");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_app_icon_images() {
+        let (config, diagnostics) = Config::parse("app_icon_images = front-app\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.app_icon_images, AppIconImages::FrontApp);
+
+        let (_, diagnostics) = Config::parse("app_icon_images = yes\n");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_network_interface() {
+        let (config, diagnostics) = Config::parse_toml("[items.network]\ninterface = \"utun\"\n");
+        assert!(diagnostics.is_empty());
+        assert!(config.network_interface.matches("utun3"));
+        assert!(!config.network_interface.matches("en0"));
+        assert!(NetworkInterface::Named("en0".to_string()).matches("en0"));
+        assert!(!NetworkInterface::Named("en".to_string()).matches("en0s"));
+
+        let (_, diagnostics) = Config::parse_toml("[items.network]\ninterface = \"en0 en1\"\n");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(parse_url("gitlab_url", "https://gitlab.example.com/").unwrap(), "https://gitlab.example.com");
+        assert!(parse_url("gitlab_url", "http://10.0.0.5:8080").is_ok());
+        for bad in ["gitlab.example.com", "https://", "https://git lab"] {
+            assert!(parse_url("gitlab_url", bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_metrics_listen() {
+        let (config, diagnostics) = Config::parse_toml("[metrics]\nlisten = \"127.0.0.1:9273\"\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.metrics_listen, Some(SocketAddr::from(([127, 0, 0, 1], 9273))));
+
+        let (_, diagnostics) = Config::parse_toml("[metrics]\nlisten = \"localhost\"\n");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_workspace_icon_rules() {
+        let contents = "workspace_icon_order = position\nworkspace_icon_dedup = on\nworkspace_icon_max = 4\nimportant_apps = Slack, , Safari\n";
+        let (config, diagnostics) = Config::parse(contents);
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.workspace_icon_order, IconOrder::Position);
+        assert_eq!(config.workspace_icon_dedup, IconDedup::On);
+        assert_eq!(config.workspace_icon_max, 4);
+        assert_eq!(config.important_apps, vec!["Slack", "Safari"]);
+
+        let (config, diagnostics) = Config::parse("important_apps =\n");
+        assert!(diagnostics.is_empty());
+        assert!(config.important_apps.is_empty());
+
+        let (_, diagnostics) = Config::parse("workspace_icon_max = -1\n");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_clamshell_items() {
+        assert_eq!(Config::default().clamshell_items, vec!["battery", "clock"]);
+
+        let (config, diagnostics) = Config::parse("clamshell_items = battery, brew\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.clamshell_items, vec!["battery", "brew"]);
+
+        let (config, _) = Config::parse("clamshell_items =\n");
+        assert!(config.clamshell_items.is_empty());
+    }
+
+    #[test]
+    fn test_parse_display_specs() {
+        let (config, diagnostics) = Config::parse("front_app_display = active\nworkspace_display = 1, 2\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.front_app_display, Some(DisplaySpec::Active));
+        assert_eq!(config.workspace_display, Some(DisplaySpec::Ids(vec![1, 2])));
+        assert_eq!(DisplaySpec::Ids(vec![1, 2]).to_string(), "1,2");
+
+        let (config, diagnostics) = Config::parse("front_app_display = auto\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.front_app_display, None);
+
+        for bad in ["front_app_display = 0\n", "workspace_display = left\n"] {
+            let (_, diagnostics) = Config::parse(bad);
+            assert_eq!(diagnostics[0].severity, Severity::Error);
+        }
+    }
+
+    #[test]
+    fn test_clock_format() {
+        assert_eq!(ClockFormat::default().strftime(), "%d/%m %H:%M");
+
+        let contents = "[items.clock]\nhours = 12\nweekday = true\nseconds = true\nlocale = \"de_DE.UTF-8\"\n";
+        let (config, diagnostics) = Config::parse_toml(contents);
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.clock.strftime(), "%a %d/%m %I:%M:%S %p");
+        assert!(config.clock.shows_seconds());
+        assert_eq!(config.clock.locale.as_deref(), Some("de_DE.UTF-8"));
+
+        let (config, _) = Config::parse_toml("[items.clock]\nformat = \"%H:%M\"\nseconds = true\n");
+        assert_eq!(config.clock.strftime(), "%H:%M");
+        assert!(!config.clock.shows_seconds());
+
+        let contents = "[items.clock]\nextra = [\"America/New_York\", \"Asia/Tokyo\"]\nextra_display = \"inline\"\n";
+        let (config, diagnostics) = Config::parse_toml(contents);
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.clock.extra, vec!["America/New_York", "Asia/Tokyo"]);
+        assert_eq!(config.clock.extra_display, ExtraClocks::Inline);
+        assert_eq!(config.clock.time_strftime(), "%H:%M");
+
+        for bad in ["hours = 13\n", "seconds = \"maybe\"\n", "extra = [\"Mars/Olympus_Mons\"]\n"] {
+            let (_, diagnostics) = Config::parse_toml(&format!("[items.clock]\n{}", bad));
+            assert_eq!(diagnostics[0].severity, Severity::Error);
+        }
+    }
+
+    #[test]
+    fn test_parse_valid() {
+        let (config, diagnostics) = Config::parse("# comment\nclock_interval = 5\n\nbrew_interval=60\n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.clock_interval, 5);
+        assert_eq!(config.brew_interval, 60);
+    }
+
+    #[test]
+    fn test_parse_reports_line_numbers() {
+        let contents = "clock_interval = 5\nbatery_interval = 10\nbrew_interval = soon\nteams_interval = 0\nnonsense\n";
+        let (_, diagnostics) = Config::parse(contents);
+        assert!(diagnostics[0].message.ends_with("did you mean `battery_interval`?"));
+        let lines: Vec<(usize, Severity)> = diagnostics.iter().map(|d| (d.line, d.severity)).collect();
+        assert_eq!(
+            lines,
+            vec![
+                (2, Severity::Warning),
+                (3, Severity::Error),
+                (4, Severity::Error),
+                (5, Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(edit_distance("brew_intervall", "brew_interval"), 1);
+        assert_eq!(suggest("brew_intervl", KNOWN_KEYS.iter().map(|(key, _)| *key)), Some("brew_interval"));
+        assert_eq!(suggest("icon_them", KNOWN_KEYS.iter().map(|(key, _)| *key)), Some("icon_theme"));
+        assert_eq!(suggest("colour", KNOWN_KEYS.iter().map(|(key, _)| *key)), None);
+        assert_eq!(suggest("bluetooth_interval", KNOWN_KEYS.iter().map(|(key, _)| *key)), None);
+    }
+
+    #[test]
+    fn test_parse_duplicate_key() {
+        let (config, diagnostics) = Config::parse("clock_interval = 5\nclock_interval = 10\n");
+        assert_eq!(config.clock_interval, 10);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 2);
+        assert!(diagnostics[0].message.contains("line 1"));
+    }
+
+    #[test]
+    fn test_load_from_file() {
+        let dir = std::env::temp_dir().join(format!("sketchybartender-load-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // Errors in settings are counted, and the file's other settings apply
+        let path = dir.join("errors.toml");
+        let contents = "strict = true\n\n[items.clock]\ninterval = 5\n\n[items.brew]\ninterval = \"soon\"\n";
+        fs::write(&path, contents).unwrap();
+        let (config, errors) = Config::load_from_file(&path, true).unwrap();
+        assert!(config.strict);
+        assert_eq!((config.clock_interval, errors), (5, 1));
+
+        // TOML that doesn't parse gives no settings: the defaults and one error when recovering
+        let path = dir.join("broken.toml");
+        fs::write(&path, "strict = false\n\n[items.clock\ninterval = 5\n").unwrap();
+        assert!(Config::load_from_file(&path, false).unwrap_err().contains("line 3, column 13"));
+        assert_eq!(Config::load_from_file(&path, true).unwrap(), (Config::default(), 1));
+        assert_eq!(Config::validate_file(&path).unwrap().len(), 1);
+
+        // Unless its `strict = true` line can still be read
+        fs::write(&path, "strict = true\n\n[items.clock\ninterval = 5\n").unwrap();
+        assert!(Config::load_from_file(&path, true).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,81 @@
+//! Menu bar apps mirrored into the bar, from `[aliases.<name>]` tables
+
+use std::fmt;
+
+use super::custom::{ItemPosition, BUILTIN_ITEMS};
+use super::suggest;
+
+/// Keys an alias accepts
+const ALIAS_KEYS: &[&str] = &["source", "position"];
+
+/// A menu bar item shown in the bar through a sketchybar alias
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AliasItem {
+    /// Owner and name of the menu bar item, e.g. `Control Center,WiFi`
+    pub source: String,
+    pub position: ItemPosition,
+}
+
+impl AliasItem {
+    /// Set a key, validating its value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "source" => self.source = value.to_string(),
+            "position" => self.position = value.parse()?,
+            _ => {
+                return Err(match suggest(key, ALIAS_KEYS.iter().copied()) {
+                    Some(suggestion) => format!("Unknown alias key {}; did you mean {}?", key, suggestion),
+                    None => format!("Unknown alias key {} (expected one of {})", key, ALIAS_KEYS.join(", ")),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AliasItem {
+    /// TOML lines for the alias's table
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "source = {}", toml::Value::String(self.source.clone()))?;
+        writeln!(f, "position = \"{}\"", self.position)
+    }
+}
+
+/// Check an alias's name and that it names a menu bar item
+pub(super) fn validate(name: &str, alias: &AliasItem) -> Result<(), String> {
+    if BUILTIN_ITEMS.contains(&name) || name.starts_with("workspace.") {
+        return Err(format!("alias {} has the name of a built-in item", name));
+    }
+    if name.chars().any(char::is_whitespace) {
+        return Err(format!("alias name {:?} can't contain spaces", name));
+    }
+    if alias.source.is_empty() {
+        return Err(format!("alias {} needs a source like \"Control Center,WiFi\"", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_item() {
+        let mut alias = AliasItem::default();
+        alias.set("source", "Control Center,WiFi").unwrap();
+        alias.set("position", "left").unwrap();
+        assert_eq!(alias.to_string(), "source = \"Control Center,WiFi\"\nposition = \"left\"\n");
+
+        assert!(alias.set("position", "top").is_err());
+        assert!(alias.set("sorce", "Clock").unwrap_err().contains("did you mean source"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let alias = AliasItem { source: "Control Center,Bluetooth".to_string(), ..AliasItem::default() };
+        assert!(validate("bluetooth", &alias).is_ok());
+        assert!(validate("wifi", &alias).is_err());
+        assert!(validate("my bluetooth", &alias).is_err());
+        assert!(validate("bluetooth", &AliasItem::default()).is_err());
+    }
+}
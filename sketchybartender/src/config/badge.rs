@@ -0,0 +1,93 @@
+//! Settings for the Dock badge item from `[items.teams]`
+//!
+//! The item counts the badge on an app's Dock icon, so it works for any app
+//! that badges its icon: new Teams (`MSTeams`), classic Teams (`Teams`), or
+//! other chat apps such as Slack or Webex.
+
+/// Which app the badge item watches and how it is drawn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadgeAppConfig {
+    /// Process name that shows the app is running, matched exactly by `pgrep -x`
+    pub process: String,
+    /// Name of the app's icon in the Dock, whose badge holds the count
+    pub dock_label: String,
+    /// Icon color while running without notifications
+    pub color: String,
+    /// Icon and border color while there are notifications
+    pub badge_color: String,
+    /// Icon color while the app isn't running
+    pub inactive_color: String,
+    /// Border color without notifications
+    pub border_color: String,
+    /// Seconds after a click at which to check the badge again
+    pub click_refresh: Vec<u64>,
+}
+
+impl Default for BadgeAppConfig {
+    fn default() -> Self {
+        Self {
+            process: "MSTeams".to_string(),
+            dock_label: "Microsoft Teams".to_string(),
+            color: "0xffffffff".to_string(),
+            badge_color: "0xfffabd2f".to_string(),
+            inactive_color: "0xff3c3836".to_string(),
+            border_color: "0xff2a2c3a".to_string(),
+            click_refresh: vec![1, 3, 6, 10],
+        }
+    }
+}
+
+impl BadgeAppConfig {
+    /// How long to wait before each re-check after a click
+    ///
+    /// `click_refresh` holds times since the click, so each wait is the gap to the previous one.
+    pub fn click_delays(&self) -> Vec<u64> {
+        let mut previous = 0;
+        self.click_refresh
+            .iter()
+            .map(|at| {
+                let delay = at - previous;
+                previous = *at;
+                delay
+            })
+            .collect()
+    }
+}
+
+/// Parse a non-empty value that is used as-is
+pub(super) fn parse_name(key: &str, value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err(format!("{} can't be empty", key));
+    }
+    Ok(value.to_string())
+}
+
+/// Parse the click refresh schedule, ascending seconds after the click
+pub(super) fn parse_schedule(key: &str, schedule: Vec<u64>) -> Result<Vec<u64>, String> {
+    if schedule.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(format!("{} must be in ascending order, found {:?}", key, schedule));
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_click_delays() {
+        assert_eq!(BadgeAppConfig::default().click_delays(), vec![1, 2, 3, 4]);
+
+        let none = BadgeAppConfig { click_refresh: Vec::new(), ..BadgeAppConfig::default() };
+        assert!(none.click_delays().is_empty());
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        assert_eq!(parse_schedule("s", vec![2, 5, 30]).unwrap(), vec![2, 5, 30]);
+        assert_eq!(parse_schedule("s", Vec::new()).unwrap(), Vec::<u64>::new());
+        assert!(parse_schedule("s", vec![5, 2]).is_err());
+        assert!(parse_schedule("s", vec![2, 2]).is_err());
+        assert!(parse_name("p", "").is_err());
+    }
+}
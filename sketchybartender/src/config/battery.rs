@@ -0,0 +1,259 @@
+//! Battery icon breakpoints, alert levels and colors from `[items.battery]`
+
+use std::fmt;
+use std::str::FromStr;
+
+/// How urgently the battery needs attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BatteryLevel {
+    /// On AC power
+    Charging,
+    /// Above the warning level
+    Normal,
+    /// At or below the warning level
+    Warning,
+    /// At or below the critical level
+    Critical,
+}
+
+impl fmt::Display for BatteryLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BatteryLevel::Charging => "charging",
+            BatteryLevel::Normal => "normal",
+            BatteryLevel::Warning => "warning",
+            BatteryLevel::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+impl BatteryLevel {
+    /// Whether moving here from `previous` should raise a low-battery alert
+    ///
+    /// Alerts fire when the battery drops into the warning or critical level,
+    /// not on every refresh while it stays there.
+    pub fn alerts_after(self, previous: Option<BatteryLevel>) -> bool {
+        self >= BatteryLevel::Warning && previous.is_none_or(|previous| previous < self)
+    }
+}
+
+/// The modifier key, as sketchybar passes it in `$MODIFIER`, that gives a click its other action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClickModifier {
+    /// Every click does the plain click's action
+    Off,
+    Shift,
+    Ctrl,
+    #[default]
+    Alt,
+    Cmd,
+}
+
+impl ClickModifier {
+    /// Whether a click with `modifier` held, if any, is meant for this
+    pub fn matches(self, modifier: Option<&str>) -> bool {
+        self != ClickModifier::Off && modifier == Some(self.to_string().as_str())
+    }
+}
+
+impl FromStr for ClickModifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ClickModifier::Off),
+            "shift" => Ok(ClickModifier::Shift),
+            "ctrl" => Ok(ClickModifier::Ctrl),
+            "alt" => Ok(ClickModifier::Alt),
+            "cmd" => Ok(ClickModifier::Cmd),
+            _ => Err(format!("Invalid click modifier: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ClickModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ClickModifier::Off => "off",
+            ClickModifier::Shift => "shift",
+            ClickModifier::Ctrl => "ctrl",
+            ClickModifier::Alt => "alt",
+            ClickModifier::Cmd => "cmd",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the battery item is drawn and when it alerts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatteryConfig {
+    /// Lowest percentage for the full, three-quarter, half and quarter icons, descending
+    pub thresholds: [u8; 4],
+    /// Percentage at or below which the warning color is used, 0 to disable
+    pub warning: u8,
+    /// Percentage at or below which the critical color is used, 0 to disable
+    pub critical: u8,
+    pub normal_color: String,
+    pub charging_color: String,
+    pub warning_color: String,
+    pub critical_color: String,
+    /// Blink the item when the battery drops to the warning or critical level
+    pub blink: bool,
+    /// Follow the percentage with the time to empty or full, when macOS has an estimate (default: true)
+    pub time_remaining: bool,
+    /// Icon color while Low Power Mode is on, unless the battery is low
+    pub low_power_color: String,
+    /// Held while clicking the item to toggle Low Power Mode instead of opening the popup
+    pub low_power_modifier: ClickModifier,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            thresholds: [90, 70, 40, 10],
+            warning: 20,
+            critical: 10,
+            normal_color: "0xffffffff".to_string(),
+            charging_color: "0xfffabd2f".to_string(),
+            warning_color: "0xfffe8019".to_string(),
+            critical_color: "0xfffb4934".to_string(),
+            blink: false,
+            time_remaining: true,
+            low_power_color: "0xff83a598".to_string(),
+            low_power_modifier: ClickModifier::Alt,
+        }
+    }
+}
+
+impl BatteryConfig {
+    /// The alert level for a charge, where charging always wins
+    pub fn level(&self, percentage: u8, is_charging: bool) -> BatteryLevel {
+        if is_charging {
+            BatteryLevel::Charging
+        } else if self.critical > 0 && percentage <= self.critical {
+            BatteryLevel::Critical
+        } else if self.warning > 0 && percentage <= self.warning {
+            BatteryLevel::Warning
+        } else {
+            BatteryLevel::Normal
+        }
+    }
+
+    /// Color for an alert level
+    pub fn color(&self, level: BatteryLevel) -> &str {
+        match level {
+            BatteryLevel::Charging => &self.charging_color,
+            BatteryLevel::Normal => &self.normal_color,
+            BatteryLevel::Warning => &self.warning_color,
+            BatteryLevel::Critical => &self.critical_color,
+        }
+    }
+
+    /// Icon color for an alert level, tinted while Low Power Mode is on unless the battery is low
+    pub fn icon_color(&self, level: BatteryLevel, low_power: bool) -> &str {
+        if low_power && level < BatteryLevel::Warning {
+            &self.low_power_color
+        } else {
+            self.color(level)
+        }
+    }
+
+    /// Number of filled quarters shown by the icon, from 4 (full) to 0 (empty)
+    pub fn quarters(&self, percentage: u8) -> usize {
+        self.thresholds.iter().filter(|threshold| percentage >= **threshold).count()
+    }
+}
+
+/// Parse a percentage from 0 to 100
+pub(super) fn parse_percentage(key: &str, value: u64) -> Result<u8, String> {
+    u8::try_from(value)
+        .ok()
+        .filter(|p| *p <= 100)
+        .ok_or_else(|| format!("Invalid value for {}: {} (expected a percentage from 0 to 100)", key, value))
+}
+
+/// Parse the modifier key of a modifier click
+pub(super) fn parse_modifier(key: &str, value: &str) -> Result<ClickModifier, String> {
+    value
+        .parse()
+        .map_err(|_: String| format!("Invalid value for {}: {} (expected shift, ctrl, alt, cmd or off)", key, value))
+}
+
+/// Parse the four icon breakpoints, which must be descending percentages
+pub(super) fn parse_thresholds(key: &str, value: Vec<u64>) -> Result<[u8; 4], String> {
+    let thresholds = value
+        .into_iter()
+        .map(|p| parse_percentage(key, p))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    let thresholds: [u8; 4] = thresholds
+        .try_into()
+        .map_err(|_| format!("{} takes four percentages, e.g. 90, 70, 40, 10", key))?;
+    if thresholds.windows(2).any(|pair| pair[0] <= pair[1]) {
+        return Err(format!("{} must be in descending order, found {:?}", key, thresholds));
+    }
+    Ok(thresholds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let config = BatteryConfig::default();
+        assert_eq!(config.level(50, false), BatteryLevel::Normal);
+        assert_eq!(config.level(20, false), BatteryLevel::Warning);
+        assert_eq!(config.level(10, false), BatteryLevel::Critical);
+        assert_eq!(config.level(5, true), BatteryLevel::Charging);
+        assert_eq!(config.color(BatteryLevel::Critical), "0xfffb4934");
+
+        let disabled = BatteryConfig { warning: 0, critical: 0, ..BatteryConfig::default() };
+        assert_eq!(disabled.level(0, false), BatteryLevel::Normal);
+    }
+
+    #[test]
+    fn test_low_power() {
+        let config = BatteryConfig::default();
+        assert_eq!(config.icon_color(BatteryLevel::Normal, true), "0xff83a598");
+        assert_eq!(config.icon_color(BatteryLevel::Normal, false), "0xffffffff");
+        assert_eq!(config.icon_color(BatteryLevel::Critical, true), "0xfffb4934");
+
+        assert!(ClickModifier::Alt.matches(Some("alt")));
+        assert!(!ClickModifier::Alt.matches(None));
+        assert!(!ClickModifier::Off.matches(Some("off")));
+        assert_eq!("cmd".parse::<ClickModifier>(), Ok(ClickModifier::Cmd));
+        assert!("option".parse::<ClickModifier>().is_err());
+    }
+
+    #[test]
+    fn test_alerts() {
+        use BatteryLevel::*;
+        assert!(Warning.alerts_after(Some(Normal)));
+        assert!(Critical.alerts_after(Some(Warning)));
+        assert!(Critical.alerts_after(None));
+        assert!(!Warning.alerts_after(Some(Warning)));
+        assert!(!Warning.alerts_after(Some(Critical)));
+        assert!(!Normal.alerts_after(Some(Charging)));
+    }
+
+    #[test]
+    fn test_quarters() {
+        let config = BatteryConfig::default();
+        assert_eq!(config.quarters(100), 4);
+        assert_eq!(config.quarters(89), 3);
+        assert_eq!(config.quarters(40), 2);
+        assert_eq!(config.quarters(9), 0);
+    }
+
+    #[test]
+    fn test_parse_thresholds() {
+        assert_eq!(parse_thresholds("t", vec![80, 60, 30, 5]).unwrap(), [80, 60, 30, 5]);
+        assert!(parse_thresholds("t", vec![80, 60, 30]).is_err());
+        assert!(parse_thresholds("t", vec![10, 40, 70, 90]).is_err());
+        assert!(parse_thresholds("t", vec![150, 60, 30, 5]).is_err());
+        assert!(parse_percentage("p", 101).is_err());
+        assert!(parse_percentage("p", 256).is_err());
+    }
+}
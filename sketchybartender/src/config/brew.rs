@@ -0,0 +1,100 @@
+//! Homebrew settings from `[items.brew]`
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Where Homebrew installs itself on Apple Silicon and on Intel Macs
+const BREW_PATHS: &[&str] = &["/opt/homebrew/bin/brew", "/usr/local/bin/brew"];
+
+/// What clicking the brew item does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BrewClick {
+    /// Run `brew upgrade` right away (default)
+    #[default]
+    Upgrade,
+    /// Ask in a dialog before upgrading
+    Confirm,
+    /// Only refresh the outdated count
+    Refresh,
+    /// Open a popup listing the outdated packages, with a row to upgrade them
+    Popup,
+}
+
+impl FromStr for BrewClick {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upgrade" => Ok(BrewClick::Upgrade),
+            "confirm" => Ok(BrewClick::Confirm),
+            "refresh" => Ok(BrewClick::Refresh),
+            "popup" => Ok(BrewClick::Popup),
+            _ => Err(format!(
+                "Invalid value for brew_click: {} (expected upgrade, confirm, refresh or popup)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BrewClick {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BrewClick::Upgrade => "upgrade",
+            BrewClick::Confirm => "confirm",
+            BrewClick::Refresh => "refresh",
+            BrewClick::Popup => "popup",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the brew item checks for and installs updates
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BrewConfig {
+    /// Path of the brew binary; `None` looks in the Apple Silicon and Intel prefixes
+    pub path: Option<String>,
+    /// Leave pinned formulae out of the outdated count
+    pub exclude_pinned: bool,
+    /// Include casks that update themselves (`--greedy`)
+    pub greedy: bool,
+    /// Run `brew update` before checking for outdated packages
+    pub update: bool,
+    /// What clicking the item does
+    pub click: BrewClick,
+    /// Up to this many seconds are added at random to each interval, so checks don't run together
+    pub jitter: u64,
+}
+
+impl BrewConfig {
+    /// The brew binary to run
+    pub fn binary(&self) -> String {
+        match &self.path {
+            Some(path) => path.clone(),
+            None => BREW_PATHS
+                .iter()
+                .find(|path| Path::new(path).exists())
+                .map_or_else(|| "brew".to_string(), |path| path.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brew_click() {
+        assert_eq!("confirm".parse::<BrewClick>(), Ok(BrewClick::Confirm));
+        assert_eq!(BrewClick::Refresh.to_string(), "refresh");
+        assert_eq!("popup".parse::<BrewClick>(), Ok(BrewClick::Popup));
+        assert!("never".parse::<BrewClick>().is_err());
+    }
+
+    #[test]
+    fn test_binary() {
+        let config = BrewConfig { path: Some("/opt/brew/bin/brew".to_string()), ..BrewConfig::default() };
+        assert_eq!(config.binary(), "/opt/brew/bin/brew");
+    }
+}
@@ -0,0 +1,48 @@
+//! Calendars and colors of the calendar item from `[items.calendar]`
+
+use super::level::{Level, LevelColors};
+
+/// Which calendars the calendar item looks in and how it counts down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarConfig {
+    /// Calendars to take events from, by name; empty for all of them
+    pub names: Vec<String>,
+    /// Minutes before the start at which the label turns `soon_color`, 0 to never (default: 10)
+    pub soon: usize,
+    /// Label colors: the normal one until then, the warning one from `soon` minutes before the start
+    pub colors: LevelColors,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            soon: 10,
+            colors: LevelColors::default(),
+        }
+    }
+}
+
+impl CalendarConfig {
+    /// Label color for an event starting in `minutes`, negative once it has started
+    pub fn color(&self, minutes: i64) -> &str {
+        let soon = self.soon > 0 && minutes <= self.soon as i64;
+        self.colors.color(if soon { Level::Warning } else { Level::Normal })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        let config = CalendarConfig::default();
+        assert_eq!(config.color(45), "0xffffffff");
+        assert_eq!(config.color(10), "0xfffabd2f");
+        assert_eq!(config.color(-5), "0xfffabd2f");
+
+        let never = CalendarConfig { soon: 0, ..CalendarConfig::default() };
+        assert_eq!(never.color(1), "0xffffffff");
+    }
+}
@@ -0,0 +1,132 @@
+//! Coins, currency and price alerts of the crypto item from `[items.crypto]`
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A price bound on one coin, like `bitcoin > 80000`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CryptoAlert {
+    /// CoinGecko ID of the coin, e.g. `bitcoin`
+    pub coin: String,
+    /// Whether the alert is for rising above the bound rather than falling below it
+    pub above: bool,
+    pub bound: f64,
+}
+
+impl CryptoAlert {
+    /// Whether `price` is past the bound
+    pub fn breached(&self, price: f64) -> bool {
+        if self.above {
+            price > self.bound
+        } else {
+            price < self.bound
+        }
+    }
+}
+
+impl FromStr for CryptoAlert {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid crypto alert: {} (expected e.g. \"bitcoin > 80000\")", s);
+        let (split, above) = match (s.find('>'), s.find('<')) {
+            (Some(index), None) => (index, true),
+            (None, Some(index)) => (index, false),
+            _ => return Err(invalid()),
+        };
+        let coin = s[..split].trim();
+        let bound = s[split + 1..].trim().parse::<f64>().ok().filter(|bound| bound.is_finite() && *bound >= 0.0);
+        match bound {
+            Some(bound) if is_coin(coin) => Ok(CryptoAlert { coin: coin.to_string(), above, bound }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for CryptoAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.coin, if self.above { '>' } else { '<' }, self.bound)
+    }
+}
+
+/// Whether a name looks like a CoinGecko ID, e.g. `bitcoin` or `shiba-inu`
+fn is_coin(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-')
+}
+
+/// Which prices the crypto item shows and when it flashes
+#[derive(Debug, Clone, PartialEq)]
+pub struct CryptoConfig {
+    /// CoinGecko IDs of the coins, in the order the label shows them; none leaves the item off
+    pub coins: Vec<String>,
+    /// Currency the prices are in, e.g. `usd` or `eur` (default: usd)
+    pub currency: String,
+    /// Bounds that flash the item when a price crosses them
+    pub alerts: Vec<CryptoAlert>,
+    /// Label color while a price is past one of the bounds
+    pub alert_color: String,
+}
+
+impl Default for CryptoConfig {
+    fn default() -> Self {
+        Self {
+            coins: Vec::new(),
+            currency: "usd".to_string(),
+            alerts: Vec::new(),
+            alert_color: "0xfffb4934".to_string(),
+        }
+    }
+}
+
+/// Parse a list of CoinGecko IDs
+pub(super) fn parse_coins(key: &str, coins: Vec<String>) -> Result<Vec<String>, String> {
+    match coins.iter().find(|coin| !is_coin(coin)) {
+        Some(coin) => Err(format!("Invalid coin for {}: {} (expected a CoinGecko ID like bitcoin)", key, coin)),
+        None => Ok(coins),
+    }
+}
+
+/// Parse a currency code like `usd`, in any case
+pub(super) fn parse_currency(key: &str, value: &str) -> Result<String, String> {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(value.to_ascii_lowercase())
+    } else {
+        Err(format!("Invalid value for {}: {} (expected a currency code like usd)", key, value))
+    }
+}
+
+/// Parse a list of alerts like `bitcoin > 80000`
+pub(super) fn parse_alerts(alerts: Vec<String>) -> Result<Vec<CryptoAlert>, String> {
+    alerts.iter().map(|alert| alert.parse()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_alert() {
+        let alert: CryptoAlert = "bitcoin > 80000".parse().unwrap();
+        assert_eq!(alert, CryptoAlert { coin: "bitcoin".to_string(), above: true, bound: 80000.0 });
+        assert!(alert.breached(80000.5) && !alert.breached(80000.0));
+        let below: CryptoAlert = "shiba-inu<0.00001".parse().unwrap();
+        assert!(!below.above && below.breached(0.000009));
+        assert_eq!(below.to_string(), "shiba-inu < 0.00001");
+
+        for bad in ["bitcoin", "bitcoin >< 5", "Bitcoin > 5", "> 5", "bitcoin > lots", "bitcoin < -1"] {
+            assert!(bad.parse::<CryptoAlert>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_lists() {
+        let list = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+        assert_eq!(parse_coins("coins", list(&["bitcoin", "ethereum"])).unwrap(), ["bitcoin", "ethereum"]);
+        assert!(parse_coins("coins", Vec::new()).unwrap().is_empty());
+        assert!(parse_coins("coins", list(&["BTC"])).unwrap_err().contains("CoinGecko"));
+        assert_eq!(parse_currency("currency", "EUR").unwrap(), "eur");
+        assert!(parse_currency("currency", "€").is_err());
+        assert_eq!(parse_alerts(list(&["bitcoin > 1", "ethereum < 2"])).unwrap().len(), 2);
+        assert!(parse_alerts(Vec::new()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! User-defined items from `[custom.<name>]` tables
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::expand::include_path;
+use super::{parse_interval, parse_optional, parse_seconds, suggest, Config};
+
+/// Keys a custom item accepts
+const CUSTOM_KEYS: &[&str] = &["command", "script", "icon", "icon_command", "interval", "jitter", "position", "click"];
+
+/// Items the daemon renders itself, which custom items can't replace
+pub const BUILTIN_ITEMS: &[&str] = &[
+    "clock", "calendar", "zoom", "weather", "sun", "moon", "crypto", "exchange", "feeds", "reviews", "gitlab", "jira",
+    "linear", "battery", "volume", "audio_output", "wifi", "network", "cpu", "top_process", "ram", "disk", "load",
+    "thermal", "fan", "focus_mode", "appearance", "brightness", "brew", "teams", "front_app", "workspaces",
+    "battery_graph", "cpu_graph", "disk_graph",
+];
+
+/// Where sketchybar places an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemPosition {
+    Left,
+    /// Right side of the bar (default)
+    #[default]
+    Right,
+    Center,
+    /// Left of the notch
+    Q,
+    /// Right of the notch
+    E,
+}
+
+impl FromStr for ItemPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(ItemPosition::Left),
+            "right" => Ok(ItemPosition::Right),
+            "center" => Ok(ItemPosition::Center),
+            "q" => Ok(ItemPosition::Q),
+            "e" => Ok(ItemPosition::E),
+            _ => Err(format!("Invalid position: {} (expected left, right, center, q or e)", s)),
+        }
+    }
+}
+
+impl fmt::Display for ItemPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ItemPosition::Left => "left",
+            ItemPosition::Right => "right",
+            ItemPosition::Center => "center",
+            ItemPosition::Q => "q",
+            ItemPosition::E => "e",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An item declared in the config, whose label comes from a shell command or a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomItem {
+    /// Shell command whose output becomes the label
+    pub command: String,
+    /// Rhai script returning the label, or a map with the label, icon, visibility and style
+    pub script: Option<String>,
+    /// Static icon glyph
+    pub icon: Option<String>,
+    /// Shell command whose output becomes the icon, replacing `icon`
+    pub icon_command: Option<String>,
+    /// Seconds between refreshes (default: 60)
+    pub interval: u64,
+    /// Up to this many seconds added at random to each interval (default: 0)
+    pub jitter: u64,
+    pub position: ItemPosition,
+    /// Shell command run when the item is clicked, after which the item refreshes
+    pub click: Option<String>,
+}
+
+impl Default for CustomItem {
+    fn default() -> Self {
+        Self {
+            command: String::new(),
+            script: None,
+            icon: None,
+            icon_command: None,
+            interval: 60,
+            jitter: 0,
+            position: ItemPosition::default(),
+            click: None,
+        }
+    }
+}
+
+impl CustomItem {
+    /// Set a key, validating its value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "command" => self.command = value.to_string(),
+            "script" => self.script = parse_optional(value),
+            "icon" => self.icon = parse_optional(value),
+            "icon_command" => self.icon_command = parse_optional(value),
+            "interval" => self.interval = parse_interval(key, parse_seconds(key, value)?)?,
+            "jitter" => self.jitter = parse_seconds(key, value)?,
+            "position" => self.position = value.parse()?,
+            "click" => self.click = parse_optional(value),
+            _ => {
+                return Err(match suggest(key, CUSTOM_KEYS.iter().copied()) {
+                    Some(suggestion) => format!("Unknown custom item key {}; did you mean {}?", key, suggestion),
+                    None => format!("Unknown custom item key {} (expected one of {})", key, CUSTOM_KEYS.join(", ")),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the script is: `~/` is the home directory, relative paths start at the config directory
+    pub fn script_path(&self) -> Option<PathBuf> {
+        self.script.as_deref().map(|script| include_path(script, &Config::get_config_dir()))
+    }
+}
+
+impl fmt::Display for CustomItem {
+    /// TOML lines for the item's table, leaving out unset optional keys
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quoted = |s: &str| toml::Value::String(s.to_string());
+
+        if !self.command.is_empty() {
+            writeln!(f, "command = {}", quoted(&self.command))?;
+        }
+        if let Some(script) = &self.script {
+            writeln!(f, "script = {}", quoted(script))?;
+        }
+        if let Some(icon) = &self.icon {
+            writeln!(f, "icon = {}", quoted(icon))?;
+        }
+        if let Some(icon_command) = &self.icon_command {
+            writeln!(f, "icon_command = {}", quoted(icon_command))?;
+        }
+        writeln!(f, "interval = {}", self.interval)?;
+        if self.jitter > 0 {
+            writeln!(f, "jitter = {}", self.jitter)?;
+        }
+        writeln!(f, "position = \"{}\"", self.position)?;
+        if let Some(click) = &self.click {
+            writeln!(f, "click = {}", quoted(click))?;
+        }
+        Ok(())
+    }
+}
+
+/// Check a custom item's name and that it has either a command or a script
+pub(super) fn validate(name: &str, item: &CustomItem) -> Result<(), String> {
+    if BUILTIN_ITEMS.contains(&name) || name.starts_with("workspace.") {
+        return Err(format!("custom item {} has the name of a built-in item", name));
+    }
+    if name.chars().any(char::is_whitespace) {
+        return Err(format!("custom item name {:?} can't contain spaces", name));
+    }
+    match (item.command.is_empty(), &item.script) {
+        (true, None) => Err(format!("custom item {} needs a command or a script", name)),
+        (false, Some(_)) => Err(format!("custom item {} has both a command and a script", name)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_item() {
+        let mut item = CustomItem::default();
+        item.set("command", "curl -s 'wttr.in?format=%t'").unwrap();
+        item.set("position", "left").unwrap();
+        item.set("interval", "900").unwrap();
+        assert_eq!(item.position, ItemPosition::Left);
+        assert_eq!(
+            item.to_string(),
+            "command = \"curl -s 'wttr.in?format=%t'\"\ninterval = 900\nposition = \"left\"\n"
+        );
+
+        item.set("jitter", "30").unwrap();
+        assert!(item.to_string().contains("\njitter = 30\n"));
+
+        assert!(item.set("position", "top").is_err());
+        assert!(item.set("interval", "0").is_err());
+        assert!(item.set("clik", "open -a Calendar").unwrap_err().contains("did you mean click"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let item = CustomItem { command: "date".to_string(), ..CustomItem::default() };
+        assert!(validate("ip", &item).is_ok());
+        assert!(validate("clock", &item).is_err());
+        assert!(validate("workspace.1", &item).is_err());
+        assert!(validate("my item", &item).is_err());
+        assert!(validate("ip", &CustomItem::default()).is_err());
+
+        let scripted = CustomItem { script: Some("battery.rhai".to_string()), ..CustomItem::default() };
+        assert!(validate("battery_time", &scripted).is_ok());
+        assert!(validate("battery_time", &CustomItem { command: "date".to_string(), ..scripted }).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! Currency pair of the exchange item from `[items.exchange]`
+
+use std::fmt;
+use std::str::FromStr;
+
+/// What one unit of `base` costs in `quote`, as in `EUR/USD`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrencyPair {
+    /// ISO 4217 code, e.g. `EUR`
+    pub base: String,
+    pub quote: String,
+}
+
+impl FromStr for CurrencyPair {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = |code: &str| {
+            let code = code.trim();
+            Some(code.to_ascii_uppercase()).filter(|_| code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()))
+        };
+        match s.split_once('/').map(|(base, quote)| (code(base), code(quote))) {
+            Some((Some(base), Some(quote))) if base != quote => Ok(CurrencyPair { base, quote }),
+            _ => Err(format!("Invalid value for exchange_pair: {} (expected two currency codes like EUR/USD)", s)),
+        }
+    }
+}
+
+impl fmt::Display for CurrencyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pair() {
+        let pair: CurrencyPair = "gbp / chf".parse().unwrap();
+        assert_eq!(pair.to_string(), "GBP/CHF");
+        let eur_usd = CurrencyPair { base: "EUR".to_string(), quote: "USD".to_string() };
+        assert_eq!("EUR/USD".parse::<CurrencyPair>().unwrap(), eur_usd);
+        for bad in ["EURUSD", "EUR/", "EUR/US", "EUR/EUR", "€/$"] {
+            assert!(bad.parse::<CurrencyPair>().is_err(), "{}", bad);
+        }
+    }
+}
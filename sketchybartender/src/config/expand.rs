@@ -0,0 +1,184 @@
+//! `${VAR}` expansion and `include` paths for the TOML config
+//!
+//! Expansion happens on string values after parsing, so comments and keys are
+//! never touched. `${VAR:-fallback}` uses the fallback when VAR is unset, and
+//! `$${` produces a literal `${`. `${secret:NAME}` reads NAME from the Keychain
+//! instead of the environment.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use crate::secrets;
+
+/// Expand `${VAR}` references in a string
+pub(super) fn expand_env(s: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(escaped) = after.strip_prefix("${") {
+            result.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = after.strip_prefix('{') {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| format!("unterminated `${{` in \"{}\"", s))?;
+            let (name, fallback) = match reference[..end].split_once(":-") {
+                Some((name, fallback)) => (name, Some(fallback)),
+                None => (&reference[..end], None),
+            };
+
+            let secret = name.strip_prefix("secret:");
+            let valid = match secret {
+                Some(secret) => secrets::validate_name(secret).is_ok(),
+                None => !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            };
+            if !valid {
+                return Err(format!("invalid variable name `{}` in \"{}\"", name, s));
+            }
+
+            match (lookup(name), fallback, secret) {
+                (Some(value), _, _) => result.push_str(&value),
+                (None, Some(fallback), _) => result.push_str(fallback),
+                (None, None, Some(secret)) => {
+                    return Err(format!(
+                        "secret {} is not in the keychain (store it with `sketchycli secret set {}`)",
+                        secret, secret
+                    ))
+                }
+                (None, None, None) => return Err(format!("environment variable {} is not set", name)),
+            }
+            rest = &reference[end + 1..];
+        } else {
+            result.push('$');
+            rest = after;
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Expand `${VAR}` in every string of a value, collecting `(variable reference, error)` pairs
+pub(super) fn expand_value(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+    match value {
+        Value::String(s) => match expand_env(s, lookup) {
+            Ok(expanded) => {
+                *s = expanded;
+                Vec::new()
+            }
+            Err(e) => {
+                // Point diagnostics at the first reference in the original text
+                let reference = s.find("${").map_or_else(|| s.clone(), |i| s[i..].to_string());
+                vec![(reference, e)]
+            }
+        },
+        Value::Array(items) => items.iter_mut().flat_map(|item| expand_value(item, lookup)).collect(),
+        Value::Table(table) => expand_table(table, lookup),
+        _ => Vec::new(),
+    }
+}
+
+/// Expand `${VAR}` in every string of a table, dropping strings that can't be expanded
+///
+/// Dropped values leave the setting at its default instead of being applied unexpanded.
+pub(super) fn expand_table(table: &mut toml::Table, lookup: &dyn Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+    let mut errors = Vec::new();
+    let mut failed = Vec::new();
+
+    for (key, value) in table.iter_mut() {
+        let value_errors = expand_value(value, lookup);
+        if value.is_str() && !value_errors.is_empty() {
+            failed.push(key.clone());
+        }
+        errors.extend(value_errors);
+    }
+
+    for key in failed {
+        table.remove(&key);
+    }
+    errors
+}
+
+/// A value with `${VAR}` expanded from the environment and Keychain, or why it can't be
+pub(super) fn expanded(mut value: Value) -> Result<Value, String> {
+    match expand_value(&mut value, &env_lookup).into_iter().next() {
+        Some((_, message)) => Err(message),
+        None => Ok(value),
+    }
+}
+
+/// Look up an environment variable, or a Keychain secret for `secret:NAME`, for expansion
+pub(super) fn env_lookup(name: &str) -> Option<String> {
+    match name.strip_prefix("secret:") {
+        Some(secret) => secrets::get(secret).ok(),
+        None => env::var(name).ok(),
+    }
+}
+
+/// Resolve an include path: `~/` is the home directory, relative paths start at `dir`
+pub(super) fn include_path(path: &str, dir: &Path) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => dir.join(path),
+    }
+}
+
+/// The paths listed by an `include` key, which takes a string or a list of strings
+pub(super) fn include_list(value: &Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::String(path) => Ok(vec![path.clone()]),
+        Value::Array(paths) => paths
+            .iter()
+            .map(|path| path.as_str().map(str::to_string))
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(|| "include takes a path or a list of paths".to_string()),
+        _ => Err("include takes a path or a list of paths".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "TOKEN" => Some("s3cret".to_string()),
+            "HOST" => Some("work-mbp".to_string()),
+            "secret:github" => Some("ghp_abc".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_env() {
+        assert_eq!(expand_env("Bearer ${TOKEN}", &lookup).unwrap(), "Bearer s3cret");
+        assert_eq!(expand_env("${HOST}/${TOKEN}", &lookup).unwrap(), "work-mbp/s3cret");
+        assert_eq!(expand_env("${CITY:-Oslo}", &lookup).unwrap(), "Oslo");
+        assert_eq!(expand_env("$${TOKEN} costs $5", &lookup).unwrap(), "${TOKEN} costs $5");
+
+        assert_eq!(expand_env("Bearer ${secret:github}", &lookup).unwrap(), "Bearer ghp_abc");
+        assert_eq!(expand_env("${secret:jira:-none}", &lookup).unwrap(), "none");
+
+        assert!(expand_env("${MISSING}", &lookup).unwrap_err().contains("MISSING is not set"));
+        assert!(expand_env("${secret:jira}", &lookup).unwrap_err().contains("sketchycli secret set jira"));
+        assert!(expand_env("${secret:my token}", &lookup).unwrap_err().contains("invalid variable name"));
+        assert!(expand_env("${TOKEN", &lookup).unwrap_err().contains("unterminated"));
+        assert!(expand_env("${TO KEN}", &lookup).unwrap_err().contains("invalid variable name"));
+    }
+
+    #[test]
+    fn test_include_paths() {
+        let dir = Path::new("/etc/sketchybar");
+        assert_eq!(include_path("local.toml", dir), dir.join("local.toml"));
+        assert_eq!(include_path("/tmp/a.toml", dir), PathBuf::from("/tmp/a.toml"));
+
+        assert_eq!(include_list(&Value::String("a.toml".into())).unwrap(), vec!["a.toml"]);
+        assert!(include_list(&Value::Integer(1)).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Feed URLs and popup length of the feeds item from `[items.feeds]`
+
+/// Which feeds the feeds item checks for new entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedsConfig {
+    /// RSS or Atom feed URLs; none keeps the item hidden
+    pub urls: Vec<String>,
+    /// Entries listed in the popup, newest first (default: 10)
+    pub recent: usize,
+}
+
+impl Default for FeedsConfig {
+    fn default() -> Self {
+        Self { urls: Vec::new(), recent: 10 }
+    }
+}
+
+/// Parse a list of http or https URLs
+pub(super) fn parse_urls(key: &str, urls: Vec<String>) -> Result<Vec<String>, String> {
+    let web = |url: &&String| {
+        let rest = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"));
+        rest.is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace) && !rest.contains('"'))
+    };
+    match urls.iter().find(|url| !web(url)) {
+        Some(url) => Err(format!("Invalid URL for {}: {} (expected http:// or https://)", key, url)),
+        None => Ok(urls),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urls() {
+        let urls = vec!["https://blog.rust-lang.org/feed.xml".to_string(), "http://example.com/rss".to_string()];
+        assert_eq!(parse_urls("urls", urls.clone()).unwrap(), urls);
+        assert!(parse_urls("urls", Vec::new()).unwrap().is_empty());
+        for bad in ["example.com/rss", "https://", "ftp://example.com", "https://a b"] {
+            assert!(parse_urls("urls", vec![bad.to_string()]).unwrap_err().contains("http://"), "{}", bad);
+        }
+    }
+}
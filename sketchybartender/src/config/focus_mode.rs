@@ -0,0 +1,43 @@
+//! The Focus item's colors and toggle shortcut from `[items.focus_mode]`
+
+use super::level::{Level, LevelColors};
+
+/// How the focus_mode item is drawn and what a click runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FocusModeConfig {
+    /// Shortcut a click runs, one with a "Set Focus" action turning Do Not Disturb on or off
+    pub shortcut: String,
+    /// Name the active Focus in the label (default: true)
+    pub label: bool,
+    /// Icon colors: the normal one while no Focus is on, the warning one while one is
+    pub colors: LevelColors,
+}
+
+impl Default for FocusModeConfig {
+    fn default() -> Self {
+        Self {
+            shortcut: "Toggle Do Not Disturb".to_string(),
+            label: true,
+            colors: LevelColors::new("0x80ffffff", "0xffd3869b", "0xffd3869b"),
+        }
+    }
+}
+
+impl FocusModeConfig {
+    /// Icon color for whether a Focus is on
+    pub fn color(&self, active: bool) -> &str {
+        self.colors.color(if active { Level::Warning } else { Level::Normal })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        let config = FocusModeConfig::default();
+        assert_eq!(config.color(true), "0xffd3869b");
+        assert_eq!(config.color(false), "0x80ffffff");
+    }
+}
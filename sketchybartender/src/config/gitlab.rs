@@ -0,0 +1,22 @@
+//! Instance, token and failure color of the gitlab item from `[items.gitlab]`
+
+/// Where the gitlab item looks for merge requests and pipelines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitlabConfig {
+    /// Address of the GitLab instance, without a trailing slash (default: https://gitlab.com)
+    pub url: String,
+    /// Personal access token with the `read_api` scope, usually `${secret:gitlab}`; none keeps the item hidden
+    pub token: Option<String>,
+    /// Item color while a pipeline of one of your merge requests has failed
+    pub failed_color: String,
+}
+
+impl Default for GitlabConfig {
+    fn default() -> Self {
+        Self {
+            url: "https://gitlab.com".to_string(),
+            token: None,
+            failed_color: "0xfffb4934".to_string(),
+        }
+    }
+}
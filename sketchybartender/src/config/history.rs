@@ -0,0 +1,53 @@
+//! Graph item settings from `[history]`
+
+/// Metrics that can be shown as a graph, each in an item named `<metric>_graph`
+pub const GRAPH_METRICS: &[&str] = &["battery", "cpu", "disk"];
+
+/// Which metrics get a graph item, and how much of their history it shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryConfig {
+    /// Metrics shown as graph items (default: none)
+    pub graphs: Vec<String>,
+    /// Samples kept per metric, which is also the graph width in points (default: 60)
+    pub retention: usize,
+    /// Seconds between samples (default: 10)
+    pub interval: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self { graphs: Vec::new(), retention: 60, interval: 10 }
+    }
+}
+
+/// Parse a list of graphed metrics
+pub(super) fn parse_graphs(graphs: Vec<String>) -> Result<Vec<String>, String> {
+    match graphs.iter().find(|graph| !GRAPH_METRICS.contains(&graph.as_str())) {
+        Some(graph) => Err(format!("Unknown graph: {} (expected {})", graph, GRAPH_METRICS.join(", "))),
+        None => Ok(graphs),
+    }
+}
+
+/// Parse how many samples to keep; a graph needs at least two points
+pub(super) fn parse_retention(key: &str, value: u64) -> Result<usize, String> {
+    match usize::try_from(value) {
+        Ok(retention) if (2..=1000).contains(&retention) => Ok(retention),
+        _ => Err(format!("Invalid value for {}: {} (expected 2 to 1000 samples)", key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_graphs() {
+        let graphs = vec!["battery".to_string(), "cpu".to_string()];
+        assert_eq!(parse_graphs(graphs.clone()).unwrap(), graphs);
+        assert!(parse_graphs(Vec::new()).unwrap().is_empty());
+        assert!(parse_graphs(vec!["ram".to_string()]).unwrap_err().contains("Unknown graph: ram"));
+
+        assert_eq!(parse_retention("retention", 120), Ok(120));
+        assert!(parse_retention("retention", 1).is_err());
+    }
+}
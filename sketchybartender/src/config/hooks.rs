@@ -0,0 +1,54 @@
+//! User scripts run on daemon events, from `[hooks]`
+
+use std::fmt;
+
+/// Daemon events a hook can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// The battery dropped to the warning or critical level
+    LowBattery,
+    /// The focused workspace changed
+    WorkspaceChange,
+    /// The number of outdated brew packages went up
+    BrewOutdated,
+    /// The Mac woke from sleep
+    Wake,
+    /// The appearance switched between light and dark
+    AppearanceChange,
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Hook::LowBattery => "on_low_battery",
+            Hook::WorkspaceChange => "on_workspace_change",
+            Hook::BrewOutdated => "on_brew_outdated",
+            Hook::Wake => "on_wake",
+            Hook::AppearanceChange => "on_appearance_change",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Shell commands run on each event; `None` runs nothing (default)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HooksConfig {
+    pub on_low_battery: Option<String>,
+    pub on_workspace_change: Option<String>,
+    pub on_brew_outdated: Option<String>,
+    pub on_wake: Option<String>,
+    pub on_appearance_change: Option<String>,
+}
+
+impl HooksConfig {
+    /// The command attached to an event
+    pub fn command(&self, hook: Hook) -> Option<&str> {
+        match hook {
+            Hook::LowBattery => self.on_low_battery.as_deref(),
+            Hook::WorkspaceChange => self.on_workspace_change.as_deref(),
+            Hook::BrewOutdated => self.on_brew_outdated.as_deref(),
+            Hook::Wake => self.on_wake.as_deref(),
+            Hook::AppearanceChange => self.on_appearance_change.as_deref(),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Site, credentials, projects and priority colors of the jira item from `[items.jira]`
+
+/// Where the jira item looks for the issues assigned to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JiraConfig {
+    /// Address of the Jira site, like `https://acme.atlassian.net`; none keeps the item hidden
+    pub url: Option<String>,
+    /// Atlassian account email, which Jira Cloud signs in with along with an API token; none sends
+    /// the token as a Jira Server or Data Center personal access token
+    pub email: Option<String>,
+    /// API token or personal access token, usually `${secret:jira}`; none keeps the item hidden
+    pub token: Option<String>,
+    /// Project keys the issues are counted in; none counts them in every project
+    pub projects: Vec<String>,
+    /// Label color while an issue is of High or Highest priority
+    pub high_color: String,
+    /// Label color while the most urgent issue is of Medium priority
+    pub medium_color: String,
+}
+
+impl Default for JiraConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            email: None,
+            token: None,
+            projects: Vec::new(),
+            high_color: "0xfffb4934".to_string(),
+            medium_color: "0xfffabd2f".to_string(),
+        }
+    }
+}
+
+/// Parse a list of project keys, like `API`
+pub(super) fn parse_projects(key: &str, projects: Vec<String>) -> Result<Vec<String>, String> {
+    let valid = |project: &&String| {
+        project.starts_with(|c: char| c.is_ascii_uppercase())
+            && project.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+    };
+    match projects.iter().find(|project| !valid(project)) {
+        Some(project) => Err(format!("Invalid project key for {}: {} (expected e.g. API)", key, project)),
+        None => Ok(projects),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_projects() {
+        let projects = vec!["API".to_string(), "WEB_2".to_string()];
+        assert_eq!(parse_projects("projects", projects.clone()).unwrap(), projects);
+        assert!(parse_projects("projects", Vec::new()).unwrap().is_empty());
+        for bad in ["api", "2FA", "API) OR (assignee"] {
+            assert!(parse_projects("projects", vec![bad.to_string()]).is_err(), "{}", bad);
+        }
+    }
+}
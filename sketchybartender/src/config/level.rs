@@ -0,0 +1,87 @@
+//! Normal, warning and critical levels, and the colors items show them in
+
+use std::fmt;
+
+/// How far a reading has gone, for items colored by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Level {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    /// The level `value` reaches against a warning and a critical threshold, either 0 for never
+    pub fn of<T: Copy + PartialOrd + Default>(value: T, warning: T, critical: T) -> Self {
+        let reached = |threshold: T| threshold > T::default() && value >= threshold;
+        if reached(critical) {
+            Level::Critical
+        } else if reached(warning) {
+            Level::Warning
+        } else {
+            Level::Normal
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Normal => "normal",
+            Level::Warning => "warning",
+            Level::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The color an item takes at each level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelColors {
+    pub normal: String,
+    pub warning: String,
+    pub critical: String,
+}
+
+impl LevelColors {
+    pub fn new(normal: &str, warning: &str, critical: &str) -> Self {
+        Self { normal: normal.to_string(), warning: warning.to_string(), critical: critical.to_string() }
+    }
+
+    pub fn color(&self, level: Level) -> &str {
+        match level {
+            Level::Normal => &self.normal,
+            Level::Warning => &self.warning,
+            Level::Critical => &self.critical,
+        }
+    }
+}
+
+impl Default for LevelColors {
+    /// White, yellow and red
+    fn default() -> Self {
+        Self::new("0xffffffff", "0xfffabd2f", "0xfffb4934")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(Level::of(50u8, 80, 90), Level::Normal);
+        assert_eq!(Level::of(80u8, 80, 90), Level::Warning);
+        assert_eq!(Level::of(250u16, 70, 100), Level::Critical);
+        assert_eq!(Level::of(100u8, 0, 0), Level::Normal);
+        assert_eq!(Level::of(100u8, 0, 90), Level::Critical);
+        assert!(Level::Critical > Level::Warning);
+        assert_eq!(Level::Warning.to_string(), "warning");
+
+        let colors = LevelColors::default();
+        assert_eq!(colors.color(Level::Normal), "0xffffffff");
+        assert_eq!(colors.color(Level::Warning), "0xfffabd2f");
+        assert_eq!(colors.color(Level::Critical), "0xfffb4934");
+    }
+}
@@ -0,0 +1,32 @@
+//! API key and teams of the linear item from `[items.linear]`
+
+/// Whose issues the linear item counts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinearConfig {
+    /// Personal API key, usually `${secret:linear}`; none keeps the item hidden
+    pub api_key: Option<String>,
+    /// Keys of the teams the issues are counted in, like `ENG`; none counts them in every team
+    pub teams: Vec<String>,
+}
+
+/// Parse a list of team keys, like `ENG`
+pub(super) fn parse_teams(key: &str, teams: Vec<String>) -> Result<Vec<String>, String> {
+    let valid = |team: &&String| team.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+    match teams.iter().find(|team| !valid(team)) {
+        Some(team) => Err(format!("Invalid team key for {}: {} (expected e.g. ENG)", key, team)),
+        None => Ok(teams),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_teams() {
+        let teams = vec!["ENG".to_string(), "OPS2".to_string()];
+        assert_eq!(parse_teams("teams", teams.clone()).unwrap(), teams);
+        assert!(parse_teams("teams", Vec::new()).unwrap().is_empty());
+        assert!(parse_teams("teams", vec!["eng".to_string()]).unwrap_err().contains("eng"));
+    }
+}
@@ -0,0 +1,53 @@
+//! Load average thresholds and colors from `[items.load]`
+
+use super::level::{Level, LevelColors};
+
+/// When and how the load item's label is colored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadConfig {
+    /// Load as a percentage of the core count at or above which the warning color is used, 0 for never
+    pub warning: u16,
+    /// Load as a percentage of the core count at or above which the critical color is used, 0 for never
+    pub critical: u16,
+    pub colors: LevelColors,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self { warning: 70, critical: 100, colors: LevelColors::default() }
+    }
+}
+
+impl LoadConfig {
+    /// The level for a load given as a percentage of the core count
+    pub fn level(&self, percentage: u16) -> Level {
+        Level::of(percentage, self.warning, self.critical)
+    }
+}
+
+/// Parse a load threshold as a percentage of the core count, which may go past 100
+pub(super) fn parse_threshold(key: &str, value: u64) -> Result<u16, String> {
+    u16::try_from(value)
+        .ok()
+        .filter(|p| *p <= 1000)
+        .ok_or_else(|| format!("Invalid value for {}: {} (expected a percentage from 0 to 1000)", key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        let config = LoadConfig::default();
+        assert_eq!(config.level(40), Level::Normal);
+        assert_eq!(config.level(70), Level::Warning);
+        assert_eq!(config.level(250), Level::Critical);
+
+        let never = LoadConfig { warning: 0, critical: 0, ..LoadConfig::default() };
+        assert_eq!(never.level(400), Level::Normal);
+
+        assert_eq!(parse_threshold("critical", 150), Ok(150));
+        assert!(parse_threshold("critical", 1001).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+//! Desktop notification settings from `[notifications]`
+
+/// Which events raise a macOS notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationsConfig {
+    /// When the battery drops to the critical level (default: on)
+    pub battery: bool,
+    /// When a brew upgrade started from the bar finishes, with what it upgraded (default: on)
+    pub brew_upgrade: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self { battery: true, brew_upgrade: true }
+    }
+}
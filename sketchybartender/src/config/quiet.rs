@@ -0,0 +1,23 @@
+//! Quiet mode settings from `[quiet]`
+
+/// What quiet mode hides, and when it turns on by itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuietConfig {
+    /// Items hidden while quiet (default: teams, brew)
+    pub items: Vec<String>,
+    /// Turn quiet mode on while the screen is shared (default: off)
+    pub auto: bool,
+    /// Processes that run only while the screen is shared, matched exactly by `pgrep -x`
+    pub share_processes: Vec<String>,
+}
+
+impl Default for QuietConfig {
+    fn default() -> Self {
+        Self {
+            items: vec!["teams".to_string(), "brew".to_string()],
+            auto: false,
+            // Zoom's sharing host and the macOS Screen Sharing server
+            share_processes: vec!["CptHost".to_string(), "screensharingd".to_string()],
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Memory pressure thresholds and colors from `[items.ram]`
+
+use super::level::{Level, LevelColors};
+
+/// When and how the ram item's label is colored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RamConfig {
+    /// Percentage in use at or above which the warning color is used, 0 to leave it to the kernel
+    pub warning: u8,
+    /// Percentage in use at or above which the critical color is used, 0 to leave it to the kernel
+    pub critical: u8,
+    pub colors: LevelColors,
+}
+
+impl Default for RamConfig {
+    fn default() -> Self {
+        Self { warning: 80, critical: 90, colors: LevelColors::default() }
+    }
+}
+
+impl RamConfig {
+    /// The higher of the kernel's pressure level and the one the thresholds give for `percentage`
+    pub fn pressure(&self, percentage: u8, kernel: Level) -> Level {
+        Level::of(percentage, self.warning, self.critical).max(kernel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pressure() {
+        let config = RamConfig::default();
+        assert_eq!(config.pressure(50, Level::Normal), Level::Normal);
+        assert_eq!(config.pressure(85, Level::Normal), Level::Warning);
+        assert_eq!(config.pressure(95, Level::Warning), Level::Critical);
+        assert_eq!(config.pressure(50, Level::Critical), Level::Critical);
+
+        let kernel_only = RamConfig { warning: 0, critical: 0, ..RamConfig::default() };
+        assert_eq!(kernel_only.pressure(100, Level::Normal), Level::Normal);
+    }
+}
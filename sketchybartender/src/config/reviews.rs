@@ -0,0 +1,41 @@
+//! GitHub token and search of the reviews item from `[items.reviews]`
+
+/// Which pull requests the reviews item counts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewsConfig {
+    /// GitHub token allowed to read the repositories, usually `${secret:github}`; none keeps the item hidden
+    pub token: Option<String>,
+    /// GitHub search for the pull requests in the queue
+    pub query: String,
+}
+
+impl Default for ReviewsConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            query: "is:open is:pr review-requested:@me archived:false".to_string(),
+        }
+    }
+}
+
+/// Parse a search query, which has to look for pull requests
+pub(super) fn parse_query(key: &str, value: &str) -> Result<String, String> {
+    if value.split_whitespace().any(|term| term == "is:pr" || term == "type:pr") {
+        Ok(value.trim().to_string())
+    } else {
+        Err(format!("{} must search for pull requests with is:pr: {}", key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = ReviewsConfig::default().query;
+        assert_eq!(parse_query("reviews_query", &query).unwrap(), query);
+        assert!(parse_query("reviews_query", " type:pr org:rust-lang ").is_ok());
+        assert!(parse_query("reviews_query", "is:issue assignee:@me").unwrap_err().contains("is:pr"));
+    }
+}
@@ -0,0 +1,331 @@
+//! Display rules from `[[rules]]` tables
+//!
+//! A rule hides or shows items while all of its conditions hold: the time of
+//! day, the day of the week, the power source, the Wi-Fi network and the
+//! focused app. Items with `show` rules are hidden while none of them match.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{parse_app_list, suggest};
+
+/// Keys a rule accepts
+const RULE_KEYS: &[&str] = &["hide", "show", "time", "days", "power", "ssid", "app"];
+
+const DAY_NAMES: &[&str] = &["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Where the Mac draws power from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+impl FromStr for PowerSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ac" => Ok(PowerSource::Ac),
+            "battery" => Ok(PowerSource::Battery),
+            _ => Err(format!("Invalid power source: {} (expected ac or battery)", s)),
+        }
+    }
+}
+
+impl fmt::Display for PowerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerSource::Ac => f.write_str("ac"),
+            PowerSource::Battery => f.write_str("battery"),
+        }
+    }
+}
+
+/// A span of the day in minutes since midnight, wrapping past midnight when `end` < `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    start: u16,
+    end: u16,
+}
+
+impl TimeRange {
+    /// Whether a minute of the day falls in the range, including `start` but not `end`
+    pub fn contains(&self, minute: u16) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+/// Parse `HH:MM` into minutes since midnight
+fn parse_clock_time(s: &str) -> Option<u16> {
+    let (hours, minutes) = s.trim().split_once(':')?;
+    let (hours, minutes) = (hours.parse::<u16>().ok()?, minutes.parse::<u16>().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl FromStr for TimeRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid time range: {} (expected HH:MM-HH:MM, e.g. 18:00-09:00)", s);
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let start = parse_clock_time(start).ok_or_else(invalid)?;
+        let end = parse_clock_time(end).ok_or_else(invalid)?;
+        if start == end {
+            return Err(format!("Time range {} is empty", s));
+        }
+        Ok(TimeRange { start, end })
+    }
+}
+
+impl fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}-{:02}:{:02}", self.start / 60, self.start % 60, self.end / 60, self.end % 60)
+    }
+}
+
+/// Parse days like `mon-fri` or `sat, sun` into 1 (Monday) to 7 (Sunday)
+fn parse_days(value: &str) -> Result<Vec<u8>, String> {
+    let day = |name: &str| {
+        DAY_NAMES
+            .iter()
+            .position(|day| day.eq_ignore_ascii_case(name.trim()))
+            .map(|index| index as u8 + 1)
+            .ok_or_else(|| format!("Invalid day: {} (expected mon, tue, wed, thu, fri, sat or sun)", name.trim()))
+    };
+
+    let mut days = Vec::new();
+    for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            // Ranges may wrap around the week, as in fri-mon
+            Some((first, last)) => {
+                let (first, last) = (day(first)?, day(last)?);
+                let mut current = first;
+                loop {
+                    days.push(current);
+                    if current == last {
+                        break;
+                    }
+                    current = current % 7 + 1;
+                }
+            }
+            None => days.push(day(part)?),
+        }
+    }
+    days.sort_unstable();
+    days.dedup();
+    Ok(days)
+}
+
+/// The state rules are matched against
+#[derive(Debug, Clone, Default)]
+pub struct RuleContext {
+    /// Minutes since local midnight
+    pub minute: u16,
+    /// Day of the week, 1 (Monday) to 7 (Sunday)
+    pub weekday: u8,
+    pub power: Option<PowerSource>,
+    /// Wi-Fi network, `None` when not connected
+    pub ssid: Option<String>,
+    /// Name of the focused app
+    pub app: Option<String>,
+}
+
+/// A condition on the bar's surroundings and the items it hides or shows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rule {
+    /// Items hidden while the rule matches
+    pub hide: Vec<String>,
+    /// Items shown only while the rule (or another `show` rule for them) matches
+    pub show: Vec<String>,
+    pub time: Option<TimeRange>,
+    /// Days of the week the rule applies on, 1 (Monday) to 7 (Sunday); empty for every day
+    pub days: Vec<u8>,
+    pub power: Option<PowerSource>,
+    /// Wi-Fi networks, any of which matches
+    pub ssid: Vec<String>,
+    /// Focused apps, any of which matches (ignoring case)
+    pub app: Vec<String>,
+}
+
+impl Rule {
+    /// Set a key, validating its value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "hide" => self.hide = parse_app_list(value),
+            "show" => self.show = parse_app_list(value),
+            "time" => self.time = Some(value.parse()?),
+            "days" => self.days = parse_days(value)?,
+            "power" => self.power = Some(value.parse()?),
+            "ssid" => self.ssid = parse_app_list(value),
+            "app" => self.app = parse_app_list(value),
+            _ => {
+                return Err(match suggest(key, RULE_KEYS.iter().copied()) {
+                    Some(suggestion) => format!("Unknown rule key {}; did you mean {}?", key, suggestion),
+                    None => format!("Unknown rule key {} (expected one of {})", key, RULE_KEYS.join(", ")),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the rule affects at least one item
+    pub(super) fn validate(&self) -> Result<(), String> {
+        if self.hide.is_empty() && self.show.is_empty() {
+            return Err("rule needs items to hide or show".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether all of the rule's conditions hold
+    pub fn matches(&self, context: &RuleContext) -> bool {
+        let in_list = |list: &[String], value: &Option<String>| {
+            list.is_empty()
+                || value.as_ref().is_some_and(|value| list.iter().any(|entry| entry.eq_ignore_ascii_case(value)))
+        };
+
+        self.time.is_none_or(|time| time.contains(context.minute))
+            && (self.days.is_empty() || self.days.contains(&context.weekday))
+            && self.power.is_none_or(|power| context.power == Some(power))
+            && in_list(&self.ssid, &context.ssid)
+            && in_list(&self.app, &context.app)
+    }
+}
+
+impl fmt::Display for Rule {
+    /// TOML lines for the rule's table, leaving out unset conditions
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[String]| toml::Value::Array(items.iter().cloned().map(toml::Value::String).collect());
+
+        if !self.hide.is_empty() {
+            writeln!(f, "hide = {}", list(&self.hide))?;
+        }
+        if !self.show.is_empty() {
+            writeln!(f, "show = {}", list(&self.show))?;
+        }
+        if let Some(time) = &self.time {
+            writeln!(f, "time = \"{}\"", time)?;
+        }
+        if !self.days.is_empty() {
+            let days: Vec<String> = self.days.iter().map(|day| DAY_NAMES[usize::from(*day) - 1].to_string()).collect();
+            writeln!(f, "days = {}", list(&days))?;
+        }
+        if let Some(power) = &self.power {
+            writeln!(f, "power = \"{}\"", power)?;
+        }
+        if !self.ssid.is_empty() {
+            writeln!(f, "ssid = {}", list(&self.ssid))?;
+        }
+        if !self.app.is_empty() {
+            writeln!(f, "app = {}", list(&self.app))?;
+        }
+        Ok(())
+    }
+}
+
+/// Which parts of the context the rules look at, so the rest needn't be gathered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleNeeds {
+    pub power: bool,
+    pub ssid: bool,
+    pub app: bool,
+}
+
+impl RuleNeeds {
+    pub fn of(rules: &[Rule]) -> Self {
+        Self {
+            power: rules.iter().any(|rule| rule.power.is_some()),
+            ssid: rules.iter().any(|rule| !rule.ssid.is_empty()),
+            app: rules.iter().any(|rule| !rule.app.is_empty()),
+        }
+    }
+}
+
+/// Items the rules hide in a context
+pub fn hidden_items(rules: &[Rule], context: &RuleContext) -> BTreeSet<String> {
+    let mut hidden = BTreeSet::new();
+    let mut shown = BTreeSet::new();
+    let mut show_only = BTreeSet::new();
+
+    for rule in rules {
+        let matches = rule.matches(context);
+        show_only.extend(rule.show.iter().cloned());
+        if matches {
+            hidden.extend(rule.hide.iter().cloned());
+            shown.extend(rule.show.iter().cloned());
+        }
+    }
+
+    hidden.extend(show_only.difference(&shown).cloned());
+    hidden
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(time: &str, weekday: u8) -> RuleContext {
+        RuleContext { minute: parse_clock_time(time).unwrap(), weekday, ..RuleContext::default() }
+    }
+
+    #[test]
+    fn test_time_range() {
+        let evening: TimeRange = "18:00-09:00".parse().unwrap();
+        assert!(evening.contains(parse_clock_time("23:30").unwrap()));
+        assert!(evening.contains(parse_clock_time("08:59").unwrap()));
+        assert!(!evening.contains(parse_clock_time("09:00").unwrap()));
+        assert_eq!(evening.to_string(), "18:00-09:00");
+
+        assert!("9-17".parse::<TimeRange>().is_err());
+        assert!("25:00-09:00".parse::<TimeRange>().is_err());
+        assert!("09:00-09:00".parse::<TimeRange>().is_err());
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("mon-fri").unwrap(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(parse_days("sat, Sun").unwrap(), vec![6, 7]);
+        assert_eq!(parse_days("fri-mon").unwrap(), vec![1, 5, 6, 7]);
+        assert!(parse_days("someday").is_err());
+    }
+
+    #[test]
+    fn test_hidden_items() {
+        let mut working_hours = Rule::default();
+        working_hours.set("show", "teams").unwrap();
+        working_hours.set("time", "09:00-18:00").unwrap();
+        working_hours.set("days", "mon-fri").unwrap();
+        let mut on_ac = Rule::default();
+        on_ac.set("hide", "battery").unwrap();
+        on_ac.set("power", "ac").unwrap();
+        let rules = vec![working_hours, on_ac];
+
+        assert!(hidden_items(&rules, &context("10:00", 2)).is_empty());
+        assert_eq!(hidden_items(&rules, &context("10:00", 6)), BTreeSet::from(["teams".to_string()]));
+
+        let plugged_in = RuleContext { power: Some(PowerSource::Ac), ..context("20:00", 1) };
+        assert_eq!(hidden_items(&rules, &plugged_in).len(), 2);
+        assert_eq!(RuleNeeds::of(&rules), RuleNeeds { power: true, ssid: false, app: false });
+    }
+
+    #[test]
+    fn test_rule_lists() {
+        let mut rule = Rule::default();
+        rule.set("hide", "brew, teams").unwrap();
+        rule.set("ssid", "Office").unwrap();
+        rule.set("app", "zoom.us").unwrap();
+
+        let meeting = RuleContext { ssid: Some("Office".into()), app: Some("Zoom.us".into()), ..RuleContext::default() };
+        assert!(rule.matches(&meeting));
+        assert!(!rule.matches(&RuleContext { app: None, ..meeting }));
+
+        assert_eq!(rule.to_string(), "hide = [\"brew\", \"teams\"]\nssid = [\"Office\"]\napp = [\"zoom.us\"]\n");
+        assert!(rule.set("dayz", "mon").unwrap_err().contains("did you mean days"));
+        assert!(Rule::default().validate().is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Per-item styling from `[items.<name>.style]` tables
+
+use std::fmt;
+
+use super::{parse_color, suggest};
+
+/// Style keys and the sketchybar properties they set
+const STYLE_KEYS: &[(&str, &str)] = &[
+    ("icon_color", "icon.color"),
+    ("icon_font", "icon.font"),
+    ("label_color", "label.color"),
+    ("label_font", "label.font"),
+    ("padding_left", "padding_left"),
+    ("padding_right", "padding_right"),
+    ("background_color", "background.color"),
+    ("background_height", "background.height"),
+    ("background_corner_radius", "background.corner_radius"),
+    ("background_border_color", "background.border_color"),
+    ("background_border_width", "background.border_width"),
+    ("graph_color", "graph.color"),
+    ("graph_fill_color", "graph.fill_color"),
+];
+
+/// What a style key accepts
+enum Kind {
+    Color,
+    Font,
+    Number,
+}
+
+fn kind(key: &str) -> Kind {
+    if key.ends_with("_color") {
+        Kind::Color
+    } else if key.ends_with("_font") {
+        Kind::Font
+    } else {
+        Kind::Number
+    }
+}
+
+/// Check a value against what its style key accepts
+fn validate(key: &str, value: &str) -> Result<(), String> {
+    match kind(key) {
+        Kind::Color => parse_color(key, value).map(|_| ()),
+        Kind::Font => {
+            if value.split(':').count() == 3 {
+                Ok(())
+            } else {
+                Err(format!("Invalid font for {}: {} (expected Family:Style:Size)", key, value))
+            }
+        }
+        Kind::Number => {
+            let number = value
+                .parse::<i32>()
+                .map_err(|_| format!("Invalid value for {}: {} (expected a whole number)", key, value))?;
+
+            // Only paddings may be negative, to pull items closer together
+            if number < 0 && !key.starts_with("padding_") {
+                return Err(format!("{} must be at least 0, found {}", key, number));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Overrides for an item's look, applied on top of what the daemon renders
+///
+/// Values are kept in the order they were set and applied after the
+/// renderer's own properties, so they win over built-in colors and fonts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemStyle {
+    /// `(style key, value)` pairs
+    values: Vec<(String, String)>,
+}
+
+impl ItemStyle {
+    /// Set a style key, validating its value
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if !STYLE_KEYS.iter().any(|(k, _)| *k == key) {
+            return match suggest(key, STYLE_KEYS.iter().map(|(k, _)| *k)) {
+                Some(suggestion) => Err(format!("Unknown style key {}; did you mean {}?", key, suggestion)),
+                None => {
+                    let keys: Vec<&str> = STYLE_KEYS.iter().map(|(k, _)| *k).collect();
+                    Err(format!("Unknown style key {} (expected one of {})", key, keys.join(", ")))
+                }
+            };
+        }
+        validate(key, value)?;
+
+        match self.values.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.values.push((key.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Whether no style keys are set
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The sketchybar properties this style sets
+    pub fn props(&self) -> Vec<(&'static str, &str)> {
+        self.values
+            .iter()
+            .filter_map(|(key, value)| {
+                let (_, prop) = STYLE_KEYS.iter().find(|(k, _)| k == key)?;
+                Some((*prop, value.as_str()))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ItemStyle {
+    /// TOML lines for the style table; numbers are bare, everything else quoted
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.values {
+            match kind(key) {
+                Kind::Number => writeln!(f, "{} = {}", key, value)?,
+                _ => writeln!(f, "{} = {}", key, toml::Value::String(value.clone()))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_props() {
+        let mut style = ItemStyle::default();
+        style.set("label_color", "0xffa6e3a1").unwrap();
+        style.set("padding_left", "-4").unwrap();
+        style.set("label_color", "0xfff38ba8").unwrap();
+        assert_eq!(style.props(), vec![("label.color", "0xfff38ba8"), ("padding_left", "-4")]);
+        assert_eq!(style.to_string(), "label_color = \"0xfff38ba8\"\npadding_left = -4\n");
+    }
+
+    #[test]
+    fn test_style_validation() {
+        let mut style = ItemStyle::default();
+        assert!(style.set("icon_font", "Hack Nerd Font:Bold:17.0").is_ok());
+        assert!(style.set("label_color", "red").is_err());
+        assert!(style.set("icon_color", "0xfff").is_err());
+        assert!(style.set("label_font", "Hack").is_err());
+        assert!(style.set("padding_right", "wide").is_err());
+        assert!(style.set("shadow", "on").is_err());
+        assert!(style.set("label_colour", "0xffffffff").unwrap_err().contains("did you mean label_color"));
+        assert!(style.set("background_height", "-2").is_err());
+        assert_eq!(style.props(), vec![("icon.font", "Hack Nerd Font:Bold:17.0")]);
+    }
+}
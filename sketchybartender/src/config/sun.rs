@@ -0,0 +1,45 @@
+//! Location and golden hour of the sun item from `[items.sun]`
+
+use super::weather::{parse_coordinates, WeatherConfig};
+
+/// Where the sun item computes sunrise and sunset for
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SunConfig {
+    /// Coordinates like "52.52,13.40"; empty to use the weather item's location when it is coordinates
+    pub location: String,
+    /// Also count down to the start and end of golden hour, when the sun is lower than 6° (default: false)
+    pub golden_hour: bool,
+}
+
+impl SunConfig {
+    /// Latitude and longitude from this item's location, or else the weather item's
+    pub fn coordinates(&self, weather: &WeatherConfig) -> Option<(f64, f64)> {
+        parse_coordinates(&self.location).or_else(|| weather.coordinates())
+    }
+}
+
+/// Parse a location given as coordinates, or empty for none
+pub(super) fn parse_location(key: &str, value: &str) -> Result<String, String> {
+    if value.is_empty() || parse_coordinates(value).is_some() {
+        Ok(value.to_string())
+    } else {
+        Err(format!("Invalid value for {}: {} (expected latitude,longitude like 52.52,13.40)", key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coordinates() {
+        let weather = WeatherConfig { location: "52.52,13.40".to_string(), ..WeatherConfig::default() };
+        assert_eq!(SunConfig::default().coordinates(&weather), Some((52.52, 13.4)));
+        let own = SunConfig { location: "40.71,-74.01".to_string(), ..SunConfig::default() };
+        assert_eq!(own.coordinates(&weather), Some((40.71, -74.01)));
+        assert_eq!(SunConfig::default().coordinates(&WeatherConfig::default()), None);
+
+        assert!(parse_location("sun_location", "").is_ok());
+        assert!(parse_location("sun_location", "Berlin").unwrap_err().contains("52.52,13.40"));
+    }
+}
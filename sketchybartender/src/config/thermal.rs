@@ -0,0 +1,74 @@
+//! Thermal state colors and the temperature readout from `[items.thermal]`
+
+use std::fmt;
+
+use super::level::{Level, LevelColors};
+
+/// How hot the machine runs, as macOS rates it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ThermalState {
+    #[default]
+    Nominal,
+    /// Slightly elevated; fans may spin up
+    Fair,
+    /// High; the CPU is being slowed down
+    Serious,
+    /// Very high; the CPU is slowed down hard until it cools off
+    Critical,
+}
+
+impl ThermalState {
+    /// Whether macOS is slowing the CPU down to cool it
+    pub fn throttling(self) -> bool {
+        self >= ThermalState::Serious
+    }
+
+    /// The level the thermal item is colored by: normal while nominal or fair, warning while serious
+    pub fn level(self) -> Level {
+        match self {
+            ThermalState::Nominal | ThermalState::Fair => Level::Normal,
+            ThermalState::Serious => Level::Warning,
+            ThermalState::Critical => Level::Critical,
+        }
+    }
+}
+
+impl fmt::Display for ThermalState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ThermalState::Nominal => "nominal",
+            ThermalState::Fair => "fair",
+            ThermalState::Serious => "serious",
+            ThermalState::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What the thermal item shows and how its icon is colored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThermalConfig {
+    /// Show the hottest CPU or SoC sensor as the label, where the SMC has one (default: true)
+    pub temperature: bool,
+    /// Icon colors by the state's level, the warning one while serious
+    pub colors: LevelColors,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self { temperature: true, colors: LevelColors::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(ThermalState::Fair.level(), Level::Normal);
+        assert_eq!(ThermalState::Serious.level(), Level::Warning);
+        assert!(!ThermalState::Fair.throttling());
+        assert!(ThermalState::Critical.throttling());
+    }
+}
@@ -0,0 +1,1600 @@
+//! The sectioned TOML config file, and conversion from the legacy key=value format
+
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use super::{alias, custom, expand, Rule};
+use super::{display_or_auto, suggest, unknown_key_message, Config, Diagnostic, Severity, DEFAULT_PROFILE, KNOWN_KEYS};
+use schema::Settings;
+
+mod schema;
+
+/// Key paths of the TOML settings, for unknown-key reporting and suggestions
+const KEY_PATHS: &[&str] = &[
+    "strict",
+    "clear_on_exit",
+    "bootstrap_items",
+    "theme.icon_theme",
+    "theme.app_icon_images",
+    "workspaces.icon_order",
+    "workspaces.icon_dedup",
+    "workspaces.icon_max",
+    "workspaces.important_apps",
+    "workspaces.display",
+    "items.clock.interval",
+    "items.clock.format",
+    "items.clock.hours",
+    "items.clock.weekday",
+    "items.clock.seconds",
+    "items.clock.locale",
+    "items.clock.align",
+    "items.clock.extra",
+    "items.clock.extra_display",
+    "items.battery.interval",
+    "items.battery.thresholds",
+    "items.battery.warning",
+    "items.battery.critical",
+    "items.battery.normal_color",
+    "items.battery.charging_color",
+    "items.battery.warning_color",
+    "items.battery.critical_color",
+    "items.battery.blink",
+    "items.battery.time_remaining",
+    "items.battery.low_power_color",
+    "items.battery.low_power_modifier",
+    "items.brew.interval",
+    "items.brew.path",
+    "items.brew.exclude_pinned",
+    "items.brew.greedy",
+    "items.brew.update",
+    "items.brew.click",
+    "items.brew.jitter",
+    "items.teams.interval",
+    "items.teams.process",
+    "items.teams.dock_label",
+    "items.teams.color",
+    "items.teams.badge_color",
+    "items.teams.inactive_color",
+    "items.teams.border_color",
+    "items.teams.click_refresh",
+    "items.wifi.interval",
+    "items.network.interval",
+    "items.network.interface",
+    "items.cpu.interval",
+    "items.ram.interval",
+    "items.ram.warning",
+    "items.ram.critical",
+    "items.ram.normal_color",
+    "items.ram.warning_color",
+    "items.ram.critical_color",
+    "items.disk.interval",
+    "items.disk.graph_max",
+    "items.load.interval",
+    "items.load.warning",
+    "items.load.critical",
+    "items.load.normal_color",
+    "items.load.warning_color",
+    "items.load.critical_color",
+    "items.top_process.interval",
+    "items.thermal.interval",
+    "items.thermal.temperature",
+    "items.thermal.normal_color",
+    "items.thermal.serious_color",
+    "items.thermal.critical_color",
+    "items.focus_mode.interval",
+    "items.focus_mode.shortcut",
+    "items.focus_mode.label",
+    "items.focus_mode.color",
+    "items.focus_mode.inactive_color",
+    "items.appearance.interval",
+    "items.brightness.interval",
+    "items.brightness.scroll_step",
+    "items.volume.scroll_step",
+    "items.volume.slider_modifier",
+    "items.calendar.interval",
+    "items.calendar.names",
+    "items.calendar.soon",
+    "items.calendar.color",
+    "items.calendar.soon_color",
+    "items.zoom.interval",
+    "items.zoom.muted_color",
+    "items.zoom.live_color",
+    "items.weather.interval",
+    "items.weather.source",
+    "items.weather.location",
+    "items.weather.api_key",
+    "items.weather.units",
+    "items.weather.max_age",
+    "items.sun.interval",
+    "items.sun.location",
+    "items.sun.golden_hour",
+    "items.moon.label",
+    "items.crypto.interval",
+    "items.crypto.coins",
+    "items.crypto.currency",
+    "items.crypto.alerts",
+    "items.crypto.alert_color",
+    "items.exchange.interval",
+    "items.exchange.pair",
+    "items.feeds.interval",
+    "items.feeds.urls",
+    "items.feeds.recent",
+    "items.reviews.interval",
+    "items.reviews.token",
+    "items.reviews.query",
+    "items.gitlab.interval",
+    "items.gitlab.url",
+    "items.gitlab.token",
+    "items.gitlab.failed_color",
+    "items.jira.interval",
+    "items.jira.url",
+    "items.jira.email",
+    "items.jira.token",
+    "items.jira.projects",
+    "items.jira.high_color",
+    "items.jira.medium_color",
+    "items.linear.interval",
+    "items.linear.api_key",
+    "items.linear.teams",
+    "quiet.items",
+    "quiet.auto",
+    "quiet.share_processes",
+    "notifications.battery",
+    "notifications.brew_upgrade",
+    "menubar.fallback",
+    "hooks.on_low_battery",
+    "hooks.on_workspace_change",
+    "hooks.on_brew_outdated",
+    "hooks.on_wake",
+    "hooks.on_appearance_change",
+    "metrics.listen",
+    "history.graphs",
+    "history.retention",
+    "history.interval",
+    "log.level",
+    "log.file",
+    "items.front_app.display",
+    "items.disabled",
+    "displays.clamshell_items",
+];
+
+/// Suggest a key path for an unknown one: a misspelling, or a legacy key used in the TOML file
+fn suggest_path(path: &str) -> Option<String> {
+    let key = path.rsplit('.').next().unwrap_or(path);
+    let moved = |legacy: &str| KNOWN_KEYS.iter().find(|(k, _)| *k == legacy).map(|(_, path)| *path);
+    if let Some(toml_path) = moved(key) {
+        return Some(toml_path.to_string());
+    }
+    suggest(path, KEY_PATHS.iter().copied())
+        .or_else(|| moved(suggest(key, KNOWN_KEYS.iter().map(|(k, _)| *k))?))
+        .map(str::to_string)
+}
+
+/// 1-based line of a byte offset
+fn line_at(contents: &str, offset: usize) -> usize {
+    contents[..offset.min(contents.len())].matches('\n').count() + 1
+}
+
+/// 1-based column of a byte offset, in characters
+fn column_at(contents: &str, offset: usize) -> usize {
+    let before = &contents[..offset.min(contents.len())];
+    before[before.rfind('\n').map_or(0, |i| i + 1)..].chars().count() + 1
+}
+
+/// Best-effort line of `key` inside the `[section]` table, for diagnostics
+fn line_of(contents: &str, section: &str, key: &str) -> usize {
+    let mut current = String::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim().to_string();
+        } else if current == section
+            && line.strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with('='))
+        {
+            return index + 1;
+        }
+    }
+
+    1
+}
+
+/// Best-effort line of `key` in the `index`th `[[section]]` table, for diagnostics
+fn array_line_of(contents: &str, section: &str, index: usize, key: &str) -> usize {
+    let header = format!("[[{}]]", section);
+    let mut tables = 0;
+    let mut found = None;
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            if line == header {
+                tables += 1;
+                if tables == index + 1 {
+                    found = Some(number + 1);
+                }
+            } else if found.is_some() {
+                break;
+            }
+        } else if found.is_some() && line.strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with('=')) {
+            return number + 1;
+        }
+    }
+
+    found.unwrap_or(1)
+}
+
+/// Merge `overlay` into `base`, replacing values but merging tables key by key
+fn merge(base: &mut Table, overlay: &Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Flatten a TOML value into the string the legacy parser expects
+fn value_string(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        Value::Array(items) => {
+            let items: Result<Vec<String>, String> = items
+                .iter()
+                .map(|item| match item {
+                    Value::String(_) | Value::Integer(_) => value_string(item),
+                    _ => Err("lists may only hold strings and numbers".to_string()),
+                })
+                .collect();
+            Ok(items?.join(", "))
+        }
+        _ => Err(format!("expected a string, number or list, found `{}`", value)),
+    }
+}
+
+/// Quote an item name for a TOML table header if it isn't a bare key
+fn toml_key(name: &str) -> String {
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        name.to_string()
+    } else {
+        Value::String(name.to_string()).to_string()
+    }
+}
+
+/// Parse TOML contents into a table, or the diagnostic for the syntax error that stops it
+fn parse_table(contents: &str) -> Result<Table, Diagnostic> {
+    toml::from_str(contents).map_err(|e| Diagnostic {
+        line: e.span().map_or(1, |span| line_at(contents, span.start)),
+        severity: Severity::Error,
+        message: e.message().to_string(),
+    })
+}
+
+/// Read the settings in TOML contents that parse, or the diagnostic for a table of the wrong shape
+fn parse_settings(contents: &str) -> Result<Settings, Diagnostic> {
+    toml::from_str(contents).map_err(|e| Diagnostic {
+        line: e.span().map_or(1, |span| line_at(contents, span.start)),
+        severity: Severity::Error,
+        message: e.message().to_string(),
+    })
+}
+
+/// A string, number or list value with `${VAR}` expanded, flattened like [`value_string`]
+fn expanded_string(value: &Value) -> Result<String, String> {
+    expand::expanded(value.clone()).and_then(|value| value_string(&value))
+}
+
+/// Format a list as a TOML array
+fn toml_list(items: &[String]) -> Value {
+    Value::Array(items.iter().cloned().map(Value::String).collect())
+}
+
+impl Config {
+    /// Parse TOML configuration contents, collecting diagnostics like [`Config::parse`]
+    ///
+    /// Relative `include` paths are resolved against the config directory.
+    pub fn parse_toml(contents: &str) -> (Self, Vec<Diagnostic>) {
+        Self::parse_toml_in(contents, &Self::get_config_dir())
+    }
+
+    /// The syntax error that keeps TOML contents from parsing at all, if any, with its line and column
+    pub(super) fn toml_syntax_error(contents: &str) -> Option<String> {
+        let e = toml::from_str::<Table>(contents).err()?;
+        let position = e.span().map_or_else(
+            || "line 1".to_string(),
+            |span| format!("line {}, column {}", line_at(contents, span.start), column_at(contents, span.start)),
+        );
+        Some(format!("{}: {}", position, e.message()))
+    }
+
+    /// Whether TOML that doesn't parse has a top-level `strict = true` line that does
+    pub(super) fn recovered_strict(contents: &str) -> bool {
+        contents
+            .lines()
+            .take_while(|line| !line.trim_start().starts_with('['))
+            .filter_map(|line| parse_table(line).ok())
+            .any(|table| table.get("strict").and_then(Value::as_bool) == Some(true))
+    }
+
+    /// Parse TOML configuration contents whose relative `include` paths start at `dir`
+    pub fn parse_toml_in(contents: &str, dir: &Path) -> (Self, Vec<Diagnostic>) {
+        let mut config = Self::default();
+        let mut diagnostics = config.apply_contents(contents, dir, &mut Vec::new());
+
+        // Tables come back sorted by name; report in file order
+        diagnostics.sort_by_key(|d| d.line);
+
+        (config, diagnostics)
+    }
+
+    /// Apply a TOML file's contents, then the files it includes
+    ///
+    /// Included files are applied after the including file, so their settings
+    /// win. `stack` holds the files being included, to catch include cycles.
+    fn apply_contents(&mut self, contents: &str, dir: &Path, stack: &mut Vec<PathBuf>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let mut table = match parse_table(contents) {
+            Ok(table) => table,
+            Err(syntax) => {
+                diagnostics.push(syntax);
+                return diagnostics;
+            }
+        };
+
+        // A section of the wrong shape, like `theme = 1`, leaves every typed setting at its default
+        let mut settings = parse_settings(contents).unwrap_or_else(|error| {
+            diagnostics.push(error);
+            Settings::default()
+        });
+
+        let include = table.remove("include").map(expand::expanded);
+        let includes = match include.map(|value| value.and_then(|value| expand::include_list(&value))) {
+            Some(Ok(paths)) => paths,
+            Some(Err(message)) => {
+                diagnostics.push(Diagnostic { line: line_of(contents, "", "include"), severity: Severity::Error, message });
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        let profiles = mem::take(&mut settings.profiles);
+        diagnostics.extend(self.apply_table(&table, settings, contents, ""));
+
+        // Check every profile against the base config, so mistakes show up before switching
+        for (name, profile) in profiles {
+            let Some(Value::Table(profile_table)) = table.get("profiles").and_then(|p| p.get(&name)) else { continue };
+            let prefix = format!("profiles.{}.", toml_key(&name));
+            diagnostics.extend(self.clone().apply_table(profile_table, profile, contents, &prefix));
+        }
+
+        let include_line = line_of(contents, "", "include");
+        for include in includes {
+            let path = expand::include_path(&include, dir);
+            let error = |message: String| Diagnostic { line: include_line, severity: Severity::Error, message };
+
+            if stack.contains(&path) {
+                diagnostics.push(error(format!("{} includes itself", path.display())));
+                continue;
+            }
+            let included = match fs::read_to_string(&path) {
+                Ok(included) => included,
+                Err(e) => {
+                    diagnostics.push(error(format!("Failed to read included file {}: {}", path.display(), e)));
+                    continue;
+                }
+            };
+
+            // Problems in the included file are reported on the include line, naming the file
+            stack.push(path.clone());
+            let included_dir = path.parent().unwrap_or(dir).to_path_buf();
+            for diagnostic in self.apply_contents(&included, &included_dir, stack) {
+                diagnostics.push(Diagnostic {
+                    line: include_line,
+                    severity: diagnostic.severity,
+                    message: format!("{} line {}: {}", path.display(), diagnostic.line, diagnostic.message),
+                });
+            }
+            stack.pop();
+        }
+
+        diagnostics
+    }
+
+    /// Apply the settings in a parsed TOML table on top of this config
+    ///
+    /// `settings` are the table's typed settings, and `prefix` its position in
+    /// the file (e.g. `profiles.work.`), used to find lines for diagnostics.
+    fn apply_table(&mut self, table: &Table, settings: Settings, contents: &str, prefix: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = settings.apply(self, contents, prefix);
+
+        // (section, key) for every setting, with items flattened to `items.<name>`
+        let mut entries: Vec<(String, &str)> = Vec::new();
+        // (item, key, value) for every `[items.<name>.style]` setting
+        let mut styles: Vec<(&str, &str, &Value)> = Vec::new();
+        // (item, key, value) for every `[custom.<name>]` setting
+        let mut custom: Vec<(&str, &str, &Value)> = Vec::new();
+        // (item, key, value) for every `[aliases.<name>]` setting
+        let mut aliases: Vec<(&str, &str, &Value)> = Vec::new();
+        for (section, value) in table {
+            match (section.as_str(), value) {
+                ("plugins", Value::Table(plugins)) => {
+                    let mut plugins = plugins.clone();
+                    for (reference, message) in expand::expand_table(&mut plugins, &expand::env_lookup) {
+                        diagnostics.push(Diagnostic {
+                            line: contents.find(&reference).map_or(1, |offset| line_at(contents, offset)),
+                            severity: Severity::Error,
+                            message,
+                        });
+                    }
+                    self.plugins.extend(plugins);
+                }
+                ("profiles", Value::Table(profiles)) if prefix.is_empty() => {
+                    // Profiles that aren't tables are reported with the rest of the typed settings
+                    for (name, profile) in profiles {
+                        if let Value::Table(profile) = profile {
+                            merge(self.profiles.entry(name.clone()).or_default(), profile)
+                        }
+                    }
+                }
+                ("rules", Value::Array(tables)) => {
+                    let section = format!("{}rules", prefix);
+                    let mut rules = Vec::new();
+                    for (index, table) in tables.iter().enumerate() {
+                        let error = |key: &str, message: String| Diagnostic {
+                            line: array_line_of(contents, &section, index, key),
+                            severity: Severity::Error,
+                            message,
+                        };
+                        let Value::Table(table) = table else {
+                            diagnostics.push(error("", "rules must be tables like [[rules]]".to_string()));
+                            continue;
+                        };
+
+                        let mut rule = Rule::default();
+                        let mut valid = true;
+                        for (key, value) in table {
+                            if let Err(message) = expanded_string(value).and_then(|value| rule.set(key, &value)) {
+                                diagnostics.push(error(key, format!("{}[{}].{}: {}", section, index, key, message)));
+                                valid = false;
+                            }
+                        }
+                        if let Err(message) = rule.validate() {
+                            diagnostics.push(error("", format!("{}[{}]: {}", section, index, message)));
+                            valid = false;
+                        }
+
+                        // A rule missing a condition would hide items in the wrong situations, so it is left out
+                        if valid {
+                            rules.push(rule);
+                        }
+                    }
+                    self.rules = rules;
+                }
+                ("custom", Value::Table(items)) => {
+                    for (item, value) in items {
+                        match value {
+                            Value::Table(keys) => {
+                                custom.extend(keys.iter().map(|(key, value)| (item.as_str(), key.as_str(), value)))
+                            }
+                            _ => diagnostics.push(Diagnostic {
+                                line: line_of(contents, &format!("{}custom", prefix), item),
+                                severity: Severity::Error,
+                                message: format!("custom item `{}` must be a table like [custom.{}]", item, item),
+                            }),
+                        }
+                    }
+                }
+                ("aliases", Value::Table(items)) => {
+                    for (item, value) in items {
+                        match value {
+                            Value::Table(keys) => {
+                                aliases.extend(keys.iter().map(|(key, value)| (item.as_str(), key.as_str(), value)))
+                            }
+                            _ => diagnostics.push(Diagnostic {
+                                line: line_of(contents, &format!("{}aliases", prefix), item),
+                                severity: Severity::Error,
+                                message: format!("alias `{}` must be a table like [aliases.{}]", item, item),
+                            }),
+                        }
+                    }
+                }
+                ("items", Value::Table(items)) => {
+                    for (item, value) in items {
+                        match value {
+                            Value::Table(keys) => {
+                                for (key, value) in keys {
+                                    match (key.as_str(), value) {
+                                        ("style", Value::Table(style)) => styles.extend(
+                                            style.iter().map(|(key, value)| (item.as_str(), key.as_str(), value)),
+                                        ),
+                                        _ => entries.push((format!("items.{}", item), key.as_str())),
+                                    }
+                                }
+                            }
+                            _ => entries.push(("items".to_string(), item.as_str())),
+                        }
+                    }
+                }
+                (_, Value::Table(keys)) => {
+                    entries.extend(keys.keys().map(|key| (section.clone(), key.as_str())))
+                }
+                _ => entries.push((String::new(), section.as_str())),
+            }
+        }
+
+        for (section, key) in entries {
+            let line = line_of(contents, &format!("{}{}", prefix, section), key);
+            let path = if section.is_empty() { key.to_string() } else { format!("{}.{}", section, key) };
+
+            // A section that isn't a table is reported with the rest of the typed settings
+            let section_prefix = format!("{}.", path);
+            if !KEY_PATHS.iter().any(|known| *known == path || known.starts_with(&section_prefix)) {
+                diagnostics.push(Diagnostic {
+                    line,
+                    severity: Severity::Warning,
+                    message: unknown_key_message(&format!("{}{}", prefix, path), suggest_path(&path).as_deref()),
+                });
+            }
+        }
+
+        for (item, key, value) in styles {
+            let section = format!("{}items.{}.style", prefix, item);
+            let line = line_of(contents, &section, key);
+            let result = expanded_string(value)
+                .and_then(|value| self.styles.entry(item.to_string()).or_default().set(key, &value));
+
+            if let Err(message) = result {
+                diagnostics.push(Diagnostic {
+                    line,
+                    severity: Severity::Error,
+                    message: format!("{}.{}: {}", section, key, message),
+                });
+            }
+        }
+        self.styles.retain(|_, style| !style.is_empty());
+
+        let mut declared: Vec<(&str, &str)> = Vec::new();
+        for (item, key, value) in custom {
+            let section = format!("{}custom.{}", prefix, toml_key(item));
+            let line = line_of(contents, &section, key);
+            let result = expanded_string(value)
+                .and_then(|value| self.custom_items.entry(item.to_string()).or_default().set(key, &value));
+
+            if let Err(message) = result {
+                diagnostics.push(Diagnostic {
+                    line,
+                    severity: Severity::Error,
+                    message: format!("{}.{}: {}", section, key, message),
+                });
+            }
+            if !declared.iter().any(|(declared, _)| *declared == item) {
+                declared.push((item, key));
+            }
+        }
+
+        // Items that can't be rendered are left out rather than registered half-defined
+        for (item, key) in declared {
+            let Some(custom_item) = self.custom_items.get(item) else { continue };
+            if let Err(message) = custom::validate(item, custom_item) {
+                self.custom_items.remove(item);
+                diagnostics.push(Diagnostic {
+                    line: line_of(contents, &format!("{}custom.{}", prefix, toml_key(item)), key),
+                    severity: Severity::Error,
+                    message,
+                });
+            }
+        }
+
+        let mut declared: Vec<(&str, &str)> = Vec::new();
+        for (item, key, value) in aliases {
+            let section = format!("{}aliases.{}", prefix, toml_key(item));
+            let line = line_of(contents, &section, key);
+            let result = expanded_string(value)
+                .and_then(|value| self.aliases.entry(item.to_string()).or_default().set(key, &value));
+
+            if let Err(message) = result {
+                diagnostics.push(Diagnostic {
+                    line,
+                    severity: Severity::Error,
+                    message: format!("{}.{}: {}", section, key, message),
+                });
+            }
+            if !declared.iter().any(|(declared, _)| *declared == item) {
+                declared.push((item, key));
+            }
+        }
+
+        // Aliases without a source have nothing to show
+        for (item, key) in declared {
+            let Some(alias) = self.aliases.get(item) else { continue };
+            if let Err(message) = alias::validate(item, alias) {
+                self.aliases.remove(item);
+                diagnostics.push(Diagnostic {
+                    line: line_of(contents, &format!("{}aliases.{}", prefix, toml_key(item)), key),
+                    severity: Severity::Error,
+                    message,
+                });
+            }
+        }
+
+        diagnostics
+    }
+
+    /// This config with a profile's settings applied on top; [`DEFAULT_PROFILE`] is the config as is
+    pub fn with_profile(&self, name: &str) -> Result<Self, String> {
+        if name == DEFAULT_PROFILE {
+            return Ok(self.clone());
+        }
+
+        let Some(profile) = self.profiles.get(name) else {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            return Err(format!(
+                "Unknown profile: {} (available: {})",
+                name,
+                std::iter::once(DEFAULT_PROFILE).chain(names).collect::<Vec<_>>().join(", ")
+            ));
+        };
+
+        let mut config = self.clone();
+        let prefix = format!("profiles.{}.", toml_key(name));
+        let contents = toml::to_string(profile).map_err(|e| e.to_string())?;
+        let settings = parse_settings(&contents).map_err(|error| format!("{}{}", prefix, error.message))?;
+        let diagnostics = config.apply_table(profile, settings, &contents, &prefix);
+        match diagnostics.into_iter().find(|d| d.severity == Severity::Error) {
+            Some(error) => Err(error.message),
+            None => Ok(config),
+        }
+    }
+
+    /// Render the configuration as a commented TOML file
+    pub fn to_toml(&self) -> String {
+        let plugins = if self.plugins.is_empty() {
+            "# [plugins.example]\n# setting = \"value\"\n".to_string()
+        } else {
+            let mut wrapper = Table::new();
+            wrapper.insert("plugins".to_string(), Value::Table(self.plugins.clone()));
+            toml::to_string(&wrapper).unwrap_or_default()
+        };
+
+        let profiles = if self.profiles.is_empty() {
+            "# [profiles.travel]\n# items.disabled = [\"teams\"]\n# [profiles.travel.items.brew]\n# interval = 86400\n".to_string()
+        } else {
+            let profiles = self.profiles.iter().map(|(name, profile)| (name.clone(), Value::Table(profile.clone())));
+            let mut wrapper = Table::new();
+            wrapper.insert("profiles".to_string(), Value::Table(profiles.collect()));
+            toml::to_string(&wrapper).unwrap_or_default()
+        };
+
+        let styles: String = self
+            .styles
+            .iter()
+            .map(|(item, style)| format!("[items.{}.style]\n{}\n", toml_key(item), style))
+            .collect();
+
+        let rules: String = self.rules.iter().map(|rule| format!("[[rules]]\n{}\n", rule)).collect();
+
+        let custom_items: String = self
+            .custom_items
+            .iter()
+            .map(|(name, item)| format!("[custom.{}]\n{}\n", toml_key(name), item))
+            .collect();
+
+        let aliases: String = self
+            .aliases
+            .iter()
+            .map(|(name, alias)| format!("[aliases.{}]\n{}\n", toml_key(name), alias))
+            .collect();
+
+        format!(
+            "# Sketchybartender Configuration\n\
+             \n\
+             # Refuse to start the daemon when this file has errors, instead of using defaults for them\n\
+             strict = {}\n\
+             # Remove the custom, alias and graph items the daemon added when it exits\n\
+             clear_on_exit = {}\n\
+             # Create the bar and its items on startup; turn off to set them up in sketchybarrc instead\n\
+             bootstrap_items = {}\n\
+             \n\
+             [theme]\n\
+             # App icon set: app-font, nerdfont or emoji\n\
+             icon_theme = \"{}\"\n\
+             # Real app icons instead of glyphs: off, front-app or all\n\
+             app_icon_images = \"{}\"\n\
+             \n\
+             [workspaces]\n\
+             # Icon order: alphabetical or position\n\
+             icon_order = \"{}\"\n\
+             # One icon per app (on), per window (off), or per app on a single display (auto)\n\
+             icon_dedup = \"{}\"\n\
+             # Maximum icons per workspace, 0 for no limit\n\
+             icon_max = {}\n\
+             # Apps shown first in workspaces\n\
+             important_apps = {}\n\
+             # Display for all workspaces: auto (each on its monitor), active, main, all or numbers\n\
+             display = \"{}\"\n\
+             \n\
+             [items]\n\
+             # Items the daemon hides and stops updating\n\
+             disabled = {}\n\
+             \n\
+             # Update intervals in seconds\n\
+             [items.clock]\n\
+             interval = {}\n\
+             # strftime format, or \"\" to build one from hours, weekday and seconds\n\
+             format = {}\n\
+             hours = {}\n\
+             weekday = {}\n\
+             # Tick on every second instead of every interval\n\
+             seconds = {}\n\
+             # Locale for day names and AM/PM, e.g. \"de_DE.UTF-8\"; \"\" for the system's\n\
+             locale = {}\n\
+             # Tick on whole multiples of interval (:00, :15, :30, :45 for 15), so the minute changes on time\n\
+             align = {}\n\
+             # Time zones also shown, e.g. [\"America/New_York\", \"Asia/Tokyo\"]\n\
+             extra = {}\n\
+             # Show them in a popup when the clock is clicked, or inline after the local time\n\
+             extra_display = \"{}\"\n\
+             \n\
+             [items.battery]\n\
+             # Fallback refresh; the item also updates on every power source notification\n\
+             interval = {}\n\
+             # Lowest percentage for the full, three-quarter, half and quarter icons\n\
+             thresholds = {}\n\
+             # Percentages at which the warning and critical colors apply, 0 to disable\n\
+             warning = {}\n\
+             critical = {}\n\
+             normal_color = \"{}\"\n\
+             charging_color = \"{}\"\n\
+             warning_color = \"{}\"\n\
+             critical_color = \"{}\"\n\
+             # Blink when the battery drops to the warning or critical level\n\
+             blink = {}\n\
+             # Follow the percentage with the time to empty or full, e.g. 85% 2:14\n\
+             time_remaining = {}\n\
+             # Icon color while Low Power Mode is on\n\
+             low_power_color = \"{}\"\n\
+             # Held while clicking to toggle Low Power Mode: shift, ctrl, alt, cmd or off\n\
+             low_power_modifier = \"{}\"\n\
+             \n\
+             [items.brew]\n\
+             interval = {}\n\
+             # brew binary, or \"\" to look in /opt/homebrew and /usr/local\n\
+             path = {}\n\
+             # Leave pinned formulae out of the count\n\
+             exclude_pinned = {}\n\
+             # Count casks that update themselves (brew outdated --greedy)\n\
+             greedy = {}\n\
+             # Run brew update before each check\n\
+             update = {}\n\
+             # On click: upgrade, confirm (ask first), refresh or popup (list the outdated packages)\n\
+             click = \"{}\"\n\
+             # Add up to this many seconds at random to each interval, so checks after wake spread out\n\
+             jitter = {}\n\
+             \n\
+             [items.teams]\n\
+             interval = {}\n\
+             # Any app that badges its Dock icon: the process name (pgrep -x) and Dock label,\n\
+             # e.g. \"Teams\" and \"Microsoft Teams classic\", or \"Slack\" and \"Slack\"\n\
+             process = {}\n\
+             dock_label = {}\n\
+             color = \"{}\"\n\
+             # Icon and border color while there are notifications\n\
+             badge_color = \"{}\"\n\
+             # Icon color while the app isn't running\n\
+             inactive_color = \"{}\"\n\
+             border_color = \"{}\"\n\
+             # Seconds after a click at which to check the badge again\n\
+             click_refresh = {}\n\
+             \n\
+             [items.wifi]\n\
+             interval = {}\n\
+             \n\
+             [items.network]\n\
+             interval = {}\n\
+             # auto (the default route's interface), a name like \"en0\", or \"utun\" for every utun<n>\n\
+             interface = \"{}\"\n\
+             \n\
+             [items.cpu]\n\
+             interval = {}\n\
+             \n\
+             [items.ram]\n\
+             interval = {}\n\
+             # Percentages in use from which the label takes warning_color and critical_color, 0 to\n\
+             # go by the kernel's memory pressure alone; pressure colors the label either way\n\
+             warning = {}\n\
+             critical = {}\n\
+             normal_color = \"{}\"\n\
+             warning_color = \"{}\"\n\
+             critical_color = \"{}\"\n\
+             \n\
+             [items.disk]\n\
+             interval = {}\n\
+             # Combined read and write MB/s at the top of disk_graph\n\
+             graph_max = {}\n\
+             \n\
+             [items.load]\n\
+             interval = {}\n\
+             # The 1-minute load as a percentage of the core count from which the label takes\n\
+             # warning_color and critical_color, 0 for never; past 100 threads wait for a core\n\
+             warning = {}\n\
+             critical = {}\n\
+             normal_color = \"{}\"\n\
+             warning_color = \"{}\"\n\
+             critical_color = \"{}\"\n\
+             \n\
+             [items.top_process]\n\
+             interval = {}\n\
+             \n\
+             [items.thermal]\n\
+             # Seconds between checks, which the fan item's readings share\n\
+             interval = {}\n\
+             # Show the hottest CPU or SoC sensor; otherwise the label names the thermal state\n\
+             temperature = {}\n\
+             # Icon colors while nominal or fair, and while macOS slows the CPU down to cool it\n\
+             normal_color = \"{}\"\n\
+             serious_color = \"{}\"\n\
+             critical_color = \"{}\"\n\
+             \n\
+             [items.focus_mode]\n\
+             # Fallback refresh; turning a Focus on or off shows within a second\n\
+             interval = {}\n\
+             # Shortcut a click runs, with a Set Focus action that turns Do Not Disturb on or off\n\
+             shortcut = {}\n\
+             # Name the active Focus next to the icon\n\
+             label = {}\n\
+             # Icon colors while a Focus is on, and while none is\n\
+             color = \"{}\"\n\
+             inactive_color = \"{}\"\n\
+             \n\
+             [items.appearance]\n\
+             # Fallback refresh; switching between light and dark shows at once\n\
+             interval = {}\n\
+             \n\
+             [items.brightness]\n\
+             # Seconds between checks, which pick up the brightness keys\n\
+             interval = {}\n\
+             # Percent per line scrolled over the item; 0 ignores scrolling\n\
+             scroll_step = {}\n\
+             \n\
+             [items.volume]\n\
+             # Percent per line scrolled over the item; 0 ignores scrolling\n\
+             scroll_step = {}\n\
+             # Held while clicking to open the slider instead of muting: shift, ctrl, alt, cmd or off\n\
+             slider_modifier = \"{}\"\n\
+             \n\
+             [items.calendar]\n\
+             # Seconds between icalBuddy checks, which also count the minutes down\n\
+             interval = {}\n\
+             # Calendars to take the next event from, by name; [] for all of them\n\
+             names = {}\n\
+             # Minutes before the start at which the label turns soon_color, 0 to never\n\
+             soon = {}\n\
+             color = \"{}\"\n\
+             soon_color = \"{}\"\n\
+             \n\
+             [items.zoom]\n\
+             # Seconds between reads of Zoom's menu bar, so a mute from Zoom itself shows this late at most\n\
+             interval = {}\n\
+             # Microphone colors while muted and while live\n\
+             muted_color = \"{}\"\n\
+             live_color = \"{}\"\n\
+             \n\
+             [items.weather]\n\
+             # Seconds between fetches; a restart sooner than that shows the cached weather\n\
+             interval = {}\n\
+             # wttr (wttr.in) or openweathermap, which needs location and api_key\n\
+             source = \"{}\"\n\
+             # A place like \"Berlin\" or \"52.52,13.40\"; the item stays off until this or api_key is set\n\
+             location = {}\n\
+             # OpenWeatherMap API key, e.g. \"${{secret:openweathermap}}\"\n\
+             api_key = {}\n\
+             # metric (°C) or imperial (°F)\n\
+             units = \"{}\"\n\
+             # Seconds the last weather is still shown while offline, 0 for as long as there is one\n\
+             max_age = {}\n\
+             \n\
+             [items.sun]\n\
+             # Seconds between checks for the next sunrise or sunset, worked out from the location\n\
+             interval = {}\n\
+             # Latitude and longitude like \"52.52,13.40\"; \"\" takes the weather location if it's coordinates\n\
+             location = \"{}\"\n\
+             # Also count down to golden hour, when the sun is less than 6° above the horizon\n\
+             golden_hour = {}\n\
+             \n\
+             [items.moon]\n\
+             # Name the phase next to the icon; either way it updates at midnight\n\
+             label = {}\n\
+             \n\
+             [items.crypto]\n\
+             # Seconds between price fetches from CoinGecko, whose free API allows a few a minute\n\
+             interval = {}\n\
+             # CoinGecko IDs, as in the coin's page address, e.g. [\"bitcoin\", \"ethereum\"]; none turns it off\n\
+             coins = {}\n\
+             # Currency code the prices are in, e.g. \"eur\"\n\
+             currency = \"{}\"\n\
+             # Flash the item when a price crosses a bound, e.g. [\"bitcoin > 80000\", \"ethereum < 2000\"]\n\
+             alerts = {}\n\
+             # Label color while a price is past a bound\n\
+             alert_color = \"{}\"\n\
+             \n\
+             [items.exchange]\n\
+             # Seconds between fetches; the ECB's reference rates change once a working day\n\
+             interval = {}\n\
+             # What one unit of the first currency costs in the second, e.g. \"EUR/USD\"; \"\" turns the item off\n\
+             pair = \"{}\"\n\
+             \n\
+             [items.feeds]\n\
+             # Seconds between checks of the feeds for new entries\n\
+             interval = {}\n\
+             # RSS or Atom feeds, e.g. [\"https://blog.rust-lang.org/feed.xml\"]; the item hides without any\n\
+             urls = {}\n\
+             # Entries listed in the popup, newest first\n\
+             recent = {}\n\
+             \n\
+             [items.reviews]\n\
+             # Seconds between checks of the pull requests waiting for your review\n\
+             interval = {}\n\
+             # GitHub token that can read the repositories, e.g. \"${{secret:github}}\"; the item hides without one\n\
+             token = {}\n\
+             # GitHub search for the pull requests in the queue\n\
+             query = {}\n\
+             \n\
+             [items.gitlab]\n\
+             # Seconds between checks of your merge requests and their pipelines\n\
+             interval = {}\n\
+             # Address of the GitLab instance\n\
+             url = {}\n\
+             # Access token with the read_api scope, e.g. \"${{secret:gitlab}}\"; the item hides without one\n\
+             token = {}\n\
+             # Color of the item while a pipeline of one of your merge requests has failed\n\
+             failed_color = \"{}\"\n\
+             \n\
+             [items.jira]\n\
+             # Seconds between checks of the issues assigned to you\n\
+             interval = {}\n\
+             # Address of the Jira site, e.g. \"https://acme.atlassian.net\"; the item hides without one\n\
+             url = {}\n\
+             # Atlassian account email for Jira Cloud; leave empty to use the token as a personal access token\n\
+             email = {}\n\
+             # API token or personal access token, e.g. \"${{secret:jira}}\"; the item hides without one\n\
+             token = {}\n\
+             # Project keys to count issues in, e.g. [\"API\", \"WEB\"]; empty counts them in every project\n\
+             projects = {}\n\
+             # Label color while an issue is of High or Highest priority\n\
+             high_color = \"{}\"\n\
+             # Label color while the most urgent issue is of Medium priority\n\
+             medium_color = \"{}\"\n\
+             \n\
+             [items.linear]\n\
+             # Seconds between counts of your active issues in the current cycle\n\
+             interval = {}\n\
+             # Personal API key, e.g. \"${{secret:linear}}\"; the item hides without one\n\
+             api_key = {}\n\
+             # Team keys to count issues in, e.g. [\"ENG\"]; empty counts them in every team\n\
+             teams = {}\n\
+             \n\
+             [items.front_app]\n\
+             # auto (as in sketchybarrc), active, main, all or numbers like \"1,2\"\n\
+             display = \"{}\"\n\
+             \n\
+             [quiet]\n\
+             # Items hidden in quiet mode (sketchycli quiet on), which also stops the battery blinking\n\
+             items = {}\n\
+             # Turn quiet mode on while one of share_processes runs (pgrep -x)\n\
+             auto = {}\n\
+             share_processes = {}\n\
+             \n\
+             [notifications]\n\
+             # macOS notifications, suppressed in quiet mode: battery at the critical level,\n\
+             # and a summary when a brew upgrade started from the bar finishes\n\
+             battery = {}\n\
+             brew_upgrade = {}\n\
+             \n\
+             [menubar]\n\
+             # Own menu bar item with the clock, battery and brew: off, auto (while sketchybar\n\
+             # isn't running) or on; switching away from off takes a daemon restart\n\
+             fallback = \"{}\"\n\
+             \n\
+             [hooks]\n\
+             # Shell commands run on daemon events, with details in environment variables:\n\
+             # EVENT, BATTERY_PERCENTAGE and BATTERY_LEVEL, FOCUSED_WORKSPACE and PREV_WORKSPACE,\n\
+             # BREW_OUTDATED, BREW_FORMULAE and BREW_CASKS, APPEARANCE; \"\" runs nothing\n\
+             on_low_battery = {}\n\
+             on_workspace_change = {}\n\
+             on_brew_outdated = {}\n\
+             on_wake = {}\n\
+             on_appearance_change = {}\n\
+             \n\
+             [metrics]\n\
+             # Serve /metrics (Prometheus) and /metrics.json here, e.g. \"127.0.0.1:9273\"; \"\" for off\n\
+             listen = \"{}\"\n\
+             \n\
+             [history]\n\
+             # Graph items for \"battery\", \"cpu\" and \"disk\", named <metric>_graph, showing the last\n\
+             # `retention` samples (also the width in points) taken every `interval` seconds\n\
+             graphs = {}\n\
+             retention = {}\n\
+             interval = {}\n\
+             \n\
+             [log]\n\
+             # error, warn, info or debug (every message and command); `file` also writes\n\
+             # sketchybartender.log next to the socket in ~/.cache/sketchybar, rotated at 1 MB\n\
+             level = \"{}\"\n\
+             file = {}\n\
+             \n\
+             [displays]\n\
+             # Items moved off the built-in display while the lid is closed\n\
+             clamshell_items = {}\n\
+             \n\
+             # Item styles: icon_color, label_color (0xAARRGGBB), icon_font, label_font\n\
+             # (Family:Style:Size), padding_left, padding_right and background_color,\n\
+             # background_height, background_corner_radius, background_border_color,\n\
+             # background_border_width, and graph_color and graph_fill_color for graph items.\n\
+             # `workspaces` styles every workspace item.\n\
+             # [items.clock.style]\n\
+             # label_color = \"0xffcad3f5\"\n\
+             \n\
+             {}\
+             # Custom items: the label is the output of `command`, run every `interval` seconds;\n\
+             # `icon` is a glyph or `icon_command` a command for it, `jitter` random extra seconds,\n\
+             # `position` left, right, center, q or e, and `click` a command run on click.\n\
+             # `script` instead of `command` runs a Rhai file (relative to this directory) that\n\
+             # returns the label, or a map with label, icon, visible and style keys\n\
+             # [custom.ip]\n\
+             # command = \"curl -s ifconfig.me\"\n\
+             # icon = \"󰩟\"\n\
+             # interval = 900\n\
+             \n\
+             {}\
+             # Aliases mirror menu bar items into the bar; `source` is the owner and name\n\
+             # sketchybar lists with `sketchybar --query default_menu_items`\n\
+             # [aliases.bluetooth]\n\
+             # source = \"Control Center,Bluetooth\"\n\
+             # position = \"right\"\n\
+             \n\
+             {}\
+             # Rules hide items while all their conditions hold, or with `show` only then.\n\
+             # Conditions: time = \"HH:MM-HH:MM\", days = \"mon-fri\", power = \"ac\" or \"battery\",\n\
+             # ssid (Wi-Fi networks) and app (focused apps)\n\
+             # [[rules]]\n\
+             # show = [\"teams\"]\n\
+             # time = \"09:00-18:00\"\n\
+             # days = \"mon-fri\"\n\
+             \n\
+             {}\
+             # Profiles override any of the settings above; switch with `sketchycli profile <name>`\n\
+             {}\
+             \n\
+             # Settings for plugins, passed through untouched\n\
+             {}",
+            self.strict,
+            self.clear_on_exit,
+            self.bootstrap_items,
+            self.icon_theme,
+            self.app_icon_images,
+            self.workspace_icon_order,
+            self.workspace_icon_dedup,
+            self.workspace_icon_max,
+            toml_list(&self.important_apps),
+            display_or_auto(&self.workspace_display),
+            toml_list(&self.disabled_items),
+            self.clock_interval,
+            Value::String(self.clock.format.clone().unwrap_or_default()),
+            self.clock.hours,
+            self.clock.weekday,
+            self.clock.seconds,
+            Value::String(self.clock.locale.clone().unwrap_or_default()),
+            self.clock.align,
+            toml_list(&self.clock.extra),
+            self.clock.extra_display,
+            self.battery_interval,
+            Value::Array(self.battery.thresholds.iter().map(|t| Value::Integer(i64::from(*t))).collect()),
+            self.battery.warning,
+            self.battery.critical,
+            self.battery.normal_color,
+            self.battery.charging_color,
+            self.battery.warning_color,
+            self.battery.critical_color,
+            self.battery.blink,
+            self.battery.time_remaining,
+            self.battery.low_power_color,
+            self.battery.low_power_modifier,
+            self.brew_interval,
+            Value::String(self.brew.path.clone().unwrap_or_default()),
+            self.brew.exclude_pinned,
+            self.brew.greedy,
+            self.brew.update,
+            self.brew.click,
+            self.brew.jitter,
+            self.teams_interval,
+            Value::String(self.teams.process.clone()),
+            Value::String(self.teams.dock_label.clone()),
+            self.teams.color,
+            self.teams.badge_color,
+            self.teams.inactive_color,
+            self.teams.border_color,
+            Value::Array(self.teams.click_refresh.iter().map(|t| Value::Integer(*t as i64)).collect()),
+            self.wifi_interval,
+            self.network_interval,
+            self.network_interface,
+            self.cpu_interval,
+            self.ram_interval,
+            self.ram.warning,
+            self.ram.critical,
+            self.ram.colors.normal,
+            self.ram.colors.warning,
+            self.ram.colors.critical,
+            self.disk_interval,
+            self.disk_graph_max,
+            self.load_interval,
+            self.load.warning,
+            self.load.critical,
+            self.load.colors.normal,
+            self.load.colors.warning,
+            self.load.colors.critical,
+            self.top_process_interval,
+            self.thermal_interval,
+            self.thermal.temperature,
+            self.thermal.colors.normal,
+            self.thermal.colors.warning,
+            self.thermal.colors.critical,
+            self.focus_mode_interval,
+            Value::String(self.focus_mode.shortcut.clone()),
+            self.focus_mode.label,
+            self.focus_mode.colors.warning,
+            self.focus_mode.colors.normal,
+            self.appearance_interval,
+            self.brightness_interval,
+            self.brightness_scroll_step,
+            self.volume_scroll_step,
+            self.volume_slider_modifier,
+            self.calendar_interval,
+            toml_list(&self.calendar.names),
+            self.calendar.soon,
+            self.calendar.colors.normal,
+            self.calendar.colors.warning,
+            self.zoom_interval,
+            self.zoom.colors.critical,
+            self.zoom.colors.normal,
+            self.weather_interval,
+            self.weather.source,
+            Value::String(self.weather.location.clone()),
+            Value::String(self.weather.api_key.clone().unwrap_or_default()),
+            self.weather.units,
+            self.weather.max_age,
+            self.sun_interval,
+            self.sun.location,
+            self.sun.golden_hour,
+            self.moon_label,
+            self.crypto_interval,
+            toml_list(&self.crypto.coins),
+            self.crypto.currency,
+            toml_list(&self.crypto.alerts.iter().map(|alert| alert.to_string()).collect::<Vec<_>>()),
+            self.crypto.alert_color,
+            self.exchange_interval,
+            self.exchange_pair.as_ref().map(|pair| pair.to_string()).unwrap_or_default(),
+            self.feeds_interval,
+            toml_list(&self.feeds.urls),
+            self.feeds.recent,
+            self.reviews_interval,
+            Value::String(self.reviews.token.clone().unwrap_or_default()),
+            Value::String(self.reviews.query.clone()),
+            self.gitlab_interval,
+            Value::String(self.gitlab.url.clone()),
+            Value::String(self.gitlab.token.clone().unwrap_or_default()),
+            self.gitlab.failed_color,
+            self.jira_interval,
+            Value::String(self.jira.url.clone().unwrap_or_default()),
+            Value::String(self.jira.email.clone().unwrap_or_default()),
+            Value::String(self.jira.token.clone().unwrap_or_default()),
+            toml_list(&self.jira.projects),
+            self.jira.high_color,
+            self.jira.medium_color,
+            self.linear_interval,
+            Value::String(self.linear.api_key.clone().unwrap_or_default()),
+            toml_list(&self.linear.teams),
+            display_or_auto(&self.front_app_display),
+            toml_list(&self.quiet.items),
+            self.quiet.auto,
+            toml_list(&self.quiet.share_processes),
+            self.notifications.battery,
+            self.notifications.brew_upgrade,
+            self.menubar_fallback,
+            Value::String(self.hooks.on_low_battery.clone().unwrap_or_default()),
+            Value::String(self.hooks.on_workspace_change.clone().unwrap_or_default()),
+            Value::String(self.hooks.on_brew_outdated.clone().unwrap_or_default()),
+            Value::String(self.hooks.on_wake.clone().unwrap_or_default()),
+            Value::String(self.hooks.on_appearance_change.clone().unwrap_or_default()),
+            self.metrics_listen.map(|address| address.to_string()).unwrap_or_default(),
+            toml_list(&self.history.graphs),
+            self.history.retention,
+            self.history.interval,
+            self.log_level,
+            self.log_file,
+            toml_list(&self.clamshell_items),
+            styles,
+            custom_items,
+            aliases,
+            rules,
+            profiles,
+            plugins,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DisplaySpec, IconOrder, IconTheme, ItemPosition};
+
+    #[test]
+    fn test_parse_toml_sections() {
+        let contents = r#"
+[theme]
+icon_theme = "emoji"
+
+[workspaces]
+icon_order = "position"
+important_apps = ["Slack", "Safari"]
+display = "1,2"
+
+[items.clock]
+interval = 5
+
+[items.clock.style]
+label_color = "0xffa6e3a1"
+padding_left = 8
+
+[items."workspace.1".style]
+background_color = "0xff89b4fa"
+
+[plugins.weather]
+city = "Oslo"
+"#;
+        let (config, diagnostics) = Config::parse_toml(contents);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(config.icon_theme, IconTheme::Emoji);
+        assert_eq!(config.workspace_icon_order, IconOrder::Position);
+        assert_eq!(config.important_apps, vec!["Slack", "Safari"]);
+        assert_eq!(config.workspace_display, Some(DisplaySpec::Ids(vec![1, 2])));
+        assert_eq!(config.clock_interval, 5);
+        assert_eq!(config.battery_interval, 600);
+        assert_eq!(config.plugins["weather"]["city"].as_str(), Some("Oslo"));
+        assert_eq!(config.styles["clock"].props(), vec![("label.color", "0xffa6e3a1"), ("padding_left", "8")]);
+        assert_eq!(config.styles["workspace.1"].props(), vec![("background.color", "0xff89b4fa")]);
+    }
+
+    #[test]
+    fn test_parse_toml_styles() {
+        let contents = "[items.battery.style]\nicon_color = \"green\"\nshadow = \"on\"\n";
+        let (config, diagnostics) = Config::parse_toml(contents);
+        let lines: Vec<(usize, Severity)> = diagnostics.iter().map(|d| (d.line, d.severity)).collect();
+        assert_eq!(lines, vec![(2, Severity::Error), (3, Severity::Error)]);
+        assert!(diagnostics[0].message.contains("items.battery.style.icon_color"));
+        assert!(config.styles.is_empty());
+
+        let mut config = Config::default();
+        config.styles.entry("workspace.1".to_string()).or_default().set("label_font", "Hack:Bold:14.0").unwrap();
+        let (converted, diagnostics) = Config::parse_toml(&config.to_toml());
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(converted, config);
+    }
+
+    #[test]
+    fn test_parse_toml_rules() {
+        let contents = r#"
+[[rules]]
+show = ["teams"]
+time = "09:00-18:00"
+days = "mon-fri"
+
+[[rules]]
+hide = ["battery"]
+power = "plugged"
+
+[[rules]]
+ssid = "Office"
+
+[profiles.desktop]
+[[profiles.desktop.rules]]
+hide = ["battery"]
+power = "ac"
+"#;
+        let (config, diagnostics) = Config::parse_toml(contents);
+        let messages: Vec<(usize, &str)> = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(messages[0].0 == 9 && messages[0].1.starts_with("rules[1].power: Invalid power source"));
+        assert_eq!(messages[1], (11, "rules[2]: rule needs items to hide or show"));
+
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].show, vec!["teams"]);
+
+        let desktop = config.with_profile("desktop").unwrap();
+        assert_eq!(desktop.rules.len(), 1);
+        assert_eq!(desktop.rules[0].hide, vec!["battery"]);
+
+        let (converted, diagnostics) = Config::parse_toml(&desktop.to_toml());
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(converted.rules, desktop.rules);
+    }
+
+    #[test]
+    fn test_parse_toml_custom_items() {
+        let contents = "[custom.ip]\ncommand = \"curl -s ifconfig.me\"\nicon = \"󰩟\"\ninterval = 900\n\n[custom.clock]\ncommand = \"date\"\n\n[custom.vpn]\nicon = \"󰖂\"\nposition = \"top\"\n";
+        let (config, diagnostics) = Config::parse_toml(contents);
+        let messages: Vec<(usize, &str)> = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect();
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].0 == 7 && messages[0].1.contains("name of a built-in item"));
+        assert_eq!(messages[1], (10, "custom item vpn needs a command or a script"));
+        assert!(messages[2].0 == 11 && messages[2].1.contains("custom.vpn.position"));
+
+        assert_eq!(config.custom_items.keys().collect::<Vec<_>>(), vec!["ip"]);
+        assert_eq!(config.custom_items["ip"].interval, 900);
+        assert_eq!(config.custom_items["ip"].icon.as_deref(), Some("󰩟"));
+
+        let (converted, diagnostics) = Config::parse_toml(&config.to_toml());
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(converted, config);
+    }
+
+    #[test]
+    fn test_parse_toml_aliases() {
+        let contents = "[aliases.bluetooth]\nsource = \"Control Center,Bluetooth\"\nposition = \"left\"\n\n[aliases.vpn]\nposition = \"right\"\n";
+        let (config, diagnostics) = Config::parse_toml(contents);
+        let messages: Vec<(usize, &str)> = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect();
+        assert_eq!(messages, vec![(6, "alias vpn needs a source like \"Control Center,WiFi\"")]);
+
+        assert_eq!(config.aliases.keys().collect::<Vec<_>>(), vec!["bluetooth"]);
+        assert_eq!(config.aliases["bluetooth"].source, "Control Center,Bluetooth");
+        assert_eq!(config.aliases["bluetooth"].position, ItemPosition::Left);
+
+        let (converted, diagnostics) = Config::parse_toml(&config.to_toml());
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(converted, config);
+    }
+
+    #[test]
+    fn test_parse_toml_diagnostics() {
+        let contents = "[theme]\nicon_theme = \"emoji\"\ncolour = \"red\"\n\n[items.clock]\ninterval = 0\n";
+        let (_, diagnostics) = Config::parse_toml(contents);
+        let lines: Vec<(usize, Severity)> = diagnostics.iter().map(|d| (d.line, d.severity)).collect();
+        assert_eq!(lines, vec![(3, Severity::Warning), (6, Severity::Error)]);
+        assert!(diagnostics[0].message.contains("theme.colour"));
+
+        let (_, diagnostics) = Config::parse_toml("brew_interval = 60\nteams_intervl = 5\n[items.clock]\nintervall = 5\n");
+        assert!(diagnostics[0].message.ends_with("did you mean `items.brew.interval`?"));
+        assert!(diagnostics[1].message.ends_with("did you mean `items.teams.interval`?"));
+        assert!(diagnostics[2].message.ends_with("did you mean `items.clock.interval`?"));
+
+        // A value of the wrong type is reported on its line, and the rest of the file still applies
+        let contents = "[items.clock]\ninterval = \"5\"\n\n[items.brew]\ninterval = 60\n";
+        let (config, diagnostics) = Config::parse_toml(contents);
+        assert_eq!((diagnostics.len(), diagnostics[0].line), (1, 2));
+        assert!(diagnostics[0].message.starts_with("items.clock.interval: invalid type: string"));
+        assert_eq!((config.clock_interval, config.brew_interval), (Config::default().clock_interval, 60));
+
+        let (_, diagnostics) = Config::parse_toml("[profiles]\ntravel = 1\n");
+        assert_eq!((diagnostics.len(), diagnostics[0].line), (1, 2));
+        assert!(diagnostics[0].message.ends_with("expected a table like [profiles.<name>]"));
+
+        let (_, diagnostics) = Config::parse_toml("[theme]\nicon_theme = \n");
+        assert_eq!(diagnostics[0].line, 2);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let contents = r#"
+[items.clock]
+interval = 15
+
+[profiles.travel]
+items.disabled = ["teams", "brew"]
+
+[profiles.travel.theme]
+icon_theme = "emoji"
+
+[profiles.travel.items.clock]
+interval = 60
+
+[profiles.broken.items.battery]
+interval = 0
+"#;
+        let (config, diagnostics) = Config::parse_toml(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].severity), (15, Severity::Error));
+        assert!(diagnostics[0].message.contains("profiles.broken.items.battery.interval"));
+
+        // The base config is untouched by its profiles
+        assert_eq!(config.clock_interval, 15);
+        assert!(config.is_enabled("teams"));
+
+        let travel = config.with_profile("travel").unwrap();
+        assert_eq!(travel.clock_interval, 60);
+        assert_eq!(travel.icon_theme, IconTheme::Emoji);
+        assert!(!travel.is_enabled("teams"));
+        assert_eq!(config.with_profile(DEFAULT_PROFILE).unwrap(), config);
+        assert!(config.with_profile("broken").is_err());
+        assert!(config.with_profile("home").unwrap_err().contains("default, broken, travel"));
+
+        let (converted, diagnostics) = Config::parse_toml(&travel.to_toml());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(converted.with_profile("travel").unwrap(), travel.with_profile("travel").unwrap());
+    }
+
+    #[test]
+    fn test_includes_and_env() {
+        let dir = std::env::temp_dir().join(format!("sketchybartender-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("local.toml"), "[items.clock]\ninterval = 30\n\n[plugins.weather]\nkey = \"${SKETCHYBARTENDER_TEST_KEY}\"\n").unwrap();
+        fs::write(dir.join("loop.toml"), "include = \"loop.toml\"\n").unwrap();
+        std::env::set_var("SKETCHYBARTENDER_TEST_KEY", "s3cret");
+
+        let contents = "include = \"local.toml\"\n\n[items.clock]\ninterval = 5\n";
+        let (config, diagnostics) = Config::parse_toml_in(contents, &dir);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(config.clock_interval, 30);
+        assert_eq!(config.plugins["weather"]["key"].as_str(), Some("s3cret"));
+
+        let contents = "include = [\"missing.toml\", \"loop.toml\"]\n\n[items.clock.style]\nlabel_font = \"${SKETCHYBARTENDER_UNSET}\"\n";
+        let (_, diagnostics) = Config::parse_toml_in(contents, &dir);
+        let messages: Vec<(usize, &str)> = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect();
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].0 == 1 && messages[0].1.contains("missing.toml"));
+        assert!(messages[1].0 == 1 && messages[1].1.contains("includes itself"));
+        let unset = "items.clock.style.label_font: environment variable SKETCHYBARTENDER_UNSET is not set";
+        assert_eq!(messages[2], (4, unset));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_toml_settings() {
+        type Expected = fn(&Config) -> bool;
+        let settings: &[(&str, Expected)] = &[
+            ("[items.clock]\ninterval = 5\n", |c| c.clock_interval == 5),
+            ("[workspaces]\nimportant_apps = [\"Slack\", \"Safari\"]\n", |c| c.important_apps == ["Slack", "Safari"]),
+            ("[items.clock]\nformat = \"%a %H:%M\"\n", |c| c.clock.format.as_deref() == Some("%a %H:%M")),
+            ("[items.clock]\nseconds = true\n", |c| c.clock.seconds),
+            ("[items.battery]\nthresholds = [80, 60, 30, 5]\n", |c| c.battery.thresholds == [80, 60, 30, 5]),
+            ("[items.battery]\nwarning = 25\n", |c| c.battery.warning == 25),
+            ("[items.battery]\nblink = true\n", |c| c.battery.blink),
+            ("[items.brew]\ngreedy = true\n", |c| c.brew.greedy),
+            ("[items.brew]\nclick = \"confirm\"\n", |c| c.brew.click.to_string() == "confirm"),
+            ("[items.teams]\nprocess = \"Slack\"\n", |c| c.teams.process == "Slack"),
+            ("[items.teams]\nclick_refresh = [2, 5]\n", |c| c.teams.click_refresh == [2, 5]),
+            ("[quiet]\nitems = [\"teams\", \"weather\"]\n", |c| c.quiet.items == ["teams", "weather"]),
+            ("[quiet]\nauto = true\n", |c| c.quiet.auto),
+            ("[items.brew]\njitter = 120\n", |c| c.brew.jitter == 120),
+            ("[items.clock]\nalign = false\n", |c| !c.clock.align),
+            ("[notifications]\nbrew_upgrade = false\n", |c| !c.notifications.brew_upgrade),
+            ("[menubar]\nfallback = \"auto\"\n", |c| c.menubar_fallback == crate::config::MenubarFallback::Auto),
+            (
+                "[hooks]\non_wake = \"~/bin/reconnect-vpn\"\n",
+                |c| c.hooks.on_wake.as_deref() == Some("~/bin/reconnect-vpn"),
+            ),
+            ("[metrics]\nlisten = \"127.0.0.1:9273\"\n", |c| c.metrics_listen == "127.0.0.1:9273".parse().ok()),
+            ("[history]\ngraphs = [\"cpu\"]\n", |c| c.history.graphs == ["cpu"]),
+            ("[history]\nretention = 120\n", |c| c.history.retention == 120),
+            ("[log]\nlevel = \"debug\"\n", |c| c.log_level == crate::config::LogLevel::Debug),
+            ("[log]\nfile = true\n", |c| c.log_file),
+            ("clear_on_exit = true\n", |c| c.clear_on_exit),
+            ("bootstrap_items = true\n", |c| c.bootstrap_items),
+            ("[items.wifi]\ninterval = 60\n", |c| c.wifi_interval == 60),
+            ("[items.network]\ninterface = \"utun\"\n", |c| c.network_interface.matches("utun3")),
+            ("[items.cpu]\ninterval = 10\n", |c| c.cpu_interval == 10),
+            ("[items.ram]\nwarning = 70\n", |c| c.ram.warning == 70),
+            ("[items.ram]\ncritical_color = \"0xffff0000\"\n", |c| c.ram.colors.critical == "0xffff0000"),
+            ("[items.disk]\ngraph_max = 200\n", |c| c.disk_graph_max == 200),
+            ("[items.load]\ncritical = 150\n", |c| c.load.critical == 150),
+            ("[items.top_process]\ninterval = 10\n", |c| c.top_process_interval == 10),
+            ("[items.thermal]\ntemperature = false\n", |c| !c.thermal.temperature),
+            ("[items.battery]\ntime_remaining = false\n", |c| !c.battery.time_remaining),
+            ("[items.battery]\nlow_power_modifier = \"cmd\"\n", |c| c.battery.low_power_modifier.to_string() == "cmd"),
+            ("[items.focus_mode]\nshortcut = \"Focus Toggle\"\n", |c| c.focus_mode.shortcut == "Focus Toggle"),
+            (
+                "[hooks]\non_appearance_change = \"~/bin/theme\"\n",
+                |c| c.hooks.on_appearance_change.as_deref() == Some("~/bin/theme"),
+            ),
+            ("[items.brightness]\nscroll_step = 10\n", |c| c.brightness_scroll_step == 10),
+            ("[items.volume]\nslider_modifier = \"shift\"\n", |c| c.volume_slider_modifier.to_string() == "shift"),
+            ("[items.calendar]\nnames = [\"Work\", \"Family\"]\n", |c| c.calendar.names == ["Work", "Family"]),
+            ("[items.zoom]\ninterval = 5\n", |c| c.zoom_interval == 5),
+            ("[items.clock]\nextra = [\"Asia/Tokyo\"]\n", |c| c.clock.extra == ["Asia/Tokyo"]),
+            ("[items.weather]\nunits = \"imperial\"\n", |c| c.weather.units == crate::config::WeatherUnits::Imperial),
+            ("[items.sun]\ngolden_hour = true\n", |c| c.sun.golden_hour),
+            ("[items.moon]\nlabel = true\n", |c| c.moon_label),
+            ("[items.crypto]\nalerts = [\"bitcoin > 80000\"]\n", |c| c.crypto.alerts[0].breached(81000.0)),
+            ("[items.exchange]\npair = \"gbp/eur\"\n", |c| c.exchange_pair == "GBP/EUR".parse().ok()),
+            (
+                "[items.feeds]\nurls = [\"https://example.com/feed.xml\"]\n",
+                |c| c.feeds.urls == ["https://example.com/feed.xml"],
+            ),
+            (
+                "[items.reviews]\nquery = \"is:pr review-requested:@me\"\n",
+                |c| c.reviews.query == "is:pr review-requested:@me",
+            ),
+            (
+                "[items.gitlab]\nurl = \"https://gitlab.example.com/\"\n",
+                |c| c.gitlab.url == "https://gitlab.example.com",
+            ),
+            ("[items.jira]\nprojects = [\"API\", \"WEB\"]\n", |c| c.jira.projects == ["API", "WEB"]),
+            ("[items.linear]\nteams = [\"ENG\"]\n", |c| c.linear.teams == ["ENG"]),
+        ];
+        for (contents, expected) in settings {
+            let (config, diagnostics) = Config::parse_toml(contents);
+            assert!(diagnostics.is_empty(), "{}: {:?}", contents, diagnostics);
+            assert!(expected(&config), "{}", contents);
+            assert_eq!(Config::parse_toml(&config.to_toml()).0, config, "{}", contents);
+        }
+
+        let invalid = [
+            "[items.clock]\ninterval = 0\n",
+            "[items.clock]\ninterval = \"5\"\n",
+            "[items.clock]\nhours = 13\n",
+            "[items.battery]\nthresholds = [10, 40, 70, 90]\n",
+            "[items.brew]\nclick = \"never\"\n",
+            "[items.teams]\nclick_refresh = [5, 2]\n",
+            "[quiet]\nauto = \"sometimes\"\n",
+            "[items.brew]\njitter = -1\n",
+            "[notifications]\nbrew_upgrade = \"loud\"\n",
+            "[menubar]\nfallback = \"sometimes\"\n",
+            "[hooks]\non_wake = 1.5\n",
+            "[metrics]\nlisten = \"localhost\"\n",
+            "[history]\nretention = 1\n",
+            "[log]\nlevel = \"verbose\"\n",
+            "clear_on_exit = \"later\"\n",
+            "bootstrap_items = \"maybe\"\n",
+            "[items.wifi]\ninterval = 0\n",
+            "[items.network]\ninterface = \"en0 en1\"\n",
+            "[items.cpu]\ninterval = \"often\"\n",
+            "[items.ram]\nwarning = 101\n",
+            "[items.disk]\ngraph_max = 0\n",
+            "[items.load]\ncritical = 1001\n",
+            "[items.top_process]\ninterval = 0\n",
+            "[items.thermal]\ntemperature = \"celsius\"\n",
+            "[items.battery]\ntime_remaining = \"hours\"\n",
+            "[items.battery]\nlow_power_modifier = \"option\"\n",
+            "[items.focus_mode]\nshortcut = \"\"\n",
+            "[hooks]\non_appearance_change = [1.5]\n",
+            "[items.brightness]\nscroll_step = 101\n",
+            "[items.volume]\nslider_modifier = \"fn\"\n",
+            "[items.calendar]\nnames = [1.5]\n",
+            "[items.zoom]\ninterval = 0\n",
+            "[items.clock]\nextra = [\"Mars/Olympus_Mons\"]\n",
+            "[items.weather]\nunits = \"kelvin\"\n",
+            "[items.sun]\ngolden_hour = \"evening\"\n",
+            "[items.moon]\nlabel = \"phase\"\n",
+            "[items.crypto]\nalerts = [\"bitcoin = 80000\"]\n",
+            "[items.exchange]\npair = \"EUR/EUR\"\n",
+            "[items.feeds]\nurls = [\"ftp://example.com/feed\"]\n",
+            "[items.reviews]\nquery = \"is:issue is:open\"\n",
+            "[items.gitlab]\nurl = \"gitlab.example.com\"\n",
+            "[items.jira]\nprojects = [\"api\"]\n",
+            "[items.linear]\nteams = [\"eng\"]\n",
+        ];
+        for contents in invalid {
+            let (_, diagnostics) = Config::parse_toml(contents);
+            assert_eq!(diagnostics.first().map(|d| d.severity), Some(Severity::Error), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn test_legacy_conversion_round_trip() {
+        let legacy = "clock_interval = 5\nimportant_apps = Slack, Safari\nfront_app_display = active\nclamshell_items =\n";
+        let (config, diagnostics) = Config::parse(legacy);
+        assert!(diagnostics.is_empty());
+
+        let (converted, diagnostics) = Config::parse_toml(&config.to_toml());
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(converted, config);
+    }
+}
@@ -0,0 +1,718 @@
+//! Typed sections of the TOML config file
+//!
+//! Every setting is deserialized with its span and only read as its type when
+//! it is applied, so a mistyped value is reported on its own line while the
+//! rest of the file still applies.
+
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use toml::{Spanned, Value};
+
+use super::line_at;
+use crate::config::{badge, battery, crypto, expand, feeds, history, jira, linear, load, reviews, sun};
+use crate::config::{
+    parse_address, parse_color, parse_display, parse_hours, parse_interval, parse_optional, parse_rate,
+    parse_time_zones, parse_url, Config, Diagnostic, Severity,
+};
+
+/// A setting as written and where, read as a `T` when it is applied
+pub(super) struct Setting<T>(Spanned<Value>, PhantomData<T>);
+
+impl<'de, T> Deserialize<'de> for Setting<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Spanned::deserialize(deserializer).map(|value| Setting(value, PhantomData))
+    }
+}
+
+/// The settings of a config file, or of one of its profiles
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [profiles.<name>]")]
+pub(super) struct Settings {
+    strict: Option<Setting<bool>>,
+    clear_on_exit: Option<Setting<bool>>,
+    bootstrap_items: Option<Setting<bool>>,
+    theme: Theme,
+    workspaces: Workspaces,
+    items: Items,
+    quiet: Quiet,
+    notifications: Notifications,
+    menubar: Menubar,
+    hooks: Hooks,
+    metrics: Metrics,
+    history: History,
+    log: Log,
+    displays: Displays,
+    /// Overlays from `[profiles.<name>]`, by name
+    pub(super) profiles: BTreeMap<String, Settings>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [theme]")]
+struct Theme {
+    icon_theme: Option<Setting<String>>,
+    app_icon_images: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [workspaces]")]
+struct Workspaces {
+    icon_order: Option<Setting<String>>,
+    icon_dedup: Option<Setting<String>>,
+    icon_max: Option<Setting<usize>>,
+    important_apps: Option<Setting<Vec<String>>>,
+    display: Option<Setting<String>>,
+}
+
+/// The `[items]` table; item styles are read with the rest of the untyped tables
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items]")]
+struct Items {
+    disabled: Option<Setting<Vec<String>>>,
+    clock: Clock,
+    battery: Battery,
+    brew: Brew,
+    teams: Teams,
+    wifi: Interval,
+    network: Network,
+    cpu: Interval,
+    ram: Ram,
+    disk: Disk,
+    load: Load,
+    top_process: Interval,
+    thermal: Thermal,
+    focus_mode: FocusMode,
+    appearance: Interval,
+    brightness: Brightness,
+    volume: Volume,
+    calendar: Calendar,
+    zoom: Zoom,
+    weather: Weather,
+    sun: Sun,
+    moon: Moon,
+    crypto: Crypto,
+    exchange: Exchange,
+    feeds: Feeds,
+    reviews: Reviews,
+    gitlab: Gitlab,
+    jira: Jira,
+    linear: Linear,
+    front_app: FrontApp,
+}
+
+/// An item with nothing to set but its update interval
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.cpu]")]
+struct Interval {
+    interval: Option<Setting<u64>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.clock]")]
+struct Clock {
+    interval: Option<Setting<u64>>,
+    format: Option<Setting<String>>,
+    hours: Option<Setting<u64>>,
+    weekday: Option<Setting<bool>>,
+    seconds: Option<Setting<bool>>,
+    locale: Option<Setting<String>>,
+    align: Option<Setting<bool>>,
+    extra: Option<Setting<Vec<String>>>,
+    extra_display: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.battery]")]
+struct Battery {
+    interval: Option<Setting<u64>>,
+    thresholds: Option<Setting<Vec<u64>>>,
+    warning: Option<Setting<u64>>,
+    critical: Option<Setting<u64>>,
+    normal_color: Option<Setting<String>>,
+    charging_color: Option<Setting<String>>,
+    warning_color: Option<Setting<String>>,
+    critical_color: Option<Setting<String>>,
+    blink: Option<Setting<bool>>,
+    time_remaining: Option<Setting<bool>>,
+    low_power_color: Option<Setting<String>>,
+    low_power_modifier: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.brew]")]
+struct Brew {
+    interval: Option<Setting<u64>>,
+    path: Option<Setting<String>>,
+    exclude_pinned: Option<Setting<bool>>,
+    greedy: Option<Setting<bool>>,
+    update: Option<Setting<bool>>,
+    click: Option<Setting<String>>,
+    jitter: Option<Setting<u64>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.teams]")]
+struct Teams {
+    interval: Option<Setting<u64>>,
+    process: Option<Setting<String>>,
+    dock_label: Option<Setting<String>>,
+    color: Option<Setting<String>>,
+    badge_color: Option<Setting<String>>,
+    inactive_color: Option<Setting<String>>,
+    border_color: Option<Setting<String>>,
+    click_refresh: Option<Setting<Vec<u64>>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.network]")]
+struct Network {
+    interval: Option<Setting<u64>>,
+    interface: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.ram]")]
+struct Ram {
+    interval: Option<Setting<u64>>,
+    warning: Option<Setting<u64>>,
+    critical: Option<Setting<u64>>,
+    normal_color: Option<Setting<String>>,
+    warning_color: Option<Setting<String>>,
+    critical_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.disk]")]
+struct Disk {
+    interval: Option<Setting<u64>>,
+    graph_max: Option<Setting<u64>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.load]")]
+struct Load {
+    interval: Option<Setting<u64>>,
+    warning: Option<Setting<u64>>,
+    critical: Option<Setting<u64>>,
+    normal_color: Option<Setting<String>>,
+    warning_color: Option<Setting<String>>,
+    critical_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.thermal]")]
+struct Thermal {
+    interval: Option<Setting<u64>>,
+    temperature: Option<Setting<bool>>,
+    normal_color: Option<Setting<String>>,
+    serious_color: Option<Setting<String>>,
+    critical_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.focus_mode]")]
+struct FocusMode {
+    interval: Option<Setting<u64>>,
+    shortcut: Option<Setting<String>>,
+    label: Option<Setting<bool>>,
+    color: Option<Setting<String>>,
+    inactive_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.brightness]")]
+struct Brightness {
+    interval: Option<Setting<u64>>,
+    scroll_step: Option<Setting<u64>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.volume]")]
+struct Volume {
+    scroll_step: Option<Setting<u64>>,
+    slider_modifier: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.calendar]")]
+struct Calendar {
+    interval: Option<Setting<u64>>,
+    names: Option<Setting<Vec<String>>>,
+    soon: Option<Setting<usize>>,
+    color: Option<Setting<String>>,
+    soon_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.zoom]")]
+struct Zoom {
+    interval: Option<Setting<u64>>,
+    muted_color: Option<Setting<String>>,
+    live_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.weather]")]
+struct Weather {
+    interval: Option<Setting<u64>>,
+    source: Option<Setting<String>>,
+    location: Option<Setting<String>>,
+    api_key: Option<Setting<String>>,
+    units: Option<Setting<String>>,
+    max_age: Option<Setting<u64>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.sun]")]
+struct Sun {
+    interval: Option<Setting<u64>>,
+    location: Option<Setting<String>>,
+    golden_hour: Option<Setting<bool>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.moon]")]
+struct Moon {
+    label: Option<Setting<bool>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.crypto]")]
+struct Crypto {
+    interval: Option<Setting<u64>>,
+    coins: Option<Setting<Vec<String>>>,
+    currency: Option<Setting<String>>,
+    alerts: Option<Setting<Vec<String>>>,
+    alert_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.exchange]")]
+struct Exchange {
+    interval: Option<Setting<u64>>,
+    pair: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.feeds]")]
+struct Feeds {
+    interval: Option<Setting<u64>>,
+    urls: Option<Setting<Vec<String>>>,
+    recent: Option<Setting<usize>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.reviews]")]
+struct Reviews {
+    interval: Option<Setting<u64>>,
+    token: Option<Setting<String>>,
+    query: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.gitlab]")]
+struct Gitlab {
+    interval: Option<Setting<u64>>,
+    url: Option<Setting<String>>,
+    token: Option<Setting<String>>,
+    failed_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.jira]")]
+struct Jira {
+    interval: Option<Setting<u64>>,
+    url: Option<Setting<String>>,
+    email: Option<Setting<String>>,
+    token: Option<Setting<String>>,
+    projects: Option<Setting<Vec<String>>>,
+    high_color: Option<Setting<String>>,
+    medium_color: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.linear]")]
+struct Linear {
+    interval: Option<Setting<u64>>,
+    api_key: Option<Setting<String>>,
+    teams: Option<Setting<Vec<String>>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [items.front_app]")]
+struct FrontApp {
+    display: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [quiet]")]
+struct Quiet {
+    items: Option<Setting<Vec<String>>>,
+    auto: Option<Setting<bool>>,
+    share_processes: Option<Setting<Vec<String>>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [notifications]")]
+struct Notifications {
+    battery: Option<Setting<bool>>,
+    brew_upgrade: Option<Setting<bool>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [menubar]")]
+struct Menubar {
+    fallback: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [hooks]")]
+struct Hooks {
+    on_low_battery: Option<Setting<String>>,
+    on_workspace_change: Option<Setting<String>>,
+    on_brew_outdated: Option<Setting<String>>,
+    on_wake: Option<Setting<String>>,
+    on_appearance_change: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [metrics]")]
+struct Metrics {
+    listen: Option<Setting<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [history]")]
+struct History {
+    graphs: Option<Setting<Vec<String>>>,
+    retention: Option<Setting<u64>>,
+    interval: Option<Setting<u64>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [log]")]
+struct Log {
+    level: Option<Setting<String>>,
+    file: Option<Setting<bool>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, expecting = "a table like [displays]")]
+struct Displays {
+    clamshell_items: Option<Setting<Vec<String>>>,
+}
+
+/// Sets config fields from settings, keeping a diagnostic for each that doesn't fit
+struct Applier<'a> {
+    contents: &'a str,
+    prefix: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Applier<'_> {
+    /// Set `target` from a setting at `path`, read as a `T` and checked by `parse`
+    ///
+    /// `parse` gets the setting's key for its messages.
+    fn set<T: DeserializeOwned, U>(
+        &mut self,
+        path: &str,
+        setting: Option<Setting<T>>,
+        target: &mut U,
+        parse: impl FnOnce(&str, T) -> Result<U, String>,
+    ) {
+        let Some(Setting(value, _)) = setting else { return };
+        let line = line_at(self.contents, value.span().start);
+        let key = path.rsplit('.').next().unwrap_or(path);
+
+        let result = expand::expanded(value.into_inner())
+            .and_then(|value| value.try_into::<T>().map_err(|e| e.message().to_string()))
+            .and_then(|value| parse(key, value));
+        match result {
+            Ok(value) => *target = value,
+            Err(message) => self.diagnostics.push(Diagnostic {
+                line,
+                severity: Severity::Error,
+                message: format!("{}{}: {}", self.prefix, path, message),
+            }),
+        }
+    }
+}
+
+/// Take a value as it is
+fn as_is<T>(_: &str, value: T) -> Result<T, String> {
+    Ok(value)
+}
+
+/// Parse a value named like `auto` into its setting
+fn named<T: FromStr<Err = String>>(_: &str, value: String) -> Result<T, String> {
+    value.parse()
+}
+
+/// An optional string, where an empty one means unset
+fn optional(_: &str, value: String) -> Result<Option<String>, String> {
+    Ok(parse_optional(&value))
+}
+
+/// A color, as sketchybar's 0xAARRGGBB
+fn color(key: &str, value: String) -> Result<String, String> {
+    parse_color(key, &value)
+}
+
+impl Settings {
+    /// Apply these settings on top of `config`, returning a diagnostic for each that doesn't fit
+    ///
+    /// `contents` is the TOML the settings were read from, and `prefix` their
+    /// table's position in it (e.g. `profiles.work.`).
+    pub(super) fn apply(self, config: &mut Config, contents: &str, prefix: &str) -> Vec<Diagnostic> {
+        let mut s = Applier { contents, prefix, diagnostics: Vec::new() };
+        let c = config;
+
+        s.set("strict", self.strict, &mut c.strict, as_is);
+        s.set("clear_on_exit", self.clear_on_exit, &mut c.clear_on_exit, as_is);
+        s.set("bootstrap_items", self.bootstrap_items, &mut c.bootstrap_items, as_is);
+
+        let theme = self.theme;
+        s.set("theme.icon_theme", theme.icon_theme, &mut c.icon_theme, named);
+        s.set("theme.app_icon_images", theme.app_icon_images, &mut c.app_icon_images, named);
+
+        let workspaces = self.workspaces;
+        s.set("workspaces.icon_order", workspaces.icon_order, &mut c.workspace_icon_order, named);
+        s.set("workspaces.icon_dedup", workspaces.icon_dedup, &mut c.workspace_icon_dedup, named);
+        s.set("workspaces.icon_max", workspaces.icon_max, &mut c.workspace_icon_max, as_is);
+        s.set("workspaces.important_apps", workspaces.important_apps, &mut c.important_apps, as_is);
+        s.set("workspaces.display", workspaces.display, &mut c.workspace_display, |key, value| {
+            parse_display(key, &value)
+        });
+
+        let items = self.items;
+        s.set("items.disabled", items.disabled, &mut c.disabled_items, as_is);
+
+        let clock = items.clock;
+        s.set("items.clock.interval", clock.interval, &mut c.clock_interval, parse_interval);
+        s.set("items.clock.format", clock.format, &mut c.clock.format, optional);
+        s.set("items.clock.hours", clock.hours, &mut c.clock.hours, parse_hours);
+        s.set("items.clock.weekday", clock.weekday, &mut c.clock.weekday, as_is);
+        s.set("items.clock.seconds", clock.seconds, &mut c.clock.seconds, as_is);
+        s.set("items.clock.locale", clock.locale, &mut c.clock.locale, optional);
+        s.set("items.clock.align", clock.align, &mut c.clock.align, as_is);
+        s.set("items.clock.extra", clock.extra, &mut c.clock.extra, parse_time_zones);
+        s.set("items.clock.extra_display", clock.extra_display, &mut c.clock.extra_display, named);
+
+        let battery = items.battery;
+        s.set("items.battery.interval", battery.interval, &mut c.battery_interval, parse_interval);
+        s.set("items.battery.thresholds", battery.thresholds, &mut c.battery.thresholds, battery::parse_thresholds);
+        s.set("items.battery.warning", battery.warning, &mut c.battery.warning, battery::parse_percentage);
+        s.set("items.battery.critical", battery.critical, &mut c.battery.critical, battery::parse_percentage);
+        s.set("items.battery.normal_color", battery.normal_color, &mut c.battery.normal_color, color);
+        s.set("items.battery.charging_color", battery.charging_color, &mut c.battery.charging_color, color);
+        s.set("items.battery.warning_color", battery.warning_color, &mut c.battery.warning_color, color);
+        s.set("items.battery.critical_color", battery.critical_color, &mut c.battery.critical_color, color);
+        s.set("items.battery.blink", battery.blink, &mut c.battery.blink, as_is);
+        s.set("items.battery.time_remaining", battery.time_remaining, &mut c.battery.time_remaining, as_is);
+        s.set("items.battery.low_power_color", battery.low_power_color, &mut c.battery.low_power_color, color);
+        s.set(
+            "items.battery.low_power_modifier",
+            battery.low_power_modifier,
+            &mut c.battery.low_power_modifier,
+            |key, value| battery::parse_modifier(key, &value),
+        );
+
+        let brew = items.brew;
+        s.set("items.brew.interval", brew.interval, &mut c.brew_interval, parse_interval);
+        s.set("items.brew.path", brew.path, &mut c.brew.path, optional);
+        s.set("items.brew.exclude_pinned", brew.exclude_pinned, &mut c.brew.exclude_pinned, as_is);
+        s.set("items.brew.greedy", brew.greedy, &mut c.brew.greedy, as_is);
+        s.set("items.brew.update", brew.update, &mut c.brew.update, as_is);
+        s.set("items.brew.click", brew.click, &mut c.brew.click, named);
+        s.set("items.brew.jitter", brew.jitter, &mut c.brew.jitter, as_is);
+
+        let teams = items.teams;
+        s.set("items.teams.interval", teams.interval, &mut c.teams_interval, parse_interval);
+        s.set("items.teams.process", teams.process, &mut c.teams.process, |key, value| {
+            badge::parse_name(key, &value)
+        });
+        s.set("items.teams.dock_label", teams.dock_label, &mut c.teams.dock_label, |key, value| {
+            badge::parse_name(key, &value)
+        });
+        s.set("items.teams.color", teams.color, &mut c.teams.color, color);
+        s.set("items.teams.badge_color", teams.badge_color, &mut c.teams.badge_color, color);
+        s.set("items.teams.inactive_color", teams.inactive_color, &mut c.teams.inactive_color, color);
+        s.set("items.teams.border_color", teams.border_color, &mut c.teams.border_color, color);
+        s.set("items.teams.click_refresh", teams.click_refresh, &mut c.teams.click_refresh, badge::parse_schedule);
+
+        s.set("items.wifi.interval", items.wifi.interval, &mut c.wifi_interval, parse_interval);
+        s.set("items.network.interval", items.network.interval, &mut c.network_interval, parse_interval);
+        s.set("items.network.interface", items.network.interface, &mut c.network_interface, named);
+        s.set("items.cpu.interval", items.cpu.interval, &mut c.cpu_interval, parse_interval);
+
+        let ram = items.ram;
+        s.set("items.ram.interval", ram.interval, &mut c.ram_interval, parse_interval);
+        s.set("items.ram.warning", ram.warning, &mut c.ram.warning, battery::parse_percentage);
+        s.set("items.ram.critical", ram.critical, &mut c.ram.critical, battery::parse_percentage);
+        s.set("items.ram.normal_color", ram.normal_color, &mut c.ram.colors.normal, color);
+        s.set("items.ram.warning_color", ram.warning_color, &mut c.ram.colors.warning, color);
+        s.set("items.ram.critical_color", ram.critical_color, &mut c.ram.colors.critical, color);
+
+        s.set("items.disk.interval", items.disk.interval, &mut c.disk_interval, parse_interval);
+        s.set("items.disk.graph_max", items.disk.graph_max, &mut c.disk_graph_max, parse_rate);
+
+        let load = items.load;
+        s.set("items.load.interval", load.interval, &mut c.load_interval, parse_interval);
+        s.set("items.load.warning", load.warning, &mut c.load.warning, load::parse_threshold);
+        s.set("items.load.critical", load.critical, &mut c.load.critical, load::parse_threshold);
+        s.set("items.load.normal_color", load.normal_color, &mut c.load.colors.normal, color);
+        s.set("items.load.warning_color", load.warning_color, &mut c.load.colors.warning, color);
+        s.set("items.load.critical_color", load.critical_color, &mut c.load.colors.critical, color);
+
+        s.set("items.top_process.interval", items.top_process.interval, &mut c.top_process_interval, parse_interval);
+
+        let thermal = items.thermal;
+        s.set("items.thermal.interval", thermal.interval, &mut c.thermal_interval, parse_interval);
+        s.set("items.thermal.temperature", thermal.temperature, &mut c.thermal.temperature, as_is);
+        s.set("items.thermal.normal_color", thermal.normal_color, &mut c.thermal.colors.normal, color);
+        s.set("items.thermal.serious_color", thermal.serious_color, &mut c.thermal.colors.warning, color);
+        s.set("items.thermal.critical_color", thermal.critical_color, &mut c.thermal.colors.critical, color);
+
+        let focus_mode = items.focus_mode;
+        s.set("items.focus_mode.interval", focus_mode.interval, &mut c.focus_mode_interval, parse_interval);
+        s.set("items.focus_mode.shortcut", focus_mode.shortcut, &mut c.focus_mode.shortcut, |key, value| {
+            badge::parse_name(key, &value)
+        });
+        s.set("items.focus_mode.label", focus_mode.label, &mut c.focus_mode.label, as_is);
+        s.set("items.focus_mode.color", focus_mode.color, &mut c.focus_mode.colors.warning, color);
+        s.set("items.focus_mode.inactive_color", focus_mode.inactive_color, &mut c.focus_mode.colors.normal, color);
+
+        s.set("items.appearance.interval", items.appearance.interval, &mut c.appearance_interval, parse_interval);
+
+        let brightness = items.brightness;
+        s.set("items.brightness.interval", brightness.interval, &mut c.brightness_interval, parse_interval);
+        s.set(
+            "items.brightness.scroll_step",
+            brightness.scroll_step,
+            &mut c.brightness_scroll_step,
+            battery::parse_percentage,
+        );
+
+        let volume = items.volume;
+        s.set("items.volume.scroll_step", volume.scroll_step, &mut c.volume_scroll_step, battery::parse_percentage);
+        s.set("items.volume.slider_modifier", volume.slider_modifier, &mut c.volume_slider_modifier, |key, value| {
+            battery::parse_modifier(key, &value)
+        });
+
+        let calendar = items.calendar;
+        s.set("items.calendar.interval", calendar.interval, &mut c.calendar_interval, parse_interval);
+        s.set("items.calendar.names", calendar.names, &mut c.calendar.names, as_is);
+        s.set("items.calendar.soon", calendar.soon, &mut c.calendar.soon, as_is);
+        s.set("items.calendar.color", calendar.color, &mut c.calendar.colors.normal, color);
+        s.set("items.calendar.soon_color", calendar.soon_color, &mut c.calendar.colors.warning, color);
+
+        let zoom = items.zoom;
+        s.set("items.zoom.interval", zoom.interval, &mut c.zoom_interval, parse_interval);
+        s.set("items.zoom.muted_color", zoom.muted_color, &mut c.zoom.colors.critical, color);
+        s.set("items.zoom.live_color", zoom.live_color, &mut c.zoom.colors.normal, color);
+
+        let weather = items.weather;
+        s.set("items.weather.interval", weather.interval, &mut c.weather_interval, parse_interval);
+        s.set("items.weather.source", weather.source, &mut c.weather.source, named);
+        s.set("items.weather.location", weather.location, &mut c.weather.location, as_is);
+        s.set("items.weather.api_key", weather.api_key, &mut c.weather.api_key, optional);
+        s.set("items.weather.units", weather.units, &mut c.weather.units, named);
+        s.set("items.weather.max_age", weather.max_age, &mut c.weather.max_age, as_is);
+
+        let sun = items.sun;
+        s.set("items.sun.interval", sun.interval, &mut c.sun_interval, parse_interval);
+        s.set("items.sun.location", sun.location, &mut c.sun.location, |key, value| {
+            sun::parse_location(key, &value)
+        });
+        s.set("items.sun.golden_hour", sun.golden_hour, &mut c.sun.golden_hour, as_is);
+
+        s.set("items.moon.label", items.moon.label, &mut c.moon_label, as_is);
+
+        let crypto = items.crypto;
+        s.set("items.crypto.interval", crypto.interval, &mut c.crypto_interval, parse_interval);
+        s.set("items.crypto.coins", crypto.coins, &mut c.crypto.coins, crypto::parse_coins);
+        s.set("items.crypto.currency", crypto.currency, &mut c.crypto.currency, |key, value| {
+            crypto::parse_currency(key, &value)
+        });
+        s.set("items.crypto.alerts", crypto.alerts, &mut c.crypto.alerts, |_, value| crypto::parse_alerts(value));
+        s.set("items.crypto.alert_color", crypto.alert_color, &mut c.crypto.alert_color, color);
+
+        let exchange = items.exchange;
+        s.set("items.exchange.interval", exchange.interval, &mut c.exchange_interval, parse_interval);
+        s.set("items.exchange.pair", exchange.pair, &mut c.exchange_pair, |_, value| {
+            parse_optional(&value).map(|pair| pair.parse()).transpose()
+        });
+
+        let feeds = items.feeds;
+        s.set("items.feeds.interval", feeds.interval, &mut c.feeds_interval, parse_interval);
+        s.set("items.feeds.urls", feeds.urls, &mut c.feeds.urls, feeds::parse_urls);
+        s.set("items.feeds.recent", feeds.recent, &mut c.feeds.recent, as_is);
+
+        let reviews = items.reviews;
+        s.set("items.reviews.interval", reviews.interval, &mut c.reviews_interval, parse_interval);
+        s.set("items.reviews.token", reviews.token, &mut c.reviews.token, optional);
+        s.set("items.reviews.query", reviews.query, &mut c.reviews.query, |key, value| {
+            reviews::parse_query(key, &value)
+        });
+
+        let gitlab = items.gitlab;
+        s.set("items.gitlab.interval", gitlab.interval, &mut c.gitlab_interval, parse_interval);
+        s.set("items.gitlab.url", gitlab.url, &mut c.gitlab.url, |key, value| parse_url(key, &value));
+        s.set("items.gitlab.token", gitlab.token, &mut c.gitlab.token, optional);
+        s.set("items.gitlab.failed_color", gitlab.failed_color, &mut c.gitlab.failed_color, color);
+
+        let jira = items.jira;
+        s.set("items.jira.interval", jira.interval, &mut c.jira_interval, parse_interval);
+        s.set("items.jira.url", jira.url, &mut c.jira.url, |key, value| {
+            parse_optional(&value).map(|url| parse_url(key, &url)).transpose()
+        });
+        s.set("items.jira.email", jira.email, &mut c.jira.email, optional);
+        s.set("items.jira.token", jira.token, &mut c.jira.token, optional);
+        s.set("items.jira.projects", jira.projects, &mut c.jira.projects, jira::parse_projects);
+        s.set("items.jira.high_color", jira.high_color, &mut c.jira.high_color, color);
+        s.set("items.jira.medium_color", jira.medium_color, &mut c.jira.medium_color, color);
+
+        let linear = items.linear;
+        s.set("items.linear.interval", linear.interval, &mut c.linear_interval, parse_interval);
+        s.set("items.linear.api_key", linear.api_key, &mut c.linear.api_key, optional);
+        s.set("items.linear.teams", linear.teams, &mut c.linear.teams, linear::parse_teams);
+
+        s.set("items.front_app.display", items.front_app.display, &mut c.front_app_display, |key, value| {
+            parse_display(key, &value)
+        });
+
+        let quiet = self.quiet;
+        s.set("quiet.items", quiet.items, &mut c.quiet.items, as_is);
+        s.set("quiet.auto", quiet.auto, &mut c.quiet.auto, as_is);
+        s.set("quiet.share_processes", quiet.share_processes, &mut c.quiet.share_processes, as_is);
+
+        s.set("notifications.battery", self.notifications.battery, &mut c.notifications.battery, as_is);
+        s.set("notifications.brew_upgrade", self.notifications.brew_upgrade, &mut c.notifications.brew_upgrade, as_is);
+        s.set("menubar.fallback", self.menubar.fallback, &mut c.menubar_fallback, named);
+
+        let hooks = self.hooks;
+        s.set("hooks.on_low_battery", hooks.on_low_battery, &mut c.hooks.on_low_battery, optional);
+        s.set("hooks.on_workspace_change", hooks.on_workspace_change, &mut c.hooks.on_workspace_change, optional);
+        s.set("hooks.on_brew_outdated", hooks.on_brew_outdated, &mut c.hooks.on_brew_outdated, optional);
+        s.set("hooks.on_wake", hooks.on_wake, &mut c.hooks.on_wake, optional);
+        s.set("hooks.on_appearance_change", hooks.on_appearance_change, &mut c.hooks.on_appearance_change, optional);
+
+        s.set("metrics.listen", self.metrics.listen, &mut c.metrics_listen, |key, value| {
+            parse_address(key, &value)
+        });
+
+        let history = self.history;
+        s.set("history.graphs", history.graphs, &mut c.history.graphs, |_, value| history::parse_graphs(value));
+        s.set("history.retention", history.retention, &mut c.history.retention, history::parse_retention);
+        s.set("history.interval", history.interval, &mut c.history.interval, parse_interval);
+
+        s.set("log.level", self.log.level, &mut c.log_level, named);
+        s.set("log.file", self.log.file, &mut c.log_file, as_is);
+        s.set("displays.clamshell_items", self.displays.clamshell_items, &mut c.clamshell_items, as_is);
+
+        s.diagnostics
+    }
+}
@@ -0,0 +1,170 @@
+//! Location, service and units of the weather item from `[items.weather]`
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Where the weather item gets the weather from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WeatherSource {
+    /// wttr.in, which needs no account (default)
+    #[default]
+    Wttr,
+    /// OpenWeatherMap, with an API key
+    OpenWeatherMap,
+}
+
+impl FromStr for WeatherSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wttr" | "wttr.in" => Ok(WeatherSource::Wttr),
+            "openweathermap" => Ok(WeatherSource::OpenWeatherMap),
+            _ => Err(format!("Invalid value for weather_source: {} (expected wttr or openweathermap)", s)),
+        }
+    }
+}
+
+impl fmt::Display for WeatherSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WeatherSource::Wttr => "wttr",
+            WeatherSource::OpenWeatherMap => "openweathermap",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Temperature scale of the weather item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WeatherUnits {
+    /// Degrees Celsius (default)
+    #[default]
+    Metric,
+    /// Degrees Fahrenheit
+    Imperial,
+}
+
+impl WeatherUnits {
+    /// Letter after the degree sign
+    pub fn symbol(self) -> &'static str {
+        match self {
+            WeatherUnits::Metric => "C",
+            WeatherUnits::Imperial => "F",
+        }
+    }
+}
+
+impl FromStr for WeatherUnits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metric" | "celsius" => Ok(WeatherUnits::Metric),
+            "imperial" | "fahrenheit" => Ok(WeatherUnits::Imperial),
+            _ => Err(format!("Invalid value for weather_units: {} (expected metric or imperial)", s)),
+        }
+    }
+}
+
+impl fmt::Display for WeatherUnits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WeatherUnits::Metric => "metric",
+            WeatherUnits::Imperial => "imperial",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Latitude and longitude from a location like `52.52,13.40`, if it is one
+pub fn parse_coordinates(location: &str) -> Option<(f64, f64)> {
+    let (latitude, longitude) = location.split_once(',')?;
+    let latitude = latitude.trim().parse::<f64>().ok().filter(|latitude| (-90.0..=90.0).contains(latitude))?;
+    let longitude = longitude.trim().parse::<f64>().ok().filter(|longitude| (-180.0..=180.0).contains(longitude))?;
+    Some((latitude, longitude))
+}
+
+/// Where and from which service the weather item fetches the weather
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeatherConfig {
+    pub source: WeatherSource,
+    /// A place name like "Berlin" or "lat,lon"; with neither this nor an API key the item stays off
+    pub location: String,
+    /// OpenWeatherMap API key, usually `${secret:NAME}`
+    pub api_key: Option<String>,
+    pub units: WeatherUnits,
+    /// Seconds the last fetched weather is still shown while offline, 0 for no limit (default: 3 hours)
+    pub max_age: u64,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        Self {
+            source: WeatherSource::default(),
+            location: String::new(),
+            api_key: None,
+            units: WeatherUnits::default(),
+            max_age: 3 * 60 * 60,
+        }
+    }
+}
+
+impl WeatherConfig {
+    /// Why the weather can't be fetched with these settings, if it can't
+    pub fn check(&self) -> Result<(), String> {
+        if self.source != WeatherSource::OpenWeatherMap {
+            return Ok(());
+        }
+        if self.api_key.is_none() {
+            return Err("openweathermap needs an api_key in [items.weather]".to_string());
+        }
+        if self.location.is_empty() {
+            return Err("openweathermap needs a location in [items.weather]".to_string());
+        }
+        Ok(())
+    }
+
+    /// The location's latitude and longitude, when it's given as coordinates
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        parse_coordinates(&self.location)
+    }
+
+    /// Whether weather fetched `age` seconds ago is still good to show while offline
+    pub fn usable(&self, age: u64) -> bool {
+        self.max_age == 0 || age <= self.max_age
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        assert!(WeatherConfig::default().check().is_ok());
+
+        let owm = WeatherConfig { source: WeatherSource::OpenWeatherMap, ..WeatherConfig::default() };
+        assert!(owm.check().unwrap_err().contains("api_key"));
+        let keyed = WeatherConfig { api_key: Some("abc".to_string()), ..owm };
+        assert!(keyed.check().unwrap_err().contains("location"));
+        assert!(WeatherConfig { location: "Oslo".to_string(), ..keyed }.check().is_ok());
+    }
+
+    #[test]
+    fn test_parse_coordinates() {
+        assert_eq!(parse_coordinates("52.52,13.40"), Some((52.52, 13.4)));
+        assert_eq!(parse_coordinates(" -33.87, 151.21 "), Some((-33.87, 151.21)));
+        assert_eq!(parse_coordinates("London,GB"), None);
+        assert_eq!(parse_coordinates("91,0"), None);
+        assert_eq!(parse_coordinates("Berlin"), None);
+    }
+
+    #[test]
+    fn test_usable() {
+        let config = WeatherConfig::default();
+        assert!(config.usable(3 * 60 * 60));
+        assert!(!config.usable(3 * 60 * 60 + 1));
+        assert!(WeatherConfig { max_age: 0, ..config }.usable(u64::MAX));
+    }
+}
@@ -0,0 +1,35 @@
+//! Microphone colors of the zoom item from `[items.zoom]`
+
+use super::level::{Level, LevelColors};
+
+/// How the zoom item shows the microphone during a meeting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoomConfig {
+    /// Icon colors: the normal one while the microphone is live, the critical one while it's muted
+    pub colors: LevelColors,
+}
+
+impl Default for ZoomConfig {
+    fn default() -> Self {
+        Self { colors: LevelColors::new("0xffb8bb26", "0xfffb4934", "0xfffb4934") }
+    }
+}
+
+impl ZoomConfig {
+    /// Icon color for whether the microphone is muted
+    pub fn color(&self, muted: bool) -> &str {
+        self.colors.color(if muted { Level::Critical } else { Level::Normal })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        let config = ZoomConfig::default();
+        assert_eq!(config.color(true), "0xfffb4934");
+        assert_eq!(config.color(false), "0xffb8bb26");
+    }
+}
@@ -0,0 +1,73 @@
+//! CPU tick counters from the Mach host
+//!
+//! `host_processor_info` reports, per core, the ticks spent in user, system,
+//! idle and nice time since boot. Usage over an interval is the share of
+//! non-idle ticks between two readings. Outside macOS there are no counters,
+//! and callers fall back to `top`.
+
+#[cfg(target_os = "macos")]
+mod native {
+    type Port = u32;
+
+    /// PROCESSOR_CPU_LOAD_INFO
+    const CPU_LOAD_INFO: i32 = 2;
+    /// CPU_STATE_MAX: user, system, idle and nice
+    const STATES: usize = 4;
+    /// CPU_STATE_IDLE
+    const IDLE: usize = 2;
+
+    extern "C" {
+        static mach_task_self_: Port;
+        fn mach_host_self() -> Port;
+        fn mach_port_deallocate(task: Port, name: Port) -> i32;
+        fn host_processor_info(
+            host: Port,
+            flavor: i32,
+            processor_count: *mut u32,
+            info: *mut *mut i32,
+            info_count: *mut u32,
+        ) -> i32;
+        fn vm_deallocate(task: Port, address: usize, size: usize) -> i32;
+    }
+
+    pub fn ticks() -> Option<(u64, u64)> {
+        unsafe {
+            let host = mach_host_self();
+            let mut processors = 0;
+            let mut info = std::ptr::null_mut();
+            let mut count = 0;
+            let result = host_processor_info(host, CPU_LOAD_INFO, &mut processors, &mut info, &mut count);
+            mach_port_deallocate(mach_task_self_, host);
+            if result != 0 || info.is_null() {
+                return None;
+            }
+
+            let loads = std::slice::from_raw_parts(info, count as usize);
+            let (mut busy, mut total) = (0, 0);
+            for core in loads.chunks_exact(STATES) {
+                for (state, ticks) in core.iter().enumerate() {
+                    // The counters are unsigned ticks in signed integers
+                    let ticks = u64::from(*ticks as u32);
+                    total += ticks;
+                    if state != IDLE {
+                        busy += ticks;
+                    }
+                }
+            }
+            vm_deallocate(mach_task_self_, info as usize, count as usize * std::mem::size_of::<i32>());
+            Some((busy, total))
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn ticks() -> Option<(u64, u64)> {
+        None
+    }
+}
+
+/// Busy and total ticks over every core since boot, or None without the Mach host
+pub fn ticks() -> Option<(u64, u64)> {
+    native::ticks()
+}
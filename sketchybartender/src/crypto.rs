@@ -0,0 +1,195 @@
+//! Crypto prices from CoinGecko's public API
+//!
+//! The `simple/price` endpoint needs no account and answers for all the
+//! configured coins at once, with their change over the last 24 hours. Alerts
+//! go by the prices of the last two fetches, so a price that stays past its
+//! bound flashes the item once rather than at every refresh.
+
+use serde_json::Value;
+use sketchybartender::config::{CryptoAlert, CryptoConfig};
+
+use crate::http;
+
+/// Tickers of the most followed coins; others show their ID in capitals
+const TICKERS: [(&str, &str); 11] = [
+    ("bitcoin", "BTC"),
+    ("ethereum", "ETH"),
+    ("tether", "USDT"),
+    ("binancecoin", "BNB"),
+    ("solana", "SOL"),
+    ("ripple", "XRP"),
+    ("cardano", "ADA"),
+    ("dogecoin", "DOGE"),
+    ("polkadot", "DOT"),
+    ("litecoin", "LTC"),
+    ("monero", "XMR"),
+];
+
+/// Currencies written with a sign before the amount; others get their code after it
+const SIGNS: [(&str, &str); 6] = [
+    ("usd", "$"),
+    ("eur", "€"),
+    ("gbp", "£"),
+    ("jpy", "¥"),
+    ("inr", "₹"),
+    ("btc", "₿"),
+];
+
+/// A coin's price in the configured currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    /// CoinGecko ID, e.g. `bitcoin`
+    pub coin: String,
+    pub price: f64,
+    /// Percent change over the last 24 hours, when CoinGecko has it
+    pub change: Option<f64>,
+}
+
+impl Quote {
+    /// Short name for the label, e.g. `BTC`
+    pub fn ticker(&self) -> String {
+        TICKERS
+            .iter()
+            .find(|(coin, _)| *coin == self.coin)
+            .map_or_else(|| self.coin.to_uppercase(), |(_, ticker)| ticker.to_string())
+    }
+}
+
+/// Digits of a whole number in groups of three, e.g. `67,012`
+fn group(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// A price in `currency`, as precise as it's worth reading: whole units from 1000 up,
+/// cents from 1 up and four significant digits below that
+pub fn format_price(price: f64, currency: &str) -> String {
+    let amount = if price >= 1000.0 {
+        group(price.round() as u64)
+    } else if price >= 1.0 {
+        format!("{:.2}", price)
+    } else if price > 0.0 {
+        let decimals = (-price.log10().floor() as usize + 3).min(12);
+        format!("{:.*}", decimals, price)
+    } else {
+        "0".to_string()
+    };
+    match SIGNS.iter().find(|(code, _)| *code == currency) {
+        Some((_, sign)) => format!("{}{}", sign, amount),
+        None => format!("{} {}", amount, currency.to_uppercase()),
+    }
+}
+
+fn url(config: &CryptoConfig) -> String {
+    format!(
+        "https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies={}&include_24hr_change=true",
+        config.coins.join(","),
+        config.currency
+    )
+}
+
+/// CoinGecko's reply, in the order of the configured coins
+fn parse(reply: &Value, config: &CryptoConfig) -> Result<Vec<Quote>, String> {
+    let currency = &config.currency;
+    config
+        .coins
+        .iter()
+        .map(|coin| {
+            let prices = &reply[coin.as_str()];
+            let price = prices[currency.as_str()]
+                .as_f64()
+                .ok_or_else(|| format!("CoinGecko has no {} price for {}", currency, coin))?;
+            let change = prices[format!("{}_24h_change", currency).as_str()].as_f64();
+            Ok(Quote { coin: coin.clone(), price, change })
+        })
+        .collect()
+}
+
+/// Fetch the prices of the configured coins
+pub fn fetch(config: &CryptoConfig) -> Result<Vec<Quote>, String> {
+    parse(&http::get("CoinGecko", &url(config))?, config)
+}
+
+fn price(quotes: &[Quote], coin: &str) -> Option<f64> {
+    quotes.iter().find(|quote| quote.coin == coin).map(|quote| quote.price)
+}
+
+/// Whether any price is past its bound
+pub fn breached(alerts: &[CryptoAlert], quotes: &[Quote]) -> bool {
+    alerts.iter().any(|alert| price(quotes, &alert.coin).is_some_and(|price| alert.breached(price)))
+}
+
+/// Alerts whose bound the price crossed between the `previous` and the `current` fetch
+pub fn crossed<'a>(alerts: &'a [CryptoAlert], previous: &[Quote], current: &[Quote]) -> Vec<&'a CryptoAlert> {
+    alerts
+        .iter()
+        .filter(|alert| match (price(previous, &alert.coin), price(current, &alert.coin)) {
+            (Some(before), Some(now)) => !alert.breached(before) && alert.breached(now),
+            _ => false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn quote(coin: &str, price: f64) -> Quote {
+        Quote { coin: coin.to_string(), price, change: None }
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = CryptoConfig { coins: vec!["ethereum".to_string(), "bitcoin".to_string()], ..Default::default() };
+        assert!(url(&config).contains("?ids=ethereum,bitcoin&vs_currencies=usd&"));
+        let reply = json!({
+            "bitcoin": {"usd": 67012.4, "usd_24h_change": -1.25},
+            "ethereum": {"usd": 3456},
+        });
+        let quotes = parse(&reply, &config).unwrap();
+        assert_eq!(quotes, [
+            Quote { coin: "ethereum".to_string(), price: 3456.0, change: None },
+            Quote { coin: "bitcoin".to_string(), price: 67012.4, change: Some(-1.25) },
+        ]);
+        assert_eq!(quotes[1].ticker(), "BTC");
+        assert_eq!(quote("shiba-inu", 0.0).ticker(), "SHIBA-INU");
+
+        let eur = CryptoConfig { currency: "eur".to_string(), coins: vec!["bitcoin".to_string()], ..config };
+        assert_eq!(parse(&reply, &eur).unwrap_err(), "CoinGecko has no eur price for bitcoin");
+    }
+
+    #[test]
+    fn test_format_price() {
+        assert_eq!(format_price(67012.4, "usd"), "$67,012");
+        assert_eq!(format_price(1_234_567.0, "eur"), "€1,234,567");
+        assert_eq!(format_price(999.0, "usd"), "$999.00");
+        assert_eq!(format_price(0.0523, "gbp"), "£0.05230");
+        assert_eq!(format_price(0.5, "chf"), "0.5000 CHF");
+        assert_eq!(format_price(0.0, "usd"), "$0");
+    }
+
+    #[test]
+    fn test_alerts() {
+        let alerts: Vec<CryptoAlert> = ["bitcoin > 70000", "bitcoin < 60000", "ethereum < 2000"]
+            .iter()
+            .map(|alert| alert.parse().unwrap())
+            .collect();
+        let before = [quote("bitcoin", 69000.0), quote("ethereum", 1900.0)];
+        let after = [quote("bitcoin", 70500.0), quote("ethereum", 1800.0)];
+        let crossed: Vec<String> = crossed(&alerts, &before, &after).iter().map(|alert| alert.to_string()).collect();
+        assert_eq!(crossed, ["bitcoin > 70000"]);
+        // Only a fetch before the bound was crossed counts
+        assert!(super::crossed(&alerts, &[], &after).is_empty());
+
+        assert!(breached(&alerts, &before));
+        assert!(!breached(&alerts, &[quote("bitcoin", 65000.0)]));
+    }
+}
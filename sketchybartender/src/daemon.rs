@@ -1,59 +1,339 @@
 use std::env;
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::os::unix::net::{UnixListener, UnixStream};
+use std::io::Write;
+use std::os::unix::net::UnixStream as StdUnixStream;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::thread;
+use std::time::UNIX_EPOCH;
 
+use serde_json::Value;
+use sketchybartender::config::{Config, Hook};
+use sketchybartender::icon_map;
+use sketchybartender::protocol::{Request, Response};
+use sketchybartender::{debug, error, info, warn};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task;
+
+use crate::bootstrap;
+use crate::events::{self, Event};
 use crate::handlers::{
     DaemonState,
-    handle_battery_refresh,
+    handle_appearance_click,
+    handle_audio_output_click,
+    handle_audio_output_refresh,
+    handle_audio_output_set,
+    handle_battery_click,
+    handle_bootstrap,
+    handle_brightness_scroll,
+    handle_calendar_click,
+    handle_brew_click,
     handle_brew_upgrade,
+    handle_custom_click,
+    handle_display_change,
+    handle_feeds_read,
+    handle_focus_mode_click,
     handle_focus_refresh,
-    handle_teams_refresh,
+    handle_focus_rules,
+    handle_icons_reload,
+    handle_profile_switch,
+    handle_quiet,
+    handle_rules_refresh,
+    handle_teams_click,
+    handle_volume_click,
+    handle_volume_scroll,
     handle_volume_refresh,
+    handle_volume_set,
     handle_workspace_refresh,
+    handle_zoom_click,
+    Battery,
+    Clock,
+    Moon,
+    Teams,
+    Wifi,
 };
+use crate::hooks;
+use crate::metrics;
+use crate::providers::{self, LastResult, Refresh};
+use crate::quiet;
+use crate::runtime;
+
+/// Stream daemon events to a `sketchycli watch [kind]` client until it disconnects
+fn stream_events(mut stream: StdUnixStream, kind: Option<&str>) {
+    let events = events::subscribe(kind);
+    for line in events {
+        if writeln!(stream, "{}", line).is_err() {
+            break;
+        }
+    }
+}
 
-pub fn handle_client(stream: UnixStream, state: Arc<Mutex<DaemonState>>) {
-    let reader = BufReader::new(stream);
+/// Answer an `icons <query>` message from sketchycli
+fn icons_query(query: &str) -> Result<String, String> {
+    match query.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["unknown"] => Ok(icon_map::unknown_apps().report()),
+        ["unknown", "--json"] => Ok(icon_map::unknown_apps().export_json()),
+        _ => Err(format!("Unknown icons query: {}", query)),
+    }
+}
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(l) => l,
-            Err(_) => break,
-        };
+/// Answer a `status` query with what the bar currently shows
+fn status(state: &Arc<Mutex<DaemonState>>) -> Value {
+    let (front_app, workspace) = match state.lock() {
+        Ok(s) => (Some(s.front_app.clone()).filter(|app| !app.is_empty()), s.focused_workspace.clone()),
+        Err(_) => (None, None),
+    };
+
+    let mut status = metrics::items_json(&metrics::snapshot());
+    status["profile"] = Config::selected_profile().into();
+    status["quiet"] = quiet::active().into();
+    status["front_app"] = front_app.into();
+    status["workspace"] = workspace.into();
+
+    // Providers whose tool keeps failing back off, and their items go stale meanwhile
+    let health: Vec<(&str, providers::Health)> =
+        providers::registered().iter().map(|provider| (provider.name(), providers::health(provider.name()))).collect();
+    status["degraded"] = health.iter().filter(|(_, health)| health.degraded()).map(|(name, _)| *name).collect();
+    status["providers"] = health.iter().map(|(name, health)| (name.to_string(), health.json())).collect();
+    status
+}
+
+/// A recorded result with when it was recorded, as `query` shows it
+fn describe_result(result: &LastResult) -> Value {
+    let mut value = result.value.clone();
+    let since_epoch = result.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    value["refreshed_at"] = since_epoch.as_secs().into();
+    value["age_seconds"] = result.at.elapsed().unwrap_or_default().as_secs().into();
+    value
+}
+
+/// Answer a `query [item]` request with what the item showed at its last refresh, or every item's
+fn query_items(item: Option<&str>) -> Result<Value, String> {
+    let results = providers::last_results();
+    let Some(item) = item else {
+        return Ok(results.iter().map(|(name, result)| (name.clone(), describe_result(result))).collect());
+    };
+    match results.get(item) {
+        Some(result) => Ok(describe_result(result)),
+        None => {
+            let known: Vec<&str> = results.keys().map(String::as_str).collect();
+            Err(format!("Nothing recorded for {}; try one of: {}", item, known.join(", ")))
+        }
+    }
+}
+
+/// Run a JSON request's command, returning what a query found
+fn query(message: &str, state: &Arc<Mutex<DaemonState>>) -> Result<Value, String> {
+    if message == "watch" || message.starts_with("watch ") {
+        return Err("watch streams text lines; send it as a plain message".to_string());
+    }
+    if let Some(query) = message.strip_prefix("icons ") {
+        return icons_query(query).map(Value::from);
+    }
+    if message == "query" || message.starts_with("query ") {
+        return query_items(message.strip_prefix("query ").map(str::trim));
+    }
+    match message {
+        "quiet" => Ok(quiet::status().into()),
+        "status" => Ok(status(state)),
+        _ => dispatch(message, state).map(|()| Value::Null),
+    }
+}
 
-        let parts: Vec<&str> = line.trim().splitn(3, ' ').collect();
-        match parts.get(0).map(|s| *s) {
-            Some("on-volume-changed") => {
-                let vol = parts.get(1).and_then(|s| s.parse().ok());
-                handle_volume_refresh(vol);
+/// Run the handler for a single message, returning an error for unknown messages
+fn dispatch(message: &str, state: &Arc<Mutex<DaemonState>>) -> Result<(), String> {
+    let parts: Vec<&str> = message.splitn(3, ' ').collect();
+    match parts.first().copied() {
+        Some("on-focus-changed") => {
+            handle_focus_refresh(None, state);
+            handle_focus_rules();
+        }
+        Some("on-workspace-changed") => handle_workspace_refresh(state),
+        Some("on-brew-clicked") => handle_brew_click(),
+        Some("brew-upgrade") => handle_brew_upgrade(),
+        Some("trigger-teams-refresh") => Teams.run(),
+        Some("on-teams-clicked") => handle_teams_click(),
+        Some("wifi") => Wifi.run(),
+        Some("on-volume-changed") => {
+            handle_volume_refresh();
+            handle_audio_output_refresh(false);
+        }
+        Some("on-audio-output-clicked") => handle_audio_output_click(),
+        Some("on-focus-mode-clicked") => handle_focus_mode_click()?,
+        Some("on-appearance-clicked") => handle_appearance_click()?,
+        Some("on-calendar-clicked") => handle_calendar_click()?,
+        Some("on-zoom-clicked") => handle_zoom_click()?,
+        Some("feeds-read") => handle_feeds_read()?,
+        // sketchybar's $SCROLL_DELTA, the lines scrolled up or, below zero, down
+        Some("on-brightness-scroll") => {
+            handle_brightness_scroll(parts.get(1).ok_or("Usage: on-brightness-scroll <delta>")?)?
+        }
+        Some("on-volume-scroll") => handle_volume_scroll(parts.get(1).ok_or("Usage: on-volume-scroll <delta>")?)?,
+        // sketchybar's $MODIFIER, if a key was held
+        Some("on-battery-clicked") => handle_battery_click(parts.get(1).copied())?,
+        Some("on-volume-clicked") => handle_volume_click(parts.get(1).copied())?,
+        Some("audio-output") => {
+            let device = parts[1..].join(" ");
+            if device.is_empty() {
+                return Err("Usage: audio-output <device>".to_string());
             }
-            Some("on-focus-changed") => handle_focus_refresh(None, &state),
-            Some("on-workspace-changed") => handle_workspace_refresh(&state),
-            Some("on-brew-clicked") => handle_brew_upgrade(),
-            Some("trigger-teams-refresh") => handle_teams_refresh(),
-            Some("on-display-configuration-changed") => handle_workspace_refresh(&state),
-            Some("on-power-source-changed") => {
-                let power_source = parts.get(1).map(|s| s.to_string());
-                handle_battery_refresh(power_source);
+            handle_audio_output_set(&device)?;
+        }
+        // The bootstrap's listener item, passing on a sketchybar event and its $INFO
+        Some("on-event") => {
+            let event = parts.get(1).ok_or("Usage: on-event <event> [info]")?;
+            let message = bootstrap::message_for(event).ok_or_else(|| format!("No handler for event {}", event))?;
+            match parts.get(2).map(|info| info.trim()).filter(|info| !info.is_empty()) {
+                Some(info) => dispatch(&format!("{} {}", message, info), state)?,
+                None => dispatch(message, state)?,
             }
-            Some("on-system-wake") => {
-                handle_workspace_refresh(&state);
-                handle_battery_refresh(None);
-                crate::handlers::handle_clock_refresh();
-                handle_teams_refresh();
+        }
+        Some("volume") => {
+            let value = parts.get(1).ok_or("Usage: volume <0-100>")?;
+            handle_volume_set(value)?;
+        }
+        Some("on-custom-clicked") => {
+            let name = parts.get(1).ok_or("Usage: on-custom-clicked <item>")?;
+            handle_custom_click(name)?;
+        }
+        Some("on-display-configuration-changed") | Some("refresh-monitors") => handle_display_change(state),
+        Some("on-power-source-changed") => {
+            let power_source = parts.get(1).map(|s| s.to_string());
+            Battery { power_source }.run();
+            handle_rules_refresh();
+        }
+        // Any registered provider, e.g. `refresh clock`
+        Some("refresh") => {
+            let name = parts.get(1).ok_or("Usage: refresh <item>")?;
+            providers::find(name).ok_or_else(|| format!("No provider for {}", name))?.run();
+        }
+        Some("reload-icons") => handle_icons_reload(state),
+        Some("bootstrap") => handle_bootstrap(state),
+        Some("quiet") => {
+            let command = parts.get(1).ok_or("Usage: quiet on|off|toggle|auto")?;
+            handle_quiet(command)?;
+        }
+        Some("profile") => {
+            let name = parts.get(1).ok_or("Usage: profile <name>")?;
+            handle_profile_switch(name, state)?;
+        }
+        Some("on-system-wake") => {
+            handle_display_change(state);
+            Battery::default().run();
+            Clock.run();
+            Teams.run();
+            Wifi.run();
+            Moon.run();
+            handle_rules_refresh();
+            hooks::run(Hook::Wake, Vec::new());
+        }
+        _ => return Err(format!("Unknown message: {}", message)),
+    }
+    Ok(())
+}
+
+/// Write one reply line, returning whether the client is still there
+async fn reply(writer: &mut OwnedWriteHalf, line: &str) -> bool {
+    writer.write_all(format!("{}\n", line).as_bytes()).await.is_ok()
+}
+
+pub async fn handle_client(stream: UnixStream, state: Arc<Mutex<DaemonState>>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    // Shutting down closes the connection, even one waiting for its next line
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line,
+            _ = runtime::stopped() => break,
+        };
+        let Ok(Some(line)) = line else {
+            break;
+        };
+        debug!("[SOCKET] Received {}", line.trim());
+        // JSON requests always get a JSON reply, and the connection stays open for more
+        if line.trim_start().starts_with('{') {
+            let response = match Request::parse(line.trim()) {
+                Ok(request) => {
+                    let message = request.message();
+                    events::publish(Event::Message(message.clone()));
+                    let handler_state = Arc::clone(&state);
+                    let result = task::spawn_blocking(move || query(&message, &handler_state))
+                        .await
+                        .unwrap_or_else(|e| Err(format!("Handler failed: {}", e)));
+                    if let Err(e) = &result {
+                        warn!("{}", e);
+                    }
+                    Response { id: Some(request.id), result }
+                }
+                Err(response) => response,
+            };
+            if !reply(&mut writer, &response.to_json()).await {
+                break;
             }
-            _ => {
-                eprintln!("Unknown message: {}", line);
+            continue;
+        }
+
+        events::publish(Event::Message(line.trim().to_string()));
+
+        // "sync <message>" asks for an acknowledgement once the handler has finished
+        let (wait, message) = match line.trim().strip_prefix("sync ") {
+            Some(rest) => (true, rest.trim().to_string()),
+            None => (false, line.trim().to_string()),
+        };
+
+        // Watching blocks on the event channel for as long as the client stays, so it gets a blocking thread
+        if message == "watch" || message.starts_with("watch ") {
+            let kind = message.strip_prefix("watch ").map(|kind| kind.trim().to_string());
+            let stream = lines.into_inner().into_inner().reunite(writer).map(UnixStream::into_std);
+            match stream {
+                Ok(Ok(stream)) if stream.set_nonblocking(false).is_ok() => {
+                    let _ = task::spawn_blocking(move || stream_events(stream, kind.as_deref())).await;
+                }
+                _ => warn!("Failed to hand the client over to the event stream"),
+            }
+            return;
+        }
+
+        // Queries reply with their output and close the connection
+        if let Some(query) = message.strip_prefix("icons ") {
+            let query = query.to_string();
+            let output = task::spawn_blocking(move || icons_query(&query))
+                .await
+                .unwrap_or_else(|e| Err(e.to_string()))
+                .unwrap_or_else(|e| format!("error {}", e));
+            let _ = reply(&mut writer, &output).await;
+            return;
+        }
+        if message == "quiet" {
+            let _ = reply(&mut writer, &quiet::status()).await;
+            return;
+        }
+
+        // Handlers block on external commands, so they run on the blocking pool
+        let handler_state = Arc::clone(&state);
+        let result = task::spawn_blocking(move || dispatch(&message, &handler_state))
+            .await
+            .unwrap_or_else(|e| Err(format!("Handler failed: {}", e)));
+        if let Err(e) = &result {
+            warn!("{}", e);
+        }
+
+        if wait {
+            let line = match result {
+                Ok(()) => "ok".to_string(),
+                Err(e) => format!("error {}", e),
+            };
+            if !reply(&mut writer, &line).await {
+                break;
             }
         }
     }
 }
 
-pub fn get_socket_path() -> PathBuf {
+/// Directory for the socket and other runtime files
+pub fn get_cache_dir() -> PathBuf {
     let cache_dir = env::var("XDG_CACHE_HOME")
         .map(PathBuf::from)
         .unwrap_or_else(|_| {
@@ -61,10 +341,15 @@ pub fn get_socket_path() -> PathBuf {
             PathBuf::from(home).join(".cache")
         });
 
-    cache_dir.join("sketchybar").join("helper.sock")
+    cache_dir.join("sketchybar")
+}
+
+pub fn get_socket_path() -> PathBuf {
+    get_cache_dir().join("helper.sock")
 }
 
-pub fn start_daemon() {
+/// Listen on the socket, handling each client in a task of its own
+pub async fn serve(state: Arc<Mutex<DaemonState>>) {
     let socket_path = get_socket_path();
 
     // Ensure parent directory exists
@@ -76,23 +361,28 @@ pub fn start_daemon() {
     let _ = fs::remove_file(&socket_path);
 
     // Create listener
-    let listener = UnixListener::bind(&socket_path).expect("Failed to bind socket");
-    println!("Sketchybar helper daemon listening on {:?}", socket_path);
-
-    // Shared state
-    let state = Arc::new(Mutex::new(DaemonState::default()));
-
-    // Accept connections
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let state = Arc::clone(&state);
-                thread::spawn(move || {
-                    handle_client(stream, state);
-                });
+    let listener = match UnixListener::bind(&socket_path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            // The main thread is busy with the run loop, so a panic here wouldn't stop the daemon
+            error!("Failed to bind socket: {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Sketchybar helper daemon listening on {:?}", socket_path);
+
+    // Accept connections until shutdown, which removes the socket
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = runtime::stopped() => break,
+        };
+        match accepted {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_client(stream, Arc::clone(&state)));
             }
             Err(e) => {
-                eprintln!("Connection error: {}", e);
+                error!("Connection error: {}", e);
             }
         }
     }
@@ -0,0 +1,119 @@
+//! Disk byte counters from IOKit
+//!
+//! Every block storage driver keeps a `Statistics` dictionary in the I/O
+//! Registry with the bytes read and written since it attached, the numbers
+//! `iostat` works from. Summing the drivers counts every disk, including
+//! external ones and mounted disk images. Outside macOS there is no registry,
+//! and callers fall back to `ioreg`.
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    /// kIOMainPortDefault
+    pub const MAIN_PORT: u32 = 0;
+    /// kCFStringEncodingUTF8
+    pub const UTF8: u32 = 0x0800_0100;
+    /// kCFNumberSInt64Type
+    pub const SINT64: isize = 4;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        pub fn IOServiceMatching(name: *const c_char) -> *mut c_void;
+        pub fn IOServiceGetMatchingServices(main_port: u32, matching: *mut c_void, iterator: *mut u32) -> i32;
+        pub fn IOIteratorNext(iterator: u32) -> u32;
+        pub fn IORegistryEntryCreateCFProperty(
+            entry: u32,
+            key: *const c_void,
+            allocator: *const c_void,
+            options: u32,
+        ) -> *const c_void;
+        pub fn IOObjectRelease(object: u32) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFStringCreateWithCString(allocator: *const c_void, string: *const c_char, encoding: u32)
+            -> *const c_void;
+        pub fn CFDictionaryGetValue(dictionary: *const c_void, key: *const c_void) -> *const c_void;
+        pub fn CFNumberGetValue(number: *const c_void, kind: isize, value: *mut c_void) -> u8;
+        pub fn CFRelease(object: *const c_void);
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::{c_void, CStr};
+
+    use super::ffi;
+
+    fn cf_string(string: &CStr) -> *const c_void {
+        unsafe { ffi::CFStringCreateWithCString(std::ptr::null(), string.as_ptr(), ffi::UTF8) }
+    }
+
+    /// A number in a statistics dictionary, 0 if it's missing
+    fn number(statistics: *const c_void, key: *const c_void) -> u64 {
+        let mut value: i64 = 0;
+        unsafe {
+            let number = ffi::CFDictionaryGetValue(statistics, key);
+            if number.is_null()
+                || ffi::CFNumberGetValue(number, ffi::SINT64, &mut value as *mut i64 as *mut c_void) == 0
+            {
+                return 0;
+            }
+        }
+        value.max(0) as u64
+    }
+
+    pub fn counters() -> Option<(u64, u64)> {
+        let mut iterator = 0;
+        // The matching dictionary is consumed by the call
+        let result = unsafe {
+            ffi::IOServiceGetMatchingServices(
+                ffi::MAIN_PORT,
+                ffi::IOServiceMatching(c"IOBlockStorageDriver".as_ptr()),
+                &mut iterator,
+            )
+        };
+        if result != 0 {
+            return None;
+        }
+
+        let statistics_key = cf_string(c"Statistics");
+        let read_key = cf_string(c"Bytes (Read)");
+        let write_key = cf_string(c"Bytes (Write)");
+        let (mut read, mut written) = (0, 0);
+        unsafe {
+            loop {
+                let driver = ffi::IOIteratorNext(iterator);
+                if driver == 0 {
+                    break;
+                }
+                let statistics = ffi::IORegistryEntryCreateCFProperty(driver, statistics_key, std::ptr::null(), 0);
+                if !statistics.is_null() {
+                    read += number(statistics, read_key);
+                    written += number(statistics, write_key);
+                    ffi::CFRelease(statistics);
+                }
+                ffi::IOObjectRelease(driver);
+            }
+            ffi::IOObjectRelease(iterator);
+            for key in [statistics_key, read_key, write_key] {
+                ffi::CFRelease(key);
+            }
+        }
+        Some((read, written))
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn counters() -> Option<(u64, u64)> {
+        None
+    }
+}
+
+/// Bytes read and written over every disk, or None without IOKit
+pub fn counters() -> Option<(u64, u64)> {
+    native::counters()
+}
@@ -0,0 +1,92 @@
+//! Display reconfiguration notifications from CoreGraphics
+//!
+//! CoreGraphics delivers reconfiguration callbacks through a run loop, so a
+//! dedicated thread registers the callback and runs its own CFRunLoop. The C
+//! callback only sends on a channel; another thread waits for the burst of
+//! per-display events to settle and then runs the Rust callback once.
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use sketchybartender::warn;
+
+/// Quiet period after the last reconfiguration event before the callback runs
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Sending end of the event channel, set by `listen`
+static EVENTS: OnceLock<Mutex<Sender<()>>> = OnceLock::new();
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::c_void;
+
+    /// kCGDisplayBeginConfigurationFlag: sent before a change, followed by the real event
+    pub const BEGIN_CONFIGURATION: u32 = 1 << 0;
+
+    pub type ReconfigurationCallback = extern "C" fn(display: u32, flags: u32, user_info: *mut c_void);
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        pub fn CGDisplayRegisterReconfigurationCallback(
+            callback: ReconfigurationCallback,
+            user_info: *mut c_void,
+        ) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFRunLoopRun();
+    }
+}
+
+#[cfg(target_os = "macos")]
+extern "C" fn on_reconfiguration(_display: u32, flags: u32, _user_info: *mut std::ffi::c_void) {
+    if flags & ffi::BEGIN_CONFIGURATION != 0 {
+        return;
+    }
+
+    if let Some(Ok(events)) = EVENTS.get().map(|events| events.lock()) {
+        let _ = events.send(());
+    }
+}
+
+/// Register for display reconfiguration events on a dedicated run-loop thread
+#[cfg(target_os = "macos")]
+fn register() {
+    thread::spawn(|| {
+        let error = unsafe {
+            ffi::CGDisplayRegisterReconfigurationCallback(on_reconfiguration, std::ptr::null_mut())
+        };
+        if error != 0 {
+            sketchybartender::error!("Failed to register display reconfiguration callback: {}", error);
+            return;
+        }
+
+        // Never returns while the callback is registered
+        unsafe { ffi::CFRunLoopRun() };
+    });
+}
+
+#[cfg(not(target_os = "macos"))]
+fn register() {}
+
+/// Run `callback` on a background thread after displays are added, removed or rearranged
+pub fn listen(callback: impl Fn() + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+    if EVENTS.set(Mutex::new(sender)).is_err() {
+        warn!("Display reconfiguration listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            // Docking fires one event per display; wait for the burst to end
+            while receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+            callback();
+        }
+    });
+
+    register();
+}
@@ -0,0 +1,150 @@
+//! Internal event bus for observing daemon activity (used by `sketchycli watch`)
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use sketchybartender::config::BatteryLevel;
+use sketchybartender::monitor_map::DisplayChange;
+
+/// Something that happened inside the daemon
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A message received on the daemon socket
+    Message(String),
+    /// A provider finished collecting data
+    Provider { name: &'static str, elapsed: Duration },
+    /// Arguments of a sketchybar invocation
+    Sketchybar(String),
+    /// A display was added or removed, or the main display moved
+    Display(DisplayChange),
+    /// The battery dropped to the warning or critical level
+    BatteryAlert { level: BatteryLevel, percentage: u8 },
+}
+
+impl Event {
+    /// Category name, as shown by `sketchycli watch` and used to filter it
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::Message(_) => "message",
+            Event::Provider { .. } => "provider",
+            Event::Sketchybar(_) => "sketchybar",
+            Event::Display(_) => "display",
+            Event::BatteryAlert { .. } => "battery",
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Message(line) => write!(f, "message    {}", line),
+            Event::Provider { name, elapsed } => {
+                write!(f, "provider   {} ({:.1}ms)", name, elapsed.as_secs_f64() * 1000.0)
+            }
+            Event::Sketchybar(args) => write!(f, "sketchybar {}", args),
+            Event::Display(change) => write!(f, "display    {}", change),
+            Event::BatteryAlert { level, percentage } => write!(f, "battery    {} at {}%", level, percentage),
+        }
+    }
+}
+
+type Listener = Arc<dyn Fn(&Event) + Send + Sync>;
+
+struct Bus {
+    started_at: Instant,
+    /// `sketchycli watch` clients, with the event kind they asked for
+    subscribers: Vec<(Option<String>, Sender<String>)>,
+    /// In-process handlers, e.g. providers reacting to display changes
+    listeners: Vec<Listener>,
+}
+
+fn bus() -> &'static Mutex<Bus> {
+    static BUS: OnceLock<Mutex<Bus>> = OnceLock::new();
+    BUS.get_or_init(|| {
+        Mutex::new(Bus {
+            started_at: Instant::now(),
+            subscribers: Vec::new(),
+            listeners: Vec::new(),
+        })
+    })
+}
+
+/// Subscribe to future events of one kind (or all of them), formatted as timestamped lines
+pub fn subscribe(kind: Option<&str>) -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    if let Ok(mut bus) = bus().lock() {
+        bus.subscribers.push((kind.map(str::to_string), tx));
+    }
+    rx
+}
+
+/// Whether a subscriber filtering on `filter` gets events of `kind`
+fn wants(filter: Option<&str>, kind: &str) -> bool {
+    filter.is_none_or(|filter| filter == kind)
+}
+
+/// Call `f` for every future event, on the thread that publishes it
+pub fn listen(f: impl Fn(&Event) + Send + Sync + 'static) {
+    if let Ok(mut bus) = bus().lock() {
+        bus.listeners.push(Arc::new(f));
+    }
+}
+
+/// Whether anyone is listening, so callers can skip building expensive events
+pub fn is_watched() -> bool {
+    bus().lock().map(|b| !b.subscribers.is_empty()).unwrap_or(false)
+}
+
+/// Publish an event to every subscriber and listener, dropping subscribers that went away
+pub fn publish(event: Event) {
+    let listeners = match bus().lock() {
+        Ok(mut bus) => {
+            if !bus.subscribers.is_empty() {
+                let line = format!("[{:>10.3}] {}", bus.started_at.elapsed().as_secs_f64(), event);
+                let kind = event.kind();
+                bus.subscribers.retain(|(filter, tx)| {
+                    !wants(filter.as_deref(), kind) || tx.send(line.clone()).is_ok()
+                });
+            }
+            bus.listeners.clone()
+        }
+        Err(_) => return,
+    };
+
+    // Outside the lock, so listeners can publish events themselves
+    for listener in listeners {
+        listener(&event);
+    }
+}
+
+/// Run a provider function and publish how long it took
+pub fn timed<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    publish(Event::Provider { name, elapsed: start.elapsed() });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_reaches_subscriber() {
+        let rx = subscribe(None);
+        publish(Event::Message("on-focus-changed".to_string()));
+
+        let line = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(line.ends_with("message    on-focus-changed"));
+    }
+
+    #[test]
+    fn test_subscriber_filter() {
+        let event = Event::Provider { name: "volume", elapsed: Duration::from_millis(2) };
+        assert!(wants(None, event.kind()));
+        assert!(wants(Some("provider"), event.kind()));
+        assert!(!wants(Some("display"), event.kind()));
+    }
+}
@@ -0,0 +1,88 @@
+//! Exchange rates from Frankfurter, which passes on the European Central Bank's reference rates
+//!
+//! The ECB publishes new rates once a working day, around 16:00 in Frankfurt,
+//! so a few fetches a day keep up. The last rate is cached, and shown for as
+//! long as fetching fails: yesterday's rate is still close enough to go by.
+
+use serde_json::{json, Value};
+use sketchybartender::config::CurrencyPair;
+use sketchybartender::warn;
+
+use crate::http;
+
+/// A pair's rate and the day it's for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rate {
+    /// What one unit of the base currency costs in the quote currency
+    pub rate: f64,
+    /// Day the ECB set the rate, as `YYYY-MM-DD`
+    pub date: String,
+    /// Fetching failed just now, so this is the last rate that came through
+    pub stale: bool,
+}
+
+impl Rate {
+    /// The rate to four decimals, or two from 100 up, e.g. `1.0876` or `162.35`
+    pub fn format(&self) -> String {
+        if self.rate >= 100.0 {
+            format!("{:.2}", self.rate)
+        } else {
+            format!("{:.4}", self.rate)
+        }
+    }
+}
+
+fn url(pair: &CurrencyPair) -> String {
+    format!("https://api.frankfurter.dev/v1/latest?base={}&symbols={}", pair.base, pair.quote)
+}
+
+/// Frankfurter's `latest` reply
+fn parse(reply: &Value, pair: &CurrencyPair) -> Option<Rate> {
+    Some(Rate {
+        rate: reply["rates"][pair.quote.as_str()].as_f64()?,
+        date: reply["date"].as_str()?.to_string(),
+        stale: false,
+    })
+}
+
+impl http::Cached for Rate {
+    fn from_cache(cache: &Value) -> Option<Self> {
+        Some(Rate { rate: cache["rate"].as_f64()?, date: cache["date"].as_str()?.to_string(), stale: false })
+    }
+
+    fn to_cache(&self) -> Value {
+        json!({"rate": self.rate, "date": self.date})
+    }
+}
+
+/// The rate of the pair, from the cache when it was fetched less than `interval` seconds ago,
+/// and from the last rate that came through while fetching fails
+pub fn current(pair: &CurrencyPair, interval: u64) -> Result<Rate, String> {
+    let fetched = http::cached("exchange", &pair.to_string(), interval, |_| true, || {
+        http::get("Frankfurter", &url(pair))
+            .and_then(|reply| parse(&reply, pair).ok_or_else(|| format!("Frankfurter has no rate for {}", pair)))
+    })?;
+    match fetched {
+        http::Fetched::Fresh(rate) => Ok(rate),
+        http::Fetched::Stale(rate, e) => {
+            warn!("[EXCHANGE] {}; showing the rate of {}", e, rate.date);
+            Ok(Rate { stale: true, ..rate })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let pair: CurrencyPair = "EUR/JPY".parse().unwrap();
+        assert_eq!(url(&pair), "https://api.frankfurter.dev/v1/latest?base=EUR&symbols=JPY");
+        let reply = json!({"amount": 1.0, "base": "EUR", "date": "2026-10-16", "rates": {"JPY": 162.347}});
+        let rate = parse(&reply, &pair).unwrap();
+        assert_eq!((rate.date.as_str(), rate.format()), ("2026-10-16", "162.35".to_string()));
+        assert_eq!(Rate { rate: 1.08761, ..rate }.format(), "1.0876");
+        assert_eq!(parse(&reply, &"EUR/USD".parse().unwrap()), None);
+    }
+}
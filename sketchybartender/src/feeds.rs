@@ -0,0 +1,392 @@
+//! New entries in RSS and Atom feeds
+//!
+//! Feeds are read with a few string searches rather than an XML parser: all
+//! the item needs is each entry's title, link and date. An entry is new when
+//! it's dated after the feeds were last marked read, which is kept in the
+//! cache directory so a restart doesn't bring old entries back.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::path::PathBuf;
+
+use sketchybartender::warn;
+
+use crate::{calendar, daemon, http, sun};
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+/// Offsets in hours of the zone names RFC 822 dates may have
+const ZONES: [(&str, i64); 12] = [
+    ("GMT", 0),
+    ("UT", 0),
+    ("UTC", 0),
+    ("Z", 0),
+    ("EST", -5),
+    ("EDT", -4),
+    ("CST", -6),
+    ("CDT", -5),
+    ("MST", -7),
+    ("MDT", -6),
+    ("PST", -8),
+    ("PDT", -7),
+];
+
+/// One entry of a feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Title of the feed it's from
+    pub feed: String,
+    pub title: String,
+    pub link: String,
+    /// Unix time it was published, or 0 when the feed doesn't say
+    pub at: i64,
+}
+
+/// The entries of all the feeds, newest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feeds {
+    pub entries: Vec<Entry>,
+    /// Unix time the feeds were last marked read
+    pub seen: i64,
+}
+
+impl Feeds {
+    pub fn is_unread(&self, entry: &Entry) -> bool {
+        entry.at > self.seen
+    }
+
+    /// Entries published since the feeds were last marked read
+    pub fn unread(&self) -> usize {
+        self.entries.iter().filter(|entry| self.is_unread(entry)).count()
+    }
+}
+
+/// An element's attributes and what's between its tags
+struct Element<'a> {
+    attributes: &'a str,
+    content: &'a str,
+}
+
+/// Every `<name>` element, nested or not
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<Element<'a>> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // `<link` mustn't find `<linkage>`
+        if !after.starts_with(['>', '/', ' ', '\t', '\r', '\n']) {
+            rest = after;
+            continue;
+        }
+        let Some(end) = after.find('>') else { break };
+        let (attributes, after_tag) = (&after[..end], &after[end + 1..]);
+        if let Some(attributes) = attributes.strip_suffix('/') {
+            found.push(Element { attributes, content: "" });
+            rest = after_tag;
+        } else {
+            let Some(close_at) = after_tag.find(&close) else { break };
+            found.push(Element { attributes, content: &after_tag[..close_at] });
+            rest = &after_tag[close_at + close.len()..];
+        }
+    }
+    found
+}
+
+/// The value of an attribute, as in `rel="alternate"`
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    while let Some(index) = rest.find(name) {
+        let after = &rest[index + name.len()..];
+        let whole = rest[..index].ends_with(char::is_whitespace) || index == 0;
+        if let Some(value) = after.trim_start().strip_prefix('=').filter(|_| whole) {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|quote| *quote == '"' || *quote == '\'')?;
+            let value = &value[1..];
+            return value.find(quote).map(|end| &value[..end]);
+        }
+        rest = after;
+    }
+    None
+}
+
+/// Replace the predefined and numeric character references
+fn decode(s: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        let after = &rest[amp + 1..];
+        let entity = after.find(';').filter(|end| *end <= 8).map(|end| &after[..end]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#')?.parse().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (character, entity) {
+            (Some(character), Some(entity)) => {
+                decoded.push(character);
+                rest = &after[entity.len() + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = after;
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// An element's text, with CDATA sections unwrapped, references decoded and whitespace collapsed
+fn text(content: &str) -> String {
+    let mut text = String::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("<![CDATA[") {
+        text.push_str(&decode(&rest[..start]));
+        let data = &rest[start + "<![CDATA[".len()..];
+        let end = data.find("]]>").unwrap_or(data.len());
+        text.push_str(&data[..end]);
+        rest = data.get(end + "]]>".len()..).unwrap_or_default();
+    }
+    text.push_str(&decode(rest));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// An RFC 822 date as RSS has them, e.g. `Fri, 16 Oct 2026 09:30:00 +0200`
+fn parse_rfc822(date: &str) -> Option<i64> {
+    let date = date.split_once(',').map_or(date, |(_, date)| date);
+    let mut parts = date.split_whitespace();
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?.get(..3)?.to_ascii_lowercase();
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    // Two-digit years are from RSS 0.91
+    let year = if year < 100 { year + 2000 } else { year };
+    let mut time = parts.next()?.split(':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next().unwrap_or(Some(0))?);
+    let offset = match parts.next() {
+        Some(zone) if zone.starts_with(['+', '-']) => sun::parse_offset(zone)?,
+        // Unknown zones are military letters, which RFC 1123 says to take as UTC
+        Some(zone) => ZONES.iter().find(|(name, _)| *name == zone).map_or(0, |(_, hours)| hours * 3600),
+        None => 0,
+    };
+    Some(calendar::days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds - offset)
+}
+
+/// An RFC 3339 date as Atom has them, e.g. `2026-10-16T09:30:00+02:00`
+fn parse_rfc3339(date: &str) -> Option<i64> {
+    let (day, time) = date.trim().split_once(['T', 't', ' '])?;
+    let mut day = day.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (day.next()??, day.next()??, day.next()??);
+    let (clock, zone) = time.split_at(time.find(['Z', 'z', '+', '-']).unwrap_or(time.len()));
+    let mut clock = clock.split(':');
+    let hours: i64 = clock.next()?.parse().ok()?;
+    let minutes: i64 = clock.next()?.parse().ok()?;
+    // Fractions of a second don't matter here
+    let seconds: i64 = clock.next().map_or(Some(0), |seconds| seconds.split('.').next()?.parse().ok())?;
+    let offset = match zone {
+        "" | "Z" | "z" => 0,
+        zone => sun::parse_offset(&zone.replace(':', ""))?,
+    };
+    Some(calendar::days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds - offset)
+}
+
+/// The link of an RSS item or Atom entry: RSS has it as text, Atom as the `href` of its alternate link
+fn link(entry: &str) -> Option<String> {
+    let links = elements(entry, "link");
+    let link = links.iter().find_map(|link| {
+        if attribute(link.attributes, "rel").is_some_and(|rel| rel != "alternate") {
+            return None;
+        }
+        attribute(link.attributes, "href").map(decode).or_else(|| Some(text(link.content)))
+    });
+    // RSS items may have only a permalink guid
+    let guid = || elements(entry, "guid").first().map(|guid| text(guid.content));
+    link.filter(|link| !link.is_empty()).or_else(|| guid().filter(|guid| guid.starts_with("http")))
+}
+
+/// The entries of an RSS or Atom feed, in the feed's order
+fn parse(xml: &str) -> Vec<Entry> {
+    let mut entries = elements(xml, "item");
+    if entries.is_empty() {
+        entries = elements(xml, "entry");
+    }
+    // The feed's title comes before the first entry, which has titles of its own
+    let head = [xml.find("<item"), xml.find("<entry")].into_iter().flatten().min().unwrap_or(xml.len());
+    let feed = elements(&xml[..head], "title").first().map(|title| text(title.content)).unwrap_or_default();
+
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let title = elements(entry.content, "title").first().map(|title| text(title.content)).unwrap_or_default();
+            let link = link(entry.content).unwrap_or_default();
+            if title.is_empty() && link.is_empty() {
+                return None;
+            }
+            let at = ["pubDate", "published", "dc:date", "updated"]
+                .iter()
+                .find_map(|name| elements(entry.content, name).first().map(|date| text(date.content)))
+                .and_then(|date| parse_rfc3339(&date).or_else(|| parse_rfc822(&date)))
+                .unwrap_or(0);
+            Some(Entry { feed: feed.clone(), title: if title.is_empty() { link.clone() } else { title }, link, at })
+        })
+        .collect()
+}
+
+fn seen_path() -> PathBuf {
+    daemon::get_cache_dir().join("feeds_seen")
+}
+
+/// Mark everything published up to Unix time `at` as read
+fn mark_read_at(at: i64) -> Result<(), String> {
+    let path = seen_path();
+    fs::write(&path, at.to_string()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Mark every entry so far as read
+pub fn mark_read() -> Result<(), String> {
+    mark_read_at(http::now() as i64)
+}
+
+/// When the feeds were last marked read; the first check marks them, so only entries
+/// published after the item was set up count
+fn seen() -> i64 {
+    if let Some(at) = fs::read_to_string(seen_path()).ok().and_then(|seen| seen.trim().parse().ok()) {
+        return at;
+    }
+    let now = http::now() as i64;
+    if let Err(e) = mark_read_at(now) {
+        warn!("[FEEDS] {}", e);
+    }
+    now
+}
+
+/// The host of a URL, to name the feed in errors
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+/// Fetch every feed, skipping the ones that fail unless they all do
+pub fn check(urls: &[String]) -> Result<Feeds, String> {
+    let mut entries = Vec::new();
+    let mut failed = None;
+    for url in urls {
+        match http::get_text(host(url), url) {
+            Ok(xml) => {
+                let found = parse(&xml);
+                if found.is_empty() {
+                    warn!("[FEEDS] No entries in {}", url);
+                }
+                entries.extend(found);
+            }
+            Err(e) => {
+                warn!("[FEEDS] {}", e);
+                failed = Some(e);
+            }
+        }
+    }
+    if let Some(e) = failed.filter(|_| entries.is_empty()) {
+        return Err(e);
+    }
+    entries.sort_by_key(|entry| Reverse(entry.at));
+    Ok(Feeds { entries, seen: seen() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::days_from_civil;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+        <rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel>
+        <title>Rust &amp; Friends</title>
+        <atom:link href="https://example.com/rss" rel="self"/>
+        <item>
+            <title><![CDATA[Announcing <Rust> 2.0]]></title>
+            <link>https://example.com/rust-2</link>
+            <pubDate>Fri, 16 Oct 2026 09:30:00 +0200</pubDate>
+        </item>
+        <item>
+            <title>Caf&#233; &#x2014; notes</title>
+            <guid>https://example.com/cafe</guid>
+            <pubDate>Thu, 15 Oct 2026 18:00:00 GMT</pubDate>
+        </item>
+        </channel></rss>"#;
+
+    const ATOM: &str = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+        <title type="text">Atom feed</title>
+        <link href="https://example.org/" rel="alternate"/>
+        <entry>
+            <title>First   post</title>
+            <link rel="edit" href="https://example.org/edit/1"/>
+            <link href="https://example.org/1?a=1&amp;b=2"/>
+            <updated>2026-10-16T12:00:00Z</updated>
+            <published>2026-10-16T08:15:30.5+02:00</published>
+        </entry>
+        <entry><title>Undated</title><link href='https://example.org/2'/></entry>
+    </feed>"#;
+
+    #[test]
+    fn test_parse_rss() {
+        let entries = parse(RSS);
+        let day = days_from_civil(2026, 10, 16) * 86400;
+        assert_eq!(entries, [
+            Entry {
+                feed: "Rust & Friends".to_string(),
+                title: "Announcing <Rust> 2.0".to_string(),
+                link: "https://example.com/rust-2".to_string(),
+                at: day + 7 * 3600 + 30 * 60,
+            },
+            Entry {
+                feed: "Rust & Friends".to_string(),
+                title: "Café — notes".to_string(),
+                link: "https://example.com/cafe".to_string(),
+                at: day - 6 * 3600,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let entries = parse(ATOM);
+        let titles: Vec<(&str, &str, &str)> =
+            entries.iter().map(|entry| (entry.feed.as_str(), entry.title.as_str(), entry.link.as_str())).collect();
+        assert_eq!(titles, [
+            ("Atom feed", "First post", "https://example.org/1?a=1&b=2"),
+            ("Atom feed", "Undated", "https://example.org/2"),
+        ]);
+        assert_eq!(entries[0].at, days_from_civil(2026, 10, 16) * 86400 + 6 * 3600 + 15 * 60 + 30);
+        assert_eq!(entries[1].at, 0);
+        assert!(parse("<html><body>Not a feed</body></html>").is_empty());
+    }
+
+    #[test]
+    fn test_dates() {
+        let day = days_from_civil(2026, 10, 16) * 86400;
+        assert_eq!(parse_rfc822("16 Oct 2026 09:30 EDT"), Some(day + 13 * 3600 + 30 * 60));
+        assert_eq!(parse_rfc822("Fri, 16 October 26 09:30:00 Z"), Some(day + 9 * 3600 + 30 * 60));
+        assert_eq!(parse_rfc822("2026-10-16"), None);
+        assert_eq!(parse_rfc3339("2026-10-16T09:30:00-05:30"), Some(day + 15 * 3600));
+        assert_eq!(parse_rfc3339("Fri, 16 Oct 2026"), None);
+    }
+
+    #[test]
+    fn test_unread() {
+        let entry = |at| Entry { feed: String::new(), title: String::new(), link: String::new(), at };
+        let feeds = Feeds { entries: vec![entry(300), entry(200), entry(0)], seen: 200 };
+        assert_eq!(feeds.unread(), 1);
+        assert_eq!(host("https://blog.rust-lang.org/feed.xml"), "blog.rust-lang.org");
+    }
+}
@@ -0,0 +1,128 @@
+//! The active Focus from the Do Not Disturb database
+//!
+//! macOS records a Focus turned on by hand, Do Not Disturb included, as an
+//! assertion in `~/Library/DoNotDisturb/DB/Assertions.json`, and names every
+//! Focus in `ModeConfigurations.json` next to it. Both are protected, so the
+//! daemon needs Full Disk Access to read them. A Focus turned on by a schedule
+//! leaves no assertion and isn't seen.
+//!
+//! There is no command to turn a Focus on or off, so clicks run a shortcut from
+//! the Shortcuts app, which has a "Set Focus" action.
+
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde_json::Value;
+
+/// A Focus that is on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Focus {
+    /// e.g. `com.apple.donotdisturb.mode.default` or `com.apple.focus.work`
+    pub identifier: String,
+    /// e.g. `Do Not Disturb` or `Work`
+    pub name: String,
+}
+
+fn db_path(file: &str) -> PathBuf {
+    let home = env::var("HOME").expect("HOME not set");
+    PathBuf::from(home).join("Library/DoNotDisturb/DB").join(file)
+}
+
+/// The file macOS rewrites whenever a Focus is turned on or off
+pub fn assertions_path() -> PathBuf {
+    db_path("Assertions.json")
+}
+
+/// The name of a built-in Focus, for when `ModeConfigurations.json` has none
+fn default_name(identifier: &str) -> String {
+    match identifier {
+        "com.apple.donotdisturb.mode.default" => "Do Not Disturb".to_string(),
+        "com.apple.sleep.sleep-mode" => "Sleep".to_string(),
+        "com.apple.donotdisturb.mode.driving" => "Driving".to_string(),
+        _ => {
+            let last = identifier.rsplit('.').next().unwrap_or(identifier);
+            let mut chars = last.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        }
+    }
+}
+
+/// Find the active Focus in the contents of `Assertions.json`, named from `ModeConfigurations.json`
+fn parse(assertions: &str, configurations: Option<&str>) -> Result<Option<Focus>, String> {
+    let assertions: Value =
+        serde_json::from_str(assertions).map_err(|e| format!("Failed to parse Assertions.json: {}", e))?;
+    let identifier = assertions["data"][0]["storeAssertionRecords"]
+        .as_array()
+        .and_then(|records| records.last())
+        .and_then(|record| record["assertionDetails"]["assertionDetailsModeIdentifier"].as_str());
+    let Some(identifier) = identifier else {
+        return Ok(None);
+    };
+
+    let name = configurations
+        .and_then(|configurations| serde_json::from_str::<Value>(configurations).ok())
+        .and_then(|configurations| {
+            let mode = &configurations["data"][0]["modeConfigurations"][identifier]["mode"];
+            mode["name"].as_str().map(str::to_string)
+        })
+        .unwrap_or_else(|| default_name(identifier));
+    Ok(Some(Focus { identifier: identifier.to_string(), name }))
+}
+
+/// The Focus that is on, if any
+pub fn current() -> Result<Option<Focus>, String> {
+    let path = assertions_path();
+    let assertions = match fs::read_to_string(&path) {
+        Ok(assertions) => assertions,
+        // Written the first time a Focus is turned on
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(format!("Failed to read {} ({}); give sketchybartender Full Disk Access", path.display(), e));
+        }
+    };
+    let configurations = fs::read_to_string(db_path("ModeConfigurations.json")).ok();
+    parse(&assertions, configurations.as_deref())
+}
+
+/// Run the shortcut that turns the Focus on or off
+pub fn toggle(shortcut: &str) -> Result<(), String> {
+    let output = Command::new("shortcuts")
+        .args(["run", shortcut])
+        .output()
+        .map_err(|e| format!("Failed to run shortcuts: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Shortcut {} failed: {}",
+            shortcut,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let assertions = r#"{"data":[{"storeAssertionRecords":[{"assertionDetails":
+            {"assertionDetailsModeIdentifier":"com.apple.focus.work"}}]}]}"#;
+        let configurations = r#"{"data":[{"modeConfigurations":{"com.apple.focus.work":
+            {"mode":{"name":"Deep Work","modeIdentifier":"com.apple.focus.work"}}}}]}"#;
+        let focus = parse(assertions, Some(configurations)).unwrap().unwrap();
+        assert_eq!(focus.identifier, "com.apple.focus.work");
+        assert_eq!(focus.name, "Deep Work");
+        assert_eq!(parse(assertions, None).unwrap().unwrap().name, "Work");
+
+        let dnd = r#"{"data":[{"storeAssertionRecords":[{"assertionDetails":
+            {"assertionDetailsModeIdentifier":"com.apple.donotdisturb.mode.default"}}]}]}"#;
+        assert_eq!(parse(dnd, None).unwrap().unwrap().name, "Do Not Disturb");
+
+        assert_eq!(parse(r#"{"data":[{}]}"#, None), Ok(None));
+        assert!(parse("not json", None).is_err());
+    }
+}
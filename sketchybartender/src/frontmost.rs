@@ -0,0 +1,138 @@
+//! Front app changes from NSWorkspace
+//!
+//! NSWorkspace posts `didActivateApplication` notifications on the main
+//! thread, so the observer is registered from `main` and the main thread
+//! runs the CFRunLoop while the socket listener runs on its own thread. The
+//! observer is a small Objective-C class built at runtime whose method only
+//! sends the app's name, bundle ID and PID on a channel; another thread runs
+//! the Rust callback.
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use sketchybartender::warn;
+
+use crate::aerospace::App;
+
+/// An activated app as NSRunningApplication describes it
+struct Activation {
+    name: String,
+    bundle_id: Option<String>,
+    pid: Option<u32>,
+}
+
+/// Sending end of the event channel, set by `listen`
+static EVENTS: OnceLock<Mutex<Sender<Activation>>> = OnceLock::new();
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use crate::objc::Id;
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {
+        pub static NSWorkspaceDidActivateApplicationNotification: Id;
+        pub static NSWorkspaceApplicationKey: Id;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFRunLoopRun();
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::c_void;
+
+    use sketchybartender::error;
+
+    use super::{ffi, Activation, EVENTS};
+    use crate::objc::{self, send, send_with, Id, Sel};
+
+    /// `appActivated:` of the observer class
+    extern "C" fn app_activated(_this: Id, _cmd: Sel, notification: Id) {
+        objc::autoreleasing(|| unsafe {
+            let user_info = send(notification, c"userInfo");
+            if user_info.is_null() {
+                return;
+            }
+            let app = send_with(user_info, c"objectForKey:", ffi::NSWorkspaceApplicationKey);
+            if app.is_null() {
+                return;
+            }
+
+            let process_identifier = objc::msg_send::<unsafe extern "C" fn(Id, Sel) -> i32>();
+            let pid = process_identifier(app, objc::selector(c"processIdentifier"));
+            let activation = objc::string(send(app, c"localizedName")).map(|name| Activation {
+                name,
+                bundle_id: objc::string(send(app, c"bundleIdentifier")),
+                pid: u32::try_from(pid).ok(),
+            });
+
+            if let (Some(activation), Some(Ok(events))) = (activation, EVENTS.get().map(|events| events.lock())) {
+                let _ = events.send(activation);
+            }
+        })
+    }
+
+    /// Register the observer with NSWorkspace's notification center; call from the main thread
+    pub fn register() {
+        let methods: [objc::Method; 1] = [(c"appActivated:", app_activated as *const c_void, c"v@:@")];
+        let Some(class) = objc::define_class(c"SketchybartenderFrontAppObserver", &methods) else {
+            error!("Failed to create the front app observer class");
+            return;
+        };
+
+        unsafe {
+            let observer = send(send(class, c"alloc"), c"init");
+            let workspace = send(objc::class(c"NSWorkspace"), c"sharedWorkspace");
+            let center = send(workspace, c"notificationCenter");
+
+            objc::msg_send::<unsafe extern "C" fn(Id, Sel, Id, Sel, Id, Id)>()(
+                center,
+                objc::selector(c"addObserver:selector:name:object:"),
+                observer,
+                objc::selector(c"appActivated:"),
+                ffi::NSWorkspaceDidActivateApplicationNotification,
+                std::ptr::null_mut(),
+            );
+        }
+    }
+
+    pub fn run() {
+        // Returns only if the run loop has nothing to wait for
+        unsafe { ffi::CFRunLoopRun() };
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn register() {}
+
+    pub fn run() {}
+}
+
+/// Run `callback` on a background thread with each app that becomes frontmost
+///
+/// Must be called from the main thread, which then has to call `run_main_loop`.
+pub fn listen(callback: impl Fn(App) + Send + 'static) {
+    let (sender, receiver) = mpsc::channel::<Activation>();
+    if EVENTS.set(Mutex::new(sender)).is_err() {
+        warn!("Front app listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        while let Ok(activation) = receiver.recv() {
+            callback(App::running(activation.name, activation.bundle_id, activation.pid));
+        }
+    });
+
+    native::register();
+}
+
+/// Deliver NSWorkspace notifications on the main thread; returns right away where there are none
+pub fn run_main_loop() {
+    native::run();
+}
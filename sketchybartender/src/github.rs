@@ -0,0 +1,111 @@
+//! Pull requests waiting for review, from GitHub's GraphQL API
+//!
+//! One search query gives both how many pull requests match and the first
+//! few of them, which is all the reviews item shows.
+
+use serde_json::{json, Value};
+
+use crate::http;
+
+const GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// Pull requests fetched for the popup; the count covers all of them
+const FIRST: usize = 20;
+
+const SEARCH: &str = "query($query: String!, $first: Int!) {
+  search(query: $query, type: ISSUE, first: $first) {
+    issueCount
+    nodes { ... on PullRequest { number title url repository { nameWithOwner } } }
+  }
+}";
+
+/// A pull request in the queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    /// `owner/name`
+    pub repository: String,
+    pub number: u64,
+    pub title: String,
+    pub url: String,
+}
+
+/// How many pull requests match the search, and the first of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Queue {
+    pub count: u64,
+    pub pulls: Vec<PullRequest>,
+}
+
+/// GitHub's reply to the search, or the first error it gives
+fn parse(reply: &Value) -> Result<Queue, String> {
+    if let Some(message) = reply["errors"][0]["message"].as_str() {
+        return Err(format!("GitHub refused the search: {}", message));
+    }
+    let search = &reply["data"]["search"];
+    let unexpected = || "Unexpected search results from GitHub".to_string();
+    let pulls = search["nodes"]
+        .as_array()
+        .ok_or_else(unexpected)?
+        .iter()
+        // Issues come back as empty objects when the query lets them in
+        .filter(|node| node["number"].is_u64())
+        .map(|node| {
+            Some(PullRequest {
+                repository: node["repository"]["nameWithOwner"].as_str()?.to_string(),
+                number: node["number"].as_u64()?,
+                title: node["title"].as_str()?.to_string(),
+                url: node["url"].as_str()?.to_string(),
+            })
+        })
+        .collect::<Option<Vec<PullRequest>>>()
+        .ok_or_else(unexpected)?;
+    Ok(Queue { count: search["issueCount"].as_u64().ok_or_else(unexpected)?, pulls })
+}
+
+/// The pull requests matching a search, e.g. `is:open is:pr review-requested:@me`
+pub fn search(token: &str, query: &str) -> Result<Queue, String> {
+    let body = json!({"query": SEARCH, "variables": {"query": query, "first": FIRST}});
+    let reply = http::post("GitHub", GRAPHQL_URL, &[format!("Authorization: bearer {}", token)], &body)?;
+    parse(&reply)
+}
+
+/// GitHub's page of the pull requests matching a search
+pub fn search_url(query: &str) -> String {
+    format!("https://github.com/pulls?q={}", http::encode(query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let reply = json!({"data": {"search": {"issueCount": 23, "nodes": [
+            {"number": 812, "title": "Fix the parser", "url": "https://github.com/acme/api/pull/812",
+             "repository": {"nameWithOwner": "acme/api"}},
+            {},
+        ]}}});
+        let queue = parse(&reply).unwrap();
+        assert_eq!(queue, Queue {
+            count: 23,
+            pulls: vec![PullRequest {
+                repository: "acme/api".to_string(),
+                number: 812,
+                title: "Fix the parser".to_string(),
+                url: "https://github.com/acme/api/pull/812".to_string(),
+            }],
+        });
+
+        let refused = json!({"errors": [{"message": "Bad credentials"}]});
+        assert_eq!(parse(&refused).unwrap_err(), "GitHub refused the search: Bad credentials");
+        assert!(parse(&json!({"data": null})).is_err());
+    }
+
+    #[test]
+    fn test_search_url() {
+        assert_eq!(
+            search_url("is:pr review-requested:@me"),
+            "https://github.com/pulls?q=is%3Apr%20review-requested%3A%40me"
+        );
+    }
+}
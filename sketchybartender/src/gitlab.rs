@@ -0,0 +1,225 @@
+//! Merge requests and their pipelines, from a GitLab instance's GraphQL API
+//!
+//! One query for the current user gives the merge requests assigned to them
+//! and the head pipeline of each merge request they opened, so a failing
+//! branch shows up without going through the projects one by one.
+
+use serde_json::{json, Value};
+
+use crate::http;
+
+/// Merge requests fetched of each kind; the assigned count covers all of them
+const FIRST: usize = 20;
+
+const QUERY: &str = "query($first: Int!) {
+  currentUser {
+    username
+    assignedMergeRequests(state: opened, first: $first) {
+      count
+      nodes { reference(full: true) title webUrl }
+    }
+    authoredMergeRequests(state: opened, first: $first) {
+      nodes { reference(full: true) title webUrl sourceBranch headPipeline { status } }
+    }
+  }
+}";
+
+/// An open merge request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeRequest {
+    /// `group/project!iid`
+    pub reference: String,
+    pub title: String,
+    pub url: String,
+}
+
+/// State of a pipeline, from GitLab's `PipelineStatusEnum`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStatus {
+    Success,
+    Failed,
+    /// Created, queued or running
+    Running,
+    /// Waiting for someone to start a manual job
+    Manual,
+    /// Canceled or skipped
+    Canceled,
+}
+
+impl PipelineStatus {
+    fn parse(status: &str) -> Self {
+        match status {
+            "SUCCESS" => Self::Success,
+            "FAILED" => Self::Failed,
+            "MANUAL" => Self::Manual,
+            "CANCELED" | "CANCELING" | "SKIPPED" => Self::Canceled,
+            _ => Self::Running,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Success => "passed",
+            Self::Failed => "failed",
+            Self::Running => "running",
+            Self::Manual => "manual",
+            Self::Canceled => "canceled",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Success => "\u{f00c}",  // nf-fa-check
+            Self::Failed => "\u{f00d}",   // nf-fa-times
+            Self::Running => "\u{f110}",  // nf-fa-spinner
+            Self::Manual => "\u{f04b}",   // nf-fa-play
+            Self::Canceled => "\u{f05e}", // nf-fa-ban
+        }
+    }
+}
+
+/// A merge request the user opened, with the head pipeline of its branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    pub merge_request: MergeRequest,
+    /// Source branch name
+    pub name: String,
+    pub pipeline: PipelineStatus,
+}
+
+/// The merge requests assigned to the user, and the pipelines of the ones they opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overview {
+    pub username: String,
+    /// Number of open merge requests assigned to the user
+    pub assigned_count: u64,
+    pub assigned: Vec<MergeRequest>,
+    /// The user's merge requests that have a pipeline
+    pub branches: Vec<Branch>,
+}
+
+impl Overview {
+    /// Number of the user's branches whose pipeline failed
+    pub fn failed(&self) -> usize {
+        self.branches.iter().filter(|branch| branch.pipeline == PipelineStatus::Failed).count()
+    }
+}
+
+fn merge_request(node: &Value) -> Option<MergeRequest> {
+    Some(MergeRequest {
+        reference: node["reference"].as_str()?.to_string(),
+        title: node["title"].as_str()?.to_string(),
+        url: node["webUrl"].as_str()?.to_string(),
+    })
+}
+
+/// GitLab's reply to the query, or the first error it gives
+fn parse(reply: &Value) -> Result<Overview, String> {
+    if let Some(message) = reply["errors"][0]["message"].as_str() {
+        return Err(format!("GitLab refused the query: {}", message));
+    }
+    let user = &reply["data"]["currentUser"];
+    if user.is_null() && reply["data"].is_object() {
+        return Err("GitLab didn't accept the token".to_string());
+    }
+    let unexpected = || "Unexpected merge requests from GitLab".to_string();
+    let nodes = |connection: &str| user[connection]["nodes"].as_array().ok_or_else(unexpected);
+    let assigned = nodes("assignedMergeRequests")?
+        .iter()
+        .map(merge_request)
+        .collect::<Option<Vec<MergeRequest>>>()
+        .ok_or_else(unexpected)?;
+    let branches = nodes("authoredMergeRequests")?
+        .iter()
+        // Merge requests without a pipeline have nothing to report
+        .filter(|node| node["headPipeline"].is_object())
+        .map(|node| {
+            Some(Branch {
+                merge_request: merge_request(node)?,
+                name: node["sourceBranch"].as_str()?.to_string(),
+                pipeline: PipelineStatus::parse(node["headPipeline"]["status"].as_str()?),
+            })
+        })
+        .collect::<Option<Vec<Branch>>>()
+        .ok_or_else(unexpected)?;
+    Ok(Overview {
+        username: user["username"].as_str().ok_or_else(unexpected)?.to_string(),
+        assigned_count: user["assignedMergeRequests"]["count"].as_u64().ok_or_else(unexpected)?,
+        assigned,
+        branches,
+    })
+}
+
+/// The user's merge requests on the instance at `url`, e.g. `https://gitlab.com`
+pub fn overview(url: &str, token: &str) -> Result<Overview, String> {
+    let body = json!({"query": QUERY, "variables": {"first": FIRST}});
+    let endpoint = format!("{}/api/graphql", url);
+    let reply = http::post("GitLab", &endpoint, &[format!("Authorization: Bearer {}", token)], &body)?;
+    parse(&reply)
+}
+
+/// The instance's page of the open merge requests assigned to `username`
+pub fn assigned_url(url: &str, username: &str) -> String {
+    format!("{}/dashboard/merge_requests?assignee_username={}", url, http::encode(username))
+}
+
+/// The page listing the pipelines of a merge request
+pub fn pipelines_url(merge_request: &MergeRequest) -> String {
+    format!("{}/pipelines", merge_request.url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let reply = json!({"data": {"currentUser": {
+            "username": "olli",
+            "assignedMergeRequests": {"count": 7, "nodes": [
+                {"reference": "acme/api!42", "title": "Fix the parser",
+                 "webUrl": "https://gitlab.com/acme/api/-/merge_requests/42"},
+            ]},
+            "authoredMergeRequests": {"nodes": [
+                {"reference": "acme/web!9", "title": "New header",
+                 "webUrl": "https://gitlab.com/acme/web/-/merge_requests/9",
+                 "sourceBranch": "header", "headPipeline": {"status": "FAILED"}},
+                {"reference": "acme/web!10", "title": "Docs",
+                 "webUrl": "https://gitlab.com/acme/web/-/merge_requests/10",
+                 "sourceBranch": "docs", "headPipeline": null},
+                {"reference": "acme/api!43", "title": "Faster",
+                 "webUrl": "https://gitlab.com/acme/api/-/merge_requests/43",
+                 "sourceBranch": "faster", "headPipeline": {"status": "WAITING_FOR_RESOURCE"}},
+            ]},
+        }}});
+        let overview = parse(&reply).unwrap();
+        assert_eq!(overview.username, "olli");
+        assert_eq!(overview.assigned_count, 7);
+        assert_eq!(overview.assigned, [MergeRequest {
+            reference: "acme/api!42".to_string(),
+            title: "Fix the parser".to_string(),
+            url: "https://gitlab.com/acme/api/-/merge_requests/42".to_string(),
+        }]);
+        let branches: Vec<(&str, PipelineStatus)> =
+            overview.branches.iter().map(|branch| (branch.name.as_str(), branch.pipeline)).collect();
+        assert_eq!(branches, [("header", PipelineStatus::Failed), ("faster", PipelineStatus::Running)]);
+        assert_eq!(overview.failed(), 1);
+        assert_eq!(
+            pipelines_url(&overview.branches[0].merge_request),
+            "https://gitlab.com/acme/web/-/merge_requests/9/pipelines"
+        );
+
+        let refused = json!({"errors": [{"message": "Invalid token"}]});
+        assert_eq!(parse(&refused).unwrap_err(), "GitLab refused the query: Invalid token");
+        assert!(parse(&json!({"data": {"currentUser": null}})).unwrap_err().contains("token"));
+        assert!(parse(&json!({"data": null})).is_err());
+    }
+
+    #[test]
+    fn test_assigned_url() {
+        assert_eq!(
+            assigned_url("https://gitlab.example.com", "olli"),
+            "https://gitlab.example.com/dashboard/merge_requests?assignee_username=olli"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Glob patterns for app names, shared by build.rs and the runtime icon lookup
+//!
+//! Supports `*` (any run of characters), `?` (any single character),
+//! `[abc]`, `[a-z]` and `[!abc]` classes, and `\` to escape a metacharacter.
+//! Literal text is normalized like app names, so patterns are case-insensitive.
+
+use std::borrow::Cow;
+
+use crate::normalize::normalize_name;
+
+/// A single element of a compiled glob pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// Normalized literal text
+    Literal(Cow<'static, str>),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[...]`, as inclusive character ranges
+    Class {
+        negated: bool,
+        ranges: Cow<'static, [(char, char)]>,
+    },
+}
+
+/// Whether a pattern needs the glob matcher (anything beyond a plain name)
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '\\'])
+}
+
+/// Normalize a literal run, keeping leading/trailing spaces as word boundaries
+fn normalize_literal(text: &str) -> String {
+    let mut out = normalize_name(text);
+    if out.is_empty() {
+        return if text.chars().any(char::is_whitespace) { " ".to_string() } else { out };
+    }
+    if text.starts_with(char::is_whitespace) {
+        out.insert(0, ' ');
+    }
+    if text.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+    out
+}
+
+/// Compile a glob pattern into tokens
+pub fn parse(pattern: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = pattern.chars().peekable();
+
+    let flush = |literal: &mut String, tokens: &mut Vec<Token>| {
+        if !literal.is_empty() {
+            let text = normalize_literal(literal);
+            if !text.is_empty() {
+                tokens.push(Token::Literal(Cow::Owned(text)));
+            }
+            literal.clear();
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => literal.push(escaped),
+                None => return Err(format!("pattern \"{}\" ends with a lone '\\'", pattern)),
+            },
+            '*' => {
+                flush(&mut literal, &mut tokens);
+                // Consecutive stars are equivalent to one
+                if tokens.last() != Some(&Token::AnyString) {
+                    tokens.push(Token::AnyString);
+                }
+            }
+            '?' => {
+                flush(&mut literal, &mut tokens);
+                tokens.push(Token::AnyChar);
+            }
+            '[' => {
+                flush(&mut literal, &mut tokens);
+                let negated = chars.next_if_eq(&'!').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let start = match chars.next() {
+                        Some(']') if !ranges.is_empty() => break,
+                        Some(c) => c,
+                        None => return Err(format!("unclosed '[' in pattern \"{}\"", pattern)),
+                    };
+                    let end = if chars.next_if_eq(&'-').is_some() {
+                        match chars.next() {
+                            Some(']') | None => {
+                                return Err(format!("unfinished range in pattern \"{}\"", pattern))
+                            }
+                            Some(end) => end,
+                        }
+                    } else {
+                        start
+                    };
+                    let (start, end) = (fold_char(start), fold_char(end));
+                    if start > end {
+                        return Err(format!("reversed range {}-{} in pattern \"{}\"", start, end, pattern));
+                    }
+                    ranges.push((start, end));
+                }
+                tokens.push(Token::Class { negated, ranges: Cow::Owned(ranges) });
+            }
+            c => literal.push(c),
+        }
+    }
+    flush(&mut literal, &mut tokens);
+
+    Ok(tokens)
+}
+
+/// Normalize a single class character the same way literal text is normalized
+fn fold_char(c: char) -> char {
+    normalize_name(&c.to_string()).chars().next().unwrap_or(c)
+}
+
+/// Match a normalized name against compiled tokens
+pub fn matches(tokens: &[Token], text: &str) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+
+    match token {
+        Token::Literal(literal) => text
+            .strip_prefix(literal.as_ref())
+            .is_some_and(|remaining| matches(rest, remaining)),
+        Token::AnyChar => {
+            let mut chars = text.chars();
+            chars.next().is_some() && matches(rest, chars.as_str())
+        }
+        Token::Class { negated, ranges } => {
+            let mut chars = text.chars();
+            match chars.next() {
+                Some(c) => {
+                    let in_class = ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c));
+                    in_class != *negated && matches(rest, chars.as_str())
+                }
+                None => false,
+            }
+        }
+        Token::AnyString => {
+            // A trailing star matches everything that is left
+            if rest.is_empty() {
+                return true;
+            }
+            text.char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(text.len()))
+                .any(|i| matches(rest, &text[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, text: &str) -> bool {
+        matches(&parse(pattern).unwrap(), &normalize_name(text))
+    }
+
+    #[test]
+    fn test_star_and_question_mark() {
+        assert!(glob("*Helper*", "Code Helper (Renderer)"));
+        assert!(glob("JetBrains *", "JetBrains Gateway"));
+        assert!(!glob("JetBrains *", "JetBrainsToolbox"));
+        assert!(glob("Python 3.1?", "Python 3.12"));
+        assert!(!glob("Python 3.1?", "Python 3.1"));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(glob("Office 20[0-9][0-9]", "Office 2024"));
+        assert!(!glob("Office 20[0-9][0-9]", "Office 20XX"));
+        assert!(glob("App [!0-9]", "App x"));
+        assert!(!glob("App [!0-9]", "App 7"));
+    }
+
+    #[test]
+    fn test_case_insensitive_and_escaped() {
+        assert!(glob("*helper*", "CODE HELPER"));
+        assert!(glob("What\\?", "what?"));
+        assert!(!glob("What\\?", "whats"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(parse("App [a-").is_err());
+        assert!(parse("App [z-a]").is_err());
+        assert!(parse("App \\").is_err());
+    }
+}
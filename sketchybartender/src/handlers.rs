@@ -1,268 +1,2409 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::process::Command;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Once, OnceLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use serde_json::{json, Value};
+use sketchybartender::config::{
+    hidden_items, AliasItem, AppIconImages, BatteryLevel, BrewClick, Config, CustomItem, ExtraClocks, Hook, JiraConfig,
+    MenubarFallback, RuleNeeds,
+};
+use sketchybartender::icon_map;
+use sketchybartender::monitor_map::{Mappings, MonitorMapper};
+use sketchybartender::{error, info, log, warn};
+
 use crate::aerospace;
-use crate::icon_map;
-use crate::monitor_map::MonitorMapper;
-use crate::providers;
+use crate::appearance;
+use crate::app_icons;
+use crate::bootstrap;
+use crate::brightness;
+use crate::calendar;
+use crate::crypto;
+use crate::clamshell::{self, Clamshell};
+use crate::daemon;
+use crate::events::{self, Event};
+use crate::feeds;
+use crate::github;
+use crate::gitlab;
+use crate::focus_mode;
+use crate::history;
+use crate::hooks;
+use crate::jira;
+use crate::low_power;
+use crate::menubar::{self, Action, Field};
+use crate::metrics;
+use crate::notify;
+use crate::providers::{self, Provider, Refresh};
+use crate::quiet;
+use crate::rules;
+use crate::runtime;
+use crate::settings;
+use crate::sketchybar::{
+    self,
+    blink_item,
+    cancel_animation,
+    icon_font,
+    icon_image_props,
+    item_displays,
+    remove_items,
+    set_item,
+    set_item_displays,
+    set_item_styles,
+    update_appearance,
+    update_audio_output,
+    update_battery,
+    update_brew,
+    update_brightness,
+    update_calendar,
+    update_clock,
+    update_crypto,
+    update_cpu,
+    update_custom,
+    update_disk,
+    update_exchange,
+    update_feeds,
+    update_gitlab,
+    update_jira,
+    update_linear,
+    update_fan,
+    update_front_app,
+    update_focus_mode,
+    update_load,
+    update_moon,
+    update_reviews,
+    update_network,
+    update_ram,
+    update_teams,
+    update_thermal,
+    update_top_process,
+    update_sun,
+    update_volume,
+    update_weather,
+    update_wifi,
+    update_zoom,
+    Animation,
+    ItemDisplays,
+    PopupEntry,
+    SketchybarBatch,
+};
+use crate::volume;
+use crate::zoom;
+
+/// Shared state for the daemon
+#[derive(Debug)]
+pub struct DaemonState {
+    /// Current front app (for deduplication)
+    pub front_app: String,
+    /// Monitor mapper for workspace filtering; shared so mappings are built outside this lock
+    pub monitor_mapper: Arc<MonitorMapper>,
+    /// Last workspace change timestamp for debouncing
+    pub last_workspace_change: Option<Instant>,
+    /// Previously rendered workspaces (to detect which ones need clearing)
+    pub previous_workspaces: HashSet<String>,
+    /// Items moved off the built-in display while the lid is closed
+    pub clamshell: Clamshell,
+    /// Mappings at the last display change, to tell which displays came and went
+    pub last_mappings: Option<Arc<Mappings>>,
+    /// Focused workspace at the last refresh, for the `on_workspace_change` hook
+    pub focused_workspace: Option<String>,
+}
+
+impl Default for DaemonState {
+    fn default() -> Self {
+        Self {
+            front_app: String::new(),
+            monitor_mapper: Arc::new(MonitorMapper::new()),
+            last_workspace_change: None,
+            previous_workspaces: HashSet::new(),
+            clamshell: Clamshell::default(),
+            last_mappings: None,
+            focused_workspace: None,
+        }
+    }
+}
+
+/// The clock, ticking on the minute (or second) when aligned
+pub struct Clock;
+
+impl Provider for Clock {
+    type Info = providers::ClockInfo;
+
+    fn name(&self) -> &'static str {
+        "clock"
+    }
+
+    fn interval(&self) -> Duration {
+        providers::clock_tick(Duration::from_secs(settings::current().clock_interval))
+    }
+
+    fn refresh(&self) -> Result<Option<providers::ClockInfo>, String> {
+        Ok(Some(providers::get_clock_info()))
+    }
+
+    fn render(&self, info: &providers::ClockInfo) -> io::Result<()> {
+        menubar::set(Field::Clock, &info.time);
+        let inline = settings::current().clock.extra_display == ExtraClocks::Inline;
+        update_clock(&info.label(inline))?;
+        update_clock_popup(if inline { &[] } else { &info.zones })
+    }
+
+    fn describe(&self, info: &providers::ClockInfo) -> Value {
+        let zones: Vec<Value> = info.zones.iter().map(|(city, time)| json!({"city": city, "time": time})).collect();
+        json!({"time": info.time, "zones": zones})
+    }
+}
+
+/// Rows of the clock's popup at the last render, so they're only replaced when a time changes
+fn last_clock_popup() -> &'static Mutex<Vec<(String, String)>> {
+    static ROWS: OnceLock<Mutex<Vec<(String, String)>>> = OnceLock::new();
+    ROWS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Show the other time zones in the popup that clicking the clock opens
+fn update_clock_popup(zones: &[(String, String)]) -> io::Result<()> {
+    let last = match last_clock_popup().lock() {
+        Ok(mut last) => Some(std::mem::replace(&mut *last, zones.to_vec())),
+        Err(_) => None,
+    };
+    if last.as_deref() == Some(zones) {
+        return Ok(());
+    }
+    // Clicking only opens the popup while there are rows in it, e.g. after a reload added time zones
+    if last.is_none_or(|last| last.is_empty() != zones.is_empty()) {
+        set_item("clock", &[("click_script", bootstrap::clock_script(&settings::current()))])?;
+    }
+    let entries: Vec<PopupEntry> = zones
+        .iter()
+        .map(|(city, time)| PopupEntry { label: format!("{} {}", city, time), icon: None, click_script: None })
+        .collect();
+    sketchybar::set_popup("clock", &entries)
+}
+
+/// Battery charge and state; the power notifications make the timer a fallback
+#[derive(Default)]
+pub struct Battery {
+    /// Power source sent with sketchybar's event, saving a pmset call
+    pub power_source: Option<String>,
+}
+
+impl Provider for Battery {
+    type Info = providers::BatteryInfo;
+
+    fn name(&self) -> &'static str {
+        "battery"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().battery_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::BatteryInfo>, String> {
+        providers::get_battery(self.power_source.clone())
+    }
+
+    fn render(&self, info: &providers::BatteryInfo) -> io::Result<()> {
+        let charging = if info.is_charging { "\u{26a1}" } else { "" };
+        menubar::set(Field::Battery, &format!("{}{}%", charging, info.percentage));
+        metrics::update(|m| m.battery = Some((info.percentage, info.is_charging)));
+        let result = update_battery(info.icon(), &info.icon_color(), &info.label_color(), &info.label());
+        if let Err(e) = sketchybar::set_popup("battery", &battery_popup(info)) {
+            error!("Failed to update the battery popup: {}", e);
+        }
+        check_battery_alert(info);
+        result
+    }
+
+    fn describe(&self, info: &providers::BatteryInfo) -> Value {
+        json!({
+            "percentage": info.percentage,
+            "charging": info.is_charging,
+            "charged": info.charged,
+            "minutes_remaining": info.minutes_remaining,
+            "adapter_watts": info.adapter_watts,
+            "battery_watts": info.battery_watts,
+            "condition": info.health.condition,
+            "cycle_count": info.health.cycle_count,
+            "max_capacity_percentage": info.health.capacity_percentage(),
+            "low_power": info.low_power,
+            "level": info.level().to_string(),
+        })
+    }
+}
+
+/// Popup rows for the battery item: the power state, time remaining, adapter and battery wattage, and the
+/// battery's health
+fn battery_popup(info: &providers::BatteryInfo) -> Vec<PopupEntry> {
+    let row = |label: String| PopupEntry { label, ..PopupEntry::default() };
+    let state = match (info.is_charging, info.charged) {
+        (false, _) => "On battery",
+        (true, false) => "Charging",
+        (true, true) if info.percentage >= 100 => "Fully charged",
+        (true, true) => "Plugged in, not charging",
+    };
+
+    let mut entries = vec![row(state.to_string())];
+    if info.low_power {
+        entries.push(row("Low Power Mode on".to_string()));
+    }
+    match (info.minutes_remaining, info.is_charging) {
+        (Some(minutes), true) => entries.push(row(format!("{} until full", providers::format_minutes(minutes)))),
+        (Some(minutes), false) => entries.push(row(format!("{} remaining", providers::format_minutes(minutes)))),
+        (None, false) => entries.push(row("Estimating time remaining".to_string())),
+        (None, true) => {}
+    }
+    if let Some(watts) = info.adapter_watts {
+        entries.push(row(format!("{} W adapter", watts)));
+    }
+    if let Some(watts) = info.battery_watts.filter(|watts| watts.abs() >= 0.1) {
+        let direction = if watts > 0.0 { "Charging at" } else { "Drawing" };
+        entries.push(row(format!("{} {:.1} W", direction, watts.abs())));
+    }
+    if let Some(condition) = &info.health.condition {
+        entries.push(row(format!("Condition: {}", condition)));
+    }
+    if let Some(percentage) = info.health.capacity_percentage() {
+        entries.push(row(format!("Maximum capacity {}%", percentage)));
+    }
+    if let Some(cycles) = info.health.cycle_count {
+        entries.push(row(format!("Cycle count {}", cycles)));
+    }
+    entries
+}
+
+/// Show or hide the battery details popup, or with the configured modifier held toggle Low Power Mode
+pub fn handle_battery_click(modifier: Option<&str>) -> Result<(), String> {
+    if providers::battery_config().low_power_modifier.matches(modifier) {
+        let on = !low_power::enabled().unwrap_or(false);
+        info!("[BATTERY] Turning Low Power Mode {}", if on { "on" } else { "off" });
+        return low_power::set(on);
+    }
+    if let Err(e) = SketchybarBatch::new().popup("battery", "toggle").execute() {
+        error!("Failed to toggle the battery popup: {}", e);
+    }
+    Ok(())
+}
+
+/// Retint the battery item when Low Power Mode is turned on or off
+pub fn handle_low_power_change() {
+    Battery::default().run();
+}
+
+/// Refresh the battery after a power notification, and the rules when the power source changed
+pub fn handle_power_change(source_changed: bool) {
+    Battery::default().run();
+    if source_changed {
+        handle_rules_refresh();
+    }
+}
+
+/// Battery level at the last refresh, to alert only when it drops into a new level
+fn last_battery_level() -> &'static Mutex<Option<BatteryLevel>> {
+    static LEVEL: OnceLock<Mutex<Option<BatteryLevel>>> = OnceLock::new();
+    LEVEL.get_or_init(|| Mutex::new(None))
+}
+
+/// Raise the low-battery alert when the battery drops to the warning or critical level
+fn check_battery_alert(info: &providers::BatteryInfo) {
+    let level = info.level();
+    let previous = match last_battery_level().lock() {
+        Ok(mut last) => last.replace(level),
+        Err(_) => return,
+    };
+
+    if !level.alerts_after(previous) {
+        return;
+    }
+
+    info!("[BATTERY] {} at {}%", level, info.percentage);
+    events::publish(Event::BatteryAlert { level, percentage: info.percentage });
+    hooks::run(Hook::LowBattery, vec![
+        ("BATTERY_PERCENTAGE", info.percentage.to_string()),
+        ("BATTERY_LEVEL", level.to_string()),
+    ]);
+
+    if level == BatteryLevel::Critical && settings::current().notifications.battery {
+        notify::send("Battery low", &format!("{}% remaining, plug in the charger", info.percentage));
+    }
+
+    // Attention animations would give away what quiet mode hides
+    if providers::battery_config().blink && !quiet::active() {
+        if let Err(e) = blink_item("battery", &info.icon_color()) {
+            error!("Failed to blink battery: {}", e);
+        }
+    }
+}
+
+/// Outdated Homebrew packages, checked with some jitter so machines don't all ask at once
+pub struct Brew;
+
+impl Provider for Brew {
+    type Info = providers::BrewInfo;
+
+    fn name(&self) -> &'static str {
+        "brew"
+    }
+
+    fn interval(&self) -> Duration {
+        let config = settings::current();
+        Duration::from_secs(config.brew_interval) + providers::jitter(config.brew.jitter)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::BrewInfo>, String> {
+        providers::get_brew_outdated().map(Some)
+    }
+
+    fn render(&self, info: &providers::BrewInfo) -> io::Result<()> {
+        let outdated = match info.total() {
+            0 => String::new(),
+            total => format!("{} outdated", total),
+        };
+        menubar::set(Field::Brew, &outdated);
+        metrics::update(|m| m.brew = Some((info.formulae, info.casks)));
+        check_brew_outdated(info);
+        if providers::brew_config().click == BrewClick::Popup {
+            if let Err(e) = sketchybar::set_popup("brew", &brew_popup(info)) {
+                error!("Failed to update the brew popup: {}", e);
+            }
+        }
+        update_brew(info.icon(), info.formulae, info.casks)
+    }
+
+    fn describe(&self, info: &providers::BrewInfo) -> Value {
+        json!({"formulae": info.formulae, "casks": info.casks, "packages": info.packages})
+    }
+}
+
+/// Popup rows for the brew item: the outdated packages, and a row upgrading them
+fn brew_popup(info: &providers::BrewInfo) -> Vec<PopupEntry> {
+    if info.packages.is_empty() {
+        return vec![PopupEntry { label: "Everything is up to date".to_string(), ..PopupEntry::default() }];
+    }
+
+    let mut entries: Vec<PopupEntry> = info
+        .packages
+        .iter()
+        .map(|package| PopupEntry { label: package.clone(), ..PopupEntry::default() })
+        .collect();
+    entries.push(PopupEntry {
+        label: "Upgrade all".to_string(),
+        icon: Some("\u{f409}".to_string()),
+        click_script: Some(format!("\"{}\" brew-upgrade", sketchycli_path())),
+    });
+    entries
+}
+
+/// Outdated package count at the last check, to run the hook only when it goes up
+fn last_brew_outdated() -> &'static Mutex<usize> {
+    static OUTDATED: OnceLock<Mutex<usize>> = OnceLock::new();
+    OUTDATED.get_or_init(|| Mutex::new(0))
+}
+
+/// Run the `on_brew_outdated` hook when packages became outdated since the last check
+fn check_brew_outdated(info: &providers::BrewInfo) {
+    let previous = match last_brew_outdated().lock() {
+        Ok(mut last) => std::mem::replace(&mut *last, info.total()),
+        Err(_) => return,
+    };
+
+    if info.total() > previous {
+        hooks::run(Hook::BrewOutdated, vec![
+            ("BREW_OUTDATED", info.total().to_string()),
+            ("BREW_FORMULAE", info.formulae.to_string()),
+            ("BREW_CASKS", info.casks.to_string()),
+        ]);
+    }
+}
+
+/// Unread Teams notifications, read from the Dock badge
+pub struct Teams;
+
+impl Provider for Teams {
+    type Info = providers::TeamsInfo;
+
+    fn name(&self) -> &'static str {
+        "teams"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().teams_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::TeamsInfo>, String> {
+        providers::get_teams_notifications().map(Some)
+    }
+
+    fn render(&self, info: &providers::TeamsInfo) -> io::Result<()> {
+        metrics::update(|m| m.teams_notifications = Some(info.notification_count));
+        update_teams(info.icon(), &info.icon_color(), &info.border_color(), info.notification_count)
+    }
+
+    fn describe(&self, info: &providers::TeamsInfo) -> Value {
+        json!({"running": info.running, "notifications": info.notification_count})
+    }
+}
+
+/// Wi-Fi network and signal strength
+pub struct Wifi;
+
+impl Provider for Wifi {
+    type Info = providers::WifiInfo;
+
+    fn name(&self) -> &'static str {
+        "wifi"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().wifi_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::WifiInfo>, String> {
+        providers::get_wifi().map(Some)
+    }
+
+    fn render(&self, info: &providers::WifiInfo) -> io::Result<()> {
+        update_wifi(info.icon(), info.ssid.as_deref())
+    }
+
+    fn describe(&self, info: &providers::WifiInfo) -> Value {
+        json!({"connected": info.connected, "ssid": info.ssid, "rssi": info.rssi, "bars": info.bars()})
+    }
+}
+
+/// CPU usage over every core since the last refresh
+pub struct Cpu;
+
+impl Provider for Cpu {
+    type Info = u8;
+
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().cpu_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<u8>, String> {
+        providers::get_cpu().map(Some).ok_or_else(|| "Failed to read CPU usage".to_string())
+    }
+
+    fn render(&self, percentage: &u8) -> io::Result<()> {
+        update_cpu(providers::cpu_icon(*percentage), *percentage)
+    }
+
+    fn describe(&self, percentage: &u8) -> Value {
+        json!({"percentage": percentage})
+    }
+}
+
+/// Memory in use, colored by the kernel's memory pressure and the configured thresholds
+pub struct Ram;
+
+impl Provider for Ram {
+    type Info = providers::RamInfo;
+
+    fn name(&self) -> &'static str {
+        "ram"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().ram_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::RamInfo>, String> {
+        providers::get_ram().map(Some).ok_or_else(|| "Failed to read memory usage".to_string())
+    }
+
+    fn render(&self, info: &providers::RamInfo) -> io::Result<()> {
+        let config = settings::current();
+        let pressure = config.ram.pressure(info.percentage, info.pressure);
+        update_ram(providers::ram_icon(info.percentage), info.percentage, config.ram.colors.color(pressure))
+    }
+
+    fn describe(&self, info: &providers::RamInfo) -> Value {
+        let pressure = settings::current().ram.pressure(info.percentage, info.pressure);
+        json!({"percentage": info.percentage, "pressure": pressure.to_string()})
+    }
+}
+
+/// The process using the most CPU, to tell who is behind a busy cpu item
+pub struct TopProcess;
+
+impl Provider for TopProcess {
+    type Info = providers::TopProcess;
+
+    fn name(&self) -> &'static str {
+        "top_process"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().top_process_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::TopProcess>, String> {
+        providers::get_top_process().map(Some)
+    }
+
+    fn render(&self, process: &providers::TopProcess) -> io::Result<()> {
+        update_top_process(process.icon(), &process.label())
+    }
+
+    fn describe(&self, process: &providers::TopProcess) -> Value {
+        json!({"name": process.name, "pid": process.pid, "cpu": process.cpu})
+    }
+}
+
+/// Thermal state and temperature, the icon colored while macOS throttles the CPU
+pub struct Thermal;
+
+impl Provider for Thermal {
+    type Info = providers::ThermalInfo;
+
+    fn name(&self) -> &'static str {
+        "thermal"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().thermal_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::ThermalInfo>, String> {
+        Ok(providers::get_thermal(settings::current().thermal.temperature))
+    }
+
+    fn render(&self, info: &providers::ThermalInfo) -> io::Result<()> {
+        let config = settings::current();
+        update_thermal(info.icon(), config.thermal.colors.color(info.state.level()), &info.label())
+    }
+
+    fn describe(&self, info: &providers::ThermalInfo) -> Value {
+        json!({"state": info.state.to_string(), "throttling": info.state.throttling(), "temperature": info.temperature})
+    }
+}
+
+/// Fan speeds, read along with the thermal item's temperature and hidden on Macs without fans
+pub struct Fan;
+
+impl Provider for Fan {
+    type Info = providers::FanInfo;
+
+    fn name(&self) -> &'static str {
+        "fan"
+    }
+
+    /// The thermal item's, so both take their values from one SMC reading
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().thermal_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::FanInfo>, String> {
+        Ok(providers::get_fans())
+    }
+
+    fn render(&self, info: &providers::FanInfo) -> io::Result<()> {
+        if info.fanless() {
+            return set_item("fan", &[("drawing", "off")]);
+        }
+        update_fan(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::FanInfo) -> Value {
+        json!({"rpm": info.rpms})
+    }
+}
+
+/// Redraw the thermal item when macOS reports a new thermal state
+pub fn handle_thermal_change() {
+    Thermal.run();
+}
+
+/// The Focus that is on, its icon dimmed while none is
+pub struct FocusMode;
+
+impl Provider for FocusMode {
+    type Info = providers::FocusModeInfo;
+
+    fn name(&self) -> &'static str {
+        "focus_mode"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().focus_mode_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::FocusModeInfo>, String> {
+        providers::get_focus_mode().map(Some)
+    }
+
+    fn render(&self, info: &providers::FocusModeInfo) -> io::Result<()> {
+        let config = settings::current();
+        let color = config.focus_mode.color(info.focus.is_some());
+        update_focus_mode(info.icon(), color, &info.label(config.focus_mode.label))
+    }
+
+    fn describe(&self, info: &providers::FocusModeInfo) -> Value {
+        let focus = info.focus.as_ref();
+        json!({
+            "active": focus.is_some(),
+            "name": focus.map(|focus| &focus.name),
+            "identifier": focus.map(|focus| &focus.identifier),
+        })
+    }
+}
+
+/// Redraw the focus_mode item when a Focus is turned on or off
+pub fn handle_focus_mode_change() {
+    FocusMode.run();
+}
+
+/// Run the configured shortcut to turn Do Not Disturb on or off, then redraw the item
+pub fn handle_focus_mode_click() -> Result<(), String> {
+    let shortcut = settings::current().focus_mode.shortcut.clone();
+    info!("[FOCUS] Running shortcut {}", shortcut);
+    let result = focus_mode::toggle(&shortcut);
+    FocusMode.run();
+    result
+}
+
+/// Light or dark appearance, switched by a click
+pub struct Appearance;
+
+impl Provider for Appearance {
+    type Info = providers::AppearanceInfo;
+
+    fn name(&self) -> &'static str {
+        "appearance"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().appearance_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::AppearanceInfo>, String> {
+        providers::get_appearance().map(Some)
+    }
+
+    fn render(&self, info: &providers::AppearanceInfo) -> io::Result<()> {
+        check_appearance_change(info);
+        update_appearance(info.icon())
+    }
+
+    fn describe(&self, info: &providers::AppearanceInfo) -> Value {
+        json!({"appearance": info.name()})
+    }
+}
+
+/// Appearance at the last refresh, to run the hook only when it switched
+fn last_appearance() -> &'static Mutex<Option<bool>> {
+    static DARK: OnceLock<Mutex<Option<bool>>> = OnceLock::new();
+    DARK.get_or_init(|| Mutex::new(None))
+}
+
+/// Run the `on_appearance_change` hook when the appearance switched since the last refresh
+fn check_appearance_change(info: &providers::AppearanceInfo) {
+    let previous = match last_appearance().lock() {
+        Ok(mut last) => last.replace(info.dark),
+        Err(_) => return,
+    };
+
+    if previous.is_some_and(|dark| dark != info.dark) {
+        info!("[APPEARANCE] Switched to {}", info.name());
+        hooks::run(Hook::AppearanceChange, vec![("APPEARANCE", info.name().to_string())]);
+    }
+}
+
+/// Redraw the appearance item when macOS switches between light and dark
+pub fn handle_appearance_change() {
+    Appearance.run();
+}
+
+/// Switch between light and dark; the listener redraws the item
+pub fn handle_appearance_click() -> Result<(), String> {
+    appearance::toggle()
+}
+
+/// Main display brightness, adjusted by scrolling over the item
+pub struct Brightness;
+
+impl Provider for Brightness {
+    type Info = providers::BrightnessInfo;
+
+    fn name(&self) -> &'static str {
+        "brightness"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().brightness_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::BrightnessInfo>, String> {
+        // Displays DisplayServices can't drive have nothing to show
+        Ok(providers::get_brightness())
+    }
+
+    fn render(&self, info: &providers::BrightnessInfo) -> io::Result<()> {
+        update_brightness(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::BrightnessInfo) -> Value {
+        json!({"percentage": info.percentage})
+    }
+}
+
+/// Step the brightness by the lines scrolled over the item, then redraw it
+pub fn handle_brightness_scroll(delta: &str) -> Result<(), String> {
+    let delta: i32 = delta.trim().parse().map_err(|_| format!("Invalid scroll delta: {}", delta))?;
+    let step = settings::current().brightness_scroll_step;
+    if delta == 0 || step == 0 {
+        return Ok(());
+    }
+    let current = brightness::percentage().ok_or("No display with adjustable brightness")?;
+    let result = brightness::set_percentage(providers::scrolled(current, delta, step));
+    Brightness.run();
+    result
+}
+
+/// The next calendar event, counting down to its start
+pub struct Calendar;
+
+impl Provider for Calendar {
+    type Info = providers::CalendarInfo;
+
+    fn name(&self) -> &'static str {
+        "calendar"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().calendar_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::CalendarInfo>, String> {
+        providers::get_calendar(&settings::current().calendar.names).map(Some)
+    }
+
+    fn render(&self, info: &providers::CalendarInfo) -> io::Result<()> {
+        update_calendar(info.icon(), &info.label(), settings::current().calendar.color(info.minutes))
+    }
+
+    fn describe(&self, info: &providers::CalendarInfo) -> Value {
+        let event = info.event.as_ref();
+        json!({
+            "title": event.map(|event| &event.title),
+            "start": event.map(|event| &event.start),
+            "minutes": event.map(|_| info.minutes),
+            "link": event.and_then(|event| event.link.as_ref()),
+        })
+    }
+}
+
+/// Join the next event's video call, or open Calendar when it has none
+pub fn handle_calendar_click() -> Result<(), String> {
+    let info = providers::get_calendar(&settings::current().calendar.names)?;
+    match info.event {
+        Some(calendar::Event { title, link: Some(link), .. }) => {
+            info!("[CALENDAR] Joining {}", title);
+            calendar::join(&link)
+        }
+        _ => calendar::open_calendar(),
+    }
+}
+
+/// Zoom's microphone during a meeting, muted or live
+pub struct Zoom;
+
+impl Provider for Zoom {
+    type Info = providers::ZoomInfo;
+
+    fn name(&self) -> &'static str {
+        "zoom"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().zoom_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::ZoomInfo>, String> {
+        providers::get_zoom().map(Some)
+    }
+
+    fn render(&self, info: &providers::ZoomInfo) -> io::Result<()> {
+        let muted = info.meeting != zoom::Meeting::Live;
+        update_zoom(info.icon(), settings::current().zoom.color(muted))
+    }
+
+    fn describe(&self, info: &providers::ZoomInfo) -> Value {
+        json!({
+            "meeting": info.meeting != zoom::Meeting::None,
+            "muted": info.meeting == zoom::Meeting::Muted,
+        })
+    }
+}
+
+/// Mute or unmute Zoom's microphone, then redraw the item
+pub fn handle_zoom_click() -> Result<(), String> {
+    let result = zoom::toggle_mute();
+    Zoom.run();
+    result
+}
+
+/// The weather where the config says, with a forecast popup
+pub struct Weather;
+
+impl Provider for Weather {
+    type Info = providers::WeatherInfo;
+
+    fn name(&self) -> &'static str {
+        "weather"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().weather_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::WeatherInfo>, String> {
+        let config = settings::current();
+        providers::get_weather(&config.weather, config.weather_interval)
+    }
+
+    fn render(&self, info: &providers::WeatherInfo) -> io::Result<()> {
+        if let Err(e) = sketchybar::set_popup("weather", &weather_popup(info)) {
+            error!("Failed to update the weather popup: {}", e);
+        }
+        update_weather(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::WeatherInfo) -> Value {
+        let report = &info.weather.report;
+        let forecast: Vec<Value> = report
+            .forecast
+            .iter()
+            .map(|day| json!({"day": day.name, "low": day.low, "high": day.high, "condition": day.condition.name()}))
+            .collect();
+        json!({
+            "place": report.place,
+            "temperature": report.temperature,
+            "feels_like": report.feels_like,
+            "units": info.units.to_string(),
+            "condition": report.condition.name(),
+            "description": report.description,
+            "forecast": forecast,
+            "updated": info.weather.updated,
+            "stale": info.weather.stale,
+        })
+    }
+}
+
+/// Popup rows for the weather item: the current weather, a row per forecast day, and when
+/// it's the last report that came through, since when
+fn weather_popup(info: &providers::WeatherInfo) -> Vec<PopupEntry> {
+    let report = &info.weather.report;
+    let now = format!("{}, feels like {}", report.description, info.degrees(report.feels_like));
+    let mut entries = vec![PopupEntry {
+        label: if report.place.is_empty() { now } else { format!("{}: {}", report.place, now) },
+        icon: Some(report.condition.icon().to_string()),
+        ..PopupEntry::default()
+    }];
+    entries.extend(report.forecast.iter().map(|day| PopupEntry {
+        label: format!("{:<8} {} / {}", day.name, info.degrees(day.low), info.degrees(day.high)),
+        icon: Some(day.condition.icon().to_string()),
+        ..PopupEntry::default()
+    }));
+    if info.weather.stale {
+        entries.push(PopupEntry {
+            label: format!("Offline, from {}", info.weather.updated),
+            icon: Some("\u{f05aa}".to_string()), // nf-md-wifi_off
+            ..PopupEntry::default()
+        });
+    }
+    entries
+}
+
+/// The next sunrise or sunset, worked out from the configured coordinates
+pub struct Sun;
+
+impl Provider for Sun {
+    type Info = providers::SunInfo;
+
+    fn name(&self) -> &'static str {
+        "sun"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().sun_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::SunInfo>, String> {
+        let config = settings::current();
+        providers::get_sun(&config.sun, &config.weather).map(Some)
+    }
+
+    fn render(&self, info: &providers::SunInfo) -> io::Result<()> {
+        update_sun(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::SunInfo) -> Value {
+        json!({
+            "next": info.next.map(|next| next.transition.name()),
+            "at": info.next.map(|next| next.at),
+            "time": info.next.map(|_| info.label()),
+        })
+    }
+}
+
+/// The moon's phase, worked out at every local midnight
+pub struct Moon;
+
+impl Provider for Moon {
+    type Info = providers::MoonInfo;
+
+    fn name(&self) -> &'static str {
+        "moon"
+    }
+
+    fn interval(&self) -> Duration {
+        providers::midnight_tick()
+    }
+
+    fn refresh(&self) -> Result<Option<providers::MoonInfo>, String> {
+        Ok(Some(providers::get_moon()))
+    }
+
+    fn render(&self, info: &providers::MoonInfo) -> io::Result<()> {
+        let label = if settings::current().moon_label { info.moon.phase.name() } else { "" };
+        update_moon(info.moon.phase.icon(), label)
+    }
+
+    fn describe(&self, info: &providers::MoonInfo) -> Value {
+        json!({
+            "phase": info.moon.phase.name(),
+            "age": (info.moon.age * 10.0).round() / 10.0,
+            "illumination": info.moon.illumination,
+        })
+    }
+}
+
+/// Prices of the configured coins, flashing when one crosses an alert's bound
+pub struct Crypto;
+
+impl Provider for Crypto {
+    type Info = providers::CryptoInfo;
+
+    fn name(&self) -> &'static str {
+        "crypto"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().crypto_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::CryptoInfo>, String> {
+        providers::get_crypto(&settings::current().crypto)
+    }
+
+    fn render(&self, info: &providers::CryptoInfo) -> io::Result<()> {
+        let config = settings::current();
+        let color = if info.breached { config.crypto.alert_color.as_str() } else { "0xffffffff" };
+        let result = update_crypto(info.icon(), &info.label(), color);
+        check_crypto_alerts(info, color);
+        result
+    }
+
+    fn describe(&self, info: &providers::CryptoInfo) -> Value {
+        let quotes: Vec<Value> = info
+            .quotes
+            .iter()
+            .map(|quote| json!({"coin": quote.coin, "price": quote.price, "change_24h": quote.change}))
+            .collect();
+        json!({
+            "currency": info.currency,
+            "quotes": quotes,
+            "breached": info.breached,
+        })
+    }
+}
+
+/// Prices at the last refresh, to flash only when one crosses a bound
+fn last_crypto_quotes() -> &'static Mutex<Vec<crypto::Quote>> {
+    static QUOTES: OnceLock<Mutex<Vec<crypto::Quote>>> = OnceLock::new();
+    QUOTES.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Flash the crypto item when a price crossed an alert's bound since the last refresh
+fn check_crypto_alerts(info: &providers::CryptoInfo, color: &str) {
+    let previous = match last_crypto_quotes().lock() {
+        Ok(mut last) => std::mem::replace(&mut *last, info.quotes.clone()),
+        Err(_) => return,
+    };
+
+    let config = settings::current();
+    let crossed = crypto::crossed(&config.crypto.alerts, &previous, &info.quotes);
+    if crossed.is_empty() {
+        return;
+    }
+    for alert in crossed {
+        info!("[CRYPTO] Price crossed {}", alert);
+    }
+
+    // Attention animations would give away what quiet mode hides
+    if !quiet::active() {
+        if let Err(e) = blink_item("crypto", color) {
+            error!("Failed to blink crypto: {}", e);
+        }
+    }
+}
+
+/// The configured currency pair's rate, fetched a few times a day
+pub struct Exchange;
+
+impl Provider for Exchange {
+    type Info = providers::ExchangeInfo;
+
+    fn name(&self) -> &'static str {
+        "exchange"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().exchange_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::ExchangeInfo>, String> {
+        let config = settings::current();
+        providers::get_exchange(config.exchange_pair.as_ref(), config.exchange_interval)
+    }
+
+    fn render(&self, info: &providers::ExchangeInfo) -> io::Result<()> {
+        update_exchange(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::ExchangeInfo) -> Value {
+        json!({
+            "pair": info.pair.to_string(),
+            "rate": info.rate.rate,
+            "date": info.rate.date,
+            "stale": info.rate.stale,
+        })
+    }
+}
+
+/// Unread entries in the configured RSS and Atom feeds, listed in a popup
+pub struct Feeds;
+
+impl Provider for Feeds {
+    type Info = providers::FeedsInfo;
+
+    fn name(&self) -> &'static str {
+        "feeds"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().feeds_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::FeedsInfo>, String> {
+        providers::get_feeds(&settings::current().feeds.urls)
+    }
+
+    fn render(&self, info: &providers::FeedsInfo) -> io::Result<()> {
+        if let Err(e) = sketchybar::set_popup("feeds", &feeds_popup(info, settings::current().feeds.recent)) {
+            error!("Failed to update the feeds popup: {}", e);
+        }
+        update_feeds(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::FeedsInfo) -> Value {
+        let recent: Vec<Value> = info
+            .feeds
+            .entries
+            .iter()
+            .take(settings::current().feeds.recent)
+            .map(|entry| {
+                json!({
+                    "feed": entry.feed,
+                    "title": entry.title,
+                    "link": entry.link,
+                    "at": entry.at,
+                    "unread": info.feeds.is_unread(entry),
+                })
+            })
+            .collect();
+        json!({
+            "unread": info.feeds.unread(),
+            "seen": info.feeds.seen,
+            "recent": recent,
+        })
+    }
+}
+
+/// Longest entry title in the feeds popup, in characters
+const FEED_TITLE_MAX: usize = 60;
+
+/// Popup rows for the feeds item: the `recent` newest entries, which open in the browser,
+/// then a row marking them all read
+fn feeds_popup(info: &providers::FeedsInfo, recent: usize) -> Vec<PopupEntry> {
+    let mut entries: Vec<PopupEntry> = info
+        .feeds
+        .entries
+        .iter()
+        .take(recent)
+        .map(|entry| {
+            PopupEntry {
+                label: sketchybar::truncate(&entry.title, FEED_TITLE_MAX),
+                // A dot for entries published since the feeds were marked read
+                icon: info.feeds.is_unread(entry).then(|| "\u{f111}".to_string()),
+                // `open` would also launch apps and files, which a feed has no business doing
+                click_script: Some(sketchybar::open_url_script(&entry.link))
+                    .filter(|_| entry.link.starts_with("https://") || entry.link.starts_with("http://")),
+            }
+        })
+        .collect();
+    if info.feeds.unread() > 0 {
+        entries.push(PopupEntry {
+            label: "Mark all as read".to_string(),
+            icon: Some("\u{f00c}".to_string()), // nf-fa-check
+            click_script: Some(format!("\"{}\" feeds-read", sketchycli_path())),
+        });
+    }
+    entries
+}
+
+/// Mark every feed entry so far as read, then redraw the item
+pub fn handle_feeds_read() -> Result<(), String> {
+    feeds::mark_read()?;
+    Feeds.run();
+    Ok(())
+}
+
+/// Pull requests waiting for the user's review on GitHub, listed in a popup
+pub struct Reviews;
+
+impl Provider for Reviews {
+    type Info = providers::ReviewsInfo;
+
+    fn name(&self) -> &'static str {
+        "reviews"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().reviews_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::ReviewsInfo>, String> {
+        providers::get_reviews(&settings::current().reviews)
+    }
+
+    fn render(&self, info: &providers::ReviewsInfo) -> io::Result<()> {
+        if let Err(e) = sketchybar::set_popup("reviews", &reviews_popup(info, &settings::current().reviews.query)) {
+            error!("Failed to update the reviews popup: {}", e);
+        }
+        update_reviews(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::ReviewsInfo) -> Value {
+        let pulls: Vec<Value> = info
+            .queue
+            .pulls
+            .iter()
+            .map(|pull| {
+                json!({"repository": pull.repository, "number": pull.number, "title": pull.title, "url": pull.url})
+            })
+            .collect();
+        json!({
+            "count": info.queue.count,
+            "pulls": pulls,
+        })
+    }
+}
+
+/// Longest pull request, merge request or issue title in the reviews, gitlab and jira popups, in characters
+const PULL_TITLE_MAX: usize = 50;
+
+/// Popup rows for the reviews item: a row per pull request, which opens it, and when the queue
+/// is longer than that, a row opening the search on GitHub
+fn reviews_popup(info: &providers::ReviewsInfo, query: &str) -> Vec<PopupEntry> {
+    let mut entries: Vec<PopupEntry> = info
+        .queue
+        .pulls
+        .iter()
+        .map(|pull| PopupEntry {
+            label: format!("{}#{} {}", pull.repository, pull.number, sketchybar::truncate(&pull.title, PULL_TITLE_MAX)),
+            icon: None,
+            click_script: Some(sketchybar::open_url_script(&pull.url)),
+        })
+        .collect();
+    let more = info.queue.count.saturating_sub(info.queue.pulls.len() as u64);
+    if more > 0 {
+        entries.push(PopupEntry {
+            label: format!("{} more on GitHub", more),
+            icon: Some("\u{f09b}".to_string()), // nf-fa-github
+            click_script: Some(sketchybar::open_url_script(&github::search_url(query))),
+        });
+    }
+    entries
+}
+
+/// Merge requests assigned to the user on GitLab and the pipelines of their own, listed in a popup
+pub struct Gitlab;
+
+impl Provider for Gitlab {
+    type Info = providers::GitlabInfo;
+
+    fn name(&self) -> &'static str {
+        "gitlab"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().gitlab_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::GitlabInfo>, String> {
+        providers::get_gitlab(&settings::current().gitlab)
+    }
+
+    fn render(&self, info: &providers::GitlabInfo) -> io::Result<()> {
+        let config = settings::current();
+        if let Err(e) = sketchybar::set_popup("gitlab", &gitlab_popup(info, &config.gitlab.url)) {
+            error!("Failed to update the gitlab popup: {}", e);
+        }
+        let color = if info.overview.failed() > 0 { config.gitlab.failed_color.as_str() } else { "0xffffffff" };
+        update_gitlab(info.icon(), &info.label(), color)
+    }
+
+    fn describe(&self, info: &providers::GitlabInfo) -> Value {
+        let merge_request =
+            |mr: &gitlab::MergeRequest| json!({"reference": mr.reference, "title": mr.title, "url": mr.url});
+        let assigned: Vec<Value> = info.overview.assigned.iter().map(merge_request).collect();
+        let pipelines: Vec<Value> = info
+            .overview
+            .branches
+            .iter()
+            .map(|branch| {
+                json!({
+                    "branch": branch.name,
+                    "status": branch.pipeline.name(),
+                    "merge_request": merge_request(&branch.merge_request),
+                })
+            })
+            .collect();
+        json!({
+            "assigned_count": info.overview.assigned_count,
+            "assigned": assigned,
+            "failed": info.overview.failed(),
+            "pipelines": pipelines,
+        })
+    }
+}
+
+/// Popup rows for the gitlab item: a row per assigned merge request, which opens it, a row opening
+/// the rest when there are more, then a row per pipeline of the user's branches, failed ones first
+fn gitlab_popup(info: &providers::GitlabInfo, url: &str) -> Vec<PopupEntry> {
+    let overview = &info.overview;
+    let mut entries: Vec<PopupEntry> = overview
+        .assigned
+        .iter()
+        .map(|mr| PopupEntry {
+            label: format!("{} {}", mr.reference, sketchybar::truncate(&mr.title, PULL_TITLE_MAX)),
+            icon: None,
+            click_script: Some(sketchybar::open_url_script(&mr.url)),
+        })
+        .collect();
+    let more = overview.assigned_count.saturating_sub(overview.assigned.len() as u64);
+    if more > 0 {
+        entries.push(PopupEntry {
+            label: format!("{} more on GitLab", more),
+            icon: Some("\u{f296}".to_string()), // nf-fa-gitlab
+            click_script: Some(sketchybar::open_url_script(&gitlab::assigned_url(url, &overview.username))),
+        });
+    }
+    let mut branches: Vec<&gitlab::Branch> = overview.branches.iter().collect();
+    branches.sort_by_key(|branch| branch.pipeline != gitlab::PipelineStatus::Failed);
+    entries.extend(branches.into_iter().map(|branch| PopupEntry {
+        label: format!("{} {} {}", branch.merge_request.reference, branch.name, branch.pipeline.name()),
+        icon: Some(branch.pipeline.icon().to_string()),
+        click_script: Some(sketchybar::open_url_script(&gitlab::pipelines_url(&branch.merge_request))),
+    }));
+    entries
+}
+
+/// Open Jira issues assigned to the user, colored by the most urgent and listed in a popup
+pub struct Jira;
+
+impl Provider for Jira {
+    type Info = providers::JiraInfo;
+
+    fn name(&self) -> &'static str {
+        "jira"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().jira_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::JiraInfo>, String> {
+        providers::get_jira(&settings::current().jira)
+    }
+
+    fn render(&self, info: &providers::JiraInfo) -> io::Result<()> {
+        let config = settings::current();
+        if let Err(e) = sketchybar::set_popup("jira", &jira_popup(info, &config.jira)) {
+            error!("Failed to update the jira popup: {}", e);
+        }
+        let color = match info.assigned.priority() {
+            Some(jira::Priority::High) => config.jira.high_color.as_str(),
+            Some(jira::Priority::Medium) => config.jira.medium_color.as_str(),
+            _ => "0xffffffff",
+        };
+        update_jira(info.icon(), &info.label(), color)
+    }
+
+    fn describe(&self, info: &providers::JiraInfo) -> Value {
+        let issues: Vec<Value> = info
+            .assigned
+            .issues
+            .iter()
+            .map(|issue| {
+                let priority = match issue.priority {
+                    jira::Priority::High => "high",
+                    jira::Priority::Medium => "medium",
+                    jira::Priority::Low => "low",
+                };
+                json!({"key": issue.key, "summary": issue.summary, "priority": priority})
+            })
+            .collect();
+        json!({
+            "count": info.assigned.count,
+            "more": info.assigned.more,
+            "issues": issues,
+        })
+    }
+}
+
+/// Popup rows for the jira item: a row per issue, most urgent first, which opens it, and when
+/// there are more issues than that, a row opening the search on the site
+fn jira_popup(info: &providers::JiraInfo, config: &JiraConfig) -> Vec<PopupEntry> {
+    let Some(url) = &config.url else {
+        return Vec::new();
+    };
+    let assigned = &info.assigned;
+    let mut entries: Vec<PopupEntry> = assigned
+        .issues
+        .iter()
+        .map(|issue| PopupEntry {
+            label: format!("{} {}", issue.key, sketchybar::truncate(&issue.summary, PULL_TITLE_MAX)),
+            icon: None,
+            click_script: Some(sketchybar::open_url_script(&jira::issue_url(url, &issue.key))),
+        })
+        .collect();
+    if assigned.more || assigned.count > assigned.issues.len() as u64 {
+        let label = if assigned.more {
+            "More in Jira".to_string()
+        } else {
+            format!("{} more in Jira", assigned.count - assigned.issues.len() as u64)
+        };
+        entries.push(PopupEntry {
+            label,
+            icon: Some(info.icon().to_string()),
+            click_script: Some(sketchybar::open_url_script(&jira::search_url(url, &jira::jql(&config.projects)))),
+        });
+    }
+    entries
+}
+
+/// Active Linear issues assigned to the user in the current cycle
+pub struct Linear;
+
+impl Provider for Linear {
+    type Info = providers::LinearInfo;
+
+    fn name(&self) -> &'static str {
+        "linear"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().linear_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::LinearInfo>, String> {
+        providers::get_linear(&settings::current().linear)
+    }
+
+    fn render(&self, info: &providers::LinearInfo) -> io::Result<()> {
+        update_linear(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::LinearInfo) -> Value {
+        json!({
+            "issues": info.count.issues,
+            "more": info.count.more,
+        })
+    }
+}
+
+/// Load averages per core, colored by the configured thresholds
+pub struct Load;
+
+impl Provider for Load {
+    type Info = providers::LoadInfo;
+
+    fn name(&self) -> &'static str {
+        "load"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().load_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::LoadInfo>, String> {
+        providers::get_load().map(Some).ok_or_else(|| "Failed to read the load averages".to_string())
+    }
+
+    fn render(&self, info: &providers::LoadInfo) -> io::Result<()> {
+        let config = settings::current();
+        let level = config.load.level(info.percentages()[0]);
+        update_load(info.icon(), &info.label(), config.load.colors.color(level))
+    }
+
+    fn describe(&self, info: &providers::LoadInfo) -> Value {
+        let percentages = info.percentages();
+        let level = settings::current().load.level(percentages[0]);
+        json!({"averages": info.averages, "cores": info.cores, "percentages": percentages, "level": level.to_string()})
+    }
+}
+
+/// Download and upload rates, from the byte counters of the configured interfaces
+pub struct Network;
+
+impl Provider for Network {
+    type Info = providers::ThroughputInfo;
+
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().network_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::ThroughputInfo>, String> {
+        providers::get_throughput(&settings::current().network_interface).map(Some)
+    }
+
+    fn render(&self, info: &providers::ThroughputInfo) -> io::Result<()> {
+        update_network(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::ThroughputInfo) -> Value {
+        json!({"interface": info.interface, "down": info.down, "up": info.up})
+    }
+}
+
+/// Disk read and write rates, from the byte counters of every disk
+pub struct Disk;
+
+impl Provider for Disk {
+    type Info = providers::DiskInfo;
+
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(settings::current().disk_interval)
+    }
+
+    fn refresh(&self) -> Result<Option<providers::DiskInfo>, String> {
+        providers::get_disk().map(Some)
+    }
+
+    fn render(&self, info: &providers::DiskInfo) -> io::Result<()> {
+        metrics::update(|m| m.disk = Some((info.read, info.write)));
+        update_disk(info.icon(), &info.label())
+    }
+
+    fn describe(&self, info: &providers::DiskInfo) -> Value {
+        json!({"read": info.read, "write": info.write})
+    }
+}
+
+/// Register the built-in providers, in the order they first render
+pub fn register_providers() {
+    providers::register(Clock);
+    providers::register(Battery::default());
+    providers::register(Brew);
+    providers::register(Teams);
+    providers::register(Wifi);
+    providers::register(Network);
+    providers::register(Cpu);
+    providers::register(TopProcess);
+    providers::register(Ram);
+    providers::register(Disk);
+    providers::register(Load);
+    providers::register(Thermal);
+    providers::register(Fan);
+    providers::register(FocusMode);
+    providers::register(Appearance);
+    providers::register(Brightness);
+    providers::register(Calendar);
+    providers::register(Zoom);
+    providers::register(Weather);
+    providers::register(Sun);
+    providers::register(Moon);
+    providers::register(Crypto);
+    providers::register(Exchange);
+    providers::register(Feeds);
+    providers::register(Reviews);
+    providers::register(Gitlab);
+    providers::register(Jira);
+    providers::register(Linear);
+}
+
+/// Re-check the badge on the configured schedule after a click
+///
+/// Clicking opens the app, and the badge takes a few seconds to clear once
+/// the notifications have been seen.
+pub fn handle_teams_click() {
+    let delays = providers::teams_config().click_delays();
+    runtime::spawn(async move {
+        for delay in delays {
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+            runtime::background(|| Teams.run());
+        }
+    });
+}
+
+/// When each custom item is next due, scheduled when it refreshes
+fn custom_due() -> &'static Mutex<HashMap<String, Instant>> {
+    static DUE: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    DUE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Schedule a custom item's next refresh one interval, plus its jitter, from now
+fn schedule_custom(due: &mut HashMap<String, Instant>, name: &str, item: &CustomItem) {
+    let delay = Duration::from_secs(item.interval) + providers::jitter(item.jitter);
+    due.insert(name.to_string(), Instant::now() + delay);
+}
+
+/// Run a custom item's commands and render their output
+pub fn handle_custom_refresh(name: &str) {
+    if !settings::enabled(name) {
+        return;
+    }
+    let Some(item) = settings::current().custom_items.get(name).cloned() else {
+        return;
+    };
+
+    if let Ok(mut due) = custom_due().lock() {
+        schedule_custom(&mut due, name, &item);
+    }
+    let Some(info) = events::timed("custom", || providers::get_custom(&item)) else {
+        return;
+    };
+    providers::record(name, json!({"label": info.label, "icon": info.icon, "visible": info.visible}));
+    if let Err(e) = update_custom(name, &info) {
+        error!("Failed to update {}: {}", name, e);
+    }
+}
+
+/// Refresh the custom items whose interval has passed, each in the background
+pub fn handle_custom_items_due() {
+    let config = settings::current();
+    let now = Instant::now();
+
+    let ready: Vec<String> = match custom_due().lock() {
+        Ok(mut due) => {
+            let ready: Vec<String> = config
+                .custom_items
+                .iter()
+                .filter(|(name, _)| settings::enabled(name) && due.get(*name).is_none_or(|at| now >= *at))
+                .map(|(name, _)| name.clone())
+                .collect();
+
+            // Rescheduled before running, so a slow command isn't started again on the next tick
+            for name in &ready {
+                schedule_custom(&mut due, name, &config.custom_items[name]);
+            }
+            ready
+        }
+        Err(_) => return,
+    };
+
+    for name in ready {
+        runtime::background(move || handle_custom_refresh(&name));
+    }
+}
+
+/// Run a custom item's click command, then refresh it
+pub fn handle_custom_click(name: &str) -> Result<(), String> {
+    let item = settings::current()
+        .custom_items
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("Unknown custom item: {}", name))?;
+
+    let name = name.to_string();
+    runtime::background(move || {
+        if let Some(click) = &item.click {
+            providers::run_shell(click);
+        }
+        handle_custom_refresh(&name);
+    });
+    Ok(())
+}
+
+/// sketchycli, installed next to the daemon
+fn sketchycli_path() -> String {
+    env::current_exe()
+        .map(|exe| exe.with_file_name("sketchycli").display().to_string())
+        .unwrap_or_else(|_| "sketchycli".to_string())
+}
+
+/// Create the bar and its items, replacing ones sketchybarrc or an earlier run added
+pub fn handle_bootstrap_items(config: &Config) {
+    bootstrap::run(config, &sketchycli_path());
+}
+
+/// Set the bar up again after sketchybar restarted and forgot every item, then redraw it
+pub fn handle_bootstrap(state: &Arc<Mutex<DaemonState>>) {
+    let current = settings::current();
+    handle_bootstrap_items(&current);
 
-/// A builder for batching sketchybar commands
-#[derive(Debug, Default)]
-pub struct SketchybarBatch {
-    args: Vec<String>,
+    // Custom, alias and graph items went with the rest; adding them as new brings them back
+    let mut empty = (*current).clone();
+    empty.custom_items.clear();
+    empty.aliases.clear();
+    empty.history.graphs.clear();
+    register_custom_items(&empty, &current);
+    register_aliases(&empty, &current);
+    register_graphs(&empty, &current);
+
+    rerender_all(state);
 }
 
-impl SketchybarBatch {
-    pub fn new() -> Self {
-        Self::default()
+/// Add custom items new to the config, and remove ones that are gone or moved
+fn register_custom_items(previous: &Config, current: &Config) {
+    let moved = |name: &String, item: &CustomItem, other: &Config| {
+        other.custom_items.get(name).is_none_or(|o| o.position != item.position)
+    };
+    let stale: Vec<&String> = previous
+        .custom_items
+        .iter()
+        .filter(|(name, item)| moved(name, item, current))
+        .map(|(name, _)| name)
+        .collect();
+    let added: Vec<(&String, &CustomItem)> = current
+        .custom_items
+        .iter()
+        .filter(|(name, item)| moved(name, item, previous))
+        .collect();
+
+    // Items added by an earlier run of the daemon are still in the bar
+    let removed: Vec<&str> = stale
+        .iter()
+        .copied()
+        .chain(added.iter().map(|(name, _)| *name))
+        .map(String::as_str)
+        .collect();
+    remove_items(&removed);
+
+    let sketchycli = sketchycli_path();
+    let mut batch = SketchybarBatch::new();
+    for (name, item) in &added {
+        let click_script = format!("\"{}\" on-custom-clicked {}", sketchycli, name);
+        let drawing = if settings::enabled(name) { "on" } else { "off" };
+        batch
+            .add(name, &item.position.to_string())
+            .set(name, &[("click_script", &click_script), ("drawing", drawing)]);
+    }
+    if let Err(e) = batch.execute() {
+        error!("Failed to add custom items: {}", e);
     }
 
-    /// Set properties on an item
-    pub fn set(&mut self, item: &str, props: &[(&str, &str)]) -> &mut Self {
-        self.args.push("--set".to_string());
-        self.args.push(item.to_string());
-        for (key, value) in props {
-            self.args.push(format!("{}={}", key, value));
+    // New and changed items render now rather than at their next interval
+    for (name, item) in &current.custom_items {
+        if previous.custom_items.get(name) != Some(item) || added.iter().any(|(added, _)| *added == name) {
+            let name = name.clone();
+            runtime::background(move || handle_custom_refresh(&name));
         }
-        self
     }
+}
+
+/// Add aliases new to the config, and remove ones that are gone or changed
+fn register_aliases(previous: &Config, current: &Config) {
+    let changed = |name: &String, alias: &AliasItem, other: &Config| other.aliases.get(name) != Some(alias);
+    let stale: Vec<&str> = previous
+        .aliases
+        .iter()
+        .filter(|(name, alias)| changed(name, alias, current))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    let added: Vec<(&String, &AliasItem)> =
+        current.aliases.iter().filter(|(name, alias)| changed(name, alias, previous)).collect();
+
+    // Aliases added by an earlier run of the daemon are still in the bar
+    let removed: Vec<&str> = stale.iter().copied().chain(added.iter().map(|(name, _)| name.as_str())).collect();
+    remove_items(&removed);
 
-    /// Add animation with curve and duration
-    pub fn animate(&mut self, curve: &str, duration: u32) -> &mut Self {
-        self.args.push("--animate".to_string());
-        self.args.push(curve.to_string());
-        self.args.push(duration.to_string());
-        self
+    // sketchybar names an alias after its source; the config's name is what rules and styles use
+    let mut batch = SketchybarBatch::new();
+    for (name, alias) in added {
+        let drawing = if settings::enabled(name) { "on" } else { "off" };
+        batch
+            .add_alias(&alias.source, &alias.position.to_string())
+            .rename(&alias.source, name)
+            .set_styled(name, &[("drawing", drawing)]);
+    }
+    if let Err(e) = batch.execute() {
+        error!("Failed to add aliases: {}", e);
     }
+}
 
-    /// Execute the batched commands
-    pub fn execute(&self) -> Result<(), std::io::Error> {
-        if self.args.is_empty() {
-            return Ok(());
-        }
+/// Share of a `max` MB/s graph that a disk rate in bytes a second fills, at most all of it
+///
+/// Worked out in floating point, as `max` in bytes can be past what fits in a u64.
+fn disk_share(bytes: u64, max: u64) -> f32 {
+    (bytes as f64 / (max as f64 * 1_000_000.0)).min(1.0) as f32
+}
 
-        let status = Command::new("sketchybar")
-            .args(&self.args)
-            .status()?;
+/// Sample the graphed metrics and push the new values to their graph items
+pub fn handle_history_sample() {
+    let config = settings::current();
+    let mut batch = SketchybarBatch::new();
+    let share = |percentage: u8| f32::from(percentage) / 100.0;
+    for metric in &config.history.graphs {
+        let value = match metric.as_str() {
+            // The battery item keeps this up to date from power notifications
+            "battery" => metrics::snapshot().battery.map(|(percentage, _)| share(percentage)),
+            "cpu" => events::timed("cpu", providers::get_system_info).map(|info| share(info.cpu_percentage)),
+            // The disk item's last rates, since sampling here would reset its counters
+            "disk" => metrics::snapshot().disk.map(|(read, write)| disk_share(read + write, config.disk_graph_max)),
+            _ => None,
+        };
+        let Some(value) = value else {
+            continue;
+        };
 
-        if status.success() {
-            Ok(())
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "sketchybar command failed",
-            ))
+        history::record(metric, value, config.history.retention);
+        let item = history::graph_item(metric);
+        if settings::enabled(&item) {
+            batch.push(&item, &[value]);
         }
     }
+    if let Err(e) = batch.execute() {
+        error!("Failed to update graphs: {}", e);
+    }
 }
 
-/// Convenience function to set properties on a single item
-fn set_item(item: &str, props: &[(&str, &str)]) -> Result<(), std::io::Error> {
+/// Add graph items new to the config with the history so far, and remove ones that are gone
+fn register_graphs(previous: &Config, current: &Config) {
+    history::set_retention(current.history.retention);
+
+    // A new width takes re-adding the item
+    let resized = previous.history.retention != current.history.retention;
+    let stale: Vec<String> = previous
+        .history
+        .graphs
+        .iter()
+        .filter(|metric| resized || !current.history.graphs.contains(metric))
+        .map(|metric| history::graph_item(metric))
+        .collect();
+    let added: Vec<&String> = current
+        .history
+        .graphs
+        .iter()
+        .filter(|metric| resized || !previous.history.graphs.contains(metric))
+        .collect();
+
+    // Items added by an earlier run of the daemon are still in the bar
+    let removed: Vec<String> =
+        stale.into_iter().chain(added.iter().map(|metric| history::graph_item(metric))).collect();
+    remove_items(&removed.iter().map(String::as_str).collect::<Vec<_>>());
+
     let mut batch = SketchybarBatch::new();
-    batch.set(item, props);
-    batch.execute()
+    for metric in added {
+        let item = history::graph_item(metric);
+        let drawing = if settings::enabled(&item) { "on" } else { "off" };
+        batch
+            .add_graph(&item, "right", current.history.retention)
+            .set_styled(&item, &[("drawing", drawing)])
+            .push(&item, &history::samples(metric));
+    }
+    if let Err(e) = batch.execute() {
+        error!("Failed to add graph items: {}", e);
+    }
 }
 
-/// Update the clock item
-fn update_clock(time: &str) -> Result<(), std::io::Error> {
-    set_item("clock", &[("label", time)])
+/// Ask in a dialog whether to upgrade the outdated packages
+fn confirm_brew_upgrade() -> bool {
+    let script = "display dialog \"Upgrade outdated Homebrew packages?\" \
+                  with title \"Homebrew\" buttons {\"Cancel\", \"Upgrade\"} default button \"Upgrade\"";
+    match Command::new("osascript").args(["-e", script]).output() {
+        // Cancel makes osascript exit with an error
+        Ok(output) => output.status.success(),
+        Err(e) => {
+            error!("Failed to ask for brew upgrade confirmation: {}", e);
+            false
+        }
+    }
 }
 
-/// Update the battery item
-fn update_battery(icon: &str, icon_color: &str, label_color: &str, percentage: u8) -> Result<(), std::io::Error> {
-    set_item("battery", &[
-        ("icon", icon),
-        ("icon.color", icon_color),
-        ("label.color", label_color),
-        ("label", &format!("{}%", percentage)),
-    ])
+pub fn handle_brew_click() {
+    match providers::brew_config().click {
+        BrewClick::Upgrade => handle_brew_upgrade(),
+        // The dialog blocks until answered; don't hold up the client
+        BrewClick::Confirm => {
+            runtime::background(|| {
+                if confirm_brew_upgrade() {
+                    handle_brew_upgrade();
+                }
+            });
+        }
+        BrewClick::Refresh => {
+            runtime::background(|| Brew.run());
+        }
+        BrewClick::Popup => {
+            if let Err(e) = SketchybarBatch::new().popup("brew", "toggle").execute() {
+                error!("Failed to toggle the brew popup: {}", e);
+            }
+        }
+    }
 }
 
-/// Update the volume item
-fn update_volume(icon: &str, percentage: u8) -> Result<(), std::io::Error> {
-    set_item("volume", &[
-        ("icon", icon),
-        ("label", &format!("{}%", percentage)),
-    ])
-}
+/// Name of the animation running while `brew upgrade` does
+const BREW_UPGRADE_ANIMATION: &str = "brew-upgrade";
+
+pub fn handle_brew_upgrade() {
+    // Set the refresh icon
+    if let Err(e) = set_item("brew", &[
+        ("label", "\u{f409}"),
+        ("label.y_offset", "0"),
+    ]) {
+        error!("Failed to set brew refreshing label: {}", e);
+    }
+
+    // Rotation is not supported, so the refresh icon bounces until the upgrade finishes
+    let bounce = Animation::new("brew")
+        .keyframe("sin", 15, &[("label.y_offset", "-3")])
+        .keyframe("sin", 15, &[("label.y_offset", "0")])
+        .baseline(&[("label.y_offset", "0")]);
+    if let Err(e) = bounce.start(BREW_UPGRADE_ANIMATION) {
+        error!("Failed to start brew animation: {}", e);
+    }
+
+    // Run brew upgrade in the background so animation can continue
+    runtime::background(|| {
+        let config = providers::brew_config();
+        let mut command = Command::new(config.binary());
+        command.arg("upgrade");
+        if config.greedy {
+            command.arg("--greedy");
+        }
+        let result = command.output();
+
+        let summary = match result {
+            Ok(output) if output.status.success() => providers::upgrade_summary(&String::from_utf8_lossy(&output.stdout)),
+            Ok(output) => {
+                error!("brew upgrade failed: {}", String::from_utf8_lossy(&output.stderr));
+                "brew upgrade failed".to_string()
+            }
+            Err(e) => {
+                error!("Failed to run brew upgrade: {}", e);
+                "brew upgrade failed".to_string()
+            }
+        };
+        if settings::current().notifications.brew_upgrade {
+            notify::send("Homebrew", &summary);
+        }
 
-/// Update the front app item
-fn update_front_app(icon: &str, app_name: &str) -> Result<(), std::io::Error> {
-    set_item("front_app", &[
-        ("icon", icon),
-        ("label", &format!("❯ {}", app_name)),
-    ])
+        // Stop bouncing and put the count back
+        if let Err(e) = cancel_animation(BREW_UPGRADE_ANIMATION) {
+            error!("Failed to reset brew offset: {}", e);
+        }
+        Brew.run();
+    });
 }
 
-/// Update the brew outdated item
-fn update_brew(icon: &str, formulae: usize, casks: usize) -> Result<(), std::io::Error> {
-    let total = formulae + casks;
-    let label = if total == 0 {
-        "✓".to_string()
-    } else {
-        format!("{}", total)
+pub fn handle_volume_refresh() {
+    if !settings::enabled("volume") {
+        return;
+    }
+
+    let Some(info) = events::timed("volume", providers::get_volume) else {
+        return;
     };
-    set_item("brew", &[
-        ("icon", icon),
-        ("label", &label),
-    ])
+    metrics::update(|m| m.volume = Some((info.percentage, info.muted)));
+    show_volume(&info);
 }
 
-/// Update the Microsoft Teams notification item
-fn update_teams(icon: &str, icon_color: &str, border_color: &str, notification_count: u32) -> Result<(), std::io::Error> {
-    let label = if notification_count > 0 {
-        format!("{}", notification_count)
-    } else {
-        String::new()
+/// Draw the volume item and its slider
+fn show_volume(info: &providers::VolumeInfo) {
+    // The slider is one of the items the daemon adds itself
+    if let Err(e) = update_volume(info.icon(), info.percentage, settings::current().bootstrap_items) {
+        error!("Failed to update volume: {}", e);
+    }
+}
+
+/// Mute or unmute, or with the configured modifier held show or hide the volume slider
+///
+/// The item is redrawn for the new state straight away; CoreAudio's
+/// notification confirms it once osascript is done.
+pub fn handle_volume_click(modifier: Option<&str>) -> Result<(), String> {
+    if settings::current().volume_slider_modifier.matches(modifier) {
+        if let Err(e) = SketchybarBatch::new().popup("volume", "toggle").execute() {
+            error!("Failed to toggle the volume slider: {}", e);
+        }
+        return Ok(());
+    }
+
+    let current = providers::get_volume().ok_or("No output volume to mute")?;
+    let info = providers::VolumeInfo { muted: !current.muted, ..current };
+    show_volume(&info);
+    volume::set_muted(info.muted).inspect_err(|_| handle_volume_refresh())
+}
+
+/// Step the volume by the lines scrolled over the item, redrawing it ahead of CoreAudio's notification
+pub fn handle_volume_scroll(delta: &str) -> Result<(), String> {
+    let delta: i32 = delta.trim().parse().map_err(|_| format!("Invalid scroll delta: {}", delta))?;
+    let step = settings::current().volume_scroll_step;
+    if delta == 0 || step == 0 {
+        return Ok(());
+    }
+
+    let current = providers::get_volume().ok_or("No output volume to adjust")?;
+    let info = providers::VolumeInfo { percentage: providers::scrolled(current.percentage, delta, step), ..current };
+    show_volume(&info);
+    volume::set(info.percentage).inspect_err(|_| handle_volume_refresh())
+}
+
+/// Set the output volume from `volume <0-100>`, e.g. sent by dragging the slider
+pub fn handle_volume_set(value: &str) -> Result<(), String> {
+    let percentage: u8 = value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|percentage| *percentage <= 100)
+        .ok_or_else(|| format!("Invalid volume: {} (expected 0-100)", value))?;
+    volume::set(percentage)
+}
+
+/// Output devices at the last render, so volume changes don't rebuild the device popup
+fn last_audio_output() -> &'static Mutex<Option<providers::AudioOutputInfo>> {
+    static OUTPUT: OnceLock<Mutex<Option<providers::AudioOutputInfo>>> = OnceLock::new();
+    OUTPUT.get_or_init(|| Mutex::new(None))
+}
+
+/// Redraw the audio_output item and its device popup; unless `force`d, only when the devices changed
+pub fn handle_audio_output_refresh(force: bool) {
+    if !settings::enabled("audio_output") {
+        return;
+    }
+
+    let Some(info) = events::timed("audio_output", providers::get_audio_output) else {
+        return;
     };
-    set_item("teams", &[
-        ("icon", icon),
-        ("icon.color", icon_color),
-        ("background.border_color", border_color),
-        ("label", &label),
-        ("drawing", "on"),
-    ])
+    let unchanged = match last_audio_output().lock() {
+        Ok(mut last) => last.replace(info.clone()).as_ref() == Some(&info),
+        Err(_) => false,
+    };
+    if unchanged && !force {
+        return;
+    }
+    providers::record("audio_output", json!({"device": info.current, "devices": info.devices}));
+
+    if let Err(e) = update_audio_output(info.icon(), info.current.as_deref().unwrap_or_default()) {
+        error!("Failed to update audio output: {}", e);
+    }
+    if let Err(e) = sketchybar::set_popup("audio_output", &audio_output_popup(&info)) {
+        error!("Failed to update the audio output popup: {}", e);
+    }
 }
 
-/// Shared state for the daemon
-#[derive(Debug)]
-pub struct DaemonState {
-    /// Current front app (for deduplication)
-    pub front_app: String,
-    /// Monitor mapper for workspace filtering
-    pub monitor_mapper: MonitorMapper,
-    /// Last workspace change timestamp for debouncing
-    pub last_workspace_change: Option<Instant>,
-    /// Previously rendered workspaces (to detect which ones need clearing)
-    pub previous_workspaces: HashSet<String>,
+/// Popup rows for the audio_output item: every output device, the current one checked
+fn audio_output_popup(info: &providers::AudioOutputInfo) -> Vec<PopupEntry> {
+    let sketchycli = sketchycli_path();
+    info.devices
+        .iter()
+        .map(|device| PopupEntry {
+            label: device.clone(),
+            icon: (info.current.as_ref() == Some(device)).then(|| "\u{f00c}".to_string()),
+            click_script: Some(format!("\"{}\" audio-output '{}'", sketchycli, device.replace('\'', "'\\''"))),
+        })
+        .collect()
 }
 
-impl Default for DaemonState {
-    fn default() -> Self {
-        Self {
-            front_app: String::new(),
-            monitor_mapper: MonitorMapper::new(),
-            last_workspace_change: None,
-            previous_workspaces: HashSet::new(),
+/// Show or hide the output device popup
+pub fn handle_audio_output_click() {
+    if let Err(e) = SketchybarBatch::new().popup("audio_output", "toggle").execute() {
+        error!("Failed to toggle the audio output popup: {}", e);
+    }
+}
+
+/// Switch the output from `audio-output <device>`, e.g. sent by a popup row; the listener redraws the item
+pub fn handle_audio_output_set(device: &str) -> Result<(), String> {
+    info!("[AUDIO] Switching output to {}", device);
+    volume::set_output(device)
+}
+
+/// Extracted icon image for an app, as a sketchybar property value
+///
+/// The app keeps its glyph until the image is extracted, and the items are drawn again then.
+fn image_path(app: &aerospace::App, state: &Arc<Mutex<DaemonState>>) -> Option<String> {
+    let state = Arc::clone(state);
+    app_icons::image_for(app.icon_source(), move || redraw_app_icons(&state))
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+pub fn handle_focus_refresh(app: Option<aerospace::App>, state: &Arc<Mutex<DaemonState>>) {
+    if !settings::enabled("front_app") {
+        return;
+    }
+
+    let app = app.or_else(|| events::timed("front_app", aerospace::get_focused_app));
+
+    if let Some(app) = &app {
+        let icon = app.icon();
+        providers::record("front_app", json!({"name": app.name, "bundle_id": app.bundle_id, "icon": icon}));
+
+        // Update state
+        if let Ok(mut s) = state.lock() {
+            if s.front_app == app.name {
+                return; // No change
+            }
+            s.front_app = app.name.clone();
+        }
+
+        let image = match app_icons::mode() {
+            AppIconImages::Off => None,
+            _ => Some(image_path(app, state)),
+        };
+
+        let display = item_displays().front_app.map(|spec| {
+            let monitor_mapper = state.lock().map(|s| Arc::clone(&s.monitor_mapper));
+            match monitor_mapper {
+                Ok(mapper) => mapper.get_mappings().resolve(&spec),
+                Err(_) => spec.to_string(),
+            }
+        });
+
+        if let Err(e) = update_front_app(&icon, image.as_ref().map(|p| p.as_deref()), &app.name, display.as_deref()) {
+            error!("Failed to update front_app: {}", e);
         }
     }
 }
 
-pub fn handle_clock_refresh() {
-    let time = providers::get_clock();
-    if let Err(e) = update_clock(&time) {
-        eprintln!("Failed to update clock: {}", e);
+/// Reload the user icon mappings and re-render every item showing app icons
+pub fn handle_icons_reload(state: &Arc<Mutex<DaemonState>>) {
+    icon_map::reload_overrides();
+    redraw_app_icons(state);
+}
+
+/// Re-render every item showing app icons
+fn redraw_app_icons(state: &Arc<Mutex<DaemonState>>) {
+    // Bypass the front_app deduplication and the workspace debounce
+    if let Ok(mut s) = state.lock() {
+        s.front_app.clear();
+        s.last_workspace_change = None;
+    }
+
+    handle_focus_refresh(None, state);
+    handle_workspace_refresh(state);
+}
+
+/// Move items off the built-in display when the lid closes, and back when it opens
+pub fn handle_clamshell_change(state: &Arc<Mutex<DaemonState>>) {
+    let monitor_mapper = if let Ok(s) = state.lock() {
+        Arc::clone(&s.monitor_mapper)
+    } else {
+        return;
+    };
+    let mappings = monitor_mapper.get_mappings();
+
+    let moves = if let Ok(mut s) = state.lock() {
+        s.clamshell.update(&mappings, &clamshell::items(), clamshell::lid_closed, clamshell::pinned_displays)
+    } else {
+        return;
+    };
+
+    if moves.is_empty() {
+        return;
+    }
+
+    let mut batch = SketchybarBatch::new();
+    for (item, display) in &moves {
+        info!("[CLAMSHELL] Pinning {} to display {}", item, display);
+        batch.set(item, &[("display", display)]);
+    }
+    if let Err(e) = batch.execute() {
+        error!("Failed to move items for clamshell mode: {}", e);
     }
 }
 
-pub fn handle_battery_refresh(power_source: Option<String>) {
-    if let Some(info) = providers::get_battery(power_source) {
-        if let Err(e) = update_battery(info.icon(), info.icon_color(), info.label_color(), info.percentage) {
-            eprintln!("Failed to update battery: {}", e);
+/// Make a configuration live, hiding items it disables and showing ones it enables again
+pub fn apply_config(config: Config) {
+    log::set_level(config.log_level);
+    log::set_file(config.log_file.then(|| daemon::get_cache_dir().join("sketchybartender.log")));
+    icon_map::set_theme(config.icon_theme);
+    app_icons::set_mode(config.app_icon_images);
+    aerospace::set_icon_rules(aerospace::IconRules::from_config(&config));
+    clamshell::set_items(config.clamshell_items.clone());
+    providers::set_clock_format(config.clock.clone());
+    providers::set_battery_config(config.battery.clone());
+    providers::set_brew_config(config.brew.clone());
+    providers::set_teams_config(config.teams.clone());
+    set_item_displays(ItemDisplays {
+        front_app: config.front_app_display.clone(),
+        workspaces: config.workspace_display.clone(),
+    });
+    set_item_styles(config.styles.clone());
+    if let Some(address) = config.metrics_listen {
+        metrics::serve(address);
+    }
+
+    let previous = settings::set(config);
+    let current = settings::current();
+    register_custom_items(&previous, &current);
+    register_aliases(&previous, &current);
+    register_graphs(&previous, &current);
+
+    let mut batch = SketchybarBatch::new();
+    for item in &current.disabled_items {
+        if previous.is_enabled(item) {
+            batch.set(item, &[("drawing", "off")]);
+        }
+    }
+    for item in &previous.disabled_items {
+        if current.is_enabled(item) && !rules::is_hidden(item) {
+            batch.set(item, &[("drawing", "on")]);
         }
     }
+    if let Err(e) = batch.execute() {
+        error!("Failed to show or hide disabled items: {}", e);
+    }
+
+    // Rules may name items the new config enables, or come with it
+    handle_rules_refresh();
+
+    // Timers pick up changed intervals now rather than after their current wait
+    runtime::reschedule();
 }
 
-pub fn handle_brew_refresh() {
-    let info = providers::get_brew_outdated();
-    if let Err(e) = update_brew(info.icon(), info.formulae, info.casks) {
-        eprintln!("Failed to update brew: {}", e);
+/// Re-render every provider's item, each in the background so a slow one doesn't hold up the rest
+fn refresh_providers() {
+    for provider in providers::registered() {
+        runtime::background(move || provider.run());
     }
 }
 
-pub fn handle_teams_refresh() {
-    let info = providers::get_teams_notifications();
-    if let Err(e) = update_teams(
-        info.icon(),
-        info.icon_color(),
-        info.border_color(),
-        info.notification_count,
-    ) {
-        eprintln!("Failed to update teams: {}", e);
+/// Re-render an item that was hidden
+fn refresh_item(item: &str) {
+    if let Some(provider) = providers::find(item) {
+        runtime::background(move || provider.run());
+        return;
+    }
+    match item {
+        "volume" => handle_volume_refresh(),
+        "audio_output" => handle_audio_output_refresh(true),
+        // front_app keeps its last label until the focus changes
+        _ => handle_custom_refresh(item),
     }
 }
 
-pub fn handle_brew_upgrade() {
-    // Set the refresh icon
-    if let Err(e) = set_item("brew", &[
-        ("label", "\u{f409}"),
-        ("label.y_offset", "0"),
-    ]) {
-        eprintln!("Failed to set brew refreshing label: {}", e);
+/// Match the display rules and quiet mode, hiding and showing the items whose state changed
+pub fn handle_rules_refresh() {
+    let config = settings::current();
+    let mut hidden = if config.rules.is_empty() {
+        BTreeSet::new()
+    } else {
+        let context = events::timed("rules", || rules::context(RuleNeeds::of(&config.rules)));
+        hidden_items(&config.rules, &context)
+    };
+    if quiet::active() {
+        hidden.extend(config.quiet.items.iter().cloned());
+    }
+
+    let previous = rules::set_hidden(hidden.clone());
+    if previous == hidden {
+        return;
     }
 
-    // Create continuous pulsing animation for the label (refresh icon)
-    // Since rotation is not supported, use a bouncing y_offset animation
     let mut batch = SketchybarBatch::new();
+    for item in hidden.difference(&previous).filter(|item| config.is_enabled(item)) {
+        info!("[RULES] Hiding {}", item);
+        batch.set(item, &[("drawing", "off")]);
+    }
+    let shown: Vec<&String> = previous.difference(&hidden).filter(|item| config.is_enabled(item)).collect();
+    for item in &shown {
+        info!("[RULES] Showing {}", item);
+        batch.set(item, &[("drawing", "on")]);
+    }
+    if let Err(e) = batch.execute() {
+        error!("Failed to apply display rules: {}", e);
+    }
 
-    // Chain 60 bounce cycles (up and down) for ~30 seconds total
-    for _ in 0..60 {
-        batch.animate("sin", 15)  // Bounce up (0.25 seconds)
-             .set("brew", &[("label.y_offset", "-3")])
-             .animate("sin", 15)  // Bounce down (0.25 seconds)
-             .set("brew", &[("label.y_offset", "0")]);
+    for item in shown {
+        refresh_item(item);
     }
+}
 
-    if let Err(e) = batch.execute() {
-        eprintln!("Failed to start brew animation: {}", e);
+/// Turn quiet mode on or off from `sketchycli quiet on|off|toggle|auto`
+pub fn handle_quiet(command: &str) -> Result<(), String> {
+    if quiet::command(command)? {
+        info!("[QUIET] {}", quiet::status());
+        handle_rules_refresh();
     }
+    Ok(())
+}
 
-    // Run brew upgrade in a separate thread so animation can continue
-    thread::spawn(|| {
-        let result = Command::new("brew")
-            .arg("upgrade")
-            .output();
+/// Show the menu bar fallback as configured, or while sketchybar isn't running with `auto`
+pub fn handle_menubar_check() {
+    let visible = match settings::current().menubar_fallback {
+        MenubarFallback::Off => false,
+        MenubarFallback::On => true,
+        MenubarFallback::Auto => !sketchybar::is_running(),
+    };
+    menubar::set_visible(visible);
+}
 
-        match result {
-            Ok(output) => {
-                if !output.status.success() {
-                    eprintln!("brew upgrade failed: {}", String::from_utf8_lossy(&output.stderr));
-                }
+/// Run an entry chosen in the menu bar fallback's menu
+pub fn handle_menubar_action(action: Action) {
+    match action {
+        Action::Refresh => refresh_providers(),
+        Action::UpgradeBrew => handle_brew_upgrade(),
+        Action::ToggleQuiet => {
+            if let Err(e) = handle_quiet("toggle") {
+                warn!("{}", e);
             }
-            Err(e) => eprintln!("Failed to run brew upgrade: {}", e),
-        }
-
-        // Refresh the brew count after upgrade completes (this cancels animation and resets offset)
-        if let Err(e) = set_item("brew", &[("label.y_offset", "0")]) {
-            eprintln!("Failed to reset brew offset: {}", e);
         }
-        handle_brew_refresh();
-    });
+    }
 }
 
-pub fn handle_volume_refresh(vol: Option<u8>) {
-    let info = if let Some(v) = vol {
-        providers::VolumeInfo { percentage: v, muted: v == 0 }
-    } else if let Some(v) = providers::get_volume() {
-        v
-    } else {
+/// Follow screen sharing with quiet mode, when `auto` is set
+pub fn handle_quiet_check() {
+    let config = settings::current();
+    if !config.quiet.auto {
         return;
-    };
+    }
+
+    let sharing = events::timed("quiet", || quiet::screen_shared(&config.quiet.share_processes));
+    if quiet::set_sharing(sharing) {
+        info!("[QUIET] {}", quiet::status());
+        handle_rules_refresh();
+    }
+}
 
-    if let Err(e) = update_volume(info.icon(), info.percentage) {
-        eprintln!("Failed to update volume: {}", e);
+/// Match the display rules after a focus change, if any of them look at the focused app
+pub fn handle_focus_rules() {
+    if RuleNeeds::of(&settings::current().rules).app {
+        handle_rules_refresh();
     }
 }
 
-pub fn handle_focus_refresh(app: Option<String>, state: &Arc<Mutex<DaemonState>>) {
-    let app = app.or_else(|| aerospace::get_focused_app());
+/// Switch the live daemon to a profile, remembering it for the next start
+pub fn handle_profile_switch(name: &str, state: &Arc<Mutex<DaemonState>>) -> Result<(), String> {
+    let config = Config::load().with_profile(name)?;
+    Config::save_selected_profile(name)?;
+    info!("[PROFILE] Switching to {}", name);
+    apply_config(config);
+    rerender_all(state);
+    Ok(())
+}
 
-    if let Some(app_name) = &app {
-        let icon = icon_map::get_icon(app_name);
+/// Reapply the config file after it changed, keeping the running settings if it can't be used
+pub fn handle_config_reload(state: &Arc<Mutex<DaemonState>>) {
+    reload_config_in(&Config::get_config_dir(), state);
+}
 
-        // Update state
-        if let Ok(mut s) = state.lock() {
-            if s.front_app == *app_name {
-                return; // No change
-            }
-            s.front_app = app_name.clone();
+/// Reapply the config in `dir` like [`handle_config_reload`]
+fn reload_config_in(dir: &Path, state: &Arc<Mutex<DaemonState>>) {
+    let config = match Config::load_selected_in(dir, Config::strict_from_env(), false) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("[CONFIG] Keeping the running configuration: {}", e);
+            return;
+        }
+    };
+    // Saving without changes, or the daemon writing out the defaults, needs no redraw
+    if config == *settings::current() {
+        return;
+    }
+
+    info!("[CONFIG] Reloading {}", Config::get_config_path().display());
+    apply_config(config);
+    rerender_all(state);
+}
+
+/// Stop the daemon and exit: end the timers and client connections, remove the socket and, with
+/// `clear_on_exit`, the custom, alias and graph items the daemon added. Callers racing to shut down wait for the first.
+pub fn handle_shutdown() -> ! {
+    static SHUTDOWN: Once = Once::new();
+    SHUTDOWN.call_once(|| {
+        runtime::shutdown();
+        // A stale socket would make sketchycli wait for a daemon that isn't there
+        let _ = fs::remove_file(daemon::get_socket_path());
+
+        let config = settings::current();
+        if config.clear_on_exit {
+            let graphs: Vec<String> = config.history.graphs.iter().map(|metric| history::graph_item(metric)).collect();
+            let items: Vec<&str> =
+                config.custom_items.keys().chain(config.aliases.keys()).chain(&graphs).map(String::as_str).collect();
+            info!("[SHUTDOWN] Removing {}", items.join(", "));
+            remove_items(&items);
         }
+        info!("[SHUTDOWN] Stopped");
+    });
+    std::process::exit(0)
+}
+
+/// Re-render everything with new settings
+fn rerender_all(state: &Arc<Mutex<DaemonState>>) {
+    icon_map::reload_overrides();
+    if let Ok(mut s) = state.lock() {
+        s.front_app.clear();
+    }
+    handle_display_change(state);
+    handle_clamshell_change(state);
+    handle_volume_refresh();
+    handle_audio_output_refresh(true);
+    refresh_providers();
+}
+
+/// Rebuild the display mappings and re-render workspaces on their new displays
+pub fn handle_display_change(state: &Arc<Mutex<DaemonState>>) {
+    // Bypass the workspace debounce so the refresh isn't dropped, and the
+    // front_app deduplication so its display selector is resolved again
+    if let Ok(mut s) = state.lock() {
+        s.monitor_mapper.invalidate_cache();
+        s.last_workspace_change = None;
+        s.front_app.clear();
+    }
+
+    handle_workspace_refresh(state);
+    handle_focus_refresh(None, state);
+    publish_display_changes(state);
+}
+
+/// Publish which displays were added or removed since the last display change
+fn publish_display_changes(state: &Arc<Mutex<DaemonState>>) {
+    let monitor_mapper = if let Ok(s) = state.lock() {
+        Arc::clone(&s.monitor_mapper)
+    } else {
+        return;
+    };
+    let mappings = monitor_mapper.get_mappings();
+
+    let previous = if let Ok(mut s) = state.lock() {
+        s.last_mappings.replace(Arc::clone(&mappings))
+    } else {
+        return;
+    };
 
-        if let Err(e) = update_front_app(icon, app_name) {
-            eprintln!("Failed to update front_app: {}", e);
+    // Nothing to compare against at startup
+    if let Some(previous) = previous {
+        for change in mappings.changes(&previous) {
+            events::publish(Event::Display(change));
         }
     }
 }
@@ -294,25 +2435,49 @@ pub fn handle_workspace_refresh(state: &Arc<Mutex<DaemonState>>) {
     // This helps avoid race conditions when aerospace is still updating
     thread::sleep(Duration::from_millis(10));
 
-    let monitor_mappings = if let Ok(s) = state.lock() {
-        // Get display mappings (cached only - monitor ID mapping doesn't change without disconnect)
-        s.monitor_mapper.get_mappings()
+    let monitor_mapper = if let Ok(s) = state.lock() {
+        Arc::clone(&s.monitor_mapper)
     } else {
         return;
     };
 
+    // Cached until the displays change - monitor ID mapping doesn't change without a reconfiguration
+    let monitor_mappings = monitor_mapper.get_mappings();
+
+    // Sketchybar displays showing workspaces (mirrored displays count once)
+    let display_ids = monitor_mappings.display_ids();
+
     // Check if there's only one monitor (native laptop display)
-    let is_single_monitor = monitor_mappings.len() == 1;
+    let is_single_monitor = display_ids.len() == 1;
 
-    // Show all windows on multiple monitors, one icon per app on single monitor
+    // By default show all windows on multiple monitors, one icon per app on single monitor
     // This queries aerospace fresh each time - no caching of workspace state
-    let infos = aerospace::get_workspace_infos(!is_single_monitor);
+    let infos = events::timed("workspaces", || aerospace::get_workspace_infos(is_single_monitor));
 
     // Get the set of current workspaces
     let current_workspaces: HashSet<String> = infos.keys().cloned().collect();
 
+    let focused = infos.iter().find(|(_, info)| info.is_focused).map(|(id, _)| id.clone());
+    let layout: serde_json::Map<String, Value> = infos
+        .iter()
+        .map(|(id, info)| {
+            let apps: Vec<&str> = info.apps.iter().map(|app| app.name.as_str()).collect();
+            (id.clone(), json!({"monitor": info.monitor_id, "apps": apps, "icons": info.icons}))
+        })
+        .collect();
+    providers::record("workspaces", json!({"focused": focused, "workspaces": layout}));
+
     // Get previous workspaces and update state
     let previous_workspaces = if let Ok(mut s) = state.lock() {
+        if focused.is_some() && focused != s.focused_workspace {
+            // The first refresh only records where focus starts
+            if let Some(previous) = std::mem::replace(&mut s.focused_workspace, focused.clone()) {
+                hooks::run(Hook::WorkspaceChange, vec![
+                    ("FOCUSED_WORKSPACE", focused.clone().unwrap_or_default()),
+                    ("PREV_WORKSPACE", previous),
+                ]);
+            }
+        }
         let prev = s.previous_workspaces.clone();
         s.previous_workspaces = current_workspaces.clone();
         prev
@@ -321,34 +2486,38 @@ pub fn handle_workspace_refresh(state: &Arc<Mutex<DaemonState>>) {
     };
 
     // Find workspaces that need to be cleared (were rendered before but not in current list)
-    let workspaces_to_clear: HashSet<String> = previous_workspaces
+    let workspaces_to_clear: BTreeSet<String> = previous_workspaces
         .difference(&current_workspaces)
         .cloned()
         .collect();
 
-    // Create a batch per display
-    let mut batches: HashMap<u32, SketchybarBatch> = HashMap::new();
+    // Create a batch per display; ordered, like the workspaces below, so the output is the same every time
+    let mut batches: BTreeMap<u32, SketchybarBatch> = BTreeMap::new();
+
+    // A configured selector puts every workspace item on the same display(s)
+    let workspace_display = item_displays().workspaces.map(|spec| monitor_mappings.resolve(&spec));
+    let display_value = |display_id: u32| workspace_display.clone().unwrap_or_else(|| display_id.to_string());
 
     // Clear workspaces that are no longer in aerospace's list
     for ws_id in workspaces_to_clear {
         let item_name = format!("workspace.{}", ws_id);
 
         // Clear on all displays
-        for display_id in monitor_mappings.keys() {
-            let batch = batches.entry(*display_id).or_insert_with(SketchybarBatch::new);
+        for display_id in &display_ids {
+            let batch = batches.entry(*display_id).or_default();
             batch.set(&item_name, &[
                 ("drawing", "off"),
                 ("background.drawing", "off"),
                 ("icon.drawing", "off"),
                 ("icon", ""),
-                ("display", &display_id.to_string()),
+                ("display", &display_value(*display_id)),
             ]);
         }
     }
 
     // Process each workspace from the fresh aerospace data
     // We only use infos.keys() which represents the current live state from aerospace
-    for (ws_id, info) in &infos {
+    for (ws_id, info) in infos.iter().collect::<BTreeMap<_, _>>() {
         let has_apps = !info.apps.is_empty();
         let is_focused = info.is_focused;
         let icons = info.icons.as_str();
@@ -356,92 +2525,139 @@ pub fn handle_workspace_refresh(state: &Arc<Mutex<DaemonState>>) {
 
         let item_name = format!("workspace.{}", ws_id);
 
+        // A workspace holding a single app can show its real icon instead of the glyph
+        let image = if has_apps && app_icons::mode() == AppIconImages::All {
+            let first = &info.apps[0];
+            let single_app = info.apps.iter().all(|app| app.name == first.name);
+            Some(if single_app { image_path(first, state) } else { None })
+        } else {
+            None
+        };
+
         // Use a simple hash-based color assignment for consistent colors
         let bg_color = "0xfff38ba8"; // blue
 
-        // Find the Sketchybar display ID for this workspace's monitor
-        // We need to iterate through monitor_mappings to find the display that maps to this aerospace monitor
-        for (display_id, aerospace_monitor_id) in &monitor_mappings {
-            if *aerospace_monitor_id == workspace_monitor {
-                let batch = batches.entry(*display_id).or_insert_with(SketchybarBatch::new);
-
-                if has_apps && is_focused {
-                    batch.set(&item_name, &[
-                        ("label", &format!("[{}]", ws_id)),
-                        ("label.color", "0xff1d2021"),
-                        ("icon", icons),
-                        ("icon.color", "0xff1d2021"),
-                        ("icon.drawing", "on"),
-                        ("drawing", "on"),
-                        ("background.drawing", "on"),
-                        ("background.color", bg_color),
-                        ("display", &display_id.to_string()),
-                    ]);
-                } else if has_apps {
-                    batch.set(&item_name, &[
-                        ("label", &format!("[{}]", ws_id)),
-                        ("label.color", "0xffffffff"),
-                        ("icon.color", "0xffffffff"),
-                        ("icon", icons),
-                        ("icon.drawing", "on"),
-                        ("drawing", "on"),
-                        ("background.drawing", "off"),
-                        ("display", &display_id.to_string()),
-                    ]);
-                } else if is_focused {
-                    batch.set(&item_name, &[
-                        ("label", &format!("\u{f444} [{}]", ws_id)),
-                        ("label.color", "0xff1d2021"),
-                        ("icon.color", "0xff1d2021"),
-                        ("icon", ""),
-                        ("drawing", "on"),
-                        ("icon.drawing", "off"),
-                        ("background.drawing", "on"),
-                        ("background.color", bg_color),
-                        ("display", &display_id.to_string()),
-                    ]);
-                } else {
-                    // Empty and not focused
-                    if is_single_monitor {
-                        // Hide completely when single monitor
-                        batch.set(&item_name, &[
-                            ("drawing", "off"),
-                            ("background.drawing", "off"),
-                            ("icon.drawing", "off"),
-                            ("display", &display_id.to_string()),
-                        ]);
-                    } else {
-                        // Show when multiple monitors
-                        batch.set(&item_name, &[
-                            ("label", &format!("\u{f444} [{}]", ws_id)),
-                            ("label.color", "0xffffffff"),
-                            ("icon.color", "0xffffffff"),
-                            ("icon", ""),
-                            ("drawing", "on"),
-                            ("icon.drawing", "off"),
-                            ("background.drawing", "off"),
-                            ("display", &display_id.to_string()),
-                        ]);
-                    }
-                }
-                break; // Only update on the correct display
+        // Monitors without a display (e.g. mid-hotplug) fall back to the main display
+        let Some(display_id) = monitor_mappings.display_for(workspace_monitor) else {
+            continue;
+        };
+        if !monitor_mappings.monitors.contains_key(&workspace_monitor) {
+            warn!(
+                "[MONITOR] Workspace {} is on unknown monitor {}; showing it on main display {}",
+                ws_id, workspace_monitor, display_id
+            );
+        }
+
+        let batch = batches.entry(display_id).or_default();
+        let display = display_value(display_id);
+
+        if has_apps && is_focused {
+            batch.set_styled(&item_name, &[
+                ("label", &format!("[{}]", ws_id)),
+                ("label.color", "0xff1d2021"),
+                ("icon", icons),
+                ("icon.font", icon_font()),
+                ("icon.color", "0xff1d2021"),
+                ("icon.drawing", "on"),
+                ("drawing", "on"),
+                ("background.drawing", "on"),
+                ("background.color", bg_color),
+                ("display", &display),
+            ]);
+        } else if has_apps {
+            batch.set_styled(&item_name, &[
+                ("label", &format!("[{}]", ws_id)),
+                ("label.color", "0xffffffff"),
+                ("icon.color", "0xffffffff"),
+                ("icon", icons),
+                ("icon.font", icon_font()),
+                ("icon.drawing", "on"),
+                ("drawing", "on"),
+                ("background.drawing", "off"),
+                ("display", &display),
+            ]);
+        } else if is_focused {
+            batch.set_styled(&item_name, &[
+                ("label", &format!("\u{f444} [{}]", ws_id)),
+                ("label.color", "0xff1d2021"),
+                ("icon.color", "0xff1d2021"),
+                ("icon", ""),
+                ("drawing", "on"),
+                ("icon.drawing", "off"),
+                ("background.drawing", "on"),
+                ("background.color", bg_color),
+                ("display", &display),
+            ]);
+        } else {
+            // Empty and not focused
+            if is_single_monitor {
+                // Hide completely when single monitor
+                batch.set_styled(&item_name, &[
+                    ("drawing", "off"),
+                    ("background.drawing", "off"),
+                    ("icon.drawing", "off"),
+                    ("display", &display),
+                ]);
+            } else {
+                // Show when multiple monitors
+                batch.set_styled(&item_name, &[
+                    ("label", &format!("\u{f444} [{}]", ws_id)),
+                    ("label.color", "0xffffffff"),
+                    ("icon.color", "0xffffffff"),
+                    ("icon", ""),
+                    ("drawing", "on"),
+                    ("icon.drawing", "off"),
+                    ("background.drawing", "off"),
+                    ("display", &display),
+                ]);
             }
         }
-    }
 
-    // Execute all batches
-    for (display_id, batch) in batches {
-        if let Err(e) = batch.execute() {
-            eprintln!("Failed to update workspaces on display {}: {}", display_id, e);
+        if let Some(image) = &image {
+            batch.set(&item_name, &icon_image_props(image.as_deref()));
         }
     }
 
+    // The displays' batches, and any other refresh in the same burst, go to sketchybar together
+    for batch in batches.into_values() {
+        batch.queue();
+    }
+
     // Update borders active color
     std::thread::sleep(std::time::Duration::from_millis(100));
     if let Err(e) = Command::new("/opt/homebrew/bin/borders")
         .arg("active_color=0xfffbf1c7")
         .status()
     {
-        eprintln!("Failed to update borders color: {}", e);
+        error!("Failed to update borders color: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_share() {
+        assert_eq!(disk_share(250_000_000, 500), 0.5);
+        assert_eq!(disk_share(2_000_000_000, 500), 1.0);
+        assert_eq!(disk_share(0, 500), 0.0);
+        // A maximum past u64::MAX bytes a second, which would overflow in integers
+        assert_eq!(disk_share(u64::MAX, u64::MAX), 1e-6);
+        assert_eq!(disk_share(u64::MAX, 1), 1.0);
+    }
+
+    #[test]
+    fn test_reload_keeps_settings_of_broken_file() {
+        let dir = std::env::temp_dir().join(format!("sketchybartender-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("sketchybartender.toml"), "[items.clock]\ninterval = 45\n\n[items.brew\n").unwrap();
+        let running = Config { clock_interval: 5, ..Config::default() };
+        settings::set(running.clone());
+
+        reload_config_in(&dir, &Arc::new(Mutex::new(DaemonState::default())));
+        assert_eq!(*settings::current(), running);
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
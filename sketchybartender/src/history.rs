@@ -0,0 +1,103 @@
+//! Time series behind the graph items
+//!
+//! Each graphed metric keeps its last `retention` samples in a ring buffer.
+//! New samples are pushed to the graph item one at a time, so sketchybar
+//! scrolls the graph instead of being sent the whole series; the buffer is
+//! only sent in full when the item is added, e.g. after a config change.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+/// The last samples of a metric, between 0 and 1, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct Series {
+    samples: VecDeque<f32>,
+    retention: usize,
+}
+
+impl Series {
+    pub fn new(retention: usize) -> Self {
+        Self { samples: VecDeque::with_capacity(retention), retention }
+    }
+
+    /// Add a sample, dropping the oldest once `retention` are kept
+    pub fn push(&mut self, value: f32) {
+        if self.retention == 0 {
+            return;
+        }
+        while self.samples.len() >= self.retention {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value.clamp(0.0, 1.0));
+    }
+
+    /// Keep at most `retention` samples from now on, dropping the oldest ones over it
+    pub fn set_retention(&mut self, retention: usize) {
+        self.retention = retention;
+        while self.samples.len() > retention {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn samples(&self) -> Vec<f32> {
+        self.samples.iter().copied().collect()
+    }
+}
+
+fn series_lock() -> &'static Mutex<BTreeMap<String, Series>> {
+    static SERIES: OnceLock<Mutex<BTreeMap<String, Series>>> = OnceLock::new();
+    SERIES.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Graph item showing a metric
+pub fn graph_item(metric: &str) -> String {
+    format!("{}_graph", metric)
+}
+
+/// Record a sample of a metric, as a fraction of its full scale
+pub fn record(metric: &str, value: f32, retention: usize) {
+    if let Ok(mut series) = series_lock().lock() {
+        series.entry(metric.to_string()).or_insert_with(|| Series::new(retention)).push(value);
+    }
+}
+
+/// Change how many samples every metric keeps
+pub fn set_retention(retention: usize) {
+    if let Ok(mut series) = series_lock().lock() {
+        for series in series.values_mut() {
+            series.set_retention(retention);
+        }
+    }
+}
+
+/// The recorded samples of a metric, oldest first
+pub fn samples(metric: &str) -> Vec<f32> {
+    series_lock()
+        .lock()
+        .ok()
+        .and_then(|series| series.get(metric).map(Series::samples))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_series() {
+        let mut series = Series::new(3);
+        for value in [0.1, 0.2, 0.3, 0.4] {
+            series.push(value);
+        }
+        assert_eq!(series.samples(), vec![0.2, 0.3, 0.4]);
+
+        series.push(1.5);
+        assert_eq!(series.samples(), vec![0.3, 0.4, 1.0]);
+
+        series.set_retention(2);
+        assert_eq!(series.samples(), vec![0.4, 1.0]);
+        series.set_retention(4);
+        series.push(0.5);
+        assert_eq!(series.samples(), vec![0.4, 1.0, 0.5]);
+    }
+}
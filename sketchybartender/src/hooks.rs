@@ -0,0 +1,36 @@
+//! Running the user's `[hooks]` scripts on daemon events
+//!
+//! Hooks run with `sh -c` on their own thread, so a slow script never holds
+//! up the item updates. The event name and its details are passed in
+//! environment variables.
+
+use std::process::Command;
+use std::thread;
+
+use sketchybartender::config::Hook;
+use sketchybartender::{error, info, warn};
+
+use crate::settings;
+
+/// Run the command attached to `hook`, if any, with `env` added to its environment
+pub fn run(hook: Hook, env: Vec<(&'static str, String)>) {
+    let Some(command) = settings::current().hooks.command(hook).map(str::to_string) else {
+        return;
+    };
+
+    info!("[HOOK] {}", hook);
+    thread::spawn(move || {
+        let result = Command::new("sh")
+            .args(["-c", &command])
+            .env("EVENT", hook.to_string())
+            .envs(env)
+            .output();
+        match result {
+            Ok(output) if !output.status.success() => {
+                warn!("[HOOK] {} failed: {}", hook, String::from_utf8_lossy(&output.stderr).trim());
+            }
+            Ok(_) => {}
+            Err(e) => error!("[HOOK] Failed to run {}: {}", hook, e),
+        }
+    });
+}
@@ -0,0 +1,191 @@
+//! JSON over HTTPS with curl, and the cache of what was fetched
+//!
+//! The request goes to curl on stdin rather than as arguments, so an API key
+//! in the URL or a header stays out of the process list. Items that fetch
+//! keep the last reply in the cache directory along with the settings it was
+//! fetched for: a restart within the refresh interval shows it instead of
+//! fetching again, and it's there to fall back on while fetching fails.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use sketchybartender::warn;
+
+use crate::daemon;
+
+/// A value in a curl config file, in quotes
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Run curl with the options of a curl config file, naming `service` in errors
+fn curl(service: &str, config: &str) -> Result<String, String> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", "--max-time", "10", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    // The few lines fit in the pipe, so writing them can't wait on curl
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(config.as_bytes()).map_err(|e| format!("Failed to pass the request to curl: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to reach {}: {}", service, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn json(service: &str, reply: &str) -> Result<Value, String> {
+    serde_json::from_str(reply).map_err(|e| format!("Unexpected reply from {}: {}", service, e))
+}
+
+/// Fetch a URL with curl, naming `service` in errors
+pub fn get_text(service: &str, url: &str) -> Result<String, String> {
+    curl(service, &format!("url = {}\n", quote(url)))
+}
+
+/// Fetch a URL with curl and read the reply as JSON, naming `service` in errors
+pub fn get(service: &str, url: &str) -> Result<Value, String> {
+    json(service, &get_text(service, url)?)
+}
+
+/// Post `body` as JSON with headers like `Authorization: bearer ...`, and read the reply as JSON
+pub fn post(service: &str, url: &str, headers: &[String], body: &Value) -> Result<Value, String> {
+    let mut config = format!("url = {}\nheader = \"Content-Type: application/json\"\n", quote(url));
+    for header in headers {
+        config.push_str(&format!("header = {}\n", quote(header)));
+    }
+    config.push_str(&format!("data = {}\n", quote(&body.to_string())));
+    json(service, &curl(service, &config)?)
+}
+
+/// Post `body` as JSON signed in with basic auth as `user:password`, and read the reply as JSON
+pub fn post_as(service: &str, url: &str, credentials: &str, body: &Value) -> Result<Value, String> {
+    let config = format!(
+        "url = {}\nuser = {}\nheader = \"Content-Type: application/json\"\ndata = {}\n",
+        quote(url),
+        quote(credentials),
+        quote(&body.to_string())
+    );
+    json(service, &curl(service, &config)?)
+}
+
+/// Percent-encode a URL path segment or query value
+pub fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b',' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The current Unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn cache_path(name: &str) -> PathBuf {
+    daemon::get_cache_dir().join(format!("{}.json", name))
+}
+
+/// What item `name` cached for the settings `key`, and the Unix time it was fetched at
+fn read_cache(name: &str, key: &str) -> Option<(u64, Value)> {
+    let mut cache: Value = serde_json::from_str(&fs::read_to_string(cache_path(name)).ok()?).ok()?;
+    if cache["key"].as_str()? != key {
+        return None;
+    }
+    Some((cache["at"].as_u64()?, cache["value"].take()))
+}
+
+/// Cache what item `name` fetched at Unix time `at` for the settings `key`, replacing what it cached before
+fn write_cache(name: &str, key: &str, at: u64, value: Value) -> Result<(), String> {
+    let cache = json!({"key": key, "at": at, "value": value});
+    let path = cache_path(name);
+    fs::write(&path, cache.to_string()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Whether a reply `age` seconds old saves a fetch; a little short of the interval, so the
+/// timer's next refresh doesn't find the last fetch a moment too recent
+fn fresh(age: u64, interval: u64) -> bool {
+    age < interval - interval / 10
+}
+
+/// A value an item fetches, and how it's kept in the cache
+pub trait Cached: Sized {
+    fn from_cache(cache: &Value) -> Option<Self>;
+    fn to_cache(&self) -> Value;
+}
+
+/// What [`cached`] came up with
+#[derive(Debug, PartialEq)]
+pub enum Fetched<T> {
+    /// Just fetched, or cached recently enough to save fetching
+    Fresh(T),
+    /// The cached value, since fetching failed with the error
+    Stale(T, String),
+}
+
+/// What item `name` fetched for the settings `key`: the cached value while it's fresh for the refresh
+/// `interval`, otherwise the one `fetch` gets, which is cached in its place
+///
+/// While fetching fails, the cached value is returned as stale if `usable` accepts its age in seconds.
+pub fn cached<T: Cached>(
+    name: &str,
+    key: &str,
+    interval: u64,
+    usable: impl Fn(u64) -> bool,
+    fetch: impl FnOnce() -> Result<T, String>,
+) -> Result<Fetched<T>, String> {
+    let now = now();
+    let cached = read_cache(name, key).and_then(|(at, cache)| Some((at, T::from_cache(&cache)?)));
+    let cached = match cached {
+        Some((at, value)) if fresh(now.saturating_sub(at), interval) => return Ok(Fetched::Fresh(value)),
+        cached => cached,
+    };
+
+    match fetch() {
+        Ok(value) => {
+            if let Err(e) = write_cache(name, key, now, value.to_cache()) {
+                warn!("[{}] {}", name.to_uppercase(), e);
+            }
+            Ok(Fetched::Fresh(value))
+        }
+        Err(e) => match cached.filter(|(at, _)| usable(now.saturating_sub(*at))) {
+            Some((_, value)) => Ok(Fetched::Stale(value, e)),
+            None => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("https://example.com/?q=a b"), "\"https://example.com/?q=a b\"");
+        assert_eq!(quote(r#"{"query":"\\d"}"#), r#""{\"query\":\"\\\\d\"}""#);
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("São Paulo"), "S%C3%A3o%20Paulo");
+        assert_eq!(encode("London,GB"), "London,GB");
+    }
+
+    #[test]
+    fn test_fresh() {
+        assert!(fresh(0, 900));
+        assert!(fresh(809, 900));
+        assert!(!fresh(810, 900));
+        assert!(!fresh(1, 1));
+    }
+}
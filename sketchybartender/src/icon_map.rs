@@ -1,22 +1,376 @@
+//! App name → icon resolution
+//!
+//! Names are matched against user overrides, then the map compiled from
+//! icon_map.json (bundle IDs, exact names, prefixes, globs), and finally a
+//! generated fallback. Other tools can use this module through the library:
+//!
+//! ```
+//! use sketchybartender::config::IconTheme;
+//! use sketchybartender::icon_map;
+//!
+//! assert_eq!(icon_map::get_icon("Safari", Some("com.apple.Safari")), ":safari:");
+//! assert_eq!(icon_map::get_themed_icon("Safari", None, IconTheme::Emoji), "🧭");
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use crate::config::IconTheme;
+use crate::glob::{self, Token};
+use crate::normalize::normalize_name;
+
+#[cfg(feature = "daemon")]
+mod user_maps;
+
+#[cfg(feature = "daemon")]
+pub use user_maps::{overrides_path, reload_overrides, user_icon_map_path};
+
 // Generated at compile time from icon_map.json
 include!(concat!(env!("OUT_DIR"), "/icon_map.rs"));
 
-/// Get the icon for an app name
-pub fn get_icon(app_name: &str) -> &'static str {
+/// User icon mappings from icon_overrides.json and a user icon_map.json, consulted before the compiled map
+#[derive(Debug, Default)]
+struct Overrides {
+    /// Normalized app names and bundle identifiers
+    exact: HashMap<String, String>,
+    /// Prefix patterns (keys ending in '*'), longest prefix first
+    prefixes: Vec<(String, String)>,
+    /// Other glob patterns such as "*Helper*", in file order
+    globs: Vec<(Vec<Token>, String)>,
+}
+
+fn overrides() -> &'static RwLock<Overrides> {
+    static OVERRIDES: OnceLock<RwLock<Overrides>> = OnceLock::new();
+    OVERRIDES.get_or_init(|| RwLock::new(Overrides::default()))
+}
+
+fn theme_lock() -> &'static RwLock<IconTheme> {
+    static THEME: OnceLock<RwLock<IconTheme>> = OnceLock::new();
+    THEME.get_or_init(|| RwLock::new(IconTheme::default()))
+}
+
+fn unknown_lock() -> &'static Mutex<UnknownApps> {
+    static UNKNOWN: OnceLock<Mutex<UnknownApps>> = OnceLock::new();
+    UNKNOWN.get_or_init(|| Mutex::new(UnknownApps::default()))
+}
+
+/// Apps that fell back to a generated icon so far, reported by `sketchycli icons unknown`
+pub fn unknown_apps() -> UnknownApps {
+    unknown_lock().lock().map(|unknown| unknown.clone()).unwrap_or_default()
+}
+
+/// Select the icon theme used by `get_icon`
+pub fn set_theme(theme: IconTheme) {
+    if let Ok(mut t) = theme_lock().write() {
+        *t = theme;
+    }
+}
+
+/// The active icon theme
+pub fn theme() -> IconTheme {
+    theme_lock().read().map(|t| *t).unwrap_or_default()
+}
+
+/// Look up a normalized app name in a set of overrides
+fn lookup_override(overrides: &Overrides, app_name: &str) -> Option<String> {
+    if let Some(icon) = overrides.exact.get(app_name) {
+        return Some(icon.clone());
+    }
+
+    overrides
+        .prefixes
+        .iter()
+        .find(|(prefix, _)| app_name.starts_with(prefix.as_str()))
+        .map(|(_, icon)| icon)
+        .or_else(|| {
+            overrides
+                .globs
+                .iter()
+                .find(|(tokens, _)| glob::matches(tokens, app_name))
+                .map(|(_, icon)| icon)
+        })
+        .cloned()
+}
+
+/// Get the icon for an app in the active theme, preferring its bundle identifier when known
+pub fn get_icon(app_name: &str, bundle_id: Option<&str>) -> String {
+    get_themed_icon(app_name, bundle_id, theme())
+}
+
+/// Get the icon for an app in a given theme, regardless of the active one
+pub fn get_themed_icon(app_name: &str, bundle_id: Option<&str>, theme: IconTheme) -> String {
+    let glyphs = match theme {
+        IconTheme::AppFont => return resolve_icon(app_name, bundle_id),
+        IconTheme::NerdFont => &NERDFONT_ICONS,
+        IconTheme::Emoji => &EMOJI_ICONS,
+    };
+
+    // The app-font icon has no glyph in most themes, so those apps get the same
+    // category or letter fallback as unmapped ones rather than all sharing ":default:"
+    themed(&resolve_icon(app_name, bundle_id), glyphs)
+        .or_else(|| themed(&fallback_icon(&normalize_name(app_name), |icon| glyphs.contains_key(icon)), glyphs))
+        .or_else(|| glyphs.get(":default:").map(|glyph| glyph.to_string()))
+        .unwrap_or_else(|| ":default:".to_string())
+}
+
+/// Translate an app-font icon name (":safari:") into a theme's glyph
+///
+/// Raw glyphs (from overrides or the letter fallback) are passed through, and
+/// names without a glyph in the theme give `None`.
+fn themed(icon: &str, glyphs: &phf::Map<&'static str, &'static str>) -> Option<String> {
+    if !(icon.len() > 2 && icon.starts_with(':') && icon.ends_with(':')) {
+        return Some(icon.to_string());
+    }
+
+    glyphs.get(icon).map(|glyph| glyph.to_string())
+}
+
+/// Resolve the app-font icon name for an app
+///
+/// Display names are localized and can change between versions, so a bundle ID
+/// match (e.g. `com.microsoft.VSCode`) wins over any name-based match.
+fn resolve_icon(app_name: &str, bundle_id: Option<&str>) -> String {
+    let normalized = normalize_name(app_name);
+    lookup_mapped(&normalized, bundle_id.map(normalize_name).as_deref()).unwrap_or_else(|| {
+        if let Ok(mut unknown) = unknown_lock().lock() {
+            unknown.record(app_name, bundle_id);
+        }
+        fallback_icon(&normalized, |_| true)
+    })
+}
+
+/// Whether an app has an explicit mapping (as opposed to a generated fallback)
+pub fn is_mapped(app_name: &str, bundle_id: Option<&str>) -> bool {
+    lookup_mapped(&normalize_name(app_name), bundle_id.map(normalize_name).as_deref()).is_some()
+}
+
+/// Look up a normalized app name and bundle ID in the overrides and compiled map
+fn lookup_mapped(app_name: &str, bundle_id: Option<&str>) -> Option<String> {
+    // User overrides take precedence over the compiled map
+    if let Ok(o) = overrides().read() {
+        if let Some(icon) = bundle_id.and_then(|id| o.exact.get(id)) {
+            return Some(icon.clone());
+        }
+        if let Some(icon) = lookup_override(&o, app_name) {
+            return Some(icon);
+        }
+    }
+
+    if let Some(icon) = bundle_id.and_then(|id| BUNDLE_ID_MAP.get(id)) {
+        return Some(icon.to_string());
+    }
+
     // First try exact match
     if let Some(icon) = ICON_MAP.get(app_name) {
-        return icon;
+        return Some(icon.to_string());
     }
 
     // Try prefix patterns
     for (prefix, icon) in PREFIX_PATTERNS {
         if app_name.starts_with(prefix) {
-            return icon;
+            return Some(icon.to_string());
+        }
+    }
+
+    // Try general glob patterns
+    for (tokens, icon) in GLOB_PATTERNS {
+        if glob::matches(tokens, app_name) {
+            return Some(icon.to_string());
+        }
+    }
+
+    // Renamed variants (e.g. "Firefox Beta") use the base app's icon
+    variant_base(app_name).and_then(|base| lookup_mapped(base, None))
+}
+
+/// Patterns for release channels and other app variants, matched against normalized names
+///
+/// `*` before the marker is the base app name. A trailing `*` lets the marker
+/// appear anywhere after the base name rather than only at the end.
+const VARIANT_PATTERNS: &[&str] = &[
+    "* beta*",
+    "*-beta",
+    "* alpha",
+    "* nightly*",
+    "* canary",
+    "* ptb",
+    "* insiders",
+    "* preview",
+    "* dev",
+    "* developer edition",
+    "* (beta)",
+];
+
+/// The base app name of a variant such as "discord ptb" or "firefox nightly 121"
+fn variant_base(app_name: &str) -> Option<&str> {
+    VARIANT_PATTERNS.iter().find_map(|pattern| {
+        let marker = pattern.strip_prefix('*')?;
+        let base = match marker.strip_suffix('*') {
+            Some(marker) => app_name.find(marker).map(|i| &app_name[..i]),
+            None => app_name.strip_suffix(marker),
+        }?;
+
+        // "Code - Insiders" has a separator before the marker
+        let base = base.trim_end_matches([' ', '-', '\u{2013}', '\u{2014}']);
+        (!base.is_empty()).then_some(base)
+    })
+}
+
+/// An app seen without an icon mapping
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnknownApp {
+    pub bundle_id: Option<String>,
+    /// How many times its icon was looked up (focus changes and workspace refreshes)
+    pub count: u64,
+}
+
+/// Unmatched app names collected while the daemon runs, to help contribute new mappings
+#[derive(Debug, Clone, Default)]
+pub struct UnknownApps {
+    apps: BTreeMap<String, UnknownApp>,
+}
+
+impl UnknownApps {
+    /// Record a lookup of an app that has no icon mapping
+    fn record(&mut self, app_name: &str, bundle_id: Option<&str>) {
+        if app_name.is_empty() {
+            return;
         }
+
+        let entry = self.apps.entry(app_name.to_string()).or_default();
+        entry.count += 1;
+        if entry.bundle_id.is_none() {
+            entry.bundle_id = bundle_id.map(|id| id.to_string());
+        }
+    }
+
+    /// Entries sorted by how often they were seen, most frequent first
+    fn by_count(&self) -> Vec<(&String, &UnknownApp)> {
+        let mut apps: Vec<_> = self.apps.iter().collect();
+        apps.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        apps
     }
 
-    // Default icon
-    ":default:"
+    /// Human-readable table of unknown apps
+    pub fn report(&self) -> String {
+        if self.apps.is_empty() {
+            return "No unknown apps seen".to_string();
+        }
+
+        self.by_count()
+            .iter()
+            .map(|(name, app)| match &app.bundle_id {
+                Some(id) => format!("{:>6}  {} ({})", app.count, name, id),
+                None => format!("{:>6}  {}", app.count, name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Entries in icon_map.json format, ready to fill in and contribute upstream
+    #[cfg(feature = "daemon")]
+    pub fn export_json(&self) -> String {
+        let entries: Vec<serde_json::Value> = self
+            .by_count()
+            .iter()
+            .map(|(name, app)| {
+                let mut entry = serde_json::json!({
+                    "iconName": format!(":{}:", icon_name_for(name)),
+                    "appNames": [name],
+                });
+                if let Some(id) = &app.bundle_id {
+                    entry["bundleIds"] = serde_json::json!([id]);
+                }
+                entry
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&entries)
+            .unwrap_or_else(|e| format!("Failed to export unknown apps: {}", e))
+    }
+}
+
+/// Suggest an icon name (snake_case) for an app name
+#[cfg(feature = "daemon")]
+fn icon_name_for(app_name: &str) -> String {
+    normalize_name(app_name)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Generic glyphs for unknown apps, keyed by whole words that commonly appear in their names
+const CATEGORY_KEYWORDS: &[(&str, &str)] = &[
+    ("terminal", ":terminal:"),
+    ("shell", ":terminal:"),
+    ("console", ":terminal:"),
+    ("player", ":play:"),
+    ("music", ":music:"),
+    ("audio", ":music:"),
+    ("video", ":tv:"),
+    ("movie", ":tv:"),
+    ("mail", ":mail:"),
+    ("chat", ":messages:"),
+    ("messenger", ":messages:"),
+    ("calendar", ":calendar:"),
+    ("note", ":notes:"),
+    ("notes", ":notes:"),
+    ("editor", ":text:"),
+    ("settings", ":gear:"),
+    ("preferences", ":gear:"),
+    ("helper", ":gear:"),
+    ("agent", ":gear:"),
+    ("game", ":games:"),
+    ("games", ":games:"),
+    ("calculator", ":calculator:"),
+    ("clock", ":clock:"),
+    ("timer", ":clock:"),
+    ("photo", ":photos:"),
+    ("photos", ":photos:"),
+    ("camera", ":photos:"),
+    ("pdf", ":preview:"),
+    ("reader", ":book:"),
+    ("book", ":book:"),
+    ("books", ":book:"),
+    ("weather", ":weather:"),
+    ("bank", ":bank:"),
+    ("finance", ":dollar:"),
+    ("password", ":passwords:"),
+    ("passwords", ":passwords:"),
+];
+
+/// Pick an icon for an app with no mapping so unknown apps don't all look identical
+///
+/// Tries a category glyph based on words in the (normalized) name, then the
+/// app's first letter as a circled glyph, and only then ":default:". Categories
+/// are limited to the icons `has_glyph` accepts, for themes without all of them.
+fn fallback_icon(app_name: &str, has_glyph: impl Fn(&str) -> bool) -> String {
+    for word in app_name.split(|c: char| !c.is_alphanumeric()) {
+        if let Some((_, icon)) = CATEGORY_KEYWORDS
+            .iter()
+            .find(|(keyword, icon)| word == *keyword && has_glyph(icon))
+        {
+            return icon.to_string();
+        }
+    }
+
+    match app_name.chars().find(|c| c.is_ascii_alphanumeric()) {
+        Some(c) => circled(c).to_string(),
+        None => ":default:".to_string(),
+    }
+}
+
+/// Circled form of an ASCII letter or digit (Ⓐ-Ⓩ, ⓪-⑨)
+fn circled(c: char) -> char {
+    let code = match c {
+        'a'..='z' => 0x24B6 + (c as u32 - 'a' as u32),
+        'A'..='Z' => 0x24B6 + (c as u32 - 'A' as u32),
+        '0' => 0x24EA,
+        '1'..='9' => 0x2460 + (c as u32 - '1' as u32),
+        _ => return c,
+    };
+    char::from_u32(code).unwrap_or(c)
 }
 
 #[cfg(test)]
@@ -25,19 +379,112 @@ mod tests {
 
     #[test]
     fn test_exact_match() {
-        assert_eq!(get_icon("Cursor"), ":cursor:");
-        assert_eq!(get_icon("Safari"), ":safari:");
-        assert_eq!(get_icon("Discord"), ":discord:");
+        assert_eq!(get_icon("Cursor", None), ":cursor:");
+        assert_eq!(get_icon("Safari", None), ":safari:");
+        assert_eq!(get_icon("Discord", None), ":discord:");
     }
 
     #[test]
     fn test_prefix_match() {
-        assert_eq!(get_icon("Adobe Photoshop 2024"), ":photoshop:");
-        assert_eq!(get_icon("MongoDB Compass Community"), ":mongodb:");
+        assert_eq!(get_icon("Adobe Photoshop 2024", None), ":photoshop:");
+        assert_eq!(get_icon("MongoDB Compass Community", None), ":mongodb:");
+    }
+
+    #[test]
+    fn test_fallback() {
+        // No category keyword: circled first letter
+        assert_eq!(get_icon("Unknown App", None), "Ⓤ");
+        assert_eq!(get_icon("7zip Unknown", None), "⑦");
+        // Category keyword as a word of the name, not part of one
+        assert_eq!(get_icon("Hyper Terminal Pro", None), ":terminal:");
+        assert_eq!(get_icon("Super Music Player", None), ":music:");
+        assert_eq!(get_icon("Facebook", None), "Ⓕ");
+        // Nothing usable
+        assert_eq!(get_icon("???", None), ":default:");
+    }
+
+    #[test]
+    fn test_normalized_match() {
+        assert_eq!(get_icon("visual studio code", None), get_icon("Visual Studio Code", None));
+        assert_eq!(get_icon("  SAFARI ", None), ":safari:");
+        assert_eq!(get_icon("Re\u{301}glages Syste\u{300}me", None), ":gear:");
+        assert_eq!(get_icon("adobe photoshop 2024", None), ":photoshop:");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert_eq!(get_icon("iZotope RX 13", None), ":izotope:");
+        assert_eq!(get_icon("MATLAB_R2031a", None), ":matlab:");
+    }
+
+    #[test]
+    fn test_variant_match() {
+        assert_eq!(get_icon("Xcode-beta", None), ":xcode:");
+        assert_eq!(get_icon("Google Chrome Beta", None), ":google_chrome:");
+        assert_eq!(get_icon("Firefox Beta 2", None), ":firefox:");
+        assert_eq!(get_icon("Discord Nightly Build", None), ":discord:");
+        // Explicit entries win over the base app
+        assert_eq!(get_icon("Firefox Nightly", None), ":firefox_developer_edition:");
+        assert_eq!(get_icon("Canary Mail", None), ":mail:");
+        // Only a marker, no base app
+        assert_eq!(variant_base("beta"), None);
+        assert_eq!(variant_base("betamax"), None);
     }
 
     #[test]
-    fn test_default() {
-        assert_eq!(get_icon("Unknown App"), ":default:");
+    fn test_bundle_id_match() {
+        // Localized display name, stable bundle ID
+        assert_eq!(get_icon("Visual Studio Code", Some("com.microsoft.VSCode")), ":code:");
+        assert_eq!(get_icon("Unknown App", Some("com.unknown.app")), "Ⓤ");
+        assert_eq!(get_icon("Safari", Some("com.unknown.app")), ":safari:");
+        // Calibre's own icon by bundle ID, the generic book by name
+        assert_eq!(get_icon("calibre", Some("net.kovidgoyal.calibre")), ":calibre:");
+        assert_eq!(get_icon("Calibre", None), ":book:");
+    }
+
+    #[test]
+    fn test_themed_glyphs() {
+        assert_eq!(get_themed_icon("Safari", None, IconTheme::AppFont), ":safari:");
+        assert_eq!(get_themed_icon("Safari", None, IconTheme::Emoji), "🧭");
+        assert_eq!(get_themed_icon("Safari", None, IconTheme::NerdFont), "\u{F0039}");
+
+        // No glyph in the theme: category, then letter, then the theme default
+        assert_eq!(get_themed_icon("Ableton Live", None, IconTheme::Emoji), "Ⓐ");
+        assert_eq!(get_themed_icon("Unknown App", None, IconTheme::Emoji), "Ⓤ");
+        assert_eq!(get_themed_icon("Hyper Terminal Pro", None, IconTheme::Emoji), "💻");
+        assert_eq!(get_themed_icon("???", None, IconTheme::Emoji), "📦");
+    }
+
+    #[test]
+    fn test_unknown_apps() {
+        let mut unknown = UnknownApps::default();
+        unknown.record("My Tool", Some("com.example.mytool"));
+        unknown.record("My Tool", Some("com.example.mytool"));
+        unknown.record("Other-App", None);
+
+        assert_eq!(unknown.apps.len(), 2);
+        assert_eq!(unknown.apps["My Tool"].count, 2);
+        assert!(unknown.report().starts_with("     2  My Tool (com.example.mytool)"));
+
+        // Lookups that miss the map are recorded, mapped ones aren't
+        get_icon("Safari", None);
+        get_icon("Unmapped Test Tool", Some("com.example.unmapped"));
+        let seen = unknown_apps();
+        assert_eq!(seen.apps["Unmapped Test Tool"].bundle_id.as_deref(), Some("com.example.unmapped"));
+        assert!(!seen.apps.contains_key("Safari"));
+    }
+
+    #[cfg(feature = "daemon")]
+    #[test]
+    fn test_unknown_apps_export() {
+        let mut unknown = UnknownApps::default();
+        unknown.record("My Tool", Some("com.example.mytool"));
+        unknown.record("My Tool", Some("com.example.mytool"));
+        unknown.record("Other-App", None);
+
+        let exported: serde_json::Value = serde_json::from_str(&unknown.export_json()).unwrap();
+        assert_eq!(exported[0]["iconName"], ":my_tool:");
+        assert_eq!(exported[0]["bundleIds"][0], "com.example.mytool");
+        assert_eq!(exported[1]["iconName"], ":other_app:");
     }
 }
@@ -0,0 +1,221 @@
+//! User icon mappings loaded from the sketchybar config directory
+//!
+//! `icon_overrides.json` maps names or patterns to icons; an optional
+//! `icon_map.json` uses the built-in map's format. Both are layered over the
+//! compiled map, with the override file taking precedence.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::glob;
+use crate::normalize::{normalize_name, normalize_prefix};
+use crate::{info, warn};
+
+use super::{lookup_override, overrides, Overrides};
+
+/// Path of the user icon override file
+pub fn overrides_path() -> PathBuf {
+    Config::get_config_dir().join("icon_overrides.json")
+}
+
+/// Path of the optional user icon map, in the same format as the built-in icon_map.json
+pub fn user_icon_map_path() -> PathBuf {
+    Config::get_config_dir().join("icon_map.json")
+}
+
+impl Overrides {
+    /// Add a mapping for an app name, bundle identifier or pattern
+    fn insert(&mut self, name: &str, icon: String) -> Result<(), String> {
+        match name.strip_suffix('*') {
+            Some(prefix) if !glob::is_glob(prefix) => {
+                self.prefixes.push((normalize_prefix(prefix), icon))
+            }
+            _ if glob::is_glob(name) => self.globs.push((glob::parse(name)?, icon)),
+            _ => {
+                self.exact.insert(normalize_name(name), icon);
+            }
+        }
+        Ok(())
+    }
+
+    /// Layer these mappings over `base`, keeping ours where both match
+    fn layered_over(mut self, base: Overrides) -> Overrides {
+        for (name, icon) in base.exact {
+            self.exact.entry(name).or_insert(icon);
+        }
+        self.prefixes.extend(base.prefixes);
+        self.globs.extend(base.globs);
+
+        // Most specific prefix wins; the stable sort keeps ours first on ties
+        self.prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    fn len(&self) -> usize {
+        self.exact.len() + self.prefixes.len() + self.globs.len()
+    }
+}
+
+/// Parse an override file of the form `{ "App Name": ":icon:", "Prefix*": ":icon:" }`
+///
+/// Keys may also be bundle identifiers such as `"com.microsoft.VSCode"` or
+/// glob patterns such as `"*Helper*"`.
+fn parse_overrides(contents: &str) -> Result<Overrides, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(contents).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let object = value
+        .as_object()
+        .ok_or("expected an object mapping app names to icons")?;
+
+    let mut overrides = Overrides::default();
+    for (name, icon) in object {
+        let icon = icon
+            .as_str()
+            .ok_or_else(|| format!("icon for \"{}\" must be a string", name))?
+            .to_string();
+        overrides.insert(name, icon)?;
+    }
+
+    // Most specific prefix wins
+    overrides.prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+    Ok(overrides)
+}
+
+/// Parse a user icon map: `[{ "iconName": ":icon:", "appNames": [...], "bundleIds": [...] }]`
+fn parse_user_icon_map(contents: &str) -> Result<Overrides, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(contents).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let entries = value
+        .as_array()
+        .ok_or("expected an array of icon entries")?;
+
+    let mut overrides = Overrides::default();
+    for entry in entries {
+        let icon = entry["iconName"]
+            .as_str()
+            .ok_or("every entry needs an \"iconName\" string")?;
+
+        let names = ["bundleIds", "appNames"]
+            .iter()
+            .filter_map(|key| entry[*key].as_array())
+            .flatten();
+        for name in names {
+            let name = name
+                .as_str()
+                .ok_or_else(|| format!("names for {} must be strings", icon))?;
+
+            // Pipe-separated names as in the built-in map (e.g. "MATLAB |MATLABWindow")
+            for name in name.split('|').filter(|n| !n.trim().is_empty()) {
+                // First occurrence wins, as in the built-in map
+                if lookup_override(&overrides, &normalize_name(name)).is_none() {
+                    overrides.insert(name, icon.to_string())?;
+                }
+            }
+        }
+    }
+
+    overrides.prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+    Ok(overrides)
+}
+
+/// Read a mapping file if it exists, returning an empty set when it doesn't
+fn load_mappings(
+    path: &Path,
+    parse: fn(&str) -> Result<Overrides, String>,
+) -> Result<Overrides, String> {
+    if !path.exists() {
+        return Ok(Overrides::default());
+    }
+
+    fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read file: {}", e))
+        .and_then(|contents| parse(&contents))
+}
+
+/// Load (or reload) the user icon map and overrides, keeping the previous set if a file is invalid
+pub fn reload_overrides() {
+    let mut layers = Vec::new();
+    for (path, parse) in [
+        (overrides_path(), parse_overrides as fn(&str) -> Result<Overrides, String>),
+        (user_icon_map_path(), parse_user_icon_map),
+    ] {
+        match load_mappings(&path, parse) {
+            Ok(o) => {
+                if path.exists() {
+                    info!("Loaded {} icon mappings from {:?}", o.len(), path);
+                }
+                layers.push(o);
+            }
+            Err(e) => {
+                warn!("Failed to load icon mappings from {:?}: {}", path, e);
+                return;
+            }
+        }
+    }
+
+    // Overrides take precedence over the user icon map
+    let loaded = layers
+        .into_iter()
+        .reduce(Overrides::layered_over)
+        .unwrap_or_default();
+
+    if let Ok(mut o) = overrides().write() {
+        *o = loaded;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_parsing() {
+        let overrides = parse_overrides(
+            r#"{ "Safari": ":firefox:", "Adobe*": ":acrobat:", "Adobe Photoshop*": ":photoshop:" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(lookup_override(&overrides, "safari"), Some(":firefox:".to_string()));
+        assert_eq!(lookup_override(&overrides, "adobe photoshop 2024"), Some(":photoshop:".to_string()));
+        assert_eq!(lookup_override(&overrides, "adobe bridge"), Some(":acrobat:".to_string()));
+        assert_eq!(lookup_override(&overrides, "cursor"), None);
+    }
+
+    #[test]
+    fn test_override_globs() {
+        let overrides = parse_overrides(r#"{ "*Helper*": ":gear:", "Office 20[0-9][0-9]": ":word:" }"#).unwrap();
+
+        assert_eq!(lookup_override(&overrides, "code helper (renderer)"), Some(":gear:".to_string()));
+        assert_eq!(lookup_override(&overrides, "office 2024"), Some(":word:".to_string()));
+        assert_eq!(lookup_override(&overrides, "office"), None);
+        assert!(parse_overrides(r#"{ "App [z-a]": ":gear:" }"#).is_err());
+    }
+
+    #[test]
+    fn test_user_icon_map_layering() {
+        let user_map = parse_user_icon_map(
+            r#"[
+                { "iconName": ":code:", "appNames": ["Code", "Cursor"], "bundleIds": ["com.example.code"] },
+                { "iconName": ":idea:", "appNames": ["JetBrains*", "Code"] }
+            ]"#,
+        )
+        .unwrap();
+        let overrides = parse_overrides(r#"{ "Cursor": ":cursor:" }"#).unwrap();
+        let layered = overrides.layered_over(user_map);
+
+        assert_eq!(lookup_override(&layered, "cursor"), Some(":cursor:".to_string()));
+        assert_eq!(lookup_override(&layered, "code"), Some(":code:".to_string()));
+        assert_eq!(lookup_override(&layered, "com.example.code"), Some(":code:".to_string()));
+        assert_eq!(lookup_override(&layered, "jetbrains rider"), Some(":idea:".to_string()));
+        assert!(parse_user_icon_map(r#"[{ "appNames": ["Code"] }]"#).is_err());
+    }
+
+    #[test]
+    fn test_override_rejects_non_string_icon() {
+        assert!(parse_overrides(r#"{ "Safari": 3 }"#).is_err());
+        assert!(parse_overrides(r#"[]"#).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Issues assigned to the user, from a Jira site's REST API
+//!
+//! Jira Cloud signs in with an account email and an API token and searches
+//! through `/rest/api/3/search/jql`, which pages with a token instead of
+//! giving a total. Jira Server and Data Center take a personal access token
+//! as a bearer token and still answer `/rest/api/2/search` with the total.
+
+use serde_json::{json, Value};
+use sketchybartender::config::JiraConfig;
+
+use crate::http;
+
+/// Issues fetched for the popup, most urgent first
+const MAX_RESULTS: usize = 20;
+
+/// How urgent an issue is, from its priority's name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Low, Lowest, Minor, Trivial or a priority of the site's own
+    Low,
+    /// Medium or Major
+    Medium,
+    /// High, Highest, Critical or Blocker
+    High,
+}
+
+impl Priority {
+    fn parse(name: &str) -> Self {
+        match name {
+            "Highest" | "High" | "Critical" | "Blocker" => Self::High,
+            "Medium" | "Major" => Self::Medium,
+            _ => Self::Low,
+        }
+    }
+}
+
+/// An issue assigned to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// Like `API-123`
+    pub key: String,
+    pub summary: String,
+    pub priority: Priority,
+}
+
+/// The open issues assigned to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assigned {
+    /// Number of issues, or the number fetched when Jira Cloud has more pages of them
+    pub count: u64,
+    /// Whether there are more issues than `count`
+    pub more: bool,
+    pub issues: Vec<Issue>,
+}
+
+impl Assigned {
+    /// The priority of the most urgent issue, if there are any
+    pub fn priority(&self) -> Option<Priority> {
+        self.issues.iter().map(|issue| issue.priority).max()
+    }
+}
+
+/// The JQL search for the open issues assigned to the user, in `projects` if there are any
+pub fn jql(projects: &[String]) -> String {
+    let mut jql = "assignee = currentUser() AND statusCategory != Done".to_string();
+    if !projects.is_empty() {
+        jql.push_str(&format!(" AND project in ({})", projects.join(", ")));
+    }
+    jql.push_str(" ORDER BY priority DESC, updated DESC");
+    jql
+}
+
+/// Jira's reply to the search
+fn parse(reply: &Value) -> Result<Assigned, String> {
+    let unexpected = || "Unexpected search results from Jira".to_string();
+    let issues = reply["issues"]
+        .as_array()
+        .ok_or_else(unexpected)?
+        .iter()
+        .map(|issue| {
+            Some(Issue {
+                key: issue["key"].as_str()?.to_string(),
+                summary: issue["fields"]["summary"].as_str()?.to_string(),
+                // Sites can turn priorities off, leaving the field null
+                priority: Priority::parse(issue["fields"]["priority"]["name"].as_str().unwrap_or_default()),
+            })
+        })
+        .collect::<Option<Vec<Issue>>>()
+        .ok_or_else(unexpected)?;
+    let (count, more) = match reply["total"].as_u64() {
+        Some(total) => (total, false),
+        None => (issues.len() as u64, reply["isLast"].as_bool() == Some(false) || reply["nextPageToken"].is_string()),
+    };
+    Ok(Assigned { count, more, issues })
+}
+
+/// The open issues assigned to the user, or an error without a site or token to ask
+pub fn assigned(config: &JiraConfig) -> Result<Assigned, String> {
+    let (Some(url), Some(token)) = (&config.url, &config.token) else {
+        return Err("Jira needs a url and a token".to_string());
+    };
+    let body = json!({"jql": jql(&config.projects), "fields": ["summary", "priority"], "maxResults": MAX_RESULTS});
+    let reply = match &config.email {
+        Some(email) => {
+            let endpoint = format!("{}/rest/api/3/search/jql", url);
+            http::post_as("Jira", &endpoint, &format!("{}:{}", email, token), &body)?
+        }
+        None => {
+            let endpoint = format!("{}/rest/api/2/search", url);
+            http::post("Jira", &endpoint, &[format!("Authorization: Bearer {}", token)], &body)?
+        }
+    };
+    parse(&reply)
+}
+
+/// The site's page of an issue
+pub fn issue_url(url: &str, key: &str) -> String {
+    format!("{}/browse/{}", url, key)
+}
+
+/// The site's page of a JQL search's results
+pub fn search_url(url: &str, jql: &str) -> String {
+    format!("{}/issues/?jql={}", url, http::encode(jql))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jql() {
+        assert_eq!(
+            jql(&[]),
+            "assignee = currentUser() AND statusCategory != Done ORDER BY priority DESC, updated DESC"
+        );
+        assert_eq!(
+            jql(&["API".to_string(), "WEB".to_string()]),
+            "assignee = currentUser() AND statusCategory != Done AND project in (API, WEB) \
+             ORDER BY priority DESC, updated DESC"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let server = json!({"total": 31, "issues": [
+            {"key": "API-7", "fields": {"summary": "Fix the login", "priority": {"name": "Medium"}}},
+            {"key": "WEB-12", "fields": {"summary": "New header", "priority": null}},
+        ]});
+        let assigned = parse(&server).unwrap();
+        assert_eq!((assigned.count, assigned.more), (31, false));
+        assert_eq!(assigned.issues[0], Issue {
+            key: "API-7".to_string(),
+            summary: "Fix the login".to_string(),
+            priority: Priority::Medium,
+        });
+        assert_eq!(assigned.issues[1].priority, Priority::Low);
+        assert_eq!(assigned.priority(), Some(Priority::Medium));
+
+        let cloud = json!({"nextPageToken": "abc", "isLast": false, "issues": [
+            {"key": "API-9", "fields": {"summary": "Outage", "priority": {"name": "Highest"}}},
+        ]});
+        let assigned = parse(&cloud).unwrap();
+        assert_eq!((assigned.count, assigned.more), (1, true));
+        assert_eq!(assigned.priority(), Some(Priority::High));
+
+        assert_eq!(parse(&json!({"issues": []})).unwrap().priority(), None);
+        assert!(parse(&json!({"errorMessages": ["Unauthorized"]})).is_err());
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(issue_url("https://acme.atlassian.net", "API-7"), "https://acme.atlassian.net/browse/API-7");
+        assert_eq!(
+            search_url("https://acme.atlassian.net", "assignee = currentUser()"),
+            "https://acme.atlassian.net/issues/?jql=assignee%20%3D%20currentUser%28%29"
+        );
+    }
+}
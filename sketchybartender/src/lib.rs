@@ -0,0 +1,17 @@
+//! Code shared between the sketchybartender daemon and sketchycli
+//!
+//! The icon lookup in [`icon_map`] can also be used by other tools. Build with
+//! `default-features = false` to leave out the daemon-only user map loading.
+
+pub mod config;
+pub mod glob;
+pub mod icon_map;
+pub mod log;
+#[cfg(feature = "daemon")]
+pub mod monitor_map;
+pub mod normalize;
+#[cfg(feature = "daemon")]
+pub mod protocol;
+pub mod secrets;
+#[cfg(feature = "daemon")]
+pub mod service;
@@ -0,0 +1,89 @@
+//! Active issues of the user in the current cycle, from Linear's GraphQL API
+//!
+//! Linear's connections don't give a total, so the query fetches the
+//! identifiers of up to a hundred issues and counts them.
+
+use serde_json::{json, Value};
+
+use crate::http;
+
+const GRAPHQL_URL: &str = "https://api.linear.app/graphql";
+
+/// Issues counted at most; beyond that the count shows a `+`
+const FIRST: usize = 100;
+
+const QUERY: &str = "query($filter: IssueFilter, $first: Int!) {
+  viewer {
+    assignedIssues(filter: $filter, first: $first) {
+      nodes { identifier }
+      pageInfo { hasNextPage }
+    }
+  }
+}";
+
+/// How many active issues the user has in the current cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Count {
+    pub issues: usize,
+    /// Whether there are more issues than `issues`
+    pub more: bool,
+}
+
+/// The filter for started and unstarted issues in an active cycle, of `teams` if there are any
+fn filter(teams: &[String]) -> Value {
+    let mut filter = json!({
+        "cycle": {"isActive": {"eq": true}},
+        "state": {"type": {"in": ["unstarted", "started"]}},
+    });
+    if !teams.is_empty() {
+        filter["team"] = json!({"key": {"in": teams}});
+    }
+    filter
+}
+
+/// Linear's reply to the query, or the first error it gives
+fn parse(reply: &Value) -> Result<Count, String> {
+    if let Some(message) = reply["errors"][0]["message"].as_str() {
+        return Err(format!("Linear refused the query: {}", message));
+    }
+    let unexpected = || "Unexpected issues from Linear".to_string();
+    let issues = &reply["data"]["viewer"]["assignedIssues"];
+    Ok(Count {
+        issues: issues["nodes"].as_array().ok_or_else(unexpected)?.len(),
+        more: issues["pageInfo"]["hasNextPage"].as_bool().ok_or_else(unexpected)?,
+    })
+}
+
+/// Count the user's active issues in the current cycle of `teams`, or of every team
+pub fn count(api_key: &str, teams: &[String]) -> Result<Count, String> {
+    let body = json!({"query": QUERY, "variables": {"filter": filter(teams), "first": FIRST}});
+    // Personal API keys go in the header as they are, without `Bearer`
+    let reply = http::post("Linear", GRAPHQL_URL, &[format!("Authorization: {}", api_key)], &body)?;
+    parse(&reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        assert!(filter(&[]).get("team").is_none());
+        let teams = filter(&["ENG".to_string()]);
+        assert_eq!(teams["team"], json!({"key": {"in": ["ENG"]}}));
+        assert_eq!(teams["cycle"], json!({"isActive": {"eq": true}}));
+    }
+
+    #[test]
+    fn test_parse() {
+        let reply = json!({"data": {"viewer": {"assignedIssues": {
+            "nodes": [{"identifier": "ENG-12"}, {"identifier": "ENG-15"}],
+            "pageInfo": {"hasNextPage": false},
+        }}}});
+        assert_eq!(parse(&reply).unwrap(), Count { issues: 2, more: false });
+
+        let refused = json!({"errors": [{"message": "Authentication required"}]});
+        assert_eq!(parse(&refused).unwrap_err(), "Linear refused the query: Authentication required");
+        assert!(parse(&json!({"data": null})).is_err());
+    }
+}
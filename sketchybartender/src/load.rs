@@ -0,0 +1,25 @@
+//! Load averages from getloadavg
+//!
+//! The load average counts the threads running or waiting to run, averaged
+//! over the last 1, 5 and 15 minutes. A load equal to the number of cores
+//! keeps every core busy, so the averages are shown per core: 100% is a fully
+//! busy machine, and anything past it means threads queue for a core.
+
+use std::ffi::c_int;
+use std::thread;
+
+extern "C" {
+    fn getloadavg(averages: *mut f64, count: c_int) -> c_int;
+}
+
+/// The 1, 5 and 15-minute load averages, or None if the kernel didn't report all three
+pub fn averages() -> Option<[f64; 3]> {
+    let mut averages = [0.0; 3];
+    let count = unsafe { getloadavg(averages.as_mut_ptr(), 3) };
+    (count == 3).then_some(averages)
+}
+
+/// Logical cores, which the averages are normalized by
+pub fn cores() -> usize {
+    thread::available_parallelism().map(|cores| cores.get()).unwrap_or(1)
+}
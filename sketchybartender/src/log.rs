@@ -0,0 +1,218 @@
+//! Daemon log with levels, timestamps and an optional rotating file
+//!
+//! Every line goes to stderr, where sketchybarrc's redirect or launchd
+//! collects it, and with `file = true` under `[log]` also to a file that is
+//! rotated once it grows past [`MAX_FILE_SIZE`]. Use the [`error!`](crate::error),
+//! [`warn!`](crate::warn), [`info!`](crate::info) and [`debug!`](crate::debug)
+//! macros; messages below `level` aren't even formatted.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use crate::config::LogLevel;
+
+/// Size at which the log file is rotated
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Rotated files kept next to the current one, `.1` being the newest
+const KEEP_FILES: usize = 3;
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// The open log file and how much has been written to it
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+fn file_lock() -> &'static Mutex<Option<LogFile>> {
+    static FILE: OnceLock<Mutex<Option<LogFile>>> = OnceLock::new();
+    FILE.get_or_init(|| Mutex::new(None))
+}
+
+/// Log messages at `level` and more severe from now on
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are logged
+pub fn enabled(level: LogLevel) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Also write to the file at `path`, or stop writing to a file with `None`
+pub fn set_file(path: Option<PathBuf>) {
+    let Ok(mut current) = file_lock().lock() else {
+        return;
+    };
+    if current.as_ref().map(|log| &log.path) == path.as_ref() {
+        return;
+    }
+
+    *current = path.and_then(|path| match open(&path) {
+        Ok(log) => Some(log),
+        Err(e) => {
+            eprintln!("{} ERROR Failed to open log file {}: {}", timestamp(SystemTime::now()), path.display(), e);
+            None
+        }
+    });
+}
+
+fn open(path: &Path) -> io::Result<LogFile> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(LogFile { path: path.to_path_buf(), file, size })
+}
+
+/// Shift `log.1` to `log.2` and so on, dropping the oldest, and move `path` to `log.1`
+fn rotate(path: &Path) -> io::Result<()> {
+    let numbered = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    for n in (1..KEEP_FILES).rev() {
+        if numbered(n).exists() {
+            fs::rename(numbered(n), numbered(n + 1))?;
+        }
+    }
+    fs::rename(path, numbered(1))
+}
+
+impl LogFile {
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size + line.len() as u64 > MAX_FILE_SIZE && self.size > 0 {
+            rotate(&self.path)?;
+            *self = open(&self.path)?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// `time` as an ISO 8601 UTC timestamp with milliseconds, e.g. `2026-10-16T07:30:00.250Z`
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // Days since the epoch to a civil date, after Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Write a message; use the macros, which skip formatting below the level
+pub fn write(level: LogLevel, message: fmt::Arguments) {
+    let label = match level {
+        LogLevel::Error => "ERROR",
+        LogLevel::Warn => "WARN ",
+        LogLevel::Info => "INFO ",
+        LogLevel::Debug => "DEBUG",
+    };
+    let line = format!("{} {} {}\n", timestamp(SystemTime::now()), label, message);
+    let _ = io::stderr().write_all(line.as_bytes());
+
+    if let Ok(mut file) = file_lock().lock() {
+        if let Some(log) = file.as_mut() {
+            if let Err(e) = log.write_line(&line) {
+                eprintln!("Failed to write log file {}, no longer writing it: {}", log.path.display(), e);
+                *file = None;
+            }
+        }
+    }
+}
+
+/// Log at a level, formatting the message only when the level is enabled
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write($level, format_args!($($arg)+));
+        }
+    };
+}
+
+/// Log a failure that loses data or leaves an item stale
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log!($crate::log::LogLevel::Error, $($arg)+) };
+}
+
+/// Log something that went wrong but was worked around
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log!($crate::log::LogLevel::Warn, $($arg)+) };
+}
+
+/// Log what the daemon does
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log!($crate::log::LogLevel::Info, $($arg)+) };
+}
+
+/// Log detail for tracking down a problem
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log!($crate::log::LogLevel::Debug, $($arg)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let time = UNIX_EPOCH + Duration::from_millis(1_792_135_800_250);
+        assert_eq!(timestamp(time), "2026-10-16T07:30:00.250Z");
+        // A leap day
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("sketchybartender-log-{}", std::process::id()));
+        let path = dir.join("sketchybartender.log");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut log = open(&path).unwrap();
+        let line = format!("{}\n", "x".repeat(MAX_FILE_SIZE as usize / 2 - 1));
+        for _ in 0..9 {
+            log.write_line(&line).unwrap();
+        }
+
+        // Two lines fit in a file, so nine fill four rotated files, of which three are kept
+        assert_eq!(fs::read_to_string(&path).unwrap(), line);
+        for n in 1..=KEEP_FILES {
+            assert_eq!(fs::metadata(format!("{}.{}", path.display(), n)).unwrap().len(), 2 * line.len() as u64);
+        }
+        assert!(!Path::new(&format!("{}.{}", path.display(), KEEP_FILES + 1)).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
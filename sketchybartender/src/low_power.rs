@@ -0,0 +1,145 @@
+//! Low Power Mode from NSProcessInfo
+//!
+//! `lowPowerModeEnabled` reads whether Low Power Mode is on, and NSProcessInfo
+//! posts a notification whenever it's turned on or off, whether from Control
+//! Center, System Settings or `pmset`. The observer only sends on a channel;
+//! another thread runs the Rust callback. Outside macOS it's always off.
+//!
+//! Turning it on or off takes root, so `set` runs `pmset` through an
+//! administrator prompt.
+
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use sketchybartender::warn;
+
+/// Sending end of the event channel, set by `listen`
+static EVENTS: OnceLock<Mutex<Sender<()>>> = OnceLock::new();
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use crate::objc::Id;
+
+    #[link(name = "Foundation", kind = "framework")]
+    extern "C" {
+        pub static NSProcessInfoPowerStateDidChangeNotification: Id;
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::c_void;
+
+    use sketchybartender::error;
+
+    use super::{ffi, EVENTS};
+    use crate::objc::{self, send, Id, Sel};
+
+    /// `powerStateChanged:` of the observer class
+    extern "C" fn power_state_changed(_this: Id, _cmd: Sel, _notification: Id) {
+        if let Some(Ok(events)) = EVENTS.get().map(|events| events.lock()) {
+            let _ = events.send(());
+        }
+    }
+
+    pub fn read() -> Option<bool> {
+        objc::autoreleasing(|| unsafe {
+            let process_info = send(objc::class(c"NSProcessInfo"), c"processInfo");
+            if process_info.is_null() {
+                return None;
+            }
+            let enabled = objc::msg_send::<unsafe extern "C" fn(Id, Sel) -> i8>();
+            Some(enabled(process_info, objc::selector(c"isLowPowerModeEnabled")) != 0)
+        })
+    }
+
+    /// Observe Low Power Mode changes; NSProcessInfo posts them on a thread of its own
+    pub fn register() {
+        let methods: [objc::Method; 1] = [(c"powerStateChanged:", power_state_changed as *const c_void, c"v@:@")];
+        let Some(class) = objc::define_class(c"SketchybartenderLowPowerObserver", &methods) else {
+            error!("Failed to create the Low Power Mode observer class");
+            return;
+        };
+
+        unsafe {
+            let observer = send(send(class, c"alloc"), c"init");
+            let center = send(objc::class(c"NSNotificationCenter"), c"defaultCenter");
+            objc::msg_send::<unsafe extern "C" fn(Id, Sel, Id, Sel, Id, Id)>()(
+                center,
+                objc::selector(c"addObserver:selector:name:object:"),
+                observer,
+                objc::selector(c"powerStateChanged:"),
+                ffi::NSProcessInfoPowerStateDidChangeNotification,
+                std::ptr::null_mut(),
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn read() -> Option<bool> {
+        None
+    }
+
+    pub fn register() {}
+}
+
+/// Whether Low Power Mode is on, or None without NSProcessInfo
+pub fn enabled() -> Option<bool> {
+    native::read()
+}
+
+/// The AppleScript that runs `pmset -a lowpowermode` as root, asking for an administrator's password
+fn set_script(on: bool) -> String {
+    format!(
+        "do shell script \"pmset -a lowpowermode {}\" with administrator privileges",
+        if on { 1 } else { 0 }
+    )
+}
+
+/// Turn Low Power Mode on or off; the listener picks up the change
+pub fn set(on: bool) -> Result<(), String> {
+    let output = Command::new("osascript")
+        .args(["-e", &set_script(on)])
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        // Also the way out when the password prompt is cancelled
+        return Err(format!("pmset failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// Run `callback` on a background thread whenever Low Power Mode is turned on or off
+pub fn listen(callback: impl Fn() + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+    if EVENTS.set(Mutex::new(sender)).is_err() {
+        warn!("Low Power Mode listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            callback();
+        }
+    });
+
+    native::register();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_script() {
+        assert_eq!(
+            set_script(true),
+            "do shell script \"pmset -a lowpowermode 1\" with administrator privileges"
+        );
+        assert!(set_script(false).contains("lowpowermode 0"));
+    }
+}
@@ -0,0 +1,242 @@
+//! sketchybar's Mach message port, so updates don't fork the `sketchybar` CLI
+//!
+//! sketchybar registers `git.felix.<bar name>` with the bootstrap server and
+//! takes each command as an out-of-line buffer of NUL-terminated arguments
+//! ending in one more NUL, the way its own CLI sends them. It replies on the
+//! port the message names with what the CLI would print; complaints start with
+//! `[!]`. Outside macOS there is no port, and callers fall back to the CLI.
+
+use std::io;
+use std::time::Duration;
+
+/// The arguments as sketchybar's CLI sends them: each NUL-terminated, then one more NUL
+pub fn encode(args: &[String]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(args.iter().map(|arg| arg.len() + 1).sum::<usize>() + 1);
+    for arg in args {
+        message.extend_from_slice(arg.as_bytes());
+        message.push(0);
+    }
+    message.push(0);
+    message
+}
+
+/// Send a command to sketchybar and return its reply; `None` when its port can't be found,
+/// e.g. because sketchybar isn't running
+pub fn send(args: &[String], timeout: Duration) -> Option<io::Result<String>> {
+    native::send(&mut encode(args), timeout)
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::env;
+    use std::ffi::{c_char, c_void, CStr, CString};
+    use std::io;
+    use std::mem;
+    use std::slice;
+    use std::time::Duration;
+
+    type Port = u32;
+    type KernReturn = i32;
+
+    const KERN_SUCCESS: KernReturn = 0;
+    const TASK_BOOTSTRAP_PORT: i32 = 4;
+    const MACH_PORT_RIGHT_RECEIVE: u32 = 1;
+    const MACH_MSG_TYPE_COPY_SEND: u32 = 19;
+    const MACH_MSG_TYPE_MAKE_SEND: u32 = 20;
+    const MACH_MSGH_BITS_COMPLEX: u32 = 0x8000_0000;
+    const MACH_SEND_MSG: i32 = 0x1;
+    const MACH_RCV_MSG: i32 = 0x2;
+    const MACH_SEND_TIMEOUT: i32 = 0x10;
+    const MACH_RCV_TIMEOUT: i32 = 0x100;
+    const MACH_SEND_TIMED_OUT: KernReturn = 0x1000_0004;
+    const MACH_RCV_TIMED_OUT: KernReturn = 0x1000_4003;
+    const MACH_MSG_VIRTUAL_COPY: u8 = 1;
+    const MACH_MSG_OOL_DESCRIPTOR: u8 = 1;
+
+    /// `mach_msg_header_t`
+    #[repr(C, packed(4))]
+    #[derive(Clone, Copy)]
+    struct Header {
+        bits: u32,
+        size: u32,
+        remote_port: Port,
+        local_port: Port,
+        voucher_port: Port,
+        id: i32,
+    }
+
+    /// `mach_msg_ool_descriptor_t` on 64-bit, its bitfields as bytes
+    #[repr(C, packed(4))]
+    #[derive(Clone, Copy)]
+    struct OolDescriptor {
+        address: *mut c_void,
+        deallocate: u8,
+        copy: u8,
+        pad: u8,
+        kind: u8,
+        size: u32,
+    }
+
+    /// A message carrying one out-of-line buffer, as sketchybar sends and expects them
+    #[repr(C, packed(4))]
+    struct Message {
+        header: Header,
+        descriptor_count: u32,
+        descriptor: OolDescriptor,
+    }
+
+    /// Room for a reply and the trailer the kernel appends
+    #[repr(C, packed(4))]
+    struct ReplyBuffer {
+        message: Message,
+        trailer: [u32; 2],
+    }
+
+    extern "C" {
+        static mach_task_self_: Port;
+        fn task_get_special_port(task: Port, which: i32, port: *mut Port) -> KernReturn;
+        fn bootstrap_look_up(bootstrap: Port, name: *const c_char, port: *mut Port) -> KernReturn;
+        fn mach_port_allocate(task: Port, right: u32, name: *mut Port) -> KernReturn;
+        fn mach_port_insert_right(task: Port, name: Port, right: Port, kind: u32) -> KernReturn;
+        fn mach_port_deallocate(task: Port, name: Port) -> KernReturn;
+        fn mach_port_mod_refs(task: Port, name: Port, right: u32, delta: i32) -> KernReturn;
+        fn mach_msg(
+            message: *mut Header,
+            option: i32,
+            send_size: u32,
+            receive_size: u32,
+            receive_port: Port,
+            timeout: u32,
+            notify: Port,
+        ) -> KernReturn;
+        fn mach_msg_destroy(message: *mut Header);
+    }
+
+    fn check(result: KernReturn, action: &str) -> io::Result<()> {
+        match result {
+            KERN_SUCCESS => Ok(()),
+            MACH_SEND_TIMED_OUT | MACH_RCV_TIMED_OUT => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, format!("sketchybar didn't {} in time", action)))
+            }
+            code => Err(io::Error::other(format!("Failed to {} sketchybar's port: {:#x}", action, code))),
+        }
+    }
+
+    /// sketchybar's port, registered under `BAR_NAME` like its CLI looks it up
+    fn look_up() -> Option<Port> {
+        let bar = env::var("BAR_NAME").unwrap_or_else(|_| "sketchybar".to_string());
+        let name = CString::new(format!("git.felix.{}", bar)).ok()?;
+        unsafe {
+            let task = mach_task_self_;
+            let mut bootstrap = 0;
+            if task_get_special_port(task, TASK_BOOTSTRAP_PORT, &mut bootstrap) != KERN_SUCCESS {
+                return None;
+            }
+            let mut port = 0;
+            let found = bootstrap_look_up(bootstrap, name.as_ptr(), &mut port);
+            mach_port_deallocate(task, bootstrap);
+            (found == KERN_SUCCESS && port != 0).then_some(port)
+        }
+    }
+
+    pub fn send(message: &mut [u8], timeout: Duration) -> Option<io::Result<String>> {
+        let port = look_up()?;
+        let reply = unsafe { exchange(port, message, timeout) };
+        unsafe {
+            mach_port_deallocate(mach_task_self_, port);
+        }
+        Some(reply)
+    }
+
+    /// Send `message` to `port` and wait for the reply on a port of our own
+    unsafe fn exchange(port: Port, message: &mut [u8], timeout: Duration) -> io::Result<String> {
+        let task = mach_task_self_;
+        let mut reply_port = 0;
+        check(mach_port_allocate(task, MACH_PORT_RIGHT_RECEIVE, &mut reply_port), "allocate a reply port for")?;
+        let reply = match check(
+            mach_port_insert_right(task, reply_port, reply_port, MACH_MSG_TYPE_MAKE_SEND),
+            "set up a reply port for",
+        ) {
+            Ok(()) => {
+                let reply = send_and_receive(port, reply_port, message, timeout);
+                mach_port_deallocate(task, reply_port);
+                reply
+            }
+            Err(e) => Err(e),
+        };
+        mach_port_mod_refs(task, reply_port, MACH_PORT_RIGHT_RECEIVE, -1);
+        reply
+    }
+
+    unsafe fn send_and_receive(
+        port: Port,
+        reply_port: Port,
+        message: &mut [u8],
+        timeout: Duration,
+    ) -> io::Result<String> {
+        let timeout = timeout.as_millis().min(u32::MAX as u128) as u32;
+        let mut request = Message {
+            header: Header {
+                bits: MACH_MSG_TYPE_COPY_SEND | (MACH_MSG_TYPE_MAKE_SEND << 8) | MACH_MSGH_BITS_COMPLEX,
+                size: mem::size_of::<Message>() as u32,
+                remote_port: port,
+                local_port: reply_port,
+                voucher_port: 0,
+                id: reply_port as i32,
+            },
+            descriptor_count: 1,
+            descriptor: OolDescriptor {
+                address: message.as_mut_ptr().cast(),
+                deallocate: 0,
+                copy: MACH_MSG_VIRTUAL_COPY,
+                pad: 0,
+                kind: MACH_MSG_OOL_DESCRIPTOR,
+                size: message.len() as u32,
+            },
+        };
+        let header = (&mut request as *mut Message).cast::<Header>();
+        let size = mem::size_of::<Message>() as u32;
+        check(mach_msg(header, MACH_SEND_MSG | MACH_SEND_TIMEOUT, size, 0, 0, timeout, 0), "send to")?;
+
+        let mut buffer: ReplyBuffer = mem::zeroed();
+        let header = (&mut buffer as *mut ReplyBuffer).cast::<Header>();
+        let size = mem::size_of::<ReplyBuffer>() as u32;
+        check(mach_msg(header, MACH_RCV_MSG | MACH_RCV_TIMEOUT, 0, size, reply_port, timeout, 0), "reply through")?;
+
+        let descriptor = buffer.message.descriptor;
+        let reply = if descriptor.address.is_null() {
+            String::new()
+        } else {
+            let bytes = slice::from_raw_parts(descriptor.address.cast::<u8>(), descriptor.size as usize);
+            match CStr::from_bytes_until_nul(bytes) {
+                Ok(text) => text.to_string_lossy().into_owned(),
+                Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+            }
+        };
+        // Frees the reply's out-of-line buffer
+        mach_msg_destroy(header);
+        Ok(reply)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    use std::io;
+    use std::time::Duration;
+
+    pub fn send(_message: &mut [u8], _timeout: Duration) -> Option<io::Result<String>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let args = ["--set".to_string(), "clock".to_string(), "label=16/10 09:30".to_string()];
+        assert_eq!(encode(&args), b"--set\0clock\0label=16/10 09:30\0\0");
+        assert_eq!(encode(&[]), b"\0");
+    }
+}
@@ -1,65 +1,197 @@
 mod aerospace;
-mod config;
+mod appearance;
+mod app_icons;
+mod bootstrap;
+mod brightness;
+mod bundles;
+mod calendar;
+mod clamshell;
+mod cpu;
+mod crypto;
 mod daemon;
+mod disk;
+mod displays;
+mod events;
+mod exchange;
+mod feeds;
+mod focus_mode;
+mod frontmost;
+mod github;
+mod gitlab;
 mod handlers;
-mod icon_map;
-mod monitor_map;
+mod history;
+mod hooks;
+mod http;
+mod jira;
+mod linear;
+mod load;
+mod low_power;
+mod mach;
+mod memory;
+mod menubar;
+mod metrics;
+mod moon;
+mod network;
+mod notify;
+#[cfg(target_os = "macos")]
+mod objc;
+mod power;
+mod processes;
 mod providers;
+mod quiet;
+mod rules;
+mod runtime;
+mod scripting;
+mod settings;
+mod signals;
+mod sketchybar;
+mod smc;
+mod sun;
+mod thermal;
+mod volume;
+mod watcher;
+mod weather;
+mod wifi;
+mod zoom;
 
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::Duration;
 
 use handlers::DaemonState;
+use sketchybartender::monitor_map::DisplayChange;
+use sketchybartender::{config, error, icon_map, info};
 
 fn main() {
-    // Load configuration
-    let config = config::Config::load();
+    // Load configuration, with the profile selected by `sketchycli profile`
+    let strict = std::env::args().skip(1).any(|arg| arg == "--strict");
+    let config = match config::Config::load_selected(strict) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Refusing to start: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Timers, socket clients and handler work run on the runtime, which lives as long as main
+    let runtime = runtime::start();
+
+    // Clock, battery, brew and teams
+    handlers::register_providers();
 
     // Shared state
     let state = Arc::new(Mutex::new(DaemonState::default()));
 
-    // Initial refresh
-    handlers::handle_workspace_refresh(&state);
-    handlers::handle_clock_refresh();
-    handlers::handle_battery_refresh(None);
-    handlers::handle_focus_refresh(None, &state);
-    handlers::handle_brew_refresh();
-    handlers::handle_teams_refresh();
-
-    // Spawn timer threads for periodic updates using configured intervals
-    let clock_interval = config.clock_interval;
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(clock_interval));
-            handlers::handle_clock_refresh();
+    // Settings are reapplied on `sketchycli profile` and when the config changes; user icon mappings on SIGHUP
+    // or when the files change. SIGTERM and SIGINT shut down cleanly rather than leave a stale socket behind
+    // The bar and its items come first, so custom and graph items are added next to them
+    if config.bootstrap_items {
+        handlers::handle_bootstrap_items(&config);
+    }
+    handlers::apply_config(config);
+    icon_map::reload_overrides();
+    let signal_state = Arc::clone(&state);
+    signals::listen(&[signals::SIGHUP, signals::SIGINT, signals::SIGTERM], move |signum| {
+        if signum == signals::SIGHUP {
+            handlers::handle_icons_reload(&signal_state);
+        } else {
+            info!("[SHUTDOWN] Received signal {}", signum);
+            handlers::handle_shutdown();
         }
     });
+    let watch_state = Arc::clone(&state);
+    watcher::watch(
+        vec![icon_map::overrides_path(), icon_map::user_icon_map_path()],
+        move || handlers::handle_icons_reload(&watch_state),
+    );
 
-    let battery_interval = config.battery_interval;
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(battery_interval));
-            handlers::handle_battery_refresh(None);
-        }
+    // Editing the config applies it without a restart; timers pick up new intervals right away
+    let config_state = Arc::clone(&state);
+    watcher::watch(
+        vec![config::Config::get_config_path(), config::Config::get_legacy_config_path()],
+        move || handlers::handle_config_reload(&config_state),
+    );
+
+    // Docking and undocking change which display each workspace belongs to
+    let display_state = Arc::clone(&state);
+    displays::listen(move || handlers::handle_display_change(&display_state));
+
+    // Volume, mute and output device changes redraw the volume and audio_output items as they happen
+    volume::listen(|| {
+        handlers::handle_volume_refresh();
+        handlers::handle_audio_output_refresh(false);
     });
 
-    let brew_interval = config.brew_interval;
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(brew_interval));
-            handlers::handle_brew_refresh();
-        }
+    // Plugging in the charger and battery percentage changes redraw the battery item as they happen
+    power::listen(handlers::handle_power_change);
+
+    // Throttling shows on the thermal item as soon as macOS reports it
+    thermal::listen(handlers::handle_thermal_change);
+
+    // Turning Low Power Mode on or off retints the battery icon straight away
+    low_power::listen(handlers::handle_low_power_change);
+
+    // Turning a Focus on or off rewrites its assertions file
+    watcher::watch(vec![focus_mode::assertions_path()], handlers::handle_focus_mode_change);
+
+    // Switching apps redraws front_app straight from NSWorkspace, without an aerospace hook
+    let focus_state = Arc::clone(&state);
+    frontmost::listen(move |app| {
+        handlers::handle_focus_refresh(Some(app), &focus_state);
+        handlers::handle_focus_rules();
     });
 
-    let teams_interval = config.teams_interval;
-    thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_secs(teams_interval));
-            handlers::handle_teams_refresh();
+    // Switching between light and dark redraws the appearance item and runs its hook
+    appearance::listen(handlers::handle_appearance_change);
+
+    // The menu bar fallback's menu runs the same handlers as sketchycli
+    menubar::listen(handlers::handle_menubar_action);
+
+    // Closing the lid removes the built-in display; opening it brings it back
+    let clamshell_state = Arc::clone(&state);
+    events::listen(move |event| {
+        if let events::Event::Display(DisplayChange::Added(_) | DisplayChange::Removed(_)) = event {
+            handlers::handle_clamshell_change(&clamshell_state);
         }
     });
 
-    // Start the daemon socket listener
-    daemon::start_daemon();
+    // Initial refresh, which also records the displays to compare later changes against
+    handlers::handle_display_change(&state);
+    handlers::handle_clamshell_change(&state);
+    handlers::handle_volume_refresh();
+    handlers::handle_focus_refresh(None, &state);
+    for provider in providers::registered() {
+        provider.run();
+    }
+
+    // Each provider gets a timer on the shared scheduler, recomputing its delay after each run and right away
+    // when a profile switch changes an interval; the battery's is a fallback in case a power notification is missed
+    for provider in providers::registered() {
+        let timer = Arc::clone(&provider);
+        runtime::repeat(move || timer.interval(), move || provider.run());
+    }
+
+    // Rules depend on the time of day and the network, which change without an event
+    runtime::repeat(|| Duration::from_secs(30), handlers::handle_rules_refresh);
+
+    // Screen sharing starts and stops without an event; quiet mode follows within seconds
+    runtime::repeat(|| Duration::from_secs(5), handlers::handle_quiet_check);
+
+    // sketchybar can quit or crash without telling anyone; the fallback shows up within seconds
+    handlers::handle_menubar_check();
+    runtime::repeat(|| Duration::from_secs(10), handlers::handle_menubar_check);
+
+    // Graphs scroll by one sample per interval
+    runtime::repeat(|| Duration::from_secs(settings::current().history.interval), handlers::handle_history_sample);
+
+    // Custom items each have their own interval; check every second which are due
+    runtime::repeat(|| Duration::from_secs(1), handlers::handle_custom_items_due);
+
+    // NSWorkspace and AppKit need the main thread; the socket listener runs on the runtime
+    let menubar_app = settings::current().menubar_fallback != config::MenubarFallback::Off;
+    let server = runtime.spawn(daemon::serve(state));
+    menubar::run_main_loop(menubar_app);
+
+    // Without a run loop, main waits for the server, which only ends when shutting down
+    let _ = runtime.block_on(server);
+    handlers::handle_shutdown();
 }
@@ -0,0 +1,179 @@
+//! Memory usage and pressure from the Mach VM statistics
+//!
+//! Usage counts memory the way Activity Monitor's "Memory Used" does: app
+//! memory (anonymous pages that can't be purged), wired pages and the pages
+//! holding compressed memory. Free and file-backed pages don't count, since
+//! macOS hands them out as soon as something needs them. The pressure level
+//! is the kernel's own, the one `memory_pressure` and Activity Monitor show.
+//! Outside macOS there are no statistics, and callers fall back to `top`.
+
+use sketchybartender::config::Level;
+
+/// Page counts from `host_statistics64`
+#[derive(Debug, Clone, Copy, Default)]
+struct MemoryPages {
+    internal: u64,
+    purgeable: u64,
+    wired: u64,
+    compressed: u64,
+}
+
+/// Share of `total` bytes in use
+fn used_percentage(pages: MemoryPages, page_size: u64, total: u64) -> u8 {
+    if total == 0 {
+        return 0;
+    }
+    let used = (pages.internal.saturating_sub(pages.purgeable) + pages.wired + pages.compressed) * page_size;
+    (used as f64 / total as f64 * 100.0).round().min(100.0) as u8
+}
+
+/// The pressure for `kern.memorystatus_vm_pressure_level`: 1 normal, 2 warning, 4 critical
+fn pressure(level: i32) -> Level {
+    match level {
+        4 => Level::Critical,
+        2 => Level::Warning,
+        _ => Level::Normal,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::{c_char, c_int, c_void, CStr};
+    use std::mem;
+
+    use super::MemoryPages;
+
+    type Port = u32;
+
+    /// HOST_VM_INFO64
+    const VM_INFO64: i32 = 4;
+
+    /// `vm_statistics64_data_t`
+    #[repr(C)]
+    #[derive(Default)]
+    struct VmStatistics64 {
+        _free_count: u32,
+        _active_count: u32,
+        _inactive_count: u32,
+        wire_count: u32,
+        _zero_fill_count: u64,
+        _reactivations: u64,
+        _pageins: u64,
+        _pageouts: u64,
+        _faults: u64,
+        _cow_faults: u64,
+        _lookups: u64,
+        _hits: u64,
+        _purges: u64,
+        purgeable_count: u32,
+        _speculative_count: u32,
+        _decompressions: u64,
+        _compressions: u64,
+        _swapins: u64,
+        _swapouts: u64,
+        compressor_page_count: u32,
+        _throttled_count: u32,
+        _external_page_count: u32,
+        internal_page_count: u32,
+        _total_uncompressed_pages_in_compressor: u64,
+    }
+
+    extern "C" {
+        static mach_task_self_: Port;
+        fn mach_host_self() -> Port;
+        fn mach_port_deallocate(task: Port, name: Port) -> i32;
+        fn host_statistics64(host: Port, flavor: i32, info: *mut i32, count: *mut u32) -> i32;
+        fn sysctlbyname(
+            name: *const c_char,
+            value: *mut c_void,
+            size: *mut usize,
+            new_value: *mut c_void,
+            new_size: usize,
+        ) -> c_int;
+    }
+
+    /// Read a fixed-size sysctl
+    fn sysctl<T: Default>(name: &CStr) -> Option<T> {
+        let mut value = T::default();
+        let mut size = mem::size_of::<T>();
+        let result = unsafe {
+            sysctlbyname(name.as_ptr(), &mut value as *mut T as *mut c_void, &mut size, std::ptr::null_mut(), 0)
+        };
+        (result == 0 && size == mem::size_of::<T>()).then_some(value)
+    }
+
+    pub fn pages() -> Option<MemoryPages> {
+        let mut stats = VmStatistics64::default();
+        let mut count = (mem::size_of::<VmStatistics64>() / mem::size_of::<i32>()) as u32;
+        let result = unsafe {
+            let host = mach_host_self();
+            let result = host_statistics64(host, VM_INFO64, &mut stats as *mut VmStatistics64 as *mut i32, &mut count);
+            mach_port_deallocate(mach_task_self_, host);
+            result
+        };
+        (result == 0).then(|| MemoryPages {
+            internal: u64::from(stats.internal_page_count),
+            purgeable: u64::from(stats.purgeable_count),
+            wired: u64::from(stats.wire_count),
+            compressed: u64::from(stats.compressor_page_count),
+        })
+    }
+
+    pub fn page_size() -> Option<u64> {
+        sysctl::<u64>(c"vm.pagesize").or_else(|| sysctl::<u32>(c"vm.pagesize").map(u64::from))
+    }
+
+    pub fn total() -> Option<u64> {
+        sysctl::<u64>(c"hw.memsize")
+    }
+
+    pub fn pressure_level() -> Option<i32> {
+        sysctl::<i32>(c"kern.memorystatus_vm_pressure_level")
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    use super::MemoryPages;
+
+    pub fn pages() -> Option<MemoryPages> {
+        None
+    }
+
+    pub fn page_size() -> Option<u64> {
+        None
+    }
+
+    pub fn total() -> Option<u64> {
+        None
+    }
+
+    pub fn pressure_level() -> Option<i32> {
+        None
+    }
+}
+
+/// Percentage of physical memory in use, or None without the VM statistics
+pub fn usage() -> Option<u8> {
+    Some(used_percentage(native::pages()?, native::page_size()?, native::total()?))
+}
+
+/// The kernel's memory pressure level, normal where it can't be read
+pub fn kernel_pressure() -> Level {
+    native::pressure_level().map(pressure).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_used_percentage() {
+        // 16 GiB in 16 KiB pages: 6 GiB of app memory, 2 GiB wired and 1 GiB compressed
+        let pages = MemoryPages { internal: 425_984, purgeable: 32_768, wired: 131_072, compressed: 65_536 };
+        assert_eq!(used_percentage(pages, 16_384, 16 << 30), 56);
+        assert_eq!(used_percentage(pages, 16_384, 0), 0);
+        assert_eq!(pressure(2), Level::Warning);
+        assert_eq!(pressure(1), Level::Normal);
+    }
+}
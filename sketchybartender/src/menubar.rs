@@ -0,0 +1,295 @@
+//! Menu bar fallback: the daemon's own status item while sketchybar is down
+//!
+//! AppKit objects may only be used on the main thread, which runs the
+//! NSApplication event loop when the fallback is enabled at start. Other
+//! threads record the clock, battery and brew state here and dispatch a
+//! redraw onto the main queue. Menu actions go back over a channel to a
+//! thread that runs the handlers.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use sketchybartender::{info, warn};
+
+use crate::frontmost;
+
+/// State shown in the status item's title
+#[derive(Debug, Clone, Copy)]
+pub enum Field {
+    Clock,
+    Battery,
+    Brew,
+}
+
+/// Entries of the status item's menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub enum Action {
+    Refresh,
+    UpgradeBrew,
+    ToggleQuiet,
+}
+
+#[derive(Debug, Default)]
+struct MenuState {
+    clock: String,
+    battery: String,
+    brew: String,
+    visible: bool,
+}
+
+impl MenuState {
+    /// Title of the status item, leaving out empty fields
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    fn title(&self) -> String {
+        [&self.clock, &self.battery, &self.brew]
+            .iter()
+            .filter(|text| !text.is_empty())
+            .map(|text| text.as_str())
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+fn state_lock() -> &'static Mutex<MenuState> {
+    static STATE: OnceLock<Mutex<MenuState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(MenuState::default()))
+}
+
+/// Whether the main thread runs NSApplication, which status items need
+static APP_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Sending end of the action channel, set by `listen`
+static ACTIONS: OnceLock<Mutex<Sender<Action>>> = OnceLock::new();
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::c_void;
+
+    // NSApplication, NSStatusBar and NSMenu are looked up by name, so AppKit has to be loaded
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {}
+
+    extern "C" {
+        /// The main dispatch queue; `dispatch_get_main_queue()` is a macro for its address
+        pub static _dispatch_main_q: c_void;
+        pub fn dispatch_async_f(queue: *const c_void, context: *mut c_void, work: extern "C" fn(*mut c_void));
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::{c_void, CStr};
+    use std::sync::atomic::{AtomicPtr, Ordering};
+
+    use sketchybartender::error;
+
+    use super::{ffi, state_lock, Action, ACTIONS, APP_RUNNING};
+    use crate::objc::{self, send, send_with, Id, Sel};
+
+    /// NSVariableStatusItemLength
+    const VARIABLE_LENGTH: f64 = -1.0;
+    /// NSApplicationActivationPolicyAccessory: no Dock icon or app menu
+    const ACCESSORY: isize = 1;
+
+    /// The status item while shown; only touched on the main thread
+    static STATUS_ITEM: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+    fn send_action(action: Action) {
+        if let Some(Ok(actions)) = ACTIONS.get().map(|actions| actions.lock()) {
+            let _ = actions.send(action);
+        }
+    }
+
+    extern "C" fn refresh(_this: Id, _cmd: Sel, _sender: Id) {
+        send_action(Action::Refresh);
+    }
+
+    extern "C" fn upgrade_brew(_this: Id, _cmd: Sel, _sender: Id) {
+        send_action(Action::UpgradeBrew);
+    }
+
+    extern "C" fn toggle_quiet(_this: Id, _cmd: Sel, _sender: Id) {
+        send_action(Action::ToggleQuiet);
+    }
+
+    /// Build the status item and its menu
+    unsafe fn create_item() -> Id {
+        let methods: [objc::Method; 3] = [
+            (c"refresh:", refresh as *const c_void, c"v@:@"),
+            (c"upgradeBrew:", upgrade_brew as *const c_void, c"v@:@"),
+            (c"toggleQuiet:", toggle_quiet as *const c_void, c"v@:@"),
+        ];
+        // The class (and its one instance) outlive every status item
+        static TARGET: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+        let mut target = TARGET.load(Ordering::SeqCst);
+        if target.is_null() {
+            let Some(class) = objc::define_class(c"SketchybartenderMenuTarget", &methods) else {
+                error!("Failed to create the menu bar target class");
+                return std::ptr::null_mut();
+            };
+            target = send(send(class, c"alloc"), c"init");
+            TARGET.store(target, Ordering::SeqCst);
+        }
+
+        let bar = send(objc::class(c"NSStatusBar"), c"systemStatusBar");
+        let status_length = objc::msg_send::<unsafe extern "C" fn(Id, Sel, f64) -> Id>();
+        let item = send(status_length(bar, objc::selector(c"statusItemWithLength:"), VARIABLE_LENGTH), c"retain");
+
+        let menu = send(objc::class(c"NSMenu"), c"alloc");
+        let menu = send_with(menu, c"initWithTitle:", objc::nsstring("sketchybartender"));
+        let entries: [(&str, &CStr); 3] = [
+            ("Refresh", c"refresh:"),
+            ("Upgrade Homebrew", c"upgradeBrew:"),
+            ("Toggle Quiet Mode", c"toggleQuiet:"),
+        ];
+        let init_entry = objc::msg_send::<unsafe extern "C" fn(Id, Sel, Id, Sel, Id) -> Id>();
+        for (title, action) in entries {
+            let entry = init_entry(
+                send(objc::class(c"NSMenuItem"), c"alloc"),
+                objc::selector(c"initWithTitle:action:keyEquivalent:"),
+                objc::nsstring(title),
+                objc::selector(action),
+                objc::nsstring(""),
+            );
+            send_with(entry, c"setTarget:", target);
+            send_with(menu, c"addItem:", entry);
+        }
+        send_with(item, c"setMenu:", menu);
+        item
+    }
+
+    /// Show, update or remove the status item; runs on the main queue
+    extern "C" fn redraw(_context: *mut c_void) {
+        let (visible, title) = match state_lock().lock() {
+            Ok(state) => (state.visible, state.title()),
+            Err(_) => return,
+        };
+
+        objc::autoreleasing(|| unsafe {
+            let mut item = STATUS_ITEM.load(Ordering::SeqCst);
+            if visible {
+                if item.is_null() {
+                    item = create_item();
+                    if item.is_null() {
+                        return;
+                    }
+                    STATUS_ITEM.store(item, Ordering::SeqCst);
+                }
+                send_with(send(item, c"button"), c"setTitle:", objc::nsstring(&title));
+            } else if !item.is_null() {
+                send_with(send(objc::class(c"NSStatusBar"), c"systemStatusBar"), c"removeStatusItem:", item);
+                send(item, c"release");
+                STATUS_ITEM.store(std::ptr::null_mut(), Ordering::SeqCst);
+            }
+        });
+    }
+
+    /// Redraw on the main thread, once NSApplication runs there
+    pub fn schedule_redraw() {
+        if APP_RUNNING.load(Ordering::SeqCst) {
+            unsafe { ffi::dispatch_async_f(&ffi::_dispatch_main_q, std::ptr::null_mut(), redraw) };
+        }
+    }
+
+    /// Run NSApplication on the main thread; never returns
+    pub fn run_app() {
+        unsafe {
+            let app = send(objc::class(c"NSApplication"), c"sharedApplication");
+            let set_policy = objc::msg_send::<unsafe extern "C" fn(Id, Sel, isize) -> u8>();
+            set_policy(app, objc::selector(c"setActivationPolicy:"), ACCESSORY);
+            APP_RUNNING.store(true, Ordering::SeqCst);
+
+            // Show whatever was recorded before the app started
+            schedule_redraw();
+            send(app, c"run");
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn schedule_redraw() {}
+
+    pub fn run_app() {}
+}
+
+/// Record a field of the title, redrawing the status item if it's shown
+pub fn set(field: Field, text: &str) {
+    let changed = match state_lock().lock() {
+        Ok(mut state) => {
+            let slot = match field {
+                Field::Clock => &mut state.clock,
+                Field::Battery => &mut state.battery,
+                Field::Brew => &mut state.brew,
+            };
+            let changed = slot != text;
+            *slot = text.to_string();
+            changed && state.visible
+        }
+        Err(_) => false,
+    };
+    if changed {
+        native::schedule_redraw();
+    }
+}
+
+/// Show or remove the status item
+pub fn set_visible(visible: bool) {
+    let changed = match state_lock().lock() {
+        Ok(mut state) => std::mem::replace(&mut state.visible, visible) != visible,
+        Err(_) => false,
+    };
+    if !changed {
+        return;
+    }
+
+    if visible && !APP_RUNNING.load(Ordering::SeqCst) {
+        warn!("[MENUBAR] Restart the daemon to show the menu bar fallback");
+        return;
+    }
+    info!("[MENUBAR] {}", if visible { "Showing the menu bar item" } else { "Removing the menu bar item" });
+    native::schedule_redraw();
+}
+
+/// Run `callback` on a background thread with each menu entry chosen
+pub fn listen(callback: impl Fn(Action) + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+    if ACTIONS.set(Mutex::new(sender)).is_err() {
+        warn!("Menu bar listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        while let Ok(action) = receiver.recv() {
+            callback(action);
+        }
+    });
+}
+
+/// Run the main thread's event loop: NSApplication with `app`, otherwise a plain run loop
+///
+/// Returns only where there is nothing to run, i.e. outside macOS.
+pub fn run_main_loop(app: bool) {
+    if app {
+        native::run_app();
+    }
+    frontmost::run_main_loop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title() {
+        let mut state = MenuState { clock: "16/10 09:30".to_string(), battery: "80%".to_string(), ..MenuState::default() };
+        assert_eq!(state.title(), "16/10 09:30  80%");
+
+        state.brew = "3 outdated".to_string();
+        state.battery.clear();
+        assert_eq!(state.title(), "16/10 09:30  3 outdated");
+    }
+}
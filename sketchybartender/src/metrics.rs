@@ -0,0 +1,284 @@
+//! Metrics export: the data behind the bar, for Prometheus, Grafana and scripts
+//!
+//! Handlers record what they collect here as they go. With `listen` set under
+//! `[metrics]`, a small HTTP server serves it as Prometheus text on `/metrics`
+//! and as JSON on `/metrics.json`. CPU, RAM and network counters aren't shown
+//! on the bar, so they are sampled when a request comes in.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use sketchybartender::{error, info, warn};
+
+use crate::events::{self, Event};
+use crate::providers::{self, NetworkInfo, SystemInfo};
+
+/// Interface whose byte counters are exported
+const NETWORK_INTERFACE: &str = "en0";
+
+/// How often a provider ran and how long it took
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProviderStats {
+    pub runs: u64,
+    pub total: Duration,
+    pub last: Duration,
+}
+
+/// The latest values the daemon collected
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// Percentage and whether it's charging
+    pub battery: Option<(u8, bool)>,
+    /// Percentage and whether it's muted
+    pub volume: Option<(u8, bool)>,
+    /// Outdated formulae and casks
+    pub brew: Option<(usize, usize)>,
+    pub teams_notifications: Option<u32>,
+    /// Read and write rates in bytes per second
+    pub disk: Option<(u64, u64)>,
+    pub providers: BTreeMap<&'static str, ProviderStats>,
+}
+
+fn metrics_lock() -> &'static Mutex<Metrics> {
+    static METRICS: OnceLock<Mutex<Metrics>> = OnceLock::new();
+    METRICS.get_or_init(|| Mutex::new(Metrics::default()))
+}
+
+/// Record collected values
+pub fn update(f: impl FnOnce(&mut Metrics)) {
+    if let Ok(mut metrics) = metrics_lock().lock() {
+        f(&mut metrics);
+    }
+}
+
+/// The values collected so far
+pub fn snapshot() -> Metrics {
+    metrics_lock().lock().map(|m| m.clone()).unwrap_or_default()
+}
+
+/// Append one Prometheus metric family
+fn family(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, String)]) {
+    if samples.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP sketchybartender_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE sketchybartender_{} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "sketchybartender_{}{} {}", name, labels, value);
+    }
+}
+
+/// A single unlabelled sample, if the value was collected
+fn sample<T: ToString>(value: Option<T>) -> Vec<(String, String)> {
+    value.map(|value| vec![(String::new(), value.to_string())]).unwrap_or_default()
+}
+
+/// Render the metrics in the Prometheus text format
+fn prometheus(metrics: &Metrics, system: Option<&SystemInfo>, network: Option<&NetworkInfo>) -> String {
+    let mut out = String::new();
+    let flag = |on: bool| u8::from(on);
+
+    let (battery, charging) = (metrics.battery.map(|(p, _)| p), metrics.battery.map(|(_, c)| flag(c)));
+    family(&mut out, "battery_percent", "gauge", "Battery charge", &sample(battery));
+    family(&mut out, "battery_charging", "gauge", "1 while on AC power", &sample(charging));
+    let (volume, muted) = (metrics.volume.map(|(p, _)| p), metrics.volume.map(|(_, m)| flag(m)));
+    family(&mut out, "volume_percent", "gauge", "Output volume", &sample(volume));
+    family(&mut out, "volume_muted", "gauge", "1 while the output is muted", &sample(muted));
+    let brew: Vec<(String, String)> = metrics
+        .brew
+        .map(|(formulae, casks)| {
+            vec![
+                ("{kind=\"formula\"}".to_string(), formulae.to_string()),
+                ("{kind=\"cask\"}".to_string(), casks.to_string()),
+            ]
+        })
+        .unwrap_or_default();
+    family(&mut out, "brew_outdated", "gauge", "Outdated Homebrew packages", &brew);
+    family(&mut out, "teams_notifications", "gauge", "Unread badge count", &sample(metrics.teams_notifications));
+    let (read, write) = (metrics.disk.map(|(r, _)| r), metrics.disk.map(|(_, w)| w));
+    family(&mut out, "disk_read_bytes_per_second", "gauge", "Disk read rate", &sample(read));
+    family(&mut out, "disk_write_bytes_per_second", "gauge", "Disk write rate", &sample(write));
+    family(&mut out, "cpu_percent", "gauge", "CPU usage", &sample(system.map(|s| s.cpu_percentage)));
+    family(&mut out, "memory_percent", "gauge", "RAM usage", &sample(system.map(|s| s.ram_percentage)));
+
+    let interface =
+        |network: &NetworkInfo, value: u64| (format!("{{interface=\"{}\"}}", network.interface), value.to_string());
+    let received: Vec<_> = network.map(|n| interface(n, n.bytes_in)).into_iter().collect();
+    let sent: Vec<_> = network.map(|n| interface(n, n.bytes_out)).into_iter().collect();
+    family(&mut out, "network_receive_bytes_total", "counter", "Bytes received since boot", &received);
+    family(&mut out, "network_transmit_bytes_total", "counter", "Bytes sent since boot", &sent);
+
+    let per_provider = |value: fn(&ProviderStats) -> String| -> Vec<(String, String)> {
+        metrics
+            .providers
+            .iter()
+            .map(|(name, stats)| (format!("{{provider=\"{}\"}}", name), value(stats)))
+            .collect()
+    };
+    family(&mut out, "provider_runs_total", "counter", "Provider runs", &per_provider(|s| s.runs.to_string()));
+    family(
+        &mut out,
+        "provider_duration_seconds_total",
+        "counter",
+        "Time spent in providers",
+        &per_provider(|s| s.total.as_secs_f64().to_string()),
+    );
+    family(
+        &mut out,
+        "provider_last_duration_seconds",
+        "gauge",
+        "Duration of the last provider run",
+        &per_provider(|s| s.last.as_secs_f64().to_string()),
+    );
+    out
+}
+
+/// The collected item values as a JSON object, as `/metrics.json` and `sketchycli status` show them
+pub fn items_json(metrics: &Metrics) -> serde_json::Value {
+    serde_json::json!({
+        "battery": metrics.battery.map(|(percent, charging)| {
+            serde_json::json!({"percent": percent, "charging": charging})
+        }),
+        "volume": metrics.volume.map(|(percent, muted)| serde_json::json!({"percent": percent, "muted": muted})),
+        "brew": metrics.brew.map(|(formulae, casks)| serde_json::json!({"formulae": formulae, "casks": casks})),
+        "teams_notifications": metrics.teams_notifications,
+        "disk": metrics.disk.map(|(read, write)| serde_json::json!({"read": read, "write": write})),
+    })
+}
+
+/// Render the metrics as JSON
+fn json(metrics: &Metrics, system: Option<&SystemInfo>, network: Option<&NetworkInfo>) -> String {
+    let providers: serde_json::Map<String, serde_json::Value> = metrics
+        .providers
+        .iter()
+        .map(|(name, stats)| {
+            let value = serde_json::json!({
+                "runs": stats.runs,
+                "total_seconds": stats.total.as_secs_f64(),
+                "last_seconds": stats.last.as_secs_f64(),
+            });
+            (name.to_string(), value)
+        })
+        .collect();
+
+    let mut values = items_json(metrics);
+    values["cpu_percent"] = serde_json::json!(system.map(|s| s.cpu_percentage));
+    values["memory_percent"] = serde_json::json!(system.map(|s| s.ram_percentage));
+    values["network"] = serde_json::json!(network.map(|n| serde_json::json!({
+        "interface": n.interface,
+        "received_bytes": n.bytes_in,
+        "sent_bytes": n.bytes_out,
+    })));
+    values["providers"] = providers.into();
+    values.to_string()
+}
+
+/// Answer one HTTP request
+fn respond(mut stream: TcpStream) {
+    let mut request = String::new();
+    if BufReader::new(&stream).read_line(&mut request).is_err() {
+        return;
+    }
+
+    let path = match request.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["GET", path, ..] => path.to_string(),
+        _ => String::new(),
+    };
+    let (status, content_type, body) = match path.as_str() {
+        "/metrics" | "/metrics.json" => {
+            let metrics = snapshot();
+            let system = providers::get_system_info();
+            let network = providers::get_network(NETWORK_INTERFACE);
+            if path == "/metrics" {
+                ("200 OK", "text/plain; version=0.0.4", prometheus(&metrics, system.as_ref(), network.as_ref()))
+            } else {
+                ("200 OK", "application/json", json(&metrics, system.as_ref(), network.as_ref()))
+            }
+        }
+        _ => ("404 Not Found", "text/plain", "Try /metrics or /metrics.json\n".to_string()),
+    };
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+}
+
+/// Serve the metrics over HTTP on `address`, once per daemon run
+pub fn serve(address: SocketAddr) {
+    static SERVING: OnceLock<SocketAddr> = OnceLock::new();
+    if let Some(serving) = SERVING.get() {
+        if *serving != address {
+            warn!("[METRICS] Still serving on {}; restart the daemon to move to {}", serving, address);
+        }
+        return;
+    }
+
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("[METRICS] Failed to listen on {}: {}", address, e);
+            return;
+        }
+    };
+    let _ = SERVING.set(address);
+    info!("[METRICS] Serving on http://{}/metrics", address);
+
+    events::listen(|event| {
+        if let Event::Provider { name, elapsed } = event {
+            update(|metrics| {
+                let stats = metrics.providers.entry(*name).or_default();
+                stats.runs += 1;
+                stats.total += *elapsed;
+                stats.last = *elapsed;
+            });
+        }
+    });
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Sampling CPU takes a second; don't make other scrapers wait for it
+            thread::spawn(move || respond(stream));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus() {
+        let mut metrics = Metrics { battery: Some((80, true)), brew: Some((3, 1)), ..Metrics::default() };
+        let stats = ProviderStats { runs: 2, total: Duration::from_millis(5), last: Duration::from_millis(2) };
+        metrics.providers.insert("clock", stats);
+
+        let text = prometheus(&metrics, None, None);
+        assert!(text.contains("# TYPE sketchybartender_battery_percent gauge\nsketchybartender_battery_percent 80\n"));
+        assert!(text.contains("sketchybartender_battery_charging 1\n"));
+        assert!(text.contains("sketchybartender_brew_outdated{kind=\"cask\"} 1\n"));
+        assert!(text.contains("sketchybartender_provider_last_duration_seconds{provider=\"clock\"} 0.002\n"));
+        // Values that weren't collected are left out rather than reported as 0
+        assert!(!text.contains("volume"));
+        assert!(!text.contains("cpu_percent"));
+    }
+
+    #[test]
+    fn test_json() {
+        let metrics = Metrics { volume: Some((40, false)), ..Metrics::default() };
+        let system = SystemInfo { cpu_percentage: 12, ram_percentage: 60 };
+        let value: serde_json::Value = serde_json::from_str(&json(&metrics, Some(&system), None)).unwrap();
+        assert_eq!(value["volume"]["percent"], 40);
+        assert_eq!(value["cpu_percent"], 12);
+        assert!(value["battery"].is_null());
+    }
+}
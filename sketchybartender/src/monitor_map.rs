@@ -1,257 +1,618 @@
-use std::collections::HashMap;
+//! Correlates sketchybar displays with aerospace monitors
+//!
+//! Shared by the daemon, which pins workspace items to displays, and
+//! `sketchycli monitors`, which prints what the mapping was built from.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::process::Command;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
-
-/// Cache entry for display mappings only (workspaces are queried fresh each time)
-#[derive(Debug, Clone)]
-struct CacheEntry {
-    /// Map of Sketchybar display ID -> Aerospace monitor ID
-    mappings: HashMap<u32, u32>,
-    /// When this cache was created
-    created_at: Instant,
+
+use crate::config::DisplaySpec;
+use crate::{info, warn};
+
+/// A display as AppKit reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    /// CGDirectDisplayID (NSScreenNumber)
+    pub number: u32,
+    /// 1-based position in NSScreen.screens
+    pub index: u32,
+    /// CGDisplay UUID, stable across reboots and reconnects
+    pub uuid: String,
+    /// Frame size in points, e.g. `1512x982`
+    pub resolution: String,
+    /// Whether this is the laptop's own display
+    pub builtin: bool,
+    pub name: String,
 }
 
-impl CacheEntry {
-    fn is_expired(&self, ttl: Duration) -> bool {
-        self.created_at.elapsed() > ttl
-    }
+/// A display as `sketchybar --query displays` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SketchybarDisplay {
+    pub arrangement_id: u32,
+    pub direct_display_id: u32,
+    pub uuid: Option<String>,
 }
 
-/// Manages the mapping between Sketchybar displays and Aerospace monitors
-#[derive(Debug)]
-pub struct MonitorMapper {
-    cache: Arc<Mutex<Option<CacheEntry>>>,
-    cache_ttl: Duration,
+/// A monitor as `aerospace list-monitors` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AerospaceMonitor {
+    pub id: u32,
+    /// 1-based position in NSScreen.screens, if aerospace reports it
+    pub screen_index: Option<u32>,
+    pub name: String,
 }
 
-impl MonitorMapper {
-    /// Create a new monitor mapper with a cache TTL (default: 5 minutes)
-    pub fn new() -> Self {
-        Self {
-            cache: Arc::new(Mutex::new(None)),
-            cache_ttl: Duration::from_secs(300), // 5 minutes
+/// Everything the mappings are computed from, probed fresh
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub screens: Vec<Screen>,
+    pub displays: Vec<SketchybarDisplay>,
+    pub monitors: Vec<AerospaceMonitor>,
+    /// Mirroring display -> primary display (CGDirectDisplayIDs)
+    pub mirrors: HashMap<u32, u32>,
+    pub mappings: Mappings,
+    /// Monitors that matched no sketchybar display and fell back to the main display
+    pub fallbacks: Vec<u32>,
+}
+
+/// Which sketchybar display shows each aerospace monitor's workspaces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mappings {
+    /// Aerospace monitor ID -> Sketchybar display ID
+    pub monitors: HashMap<u32, u32>,
+    /// Sketchybar display of the main screen, for monitors that match no display
+    pub main_display: Option<u32>,
+    /// Sketchybar display of the built-in screen; `None` with the lid closed
+    pub builtin_display: Option<u32>,
+    /// Every display sketchybar knows about, including mirroring ones
+    pub displays: Vec<SketchybarDisplay>,
+}
+
+/// How the set of displays changed between two mappings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayChange {
+    Added(SketchybarDisplay),
+    Removed(SketchybarDisplay),
+    /// The menu bar moved to another display
+    MainChanged(SketchybarDisplay),
+}
+
+impl fmt::Display for DisplayChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, display) = match self {
+            DisplayChange::Added(display) => ("display-added", display),
+            DisplayChange::Removed(display) => ("display-removed", display),
+            DisplayChange::MainChanged(display) => ("display-main-changed", display),
+        };
+        write!(
+            f,
+            "{} {} {}",
+            name,
+            display.arrangement_id,
+            display.uuid.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+impl SketchybarDisplay {
+    /// Identity that survives arrangement IDs being renumbered
+    fn key(&self) -> String {
+        match &self.uuid {
+            Some(uuid) => uuid.clone(),
+            None => self.direct_display_id.to_string(),
         }
     }
+}
 
-    /// Get the mapping of Sketchybar display ID -> Aerospace monitor ID
-    pub fn get_mappings(&self) -> HashMap<u32, u32> {
-        // Check cache first
-        if let Ok(cache) = self.cache.lock() {
-            if let Some(entry) = cache.as_ref() {
-                if !entry.is_expired(self.cache_ttl) {
-                    return entry.mappings.clone();
-                }
+impl Mappings {
+    /// Get the display for a monitor, falling back to the main display
+    pub fn display_for(&self, monitor_id: u32) -> Option<u32> {
+        self.monitors.get(&monitor_id).copied().or(self.main_display)
+    }
+
+    /// Get the displays showing workspaces (mirrored displays count once)
+    pub fn display_ids(&self) -> HashSet<u32> {
+        self.monitors.values().copied().collect()
+    }
+
+    /// Resolve a display selector to a value for sketchybar's `display` property
+    ///
+    /// `main` and `all` are resolved to display numbers here; `active` is
+    /// passed through for sketchybar to follow focus itself.
+    pub fn resolve(&self, spec: &DisplaySpec) -> String {
+        let ids = match spec {
+            DisplaySpec::Active => return "active".to_string(),
+            DisplaySpec::Main => self.main_display.into_iter().collect(),
+            DisplaySpec::All => {
+                let mut ids: Vec<u32> = self.display_ids().into_iter().chain(self.main_display).collect();
+                ids.sort_unstable();
+                ids.dedup();
+                ids
             }
+            DisplaySpec::Ids(ids) => ids.clone(),
+        };
+
+        // Nothing known yet; showing the item on the active display beats hiding it
+        if ids.is_empty() {
+            "active".to_string()
+        } else {
+            DisplaySpec::Ids(ids).to_string()
         }
+    }
 
-        // Cache miss or expired - rebuild mappings
-        let mappings = self.build_mappings();
+    /// The sketchybar display with the menu bar
+    fn main(&self) -> Option<&SketchybarDisplay> {
+        let main = self.main_display?;
+        self.displays.iter().find(|display| display.arrangement_id == main)
+    }
 
-        // Update cache
-        if let Ok(mut cache) = self.cache.lock() {
-            *cache = Some(CacheEntry {
-                mappings: mappings.clone(),
-                created_at: Instant::now(),
-            });
+    /// List the displays added and removed since `previous`, and whether the main display moved
+    pub fn changes(&self, previous: &Mappings) -> Vec<DisplayChange> {
+        let keys = |mappings: &Mappings| -> HashSet<String> {
+            mappings.displays.iter().map(SketchybarDisplay::key).collect()
+        };
+        let (before, after) = (keys(previous), keys(self));
+
+        let mut changes: Vec<DisplayChange> = previous
+            .displays
+            .iter()
+            .filter(|display| !after.contains(&display.key()))
+            .map(|display| DisplayChange::Removed(display.clone()))
+            .collect();
+        changes.extend(
+            self.displays
+                .iter()
+                .filter(|display| !before.contains(&display.key()))
+                .map(|display| DisplayChange::Added(display.clone())),
+        );
+
+        if let Some(main) = self.main() {
+            if previous.main().map(SketchybarDisplay::key) != Some(main.key()) {
+                changes.push(DisplayChange::MainChanged(main.clone()));
+            }
         }
 
-        mappings
+        changes
     }
+}
 
+/// Parse `number|index|uuid|resolution|builtin|name` lines from the AppKit query
+fn parse_screens(output: &str) -> Vec<Screen> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(6, '|').collect();
+            if parts.len() != 6 {
+                return None;
+            }
+            Some(Screen {
+                number: parts[0].parse().ok()?,
+                index: parts[1].parse().ok()?,
+                uuid: parts[2].to_string(),
+                resolution: parts[3].to_string(),
+                builtin: parts[4] == "1",
+                name: parts[5].to_string(),
+            })
+        })
+        .collect()
+}
 
-    /// Build the monitor mappings from scratch (display mappings only)
-    fn build_mappings(&self) -> HashMap<u32, u32> {
-        let mut mappings = HashMap::new();
-
-        // Get NSScreen data (NSScreen ID -> NSScreen Name)
-        let nsscreen_map = self.get_nsscreen_map();
-
-        // Get Sketchybar data (Sketchybar ID -> NSScreen ID)
-        let sketchybar_map = self.get_sketchybar_map();
-
-        // Get Aerospace data (Aerospace ID -> NSScreen Name)
-        let aerospace_map = self.get_aerospace_map();
-
-        // Build the mapping: Sketchybar ID -> Aerospace ID
-        for (sb_id, nsscreen_id) in sketchybar_map {
-            // Find NSScreen name for this NSScreen ID
-            if let Some(nsscreen_name) = nsscreen_map.get(&nsscreen_id) {
-                // Find Aerospace ID for this NSScreen name
-                for (aero_id, aero_name) in &aerospace_map {
-                    if aero_name == nsscreen_name {
-                        mappings.insert(sb_id, *aero_id);
-                        break;
-                    }
-                }
+/// Parse `mirror|display|primary` lines into a map of mirroring display -> primary display
+fn parse_mirrors(output: &str) -> HashMap<u32, u32> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("mirror|")?;
+            let (mirror, primary) = rest.split_once('|')?;
+            Some((mirror.parse().ok()?, primary.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse the JSON array from `sketchybar --query displays`
+fn parse_sketchybar_displays(json: &str) -> Vec<SketchybarDisplay> {
+    let value: serde_json::Value = match serde_json::from_str(json) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    value
+        .as_array()
+        .map(|displays| {
+            displays
+                .iter()
+                .filter_map(|display| {
+                    Some(SketchybarDisplay {
+                        arrangement_id: display["arrangement-id"].as_u64()? as u32,
+                        direct_display_id: display["DirectDisplayID"].as_u64()? as u32,
+                        uuid: display["UUID"].as_str().map(|uuid| uuid.to_string()),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse `id|screen-index|name` lines from `aerospace list-monitors`
+fn parse_aerospace_monitors(output: &str) -> Vec<AerospaceMonitor> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(3, '|').collect();
+            if parts.len() != 3 {
+                return None;
             }
-        }
+            Some(AerospaceMonitor {
+                id: parts[0].trim().parse().ok()?,
+                screen_index: parts[1].trim().parse().ok(),
+                name: parts[2].to_string(),
+            })
+        })
+        .collect()
+}
 
-        mappings
+/// Find the sketchybar display showing a screen, following mirrors to the primary
+fn display_for_screen<'a>(
+    screen: &Screen,
+    displays: &[&'a SketchybarDisplay],
+    mirrors: &HashMap<u32, u32>,
+) -> Option<&'a SketchybarDisplay> {
+    match mirrors.get(&screen.number) {
+        Some(primary) => displays.iter().find(|display| display.direct_display_id == *primary).copied(),
+        None => displays
+            .iter()
+            .find(|display| !screen.uuid.is_empty() && display.uuid.as_deref() == Some(screen.uuid.as_str()))
+            .or_else(|| displays.iter().find(|display| display.direct_display_id == screen.number))
+            .copied(),
     }
+}
 
-    /// Get NSScreen ID -> NSScreen Name mapping
-    fn get_nsscreen_map(&self) -> HashMap<u32, String> {
-        let mut map = HashMap::new();
-
-        let swift_code = r#"import AppKit; for screen in NSScreen.screens { if let screenID = screen.deviceDescription[NSDeviceDescriptionKey("NSScreenNumber")] as? NSNumber { print("\(screenID.intValue)|\(screen.localizedName)") } }"#;
-
-        if let Ok(output) = Command::new("swift")
-            .arg("-e")
-            .arg(swift_code)
-            .output()
-        {
-            if output.status.success() {
-                for line in String::from_utf8_lossy(&output.stdout).lines() {
-                    let parts: Vec<&str> = line.splitn(2, '|').collect();
-                    if parts.len() == 2 {
-                        if let Ok(id) = parts[0].parse::<u32>() {
-                            map.insert(id, parts[1].to_string());
-                        }
-                    }
+/// Drop displays that mirror another; they show the primary's content and get no items
+fn primary_displays<'a>(
+    displays: &'a [SketchybarDisplay],
+    mirrors: &HashMap<u32, u32>,
+) -> Vec<&'a SketchybarDisplay> {
+    displays
+        .iter()
+        .filter(|display| !mirrors.contains_key(&display.direct_display_id))
+        .collect()
+}
+
+/// Map Aerospace monitor IDs to the Sketchybar display showing their workspaces
+///
+/// Each aerospace monitor is matched to an AppKit screen by its position in
+/// NSScreen.screens (or by name, if it is unique, on older aerospace versions),
+/// and the screen to a sketchybar display by UUID, falling back to the
+/// CGDirectDisplayID. Nothing depends on the order displays are listed in.
+///
+/// Mirrored displays are collapsed into the primary display of their mirror
+/// set (`mirrors` maps mirroring display -> primary), so every monitor in the
+/// set is pinned to the primary's sketchybar display.
+fn correlate(
+    screens: &[Screen],
+    displays: &[SketchybarDisplay],
+    monitors: &[AerospaceMonitor],
+    mirrors: &HashMap<u32, u32>,
+) -> HashMap<u32, u32> {
+    let mut mappings = HashMap::new();
+    let displays = primary_displays(displays, mirrors);
+
+    for monitor in monitors {
+        let screen = match monitor.screen_index {
+            Some(index) => screens.iter().find(|screen| screen.index == index),
+            None => {
+                let mut named = screens.iter().filter(|screen| screen.name == monitor.name);
+                match (named.next(), named.next()) {
+                    (Some(screen), None) => Some(screen),
+                    _ => None, // Missing or ambiguous (identical monitors)
                 }
             }
-        }
+        };
 
-        map
+        if let Some(display) = screen.and_then(|screen| display_for_screen(screen, &displays, mirrors)) {
+            mappings.insert(monitor.id, display.arrangement_id);
+        }
     }
 
-    /// Get Sketchybar ID -> NSScreen ID mapping
-    fn get_sketchybar_map(&self) -> HashMap<u32, u32> {
-        let mut map = HashMap::new();
-
-        if let Ok(output) = Command::new("sketchybar")
-            .args(["--query", "displays"])
-            .output()
-        {
-            if output.status.success() {
-                // Parse JSON output
-                if let Ok(json) = String::from_utf8(output.stdout) {
-                    // Parse manually to avoid adding serde dependency
-                    // Expected format: [{"arrangement-id":1,"DirectDisplayID":3},...]
-                    // The JSON spans multiple lines, so we need to work with the whole string
-
-                    // Find all arrangement-id and DirectDisplayID pairs
-                    let mut arr_id: Option<u32> = None;
-                    let mut disp_id: Option<u32> = None;
-
-                    for line in json.lines() {
-                        let trimmed = line.trim();
-
-                        // Reset when we see a new object start
-                        if trimmed.starts_with('{') {
-                            arr_id = None;
-                            disp_id = None;
-                        }
-
-                        if let Some(id) = self.extract_json_number(&line, "arrangement-id") {
-                            arr_id = Some(id);
-                        }
-                        if let Some(id) = self.extract_json_number(&line, "DirectDisplayID") {
-                            disp_id = Some(id);
-                        }
-
-                        // When we see object end and have both values, add to map
-                        if trimmed.ends_with("},") || trimmed.ends_with('}') {
-                            if let (Some(a), Some(d)) = (arr_id, disp_id) {
-                                map.insert(a, d);
-                            }
-                        }
-                    }
-                }
+    mappings
+}
+
+/// Find the sketchybar display of the main screen (the first in NSScreen.screens)
+///
+/// Falls back to the lowest arrangement ID when AppKit's screens can't be
+/// matched, e.g. while a display is still being attached.
+fn main_display(
+    screens: &[Screen],
+    displays: &[SketchybarDisplay],
+    mirrors: &HashMap<u32, u32>,
+) -> Option<u32> {
+    let displays = primary_displays(displays, mirrors);
+
+    screens
+        .iter()
+        .find(|screen| screen.index == 1)
+        .and_then(|screen| display_for_screen(screen, &displays, mirrors))
+        .map(|display| display.arrangement_id)
+        .or_else(|| displays.iter().map(|display| display.arrangement_id).min())
+}
+
+/// Find the sketchybar display of the built-in screen, if it is active
+fn builtin_display(
+    screens: &[Screen],
+    displays: &[SketchybarDisplay],
+    mirrors: &HashMap<u32, u32>,
+) -> Option<u32> {
+    let displays = primary_displays(displays, mirrors);
+
+    screens
+        .iter()
+        .find(|screen| screen.builtin)
+        .and_then(|screen| display_for_screen(screen, &displays, mirrors))
+        .map(|display| display.arrangement_id)
+}
+
+/// Pin monitors that matched no display to the main display
+///
+/// Returns the mappings and the IDs of the monitors that fell back.
+fn with_fallbacks(
+    mut matched: HashMap<u32, u32>,
+    monitors: &[AerospaceMonitor],
+    main_display: Option<u32>,
+    builtin_display: Option<u32>,
+) -> (Mappings, Vec<u32>) {
+    let mut fallbacks = Vec::new();
+
+    if let Some(main) = main_display {
+        for monitor in monitors {
+            if let Entry::Vacant(entry) = matched.entry(monitor.id) {
+                entry.insert(main);
+                fallbacks.push(monitor.id);
             }
         }
+    }
+
+    let mappings = Mappings {
+        monitors: matched,
+        main_display,
+        builtin_display,
+        displays: Vec::new(),
+    };
+    (mappings, fallbacks)
+}
+
+/// Cached display mappings (workspaces are queried fresh each time)
+///
+/// The generation is bumped on every invalidation, so mappings built from a
+/// probe that raced with a display change are never stored.
+#[derive(Debug, Default)]
+struct Cache {
+    mappings: Option<Arc<Mappings>>,
+    generation: u64,
+}
 
-        map
+impl Cache {
+    /// Store mappings built during `generation`, unless the cache was invalidated since
+    fn store(&mut self, generation: u64, mappings: &Arc<Mappings>) {
+        if self.generation == generation {
+            self.mappings = Some(Arc::clone(mappings));
+        }
     }
 
-    /// Get Aerospace monitor ID -> NSScreen Name mapping
-    fn get_aerospace_map(&self) -> HashMap<u32, String> {
-        let mut map = HashMap::new();
-
-        if let Ok(output) = Command::new("aerospace")
-            .args(["list-monitors", "--format", "%{monitor-id}|%{monitor-name}"])
-            .output()
-        {
-            if output.status.success() {
-                for line in String::from_utf8_lossy(&output.stdout).lines() {
-                    let parts: Vec<&str> = line.splitn(2, '|').collect();
-                    if parts.len() == 2 {
-                        if let Ok(id) = parts[0].parse::<u32>() {
-                            map.insert(id, parts[1].to_string());
-                        }
-                    }
-                }
+    fn invalidate(&mut self) {
+        self.mappings = None;
+        self.generation += 1;
+    }
+}
+
+/// Manages the mapping between Sketchybar displays and Aerospace monitors
+///
+/// Mappings are built once and shared until a display change or an explicit
+/// `refresh-monitors` invalidates them.
+#[derive(Debug, Default)]
+pub struct MonitorMapper {
+    cache: Mutex<Cache>,
+}
+
+impl MonitorMapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the mapping of Aerospace monitor ID -> Sketchybar display ID
+    pub fn get_mappings(&self) -> Arc<Mappings> {
+        let generation = match self.cache.lock() {
+            Ok(cache) => match &cache.mappings {
+                Some(mappings) => return Arc::clone(mappings),
+                None => cache.generation,
+            },
+            Err(_) => 0,
+        };
+
+        // Probing runs external commands; don't hold the lock meanwhile
+        let mappings = Arc::new(self.build_mappings());
+
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.store(generation, &mappings);
+        }
+
+        mappings
+    }
+
+    /// Build the monitor mappings from scratch (display mappings only)
+    fn build_mappings(&self) -> Mappings {
+        let snapshot = self.snapshot();
+
+        let mut pairs: Vec<_> = snapshot.mirrors.iter().collect();
+        pairs.sort();
+        for (mirror, primary) in pairs {
+            info!(
+                "[MONITOR] Display {} mirrors display {}; pinning its items to display {}",
+                mirror, primary, primary
+            );
+        }
+
+        // Counts differ while a display is being attached or detached
+        for monitor in snapshot.monitors.iter().filter(|m| snapshot.fallbacks.contains(&m.id)) {
+            warn!(
+                "[MONITOR] Aerospace monitor {} ({}) matches no sketchybar display; showing its workspaces on main display {}",
+                monitor.id,
+                monitor.name,
+                snapshot.mappings.main_display.unwrap_or_default()
+            );
+        }
+
+        let used = snapshot.mappings.display_ids();
+        for display in primary_displays(&snapshot.displays, &snapshot.mirrors) {
+            if !used.contains(&display.arrangement_id) {
+                warn!(
+                    "[MONITOR] Sketchybar display {} matches no aerospace monitor; it shows no workspaces",
+                    display.arrangement_id
+                );
             }
         }
 
-        map
+        snapshot.mappings
     }
 
-    /// Extract a number value from a JSON string (simple parser, no dependencies)
-    fn extract_json_number(&self, json: &str, key: &str) -> Option<u32> {
-        let search = format!("\"{}\":", key);
-        if let Some(pos) = json.find(&search) {
-            let after = &json[pos + search.len()..];
-            let num_str: String = after
-                .chars()
-                .skip_while(|c| c.is_whitespace())
-                .take_while(|c| c.is_numeric())
-                .collect();
-            num_str.parse().ok()
-        } else {
-            None
+    /// Probe sketchybar, aerospace and AppKit and correlate them, bypassing the cache
+    pub fn snapshot(&self) -> Snapshot {
+        let (screens, mirrors) = self.get_screens();
+        let displays = self.get_sketchybar_displays();
+        let monitors = self.get_aerospace_monitors();
+
+        let matched = correlate(&screens, &displays, &monitors, &mirrors);
+        let main = main_display(&screens, &displays, &mirrors);
+        let builtin = builtin_display(&screens, &displays, &mirrors);
+        let (mut mappings, fallbacks) = with_fallbacks(matched, &monitors, main, builtin);
+        mappings.displays = displays.clone();
+
+        Snapshot { screens, displays, monitors, mirrors, mappings, fallbacks }
+    }
+
+    /// Get the displays AppKit knows about, and which displays mirror another
+    fn get_screens(&self) -> (Vec<Screen>, HashMap<u32, u32>) {
+        let swift_code = r#"import AppKit; for (index, screen) in NSScreen.screens.enumerated() { if let number = screen.deviceDescription[NSDeviceDescriptionKey("NSScreenNumber")] as? NSNumber { let uuid = CGDisplayCreateUUIDFromDisplayID(number.uint32Value).map { CFUUIDCreateString(nil, $0.takeRetainedValue()) as String } ?? ""; print("\(number.intValue)|\(index + 1)|\(uuid)|\(Int(screen.frame.width))x\(Int(screen.frame.height))|\(CGDisplayIsBuiltin(number.uint32Value))|\(screen.localizedName)") } }; var ids = [CGDirectDisplayID](repeating: 0, count: 32); var count: UInt32 = 0; CGGetOnlineDisplayList(32, &ids, &count); for id in ids.prefix(Int(count)) { let primary = CGDisplayMirrorsDisplay(id); if primary != 0 { print("mirror|\(id)|\(primary)") } }"#;
+
+        match Command::new("swift").arg("-e").arg(swift_code).output() {
+            Ok(output) if output.status.success() => {
+                let output = String::from_utf8_lossy(&output.stdout);
+                (parse_screens(&output), parse_mirrors(&output))
+            }
+            _ => (Vec::new(), HashMap::new()),
         }
     }
 
+    /// Get the displays sketchybar knows about
+    fn get_sketchybar_displays(&self) -> Vec<SketchybarDisplay> {
+        match Command::new("sketchybar").args(["--query", "displays"]).output() {
+            Ok(output) if output.status.success() => {
+                parse_sketchybar_displays(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the monitors aerospace knows about
+    fn get_aerospace_monitors(&self) -> Vec<AerospaceMonitor> {
+        let run = |format: &str| {
+            Command::new("aerospace")
+                .args(["list-monitors", "--format", format])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| parse_aerospace_monitors(&String::from_utf8_lossy(&output.stdout)))
+        };
+
+        // Older aerospace versions don't know the AppKit screen index
+        run("%{monitor-id}|%{monitor-appkit-nsscreen-screens-id}|%{monitor-name}")
+            .or_else(|| run("%{monitor-id}||%{monitor-name}"))
+            .unwrap_or_default()
+    }
+
     /// Invalidate the cache (useful when monitors are added/removed)
-    #[allow(dead_code)]
     pub fn invalidate_cache(&self) {
         if let Ok(mut cache) = self.cache.lock() {
-            *cache = None;
+            cache.invalidate();
         }
     }
 }
 
-impl Default for MonitorMapper {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_cache_expiration() {
-        let entry = CacheEntry {
-            mappings: HashMap::new(),
-            created_at: Instant::now() - Duration::from_secs(400),
-        };
-        assert!(entry.is_expired(Duration::from_secs(300)));
+    fn test_cache_store_and_invalidate() {
+        let mappings = Arc::new(Mappings { monitors: HashMap::from([(1, 1)]), main_display: Some(1), ..Mappings::default() });
+        let mut cache = Cache::default();
+
+        cache.store(0, &mappings);
+        assert_eq!(cache.mappings.as_deref(), Some(&*mappings));
+
+        cache.invalidate();
+        assert!(cache.mappings.is_none());
     }
 
     #[test]
-    fn test_cache_not_expired() {
-        let entry = CacheEntry {
-            mappings: HashMap::new(),
-            created_at: Instant::now(),
+    fn test_resolve_display_spec() {
+        let mappings = Mappings { monitors: HashMap::from([(1, 2), (2, 3)]), main_display: Some(1), ..Mappings::default() };
+        assert_eq!(mappings.resolve(&DisplaySpec::Active), "active");
+        assert_eq!(mappings.resolve(&DisplaySpec::Main), "1");
+        assert_eq!(mappings.resolve(&DisplaySpec::All), "1,2,3");
+        assert_eq!(mappings.resolve(&DisplaySpec::Ids(vec![2])), "2");
+        assert_eq!(Mappings::default().resolve(&DisplaySpec::Main), "active");
+    }
+
+    #[test]
+    fn test_display_changes() {
+        let display = |arrangement_id: u32, uuid: &str| SketchybarDisplay {
+            arrangement_id,
+            direct_display_id: arrangement_id,
+            uuid: Some(uuid.to_string()),
+        };
+        let docked = Mappings {
+            main_display: Some(1),
+            displays: vec![display(1, "UUID-DELL"), display(2, "UUID-BUILTIN")],
+            ..Mappings::default()
         };
-        assert!(!entry.is_expired(Duration::from_secs(300)));
+        let undocked = Mappings {
+            main_display: Some(1),
+            displays: vec![display(1, "UUID-BUILTIN")],
+            ..Mappings::default()
+        };
+
+        // The built-in display is renumbered, not added, when the external one goes away
+        assert_eq!(
+            undocked.changes(&docked),
+            vec![
+                DisplayChange::Removed(display(1, "UUID-DELL")),
+                DisplayChange::MainChanged(display(1, "UUID-BUILTIN")),
+            ]
+        );
+        assert_eq!(DisplayChange::Added(display(2, "UUID-DELL")).to_string(), "display-added 2 UUID-DELL");
+        assert!(docked.changes(&docked).is_empty());
     }
 
     #[test]
-    fn test_json_number_extraction() {
-        let mapper = MonitorMapper::new();
-        let json = r#"{"arrangement-id": 1, "DirectDisplayID": 3}"#;
-        assert_eq!(mapper.extract_json_number(json, "arrangement-id"), Some(1));
-        assert_eq!(mapper.extract_json_number(json, "DirectDisplayID"), Some(3));
+    fn test_cache_drops_stale_build() {
+        let mut cache = Cache::default();
+        let generation = cache.generation;
+
+        // A display change arrives while the mappings are being built
+        cache.invalidate();
+        cache.store(generation, &Arc::new(Mappings::default()));
+        assert!(cache.mappings.is_none());
+    }
+
+    fn screen(number: u32, index: u32, uuid: &str, name: &str) -> Screen {
+        Screen { number, index, uuid: uuid.to_string(), resolution: "1920x1080".to_string(), builtin: name == "Built-in", name: name.to_string() }
     }
 
     #[test]
-    fn test_sketchybar_map_parsing() {
-        let mapper = MonitorMapper::new();
+    fn test_sketchybar_display_parsing() {
         // Simulate actual sketchybar JSON output format (multi-line)
         let json = r#"[
 	{
@@ -265,32 +626,105 @@ mod tests {
 		"UUID":"test2"
 	}
 ]"#;
-        // Test the parsing logic manually
-        let mut map = HashMap::new();
-        let mut arr_id: Option<u32> = None;
-        let mut disp_id: Option<u32> = None;
-
-        for line in json.lines() {
-            let trimmed = line.trim();
-            if trimmed.starts_with('{') {
-                arr_id = None;
-                disp_id = None;
-            }
-            if let Some(id) = mapper.extract_json_number(&line, "arrangement-id") {
-                arr_id = Some(id);
-            }
-            if let Some(id) = mapper.extract_json_number(&line, "DirectDisplayID") {
-                disp_id = Some(id);
-            }
-            if trimmed.ends_with("},") || trimmed.ends_with('}') {
-                if let (Some(a), Some(d)) = (arr_id, disp_id) {
-                    map.insert(a, d);
-                }
-            }
-        }
+        let displays = parse_sketchybar_displays(json);
 
-        assert_eq!(map.get(&1), Some(&3));
-        assert_eq!(map.get(&2), Some(&2));
-        assert_eq!(map.len(), 2);
+        assert_eq!(displays.len(), 2);
+        assert_eq!(displays[0], SketchybarDisplay { arrangement_id: 1, direct_display_id: 3, uuid: Some("test1".to_string()) });
+        assert_eq!(displays[1].direct_display_id, 2);
+        assert!(parse_sketchybar_displays("not json").is_empty());
+    }
+
+    #[test]
+    fn test_screen_and_monitor_parsing() {
+        let screens = parse_screens("1|1|AAAA-1|1920x1080|1|Built-in\n5|2|BBBB-2|1920x1080|0|DELL U2720Q\n");
+        assert_eq!(screens, vec![screen(1, 1, "AAAA-1", "Built-in"), screen(5, 2, "BBBB-2", "DELL U2720Q")]);
+
+        let monitors = parse_aerospace_monitors("1|2|DELL U2720Q\n2||Built-in Retina Display\n");
+        assert_eq!(monitors[0].screen_index, Some(2));
+        assert_eq!(monitors[1].screen_index, None);
+        assert_eq!(monitors[1].name, "Built-in Retina Display");
+    }
+
+    #[test]
+    fn test_correlate_identical_monitors_by_uuid() {
+        // Two identical monitors, listed in a different order by each tool
+        let screens = vec![screen(7, 1, "UUID-LEFT", "DELL U2720Q"), screen(9, 2, "UUID-RIGHT", "DELL U2720Q")];
+        let displays = vec![
+            SketchybarDisplay { arrangement_id: 1, direct_display_id: 9, uuid: Some("UUID-RIGHT".to_string()) },
+            SketchybarDisplay { arrangement_id: 2, direct_display_id: 7, uuid: Some("UUID-LEFT".to_string()) },
+        ];
+        let monitors = vec![
+            AerospaceMonitor { id: 1, screen_index: Some(2), name: "DELL U2720Q".to_string() },
+            AerospaceMonitor { id: 2, screen_index: Some(1), name: "DELL U2720Q".to_string() },
+        ];
+
+        let mappings = correlate(&screens, &displays, &monitors, &HashMap::new());
+        assert_eq!(mappings.get(&1), Some(&1));
+        assert_eq!(mappings.get(&2), Some(&2));
+    }
+
+    #[test]
+    fn test_correlate_mirrored_displays() {
+        assert_eq!(parse_mirrors("1|1||1920x1080|1|Built-in\nmirror|5|1\n"), HashMap::from([(5, 1)]));
+
+        // The projector (5) mirrors the built-in display (1)
+        let screens = vec![screen(1, 1, "UUID-BUILTIN", "Built-in"), screen(5, 2, "UUID-PROJECTOR", "Projector")];
+        let displays = vec![
+            SketchybarDisplay { arrangement_id: 1, direct_display_id: 1, uuid: Some("UUID-BUILTIN".to_string()) },
+            SketchybarDisplay { arrangement_id: 2, direct_display_id: 5, uuid: Some("UUID-PROJECTOR".to_string()) },
+        ];
+        let monitors = vec![
+            AerospaceMonitor { id: 1, screen_index: Some(1), name: "Built-in".to_string() },
+            AerospaceMonitor { id: 2, screen_index: Some(2), name: "Projector".to_string() },
+        ];
+
+        // Both monitors' workspaces go to the built-in display's sketchybar display
+        let mappings = correlate(&screens, &displays, &monitors, &HashMap::from([(5, 1)]));
+        assert_eq!(mappings, HashMap::from([(1, 1), (2, 1)]));
+    }
+
+    #[test]
+    fn test_correlate_by_name_without_screen_index() {
+        let screens = vec![screen(1, 1, "", "Built-in"), screen(7, 2, "", "DELL"), screen(9, 3, "", "DELL")];
+        let displays = vec![
+            SketchybarDisplay { arrangement_id: 1, direct_display_id: 1, uuid: None },
+            SketchybarDisplay { arrangement_id: 2, direct_display_id: 7, uuid: None },
+        ];
+        let monitors = vec![
+            AerospaceMonitor { id: 1, screen_index: None, name: "Built-in".to_string() },
+            AerospaceMonitor { id: 2, screen_index: None, name: "DELL".to_string() },
+        ];
+
+        // Ambiguous names are left unmapped rather than guessed
+        let mappings = correlate(&screens, &displays, &monitors, &HashMap::new());
+        assert_eq!(mappings, HashMap::from([(1, 1)]));
+    }
+
+    #[test]
+    fn test_fallback_to_main_display() {
+        // Hotplug: aerospace already sees the new monitor, sketchybar doesn't yet
+        let screens = vec![screen(5, 1, "UUID-DELL", "DELL"), screen(1, 2, "UUID-BUILTIN", "Built-in")];
+        let displays = vec![
+            SketchybarDisplay { arrangement_id: 1, direct_display_id: 5, uuid: Some("UUID-DELL".to_string()) },
+        ];
+        let monitors = vec![
+            AerospaceMonitor { id: 1, screen_index: Some(1), name: "DELL".to_string() },
+            AerospaceMonitor { id: 2, screen_index: Some(2), name: "Built-in".to_string() },
+        ];
+
+        let matched = correlate(&screens, &displays, &monitors, &HashMap::new());
+        let main = main_display(&screens, &displays, &HashMap::new());
+        assert_eq!(main, Some(1));
+
+        let builtin = builtin_display(&screens, &displays, &HashMap::new());
+        assert_eq!(builtin, None);
+
+        let (mappings, fallbacks) = with_fallbacks(matched, &monitors, main, builtin);
+        assert_eq!(mappings.monitors, HashMap::from([(1, 1), (2, 1)]));
+        assert_eq!(fallbacks, vec![2]);
+
+        // Monitors aerospace reports after the mappings were built also land on the main display
+        assert_eq!(mappings.display_for(3), Some(1));
+        assert_eq!(mappings.display_ids(), HashSet::from([1]));
     }
 }
@@ -0,0 +1,104 @@
+//! The moon's phase, from the time since a known new moon
+//!
+//! Phases are counted in mean synodic months, which puts a new or full moon
+//! within about half a day of the real one: plenty for an icon that changes
+//! every three or four days.
+
+/// Mean days from one new moon to the next
+const SYNODIC_MONTH: f64 = 29.530588853;
+
+/// Unix time of the new moon of 2000-01-06 18:14 UTC
+const NEW_MOON: i64 = 947_182_440;
+
+/// One of the eight phases the icons tell apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+const PHASES: [Phase; 8] = [
+    Phase::New,
+    Phase::WaxingCrescent,
+    Phase::FirstQuarter,
+    Phase::WaxingGibbous,
+    Phase::Full,
+    Phase::WaningGibbous,
+    Phase::LastQuarter,
+    Phase::WaningCrescent,
+];
+
+impl Phase {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Phase::New => "\u{f0f64}",            // nf-md-moon_new
+            Phase::WaxingCrescent => "\u{f0f67}", // nf-md-moon_waxing_crescent
+            Phase::FirstQuarter => "\u{f0f61}",   // nf-md-moon_first_quarter
+            Phase::WaxingGibbous => "\u{f0f68}",  // nf-md-moon_waxing_gibbous
+            Phase::Full => "\u{f0f62}",           // nf-md-moon_full
+            Phase::WaningGibbous => "\u{f0f66}",  // nf-md-moon_waning_gibbous
+            Phase::LastQuarter => "\u{f0f63}",    // nf-md-moon_last_quarter
+            Phase::WaningCrescent => "\u{f0f65}", // nf-md-moon_waning_crescent
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::New => "New moon",
+            Phase::WaxingCrescent => "Waxing crescent",
+            Phase::FirstQuarter => "First quarter",
+            Phase::WaxingGibbous => "Waxing gibbous",
+            Phase::Full => "Full moon",
+            Phase::WaningGibbous => "Waning gibbous",
+            Phase::LastQuarter => "Last quarter",
+            Phase::WaningCrescent => "Waning crescent",
+        }
+    }
+}
+
+/// The moon at some moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Moon {
+    pub phase: Phase,
+    /// Days since the last new moon
+    pub age: f64,
+    /// Percentage of the disc that's lit
+    pub illumination: u8,
+}
+
+/// The moon at a Unix time
+pub fn at(unix: i64) -> Moon {
+    let age = ((unix - NEW_MOON) as f64 / 86400.0).rem_euclid(SYNODIC_MONTH);
+    let fraction = age / SYNODIC_MONTH;
+    // Each phase is centered on its moment, so the new moon spans the last and first sixteenth
+    let phase = PHASES[(fraction * 8.0).round() as usize % 8];
+    let illumination = (1.0 - (fraction * std::f64::consts::TAU).cos()) / 2.0 * 100.0;
+    Moon { phase, age, illumination: illumination.round() as u8 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_at() {
+        // The new moon of the eclipse of 2024-04-08, then first quarter and full moon
+        let new = at(1_712_600_460);
+        assert_eq!((new.phase, new.illumination), (Phase::New, 0));
+        assert!(new.age > 29.0);
+        assert_eq!(at(1_713_208_380).phase, Phase::FirstQuarter);
+        let full = at(1_713_916_140);
+        assert_eq!((full.phase, full.illumination), (Phase::Full, 100));
+
+        assert_eq!(at(NEW_MOON).age, 0.0);
+        assert_eq!(at(NEW_MOON - 86400).phase, Phase::New);
+        assert_eq!(at(NEW_MOON + 5 * 86400).phase, Phase::WaxingCrescent);
+        assert_eq!(at(NEW_MOON + 22 * 86400).phase, Phase::LastQuarter);
+    }
+}
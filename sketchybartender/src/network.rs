@@ -0,0 +1,130 @@
+//! Interface byte counters from getifaddrs
+//!
+//! Each interface's link-level (`AF_LINK`) entry carries an `if_data` with its
+//! byte counters, so one call reads every interface without a subprocess. The
+//! counters are 32 bits and wrap after 4 GiB, which at a sample every few
+//! seconds only loses the one sample that spans the wrap. Outside macOS there
+//! are no counters, and callers fall back to `netstat`.
+
+use std::process::Command;
+
+use crate::providers::NetworkInfo;
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::{c_char, c_int, c_uint, c_void, CStr};
+    use std::ptr;
+
+    use crate::providers::NetworkInfo;
+
+    /// `AF_LINK`
+    const AF_LINK: u8 = 18;
+
+    /// `struct ifaddrs`
+    #[repr(C)]
+    struct IfAddrs {
+        next: *mut IfAddrs,
+        name: *const c_char,
+        _flags: c_uint,
+        addr: *const SockAddr,
+        _netmask: *const SockAddr,
+        _destination: *const SockAddr,
+        data: *const c_void,
+    }
+
+    /// The start of `struct sockaddr`
+    #[repr(C)]
+    struct SockAddr {
+        _len: u8,
+        family: u8,
+    }
+
+    /// The start of `struct if_data`, up to the byte counters
+    #[repr(C)]
+    struct IfData {
+        /// Type and lengths, MTU, metric, baud rate, packet, error and collision counts
+        _head: [u8; 40],
+        ibytes: u32,
+        obytes: u32,
+    }
+
+    extern "C" {
+        fn getifaddrs(addresses: *mut *mut IfAddrs) -> c_int;
+        fn freeifaddrs(addresses: *mut IfAddrs);
+    }
+
+    pub fn counters(matches: &dyn Fn(&str) -> bool) -> Vec<NetworkInfo> {
+        let mut counters = Vec::new();
+        unsafe {
+            let mut addresses = ptr::null_mut();
+            if getifaddrs(&mut addresses) != 0 {
+                return counters;
+            }
+            let mut entry = addresses;
+            while !entry.is_null() {
+                let address = &*entry;
+                entry = address.next;
+                if address.addr.is_null() || address.data.is_null() || (*address.addr).family != AF_LINK {
+                    continue;
+                }
+                let name = CStr::from_ptr(address.name).to_string_lossy();
+                if !matches(&name) {
+                    continue;
+                }
+                let data = &*address.data.cast::<IfData>();
+                counters.push(NetworkInfo {
+                    interface: name.into_owned(),
+                    bytes_in: u64::from(data.ibytes),
+                    bytes_out: u64::from(data.obytes),
+                });
+            }
+            freeifaddrs(addresses);
+        }
+        counters
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    use crate::providers::NetworkInfo;
+
+    pub fn counters(_matches: &dyn Fn(&str) -> bool) -> Vec<NetworkInfo> {
+        Vec::new()
+    }
+}
+
+/// Byte counters of every interface `matches` accepts, empty without getifaddrs
+pub fn counters(matches: impl Fn(&str) -> bool) -> Vec<NetworkInfo> {
+    native::counters(&matches)
+}
+
+/// Parse the interface from `route -n get default` output
+fn parse_route(stdout: &str) -> Option<String> {
+    stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("interface:"))
+        .map(|interface| interface.trim().to_string())
+        .filter(|interface| !interface.is_empty())
+}
+
+/// The interface of the default route, None while offline
+pub fn default_interface() -> Option<String> {
+    let output = Command::new("route").args(["-n", "get", "default"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_route(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_route() {
+        let stdout = "   route to: default\ndestination: default\n       mask: default\n    gateway: 192.168.1.1\n  \
+                      interface: en0\n      flags: <UP,GATEWAY,DONE,STATIC,PRCLONING,GLOBAL>\n";
+        assert_eq!(parse_route(stdout), Some("en0".to_string()));
+        assert_eq!(parse_route("route: writing to routing socket: not in table\n"), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! App name normalization shared by build.rs and the runtime icon lookup
+//!
+//! Names are compared after case folding, whitespace collapsing, and folding
+//! accented Latin letters to their base letter. Folding both precomposed
+//! characters and combining marks means NFC ("é") and NFD ("e\u{301}")
+//! spellings compare equal without shipping full Unicode normalization tables.
+
+/// Normalize an app name or bundle identifier for map lookups
+pub fn normalize_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut pending_space = false;
+
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if is_ignorable(c) {
+            continue;
+        }
+
+        if c.is_whitespace() {
+            pending_space = !out.is_empty();
+            continue;
+        }
+
+        if pending_space {
+            out.push(' ');
+            pending_space = false;
+        }
+
+        match fold_latin(c) {
+            Some(folded) => out.push_str(folded),
+            None => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Normalize a prefix pattern, keeping a trailing space as a word boundary
+pub fn normalize_prefix(prefix: &str) -> String {
+    let mut out = normalize_name(prefix);
+    if !out.is_empty() && prefix.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+    out
+}
+
+/// Characters that never affect which app a name refers to
+fn is_ignorable(c: char) -> bool {
+    matches!(c,
+        // Combining diacritical marks (left behind by NFD input)
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+        // Soft hyphen, zero-width spaces/joiners, byte order mark
+        | '\u{00AD}'
+        | '\u{200B}'..='\u{200D}'
+        | '\u{2060}'
+        | '\u{FEFF}'
+    )
+}
+
+/// Fold a lowercase accented Latin letter to its unaccented form
+fn fold_latin(c: char) -> Option<&'static str> {
+    let folded = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(folded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_and_whitespace() {
+        assert_eq!(normalize_name("Visual Studio Code"), "visual studio code");
+        assert_eq!(normalize_name("  visual   STUDIO\u{00A0}code "), "visual studio code");
+    }
+
+    #[test]
+    fn test_nfc_and_nfd_match() {
+        assert_eq!(normalize_name("Aper\u{00E7}u"), normalize_name("Aperc\u{0327}u"));
+        assert_eq!(normalize_name("Réglages Système"), "reglages systeme");
+    }
+
+    #[test]
+    fn test_invisible_characters_removed() {
+        assert_eq!(normalize_name("System\u{00AD}einstellungen"), "systemeinstellungen");
+        assert_eq!(normalize_name("\u{FEFF}Safari"), "safari");
+    }
+
+    #[test]
+    fn test_prefix_keeps_word_boundary() {
+        assert_eq!(normalize_prefix("JetBrains "), "jetbrains ");
+        assert_eq!(normalize_prefix("Adobe"), "adobe");
+    }
+
+    #[test]
+    fn test_non_latin_preserved() {
+        assert_eq!(normalize_name("系统设置"), "系统设置");
+    }
+}
@@ -0,0 +1,32 @@
+//! macOS notifications for events worth interrupting for
+//!
+//! Notifications are posted with `osascript`'s `display notification`, which
+//! needs no app bundle of our own. Quiet mode holds them back like it hides
+//! notification counts.
+
+use std::process::Command;
+
+use sketchybartender::error;
+
+use crate::providers::applescript_string;
+use crate::quiet;
+
+/// Post a notification, unless quiet mode is on
+pub fn send(title: &str, message: &str) {
+    if quiet::active() {
+        return;
+    }
+
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(message),
+        applescript_string(title)
+    );
+    match Command::new("osascript").args(["-e", &script]).output() {
+        Ok(output) if !output.status.success() => {
+            error!("Failed to post notification: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(_) => {}
+        Err(e) => error!("Failed to run osascript: {}", e),
+    }
+}
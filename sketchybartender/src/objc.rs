@@ -0,0 +1,114 @@
+//! Minimal Objective-C runtime bridge for the AppKit APIs the daemon uses
+//!
+//! Messages go through `objc_msgSend`, cast to the exact signature of each
+//! message before the call, as the runtime requires. Classes whose methods
+//! AppKit calls back into are built at runtime from `extern "C"` functions.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+
+pub type Id = *mut c_void;
+pub type Sel = *const c_void;
+
+mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    use super::{Id, Sel};
+
+    #[link(name = "objc")]
+    extern "C" {
+        pub fn objc_getClass(name: *const c_char) -> Id;
+        pub fn sel_registerName(name: *const c_char) -> Sel;
+        pub fn objc_allocateClassPair(superclass: Id, name: *const c_char, extra_bytes: usize) -> Id;
+        pub fn class_addMethod(class: Id, selector: Sel, imp: *const c_void, types: *const c_char) -> u8;
+        pub fn objc_registerClassPair(class: Id);
+        pub fn objc_autoreleasePoolPush() -> *mut c_void;
+        pub fn objc_autoreleasePoolPop(pool: *mut c_void);
+        pub fn objc_msgSend();
+    }
+}
+
+/// A method of a class built at runtime: selector, implementation and type encoding
+pub type Method = (&'static CStr, *const c_void, &'static CStr);
+
+pub fn class(name: &CStr) -> Id {
+    unsafe { ffi::objc_getClass(name.as_ptr()) }
+}
+
+pub fn selector(name: &CStr) -> Sel {
+    unsafe { ffi::sel_registerName(name.as_ptr()) }
+}
+
+/// `objc_msgSend` as a function of type `F`, which must match the message's signature
+///
+/// # Safety
+/// `F` must be an `unsafe extern "C" fn` taking the receiver and selector
+/// first, followed by the message's arguments.
+pub unsafe fn msg_send<F: Copy>() -> F {
+    let function = ffi::objc_msgSend as unsafe extern "C" fn();
+    std::mem::transmute_copy(&function)
+}
+
+/// Send a message without arguments that returns an object
+///
+/// # Safety
+/// `receiver` must be an object or class that responds to `name` with that signature.
+pub unsafe fn send(receiver: Id, name: &CStr) -> Id {
+    msg_send::<unsafe extern "C" fn(Id, Sel) -> Id>()(receiver, selector(name))
+}
+
+/// Send a message with one object argument that returns an object
+///
+/// # Safety
+/// `receiver` must be an object or class that responds to `name` with that signature.
+pub unsafe fn send_with(receiver: Id, name: &CStr, argument: Id) -> Id {
+    msg_send::<unsafe extern "C" fn(Id, Sel, Id) -> Id>()(receiver, selector(name), argument)
+}
+
+/// An autoreleased NSString
+pub fn nsstring(s: &str) -> Id {
+    let s = CString::new(s.replace('\0', "")).unwrap_or_default();
+    unsafe {
+        msg_send::<unsafe extern "C" fn(Id, Sel, *const c_char) -> Id>()(
+            class(c"NSString"),
+            selector(c"stringWithUTF8String:"),
+            s.as_ptr(),
+        )
+    }
+}
+
+/// Copy an NSString into a Rust string
+///
+/// # Safety
+/// `object` must be null or an NSString.
+pub unsafe fn string(object: Id) -> Option<String> {
+    if object.is_null() {
+        return None;
+    }
+    let utf8 = send(object, c"UTF8String") as *const c_char;
+    (!utf8.is_null()).then(|| CStr::from_ptr(utf8).to_string_lossy().into_owned())
+}
+
+/// Build and register an NSObject subclass with the given methods
+pub fn define_class(name: &CStr, methods: &[Method]) -> Option<Id> {
+    unsafe {
+        let class = ffi::objc_allocateClassPair(class(c"NSObject"), name.as_ptr(), 0);
+        if class.is_null() {
+            return None;
+        }
+        for (name, imp, types) in methods {
+            ffi::class_addMethod(class, selector(name), *imp, types.as_ptr());
+        }
+        ffi::objc_registerClassPair(class);
+        Some(class)
+    }
+}
+
+/// Run `f` inside an autorelease pool, for callbacks on threads without one
+pub fn autoreleasing<T>(f: impl FnOnce() -> T) -> T {
+    unsafe {
+        let pool = ffi::objc_autoreleasePoolPush();
+        let result = f();
+        ffi::objc_autoreleasePoolPop(pool);
+        result
+    }
+}
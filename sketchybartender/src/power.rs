@@ -0,0 +1,400 @@
+//! Power source state and notifications from IOKit
+//!
+//! IOKit signals every power source change (charger plugged in or out, the
+//! percentage moving) through a run loop source, so a dedicated thread adds it
+//! to its own CFRunLoop. The C callback only sends on a channel; another
+//! thread runs the Rust callback, telling it whether the providing power
+//! source changed or only the battery state.
+//!
+//! The same snapshot describes the internal battery: its charge, whether it's
+//! charging or full, and macOS's estimate of the time to empty or full. The
+//! adapter's rating and the battery's voltage and current, for the power going
+//! in or out, come from the adapter details and the `AppleSmartBattery` entry,
+//! which also counts charge cycles and how much the battery holds next to new.
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use sketchybartender::config::PowerSource;
+use sketchybartender::warn;
+
+/// Quiet period after the last notification before the callback runs
+///
+/// Plugging in the charger sends several notifications in quick succession.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// The internal battery's entry in the power source snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatteryDescription {
+    pub current_capacity: i64,
+    pub max_capacity: i64,
+    pub on_ac: bool,
+    pub charging: bool,
+    pub charged: bool,
+    /// Minutes, -1 while macOS is still estimating
+    pub time_to_empty: i64,
+    /// Minutes, -1 while macOS is still estimating
+    pub time_to_full: i64,
+}
+
+/// How worn the internal battery is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatteryHealth {
+    /// macOS's verdict, e.g. `Normal` or `Service Recommended`
+    pub condition: Option<String>,
+    pub cycle_count: Option<u32>,
+    /// What the battery holds when full, in mAh
+    pub max_capacity: Option<u32>,
+    /// What the battery held when new, in mAh
+    pub design_capacity: Option<u32>,
+}
+
+impl BatteryHealth {
+    /// Full charge capacity as a percentage of the design capacity, which System Settings calls maximum capacity
+    pub fn capacity_percentage(&self) -> Option<u32> {
+        let design = self.design_capacity.filter(|design| *design > 0)?;
+        Some((self.max_capacity? * 100 + design / 2) / design)
+    }
+}
+
+/// Sending end of the event channel, set by `listen`
+static EVENTS: OnceLock<Mutex<Sender<()>>> = OnceLock::new();
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    /// kCFStringEncodingUTF8
+    pub const UTF8: u32 = 0x0800_0100;
+
+    pub type PowerSourceCallback = extern "C" fn(context: *mut c_void);
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        pub fn IOPSNotificationCreateRunLoopSource(callback: PowerSourceCallback, context: *mut c_void) -> *mut c_void;
+        pub fn IOPSCopyPowerSourcesInfo() -> *const c_void;
+        pub fn IOPSGetProvidingPowerSourceType(snapshot: *const c_void) -> *const c_void;
+        pub fn IOPSCopyPowerSourcesList(snapshot: *const c_void) -> *const c_void;
+        pub fn IOPSGetPowerSourceDescription(snapshot: *const c_void, source: *const c_void) -> *const c_void;
+        pub fn IOPSCopyExternalPowerAdapterDetails() -> *const c_void;
+        pub fn IOServiceMatching(name: *const c_char) -> *mut c_void;
+        pub fn IOServiceGetMatchingService(main_port: u32, matching: *mut c_void) -> u32;
+        pub fn IORegistryEntryCreateCFProperty(
+            entry: u32,
+            key: *const c_void,
+            allocator: *const c_void,
+            options: u32,
+        ) -> *const c_void;
+        pub fn IOObjectRelease(object: u32) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub static kCFRunLoopDefaultMode: *const c_void;
+
+        pub fn CFRunLoopGetCurrent() -> *mut c_void;
+        pub fn CFRunLoopAddSource(run_loop: *mut c_void, source: *mut c_void, mode: *const c_void);
+        pub fn CFRunLoopRun();
+        pub fn CFStringGetCString(string: *const c_void, buffer: *mut c_char, size: isize, encoding: u32) -> u8;
+        pub fn CFStringCreateWithCString(allocator: *const c_void, string: *const c_char, encoding: u32)
+            -> *const c_void;
+        pub fn CFArrayGetCount(array: *const c_void) -> isize;
+        pub fn CFArrayGetValueAtIndex(array: *const c_void, index: isize) -> *const c_void;
+        pub fn CFDictionaryGetValue(dictionary: *const c_void, key: *const c_void) -> *const c_void;
+        pub fn CFNumberGetValue(number: *const c_void, kind: isize, value: *mut c_void) -> u8;
+        pub fn CFBooleanGetValue(boolean: *const c_void) -> u8;
+        pub fn CFRelease(object: *const c_void);
+    }
+
+    /// kCFNumberSInt64Type
+    pub const SINT64: isize = 4;
+    /// kIOMainPortDefault
+    pub const MAIN_PORT: u32 = 0;
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::{c_void, CStr};
+    use std::thread;
+
+    use sketchybartender::config::PowerSource;
+    use sketchybartender::error;
+
+    use super::{ffi, BatteryDescription, BatteryHealth, EVENTS};
+
+    extern "C" fn on_power_change(_context: *mut c_void) {
+        if let Some(Ok(events)) = EVENTS.get().map(|events| events.lock()) {
+            let _ = events.send(());
+        }
+    }
+
+    /// The power source the Mac is running on
+    pub fn source() -> Option<PowerSource> {
+        let mut buffer = [0 as std::ffi::c_char; 64];
+        let name = unsafe {
+            let snapshot = ffi::IOPSCopyPowerSourcesInfo();
+            if snapshot.is_null() {
+                return None;
+            }
+            // The type belongs to the snapshot, so it's copied out before the release
+            let kind = ffi::IOPSGetProvidingPowerSourceType(snapshot);
+            let copied = !kind.is_null()
+                && ffi::CFStringGetCString(kind, buffer.as_mut_ptr(), buffer.len() as isize, ffi::UTF8) != 0;
+            ffi::CFRelease(snapshot);
+            if !copied {
+                return None;
+            }
+            CStr::from_ptr(buffer.as_ptr())
+        };
+
+        match name.to_str().ok()? {
+            "AC Power" => Some(PowerSource::Ac),
+            "Battery Power" => Some(PowerSource::Battery),
+            _ => None,
+        }
+    }
+
+    /// A CFString key, released on drop
+    struct Key(*const c_void);
+
+    impl Key {
+        fn new(name: &CStr) -> Self {
+            Self(unsafe { ffi::CFStringCreateWithCString(std::ptr::null(), name.as_ptr(), ffi::UTF8) })
+        }
+    }
+
+    impl Drop for Key {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                unsafe { ffi::CFRelease(self.0) };
+            }
+        }
+    }
+
+    fn value(dictionary: *const c_void, key: &CStr) -> *const c_void {
+        let key = Key::new(key);
+        if key.0.is_null() {
+            return std::ptr::null();
+        }
+        unsafe { ffi::CFDictionaryGetValue(dictionary, key.0) }
+    }
+
+    fn number(number: *const c_void) -> Option<i64> {
+        let mut value: i64 = 0;
+        let read = !number.is_null()
+            && unsafe { ffi::CFNumberGetValue(number, ffi::SINT64, &mut value as *mut i64 as *mut c_void) } != 0;
+        read.then_some(value)
+    }
+
+    fn flag(dictionary: *const c_void, key: &CStr) -> bool {
+        let boolean = value(dictionary, key);
+        !boolean.is_null() && unsafe { ffi::CFBooleanGetValue(boolean) } != 0
+    }
+
+    fn string(string: *const c_void) -> Option<String> {
+        let mut buffer = [0 as std::ffi::c_char; 64];
+        let copied = !string.is_null()
+            && unsafe { ffi::CFStringGetCString(string, buffer.as_mut_ptr(), buffer.len() as isize, ffi::UTF8) } != 0;
+        copied.then(|| unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().into_owned())
+    }
+
+    /// Read the internal battery's entry of a power source snapshot with `read`, None on Macs without one
+    fn with_internal_battery<T>(read: impl FnOnce(*const c_void) -> T) -> Option<T> {
+        unsafe {
+            let snapshot = ffi::IOPSCopyPowerSourcesInfo();
+            if snapshot.is_null() {
+                return None;
+            }
+            let sources = ffi::IOPSCopyPowerSourcesList(snapshot);
+            let mut result = None;
+            if !sources.is_null() {
+                // Descriptions belong to the snapshot, so they're read before the release
+                let battery = (0..ffi::CFArrayGetCount(sources))
+                    .map(|index| ffi::CFArrayGetValueAtIndex(sources, index))
+                    .map(|source| ffi::IOPSGetPowerSourceDescription(snapshot, source))
+                    .find(|description| {
+                        !description.is_null()
+                            && string(value(*description, c"Type")).as_deref() == Some("InternalBattery")
+                    });
+                result = battery.map(read);
+                ffi::CFRelease(sources);
+            }
+            ffi::CFRelease(snapshot);
+            result
+        }
+    }
+
+    /// The internal battery's description, None on Macs without one
+    pub fn battery() -> Option<BatteryDescription> {
+        with_internal_battery(|description| BatteryDescription {
+            current_capacity: number(value(description, c"Current Capacity")).unwrap_or(0),
+            max_capacity: number(value(description, c"Max Capacity")).unwrap_or(100),
+            on_ac: string(value(description, c"Power Source State")).as_deref() == Some("AC Power"),
+            charging: flag(description, c"Is Charging"),
+            charged: flag(description, c"Is Charged"),
+            time_to_empty: number(value(description, c"Time to Empty")).unwrap_or(-1),
+            time_to_full: number(value(description, c"Time to Full Charge")).unwrap_or(-1),
+        })
+    }
+
+    /// The connected adapter's rating in watts
+    pub fn adapter_watts() -> Option<u32> {
+        unsafe {
+            let details = ffi::IOPSCopyExternalPowerAdapterDetails();
+            if details.is_null() {
+                return None;
+            }
+            let watts = number(value(details, c"Watts"));
+            ffi::CFRelease(details);
+            watts.and_then(|watts| u32::try_from(watts).ok())
+        }
+    }
+
+    /// Numbers from the `AppleSmartBattery` registry entry, None for each it doesn't have
+    fn smart_battery<const N: usize>(names: [&CStr; N]) -> Option<[Option<i64>; N]> {
+        unsafe {
+            let service = ffi::IOServiceGetMatchingService(
+                ffi::MAIN_PORT,
+                ffi::IOServiceMatching(c"AppleSmartBattery".as_ptr()),
+            );
+            if service == 0 {
+                return None;
+            }
+            let numbers = names.map(|name| {
+                let key = Key::new(name);
+                let value = ffi::IORegistryEntryCreateCFProperty(service, key.0, std::ptr::null(), 0);
+                let number = number(value);
+                if !value.is_null() {
+                    ffi::CFRelease(value);
+                }
+                number
+            });
+            ffi::IOObjectRelease(service);
+            Some(numbers)
+        }
+    }
+
+    /// Voltage in mV and current in mA of the battery, negative while it discharges
+    pub fn battery_flow() -> Option<(i64, i64)> {
+        let [voltage, amperage] = smart_battery([c"Voltage", c"Amperage"])?;
+        voltage.zip(amperage)
+    }
+
+    /// Cycle count and capacities from `AppleSmartBattery`, and the condition from the power source description
+    pub fn health() -> Option<BatteryHealth> {
+        let [cycles, raw_max, max, design] =
+            smart_battery([c"CycleCount", c"AppleRawMaxCapacity", c"MaxCapacity", c"DesignCapacity"])?;
+        let unsigned = |number: Option<i64>| number.and_then(|number| u32::try_from(number).ok());
+        // Apple silicon reports MaxCapacity as a percentage and the mAh as AppleRawMaxCapacity
+        let max_capacity = unsigned(raw_max).or(unsigned(max).filter(|max| *max > 100));
+        // BatteryHealthCondition is only there when the battery needs attention
+        let condition = with_internal_battery(|description| {
+            let condition = string(value(description, c"BatteryHealthCondition"));
+            condition.or_else(|| string(value(description, c"BatteryHealth")))
+        })
+        .flatten();
+        Some(BatteryHealth {
+            condition: condition.map(|condition| if condition == "Good" { "Normal".to_string() } else { condition }),
+            cycle_count: unsigned(cycles),
+            max_capacity,
+            design_capacity: unsigned(design),
+        })
+    }
+
+    /// Register for power source notifications on a dedicated run-loop thread
+    pub fn register() {
+        thread::spawn(|| {
+            unsafe {
+                let source = ffi::IOPSNotificationCreateRunLoopSource(on_power_change, std::ptr::null_mut());
+                if source.is_null() {
+                    error!("Failed to register power source notifications");
+                    return;
+                }
+                ffi::CFRunLoopAddSource(ffi::CFRunLoopGetCurrent(), source, ffi::kCFRunLoopDefaultMode);
+
+                // Never returns while the source is registered
+                ffi::CFRunLoopRun();
+            }
+        });
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    use sketchybartender::config::PowerSource;
+
+    use super::{BatteryDescription, BatteryHealth};
+
+    pub fn source() -> Option<PowerSource> {
+        None
+    }
+
+    pub fn health() -> Option<BatteryHealth> {
+        None
+    }
+
+    pub fn battery() -> Option<BatteryDescription> {
+        None
+    }
+
+    pub fn adapter_watts() -> Option<u32> {
+        None
+    }
+
+    pub fn battery_flow() -> Option<(i64, i64)> {
+        None
+    }
+
+    pub fn register() {}
+}
+
+/// The power source the Mac is running on, or None on a UPS or without IOKit
+pub fn source() -> Option<PowerSource> {
+    native::source()
+}
+
+/// The internal battery as IOKit describes it, or None without IOKit or a battery
+pub fn battery() -> Option<BatteryDescription> {
+    native::battery()
+}
+
+/// The power adapter's rating in watts, None on battery or without IOKit
+pub fn adapter_watts() -> Option<u32> {
+    native::adapter_watts()
+}
+
+/// The internal battery's condition, cycle count and capacities, or None without IOKit or a battery
+pub fn health() -> Option<BatteryHealth> {
+    native::health()
+}
+
+/// Power into the battery in watts, negative while it discharges
+pub fn battery_watts() -> Option<f32> {
+    native::battery_flow().map(|(millivolts, milliamps)| (millivolts * milliamps) as f32 / 1_000_000.0)
+}
+
+/// Run `callback` on a background thread when the power source or battery state changes
+///
+/// The callback gets whether the power source changed, as opposed to only the
+/// battery percentage or charge state.
+pub fn listen(callback: impl Fn(bool) + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+    if EVENTS.set(Mutex::new(sender)).is_err() {
+        warn!("Power source listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut last_source = source();
+        while receiver.recv().is_ok() {
+            while receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+            let current = source();
+            callback(current != last_source);
+            last_source = current;
+        }
+    });
+
+    native::register();
+}
@@ -0,0 +1,150 @@
+//! Resolving helper processes to the app that owns them
+//!
+//! Electron and Chromium apps show up as "Code Helper (Renderer)" or
+//! "Google Chrome Helper" for some windows. Helpers usually live inside the
+//! parent's bundle, so the outermost .app in their executable path is the
+//! parent; standalone helpers fall back to the parent process. The same
+//! executable path gives the bundle ID of apps reported without one.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+use sketchybartender::normalize::normalize_name;
+
+use crate::aerospace::App;
+use crate::bundles;
+
+/// Whether an app looks like a helper process of another app
+pub fn is_helper(app: &App) -> bool {
+    normalize_name(&app.name).contains("helper")
+        || app
+            .bundle_id
+            .as_deref()
+            .is_some_and(|id| normalize_name(id).contains(".helper"))
+}
+
+/// Parent apps by helper PID, so each helper is only inspected once
+fn parents() -> &'static Mutex<HashMap<u32, Option<App>>> {
+    static PARENTS: OnceLock<Mutex<HashMap<u32, Option<App>>>> = OnceLock::new();
+    PARENTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The app that owns a helper process
+pub fn parent_app(pid: u32) -> Option<App> {
+    if let Ok(cache) = parents().lock() {
+        if let Some(parent) = cache.get(&pid) {
+            return parent.clone();
+        }
+    }
+
+    let parent = resolve_parent(pid);
+
+    if let Ok(mut cache) = parents().lock() {
+        cache.insert(pid, parent.clone());
+    }
+    parent
+}
+
+fn resolve_parent(pid: u32) -> Option<App> {
+    let path = ps(pid, "comm")?;
+    let bundles = bundle_paths(&path);
+
+    // Nested helper: Parent.app/Contents/Frameworks/Helper.app/...
+    if bundles.len() >= 2 {
+        return app_for_bundle(bundles[0]);
+    }
+
+    // Standalone helper: ask the process that launched it
+    let ppid: u32 = ps(pid, "ppid")?.parse().ok()?;
+    if ppid <= 1 {
+        return None;
+    }
+    let parent_path = ps(ppid, "comm")?;
+    bundle_paths(&parent_path).first().and_then(|bundle| app_for_bundle(bundle))
+}
+
+/// Bundle IDs by PID, so each process is only inspected once
+fn bundle_ids() -> &'static Mutex<HashMap<u32, Option<String>>> {
+    static BUNDLE_IDS: OnceLock<Mutex<HashMap<u32, Option<String>>>> = OnceLock::new();
+    BUNDLE_IDS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The bundle ID of the app a process runs from, read from the bundle's Info.plist
+pub fn bundle_id(pid: u32) -> Option<String> {
+    if let Ok(cache) = bundle_ids().lock() {
+        if let Some(bundle_id) = cache.get(&pid) {
+            return bundle_id.clone();
+        }
+    }
+
+    let bundle_id = ps(pid, "comm").and_then(|path| {
+        let bundle = *bundle_paths(&path).last()?;
+        bundles::info(Path::new(bundle), "CFBundleIdentifier")
+    });
+
+    if let Ok(mut cache) = bundle_ids().lock() {
+        cache.insert(pid, bundle_id.clone());
+    }
+    bundle_id
+}
+
+/// A single `ps` column for a process
+fn ps(pid: u32, column: &str) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-o", &format!("{}=", column), "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+/// Every `.app` bundle containing an executable, outermost first
+fn bundle_paths(executable: &str) -> Vec<&str> {
+    executable
+        .match_indices(".app/")
+        .map(|(i, _)| &executable[..i + ".app".len()])
+        .collect()
+}
+
+/// Describe the app in a bundle directory
+fn app_for_bundle(bundle: &str) -> Option<App> {
+    let bundle = Path::new(bundle);
+    let name = bundle.file_stem()?.to_string_lossy().into_owned();
+    let bundle_id = bundles::info(bundle, "CFBundleIdentifier");
+
+    Some(App { name, bundle_id, ..App::default() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_paths() {
+        let path = "/Applications/Visual Studio Code.app/Contents/Frameworks/Code Helper (Renderer).app/Contents/MacOS/Code Helper (Renderer)";
+        assert_eq!(
+            bundle_paths(path),
+            vec![
+                "/Applications/Visual Studio Code.app",
+                "/Applications/Visual Studio Code.app/Contents/Frameworks/Code Helper (Renderer).app",
+            ]
+        );
+        assert!(bundle_paths("/usr/bin/helper").is_empty());
+    }
+
+    #[test]
+    fn test_is_helper() {
+        let helper = App { name: "Code Helper (Renderer)".to_string(), ..App::default() };
+        let by_bundle = App {
+            name: "Renderer".to_string(),
+            bundle_id: Some("com.google.Chrome.helper".to_string()),
+            ..App::default()
+        };
+        assert!(is_helper(&helper));
+        assert!(is_helper(&by_bundle));
+        assert!(!is_helper(&App { name: "Safari".to_string(), ..App::default() }));
+    }
+}
@@ -0,0 +1,143 @@
+//! JSON requests and replies between sketchycli and the daemon
+//!
+//! A line on the socket that starts with `{` is a request:
+//! `{"v":1,"id":7,"command":"profile","args":["work"]}`. The daemon answers
+//! every request, once its handler has finished, with a reply carrying the
+//! same id: `{"v":1,"id":7,"ok":true,"data":null}`, or `"ok":false` with an
+//! `error`. Queries such as `status` put what they found in `data`. Plain text
+//! lines keep working, so aerospace and sketchybar hooks stay fire-and-forget.
+
+use serde_json::{json, Value};
+
+/// Protocol version; requests for another version are refused rather than guessed at
+pub const VERSION: u64 = 1;
+
+/// A command for the daemon, e.g. `profile work`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    /// Chosen by the client and echoed in the reply
+    pub id: u64,
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl Request {
+    pub fn new(id: u64, command: &str, args: &[&str]) -> Self {
+        Self { id, command: command.to_string(), args: args.iter().map(|arg| arg.to_string()).collect() }
+    }
+
+    /// The request as the text message the daemon dispatches
+    pub fn message(&self) -> String {
+        let mut parts = vec![self.command.as_str()];
+        parts.extend(self.args.iter().map(String::as_str));
+        parts.join(" ")
+    }
+
+    pub fn to_json(&self) -> String {
+        json!({"v": VERSION, "id": self.id, "command": self.command, "args": self.args}).to_string()
+    }
+
+    /// Parse a request line; the error is the reply to send back
+    pub fn parse(line: &str) -> Result<Self, Response> {
+        let value: Value =
+            serde_json::from_str(line).map_err(|e| Response::error(None, format!("Invalid request: {}", e)))?;
+        let id = value.get("id").and_then(Value::as_u64);
+        let fail = |message: String| Response::error(id, message);
+
+        match value.get("v").and_then(Value::as_u64) {
+            Some(VERSION) => {}
+            Some(v) => return Err(fail(format!("Unsupported protocol version {}, expected {}", v, VERSION))),
+            None => return Err(fail("Request without a protocol version \"v\"".to_string())),
+        }
+        let id = id.ok_or_else(|| fail("Request without an \"id\"".to_string()))?;
+        let command = match value.get("command").and_then(Value::as_str) {
+            Some(command) if !command.trim().is_empty() => command.trim().to_string(),
+            _ => return Err(fail("Request without a \"command\"".to_string())),
+        };
+        let args = match value.get("args") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(args)) => args
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| fail("\"args\" must be strings".to_string()))?,
+            Some(_) => return Err(fail("\"args\" must be a list".to_string())),
+        };
+
+        Ok(Self { id, command, args })
+    }
+}
+
+/// The daemon's answer to a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// The request's id; `None` when the request was too broken to tell
+    pub id: Option<u64>,
+    /// What a query found, or `Value::Null`; the error message when it failed
+    pub result: Result<Value, String>,
+}
+
+impl Response {
+    pub fn ok(id: u64, data: Value) -> Self {
+        Self { id: Some(id), result: Ok(data) }
+    }
+
+    pub fn error(id: Option<u64>, message: impl Into<String>) -> Self {
+        Self { id, result: Err(message.into()) }
+    }
+
+    pub fn to_json(&self) -> String {
+        match &self.result {
+            Ok(data) => json!({"v": VERSION, "id": self.id, "ok": true, "data": data}),
+            Err(error) => json!({"v": VERSION, "id": self.id, "ok": false, "error": error}),
+        }
+        .to_string()
+    }
+
+    /// Parse a reply line from the daemon
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(line).map_err(|e| format!("Invalid reply: {}", e))?;
+        let id = value.get("id").and_then(Value::as_u64);
+        let result = match value.get("ok").and_then(Value::as_bool) {
+            Some(true) => Ok(value.get("data").cloned().unwrap_or(Value::Null)),
+            Some(false) => Err(value.get("error").and_then(Value::as_str).unwrap_or("Unknown error").to_string()),
+            None => return Err("Reply without \"ok\"".to_string()),
+        };
+        Ok(Self { id, result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let request = Request::new(7, "profile", &["work"]);
+        assert_eq!(request.message(), "profile work");
+        assert_eq!(Request::parse(&request.to_json()), Ok(request));
+
+        let response = Response::ok(7, json!({"brew": 3}));
+        assert_eq!(Response::parse(&response.to_json()), Ok(response));
+        let response = Response::error(Some(7), "Unknown profile: wrk");
+        assert_eq!(Response::parse(&response.to_json()), Ok(response));
+
+        // Arguments are optional
+        let request = Request::parse(r#"{"v":1,"id":2,"command":"status"}"#).unwrap();
+        assert_eq!(request.message(), "status");
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        let error = |line: &str| Request::parse(line).unwrap_err();
+
+        assert_eq!(error("{nope").id, None);
+        let future = error(r#"{"v":2,"id":3,"command":"status"}"#);
+        assert_eq!(future.id, Some(3));
+        assert!(future.result.unwrap_err().contains("version 2"));
+        assert!(error(r#"{"id":3,"command":"status"}"#).result.unwrap_err().contains("version"));
+        assert!(error(r#"{"v":1,"command":"status"}"#).result.unwrap_err().contains("id"));
+        assert!(error(r#"{"v":1,"id":3,"command":" "}"#).result.unwrap_err().contains("command"));
+        assert!(error(r#"{"v":1,"id":3,"command":"quiet","args":[true]}"#).result.unwrap_err().contains("strings"));
+    }
+}
@@ -1,67 +1,391 @@
+use std::collections::BTreeMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::process::Command;
+use std::io;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+use sketchybartender::config::{
+    BadgeAppConfig, BatteryConfig, BatteryLevel, BrewConfig, ClockFormat, CryptoConfig, CurrencyPair, CustomItem,
+    GitlabConfig, ItemStyle, JiraConfig, Level, LinearConfig, NetworkInterface, ReviewsConfig, SunConfig,
+    ThermalState, WeatherConfig, WeatherUnits,
+};
+use sketchybartender::{error, info, warn};
+
+use crate::{
+    appearance, brightness, calendar, cpu, crypto, disk, events, exchange, feeds, focus_mode, github, gitlab, jira,
+    linear, load, low_power, memory, moon, network, power, rules, runtime, scripting, settings, smc, sun, thermal,
+    volume, weather, wifi, zoom,
+};
+
+/// An item that collects its data on a timer and renders it
+///
+/// Registered providers get a timer and the `refresh <name>` message without
+/// changes to `main.rs` or the message dispatch; see [`register`].
+pub trait Provider: Send + Sync + 'static {
+    /// What `refresh` collects and `render` shows
+    type Info;
+
+    /// The item's name, as in `sketchybarrc` and the config
+    fn name(&self) -> &'static str;
+
+    /// Time until the next refresh, read again after every run so config changes apply
+    fn interval(&self) -> Duration;
+
+    /// Collect the latest data; `Ok(None)` when there is nothing to show, `Err` when the tool behind it
+    /// failed, which backs the provider off
+    fn refresh(&self) -> Result<Option<Self::Info>, String>;
+
+    /// Show the collected data on the bar and wherever else it goes
+    fn render(&self, info: &Self::Info) -> io::Result<()>;
+
+    /// The collected data as a JSON object, kept for `sketchycli query`
+    fn describe(&self, info: &Self::Info) -> Value;
+}
+
+/// A provider whatever its `Info`, as kept in the registry
+pub trait Refresh: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Time until the next refresh, lengthened while the provider keeps failing
+    fn interval(&self) -> Duration;
+    /// Collect and render, unless the item is disabled
+    fn run(&self);
+}
+
+impl<P: Provider> Refresh for P {
+    fn name(&self) -> &'static str {
+        Provider::name(self)
+    }
+
+    fn interval(&self) -> Duration {
+        health(Provider::name(self)).delay(Provider::interval(self))
+    }
+
+    fn run(&self) {
+        let name = Provider::name(self);
+        if !settings::enabled(name) {
+            return;
+        }
+        let info = match events::timed(name, || self.refresh()) {
+            Ok(info) => {
+                let failures = record_success(name);
+                if failures > 0 {
+                    info!("[PROVIDER] {} recovered after {} failure(s)", name, failures);
+                }
+                info
+            }
+            Err(e) => {
+                let health = record_failure(name, e);
+                let retry = health.delay(Provider::interval(self)).as_secs();
+                let degraded = if health.degraded() { ", degraded" } else { "" };
+                let error = health.last_error.unwrap_or_default();
+                warn!(
+                    "[PROVIDER] {} failed {} time(s) in a row{}, next try in {}s: {}",
+                    name, health.failures, degraded, retry, error
+                );
+                return;
+            }
+        };
+        let Some(info) = info else {
+            return;
+        };
+        record(name, self.describe(&info));
+        if let Err(e) = self.render(&info) {
+            error!("Failed to update {}: {}", name, e);
+        }
+    }
+}
+
+fn registry() -> &'static RwLock<Vec<Arc<dyn Refresh>>> {
+    static REGISTRY: OnceLock<RwLock<Vec<Arc<dyn Refresh>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Add a provider, replacing one registered under the same name
+pub fn register(provider: impl Provider) {
+    if let Ok(mut providers) = registry().write() {
+        providers.retain(|registered| registered.name() != Provider::name(&provider));
+        providers.push(Arc::new(provider));
+    }
+}
+
+/// The registered providers, in the order they were registered
+pub fn registered() -> Vec<Arc<dyn Refresh>> {
+    registry().read().map(|providers| providers.clone()).unwrap_or_default()
+}
+
+/// The provider behind an item, if it has one
+pub fn find(name: &str) -> Option<Arc<dyn Refresh>> {
+    registered().into_iter().find(|provider| provider.name() == name)
+}
+
+/// What an item showed at its last refresh
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastResult {
+    pub at: SystemTime,
+    pub value: Value,
+}
+
+fn results_lock() -> &'static Mutex<BTreeMap<String, LastResult>> {
+    static RESULTS: OnceLock<Mutex<BTreeMap<String, LastResult>>> = OnceLock::new();
+    RESULTS.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Keep what an item showed, for `sketchycli query`; providers are recorded as they run
+pub fn record(item: &str, value: Value) {
+    if let Ok(mut results) = results_lock().lock() {
+        results.insert(item.to_string(), LastResult { at: SystemTime::now(), value });
+    }
+}
+
+/// The last result of every item that has refreshed, by item name
+pub fn last_results() -> BTreeMap<String, LastResult> {
+    results_lock().lock().map(|results| results.clone()).unwrap_or_default()
+}
+
+/// Failures in a row after which a provider counts as degraded
+const DEGRADED_AFTER: u32 = 3;
+
+/// Longest a failing provider waits between tries, unless its own interval is longer
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// How a provider's recent refreshes went
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Health {
+    /// Refreshes in a row that failed, 0 after a success
+    pub failures: u32,
+    /// Why the last failed refresh failed
+    pub last_error: Option<String>,
+}
+
+impl Health {
+    /// Whether the provider has failed often enough in a row that its item is likely stale
+    pub fn degraded(&self) -> bool {
+        self.failures >= DEGRADED_AFTER
+    }
+
+    /// `interval` doubled for every failure in a row, up to [`MAX_BACKOFF`]
+    pub fn delay(&self, interval: Duration) -> Duration {
+        if self.failures == 0 {
+            return interval;
+        }
+        interval.saturating_mul(2u32.saturating_pow(self.failures)).min(MAX_BACKOFF.max(interval))
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::json!({"failures": self.failures, "degraded": self.degraded(), "last_error": self.last_error})
+    }
+}
+
+fn health_lock() -> &'static Mutex<BTreeMap<String, Health>> {
+    static HEALTH: OnceLock<Mutex<BTreeMap<String, Health>>> = OnceLock::new();
+    HEALTH.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// How a provider's recent refreshes went; a provider that hasn't failed is healthy
+pub fn health(name: &str) -> Health {
+    health_lock().lock().ok().and_then(|health| health.get(name).cloned()).unwrap_or_default()
+}
+
+/// Count a failed refresh, returning the provider's health with it
+fn record_failure(name: &str, error: String) -> Health {
+    let Ok(mut health) = health_lock().lock() else {
+        return Health::default();
+    };
+    let entry = health.entry(name.to_string()).or_default();
+    entry.failures += 1;
+    entry.last_error = Some(error);
+    entry.clone()
+}
+
+/// Reset the failure count after a refresh worked, returning how many failures it ended
+fn record_success(name: &str) -> u32 {
+    health_lock().lock().ok().and_then(|mut health| health.remove(name)).map_or(0, |health| health.failures)
+}
 
 /// Battery information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct BatteryInfo {
     pub percentage: u8,
+    /// On AC power, whether or not the battery is taking charge
     pub is_charging: bool,
+    /// On AC power but not taking charge, because it's full or macOS holds it below full
+    pub charged: bool,
+    /// Minutes until empty on battery or until full while charging, None while macOS is estimating
+    pub minutes_remaining: Option<u32>,
+    /// The adapter's rating
+    pub adapter_watts: Option<u32>,
+    /// Power into the battery, negative while it discharges
+    pub battery_watts: Option<f32>,
+    pub health: power::BatteryHealth,
+    /// Low Power Mode is on
+    pub low_power: bool,
+}
+
+fn battery_lock() -> &'static RwLock<BatteryConfig> {
+    static BATTERY: OnceLock<RwLock<BatteryConfig>> = OnceLock::new();
+    BATTERY.get_or_init(|| RwLock::new(BatteryConfig::default()))
+}
+
+/// Set the battery breakpoints, alert levels and colors from the config
+pub fn set_battery_config(config: BatteryConfig) {
+    if let Ok(mut b) = battery_lock().write() {
+        *b = config;
+    }
+}
+
+/// The configured battery breakpoints, alert levels and colors
+pub fn battery_config() -> BatteryConfig {
+    battery_lock().read().map(|b| b.clone()).unwrap_or_default()
 }
 
 impl BatteryInfo {
     /// Get the appropriate icon for the battery state
     pub fn icon(&self) -> &'static str {
-        match self.percentage {
-            90..=100 => "\u{f240}",
-            70..=89 => "\u{f241}",
-            40..=69 => "\u{f242}",
-            10..=39 => "\u{f243}",
+        match battery_config().quarters(self.percentage) {
+            4 => "\u{f240}",
+            3 => "\u{f241}",
+            2 => "\u{f242}",
+            1 => "\u{f243}",
             _ => "\u{f244}",
         }
     }
 
-    /// Get the icon color based on charging state
-    pub fn icon_color(&self) -> &'static str {
-        if self.is_charging {
-            "0xfffabd2f" // Yellow when charging
-        } else if self.percentage <= 10 {
-            "0xfffb4934" // Red when battery is critically low
-        } else {
-            "0xffffffff" // White when not charging
+    /// Get the alert level for the charge and charging state
+    pub fn level(&self) -> BatteryLevel {
+        battery_config().level(self.percentage, self.is_charging)
+    }
+
+    /// Get the icon color for the alert level, tinted in Low Power Mode
+    pub fn icon_color(&self) -> String {
+        battery_config().icon_color(self.level(), self.low_power).to_string()
+    }
+
+    pub fn label_color(&self) -> String {
+        battery_config().color(self.level()).to_string()
+    }
+
+    /// The percentage, and with `time_remaining` configured the time to empty or full, e.g. `85% 2:14`
+    pub fn label(&self) -> String {
+        match self.minutes_remaining.filter(|_| battery_config().time_remaining) {
+            Some(minutes) => format!("{}% {}", self.percentage, format_minutes(minutes)),
+            None => format!("{}%", self.percentage),
         }
     }
 
-    pub fn label_color(&self) -> &'static str {
-        self.icon_color()
+    /// Build the info from the internal battery's IOKit description
+    fn from_description(
+        description: power::BatteryDescription,
+        adapter_watts: Option<u32>,
+        battery_watts: Option<f32>,
+    ) -> Self {
+        let percentage = description.current_capacity * 100 / description.max_capacity.max(1);
+        let minutes = |time: i64| u32::try_from(time).ok().filter(|minutes| *minutes > 0);
+        let minutes_remaining = match (description.on_ac, description.charging) {
+            (true, true) => minutes(description.time_to_full),
+            (true, false) => None,
+            (false, _) => minutes(description.time_to_empty),
+        };
+        Self {
+            percentage: percentage.clamp(0, 100) as u8,
+            is_charging: description.on_ac,
+            charged: description.on_ac && (description.charged || !description.charging),
+            minutes_remaining,
+            adapter_watts,
+            battery_watts,
+            ..Self::default()
+        }
     }
 }
 
-/// Get current battery information
-/// If power_source is provided (from sketchybar event), use it directly instead of querying pmset
-pub fn get_battery(power_source: Option<String>) -> Option<BatteryInfo> {
-    let output = Command::new("pmset")
-        .args(["-g", "batt"])
-        .output()
-        .ok()?;
+/// A duration in minutes as hours and minutes, e.g. `2:05`
+pub fn format_minutes(minutes: u32) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+/// Parse the internal battery from `pmset -g batt` output; None on a Mac without a battery
+///
+/// The battery line reads like `-InternalBattery-0 (id=123) 85%; discharging; 2:14 remaining present: true`,
+/// with `charging`, `charged`, `finishing charge` or `AC attached; not charging` as the state.
+fn parse_pmset_battery(stdout: &str) -> Option<BatteryInfo> {
+    let line = stdout.lines().find(|line| line.contains('%'))?;
+    let mut fields = line.split(';').map(str::trim);
+    let percentage = fields.next()?.split_whitespace().last()?.trim_end_matches('%').parse::<u8>().ok()?;
+    let state = fields.next().unwrap_or_default();
+    let charging = state == "charging" || state == "finishing charge";
+    let on_ac = stdout.contains("AC Power");
 
-    // Parse percentage - look for word containing '%' (e.g., "26%;" or "100%")
-    let percentage = stdout
-        .split_whitespace()
-        .find(|s| s.contains('%'))
-        .and_then(|s| {
-            // Extract digits before the '%' sign
-            s.split('%').next()?.parse::<u8>().ok()
-        })?;
-
-    // Check if charging - use provided power_source if available, otherwise parse from pmset output
-    let is_charging = if let Some(source) = power_source {
-        source == "AC"
-    } else {
-        stdout.contains("AC Power")
+    let remaining = line.split_whitespace().zip(line.split_whitespace().skip(1)).find_map(|(time, word)| {
+        let (hours, minutes) = time.split_once(':')?;
+        (word == "remaining").then_some(())?;
+        Some(hours.parse::<u32>().ok()? * 60 + minutes.parse::<u32>().ok()?)
+    });
+    Some(BatteryInfo {
+        percentage,
+        is_charging: on_ac,
+        charged: on_ac && !charging,
+        minutes_remaining: remaining.filter(|minutes| *minutes > 0 && (charging || !on_ac)),
+        ..BatteryInfo::default()
+    })
+}
+
+/// Parse the battery's wear from `ioreg -rn AppleSmartBattery`, whose top-level lines read like `"CycleCount" = 312`
+fn parse_ioreg_battery(stdout: &str) -> power::BatteryHealth {
+    let number = |key: &str| -> Option<u32> {
+        let prefix = format!("\"{}\" = ", key);
+        stdout.lines().find_map(|line| line.trim_start().strip_prefix(prefix.as_str())?.trim().parse().ok())
+    };
+    // Apple silicon reports MaxCapacity as a percentage and the mAh as AppleRawMaxCapacity
+    let max_capacity = number("AppleRawMaxCapacity").or(number("MaxCapacity").filter(|max| *max > 100));
+    power::BatteryHealth {
+        condition: None,
+        cycle_count: number("CycleCount"),
+        max_capacity,
+        design_capacity: number("DesignCapacity"),
+    }
+}
+
+/// Get the battery's condition, cycle count and capacities, from IOKit or else `ioreg`
+fn get_battery_health() -> power::BatteryHealth {
+    if let Some(health) = power::health() {
+        return health;
+    }
+    match Command::new("ioreg").args(["-rn", "AppleSmartBattery"]).output() {
+        Ok(output) => parse_ioreg_battery(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => power::BatteryHealth::default(),
+    }
+}
+
+/// Get current battery information from IOKit, or else `pmset`; `Ok(None)` on a Mac without a battery
+/// If power_source is provided (from sketchybar event), it decides whether the Mac is on AC power
+pub fn get_battery(power_source: Option<String>) -> Result<Option<BatteryInfo>, String> {
+    let info = match power::battery() {
+        Some(description) => {
+            let adapter_watts = if description.on_ac { power::adapter_watts() } else { None };
+            Some(BatteryInfo::from_description(description, adapter_watts, power::battery_watts()))
+        }
+        None => {
+            let output = Command::new("pmset")
+                .args(["-g", "batt"])
+                .output()
+                .map_err(|e| format!("Failed to run pmset: {}", e))?;
+            if !output.status.success() {
+                return Err(format!("pmset failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+            }
+            parse_pmset_battery(&String::from_utf8_lossy(&output.stdout))
+        }
     };
 
-    Some(BatteryInfo { percentage, is_charging })
+    Ok(info.map(|mut info| {
+        if let Some(source) = power_source {
+            info.is_charging = source == "AC";
+        }
+        info.health = get_battery_health();
+        info.low_power = low_power::enabled().unwrap_or(false);
+        info
+    }))
 }
 
 /// Volume information
@@ -86,7 +410,14 @@ impl VolumeInfo {
 }
 
 /// Get current volume information
+///
+/// Reads CoreAudio directly, falling back to osascript for output devices
+/// without a volume control CoreAudio exposes.
 pub fn get_volume() -> Option<VolumeInfo> {
+    volume::current().or_else(get_volume_osascript)
+}
+
+fn get_volume_osascript() -> Option<VolumeInfo> {
     let output = Command::new("osascript")
         .args(["-e", "output volume of (get volume settings)"])
         .output()
@@ -108,27 +439,181 @@ pub fn get_volume() -> Option<VolumeInfo> {
     Some(VolumeInfo { percentage, muted })
 }
 
-/// Get current time formatted as DD/MM HH:MM
-pub fn get_clock() -> String {
+/// Audio output devices and the one sound currently plays on
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AudioOutputInfo {
+    pub current: Option<String>,
+    pub devices: Vec<String>,
+}
+
+impl AudioOutputInfo {
+    /// Get an icon for the kind of device, guessed from its name
+    pub fn icon(&self) -> &'static str {
+        let name = self.current.as_deref().unwrap_or_default().to_lowercase();
+        if ["airpods", "headphones", "buds", "headset"].iter().any(|kind| name.contains(kind)) {
+            "\u{f02cb}" // nf-md-headphones
+        } else if ["displayport", "hdmi", "display", "monitor"].iter().any(|kind| name.contains(kind)) {
+            "\u{f0379}" // nf-md-monitor
+        } else {
+            "\u{f04c3}" // nf-md-speaker
+        }
+    }
+}
+
+/// Device names from `SwitchAudioSource -a` or `-c` output, one per line
+fn parse_switch_audio_source(stdout: &str) -> Vec<String> {
+    stdout.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string).collect()
+}
+
+/// Get the audio output devices
+///
+/// Reads CoreAudio directly, falling back to `SwitchAudioSource` where it lists no devices.
+pub fn get_audio_output() -> Option<AudioOutputInfo> {
+    volume::outputs().or_else(|| {
+        let switch_audio_source = |args: &[&str]| {
+            let output = Command::new("SwitchAudioSource").args(args).output().ok()?;
+            output.status.success().then(|| parse_switch_audio_source(&String::from_utf8_lossy(&output.stdout)))
+        };
+        Some(AudioOutputInfo {
+            devices: switch_audio_source(&["-a", "-t", "output"])?,
+            current: switch_audio_source(&["-c", "-t", "output"])?.pop(),
+        })
+    })
+}
+
+fn clock_lock() -> &'static RwLock<ClockFormat> {
+    static CLOCK: OnceLock<RwLock<ClockFormat>> = OnceLock::new();
+    CLOCK.get_or_init(|| RwLock::new(ClockFormat::default()))
+}
+
+/// Set the clock format from the config
+pub fn set_clock_format(format: ClockFormat) {
+    if let Ok(mut c) = clock_lock().write() {
+        *c = format;
+    }
+}
+
+/// The configured clock format
+pub fn clock_format() -> ClockFormat {
+    clock_lock().read().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// Format the current time with `date`, in `zone` if given and otherwise the local time zone
+fn format_time(format: &ClockFormat, strftime: &str, zone: Option<&str>) -> Option<String> {
     // Use shell command to avoid pulling in chrono dependency
-    let output = Command::new("date")
-        .args(["+%d/%m %H:%M"])
-        .output();
-    
-    match output {
-        Ok(o) if o.status.success() => {
-            String::from_utf8_lossy(&o.stdout).trim().to_string()
+    let mut command = Command::new("date");
+    command.arg(format!("+{}", strftime));
+    if let Some(locale) = &format.locale {
+        command.env("LC_TIME", locale);
+    }
+    if let Some(zone) = zone {
+        command.env("TZ", zone);
+    }
+
+    match command.output() {
+        Ok(o) if o.status.success() => Some(String::from_utf8_lossy(&o.stdout).trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Get the current time in the configured format (default DD/MM HH:MM)
+pub fn get_clock() -> String {
+    let format = clock_format();
+    format_time(&format, &format.strftime(), None).unwrap_or_else(|| "??/?? ??:??".to_string())
+}
+
+/// The local time and the time in the configured extra time zones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClockInfo {
+    pub time: String,
+    /// City and time of each extra time zone, e.g. `New York` and `03:30`
+    pub zones: Vec<(String, String)>,
+}
+
+impl ClockInfo {
+    /// The local time, followed with `inline` by the other time zones, e.g. `16/10 09:30 · New York 03:30`
+    pub fn label(&self, inline: bool) -> String {
+        let mut label = self.time.clone();
+        if inline {
+            for (city, time) in &self.zones {
+                label.push_str(&format!(" · {} {}", city, time));
+            }
         }
-        _ => "??/?? ??:??".to_string()
+        label
     }
 }
 
+/// The city a time zone is named for, e.g. `New York` for `America/New_York`
+pub fn zone_city(zone: &str) -> String {
+    zone.rsplit('/').next().unwrap_or(zone).replace('_', " ")
+}
+
+/// Get the current time, and the time of day in each extra time zone
+pub fn get_clock_info() -> ClockInfo {
+    let format = clock_format();
+    let zones = format
+        .extra
+        .iter()
+        .map(|zone| {
+            let time = format_time(&format, format.time_strftime(), Some(zone)).unwrap_or_else(|| "??:??".to_string());
+            (zone_city(zone), time)
+        })
+        .collect();
+    ClockInfo { time: get_clock(), zones }
+}
+
+/// Time from `now` (since the epoch) to the next whole multiple of `interval`
+///
+/// Time zones are offset from UTC by whole minutes (or half hours), so for
+/// intervals that divide a minute the ticks land on local minute boundaries.
+fn align_delay(now: Duration, interval: Duration) -> Duration {
+    let interval_nanos = interval.as_nanos().max(1);
+    let into_interval = now.as_nanos() % interval_nanos;
+    Duration::from_nanos((interval_nanos - into_interval) as u64)
+}
+
+/// Time until the next clock tick: the next whole second when seconds are shown,
+/// the next multiple of `interval` when aligned, else `interval`
+pub fn clock_tick(interval: Duration) -> Duration {
+    let format = clock_format();
+    let interval = if format.shows_seconds() {
+        Duration::from_secs(1)
+    } else if format.align {
+        interval
+    } else {
+        return interval;
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    align_delay(now, interval)
+}
+
+/// Time until the next local midnight, for items that change once a day
+pub fn midnight_tick() -> Duration {
+    // Without the local offset the tick lands on midnight UTC, which is still once a day
+    let offset = sun::now().map_or(0, |(_, offset)| offset);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let local = Duration::from_secs((now.as_secs() as i64 + offset).max(0) as u64);
+    align_delay(local, Duration::from_secs(24 * 60 * 60))
+}
+
+/// A random delay of up to `max_secs` seconds, to spread out refreshes that would otherwise run together
+pub fn jitter(max_secs: u64) -> Duration {
+    if max_secs == 0 {
+        return Duration::ZERO;
+    }
+    // RandomState is seeded randomly and differs on every call; no need for a rand dependency
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % (max_secs * 1000 + 1))
+}
 
 /// Brew outdated information
 #[derive(Debug, Clone, Default)]
 pub struct BrewInfo {
     pub formulae: usize,
     pub casks: usize,
+    /// Names of the outdated formulae, then casks
+    pub packages: Vec<String>,
 }
 
 impl BrewInfo {
@@ -143,33 +628,108 @@ impl BrewInfo {
     }
 }
 
+fn brew_lock() -> &'static RwLock<BrewConfig> {
+    static BREW: OnceLock<RwLock<BrewConfig>> = OnceLock::new();
+    BREW.get_or_init(|| RwLock::new(BrewConfig::default()))
+}
+
+/// Set the brew binary, outdated check and click behavior from the config
+pub fn set_brew_config(config: BrewConfig) {
+    if let Ok(mut b) = brew_lock().write() {
+        *b = config;
+    }
+}
+
+/// The configured brew binary, outdated check and click behavior
+pub fn brew_config() -> BrewConfig {
+    brew_lock().read().map(|b| b.clone()).unwrap_or_default()
+}
+
+/// Run brew with arguments, returning its output or why it failed
+fn brew_output(brew: &str, args: &[&str]) -> Result<String, String> {
+    match Command::new(brew).args(args).output() {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        Ok(output) => Err(format!("brew {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim())),
+        Err(e) => Err(format!("Failed to run {}: {}", brew, e)),
+    }
+}
+
+/// The packages in `brew outdated -q` output, leaving out `excluded` ones
+fn outdated_packages(stdout: &str, excluded: &[&str]) -> Vec<String> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && !excluded.contains(name))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Summarize `brew upgrade` output as the packages it upgraded
+///
+/// Each upgrade run lists its packages as `name old -> new` under an
+/// `==> Upgrading N outdated packages:` header; the per-package sections
+/// that follow repeat the versions indented, and are skipped.
+pub fn upgrade_summary(stdout: &str) -> String {
+    let mut upgraded: Vec<&str> = Vec::new();
+    let mut in_list = false;
+    for line in stdout.lines() {
+        if line.starts_with("==>") {
+            in_list = line.starts_with("==> Upgrading") && line.ends_with(':');
+        } else if in_list && line.contains(" -> ") {
+            if let Some(name) = line.split_whitespace().next().filter(|name| !upgraded.contains(name)) {
+                upgraded.push(name);
+            }
+        }
+    }
+
+    match upgraded.as_slice() {
+        [] => "Everything is up to date".to_string(),
+        [only] => format!("Upgraded {}", only),
+        [first @ .., last] if first.len() < 3 => format!("Upgraded {} and {}", first.join(", "), last),
+        _ => format!("Upgraded {} and {} more", upgraded[..3].join(", "), upgraded.len() - 3),
+    }
+}
+
 /// Get outdated brew formulae and casks count
-pub fn get_brew_outdated() -> BrewInfo {
+pub fn get_brew_outdated() -> Result<BrewInfo, String> {
+    let config = brew_config();
+    let brew = config.binary();
     let mut info = BrewInfo::default();
 
-    // Get outdated formulae
-    if let Ok(output) = Command::new("brew")
-        .args(["outdated", "--formula", "-q"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            info.formulae = stdout.lines().filter(|l| !l.is_empty()).count();
+    // Counting against a stale index still beats not counting
+    if config.update {
+        if let Err(e) = brew_output(&brew, &["update", "--quiet"]) {
+            warn!("{}", e);
         }
     }
 
+    let pinned = if config.exclude_pinned {
+        brew_output(&brew, &["list", "--pinned"]).unwrap_or_else(|e| {
+            warn!("{}", e);
+            String::new()
+        })
+    } else {
+        String::new()
+    };
+    let pinned: Vec<&str> = pinned.split_whitespace().collect();
+
+    // Get outdated formulae
+    let stdout = brew_output(&brew, &["outdated", "--formula", "-q"])?;
+    let formulae = outdated_packages(&stdout, &pinned);
+    info.formulae = formulae.len();
+    info.packages = formulae;
+
     // Get outdated casks
-    if let Ok(output) = Command::new("brew")
-        .args(["outdated", "--cask", "-q"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            info.casks = stdout.lines().filter(|l| !l.is_empty()).count();
-        }
+    let mut args = vec!["outdated", "--cask", "-q"];
+    if config.greedy {
+        args.push("--greedy");
     }
+    let stdout = brew_output(&brew, &args)?;
+    let casks = outdated_packages(&stdout, &[]);
+    info.casks = casks.len();
+    info.packages.extend(casks);
 
-    info
+    Ok(info)
 }
 
 /// CPU and RAM usage information
@@ -182,120 +742,1226 @@ pub struct SystemInfo {
 impl SystemInfo {
     /// Get the appropriate CPU icon based on usage
     pub fn cpu_icon(&self) -> &'static str {
-        match self.cpu_percentage {
-            80..=100 => "󰻠", // nf-md-cpu_high
-            50..=79 => "󰻟",  // nf-md-cpu_medium
-            _ => "󰘚",       // nf-md-cpu_low
-        }
+        cpu_icon(self.cpu_percentage)
     }
 
     /// Get the appropriate RAM icon based on usage
     pub fn ram_icon(&self) -> &'static str {
-        match self.ram_percentage {
-            80..=100 => "󰍛", // nf-md-memory_high
-            50..=79 => "󰍛",  // nf-md-memory_medium
-            _ => "󰍛",       // nf-md-memory_low
-        }
+        ram_icon(self.ram_percentage)
     }
 }
 
-/// Microsoft Teams notification information
-#[derive(Debug, Clone, Default)]
-pub struct TeamsInfo {
-    pub running: bool,
-    pub notification_count: u32,
+/// Get the RAM icon for a usage percentage
+pub fn ram_icon(percentage: u8) -> &'static str {
+    match percentage {
+        80..=100 => "󰍛", // nf-md-memory_high
+        50..=79 => "󰍛",  // nf-md-memory_medium
+        _ => "󰍛",       // nf-md-memory_low
+    }
 }
 
-impl TeamsInfo {
-    /// Get the appropriate icon (Microsoft Teams icon)
+/// Get the CPU icon for a usage percentage
+pub fn cpu_icon(percentage: u8) -> &'static str {
+    match percentage {
+        80..=100 => "󰻠", // nf-md-cpu_high
+        50..=79 => "󰻟",  // nf-md-cpu_medium
+        _ => "󰘚",       // nf-md-cpu_low
+    }
+}
+
+/// Parse a `top` memory size such as `2150M` or `15G` into bytes
+fn parse_size(size: &str) -> Option<f64> {
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let scale = match unit {
+        "" | "B" => 1.0,
+        "K" => 1024.0,
+        "M" => 1024.0 * 1024.0,
+        "G" => 1024.0 * 1024.0 * 1024.0,
+        "T" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some(number.parse::<f64>().ok()? * scale)
+}
+
+/// Parse CPU and RAM usage from the last sample in `top -l N -n 0` output
+///
+/// CPU usage is 100% minus idle, from `CPU usage: 5.1% user, 3.2% sys, 91.7% idle`;
+/// RAM usage is used over used plus unused, from `PhysMem: 15G used (...), 421M unused.`
+fn parse_top(stdout: &str) -> Option<SystemInfo> {
+    let cpu_line = stdout.lines().rfind(|line| line.starts_with("CPU usage:"))?;
+    let idle = cpu_line
+        .split(',')
+        .find_map(|part| part.trim().strip_suffix("% idle"))?
+        .parse::<f64>()
+        .ok()?;
+
+    let mem_line = stdout.lines().rfind(|line| line.starts_with("PhysMem:"))?;
+    let mem = mem_line.trim_start_matches("PhysMem:").trim().trim_end_matches('.');
+    let used = parse_size(mem.split_whitespace().next()?)?;
+    let unused = parse_size(mem.rsplit(", ").next()?.strip_suffix(" unused")?)?;
+
+    Some(SystemInfo {
+        cpu_percentage: (100.0 - idle).clamp(0.0, 100.0).round() as u8,
+        ram_percentage: (used / (used + unused) * 100.0).round() as u8,
+    })
+}
+
+/// Get CPU usage over the last second, and RAM usage from the VM statistics where there are some
+pub fn get_system_info() -> Option<SystemInfo> {
+    // The first sample covers the time since boot; the second the last second
+    let output = Command::new("top").args(["-l", "2", "-n", "0", "-s", "1"]).output().ok()?;
+    let mut info = parse_top(&String::from_utf8_lossy(&output.stdout))?;
+    if let Some(percentage) = memory::usage() {
+        info.ram_percentage = percentage;
+    }
+    Some(info)
+}
+
+/// Memory in use and the kernel's pressure level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RamInfo {
+    pub percentage: u8,
+    pub pressure: Level,
+}
+
+/// Get memory usage from the VM statistics, or else from `top`
+pub fn get_ram() -> Option<RamInfo> {
+    let percentage = memory::usage().or_else(|| get_system_info().map(|info| info.ram_percentage))?;
+    Some(RamInfo { percentage, pressure: memory::kernel_pressure() })
+}
+
+/// Load averages over the last 1, 5 and 15 minutes and the cores they're shared by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadInfo {
+    pub averages: [f64; 3],
+    pub cores: usize,
+}
+
+impl LoadInfo {
     pub fn icon(&self) -> &'static str {
-        "󰊻" // nf-md-microsoft_teams
+        "\u{f029a}" // nf-md-gauge
     }
 
-    /// Get the icon color based on state
-    pub fn icon_color(&self) -> &'static str {
-        if !self.running {
-            "0xff3c3836" // Same as active workspace bg when not running
-        } else if self.notification_count > 0 {
-            "0xfffabd2f" // Yellow/amber when notifications
+    /// The averages as percentages of the core count
+    pub fn percentages(&self) -> [u16; 3] {
+        let cores = self.cores.max(1) as f64;
+        self.averages.map(|average| (average / cores * 100.0).round().min(f64::from(u16::MAX)) as u16)
+    }
+
+    /// The three percentages, e.g. `45% 38% 30%`
+    pub fn label(&self) -> String {
+        let [one, five, fifteen] = self.percentages();
+        format!("{}% {}% {}%", one, five, fifteen)
+    }
+}
+
+/// Get the load averages from getloadavg
+pub fn get_load() -> Option<LoadInfo> {
+    Some(LoadInfo { averages: load::averages()?, cores: load::cores() })
+}
+
+/// How hot the machine runs, and the hottest CPU or SoC sensor where it was read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermalInfo {
+    pub state: ThermalState,
+    /// °C
+    pub temperature: Option<f32>,
+}
+
+impl ThermalInfo {
+    pub fn icon(&self) -> &'static str {
+        if self.state.throttling() {
+            "\u{f10c2}" // nf-md-thermometer_high
         } else {
-            "0xffffffff" // White (same as other icons)
+            "\u{f050f}" // nf-md-thermometer
         }
     }
 
-    /// Get the border color based on state
-    pub fn border_color(&self) -> &'static str {
-        if self.notification_count > 0 {
-            "0xfffabd2f" // Yellow/amber border for notifications
-        } else {
-            "0xff2a2c3a" // Default border
+    /// The temperature, e.g. `52°C`, or else the state
+    pub fn label(&self) -> String {
+        match self.temperature {
+            Some(celsius) => format!("{:.0}\u{b0}C", celsius),
+            None => self.state.to_string(),
         }
     }
 }
 
-/// Get Microsoft Teams notification count
-pub fn get_teams_notifications() -> TeamsInfo {
-    let mut info = TeamsInfo::default();
+/// Read the SMC, sharing the reading between the thermal and fan items when they refresh together
+fn smc_sensors() -> Option<smc::Sensors> {
+    smc::sensors(Duration::from_secs(settings::current().thermal_interval) / 2)
+}
 
-    // Check if Teams is running (MSTeams is the new Teams app process name)
-    let running = Command::new("pgrep")
-        .args(["-x", "MSTeams"])
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false);
+/// Get the thermal state, and with `temperature` the hottest sensor from the SMC
+pub fn get_thermal(temperature: bool) -> Option<ThermalInfo> {
+    let state = thermal::current()?;
+    let temperature = if temperature { smc_sensors().and_then(|sensors| sensors.temperature) } else { None };
+    Some(ThermalInfo { state, temperature })
+}
 
-    info.running = running;
+/// Each fan's speed in RPM
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FanInfo {
+    pub rpms: Vec<f32>,
+}
 
-    if !running {
-        return info;
+impl FanInfo {
+    /// Macs without fans have nothing to show
+    pub fn fanless(&self) -> bool {
+        self.rpms.is_empty()
     }
 
-    // Get notification count from Dock badge via AppleScript
-    let script = r#"
-tell application "System Events"
-    tell UI element "Microsoft Teams" of list 1 of process "Dock"
-        try
-            set badgeValue to value of attribute "AXStatusLabel"
-            if badgeValue is not missing value then
-                return badgeValue
-            end if
-        end try
-    end tell
-end tell
-return "0"
-"#;
-
-    if let Ok(output) = Command::new("osascript")
-        .args(["-e", script])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            // Extract only digits from the result
-            let count_str: String = stdout.trim().chars().filter(|c| c.is_ascii_digit()).collect();
-            info.notification_count = count_str.parse().unwrap_or(0);
+    pub fn icon(&self) -> &'static str {
+        if self.rpms.iter().any(|rpm| *rpm >= 1.0) {
+            "\u{f0210}" // nf-md-fan
+        } else {
+            "\u{f081d}" // nf-md-fan_off
         }
     }
 
-    info
+    /// Every fan's speed, e.g. `1840 rpm` or `2210 / 2190 rpm`
+    pub fn label(&self) -> String {
+        let rpms: Vec<String> = self.rpms.iter().map(|rpm| format!("{:.0}", rpm)).collect();
+        format!("{} rpm", rpms.join(" / "))
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Get the fan speeds from the SMC, or None without one
+pub fn get_fans() -> Option<FanInfo> {
+    smc_sensors().map(|sensors| FanInfo { rpms: sensors.fans })
+}
 
-    #[test]
-    fn test_battery_icons() {
-        let high = BatteryInfo { percentage: 95, is_charging: false };
-        assert_eq!(high.icon(), "󱊣");
+/// Whether the dark appearance is on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AppearanceInfo {
+    pub dark: bool,
+}
 
-        let is_charging = BatteryInfo { percentage: 50, is_charging: true };
-        assert_eq!(is_charging.icon(), "\u{f0e7}"); // nf-fa-bolt
+impl AppearanceInfo {
+    pub fn icon(&self) -> &'static str {
+        if self.dark {
+            "\u{f0594}" // nf-md-weather_night
+        } else {
+            "\u{f05a8}" // nf-md-white_balance_sunny
+        }
+    }
+
+    /// `dark` or `light`, as passed to the hook
+    pub fn name(&self) -> &'static str {
+        if self.dark {
+            "dark"
+        } else {
+            "light"
+        }
+    }
+}
+
+/// Get the current appearance
+pub fn get_appearance() -> Result<AppearanceInfo, String> {
+    appearance::dark().map(|dark| AppearanceInfo { dark })
+}
+
+/// A percentage after scrolling `delta` lines from `current`, `step` percent a line
+pub fn scrolled(current: u8, delta: i32, step: u8) -> u8 {
+    (i32::from(current) + delta.saturating_mul(i32::from(step))).clamp(0, 100) as u8
+}
+
+/// Brightness of the main display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrightnessInfo {
+    pub percentage: u8,
+}
+
+impl BrightnessInfo {
+    pub fn icon(&self) -> &'static str {
+        match self.percentage {
+            67..=100 => "\u{f00e0}", // nf-md-brightness_7
+            34..=66 => "\u{f00df}",  // nf-md-brightness_6
+            _ => "\u{f00de}",        // nf-md-brightness_5
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("{}%", self.percentage)
+    }
+}
+
+/// Get the main display's brightness, or None when no display can report it
+pub fn get_brightness() -> Option<BrightnessInfo> {
+    brightness::percentage().map(|percentage| BrightnessInfo { percentage })
+}
+
+/// The next calendar event and the minutes until it starts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CalendarInfo {
+    pub event: Option<calendar::Event>,
+    /// Negative once the event has started
+    pub minutes: i64,
+}
+
+impl CalendarInfo {
+    /// A camera while the next event has a meeting link to join, otherwise a calendar
+    pub fn icon(&self) -> &'static str {
+        if self.event.as_ref().is_some_and(|event| event.link.is_some()) {
+            "\u{f03d}" // nf-fa-video_camera
+        } else {
+            "\u{f073}" // nf-fa-calendar
+        }
+    }
+
+    /// The title and the time left, e.g. `Standup in 25m`, `Review in 1h 5m` or `Standup now`
+    pub fn label(&self) -> String {
+        let Some(event) = &self.event else {
+            return String::new();
+        };
+        match self.minutes {
+            ..=0 => format!("{} now", event.title),
+            1..=59 => format!("{} in {}m", event.title, self.minutes),
+            _ => format!("{} in {}h {}m", event.title, self.minutes / 60, self.minutes % 60),
+        }
+    }
+}
+
+/// Get the next event in the named calendars, or in all of them when none are named
+pub fn get_calendar(names: &[String]) -> Result<CalendarInfo, String> {
+    let Some(event) = calendar::next(names)? else {
+        return Ok(CalendarInfo::default());
+    };
+    let now = calendar::now()?;
+    let minutes = calendar::minutes_until(&now, &event.start).ok_or_else(|| format!("Unexpected time: {}", now))?;
+    Ok(CalendarInfo { event: Some(event), minutes })
+}
+
+/// Whether Zoom is in a meeting and its microphone is live
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZoomInfo {
+    pub meeting: zoom::Meeting,
+}
+
+impl ZoomInfo {
+    /// A struck-through microphone while muted, or None outside a meeting
+    pub fn icon(&self) -> Option<&'static str> {
+        match self.meeting {
+            zoom::Meeting::None => None,
+            zoom::Meeting::Muted => Some("\u{f036d}"), // nf-md-microphone_off
+            zoom::Meeting::Live => Some("\u{f036c}"),  // nf-md-microphone
+        }
+    }
+}
+
+/// Get Zoom's meeting state from its menu bar
+pub fn get_zoom() -> Result<ZoomInfo, String> {
+    zoom::meeting().map(|meeting| ZoomInfo { meeting })
+}
+
+/// The weather and its forecast, in the configured units
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeatherInfo {
+    pub weather: weather::Weather,
+    pub units: WeatherUnits,
+}
+
+impl WeatherInfo {
+    pub fn icon(&self) -> &'static str {
+        self.weather.report.condition.icon()
+    }
+
+    /// The temperature, e.g. `12°C`
+    pub fn label(&self) -> String {
+        self.degrees(self.weather.report.temperature)
+    }
+
+    /// A temperature with the units' symbol
+    pub fn degrees(&self, temperature: i32) -> String {
+        format!("{}°{}", temperature, self.units.symbol())
+    }
+}
+
+/// Get the weather, from the cache while it's newer than `interval` seconds or fetching fails
+///
+/// None until a location or an API key is set, so nothing is fetched (and no IP address located) unasked.
+pub fn get_weather(config: &WeatherConfig, interval: u64) -> Result<Option<WeatherInfo>, String> {
+    if config.location.is_empty() && config.api_key.is_none() {
+        return Ok(None);
+    }
+    weather::current(config, interval).map(|weather| Some(WeatherInfo { weather, units: config.units }))
+}
+
+/// The next sunrise, sunset or golden hour change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SunInfo {
+    /// None through polar day or night
+    pub next: Option<sun::Next>,
+    /// The local time zone's offset from UTC in seconds
+    pub offset: i64,
+}
+
+impl SunInfo {
+    /// The sun coming up or going down, or None when it won't for days
+    pub fn icon(&self) -> Option<&'static str> {
+        match self.next?.transition {
+            sun::Transition::Sunrise => Some("\u{f059c}"), // nf-md-weather_sunset_up
+            sun::Transition::Sunset => Some("\u{f059b}"),  // nf-md-weather_sunset_down
+            // nf-md-weather_sunset
+            sun::Transition::GoldenHourEnd | sun::Transition::GoldenHourStart => Some("\u{f059a}"),
+        }
+    }
+
+    /// Local time of the transition, e.g. `18:11`
+    pub fn label(&self) -> String {
+        self.next.map(|next| sun::local_time(next.at, self.offset)).unwrap_or_default()
+    }
+}
+
+/// Get the next transition of the sun at the sun item's coordinates, or else the weather item's
+pub fn get_sun(config: &SunConfig, weather: &WeatherConfig) -> Result<SunInfo, String> {
+    let (latitude, longitude) = config
+        .coordinates(weather)
+        .ok_or_else(|| "No coordinates to work out the sun's times for; set location in [items.sun]".to_string())?;
+    let (now, offset) = sun::now()?;
+    Ok(SunInfo { next: sun::next(now, latitude, longitude, config.golden_hour), offset })
+}
+
+/// The moon's phase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoonInfo {
+    pub moon: moon::Moon,
+}
+
+/// Get the moon's phase right now
+pub fn get_moon() -> MoonInfo {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    MoonInfo { moon: moon::at(now.as_secs() as i64) }
+}
+
+/// Prices of the configured coins
+#[derive(Debug, Clone, PartialEq)]
+pub struct CryptoInfo {
+    pub quotes: Vec<crypto::Quote>,
+    pub currency: String,
+    /// Whether a price is past one of the configured bounds
+    pub breached: bool,
+}
+
+impl CryptoInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{f0813}" // nf-md-bitcoin
+    }
+
+    /// Each coin's price and 24-hour change, e.g. `BTC $67,012 -1.3%`
+    pub fn label(&self) -> String {
+        self.quotes
+            .iter()
+            .map(|quote| {
+                let price = format!("{} {}", quote.ticker(), crypto::format_price(quote.price, &self.currency));
+                match quote.change {
+                    Some(change) => format!("{} {:+.1}%", price, change),
+                    None => price,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+/// Get the prices of the configured coins from CoinGecko, or None when there are none
+pub fn get_crypto(config: &CryptoConfig) -> Result<Option<CryptoInfo>, String> {
+    if config.coins.is_empty() {
+        return Ok(None);
+    }
+    let quotes = crypto::fetch(config)?;
+    let breached = crypto::breached(&config.alerts, &quotes);
+    Ok(Some(CryptoInfo { quotes, currency: config.currency.clone(), breached }))
+}
+
+/// The rate of the configured currency pair
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeInfo {
+    pub pair: CurrencyPair,
+    pub rate: exchange::Rate,
+}
+
+impl ExchangeInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{f0114}" // nf-md-cash
+    }
+
+    /// The pair and its rate, e.g. `EUR/USD 1.0876`
+    pub fn label(&self) -> String {
+        format!("{} {}", self.pair, self.rate.format())
+    }
+}
+
+/// Get the rate of a currency pair, from the cache while it's newer than `interval` seconds or fetching fails;
+/// None until a pair is set
+pub fn get_exchange(pair: Option<&CurrencyPair>, interval: u64) -> Result<Option<ExchangeInfo>, String> {
+    let Some(pair) = pair else {
+        return Ok(None);
+    };
+    exchange::current(pair, interval).map(|rate| Some(ExchangeInfo { pair: pair.clone(), rate }))
+}
+
+/// New entries in the configured feeds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedsInfo {
+    pub feeds: feeds::Feeds,
+}
+
+impl FeedsInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{f046b}" // nf-md-rss
+    }
+
+    /// The number of unread entries, empty when there are none
+    pub fn label(&self) -> String {
+        match self.feeds.unread() {
+            0 => String::new(),
+            unread => unread.to_string(),
+        }
+    }
+}
+
+/// Check the feeds, or None when there are none to check
+pub fn get_feeds(urls: &[String]) -> Result<Option<FeedsInfo>, String> {
+    if urls.is_empty() {
+        return Ok(None);
+    }
+    feeds::check(urls).map(|feeds| Some(FeedsInfo { feeds }))
+}
+
+/// Pull requests waiting for the user's review
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewsInfo {
+    pub queue: github::Queue,
+}
+
+impl ReviewsInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{f407}" // nf-oct-git_pull_request
+    }
+
+    /// The number of pull requests in the queue, empty when there are none
+    pub fn label(&self) -> String {
+        match self.queue.count {
+            0 => String::new(),
+            count => count.to_string(),
+        }
+    }
+}
+
+/// Search GitHub for the review queue, or None without a token to search with
+pub fn get_reviews(config: &ReviewsConfig) -> Result<Option<ReviewsInfo>, String> {
+    let Some(token) = &config.token else {
+        return Ok(None);
+    };
+    github::search(token, &config.query).map(|queue| Some(ReviewsInfo { queue }))
+}
+
+/// The user's merge requests on GitLab
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitlabInfo {
+    pub overview: gitlab::Overview,
+}
+
+impl GitlabInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{f296}" // nf-fa-gitlab
+    }
+
+    /// The number of merge requests assigned to the user, empty when there are none
+    pub fn label(&self) -> String {
+        match self.overview.assigned_count {
+            0 => String::new(),
+            count => count.to_string(),
+        }
+    }
+}
+
+/// Ask the GitLab instance for the user's merge requests, or None without a token to ask with
+pub fn get_gitlab(config: &GitlabConfig) -> Result<Option<GitlabInfo>, String> {
+    let Some(token) = &config.token else {
+        return Ok(None);
+    };
+    gitlab::overview(&config.url, token).map(|overview| Some(GitlabInfo { overview }))
+}
+
+/// The open Jira issues assigned to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JiraInfo {
+    pub assigned: jira::Assigned,
+}
+
+impl JiraInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{e75c}" // nf-dev-jira
+    }
+
+    /// The number of issues, with a `+` when there are more than were fetched, empty when there are none
+    pub fn label(&self) -> String {
+        match (self.assigned.count, self.assigned.more) {
+            (0, false) => String::new(),
+            (count, false) => count.to_string(),
+            (count, true) => format!("{}+", count),
+        }
+    }
+}
+
+/// Search Jira for the issues assigned to the user, or None without a site and token to search
+pub fn get_jira(config: &JiraConfig) -> Result<Option<JiraInfo>, String> {
+    if config.url.is_none() || config.token.is_none() {
+        return Ok(None);
+    }
+    jira::assigned(config).map(|assigned| Some(JiraInfo { assigned }))
+}
+
+/// The user's active Linear issues in the current cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinearInfo {
+    pub count: linear::Count,
+}
+
+impl LinearInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{f0ae}" // nf-fa-tasks
+    }
+
+    /// The number of issues, with a `+` when there are more than were counted, empty when there are none
+    pub fn label(&self) -> String {
+        match (self.count.issues, self.count.more) {
+            (0, false) => String::new(),
+            (issues, false) => issues.to_string(),
+            (issues, true) => format!("{}+", issues),
+        }
+    }
+}
+
+/// Count the user's issues in the current cycle, or None without an API key to ask with
+pub fn get_linear(config: &LinearConfig) -> Result<Option<LinearInfo>, String> {
+    let Some(api_key) = &config.api_key else {
+        return Ok(None);
+    };
+    linear::count(api_key, &config.teams).map(|count| Some(LinearInfo { count }))
+}
+
+/// The Focus that is on, if any
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FocusModeInfo {
+    pub focus: Option<focus_mode::Focus>,
+}
+
+impl FocusModeInfo {
+    /// The built-in Focuses get their own glyph, the rest and no Focus at all the moon
+    pub fn icon(&self) -> &'static str {
+        let identifier = self.focus.as_ref().map(|focus| focus.identifier.as_str()).unwrap_or_default();
+        match identifier {
+            "com.apple.sleep.sleep-mode" => "\u{f236}",         // nf-fa-bed
+            "com.apple.focus.work" => "\u{f0b1}",               // nf-fa-briefcase
+            "com.apple.focus.personal" => "\u{f007}",           // nf-fa-user
+            "com.apple.donotdisturb.mode.driving" => "\u{f1b9}", // nf-fa-car
+            _ => "\u{f186}",                                    // nf-fa-moon_o
+        }
+    }
+
+    /// The Focus's name when `show_name`, otherwise nothing
+    pub fn label(&self, show_name: bool) -> String {
+        self.focus.as_ref().filter(|_| show_name).map(|focus| focus.name.clone()).unwrap_or_default()
+    }
+}
+
+/// Get the Focus that is on from the Do Not Disturb database
+pub fn get_focus_mode() -> Result<FocusModeInfo, String> {
+    focus_mode::current().map(|focus| FocusModeInfo { focus })
+}
+
+/// Busy and total CPU ticks at the last sample
+fn cpu_ticks_lock() -> &'static Mutex<(u64, u64)> {
+    static TICKS: OnceLock<Mutex<(u64, u64)>> = OnceLock::new();
+    TICKS.get_or_init(|| Mutex::new((0, 0)))
+}
+
+/// Usage between two readings of the busy and total ticks
+fn cpu_usage(previous: (u64, u64), current: (u64, u64)) -> u8 {
+    let busy = current.0.saturating_sub(previous.0);
+    let total = current.1.saturating_sub(previous.1);
+    if total == 0 {
+        return 0;
+    }
+    (busy as f64 / total as f64 * 100.0).round().min(100.0) as u8
+}
+
+/// Get CPU usage since the last call, from the Mach host's tick counters or else `top`
+///
+/// The first call covers the time since boot.
+pub fn get_cpu() -> Option<u8> {
+    let Some(current) = cpu::ticks() else {
+        return get_system_info().map(|info| info.cpu_percentage);
+    };
+    let mut previous = cpu_ticks_lock().lock().ok()?;
+    let usage = cpu_usage(*previous, current);
+    *previous = current;
+    Some(usage)
+}
+
+/// The process using the most CPU
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopProcess {
+    pub name: String,
+    pub pid: u32,
+    /// Share of one core, so past 100% on a multithreaded process
+    pub cpu: f32,
+}
+
+impl TopProcess {
+    pub fn icon(&self) -> &'static str {
+        "\u{f0128}" // nf-md-chart_bar
+    }
+
+    /// Name and usage, e.g. `WindowServer 45%`
+    pub fn label(&self) -> String {
+        format!("{} {:.0}%", self.name, self.cpu)
+    }
+}
+
+/// Parse the first row of `ps -Aro pid=,pcpu=,comm=`, which lists processes by CPU usage
+///
+/// `comm` is the executable's path, which may hold spaces, so the name is what follows its last slash.
+fn parse_ps_top(stdout: &str) -> Option<TopProcess> {
+    let line = stdout.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let (pid, rest) = line.split_once(char::is_whitespace)?;
+    let (cpu, path) = rest.trim_start().split_once(char::is_whitespace)?;
+    let name = path.trim().rsplit('/').next().filter(|name| !name.is_empty())?;
+    Some(TopProcess { name: name.to_string(), pid: pid.parse().ok()?, cpu: cpu.parse().ok()? })
+}
+
+/// Get the process using the most CPU, by the decaying average `ps` reports
+pub fn get_top_process() -> Result<TopProcess, String> {
+    let output = Command::new("ps")
+        .args(["-Aro", "pid=,pcpu=,comm="])
+        .output()
+        .map_err(|e| format!("Failed to run ps: {}", e))?;
+    parse_ps_top(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| "No processes in ps output".to_string())
+}
+
+/// Bytes moved over a network interface since boot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkInfo {
+    pub interface: String,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+/// Parse the interface counters from `netstat -ib -I <interface>` output
+fn parse_netstat(interface: &str, stdout: &str) -> Option<NetworkInfo> {
+    let mut lines = stdout.lines();
+    let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let ibytes = header.iter().position(|column| *column == "Ibytes")?;
+    let obytes = header.iter().position(|column| *column == "Obytes")?;
+
+    // The link-level row has the counters for the whole interface and every column filled in
+    let row: Vec<&str> = lines
+        .find(|line| line.contains("<Link#"))?
+        .split_whitespace()
+        .collect();
+    Some(NetworkInfo {
+        interface: interface.to_string(),
+        bytes_in: row.get(ibytes)?.parse().ok()?,
+        bytes_out: row.get(obytes)?.parse().ok()?,
+    })
+}
+
+/// Get the byte counters of a network interface, from getifaddrs or else `netstat`
+pub fn get_network(interface: &str) -> Option<NetworkInfo> {
+    if let Some(info) = network::counters(|name| name == interface).pop() {
+        return Some(info);
+    }
+    let output = Command::new("netstat").args(["-ib", "-I", interface]).output().ok()?;
+    parse_netstat(interface, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// Download and upload rates in bytes per second
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThroughputInfo {
+    /// The interface or family counted, None while offline
+    pub interface: Option<String>,
+    pub down: u64,
+    pub up: u64,
+}
+
+impl ThroughputInfo {
+    pub fn icon(&self) -> &'static str {
+        if self.interface.is_some() {
+            "\u{f06f3}" // nf-md-network
+        } else {
+            "\u{f0c9b}" // nf-md-network_off
+        }
+    }
+
+    /// Both rates, e.g. `↓ 1.2 MB/s ↑ 48 KB/s`
+    pub fn label(&self) -> String {
+        format!("\u{2193} {} \u{2191} {}", format_rate(self.down), format_rate(self.up))
+    }
+}
+
+/// A rate in KB/s, or MB/s from 1000 KB/s up
+fn format_rate(bytes_per_second: u64) -> String {
+    if bytes_per_second < 999_500 {
+        format!("{} KB/s", (bytes_per_second + 500) / 1000)
+    } else {
+        format!("{:.1} MB/s", bytes_per_second as f64 / 1_000_000.0)
+    }
+}
+
+/// Counters at one sample, to compute rates from at the next
+#[derive(Debug, Clone)]
+struct NetworkSample {
+    interface: String,
+    at: Instant,
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+fn network_sample_lock() -> &'static Mutex<Option<NetworkSample>> {
+    static SAMPLE: OnceLock<Mutex<Option<NetworkSample>>> = OnceLock::new();
+    SAMPLE.get_or_init(|| Mutex::new(None))
+}
+
+/// Rates between two samples; zero after a switch of interface or when the counters went back
+fn throughput(previous: Option<&NetworkSample>, current: &NetworkSample) -> ThroughputInfo {
+    let mut info = ThroughputInfo { interface: Some(current.interface.clone()), ..ThroughputInfo::default() };
+    let Some(previous) = previous.filter(|previous| previous.interface == current.interface) else {
+        return info;
+    };
+    let elapsed = current.at.duration_since(previous.at).as_secs_f64();
+    if elapsed <= 0.0 || current.bytes_in < previous.bytes_in || current.bytes_out < previous.bytes_out {
+        return info;
+    }
+    info.down = ((current.bytes_in - previous.bytes_in) as f64 / elapsed).round() as u64;
+    info.up = ((current.bytes_out - previous.bytes_out) as f64 / elapsed).round() as u64;
+    info
+}
+
+/// Sample the configured interfaces and get the rates since the last sample
+///
+/// The first sample, and the first after switching interfaces, has nothing to
+/// compare with and reports zero.
+pub fn get_throughput(selection: &NetworkInterface) -> Result<ThroughputInfo, String> {
+    let (interface, counters) = match selection {
+        NetworkInterface::Auto => {
+            let Some(interface) = network::default_interface() else {
+                return Ok(ThroughputInfo::default());
+            };
+            let counters = get_network(&interface).into_iter().collect();
+            (interface, counters)
+        }
+        NetworkInterface::Named(name) => {
+            let mut counters = network::counters(|interface| selection.matches(interface));
+            if counters.is_empty() {
+                counters.extend(get_network(name));
+            }
+            (name.clone(), counters)
+        }
+    };
+    if counters.is_empty() {
+        return Err(format!("No byte counters for {}", interface));
+    }
+
+    let current = NetworkSample {
+        interface,
+        at: Instant::now(),
+        bytes_in: counters.iter().map(|c| c.bytes_in).sum(),
+        bytes_out: counters.iter().map(|c| c.bytes_out).sum(),
+    };
+    let mut last = network_sample_lock().lock().map_err(|e| e.to_string())?;
+    let info = throughput(last.as_ref(), &current);
+    *last = Some(current);
+    Ok(info)
+}
+
+/// Parse the bytes read and written over every disk from `ioreg -c IOBlockStorageDriver -r -w 0 -k Statistics`
+fn parse_ioreg_statistics(stdout: &str) -> Option<(u64, u64)> {
+    let counter = |line: &str, key: &str| -> Option<u64> {
+        let start = line.find(key)? + key.len();
+        let digits = line[start..].split(|c: char| !c.is_ascii_digit()).next()?;
+        digits.parse().ok()
+    };
+    let drivers: Vec<&str> = stdout.lines().filter(|line| line.contains("\"Statistics\" = {")).collect();
+    if drivers.is_empty() {
+        return None;
+    }
+    let read = drivers.iter().filter_map(|line| counter(line, "\"Bytes (Read)\"=")).sum();
+    let written = drivers.iter().filter_map(|line| counter(line, "\"Bytes (Write)\"=")).sum();
+    Some((read, written))
+}
+
+/// Get the bytes read and written over every disk, from IOKit or else `ioreg`
+fn get_disk_counters() -> Option<(u64, u64)> {
+    if let Some(counters) = disk::counters() {
+        return Some(counters);
+    }
+    let output = Command::new("ioreg")
+        .args(["-c", "IOBlockStorageDriver", "-r", "-w", "0", "-k", "Statistics"])
+        .output()
+        .ok()?;
+    parse_ioreg_statistics(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Disk read and write rates in bytes per second
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskInfo {
+    pub read: u64,
+    pub write: u64,
+}
+
+impl DiskInfo {
+    pub fn icon(&self) -> &'static str {
+        "\u{f02ca}" // nf-md-harddisk
+    }
 
-        let low = BatteryInfo { percentage: 5, is_charging: false };
+    /// Both rates, e.g. `R 12.4 MB/s W 320 KB/s`
+    pub fn label(&self) -> String {
+        format!("R {} W {}", format_rate(self.read), format_rate(self.write))
+    }
+}
+
+/// Disk counters at one sample, to compute rates from at the next
+#[derive(Debug, Clone, Copy)]
+struct DiskSample {
+    at: Instant,
+    read: u64,
+    written: u64,
+}
+
+fn disk_sample_lock() -> &'static Mutex<Option<DiskSample>> {
+    static SAMPLE: OnceLock<Mutex<Option<DiskSample>>> = OnceLock::new();
+    SAMPLE.get_or_init(|| Mutex::new(None))
+}
+
+/// Rates between two samples; zero when the counters went back, as when a disk is ejected
+fn disk_rates(previous: Option<&DiskSample>, current: &DiskSample) -> DiskInfo {
+    let Some(previous) = previous else {
+        return DiskInfo::default();
+    };
+    let elapsed = current.at.duration_since(previous.at).as_secs_f64();
+    if elapsed <= 0.0 || current.read < previous.read || current.written < previous.written {
+        return DiskInfo::default();
+    }
+    DiskInfo {
+        read: ((current.read - previous.read) as f64 / elapsed).round() as u64,
+        write: ((current.written - previous.written) as f64 / elapsed).round() as u64,
+    }
+}
+
+/// Sample the disk counters and get the rates since the last sample
+///
+/// The first sample has nothing to compare with and reports zero.
+pub fn get_disk() -> Result<DiskInfo, String> {
+    let (read, written) = get_disk_counters().ok_or_else(|| "No disk statistics".to_string())?;
+    let current = DiskSample { at: Instant::now(), read, written };
+    let mut last = disk_sample_lock().lock().map_err(|e| e.to_string())?;
+    let info = disk_rates(last.as_ref(), &current);
+    *last = Some(current);
+    Ok(info)
+}
+
+/// Wi-Fi network, signal strength and link state
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WifiInfo {
+    /// None while disconnected, or when macOS withholds the network name
+    pub ssid: Option<String>,
+    /// Signal strength in dBm, where CoreWLAN reports it
+    pub rssi: Option<i32>,
+    pub connected: bool,
+}
+
+impl WifiInfo {
+    /// Signal bars from 1 to 4, 0 while disconnected; a connection of unknown strength gets 4
+    pub fn bars(&self) -> u8 {
+        if !self.connected {
+            return 0;
+        }
+        match self.rssi {
+            Some(rssi) if rssi < -75 => 1,
+            Some(rssi) if rssi < -67 => 2,
+            Some(rssi) if rssi < -55 => 3,
+            _ => 4,
+        }
+    }
+
+    /// Get the icon for the signal bars, or the disconnected icon
+    pub fn icon(&self) -> &'static str {
+        match self.bars() {
+            0 => "\u{f092e}", // nf-md-wifi_strength_off_outline
+            1 => "\u{f091f}",
+            2 => "\u{f0922}",
+            3 => "\u{f0925}",
+            _ => "\u{f0928}",
+        }
+    }
+}
+
+/// Parse the link state and network from `ipconfig getsummary` output, which has no RSSI
+fn parse_wifi_summary(stdout: &str) -> WifiInfo {
+    let active = stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("LinkStatusActive : "))
+        .is_some_and(|status| status.trim() == "TRUE");
+    let ssid = rules::parse_ssid(stdout);
+    WifiInfo { connected: active && ssid.is_some(), ssid: ssid.filter(|_| active), rssi: None }
+}
+
+/// Get the Wi-Fi network and signal strength
+///
+/// Reads CoreWLAN directly, falling back to `ipconfig getsummary` on `en0`
+/// where there is no CoreWLAN interface.
+pub fn get_wifi() -> Result<WifiInfo, String> {
+    if let Some(info) = wifi::current() {
+        return Ok(info);
+    }
+    let output = Command::new("ipconfig")
+        .args(["getsummary", "en0"])
+        .output()
+        .map_err(|e| format!("Failed to run ipconfig: {}", e))?;
+    Ok(parse_wifi_summary(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Microsoft Teams notification information
+#[derive(Debug, Clone, Default)]
+pub struct TeamsInfo {
+    pub running: bool,
+    pub notification_count: u32,
+}
+
+fn teams_lock() -> &'static RwLock<BadgeAppConfig> {
+    static TEAMS: OnceLock<RwLock<BadgeAppConfig>> = OnceLock::new();
+    TEAMS.get_or_init(|| RwLock::new(BadgeAppConfig::default()))
+}
+
+/// Set the app, Dock label, colors and click refresh schedule of the Teams item from the config
+pub fn set_teams_config(config: BadgeAppConfig) {
+    if let Ok(mut t) = teams_lock().write() {
+        *t = config;
+    }
+}
+
+/// The configured app, Dock label, colors and click refresh schedule of the Teams item
+pub fn teams_config() -> BadgeAppConfig {
+    teams_lock().read().map(|t| t.clone()).unwrap_or_default()
+}
+
+impl TeamsInfo {
+    /// Get the appropriate icon (Microsoft Teams icon)
+    pub fn icon(&self) -> &'static str {
+        "󰊻" // nf-md-microsoft_teams
+    }
+
+    /// Get the icon color based on state
+    pub fn icon_color(&self) -> String {
+        let config = teams_config();
+        if !self.running {
+            config.inactive_color
+        } else if self.notification_count > 0 {
+            config.badge_color
+        } else {
+            config.color
+        }
+    }
+
+    /// Get the border color based on state
+    pub fn border_color(&self) -> String {
+        let config = teams_config();
+        if self.notification_count > 0 {
+            config.badge_color
+        } else {
+            config.border_color
+        }
+    }
+}
+
+/// Quote a string for use in AppleScript
+pub fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Get Microsoft Teams notification count
+pub fn get_teams_notifications() -> Result<TeamsInfo, String> {
+    let config = teams_config();
+    let mut info = TeamsInfo::default();
+
+    // Check if the app is running (MSTeams is the new Teams app process name)
+    let running = Command::new("pgrep")
+        .args(["-x", &config.process])
+        .output()
+        .map(|o| o.status.success())
+        .map_err(|e| format!("Failed to run pgrep: {}", e))?;
+
+    info.running = running;
+
+    if !running {
+        return Ok(info);
+    }
+
+    // Get notification count from Dock badge via AppleScript
+    let script = format!(
+        r#"
+tell application "System Events"
+    tell UI element {} of list 1 of process "Dock"
+        try
+            set badgeValue to value of attribute "AXStatusLabel"
+            if badgeValue is not missing value then
+                return badgeValue
+            end if
+        end try
+    end tell
+end tell
+return "0"
+"#,
+        applescript_string(&config.dock_label)
+    );
+
+    // System Events can take a while to answer, or never does while the Dock is restarting
+    let mut command = Command::new("osascript");
+    command.args(["-e", &script]);
+    let output = runtime::output(command, Duration::from_secs(10)).map_err(|e| format!("osascript failed: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("osascript failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Extract only digits from the result
+    let count_str: String = stdout.trim().chars().filter(|c| c.is_ascii_digit()).collect();
+    info.notification_count = count_str.parse().unwrap_or(0);
+
+    Ok(info)
+}
+
+/// Custom item output
+#[derive(Debug, Clone, Default)]
+pub struct CustomInfo {
+    /// Icon from `icon_command` or the script, or the static `icon`
+    pub icon: Option<String>,
+    pub label: String,
+    /// Colors and fonts set by the script
+    pub style: ItemStyle,
+    /// Whether the script shows or hides the item; `None` leaves it as it is
+    pub visible: Option<bool>,
+}
+
+/// First line of a command's output, as sketchybar labels are single-line
+fn first_line(stdout: &str) -> String {
+    stdout.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default().to_string()
+}
+
+/// Run a shell command, returning the first line of its output
+pub fn run_shell(command: &str) -> Option<String> {
+    match Command::new("sh").args(["-c", command]).output() {
+        Ok(output) if output.status.success() => Some(first_line(&String::from_utf8_lossy(&output.stdout))),
+        Ok(output) => {
+            warn!("`{}` failed: {}", command, String::from_utf8_lossy(&output.stderr).trim());
+            None
+        }
+        Err(e) => {
+            error!("Failed to run `{}`: {}", command, e);
+            None
+        }
+    }
+}
+
+/// Run a custom item's commands or script; `None` when the label command or the script fails
+pub fn get_custom(item: &CustomItem) -> Option<CustomInfo> {
+    if let Some(path) = item.script_path() {
+        let mut info = scripting::run(&path)?;
+        info.icon = info.icon.or_else(|| item.icon.clone());
+        return Some(info);
+    }
+
+    let label = run_shell(&item.command)?;
+    let icon = match &item.icon_command {
+        Some(command) => run_shell(command).or_else(|| item.icon.clone()),
+        None => item.icon.clone(),
+    };
+    Some(CustomInfo { icon, label, ..CustomInfo::default() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_battery_icons() {
+        let high = BatteryInfo { percentage: 95, is_charging: false, ..BatteryInfo::default() };
+        assert_eq!(high.icon(), "󱊣");
+
+        let is_charging = BatteryInfo { percentage: 50, is_charging: true, ..BatteryInfo::default() };
+        assert_eq!(is_charging.icon(), "\u{f0e7}"); // nf-fa-bolt
+
+        let low = BatteryInfo { percentage: 5, is_charging: false, ..BatteryInfo::default() };
         assert_eq!(low.icon(), "󰂎");
     }
 
+    #[test]
+    fn test_battery_from_description() {
+        let description = power::BatteryDescription {
+            current_capacity: 85,
+            max_capacity: 100,
+            time_to_empty: 134,
+            time_to_full: -1,
+            ..power::BatteryDescription::default()
+        };
+        let info = BatteryInfo::from_description(description, None, Some(-11.6));
+        assert_eq!((info.percentage, info.is_charging, info.charged), (85, false, false));
+        assert_eq!(info.minutes_remaining, Some(134));
+        assert_eq!(info.label(), "85% 2:14");
+
+        let charging = power::BatteryDescription { on_ac: true, charging: true, time_to_full: 65, ..description };
+        let info = BatteryInfo::from_description(charging, Some(96), Some(23.4));
+        assert_eq!((info.is_charging, info.charged, info.minutes_remaining), (true, false, Some(65)));
+
+        // Optimized charging holds the battery at 80%: plugged in, not charging, no estimate
+        let held = power::BatteryDescription { on_ac: true, current_capacity: 80, time_to_full: 0, ..description };
+        let info = BatteryInfo::from_description(held, Some(96), Some(0.0));
+        assert_eq!((info.charged, info.minutes_remaining), (true, None));
+        assert_eq!(info.label(), "80%");
+    }
+
+    #[test]
+    fn test_parse_pmset_battery() {
+        let stdout = "Now drawing from 'Battery Power'\n \
+                      -InternalBattery-0 (id=4653155)\t85%; discharging; 2:14 remaining present: true\n";
+        let info = parse_pmset_battery(stdout).unwrap();
+        assert_eq!((info.percentage, info.is_charging, info.minutes_remaining), (85, false, Some(134)));
+
+        let stdout = "Now drawing from 'AC Power'\n \
+                      -InternalBattery-0 (id=4653155)\t80%; AC attached; not charging present: true\n";
+        let info = parse_pmset_battery(stdout).unwrap();
+        assert_eq!((info.percentage, info.is_charging, info.charged), (80, true, true));
+
+        let stdout = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t45%; charging; 1:05 remaining\n";
+        assert_eq!(parse_pmset_battery(stdout).unwrap().minutes_remaining, Some(65));
+        assert!(parse_pmset_battery("Now drawing from 'AC Power'\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_ioreg_battery() {
+        let stdout = concat!(
+            "+-o AppleSmartBattery  <class AppleSmartBattery>\n",
+            "    {\n",
+            "      \"BatteryData\" = {\"CycleCount\"=1,\"DesignCapacity\"=1}\n",
+            "      \"AppleRawMaxCapacity\" = 4102\n",
+            "      \"CycleCount\" = 312\n",
+            "      \"DesignCapacity\" = 4563\n",
+            "      \"MaxCapacity\" = 100\n",
+            "    }\n",
+        );
+        let health = parse_ioreg_battery(stdout);
+        assert_eq!(health.cycle_count, Some(312));
+        assert_eq!((health.max_capacity, health.design_capacity), (Some(4102), Some(4563)));
+        assert_eq!(health.capacity_percentage(), Some(90));
+
+        // Intel Macs report MaxCapacity in mAh
+        let health = parse_ioreg_battery("  \"MaxCapacity\" = 5103\n  \"DesignCapacity\" = 5103\n");
+        assert_eq!(health.capacity_percentage(), Some(100));
+        assert_eq!(parse_ioreg_battery(""), power::BatteryHealth::default());
+    }
+
     #[test]
     fn test_volume_icons() {
         let high = VolumeInfo { percentage: 80, muted: false };
@@ -308,10 +1974,344 @@ mod tests {
         assert_eq!(zero.icon(), "\u{f244}");
     }
 
+    #[test]
+    fn test_applescript_string() {
+        assert_eq!(applescript_string("Microsoft Teams"), "\"Microsoft Teams\"");
+        assert_eq!(applescript_string("a \"b\" \\c"), "\"a \\\"b\\\" \\\\c\"");
+    }
+
+    #[test]
+    fn test_first_line() {
+        assert_eq!(first_line("\n  +12°C  \nsunny\n"), "+12°C");
+        assert_eq!(first_line(""), "");
+    }
+
+    #[test]
+    fn test_align_delay() {
+        let interval = Duration::from_secs(15);
+        assert_eq!(align_delay(Duration::from_secs(1_700_000_047), interval), Duration::from_secs(8));
+        assert_eq!(align_delay(Duration::from_secs(1_700_000_040), interval), interval);
+        assert_eq!(align_delay(Duration::from_millis(1_700_000_059_250), Duration::from_secs(1)), Duration::from_millis(750));
+    }
+
+    #[test]
+    fn test_jitter() {
+        assert_eq!(jitter(0), Duration::ZERO);
+        assert!((0..20).all(|_| jitter(3) <= Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_outdated_packages() {
+        let stdout = "git\nnode\n\npython@3.12\n";
+        assert_eq!(outdated_packages(stdout, &[]).len(), 3);
+        assert_eq!(outdated_packages(stdout, &["node", "wget"]), ["git", "python@3.12"]);
+    }
+
+    #[test]
+    fn test_upgrade_summary() {
+        let stdout = "==> Upgrading 2 outdated packages:\ngit 2.42.0 -> 2.43.0\nnode 21.1.0 -> 21.2.0\n\
+                      ==> Fetching git\n==> Upgrading git\n  2.42.0 -> 2.43.0\n";
+        assert_eq!(upgrade_summary(stdout), "Upgraded git and node");
+
+        let casks = "==> Upgrading 1 outdated package:\nfirefox 119.0 -> 120.0\n";
+        assert_eq!(upgrade_summary(&format!("{}{}", stdout, casks)), "Upgraded git, node and firefox");
+
+        let many = "==> Upgrading 5 outdated packages:\na 1 -> 2\nb 1 -> 2\nc 1 -> 2\nd 1 -> 2\ne 1 -> 2\n";
+        assert_eq!(upgrade_summary(many), "Upgraded a, b, c and 2 more");
+        assert_eq!(upgrade_summary(""), "Everything is up to date");
+    }
+
+    #[test]
+    fn test_parse_top() {
+        let stdout = "Processes: 512 total\n\
+                      CPU usage: 2.50% user, 7.50% sys, 90.0% idle\n\
+                      PhysMem: 8G used (1500M wired), 8G unused.\n\
+                      Processes: 511 total\n\
+                      CPU usage: 20.1% user, 10.0% sys, 69.9% idle\n\
+                      PhysMem: 12G used (2150M wired, 3G compressor), 4096M unused.\n";
+        let info = parse_top(stdout).unwrap();
+        assert_eq!(info.cpu_percentage, 30);
+        assert_eq!(info.ram_percentage, 75);
+        assert!(parse_top("Processes: 1 total\n").is_none());
+    }
+
+    #[test]
+    fn test_cpu_usage() {
+        assert_eq!(cpu_usage((1_000, 4_000), (1_300, 5_000)), 30);
+        assert_eq!(cpu_usage((0, 0), (500, 2_000)), 25);
+        assert_eq!(cpu_usage((1_000, 4_000), (1_000, 4_000)), 0);
+        assert_eq!(cpu_icon(85), "󰻠");
+    }
+
+    #[test]
+    fn test_parse_netstat() {
+        let stdout = "Name  Mtu   Network       Address            Ipkts Ierrs     Ibytes    Opkts Oerrs     Obytes  Coll\n\
+                      en0   1500  <Link#11>   a4:83:e7:00:00:01  1000     0    5000000      800     0    1200000     0\n\
+                      en0   1500  192.168.1     192.168.1.20      900     -    4000000      700     -    1000000     -\n";
+        let info = parse_netstat("en0", stdout).unwrap();
+        assert_eq!((info.bytes_in, info.bytes_out), (5_000_000, 1_200_000));
+        assert!(parse_netstat("en0", "").is_none());
+    }
+
+    #[test]
+    fn test_audio_output() {
+        let devices = parse_switch_audio_source("MacBook Pro Speakers\nAirPods Pro\n\nDELL U2720Q (DisplayPort)\n");
+        assert_eq!(devices, vec!["MacBook Pro Speakers", "AirPods Pro", "DELL U2720Q (DisplayPort)"]);
+
+        let info = |current: &str| AudioOutputInfo { current: Some(current.to_string()), devices: devices.clone() };
+        assert_eq!(info("AirPods Pro").icon(), "\u{f02cb}");
+        assert_eq!(info("DELL U2720Q (DisplayPort)").icon(), "\u{f0379}");
+        assert_eq!(info("MacBook Pro Speakers").icon(), "\u{f04c3}");
+    }
+
+    #[test]
+    fn test_throughput() {
+        let at = Instant::now();
+        let sample = |interface: &str, secs, bytes_in, bytes_out| NetworkSample {
+            interface: interface.to_string(),
+            at: at + Duration::from_secs(secs),
+            bytes_in,
+            bytes_out,
+        };
+        let first = sample("en0", 0, 1_000_000, 200_000);
+
+        let info = throughput(Some(&first), &sample("en0", 2, 3_500_000, 296_000));
+        assert_eq!((info.down, info.up), (1_250_000, 48_000));
+        assert_eq!(info.label(), "\u{2193} 1.2 MB/s \u{2191} 48 KB/s");
+
+        assert_eq!(throughput(None, &first).down, 0);
+        assert_eq!(throughput(Some(&first), &sample("utun", 2, 3_500_000, 296_000)).down, 0);
+        assert_eq!(throughput(Some(&first), &sample("en0", 2, 500, 296_000)).down, 0);
+        assert_eq!(format_rate(999_400), "999 KB/s");
+        assert_eq!(format_rate(999_600), "1.0 MB/s");
+    }
+
+    #[test]
+    fn test_parse_ps_top() {
+        let stdout = "  412 148.3 /Applications/Google Chrome.app/Contents/MacOS/Google Chrome\n\
+                      \x20 151  12.0 /System/Library/PrivateFrameworks/SkyLight.framework/Resources/WindowServer\n";
+        let top = parse_ps_top(stdout).unwrap();
+        assert_eq!(top, TopProcess { name: "Google Chrome".to_string(), pid: 412, cpu: 148.3 });
+        assert_eq!(top.label(), "Google Chrome 148%");
+        assert_eq!(parse_ps_top("  1   0.0 launchd\n").unwrap().name, "launchd");
+        assert!(parse_ps_top("").is_none());
+    }
+
+    #[test]
+    fn test_thermal_label() {
+        let info = ThermalInfo { state: ThermalState::Serious, temperature: Some(91.6) };
+        assert_eq!(info.label(), "92\u{b0}C");
+        assert_eq!(info.icon(), "\u{f10c2}");
+        assert_eq!(ThermalInfo { temperature: None, ..info }.label(), "serious");
+    }
+
+    #[test]
+    fn test_fan_label() {
+        let info = FanInfo { rpms: vec![2210.4, 2189.6] };
+        assert_eq!(info.label(), "2210 / 2190 rpm");
+        assert_eq!(info.icon(), "\u{f0210}");
+        assert_eq!(FanInfo { rpms: vec![0.0] }.icon(), "\u{f081d}");
+        assert!(FanInfo::default().fanless());
+    }
+
+    #[test]
+    fn test_scrolled() {
+        assert_eq!(scrolled(50, 1, 5), 55);
+        assert_eq!(scrolled(50, -3, 5), 35);
+        assert_eq!(scrolled(98, 2, 5), 100);
+        assert_eq!(scrolled(3, -1, 5), 0);
+        assert_eq!(scrolled(50, 4, 0), 50);
+    }
+
+    #[test]
+    fn test_brightness_icon() {
+        assert_eq!(BrightnessInfo { percentage: 100 }.icon(), "\u{f00e0}");
+        assert_eq!(BrightnessInfo { percentage: 50 }.icon(), "\u{f00df}");
+        assert_eq!(BrightnessInfo { percentage: 0 }.icon(), "\u{f00de}");
+        assert_eq!(BrightnessInfo { percentage: 69 }.label(), "69%");
+    }
+
+    #[test]
+    fn test_calendar_label() {
+        let event = calendar::Event { title: "Standup".to_string(), start: "2026-10-16 09:30".to_string(), link: None };
+        let info = |minutes| CalendarInfo { event: Some(event.clone()), minutes };
+        assert_eq!(info(25).label(), "Standup in 25m");
+        assert_eq!(info(25).icon(), "\u{f073}");
+        assert_eq!(info(65).label(), "Standup in 1h 5m");
+        assert_eq!(info(0).label(), "Standup now");
+        assert_eq!(info(-10).label(), "Standup now");
+        assert_eq!(CalendarInfo::default().label(), "");
+
+        let link = Some("https://meet.google.com/abc-defg-hij".to_string());
+        let meeting = CalendarInfo { event: Some(calendar::Event { link, ..event }), minutes: 5 };
+        assert_eq!(meeting.icon(), "\u{f03d}");
+    }
+
+    #[test]
+    fn test_zoom_icon() {
+        assert_eq!(ZoomInfo { meeting: zoom::Meeting::Muted }.icon(), Some("\u{f036d}"));
+        assert_eq!(ZoomInfo { meeting: zoom::Meeting::Live }.icon(), Some("\u{f036c}"));
+        assert_eq!(ZoomInfo::default().icon(), None);
+    }
+
+    #[test]
+    fn test_sun_info() {
+        let next = sun::Next { transition: sun::Transition::Sunset, at: 1_792_167_060 };
+        let info = SunInfo { next: Some(next), offset: 7200 };
+        assert_eq!((info.icon(), info.label().as_str()), (Some("\u{f059b}"), "18:11"));
+        let polar = SunInfo { next: None, offset: 3600 };
+        assert_eq!((polar.icon(), polar.label().as_str()), (None, ""));
+    }
+
+    #[test]
+    fn test_crypto_label() {
+        let quotes = vec![
+            crypto::Quote { coin: "bitcoin".to_string(), price: 67012.4, change: Some(-1.25) },
+            crypto::Quote { coin: "ethereum".to_string(), price: 3456.0, change: None },
+        ];
+        let info = CryptoInfo { quotes, currency: "eur".to_string(), breached: false };
+        assert_eq!(info.label(), "BTC €67,012 -1.2%  ETH €3,456");
+    }
+
+    #[test]
+    fn test_focus_mode_label() {
+        let focus = focus_mode::Focus { identifier: "com.apple.focus.work".to_string(), name: "Work".to_string() };
+        let info = FocusModeInfo { focus: Some(focus) };
+        assert_eq!(info.icon(), "\u{f0b1}");
+        assert_eq!(info.label(true), "Work");
+        assert_eq!(info.label(false), "");
+        assert_eq!(FocusModeInfo::default().icon(), "\u{f186}");
+        assert_eq!(FocusModeInfo::default().label(true), "");
+    }
+
+    #[test]
+    fn test_load_percentages() {
+        let info = LoadInfo { averages: [3.6, 3.04, 2.4], cores: 8 };
+        assert_eq!(info.percentages(), [45, 38, 30]);
+        assert_eq!(info.label(), "45% 38% 30%");
+        assert_eq!(LoadInfo { averages: [12.5, 0.0, 0.0], cores: 0 }.percentages(), [1250, 0, 0]);
+    }
+
+    #[test]
+    fn test_disk_rates() {
+        let stdout = "+-o IOBlockStorageDriver  <class IOBlockStorageDriver, id 0x100000412>\n    {\n      \
+                      \"Statistics\" = {\"Operations (Write)\"=5120,\"Bytes (Read)\"=4096000,\"Errors (Write)\"=0,\
+                      \"Bytes (Write)\"=1024000}\n    }\n      \"Statistics\" = {\"Bytes (Read)\"=904000,\
+                      \"Bytes (Write)\"=0}\n";
+        assert_eq!(parse_ioreg_statistics(stdout), Some((5_000_000, 1_024_000)));
+        assert_eq!(parse_ioreg_statistics(""), None);
+
+        let at = Instant::now();
+        let first = DiskSample { at, read: 5_000_000, written: 1_024_000 };
+        let second = DiskSample { at: at + Duration::from_secs(2), read: 29_800_000, written: 1_664_000 };
+        let info = disk_rates(Some(&first), &second);
+        assert_eq!(info, DiskInfo { read: 12_400_000, write: 320_000 });
+        assert_eq!(info.label(), "R 12.4 MB/s W 320 KB/s");
+        assert_eq!(disk_rates(None, &first), DiskInfo::default());
+        assert_eq!(disk_rates(Some(&second), &first), DiskInfo::default());
+    }
+
+    #[test]
+    fn test_wifi() {
+        let stdout = "<dictionary> {\n  InterfaceType : WiFi\n  LinkStatusActive : TRUE\n  SSID : Office\n}\n";
+        let info = parse_wifi_summary(stdout);
+        assert_eq!(info, WifiInfo { ssid: Some("Office".to_string()), rssi: None, connected: true });
+        assert_eq!(info.bars(), 4);
+
+        let disconnected = parse_wifi_summary("<dictionary> {\n  LinkStatusActive : FALSE\n  SSID : Office\n}\n");
+        assert_eq!(disconnected, WifiInfo::default());
+        assert_eq!(disconnected.icon(), "\u{f092e}");
+
+        let weak = WifiInfo { rssi: Some(-80), ..info.clone() };
+        let fair = WifiInfo { rssi: Some(-70), ..info.clone() };
+        let good = WifiInfo { rssi: Some(-60), ..info.clone() };
+        assert_eq!((weak.bars(), fair.bars(), good.bars()), (1, 2, 3));
+    }
+
     #[test]
     fn test_clock() {
         let clock = get_clock();
         assert!(clock.contains('/'));
         assert!(clock.contains(':'));
+
+        let interval = Duration::from_secs(15);
+        assert!(clock_tick(interval) <= interval);
+        set_clock_format(ClockFormat { align: false, ..ClockFormat::default() });
+        assert_eq!(clock_tick(interval), interval);
+        set_clock_format(ClockFormat { seconds: true, ..ClockFormat::default() });
+        assert!(clock_tick(interval) <= Duration::from_secs(1));
+        set_clock_format(ClockFormat::default());
+    }
+
+    #[test]
+    fn test_clock_zones() {
+        assert_eq!(zone_city("America/New_York"), "New York");
+        assert_eq!(zone_city("UTC"), "UTC");
+
+        let info = ClockInfo {
+            time: "16/10 09:30".to_string(),
+            zones: vec![("New York".to_string(), "03:30".to_string()), ("Tokyo".to_string(), "16:30".to_string())],
+        };
+        assert_eq!(info.label(false), "16/10 09:30");
+        assert_eq!(info.label(true), "16/10 09:30 · New York 03:30 · Tokyo 16:30");
+    }
+
+    struct Uptime(u64);
+
+    impl Provider for Uptime {
+        type Info = u64;
+
+        fn name(&self) -> &'static str {
+            "uptime"
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_secs(self.0)
+        }
+
+        fn refresh(&self) -> Result<Option<u64>, String> {
+            Ok(Some(self.0))
+        }
+
+        fn render(&self, _: &u64) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn describe(&self, seconds: &u64) -> Value {
+            serde_json::json!({"seconds": seconds})
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        assert!(find("uptime").is_none());
+        register(Uptime(60));
+        register(Uptime(30));
+
+        let uptime = registered().into_iter().filter(|provider| provider.name() == "uptime").collect::<Vec<_>>();
+        assert_eq!(uptime.len(), 1);
+        assert_eq!(find("uptime").unwrap().interval(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let interval = Duration::from_secs(60);
+        let mut health = Health::default();
+        assert_eq!(health.delay(interval), interval);
+
+        for _ in 0..3 {
+            health = record_failure("flaky", "timed out".to_string());
+        }
+        assert_eq!(health, Health { failures: 3, last_error: Some("timed out".to_string()) });
+        assert!(health.degraded());
+        assert_eq!(health.delay(interval), Duration::from_secs(480));
+
+        // The backoff is capped, but never shortens an interval that is longer already
+        health.failures = 40;
+        assert_eq!(health.delay(interval), MAX_BACKOFF);
+        assert_eq!(health.delay(Duration::from_secs(7200)), Duration::from_secs(7200));
+
+        assert_eq!(record_success("flaky"), 3);
+        assert_eq!(super::health("flaky"), Health::default());
     }
 }
@@ -0,0 +1,103 @@
+//! Quiet mode: hides notification counts and alerts on calls and demos
+//!
+//! `sketchycli quiet on|off` forces the mode; `sketchycli quiet auto` hands
+//! it back to screen sharing detection, when `auto` is set in the config.
+
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// How quiet mode was set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct QuietState {
+    /// Set by `sketchycli quiet on|off`, overriding detection
+    forced: Option<bool>,
+    /// Whether screen sharing was detected at the last check
+    sharing: bool,
+}
+
+impl QuietState {
+    fn active(&self) -> bool {
+        self.forced.unwrap_or(self.sharing)
+    }
+}
+
+fn state_lock() -> &'static Mutex<QuietState> {
+    static STATE: OnceLock<Mutex<QuietState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(QuietState::default()))
+}
+
+/// Update the state, returning whether quiet mode turned on or off
+fn update(f: impl FnOnce(&mut QuietState)) -> bool {
+    match state_lock().lock() {
+        Ok(mut state) => {
+            let was_active = state.active();
+            f(&mut state);
+            state.active() != was_active
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether quiet mode is on
+pub fn active() -> bool {
+    state_lock().lock().map(|state| state.active()).unwrap_or(false)
+}
+
+/// Apply a `sketchycli quiet` command, returning whether quiet mode turned on or off
+pub fn command(command: &str) -> Result<bool, String> {
+    match command {
+        "on" => Ok(update(|state| state.forced = Some(true))),
+        "off" => Ok(update(|state| state.forced = Some(false))),
+        "toggle" => Ok(update(|state| state.forced = Some(!state.active()))),
+        "auto" => Ok(update(|state| state.forced = None)),
+        _ => Err(format!("Unknown quiet command: {} (expected on, off, toggle or auto)", command)),
+    }
+}
+
+/// Record whether the screen is shared, returning whether quiet mode turned on or off
+pub fn set_sharing(sharing: bool) -> bool {
+    update(|state| state.sharing = sharing)
+}
+
+/// Describe the mode for `sketchycli quiet`
+pub fn status() -> String {
+    let state = state_lock().lock().map(|state| *state).unwrap_or_default();
+    let mode = if state.active() { "on" } else { "off" };
+    match (state.forced, state.sharing) {
+        (Some(_), _) => format!("{} (set with sketchycli quiet; `sketchycli quiet auto` to follow screen sharing)", mode),
+        (None, true) => format!("{} (screen sharing)", mode),
+        (None, false) => mode.to_string(),
+    }
+}
+
+/// Whether any of the processes that run during screen sharing is running
+pub fn screen_shared(processes: &[String]) -> bool {
+    processes.iter().any(|process| {
+        Command::new("pgrep")
+            .args(["-x", process])
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quiet_state() {
+        let mut state = QuietState::default();
+        assert!(!state.active());
+
+        state.sharing = true;
+        assert!(state.active());
+
+        // Forcing wins over detection both ways
+        state.forced = Some(false);
+        assert!(!state.active());
+        state.sharing = false;
+        state.forced = Some(true);
+        assert!(state.active());
+    }
+}
@@ -0,0 +1,120 @@
+//! Display rules: items hidden by time of day, power source, network and focused app
+//!
+//! The rules are matched on a timer and when the power source or focus
+//! changes. Hidden items are skipped by their handlers like disabled ones.
+
+use std::collections::BTreeSet;
+use std::process::Command;
+use std::sync::{OnceLock, RwLock};
+
+use sketchybartender::config::{PowerSource, RuleContext, RuleNeeds};
+
+use crate::{aerospace, power};
+
+fn hidden_lock() -> &'static RwLock<BTreeSet<String>> {
+    static HIDDEN: OnceLock<RwLock<BTreeSet<String>>> = OnceLock::new();
+    HIDDEN.get_or_init(|| RwLock::new(BTreeSet::new()))
+}
+
+/// Replace the items hidden by rules, returning the previous ones
+pub fn set_hidden(hidden: BTreeSet<String>) -> BTreeSet<String> {
+    match hidden_lock().write() {
+        Ok(mut h) => std::mem::replace(&mut *h, hidden),
+        Err(_) => BTreeSet::new(),
+    }
+}
+
+/// Whether a rule currently hides an item
+pub fn is_hidden(item: &str) -> bool {
+    hidden_lock().read().map(|h| h.contains(item)).unwrap_or(false)
+}
+
+/// Parse `date '+%H:%M %u'` output into minutes since midnight and the day of the week
+fn parse_date(stdout: &str) -> Option<(u16, u8)> {
+    let (time, weekday) = stdout.trim().split_once(' ')?;
+    let (hours, minutes) = time.split_once(':')?;
+    let minute = hours.parse::<u16>().ok()? * 60 + minutes.parse::<u16>().ok()?;
+    Some((minute, weekday.parse().ok()?))
+}
+
+/// Parse the power source from `pmset -g batt` output
+fn parse_power_source(stdout: &str) -> Option<PowerSource> {
+    if stdout.contains("'AC Power'") {
+        Some(PowerSource::Ac)
+    } else if stdout.contains("'Battery Power'") {
+        Some(PowerSource::Battery)
+    } else {
+        None
+    }
+}
+
+/// Parse the Wi-Fi network from `ipconfig getsummary` output
+pub fn parse_ssid(stdout: &str) -> Option<String> {
+    stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("SSID : "))
+        .map(|ssid| ssid.trim().to_string())
+        .filter(|ssid| !ssid.is_empty())
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output() {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        _ => None,
+    }
+}
+
+/// The power source from IOKit, or from `pmset` where IOKit has no answer
+pub fn power_source() -> Option<PowerSource> {
+    power::source().or_else(|| command_output("pmset", &["-g", "batt"]).and_then(|stdout| parse_power_source(&stdout)))
+}
+
+/// Minutes since midnight and the day of the week, Monday being 1
+pub fn time_of_day() -> (u16, u8) {
+    command_output("date", &["+%H:%M %u"])
+        .and_then(|stdout| parse_date(&stdout))
+        .unwrap_or_default()
+}
+
+/// Gather the state the rules look at, skipping what none of them need
+pub fn context(needs: RuleNeeds) -> RuleContext {
+    let (minute, weekday) = time_of_day();
+
+    RuleContext {
+        minute,
+        weekday,
+        power: needs.power.then(power_source).flatten(),
+        ssid: needs
+            .ssid
+            .then(|| command_output("ipconfig", &["getsummary", "en0"]))
+            .flatten()
+            .and_then(|stdout| parse_ssid(&stdout)),
+        app: needs.app.then(aerospace::get_focused_app).flatten().map(|app| app.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("09:30 1\n"), Some((570, 1)));
+        assert_eq!(parse_date("23:59 7"), Some((1439, 7)));
+        assert_eq!(parse_date("garbage"), None);
+    }
+
+    #[test]
+    fn test_parse_power_source() {
+        let ac = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=1234)\t100%; charged;";
+        assert_eq!(parse_power_source(ac), Some(PowerSource::Ac));
+        assert_eq!(parse_power_source("Now drawing from 'Battery Power'"), Some(PowerSource::Battery));
+    }
+
+    #[test]
+    fn test_parse_ssid() {
+        let summary = "<dictionary> {\n  BSSID : <redacted>\n  InterfaceType : WiFi\n  SSID : Office Wi-Fi\n}";
+        assert_eq!(parse_ssid(summary), Some("Office Wi-Fi".to_string()));
+        assert_eq!(parse_ssid("<dictionary> {\n  InterfaceType : WiFi\n}"), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! The tokio runtime behind the daemon's timers, socket clients and background work
+//!
+//! Handlers are synchronous and wait on external commands, so they run on the
+//! runtime's blocking pool rather than on its async workers, which only wait
+//! on timers and sockets. Timers share one scheduler task, which keeps them
+//! in a min-heap by when they are next due; each computes its delay again
+//! after every run and when [`reschedule`] wakes the scheduler, e.g. after a
+//! profile switch changed an interval. External commands can be given a
+//! timeout with [`output`]. [`shutdown`] ends the timers and tells socket tasks
+//! to wind down.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::io;
+use std::process::{Command, Output};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use sketchybartender::error;
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Handle of the running runtime, set by `start`
+static HANDLE: OnceLock<Handle> = OnceLock::new();
+
+/// Start the runtime; tasks keep running on its worker threads until it's dropped
+pub fn start() -> Runtime {
+    let runtime = Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("sketchybartender")
+        .build()
+        .expect("Failed to start the tokio runtime");
+    let _ = HANDLE.set(runtime.handle().clone());
+    runtime
+}
+
+fn handle() -> &'static Handle {
+    HANDLE.get().expect("runtime::start not called")
+}
+
+/// Run a future on the runtime
+pub fn spawn(future: impl Future<Output = ()> + Send + 'static) -> JoinHandle<()> {
+    handle().spawn(future)
+}
+
+/// Run blocking work, such as a handler, on the blocking pool
+///
+/// Falls back to a thread of its own before the runtime is started, e.g. in tests.
+pub fn background(f: impl FnOnce() + Send + 'static) {
+    match HANDLE.get() {
+        Some(handle) => drop(handle.spawn_blocking(f)),
+        None => drop(thread::spawn(f)),
+    }
+}
+
+fn rescheduled() -> &'static Notify {
+    static RESCHEDULED: OnceLock<Notify> = OnceLock::new();
+    RESCHEDULED.get_or_init(Notify::new)
+}
+
+/// Make every timer compute its delay again, e.g. after the config changed
+pub fn reschedule() {
+    rescheduled().notify_waiters();
+}
+
+fn stopping() -> &'static watch::Sender<bool> {
+    static STOPPING: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    STOPPING.get_or_init(|| watch::channel(false).0)
+}
+
+/// Stop every timer and make [`stopped`] return, e.g. on SIGTERM
+pub fn shutdown() {
+    stopping().send_replace(true);
+}
+
+/// Wait until [`shutdown`] is called; returns right away when it already was
+pub async fn stopped() {
+    let _ = stopping().subscribe().wait_for(|stopping| *stopping).await;
+}
+
+/// A timer as the scheduler keeps it
+struct Timer {
+    delay: Box<dyn Fn() -> Duration + Send>,
+    handler: Arc<dyn Fn() + Send + Sync>,
+    /// Whether a run is still going, which holds off the next one
+    running: bool,
+}
+
+fn timers() -> &'static mpsc::UnboundedSender<Timer> {
+    static TIMERS: OnceLock<mpsc::UnboundedSender<Timer>> = OnceLock::new();
+    TIMERS.get_or_init(|| {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        spawn(schedule(receiver));
+        sender
+    })
+}
+
+/// Run `handler` on the blocking pool after every `delay()`, until [`shutdown`]
+///
+/// A run that is still going delays the next one rather than overlapping it.
+pub fn repeat(delay: impl Fn() -> Duration + Send + 'static, handler: impl Fn() + Send + Sync + 'static) {
+    let timer = Timer { delay: Box::new(delay), handler: Arc::new(handler), running: false };
+    let _ = timers().send(timer);
+}
+
+/// Run every timer when it's due, taking new ones from `added`
+async fn schedule(mut added: mpsc::UnboundedReceiver<Timer>) {
+    let mut timers: Vec<Timer> = Vec::new();
+    // Next due time of each timer that isn't running, by index into `timers`
+    let mut due: BinaryHeap<Reverse<(Instant, usize)>> = BinaryHeap::new();
+    let (finished, mut finished_runs) = mpsc::unbounded_channel::<usize>();
+
+    loop {
+        let rescheduled = rescheduled().notified();
+        let next = due.peek().map(|Reverse((at, _))| *at);
+        tokio::select! {
+            Some(timer) = added.recv() => {
+                due.push(Reverse((Instant::now() + (timer.delay)(), timers.len())));
+                timers.push(timer);
+            }
+            _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                let now = Instant::now();
+                while let Some(Reverse((_, index))) = due.peek().copied().filter(|Reverse((at, _))| *at <= now) {
+                    due.pop();
+                    let timer = &mut timers[index];
+                    timer.running = true;
+                    let (handler, finished) = (Arc::clone(&timer.handler), finished.clone());
+                    spawn(async move {
+                        if let Err(e) = tokio::task::spawn_blocking(move || handler()).await {
+                            error!("Timer task failed: {}", e);
+                        }
+                        let _ = finished.send(index);
+                    });
+                }
+            }
+            Some(index) = finished_runs.recv() => {
+                let timer = &mut timers[index];
+                timer.running = false;
+                due.push(Reverse((Instant::now() + (timer.delay)(), index)));
+            }
+            _ = rescheduled => {
+                let now = Instant::now();
+                due = timers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, timer)| !timer.running)
+                    .map(|(index, timer)| Reverse((now + (timer.delay)(), index)))
+                    .collect();
+            }
+            _ = stopped() => break,
+        }
+    }
+}
+
+/// Run a command like [`Command::output`], killing it once `timeout` has passed
+///
+/// Call from a blocking context such as a handler, not from async code.
+pub fn output(command: Command, timeout: Duration) -> io::Result<Output> {
+    let Some(handle) = HANDLE.get() else {
+        let mut command = command;
+        return command.output();
+    };
+
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut command = tokio::process::Command::from(command);
+    command.kill_on_drop(true);
+    handle.block_on(async {
+        match tokio::time::timeout(timeout, command.output()).await {
+            Ok(output) => output,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} didn't finish within {} seconds", program, timeout.as_secs_f32()),
+            )),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_timeout() {
+        // Other tests may run commands through the handle, so the runtime stays up
+        std::mem::forget(start());
+
+        let mut sleep = Command::new("sleep");
+        sleep.arg("5");
+        let e = output(sleep, Duration::from_millis(100)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(e.to_string().starts_with("sleep didn't finish"));
+
+        assert!(output(Command::new("true"), Duration::from_secs(5)).unwrap().status.success());
+    }
+
+    #[test]
+    fn test_scheduler() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        std::mem::forget(start());
+
+        let (fast, slow) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let counter = |count: &Arc<AtomicUsize>| {
+            let count = Arc::clone(count);
+            move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+        repeat(|| Duration::from_millis(10), counter(&fast));
+        repeat(|| Duration::from_secs(60), counter(&slow));
+
+        // Both timers share the scheduler, which runs each when it's due
+        let started = std::time::Instant::now();
+        while fast.load(Ordering::SeqCst) < 3 {
+            assert!(started.elapsed() < Duration::from_secs(5), "timer didn't repeat");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(slow.load(Ordering::SeqCst), 0);
+
+        // Shutting down stops them, and waiting for the shutdown returns right away
+        shutdown();
+        handle().block_on(async { tokio::time::timeout(Duration::from_secs(1), stopped()).await.unwrap() });
+        thread::sleep(Duration::from_millis(50));
+        let runs = fast.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(fast.load(Ordering::SeqCst), runs);
+    }
+}
@@ -0,0 +1,151 @@
+//! Rhai scripts for custom items
+//!
+//! A custom item with `script` instead of `command` runs the script on its
+//! interval, like any other provider. Scripts read what the daemon already
+//! collected through the functions registered here, so they don't start
+//! processes of their own, and return the label or a map with `label`,
+//! `icon`, `visible` and any item style key.
+
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use rhai::{Dynamic, Engine, Map};
+use sketchybartender::config::ItemStyle;
+use sketchybartender::{error, info};
+
+use crate::metrics;
+use crate::providers::CustomInfo;
+use crate::rules;
+
+/// Operations a script may run before it's stopped, so an endless loop can't hang its item
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// A map from `(key, value)` pairs
+fn map<const N: usize>(entries: [(&str, Dynamic); N]) -> Dynamic {
+    let map: Map = entries.into_iter().map(|(key, value)| (key.into(), value)).collect();
+    Dynamic::from_map(map)
+}
+
+/// `()` for values the daemon hasn't collected
+fn or_unit<T>(value: Option<T>, f: impl FnOnce(T) -> Dynamic) -> Dynamic {
+    value.map(f).unwrap_or(Dynamic::UNIT)
+}
+
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| info!("[SCRIPT] {}", text));
+
+        // #{percentage, charging}
+        engine.register_fn("battery", || {
+            or_unit(metrics::snapshot().battery, |(percentage, charging)| {
+                map([("percentage", (percentage as i64).into()), ("charging", charging.into())])
+            })
+        });
+        // #{percentage, muted}
+        engine.register_fn("volume", || {
+            or_unit(metrics::snapshot().volume, |(percentage, muted)| {
+                map([("percentage", (percentage as i64).into()), ("muted", muted.into())])
+            })
+        });
+        // "ac" or "battery"
+        engine.register_fn("power_source", || or_unit(rules::power_source(), |source| source.to_string().into()));
+        // #{hour, minute, weekday}, Monday being 1
+        engine.register_fn("time", || {
+            let (minute, weekday) = rules::time_of_day();
+            map([
+                ("hour", i64::from(minute / 60).into()),
+                ("minute", i64::from(minute % 60).into()),
+                ("weekday", i64::from(weekday).into()),
+            ])
+        });
+        // Outdated formulae and casks together
+        engine.register_fn("brew_outdated", || {
+            or_unit(metrics::snapshot().brew, |(formulae, casks)| ((formulae + casks) as i64).into())
+        });
+        engine.register_fn("teams_notifications", || {
+            or_unit(metrics::snapshot().teams_notifications, |count| i64::from(count).into())
+        });
+        engine
+    })
+}
+
+/// Turn what a script returned into the item's output
+fn output(result: Dynamic) -> Result<CustomInfo, String> {
+    if result.is_unit() {
+        return Err("the script returned nothing; return a label or a map".to_string());
+    }
+    let Some(values) = result.clone().try_cast::<Map>() else {
+        return Ok(CustomInfo { label: result.to_string(), ..CustomInfo::default() });
+    };
+
+    let mut info = CustomInfo::default();
+    let mut style = ItemStyle::default();
+    for (key, value) in values {
+        match key.as_str() {
+            "label" => info.label = value.to_string(),
+            "icon" => info.icon = Some(value.to_string()),
+            "visible" => match value.as_bool() {
+                Ok(visible) => info.visible = Some(visible),
+                Err(found) => return Err(format!("visible must be true or false, found {}", found)),
+            },
+            key => style.set(key, &value.to_string())?,
+        }
+    }
+    info.style = style;
+    Ok(info)
+}
+
+/// Run a custom item's script; `None` when it can't be read, fails, or returns something unusable
+pub fn run(path: &Path) -> Option<CustomInfo> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            error!("[SCRIPT] Failed to read {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    let result = engine().eval::<Dynamic>(&source).map_err(|e| e.to_string()).and_then(output);
+    match result {
+        Ok(info) => Some(info),
+        Err(e) => {
+            error!("[SCRIPT] {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output() {
+        let info = output(engine().eval("`${40 + 2}%`").unwrap()).unwrap();
+        assert_eq!(info.label, "42%");
+        assert_eq!(info.visible, None);
+
+        let script = r#"
+            let left = "3h left";
+            #{ label: left, icon: "󰂀", visible: false, label_color: "0xffed8796", padding_left: 4 }
+        "#;
+        let info = output(engine().eval(script).unwrap()).unwrap();
+        assert_eq!((info.label.as_str(), info.icon.as_deref()), ("3h left", Some("󰂀")));
+        assert_eq!(info.visible, Some(false));
+        assert_eq!(info.style.props(), vec![("label.color", "0xffed8796"), ("padding_left", "4")]);
+
+        let typo = output(engine().eval("#{ label_colour: \"0xffed8796\" }").unwrap());
+        assert!(typo.unwrap_err().contains("did you mean label_color"));
+        assert!(output(engine().eval("#{ visible: \"no\" }").unwrap()).is_err());
+        assert!(output(Dynamic::UNIT).is_err());
+    }
+
+    #[test]
+    fn test_operation_limit() {
+        assert!(engine().eval::<Dynamic>("loop {}").is_err());
+    }
+}
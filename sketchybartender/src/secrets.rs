@@ -0,0 +1,85 @@
+//! API tokens and other secrets, kept in the macOS Keychain
+//!
+//! Secrets are generic passwords under the `sketchybartender` service, one
+//! account per name. The config refers to them as `${secret:NAME}`, and
+//! `sketchycli secret set NAME` stores them, so tokens stay out of the
+//! config file.
+
+use std::process::{Command, Stdio};
+
+/// Keychain service the secrets are stored under
+const SERVICE: &str = "sketchybartender";
+
+/// Check that a secret name is usable as a Keychain account and in `${secret:NAME}`
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid secret name: {:?} (use letters, digits, '_', '-' and '.')", name))
+    }
+}
+
+/// Run `security` with arguments, returning its output or its error message
+fn security(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("security")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run security: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Read a secret from the Keychain
+pub fn get(name: &str) -> Result<String, String> {
+    validate_name(name)?;
+    security(&["find-generic-password", "-s", SERVICE, "-a", name, "-w"])
+        .map(|secret| secret.trim_end_matches('\n').to_string())
+        .map_err(|_| format!("secret {} is not in the keychain (store it with `sketchycli secret set {}`)", name, name))
+}
+
+/// Store a secret, asking for it on the terminal
+///
+/// `security` prompts for the value itself, so it never appears in the
+/// process list or the shell history.
+pub fn set(name: &str) -> Result<(), String> {
+    validate_name(name)?;
+    let status = Command::new("security")
+        // -U updates an existing item; -w last makes security prompt for the value
+        .args(["add-generic-password", "-U", "-s", SERVICE, "-a", name, "-l", &format!("{} {}", SERVICE, name), "-w"])
+        .stdin(Stdio::inherit())
+        .status()
+        .map_err(|e| format!("Failed to run security: {}", e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to store secret {}", name))
+    }
+}
+
+/// Remove a secret from the Keychain
+pub fn delete(name: &str) -> Result<(), String> {
+    validate_name(name)?;
+    security(&["delete-generic-password", "-s", SERVICE, "-a", name])
+        .map(|_| ())
+        .map_err(|_| format!("secret {} is not in the keychain", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("github").is_ok());
+        assert!(validate_name("jira.work-token_2").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("my token").is_err());
+        assert!(validate_name("a}b").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Running the daemon as a launchd agent
+//!
+//! `sketchycli service install` writes a plist to `~/Library/LaunchAgents`
+//! and loads it, so launchd starts the daemon at login and restarts it when it
+//! exits, instead of `sketchybarrc` starting it. The plist pins the cache
+//! directory the CLI sees, so the daemon listens on the socket sketchycli
+//! connects to.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// launchd label of the agent, also the plist's file name
+pub const LABEL: &str = "io.github.olli-io.sketchybartender";
+
+/// Where the agent's plist is installed
+pub fn plist_path() -> PathBuf {
+    let home = env::var("HOME").expect("HOME not set");
+    PathBuf::from(home).join("Library/LaunchAgents").join(format!("{}.plist", LABEL))
+}
+
+/// `$XDG_CACHE_HOME`, or `~/.cache`, as the daemon works it out
+fn cache_home() -> PathBuf {
+    env::var("XDG_CACHE_HOME").map(PathBuf::from).unwrap_or_else(|_| {
+        let home = env::var("HOME").expect("HOME not set");
+        PathBuf::from(home).join(".cache")
+    })
+}
+
+/// The directory holding the socket
+pub fn cache_dir() -> PathBuf {
+    cache_home().join("sketchybar")
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// The agent's plist, running `daemon` with `environment`
+///
+/// `cache_dir` is where the socket lives; launchd writes the daemon's output
+/// to `launchd.log` in it.
+pub fn plist(daemon: &Path, cache_dir: &Path, environment: &[(&str, String)]) -> String {
+    let environment: String = environment
+        .iter()
+        .map(|(key, value)| format!("        <key>{}</key>\n        <string>{}</string>\n", key, escape(value)))
+        .collect();
+    let log = escape(&cache_dir.join("launchd.log").display().to_string());
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \x20   <key>Label</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>ProgramArguments</key>\n\
+         \x20   <array>\n\
+         \x20       <string>{}</string>\n\
+         \x20   </array>\n\
+         \x20   <key>EnvironmentVariables</key>\n\
+         \x20   <dict>\n\
+         {}\
+         \x20   </dict>\n\
+         \x20   <key>RunAtLoad</key>\n\
+         \x20   <true/>\n\
+         \x20   <key>KeepAlive</key>\n\
+         \x20   <true/>\n\
+         \x20   <key>ProcessType</key>\n\
+         \x20   <string>Interactive</string>\n\
+         \x20   <key>StandardOutPath</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>StandardErrorPath</key>\n\
+         \x20   <string>{}</string>\n\
+         </dict>\n\
+         </plist>\n",
+        LABEL,
+        escape(&daemon.display().to_string()),
+        environment,
+        log,
+        log
+    )
+}
+
+/// The environment the daemon needs under launchd, which starts agents with a bare PATH
+fn environment() -> Vec<(&'static str, String)> {
+    let path = env::var("PATH").unwrap_or_else(|_| "/opt/homebrew/bin:/usr/local/bin:/usr/bin:/bin".to_string());
+    let mut environment = vec![("PATH", path), ("XDG_CACHE_HOME", cache_home().display().to_string())];
+    if let Ok(config_home) = env::var("XDG_CONFIG_HOME") {
+        environment.push(("XDG_CONFIG_HOME", config_home));
+    }
+    environment
+}
+
+/// Run `launchctl` with arguments, returning its error message if it fails
+fn launchctl(args: &[&str]) -> Result<(), String> {
+    let output = Command::new("launchctl")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run launchctl: {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(format!("launchctl {} failed: {}", args[0], message))
+    }
+}
+
+/// The user's launchd domain, e.g. `gui/501`
+fn domain() -> Result<String, String> {
+    let output = Command::new("id").arg("-u").output().map_err(|e| format!("Failed to run id: {}", e))?;
+    Ok(format!("gui/{}", String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Whether the agent is loaded into launchd
+pub fn is_loaded() -> bool {
+    domain().is_ok_and(|domain| launchctl(&["print", &format!("{}/{}", domain, LABEL)]).is_ok())
+}
+
+/// Write the plist for the `daemon` binary and load it, replacing an installed one
+pub fn install(daemon: &Path) -> Result<(), String> {
+    if is_loaded() {
+        stop()?;
+    }
+
+    let path = plist_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::create_dir_all(cache_dir()).map_err(|e| format!("Failed to create {}: {}", cache_dir().display(), e))?;
+    fs::write(&path, plist(daemon, &cache_dir(), &environment()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    start()
+}
+
+/// Unload the agent and remove its plist
+pub fn uninstall() -> Result<(), String> {
+    if is_loaded() {
+        stop()?;
+    }
+    let path = plist_path();
+    fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+}
+
+/// Load the installed agent, which starts the daemon
+pub fn start() -> Result<(), String> {
+    let path = plist_path();
+    if !path.exists() {
+        return Err(format!("{} isn't installed; run `sketchycli service install` first", LABEL));
+    }
+    if is_loaded() {
+        return Ok(());
+    }
+    launchctl(&["bootstrap", &domain()?, &path.display().to_string()])
+}
+
+/// Unload the agent, which stops the daemon until the next `start` or login
+pub fn stop() -> Result<(), String> {
+    launchctl(&["bootout", &format!("{}/{}", domain()?, LABEL)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plist() {
+        let plist = plist(
+            Path::new("/Users/me/.local/bin/sketchybartender"),
+            Path::new("/Users/me/.cache/sketchybar"),
+            &[("PATH", "/opt/homebrew/bin:/usr/bin".to_string()), ("XDG_CONFIG_HOME", "/tmp/a&b".to_string())],
+        );
+
+        assert!(plist.contains(&format!("<string>{}</string>", LABEL)));
+        assert!(plist.contains("<string>/Users/me/.local/bin/sketchybartender</string>"));
+        assert!(plist.contains("<key>KeepAlive</key>\n    <true/>"));
+        assert!(plist.contains("<key>PATH</key>\n        <string>/opt/homebrew/bin:/usr/bin</string>"));
+        assert!(plist.contains("<string>/tmp/a&amp;b</string>"));
+        assert!(plist.contains("<string>/Users/me/.cache/sketchybar/launchd.log</string>"));
+    }
+}
@@ -0,0 +1,33 @@
+//! The live configuration, with the selected profile applied
+//!
+//! Timer loops and handlers read from here on every tick, so switching
+//! profiles takes effect without restarting the daemon.
+
+use std::sync::{Arc, OnceLock, RwLock};
+
+use sketchybartender::config::Config;
+
+use crate::rules;
+
+fn config_lock() -> &'static RwLock<Arc<Config>> {
+    static CONFIG: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+    CONFIG.get_or_init(|| RwLock::new(Arc::new(Config::default())))
+}
+
+/// Replace the live configuration, returning the previous one
+pub fn set(config: Config) -> Arc<Config> {
+    match config_lock().write() {
+        Ok(mut c) => std::mem::replace(&mut *c, Arc::new(config)),
+        Err(_) => Arc::new(Config::default()),
+    }
+}
+
+/// The live configuration
+pub fn current() -> Arc<Config> {
+    config_lock().read().map(|c| Arc::clone(&c)).unwrap_or_default()
+}
+
+/// Whether an item is shown and updated under the live configuration and display rules
+pub fn enabled(item: &str) -> bool {
+    current().is_enabled(item) && !rules::is_hidden(item)
+}
@@ -0,0 +1,62 @@
+//! Minimal Unix signal handling without pulling in libc
+//!
+//! The signal handler only writes the signal number into a socket pair; a
+//! regular thread reads it back and runs the callback outside signal context.
+
+use std::ffi::{c_int, c_void};
+use std::io::Read;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+
+use sketchybartender::error;
+
+pub const SIGHUP: c_int = 1;
+pub const SIGINT: c_int = 2;
+pub const SIGTERM: c_int = 15;
+
+extern "C" {
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+}
+
+/// Write end of the self-pipe, -1 until `listen` is called
+static PIPE_FD: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(signum: c_int) {
+    let fd = PIPE_FD.load(Ordering::Relaxed);
+    if fd >= 0 {
+        let byte = signum as u8;
+        // write(2) is async-signal-safe
+        unsafe {
+            write(fd, &byte as *const u8 as *const c_void, 1);
+        }
+    }
+}
+
+/// Install handlers for `signals` and run `callback` on a background thread for each delivery
+pub fn listen(signals: &[c_int], callback: impl Fn(c_int) + Send + 'static) {
+    let (mut reader, writer) = match UnixStream::pair() {
+        Ok(pair) => pair,
+        Err(e) => {
+            error!("Failed to create signal pipe: {}", e);
+            return;
+        }
+    };
+
+    PIPE_FD.store(writer.into_raw_fd(), Ordering::Relaxed);
+
+    for &signum in signals {
+        unsafe {
+            signal(signum, on_signal);
+        }
+    }
+
+    thread::spawn(move || {
+        let mut byte = [0u8; 1];
+        while reader.read_exact(&mut byte).is_ok() {
+            callback(byte[0] as c_int);
+        }
+    });
+}
@@ -0,0 +1,1024 @@
+//! Sketchybar command batching and item renderers
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::iter;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use sketchybartender::config::{DisplaySpec, ItemStyle};
+use sketchybartender::{debug, error, icon_map};
+
+use crate::events::{self, Event};
+use crate::mach;
+use crate::providers::CustomInfo;
+use crate::runtime;
+
+/// How long a batch may take before sketchybar counts as stuck
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a queued batch waits for others to join it
+const COALESCE_WINDOW: Duration = Duration::from_millis(15);
+
+/// Font for app icons in the active icon theme
+pub fn icon_font() -> &'static str {
+    icon_map::theme().font()
+}
+
+/// Display selectors from the config; `None` keeps the default placement
+#[derive(Debug, Clone, Default)]
+pub struct ItemDisplays {
+    pub front_app: Option<DisplaySpec>,
+    pub workspaces: Option<DisplaySpec>,
+}
+
+fn displays_lock() -> &'static RwLock<ItemDisplays> {
+    static DISPLAYS: OnceLock<RwLock<ItemDisplays>> = OnceLock::new();
+    DISPLAYS.get_or_init(|| RwLock::new(ItemDisplays::default()))
+}
+
+/// Set the displays front_app and the workspace items are shown on
+pub fn set_item_displays(displays: ItemDisplays) {
+    if let Ok(mut d) = displays_lock().write() {
+        *d = displays;
+    }
+}
+
+/// Displays front_app and the workspace items are shown on
+pub fn item_displays() -> ItemDisplays {
+    displays_lock().read().map(|d| d.clone()).unwrap_or_default()
+}
+
+fn styles_lock() -> &'static RwLock<BTreeMap<String, ItemStyle>> {
+    static STYLES: OnceLock<RwLock<BTreeMap<String, ItemStyle>>> = OnceLock::new();
+    STYLES.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+/// Set the per-item style overrides from the config
+pub fn set_item_styles(styles: BTreeMap<String, ItemStyle>) {
+    if let Ok(mut s) = styles_lock().write() {
+        *s = styles;
+    }
+}
+
+/// Style overrides for an item; `workspace.N` items fall back to the `workspaces` style
+pub fn item_style(item: &str) -> ItemStyle {
+    let Ok(styles) = styles_lock().read() else {
+        return ItemStyle::default();
+    };
+
+    styles
+        .get(item)
+        .or_else(|| item.starts_with("workspace.").then(|| styles.get("workspaces")).flatten())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// A builder for batching sketchybar commands
+#[derive(Debug, Default)]
+pub struct SketchybarBatch {
+    args: Vec<String>,
+}
+
+impl SketchybarBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set properties on an item
+    pub fn set(&mut self, item: &str, props: &[(&str, &str)]) -> &mut Self {
+        self.args.push("--set".to_string());
+        self.args.push(item.to_string());
+        for (key, value) in props {
+            self.args.push(format!("{}={}", key, value));
+        }
+        self
+    }
+
+    /// Set properties on an item, followed by its configured style overrides
+    pub fn set_styled(&mut self, item: &str, props: &[(&str, &str)]) -> &mut Self {
+        let style = item_style(item);
+        let mut props = props.to_vec();
+        props.extend(style.props());
+        self.set(item, &props)
+    }
+
+    /// Add a new item at a bar position
+    pub fn add(&mut self, item: &str, position: &str) -> &mut Self {
+        self.args.push("--add".to_string());
+        self.args.push("item".to_string());
+        self.args.push(item.to_string());
+        self.args.push(position.to_string());
+        self
+    }
+
+    /// Add a new graph item at a bar position, `width` points wide
+    pub fn add_graph(&mut self, item: &str, position: &str, width: usize) -> &mut Self {
+        self.args.push("--add".to_string());
+        self.args.push("graph".to_string());
+        self.args.push(item.to_string());
+        self.args.push(position.to_string());
+        self.args.push(width.to_string());
+        self
+    }
+
+    /// Mirror a menu bar item, named `<owner>,<name>` until renamed, at a bar position
+    pub fn add_alias(&mut self, source: &str, position: &str) -> &mut Self {
+        self.args.push("--add".to_string());
+        self.args.push("alias".to_string());
+        self.args.push(source.to_string());
+        self.args.push(position.to_string());
+        self
+    }
+
+    /// Give an item a new name
+    pub fn rename(&mut self, item: &str, name: &str) -> &mut Self {
+        self.args.push("--rename".to_string());
+        self.args.push(item.to_string());
+        self.args.push(name.to_string());
+        self
+    }
+
+    /// Add a slider at a bar position, e.g. `popup.volume`, `width` points wide
+    pub fn add_slider(&mut self, item: &str, position: &str, width: usize) -> &mut Self {
+        self.args.push("--add".to_string());
+        self.args.push("slider".to_string());
+        self.args.push(item.to_string());
+        self.args.push(position.to_string());
+        self.args.push(width.to_string());
+        self
+    }
+
+    /// Move a slider's knob to `percentage`
+    pub fn slide(&mut self, item: &str, percentage: u8) -> &mut Self {
+        self.set(item, &[("slider.percentage", &percentage.to_string())])
+    }
+
+    /// Add an item to the popup menu `parent` opens
+    pub fn add_popup(&mut self, parent: &str, item: &str) -> &mut Self {
+        self.args.push("--add".to_string());
+        self.args.push("item".to_string());
+        self.args.push(item.to_string());
+        self.args.push(format!("popup.{}", parent));
+        self
+    }
+
+    /// Show, hide or toggle an item's popup: `drawing` is `on`, `off` or `toggle`
+    pub fn popup(&mut self, item: &str, drawing: &str) -> &mut Self {
+        self.set(item, &[("popup.drawing", drawing)])
+    }
+
+    /// Remove an item, or every item matching a `/regex/`
+    pub fn remove(&mut self, item: &str) -> &mut Self {
+        self.args.push("--remove".to_string());
+        self.args.push(item.to_string());
+        self
+    }
+
+    /// Append values between 0 and 1 to a graph, which scrolls the oldest out
+    pub fn push(&mut self, item: &str, values: &[f32]) -> &mut Self {
+        self.args.push("--push".to_string());
+        self.args.push(item.to_string());
+        self.args.extend(values.iter().map(|value| format!("{:.3}", value)));
+        self
+    }
+
+    /// Set properties on the bar itself
+    pub fn bar(&mut self, props: &[(&str, &str)]) -> &mut Self {
+        self.args.push("--bar".to_string());
+        self.args.extend(props.iter().map(|(key, value)| format!("{}={}", key, value)));
+        self
+    }
+
+    /// Set the properties items added after this start with
+    pub fn defaults(&mut self, props: &[(&str, &str)]) -> &mut Self {
+        self.args.push("--default".to_string());
+        self.args.extend(props.iter().map(|(key, value)| format!("{}={}", key, value)));
+        self
+    }
+
+    /// Declare a custom event items can subscribe to
+    pub fn add_event(&mut self, event: &str) -> &mut Self {
+        self.args.push("--add".to_string());
+        self.args.push("event".to_string());
+        self.args.push(event.to_string());
+        self
+    }
+
+    /// Run an item's script when any of the events fire
+    pub fn subscribe(&mut self, item: &str, events: &[&str]) -> &mut Self {
+        self.args.push("--subscribe".to_string());
+        self.args.push(item.to_string());
+        self.args.extend(events.iter().map(|event| event.to_string()));
+        self
+    }
+
+    /// Add animation with curve and duration
+    pub fn animate(&mut self, curve: &str, duration: u32) -> &mut Self {
+        self.args.push("--animate".to_string());
+        self.args.push(curve.to_string());
+        self.args.push(duration.to_string());
+        self
+    }
+
+    /// Append another batch's commands
+    pub fn extend(&mut self, other: SketchybarBatch) -> &mut Self {
+        self.args.extend(other.args);
+        self
+    }
+
+    /// Execute the batch along with any others queued in the next few milliseconds, logging failures
+    ///
+    /// Bursts of updates, e.g. a batch per display on a workspace change, then take a single
+    /// message to sketchybar.
+    pub fn queue(self) {
+        let Ok(mut pending) = queued().lock() else {
+            return;
+        };
+        if let Some(batch) = pending.as_mut() {
+            batch.extend(self);
+            return;
+        }
+
+        *pending = Some(self);
+        runtime::background(|| {
+            thread::sleep(COALESCE_WINDOW);
+            let batch = queued().lock().ok().and_then(|mut queued| queued.take());
+            if let Err(e) = batch.map_or(Ok(()), |batch| batch.execute()) {
+                error!("Failed to run queued sketchybar commands: {}", e);
+            }
+        });
+    }
+
+    /// Execute the batched commands
+    pub fn execute(&self) -> Result<(), std::io::Error> {
+        if self.args.is_empty() {
+            return Ok(());
+        }
+
+        let args = deduplicated(&self.args);
+        if events::is_watched() {
+            events::publish(Event::Sketchybar(args.join(" ")));
+        }
+        debug!("[SKETCHYBAR] {}", args.join(" "));
+
+        // One message over sketchybar's port; the CLI is the fallback while it can't be reached
+        if let Some(reply) = mach::send(&args, TIMEOUT) {
+            let reply = reply?;
+            return match reply.strip_prefix("[!]") {
+                Some(complaint) => Err(std::io::Error::other(format!("sketchybar: {}", complaint.trim()))),
+                None => Ok(()),
+            };
+        }
+
+        // sketchybar's complaints go to the daemon's log
+        let mut command = Command::new("sketchybar");
+        command.args(&args).stdout(Stdio::null()).stderr(Stdio::inherit());
+        let output = runtime::output(command, TIMEOUT)?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::other("sketchybar command failed"))
+        }
+    }
+}
+
+/// Batches waiting in [`SketchybarBatch::queue`] for the coalescing window to end
+fn queued() -> &'static Mutex<Option<SketchybarBatch>> {
+    static QUEUED: OnceLock<Mutex<Option<SketchybarBatch>>> = OnceLock::new();
+    QUEUED.get_or_init(|| Mutex::new(None))
+}
+
+/// The arguments without `--set` properties that a later `--set` of the same item overrides
+///
+/// Animations chain sets of the same property on purpose, so batches with `--animate` are left
+/// alone, and so are toggles, two of which cancel out. Any other command ends the run of sets
+/// that are merged, since it may add, remove or rename the items they name.
+fn deduplicated(args: &[String]) -> Vec<String> {
+    if args.iter().any(|arg| arg == "--animate") {
+        return args.to_vec();
+    }
+
+    // Every command starts with a `--` flag
+    let mut commands: Vec<&[String]> = Vec::new();
+    let mut start = 0;
+    for (index, arg) in args.iter().enumerate().skip(1) {
+        if arg.starts_with("--") {
+            commands.push(&args[start..index]);
+            start = index;
+        }
+    }
+    commands.push(&args[start..]);
+
+    // Walking backwards, the first set of a property is the one that sticks
+    let mut seen: HashSet<(&str, &str)> = HashSet::new();
+    let mut kept: Vec<Vec<&String>> = Vec::new();
+    for command in commands.into_iter().rev() {
+        let [flag, item, props @ ..] = command else {
+            seen.clear();
+            kept.push(command.iter().collect());
+            continue;
+        };
+        if flag != "--set" {
+            seen.clear();
+            kept.push(command.iter().collect());
+            continue;
+        }
+
+        let mut props: Vec<&String> = props
+            .iter()
+            .rev()
+            .filter(|prop| {
+                let (key, value) = prop.split_once('=').unwrap_or((prop.as_str(), ""));
+                value == "toggle" || seen.insert((item.as_str(), key))
+            })
+            .collect();
+        if !props.is_empty() {
+            props.reverse();
+            kept.push(iter::once(flag).chain(iter::once(item)).chain(props).collect());
+        }
+    }
+    kept.into_iter().rev().flatten().cloned().collect()
+}
+
+/// sketchybar animates at 60 frames a second
+const FRAME: Duration = Duration::from_micros(16_667);
+
+/// How far ahead a looping animation is queued before it's topped up
+const LOOP_AHEAD: Duration = Duration::from_secs(5);
+
+type Props = Vec<(String, String)>;
+
+fn borrowed(props: &Props) -> Vec<(&str, &str)> {
+    props.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect()
+}
+
+/// One step of an animation: properties reached over a number of frames along a curve
+#[derive(Debug, Clone)]
+struct Keyframe {
+    curve: String,
+    frames: u32,
+    props: Props,
+}
+
+/// Keyframed animation of one item, played once or looped under a name until cancelled
+#[derive(Debug, Clone)]
+pub struct Animation {
+    item: String,
+    keyframes: Vec<Keyframe>,
+    baseline: Props,
+}
+
+impl Animation {
+    pub fn new(item: &str) -> Self {
+        Self { item: item.to_string(), keyframes: Vec::new(), baseline: Vec::new() }
+    }
+
+    /// Animate to `props` over `frames` (60 a second) along a sketchybar curve, e.g. `sin` or `linear`
+    pub fn keyframe(mut self, curve: &str, frames: u32, props: &[(&str, &str)]) -> Self {
+        self.keyframes.push(Keyframe {
+            curve: curve.to_string(),
+            frames,
+            props: props.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+        });
+        self
+    }
+
+    /// Properties the item is reset to when the animation is cancelled
+    pub fn baseline(mut self, props: &[(&str, &str)]) -> Self {
+        self.baseline = props.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        self
+    }
+
+    /// How long one pass through the keyframes takes
+    pub fn duration(&self) -> Duration {
+        FRAME * self.keyframes.iter().map(|keyframe| keyframe.frames).sum::<u32>()
+    }
+
+    /// Queue one pass through the keyframes on a batch
+    pub fn append_to(&self, batch: &mut SketchybarBatch) {
+        for keyframe in &self.keyframes {
+            batch.animate(&keyframe.curve, keyframe.frames).set(&self.item, &borrowed(&keyframe.props));
+        }
+    }
+
+    /// Play the keyframes `times` times
+    pub fn play(&self, times: usize) -> Result<(), std::io::Error> {
+        let mut batch = SketchybarBatch::new();
+        for _ in 0..times {
+            self.append_to(&mut batch);
+        }
+        batch.execute()
+    }
+
+    /// Enough passes through the keyframes to fill `ahead`, and how long they take
+    fn passes(&self, ahead: Duration) -> (SketchybarBatch, Duration) {
+        let passes = (ahead.as_millis() / self.duration().as_millis().max(1)).max(1) as u32;
+        let mut batch = SketchybarBatch::new();
+        for _ in 0..passes {
+            self.append_to(&mut batch);
+        }
+        (batch, self.duration() * passes)
+    }
+
+    /// Loop the keyframes until [`cancel_animation`] is called with `name`, replacing an
+    /// animation already running under it
+    ///
+    /// sketchybar can't loop by itself, so passes are queued a few seconds ahead and topped up
+    /// in the background.
+    pub fn start(self, name: &str) -> Result<(), std::io::Error> {
+        let (batch, ahead) = self.passes(LOOP_AHEAD);
+        let running = Running::new(self.item, self.baseline);
+        let id = running.id;
+        register(name, running.clone());
+        queue_passes(name, &running, &batch)?;
+
+        let name = name.to_string();
+        runtime::background(move || loop {
+            thread::sleep(ahead);
+            if !is_animating(&name, id) {
+                return;
+            }
+            if let Err(e) = queue_passes(&name, &running, &batch) {
+                error!("Failed to continue the {} animation: {}", name, e);
+                return;
+            }
+        });
+        Ok(())
+    }
+}
+
+/// A looping animation, and what to reset its item to when it stops
+#[derive(Debug, Clone)]
+struct Running {
+    /// Tells the animation apart from one that replaced it under the same name
+    id: u64,
+    item: String,
+    baseline: Props,
+}
+
+impl Running {
+    fn new(item: String, baseline: Props) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self { id: NEXT_ID.fetch_add(1, Ordering::Relaxed), item, baseline }
+    }
+
+    /// The batch resetting the item to its baseline, if it has one
+    ///
+    /// Setting the properties without --animate drops the queued steps.
+    fn reset(&self) -> Option<SketchybarBatch> {
+        if self.baseline.is_empty() {
+            return None;
+        }
+        let mut batch = SketchybarBatch::new();
+        batch.set(&self.item, &borrowed(&self.baseline));
+        Some(batch)
+    }
+}
+
+/// Looping animations by name; the lock is only held to look them up, never while sketchybar runs
+fn animations() -> &'static Mutex<HashMap<String, Running>> {
+    static ANIMATIONS: OnceLock<Mutex<HashMap<String, Running>>> = OnceLock::new();
+    ANIMATIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn register(name: &str, running: Running) {
+    if let Ok(mut animations) = animations().lock() {
+        animations.insert(name.to_string(), running);
+    }
+}
+
+/// Whether the animation with `id` is still the one running under `name`
+fn is_animating(name: &str, id: u64) -> bool {
+    animations().lock().is_ok_and(|animations| animations.get(name).is_some_and(|running| running.id == id))
+}
+
+/// Queue passes of a looping animation
+///
+/// A cancel landing while they are sent may reset the item before they arrive, so the item
+/// is reset again afterwards if the animation was cancelled meanwhile. One that replaced it
+/// queues its own passes and is left alone.
+fn queue_passes(name: &str, running: &Running, batch: &SketchybarBatch) -> Result<(), std::io::Error> {
+    batch.execute()?;
+    let cancelled = animations().lock().is_ok_and(|animations| !animations.contains_key(name));
+    match running.reset() {
+        Some(reset) if cancelled => reset.execute(),
+        _ => Ok(()),
+    }
+}
+
+/// Stop the looping animation started under `name` and reset its item to the baseline
+pub fn cancel_animation(name: &str) -> Result<(), std::io::Error> {
+    let animation = animations().lock().ok().and_then(|mut animations| animations.remove(name));
+    animation.and_then(|animation| animation.reset()).map_or(Ok(()), |reset| reset.execute())
+}
+
+/// Convenience function to set properties on a single item, with its style applied
+pub fn set_item(item: &str, props: &[(&str, &str)]) -> Result<(), std::io::Error> {
+    let mut batch = SketchybarBatch::new();
+    batch.set_styled(item, props);
+    batch.execute()
+}
+
+/// Update the clock item
+pub fn update_clock(time: &str) -> Result<(), std::io::Error> {
+    set_item("clock", &[("label", time)])
+}
+
+/// Update the battery item
+pub fn update_battery(icon: &str, icon_color: &str, label_color: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("battery", &[
+        ("icon", icon),
+        ("icon.color", icon_color),
+        ("label.color", label_color),
+        ("label", label),
+    ])
+}
+
+/// Flash an item's icon and label a few times to draw attention to it, ending on `color`
+pub fn blink_item(item: &str, color: &str) -> Result<(), std::io::Error> {
+    Animation::new(item)
+        .keyframe("linear", 10, &[("icon.color", "0x00000000"), ("label.color", "0x00000000")])
+        .keyframe("linear", 10, &[("icon.color", color), ("label.color", color)])
+        .play(5)
+}
+
+/// A row in an item's popup menu
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PopupEntry {
+    pub label: String,
+    pub icon: Option<String>,
+    /// Shell command run when the row is clicked, after the popup closes
+    pub click_script: Option<String>,
+}
+
+/// Shell command opening a URL in the browser, for a popup row's click_script
+pub fn open_url_script(url: &str) -> String {
+    format!("open '{}'", url.replace('\'', "'\\''"))
+}
+
+/// `text` cut to `max` characters to fit a popup row, with an ellipsis if it was longer
+pub fn truncate(text: &str, max: usize) -> String {
+    let mut short: String = text.chars().take(max).collect();
+    if text.chars().count() > max {
+        short.push('…');
+    }
+    short
+}
+
+/// Name of a row in `parent`'s popup
+pub fn popup_item(parent: &str, index: usize) -> String {
+    format!("{}.popup.{}", parent, index)
+}
+
+/// Replace the rows of `parent`'s popup, which clicking a row closes
+pub fn set_popup(parent: &str, entries: &[PopupEntry]) -> Result<(), std::io::Error> {
+    let mut batch = SketchybarBatch::new();
+    // However many rows the last update added
+    batch.remove(&format!("/{}\\.popup\\..*/", parent.replace('.', "\\.")));
+
+    let close = format!("sketchybar --set {} popup.drawing=off", parent);
+    for (index, entry) in entries.iter().enumerate() {
+        let item = popup_item(parent, index);
+        let click_script = match &entry.click_script {
+            Some(script) => format!("{}; {}", close, script),
+            None => close.clone(),
+        };
+        let mut props = vec![("label", entry.label.as_str()), ("click_script", click_script.as_str())];
+        match &entry.icon {
+            Some(icon) => props.extend([("icon", icon.as_str()), ("icon.drawing", "on")]),
+            None => props.push(("icon.drawing", "off")),
+        }
+        batch.add_popup(parent, &item).set(&item, &props);
+    }
+    batch.execute()
+}
+
+/// Whether sketchybar is running, for the menu bar fallback
+pub fn is_running() -> bool {
+    Command::new("pgrep")
+        .args(["-x", "sketchybar"])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Slider in the volume item's popup
+pub const VOLUME_SLIDER: &str = "volume.slider";
+
+/// Update the volume item, and the slider in its popup when there is one
+pub fn update_volume(icon: &str, percentage: u8, slider: bool) -> Result<(), std::io::Error> {
+    let mut batch = SketchybarBatch::new();
+    batch.set_styled("volume", &[
+        ("icon", icon),
+        ("label", &format!("{}%", percentage)),
+    ]);
+    if slider {
+        batch.slide(VOLUME_SLIDER, percentage);
+    }
+    batch.execute()
+}
+
+/// Update the Wi-Fi item; the label is the network name, empty while disconnected or withheld
+pub fn update_wifi(icon: &str, ssid: Option<&str>) -> Result<(), std::io::Error> {
+    set_item("wifi", &[
+        ("icon", icon),
+        ("label", ssid.unwrap_or_default()),
+    ])
+}
+
+/// Update the CPU item
+pub fn update_cpu(icon: &str, percentage: u8) -> Result<(), std::io::Error> {
+    set_item("cpu", &[
+        ("icon", icon),
+        ("label", &format!("{}%", percentage)),
+    ])
+}
+
+/// Update the RAM item, its label colored by memory pressure
+pub fn update_ram(icon: &str, percentage: u8, label_color: &str) -> Result<(), std::io::Error> {
+    set_item("ram", &[
+        ("icon", icon),
+        ("label", &format!("{}%", percentage)),
+        ("label.color", label_color),
+    ])
+}
+
+/// Update the top_process item with the heaviest process
+pub fn update_top_process(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("top_process", &[
+        ("icon", icon),
+        ("label", label),
+    ])
+}
+
+/// Update the thermal item, its icon colored by the thermal state
+pub fn update_thermal(icon: &str, icon_color: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("thermal", &[
+        ("icon", icon),
+        ("icon.color", icon_color),
+        ("label", label),
+    ])
+}
+
+/// Update the fan item with the fan speeds
+pub fn update_fan(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("fan", &[
+        ("icon", icon),
+        ("label", label),
+    ])
+}
+
+/// Update the focus_mode item, its icon colored by whether a Focus is on
+pub fn update_focus_mode(icon: &str, icon_color: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("focus_mode", &[
+        ("icon", icon),
+        ("icon.color", icon_color),
+        ("label", label),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+    ])
+}
+
+/// Update the brightness item with the main display's level
+pub fn update_brightness(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("brightness", &[
+        ("icon", icon),
+        ("label", label),
+    ])
+}
+
+/// Update the calendar item with the next event, hiding the label when there is none
+pub fn update_calendar(icon: &str, label: &str, label_color: &str) -> Result<(), std::io::Error> {
+    set_item("calendar", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.color", label_color),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+    ])
+}
+
+/// Update the zoom item with the microphone state, hiding it outside a meeting
+pub fn update_zoom(icon: Option<&str>, icon_color: &str) -> Result<(), std::io::Error> {
+    match icon {
+        Some(icon) => set_item("zoom", &[
+            ("icon", icon),
+            ("icon.color", icon_color),
+            ("drawing", "on"),
+        ]),
+        None => set_item("zoom", &[("drawing", "off")]),
+    }
+}
+
+/// Update the weather item with the current condition and temperature
+pub fn update_weather(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("weather", &[
+        ("icon", icon),
+        ("label", label),
+    ])
+}
+
+/// Update the sun item with the next sunrise or sunset, hiding it while there's none for days
+pub fn update_sun(icon: Option<&str>, label: &str) -> Result<(), std::io::Error> {
+    match icon {
+        Some(icon) => set_item("sun", &[
+            ("icon", icon),
+            ("label", label),
+            ("drawing", "on"),
+        ]),
+        None => set_item("sun", &[("drawing", "off")]),
+    }
+}
+
+/// Update the moon item with the phase, naming it when `label` isn't empty
+pub fn update_moon(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("moon", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+    ])
+}
+
+/// Update the crypto item with the prices, in `label_color` while one is past an alert's bound
+pub fn update_crypto(icon: &str, label: &str, label_color: &str) -> Result<(), std::io::Error> {
+    set_item("crypto", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.color", label_color),
+    ])
+}
+
+/// Update the exchange item with the currency pair's rate
+pub fn update_exchange(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("exchange", &[
+        ("icon", icon),
+        ("label", label),
+    ])
+}
+
+/// Show the feeds item with the unread count, leaving the label out when it's empty
+pub fn update_feeds(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("feeds", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+        ("drawing", "on"),
+    ])
+}
+
+/// Show the reviews item with the size of the queue, leaving the label out when it's empty
+pub fn update_reviews(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("reviews", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+        ("drawing", "on"),
+    ])
+}
+
+/// Show the gitlab item with the assigned merge requests, in `color` while a pipeline has failed
+pub fn update_gitlab(icon: &str, label: &str, color: &str) -> Result<(), std::io::Error> {
+    set_item("gitlab", &[
+        ("icon", icon),
+        ("icon.color", color),
+        ("label", label),
+        ("label.color", color),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+        ("drawing", "on"),
+    ])
+}
+
+/// Show the jira item with the assigned issues, the label in `label_color` by the most urgent one
+pub fn update_jira(icon: &str, label: &str, label_color: &str) -> Result<(), std::io::Error> {
+    set_item("jira", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.color", label_color),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+        ("drawing", "on"),
+    ])
+}
+
+/// Show the linear item with the cycle's issue count, leaving the label out when it's empty
+pub fn update_linear(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("linear", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.drawing", if label.is_empty() { "off" } else { "on" }),
+        ("drawing", "on"),
+    ])
+}
+
+/// Update the appearance item
+pub fn update_appearance(icon: &str) -> Result<(), std::io::Error> {
+    set_item("appearance", &[("icon", icon)])
+}
+
+/// Update the load item, its label colored by how busy the cores are
+pub fn update_load(icon: &str, label: &str, label_color: &str) -> Result<(), std::io::Error> {
+    set_item("load", &[
+        ("icon", icon),
+        ("label", label),
+        ("label.color", label_color),
+    ])
+}
+
+/// Update the disk item with the read and write rates
+pub fn update_disk(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("disk", &[
+        ("icon", icon),
+        ("label", label),
+    ])
+}
+
+/// Update the network item with the download and upload rates
+pub fn update_network(icon: &str, label: &str) -> Result<(), std::io::Error> {
+    set_item("network", &[
+        ("icon", icon),
+        ("label", label),
+    ])
+}
+
+/// Update the audio_output item with the device sound plays on
+pub fn update_audio_output(icon: &str, device: &str) -> Result<(), std::io::Error> {
+    set_item("audio_output", &[
+        ("icon", icon),
+        ("label", device),
+    ])
+}
+
+/// Item properties showing an app icon image, or the glyph when there is none
+pub fn icon_image_props(image: Option<&str>) -> Vec<(&str, &str)> {
+    match image {
+        Some(path) => vec![
+            ("icon", ""),
+            ("icon.drawing", "on"),
+            ("icon.background.drawing", "on"),
+            ("icon.background.image", path),
+            ("icon.background.image.scale", "0.5"),
+        ],
+        None => vec![
+            ("icon.drawing", "on"),
+            ("icon.background.drawing", "off"),
+        ],
+    }
+}
+
+/// Update the front app item
+///
+/// `image` is `Some` when app icon images are enabled for front_app; its inner
+/// value is `None` when the app's icon couldn't be extracted.
+pub fn update_front_app(
+    icon: &str,
+    image: Option<Option<&str>>,
+    app_name: &str,
+    display: Option<&str>,
+) -> Result<(), std::io::Error> {
+    let mut batch = SketchybarBatch::new();
+    batch.set_styled("front_app", &[
+        ("icon", icon),
+        ("icon.font", icon_font()),
+        ("label", &format!("❯ {}", app_name)),
+    ]);
+    if let Some(display) = display {
+        batch.set("front_app", &[("display", display)]);
+    }
+    if let Some(image) = image {
+        batch.set("front_app", &icon_image_props(image));
+    }
+    batch.execute()
+}
+
+/// Update the brew outdated item
+pub fn update_brew(icon: &str, formulae: usize, casks: usize) -> Result<(), std::io::Error> {
+    let total = formulae + casks;
+    let label = if total == 0 {
+        "✓".to_string()
+    } else {
+        format!("{}", total)
+    };
+    set_item("brew", &[
+        ("icon", icon),
+        ("label", &label),
+    ])
+}
+
+/// Remove items that may or may not exist, such as custom items left from an earlier run
+pub fn remove_items(items: &[&str]) {
+    for item in items {
+        // sketchybar fails for items it doesn't know, which is expected here
+        if mach::send(&["--remove".to_string(), item.to_string()], TIMEOUT).is_some() {
+            continue;
+        }
+        let _ = Command::new("sketchybar")
+            .args(["--remove", item])
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// Update a custom item, leaving its icon and drawing alone when it doesn't set them
+pub fn update_custom(item: &str, info: &CustomInfo) -> Result<(), std::io::Error> {
+    let mut props = vec![("label", info.label.as_str())];
+    if let Some(icon) = &info.icon {
+        props.push(("icon", icon));
+    }
+    props.extend(info.style.props());
+    if let Some(visible) = info.visible {
+        props.push(("drawing", if visible { "on" } else { "off" }));
+    }
+    set_item(item, &props)
+}
+
+/// Update the Microsoft Teams notification item
+pub fn update_teams(icon: &str, icon_color: &str, border_color: &str, notification_count: u32) -> Result<(), std::io::Error> {
+    let label = if notification_count > 0 {
+        format!("{}", notification_count)
+    } else {
+        String::new()
+    };
+    set_item("teams", &[
+        ("icon", icon),
+        ("icon.color", icon_color),
+        ("background.border_color", border_color),
+        ("label", &label),
+        ("drawing", "on"),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_deduplicated() {
+        let batch = args("--set clock label=1 icon=x --set battery label=2 --set clock label=3");
+        assert_eq!(deduplicated(&batch), args("--set clock icon=x --set battery label=2 --set clock label=3"));
+
+        // Sets emptied by later ones are dropped, and toggles kept
+        let batch = args(
+            "--set a drawing=on --set b popup.drawing=toggle --set a drawing=off --set b popup.drawing=toggle",
+        );
+        let expected = args("--set b popup.drawing=toggle --set a drawing=off --set b popup.drawing=toggle");
+        assert_eq!(deduplicated(&batch), expected);
+
+        // Other commands and animations keep the sets around them
+        let batch = args("--set a drawing=on --remove a --add item a left --set a drawing=on");
+        assert_eq!(deduplicated(&batch), batch);
+        let batch = args("--animate sin 15 --set a y=1 --animate sin 15 --set a y=0");
+        assert_eq!(deduplicated(&batch), batch);
+    }
+
+    #[test]
+    fn test_popup_text() {
+        assert_eq!(open_url_script("https://example.com/?q=it's"), "open 'https://example.com/?q=it'\\''s'");
+        assert_eq!(truncate("Fix the parser", 20), "Fix the parser");
+        assert_eq!(truncate("Fix the parser", 7), "Fix the…");
+        assert_eq!(truncate("Ünïcödé", 3), "Ünï…");
+    }
+
+    #[test]
+    fn test_animation_passes() {
+        let pulse = Animation::new("brew")
+            .keyframe("sin", 15, &[("icon.color", "0xfffabd2f")])
+            .keyframe("sin", 15, &[("icon.color", "0xffffffff")]);
+        assert_eq!(pulse.duration(), FRAME * 30);
+
+        // A loop is queued a whole number of passes ahead, at least one
+        let (batch, ahead) = pulse.passes(Duration::from_secs(2));
+        assert_eq!(ahead, FRAME * 30 * 4);
+        assert_eq!(batch.args.iter().filter(|arg| *arg == "--animate").count(), 8);
+        assert_eq!(batch.args[..6], args("--animate sin 15 --set brew icon.color=0xfffabd2f"));
+        assert_eq!(pulse.passes(Duration::ZERO).1, pulse.duration());
+    }
+
+    #[test]
+    fn test_cancel_animation() {
+        let first = Running::new("brew".to_string(), Vec::new());
+        register("test.cancel", first.clone());
+        assert!(is_animating("test.cancel", first.id));
+
+        // A replacement stops the loop of the animation it replaces
+        let second = Running::new("brew".to_string(), Vec::new());
+        register("test.cancel", second.clone());
+        assert!(!is_animating("test.cancel", first.id));
+        assert!(is_animating("test.cancel", second.id));
+
+        cancel_animation("test.cancel").unwrap();
+        assert!(!is_animating("test.cancel", second.id));
+        cancel_animation("test.cancel").unwrap();
+    }
+
+    #[test]
+    fn test_animation_baseline() {
+        let running = Running::new("brew".to_string(), vec![("icon.color".to_string(), "0xffffffff".to_string())]);
+        assert_eq!(running.reset().unwrap().args, args("--set brew icon.color=0xffffffff"));
+        assert!(Running::new("brew".to_string(), Vec::new()).reset().is_none());
+    }
+}
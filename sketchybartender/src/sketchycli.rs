@@ -1,9 +1,16 @@
 //! Lightweight CLI tool that forwards messages to the daemon via socket
 
 use std::env;
-use std::io::Write;
+use std::io::{self, BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use sketchybartender::config::{Config, Diagnostic, Severity, DEFAULT_PROFILE};
+use sketchybartender::monitor_map::{MonitorMapper, Snapshot};
+use sketchybartender::protocol::{Request, Response};
+use sketchybartender::{secrets, service};
 
 fn get_socket_path() -> PathBuf {
     let cache_dir = env::var("XDG_CACHE_HOME")
@@ -16,30 +23,460 @@ fn get_socket_path() -> PathBuf {
     cache_dir.join("sketchybar").join("helper.sock")
 }
 
+/// Ask a yes/no question on the terminal, defaulting to yes
+fn confirm(question: &str) -> bool {
+    print!("{} [Y/n] ", question);
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    let answer = answer.trim();
+    answer.is_empty() || answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+}
+
+/// Print diagnostics for a config file and return whether it has errors
+fn report_diagnostics(path: &Path, diagnostics: &[Diagnostic]) -> bool {
+    for diagnostic in diagnostics {
+        eprintln!("{}: {}", path.display(), diagnostic);
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    let warnings = diagnostics.len() - errors;
+
+    if diagnostics.is_empty() {
+        println!("✓ {} is valid", path.display());
+    } else {
+        eprintln!("{} error(s), {} warning(s)", errors, warnings);
+    }
+
+    errors > 0
+}
+
+/// Validate the config file, exiting non-zero if it contains errors
+fn config_validate(path: &Path) {
+    match Config::validate_file(path) {
+        Ok(diagnostics) => {
+            if report_diagnostics(path, &diagnostics) {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to validate {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Open the config file in $EDITOR, validating after each save
+fn config_edit(path: &Path) {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    loop {
+        // Let the shell split the editor command so values like "code --wait" work
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path)
+            .status();
+
+        match status {
+            Ok(s) if s.success() => {}
+            Ok(s) => {
+                eprintln!("Editor exited with {}", s);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Failed to launch editor '{}': {}", editor, e);
+                std::process::exit(1);
+            }
+        }
+
+        let has_errors = match Config::validate_file(path) {
+            Ok(diagnostics) => report_diagnostics(path, &diagnostics),
+            Err(e) => {
+                eprintln!("Failed to validate {}: {}", path.display(), e);
+                true
+            }
+        };
+
+        if !has_errors {
+            break;
+        }
+
+        if !confirm("Settings with errors would keep their defaults. Edit again?") {
+            std::process::exit(1);
+        }
+    }
+
+    // sketchybarrc restarts the daemon, which picks up the new config
+    if confirm("Reload sketchybar to apply the new config?") {
+        match Command::new("sketchybar").arg("--reload").status() {
+            Ok(s) if s.success() => println!("✓ sketchybar reloaded"),
+            Ok(s) => eprintln!("sketchybar --reload exited with {}", s),
+            Err(e) => eprintln!("Failed to run sketchybar --reload: {}", e),
+        }
+    }
+}
+
+/// Handle `sketchycli config <subcommand>` locally without the daemon
+fn config_command(args: &[String]) {
+    let path = Config::get_config_path();
+
+    // Converts a legacy sketchybartenderrc, or writes the defaults, so there is a file to work on
+    if !path.exists() {
+        Config::load();
+    }
+
+    match args.first().map(|s| s.as_str()) {
+        Some("validate") => config_validate(&path),
+        Some("edit") => config_edit(&path),
+        _ => {
+            eprintln!("Usage: sketchycli config <validate|edit>");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Handle `sketchycli secret <set|get|delete> <name>` locally without the daemon
+fn secret_command(args: &[String]) {
+    let result = match (args.first().map(String::as_str), args.get(1)) {
+        (Some("set"), Some(name)) => secrets::set(name).map(|_| println!("✓ Stored {} in the keychain", name)),
+        (Some("get"), Some(name)) => secrets::get(name).map(|secret| println!("{}", secret)),
+        (Some("delete"), Some(name)) => secrets::delete(name).map(|_| println!("✓ Deleted {}", name)),
+        _ => Err("Usage: sketchycli secret <set|get|delete> <name>".to_string()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// List the profiles in the config, marking the selected one
+fn profile_list() {
+    let config = Config::load();
+    let selected = Config::selected_profile();
+
+    let names = std::iter::once(DEFAULT_PROFILE).chain(config.profiles.keys().map(String::as_str));
+    for name in names {
+        let marker = if name == selected { "*" } else { " " };
+        println!("{} {}", marker, name);
+    }
+}
+
+/// Get the workspaces on each aerospace monitor, in aerospace's order
+fn workspaces_by_monitor() -> Vec<(u32, Vec<String>)> {
+    let output = match Command::new("aerospace")
+        .args(["list-workspaces", "--all", "--format", "%{workspace}|%{monitor-id}"])
+        .output()
+    {
+        Ok(o) if o.status.success() => o,
+        _ => return Vec::new(),
+    };
+
+    let mut result: Vec<(u32, Vec<String>)> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((workspace, monitor)) = line.split_once('|') else {
+            continue;
+        };
+        let Ok(monitor) = monitor.trim().parse::<u32>() else {
+            continue;
+        };
+        match result.iter_mut().find(|(id, _)| *id == monitor) {
+            Some((_, workspaces)) => workspaces.push(workspace.to_string()),
+            None => result.push((monitor, vec![workspace.to_string()])),
+        }
+    }
+
+    result
+}
+
+/// Render the monitor snapshot as JSON
+fn monitors_json(snapshot: &Snapshot, workspaces: &[(u32, Vec<String>)]) -> serde_json::Value {
+    let workspaces_on = |monitor: u32| {
+        workspaces
+            .iter()
+            .find(|(id, _)| *id == monitor)
+            .map(|(_, workspaces)| workspaces.clone())
+            .unwrap_or_default()
+    };
+
+    serde_json::json!({
+        "mainDisplay": snapshot.mappings.main_display,
+        "builtinDisplay": snapshot.mappings.builtin_display,
+        "screens": snapshot.screens.iter().map(|screen| serde_json::json!({
+            "displayId": screen.number,
+            "index": screen.index,
+            "uuid": screen.uuid,
+            "resolution": screen.resolution,
+            "builtin": screen.builtin,
+            "name": screen.name,
+            "mirrors": snapshot.mirrors.get(&screen.number),
+        })).collect::<Vec<_>>(),
+        "sketchybarDisplays": snapshot.displays.iter().map(|display| serde_json::json!({
+            "arrangementId": display.arrangement_id,
+            "displayId": display.direct_display_id,
+            "uuid": display.uuid,
+        })).collect::<Vec<_>>(),
+        "aerospaceMonitors": snapshot.monitors.iter().map(|monitor| serde_json::json!({
+            "id": monitor.id,
+            "screenIndex": monitor.screen_index,
+            "name": monitor.name,
+            "sketchybarDisplay": snapshot.mappings.monitors.get(&monitor.id),
+            "fallback": snapshot.fallbacks.contains(&monitor.id),
+            "workspaces": workspaces_on(monitor.id),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Print the monitor snapshot for humans
+fn print_monitors(snapshot: &Snapshot, workspaces: &[(u32, Vec<String>)]) {
+    println!("Screens (AppKit):");
+    for screen in &snapshot.screens {
+        let mirror = match snapshot.mirrors.get(&screen.number) {
+            Some(primary) => format!("  (mirrors {})", primary),
+            None => String::new(),
+        };
+        println!(
+            "  {}  display {}  {}  {}  {}{}",
+            screen.index, screen.number, screen.resolution, screen.uuid, screen.name, mirror
+        );
+    }
+
+    println!("\nSketchybar displays:");
+    for display in &snapshot.displays {
+        println!(
+            "  {}  display {}  {}",
+            display.arrangement_id,
+            display.direct_display_id,
+            display.uuid.as_deref().unwrap_or("-")
+        );
+    }
+
+    println!("\nAerospace monitors:");
+    for monitor in &snapshot.monitors {
+        let target = match snapshot.mappings.monitors.get(&monitor.id) {
+            Some(display) if snapshot.fallbacks.contains(&monitor.id) => {
+                format!("sketchybar display {} (no match, main display)", display)
+            }
+            Some(display) => format!("sketchybar display {}", display),
+            None => "unmapped".to_string(),
+        };
+        let assigned = workspaces
+            .iter()
+            .find(|(id, _)| *id == monitor.id)
+            .map(|(_, workspaces)| workspaces.join(", "))
+            .unwrap_or_default();
+        println!("  {}  {}  -> {}", monitor.id, monitor.name, target);
+        if !assigned.is_empty() {
+            println!("     workspaces: {}", assigned);
+        }
+    }
+}
+
+/// Handle `sketchycli monitors [--json]` locally without the daemon
+/// Handle `sketchycli service <install|uninstall|start|stop>` locally without the daemon
+fn service_command(args: &[String]) {
+    let result = match args.first().map(String::as_str) {
+        Some("install") => env::current_exe()
+            .map(|exe| exe.with_file_name("sketchybartender"))
+            .map_err(|e| format!("Failed to find sketchybartender: {}", e))
+            .and_then(|daemon| service::install(&daemon))
+            .map(|_| println!("✓ Installed {}; launchd now keeps the daemon running", service::plist_path().display())),
+        Some("uninstall") => service::uninstall().map(|_| println!("✓ Uninstalled {}", service::LABEL)),
+        Some("start") => service::start().map(|_| println!("✓ Started {}", service::LABEL)),
+        Some("stop") => service::stop().map(|_| println!("✓ Stopped {}", service::LABEL)),
+        _ => Err("Usage: sketchycli service <install|uninstall|start|stop>".to_string()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn monitors_command(args: &[String]) {
+    let snapshot = MonitorMapper::new().snapshot();
+    let workspaces = workspaces_by_monitor();
+
+    if args.iter().any(|a| a == "--json") {
+        let json = monitors_json(&snapshot, &workspaces);
+        println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+    } else {
+        print_monitors(&snapshot, &workspaces);
+    }
+}
+
+/// How long `--wait` and queries block for the daemon's reply
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
-        eprintln!("Usage: sketchycli <command> [args...]");
+        eprintln!("Usage: sketchycli [send] [--wait] <command> [args...]");
+        std::process::exit(1);
+    }
+
+    if args[1] == "config" {
+        config_command(&args[2..]);
+        return;
+    }
+
+    if args[1] == "secret" {
+        secret_command(&args[2..]);
+        return;
+    }
+
+    if args[1] == "service" {
+        service_command(&args[2..]);
+        return;
+    }
+
+    if args[1] == "monitors" {
+        monitors_command(&args[2..]);
+        return;
+    }
+
+    if args[1] == "profile" && args.len() == 2 {
+        profile_list();
+        return;
+    }
+
+    // `send` is optional: `sketchycli send on-focus-changed` == `sketchycli on-focus-changed`
+    let rest = if args[1] == "send" { &args[2..] } else { &args[1..] };
+    // `--wait` is only a flag before the command, so its arguments are passed on as given
+    let (wait_flag, rest) = match rest.split_first() {
+        Some((first, rest)) if first == "--wait" => (true, rest),
+        _ => (false, rest),
+    };
+    // Profile switches and quiet mode always wait, so a bad name is reported
+    let wait = wait_flag || rest.first().is_some_and(|a| a == "profile" || a == "quiet");
+    let command: Vec<&str> = rest.iter().map(|s| s.as_str()).collect();
+
+    if command.is_empty() {
+        eprintln!("Usage: sketchycli [send] [--wait] <command> [args...]");
         std::process::exit(1);
     }
 
-    // Forward all arguments (excluding program name) to daemon
-    let message = args[1..].join(" ");
+    // Forward all arguments (excluding program name and flags) to daemon
+    let message = command.join(" ");
+    let is_watch = command[0] == "watch";
+    // `quiet` on its own asks for the current mode
+    let is_query = command[0] == "icons" || command[0] == "status" || command[0] == "query" || command == ["quiet"];
 
     // Forward to daemon
     let socket_path = get_socket_path();
-    match UnixStream::connect(&socket_path) {
-        Ok(mut stream) => {
-            if let Err(e) = writeln!(stream, "{}", message) {
-                eprintln!("Failed to send message: {}", e);
-                std::process::exit(1);
-            }
-        }
+    let mut stream = match UnixStream::connect(&socket_path) {
+        Ok(stream) => stream,
         Err(e) => {
             eprintln!("Failed to connect to daemon at {:?}: {}", socket_path, e);
             eprintln!("Is sketchybartender daemon running?");
             std::process::exit(1);
         }
+    };
+
+    // Queries and waiting sends get a reply, so they go as JSON requests
+    if command[0] == "status" || command[0] == "query" {
+        let request_args: Vec<&str> = command.iter().copied().filter(|arg| *arg != "--json").collect();
+        let reply = request(stream, &request_args);
+        if command.contains(&"--json") {
+            println!("{}", serde_json::to_string_pretty(&reply).unwrap_or_default());
+        } else if request_args == ["query"] {
+            // Every item, each with its fields indented below
+            for (item, fields) in reply.as_object().into_iter().flatten() {
+                println!("{}", item);
+                print_fields(fields, "  ");
+            }
+        } else {
+            // Failure counts are for scripts; people see which providers are degraded
+            let mut reply = reply;
+            if let Some(fields) = reply.as_object_mut() {
+                fields.remove("providers");
+            }
+            print_fields(&reply, "");
+        }
+        return;
+    }
+    if is_query || (wait && !is_watch) {
+        match request(stream, &command) {
+            serde_json::Value::Null => {}
+            serde_json::Value::String(output) => println!("{}", output),
+            data => println!("{}", data),
+        }
+        return;
+    }
+
+    if let Err(e) = writeln!(stream, "{}", message) {
+        eprintln!("Failed to send message: {}", e);
+        std::process::exit(1);
+    }
+
+    // `watch` keeps the connection open and prints events as they arrive
+    if is_watch {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => println!("{}", line),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Send a JSON request and return what the daemon replied, exiting with its error if it failed
+fn request(mut stream: UnixStream, command: &[&str]) -> serde_json::Value {
+    let request = Request::new(1, command[0], &command[1..]);
+    if let Err(e) = writeln!(stream, "{}", request.to_json()) {
+        eprintln!("Failed to send message: {}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = stream.set_read_timeout(Some(WAIT_TIMEOUT)) {
+        eprintln!("Failed to set read timeout: {}", e);
+    }
+
+    let mut reply = String::new();
+    let response = match BufReader::new(stream).read_line(&mut reply) {
+        Ok(0) => Err("Daemon closed the connection without replying".to_string()),
+        Ok(_) => Response::parse(reply.trim()),
+        Err(e) => Err(format!("No reply from daemon: {}", e)),
+    };
+    match response.and_then(|response| response.result) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Print a `status` or `query` reply for humans, one field per line
+fn print_fields(fields: &serde_json::Value, indent: &str) {
+    let Some(values) = fields.as_object() else {
+        return;
+    };
+    for (key, value) in values {
+        let shown = match value {
+            serde_json::Value::Null => "-".to_string(),
+            serde_json::Value::String(value) => value.clone(),
+            serde_json::Value::Array(values) if values.is_empty() => "-".to_string(),
+            serde_json::Value::Array(values) => {
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|value| value.as_str().map_or_else(|| value.to_string(), str::to_string))
+                    .collect();
+                values.join(", ")
+            }
+            serde_json::Value::Object(fields) => {
+                fields.iter().map(|(field, value)| format!("{}={}", field, value)).collect::<Vec<_>>().join(" ")
+            }
+            value => value.to_string(),
+        };
+        println!("{}{:<20} {}", indent, key, shown);
     }
 }
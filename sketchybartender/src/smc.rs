@@ -0,0 +1,269 @@
+//! Sensor readings from the System Management Controller
+//!
+//! The SMC answers four-character keys through the `AppleSMC` IOKit service:
+//! one call asks for a key's type and size, a second for its bytes. Which keys
+//! exist depends on the Mac: Intel Macs report CPU temperatures as `TC0P` and
+//! the like in 8.8 fixed point, Apple silicon reports SoC temperatures as
+//! `Tp09` and the like in floats. Fan speeds are `F<n>Ac` for each of the
+//! `FNum` fans. Outside macOS there is no SMC, and every reading is None.
+//!
+//! The thermal and fan items both need a reading, so the last one is kept and
+//! handed to whichever asks next, as long as it's recent enough.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// CPU and SoC temperature keys, of which the hottest present one is reported
+const TEMPERATURE_KEYS: &[&str] = &[
+    // Intel: CPU proximity, die and core sensors
+    "TC0P", "TC0D", "TC0E", "TC0F", "TCXC",
+    // Apple silicon: performance and efficiency core clusters, M1 to M3
+    "Tp01", "Tp05", "Tp09", "Tp0D", "Tp0T", "Tp0X", "Tp0b", "Tp1h", "Tp1t", "Tp1p", "Tp1l", "Te05", "Tf04", "Tf09",
+];
+
+/// Decode a key's bytes by its SMC data type
+fn decode(data_type: &[u8; 4], bytes: &[u8]) -> Option<f32> {
+    let word = |bytes: &[u8]| Some(u16::from_be_bytes(bytes.get(..2)?.try_into().ok()?));
+    match data_type {
+        b"flt " => Some(f32::from_le_bytes(bytes.get(..4)?.try_into().ok()?)),
+        b"sp78" => Some(f32::from(word(bytes)? as i16) / 256.0),
+        b"fpe2" => Some(f32::from(word(bytes)?) / 4.0),
+        b"ui8 " => bytes.first().map(|byte| f32::from(*byte)),
+        b"ui16" => word(bytes).map(f32::from),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    /// kIOMainPortDefault
+    pub const MAIN_PORT: u32 = 0;
+    /// The SMC user client's method for every command
+    pub const HANDLE_YPC_EVENT: u32 = 2;
+    pub const READ_BYTES: u8 = 5;
+    pub const READ_KEY_INFO: u8 = 9;
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct Version {
+        _major: u8,
+        _minor: u8,
+        _build: u8,
+        _reserved: u8,
+        _release: u16,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct PowerLimits {
+        _version: u16,
+        _length: u16,
+        _cpu: u32,
+        _gpu: u32,
+        _memory: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default, Clone, Copy)]
+    pub struct KeyInfo {
+        pub size: u32,
+        pub data_type: u32,
+        pub _attributes: u8,
+    }
+
+    /// `SMCKeyData_t`, the input and output of every command
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct KeyData {
+        pub key: u32,
+        pub _version: Version,
+        pub _limits: PowerLimits,
+        pub info: KeyInfo,
+        pub result: u8,
+        pub _status: u8,
+        pub command: u8,
+        pub _data32: u32,
+        pub bytes: [u8; 32],
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        pub fn IOServiceMatching(name: *const c_char) -> *mut c_void;
+        pub fn IOServiceGetMatchingService(main_port: u32, matching: *mut c_void) -> u32;
+        pub fn IOServiceOpen(service: u32, owning_task: u32, kind: u32, connection: *mut u32) -> i32;
+        pub fn IOServiceClose(connection: u32) -> i32;
+        pub fn IOConnectCallStructMethod(
+            connection: u32,
+            selector: u32,
+            input: *const c_void,
+            input_size: usize,
+            output: *mut c_void,
+            output_size: *mut usize,
+        ) -> i32;
+        pub fn IOObjectRelease(object: u32) -> i32;
+    }
+
+    extern "C" {
+        pub static mach_task_self_: u32;
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::c_void;
+
+    use super::ffi;
+
+    /// An open connection to the SMC, closed on drop
+    pub struct Connection(u32);
+
+    impl Connection {
+        pub fn open() -> Option<Self> {
+            unsafe {
+                let service = ffi::IOServiceGetMatchingService(
+                    ffi::MAIN_PORT,
+                    ffi::IOServiceMatching(c"AppleSMC".as_ptr()),
+                );
+                if service == 0 {
+                    return None;
+                }
+                let mut connection = 0;
+                let result = ffi::IOServiceOpen(service, ffi::mach_task_self_, 0, &mut connection);
+                ffi::IOObjectRelease(service);
+                (result == 0).then_some(Self(connection))
+            }
+        }
+
+        fn call(&self, input: &ffi::KeyData) -> Option<ffi::KeyData> {
+            let mut output = ffi::KeyData::default();
+            let mut size = std::mem::size_of::<ffi::KeyData>();
+            let result = unsafe {
+                ffi::IOConnectCallStructMethod(
+                    self.0,
+                    ffi::HANDLE_YPC_EVENT,
+                    input as *const ffi::KeyData as *const c_void,
+                    std::mem::size_of::<ffi::KeyData>(),
+                    &mut output as *mut ffi::KeyData as *mut c_void,
+                    &mut size,
+                )
+            };
+            (result == 0 && output.result == 0).then_some(output)
+        }
+
+        /// A key's data type and bytes, or None if this Mac doesn't have it
+        pub fn read(&self, key: &str) -> Option<([u8; 4], Vec<u8>)> {
+            let key = u32::from_be_bytes(key.as_bytes().try_into().ok()?);
+            let info = self.call(&ffi::KeyData { key, command: ffi::READ_KEY_INFO, ..Default::default() })?.info;
+            let size = (info.size as usize).min(32);
+            let data = self.call(&ffi::KeyData { key, info, command: ffi::READ_BYTES, ..Default::default() })?;
+            Some((info.data_type.to_be_bytes(), data.bytes[..size].to_vec()))
+        }
+    }
+
+    impl Drop for Connection {
+        fn drop(&mut self) {
+            unsafe { ffi::IOServiceClose(self.0) };
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub struct Connection;
+
+    impl Connection {
+        pub fn open() -> Option<Self> {
+            None
+        }
+
+        pub fn read(&self, _key: &str) -> Option<([u8; 4], Vec<u8>)> {
+            None
+        }
+    }
+}
+
+/// One reading of the sensors the thermal and fan items show
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sensors {
+    /// The hottest CPU or SoC sensor in °C, None where the SMC has none of the known keys
+    pub temperature: Option<f32>,
+    /// Each fan's speed in RPM, empty on fanless Macs
+    pub fans: Vec<f32>,
+}
+
+fn read() -> Option<Sensors> {
+    let connection = native::Connection::open()?;
+    let value = |key: &str| connection.read(key).and_then(|(data_type, bytes)| decode(&data_type, &bytes));
+    let temperature = TEMPERATURE_KEYS
+        .iter()
+        .filter_map(|key| value(key))
+        // Absent sensors read as 0, and a few report garbage
+        .filter(|celsius| (1.0..150.0).contains(celsius))
+        .reduce(f32::max);
+    let count = value("FNum").unwrap_or(0.0) as usize;
+    let fans = (0..count).filter_map(|fan| value(&format!("F{}Ac", fan))).collect();
+    Some(Sensors { temperature, fans })
+}
+
+/// The last reading and when it was taken
+fn last_lock() -> &'static Mutex<Option<(Instant, Sensors)>> {
+    static LAST: OnceLock<Mutex<Option<(Instant, Sensors)>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// Read the sensors, or reuse the last reading if it's younger than `max_age`
+pub fn sensors(max_age: Duration) -> Option<Sensors> {
+    cached(last_lock(), max_age, read)
+}
+
+/// The reading kept in `last` if it's younger than `max_age`, or a new one from `read` kept in its place
+fn cached(
+    last: &Mutex<Option<(Instant, Sensors)>>,
+    max_age: Duration,
+    read: impl FnOnce() -> Option<Sensors>,
+) -> Option<Sensors> {
+    let mut last = last.lock().ok()?;
+    if let Some((at, sensors)) = last.as_ref() {
+        if at.elapsed() < max_age {
+            return Some(sensors.clone());
+        }
+    }
+    let sensors = read()?;
+    *last = Some((Instant::now(), sensors.clone()));
+    Some(sensors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"flt ", &47.5f32.to_le_bytes()), Some(47.5));
+        assert_eq!(decode(b"sp78", &[0x33, 0x80]), Some(51.5));
+        assert_eq!(decode(b"fpe2", &[0x1f, 0x40]), Some(2000.0));
+        assert_eq!(decode(b"ui8 ", &[2]), Some(2.0));
+        assert_eq!(decode(b"sp78", &[0x33]), None);
+        assert_eq!(decode(b"ch8*", b"abcd"), None);
+    }
+
+    #[test]
+    fn test_cached() {
+        let last = Mutex::new(None);
+        let reading = |celsius: f32| move || Some(Sensors { temperature: Some(celsius), fans: Vec::new() });
+        let temperature = |sensors: Option<Sensors>| sensors.and_then(|sensors| sensors.temperature);
+
+        assert_eq!(temperature(cached(&last, Duration::from_secs(60), reading(50.0))), Some(50.0));
+        // Within max_age the fan item gets the thermal item's reading
+        assert_eq!(temperature(cached(&last, Duration::from_secs(60), reading(70.0))), Some(50.0));
+        // Past it the sensors are read again, and the new reading kept
+        assert_eq!(temperature(cached(&last, Duration::ZERO, reading(70.0))), Some(70.0));
+        assert_eq!(temperature(cached(&last, Duration::from_secs(60), reading(90.0))), Some(70.0));
+
+        // A failed read keeps the last reading for the next caller
+        assert_eq!(cached(&last, Duration::ZERO, || None), None);
+        assert_eq!(temperature(cached(&last, Duration::from_secs(60), reading(90.0))), Some(70.0));
+    }
+}
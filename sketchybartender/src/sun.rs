@@ -0,0 +1,178 @@
+//! Sunrise, sunset and golden hour, worked out from the date and coordinates
+//!
+//! The sunrise equation (as on Wikipedia's page of that name) is within a
+//! minute or two away from the poles, which is as close as the item shows.
+//! Sunrise and sunset are when the top of the sun meets the horizon, allowing
+//! for refraction; golden hour is while the sun is less than 6° above it.
+
+use std::process::Command;
+
+/// Sun elevation at sunrise and sunset: the sun's radius and refraction at the horizon
+const HORIZON: f64 = -0.833;
+
+/// Sun elevation at which golden hour ends in the morning and starts in the evening
+const GOLDEN_HOUR: f64 = 6.0;
+
+/// Julian day of 1970-01-01 00:00 UTC
+const UNIX_EPOCH_JULIAN: f64 = 2_440_587.5;
+
+/// Days from 1970-01-01 to 2000-01-01, the epoch of the equation
+const J2000_DAYS: i64 = 10_957;
+
+/// A moment in the sun's day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Sunrise,
+    /// Morning golden hour is over
+    GoldenHourEnd,
+    /// Evening golden hour begins
+    GoldenHourStart,
+    Sunset,
+}
+
+impl Transition {
+    pub fn name(self) -> &'static str {
+        match self {
+            Transition::Sunrise => "sunrise",
+            Transition::GoldenHourEnd => "golden_hour_end",
+            Transition::GoldenHourStart => "golden_hour_start",
+            Transition::Sunset => "sunset",
+        }
+    }
+}
+
+/// The next transition and its Unix time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Next {
+    pub transition: Transition,
+    pub at: i64,
+}
+
+/// Unix times at which the sun crosses `elevation` going up and coming down on the solar day
+/// `day` days after 1970-01-01, or None while it stays above or below it all day
+fn crossings(day: i64, latitude: f64, longitude: f64, elevation: f64) -> Option<(i64, i64)> {
+    // Mean solar noon at the longitude, in days since 2000-01-01 12:00 UTC
+    let noon = (day - J2000_DAYS) as f64 - longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * noon).rem_euclid(360.0).to_radians();
+    let center = 1.9148 * anomaly.sin() + 0.0200 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic = (anomaly.to_degrees() + center + 180.0 + 102.9372).rem_euclid(360.0).to_radians();
+    let transit = 2_451_545.0 + noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic).sin();
+
+    let declination = (ecliptic.sin() * 23.4397_f64.to_radians().sin()).asin();
+    let latitude = latitude.to_radians();
+    let hour_angle = (elevation.to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&hour_angle) {
+        return None;
+    }
+    let half_day = hour_angle.acos().to_degrees() / 360.0;
+    let unix = |julian: f64| ((julian - UNIX_EPOCH_JULIAN) * 86400.0).round() as i64;
+    Some((unix(transit - half_day), unix(transit + half_day)))
+}
+
+/// The transitions on a solar day, in order
+fn transitions(day: i64, latitude: f64, longitude: f64, golden_hour: bool) -> Vec<Next> {
+    let mut transitions = Vec::new();
+    let mut add = |elevation, rising, setting| {
+        if let Some((up, down)) = crossings(day, latitude, longitude, elevation) {
+            transitions.push(Next { transition: rising, at: up });
+            transitions.push(Next { transition: setting, at: down });
+        }
+    };
+    add(HORIZON, Transition::Sunrise, Transition::Sunset);
+    if golden_hour {
+        add(GOLDEN_HOUR, Transition::GoldenHourEnd, Transition::GoldenHourStart);
+    }
+    transitions.sort_by_key(|next| next.at);
+    transitions
+}
+
+/// The first transition after `now`, or None through polar day or night
+pub fn next(now: i64, latitude: f64, longitude: f64, golden_hour: bool) -> Option<Next> {
+    let today = now.div_euclid(86400);
+    (today - 1..=today + 2)
+        .flat_map(|day| transitions(day, latitude, longitude, golden_hour))
+        .find(|next| next.at > now)
+}
+
+/// `+0200` or `-0530` as seconds east of UTC
+pub fn parse_offset(offset: &str) -> Option<i64> {
+    let (sign, digits) = offset.split_at_checked(1)?;
+    let sign = match sign {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours: i64 = digits.get(..2)?.parse().ok()?;
+    let minutes: i64 = digits.get(2..4)?.parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The Unix time and the local time zone's offset from UTC in seconds
+pub fn now() -> Result<(i64, i64), String> {
+    let output = Command::new("date")
+        .arg("+%s %z")
+        .output()
+        .map_err(|e| format!("Failed to run date: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (seconds, offset) = stdout.trim().split_once(' ').ok_or_else(|| format!("Unexpected time: {}", stdout))?;
+    match (seconds.parse::<i64>(), parse_offset(offset)) {
+        (Ok(seconds), Some(offset)) => Ok((seconds, offset)),
+        _ => Err(format!("Unexpected time: {}", stdout.trim())),
+    }
+}
+
+/// A Unix time as `HH:MM` at `offset` seconds from UTC, to the nearest minute
+pub fn local_time(at: i64, offset: i64) -> String {
+    let minutes = (at + offset + 30).rem_euclid(86400) / 60;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::days_from_civil;
+
+    /// Local `HH:MM` of both crossings
+    fn times(day: i64, latitude: f64, longitude: f64, elevation: f64, offset: i64) -> Option<(String, String)> {
+        let (up, down) = crossings(day, latitude, longitude, elevation)?;
+        Some((local_time(up, offset), local_time(down, offset)))
+    }
+
+    #[test]
+    fn test_crossings() {
+        let pair = |up: &str, down: &str| Some((up.to_string(), down.to_string()));
+        let berlin = days_from_civil(2026, 10, 16);
+        assert_eq!(times(berlin, 52.52, 13.405, HORIZON, 7200), pair("07:33", "18:11"));
+        assert_eq!(times(berlin, 52.52, 13.405, GOLDEN_HOUR, 7200), pair("08:20", "17:24"));
+        // Sunset falls on the next day in UTC
+        assert_eq!(times(days_from_civil(2026, 6, 21), 40.7128, -74.006, HORIZON, -4 * 3600), pair("05:25", "20:31"));
+        assert_eq!(times(days_from_civil(2026, 3, 20), -33.87, 151.21, HORIZON, 11 * 3600), pair("06:58", "19:08"));
+        // Polar night and midnight sun in Tromsø
+        assert_eq!(times(days_from_civil(2026, 12, 21), 69.65, 18.96, HORIZON, 3600), None);
+        assert_eq!(times(days_from_civil(2026, 6, 21), 69.65, 18.96, HORIZON, 7200), None);
+    }
+
+    #[test]
+    fn test_next() {
+        // 2026-10-16 12:00 in Berlin
+        let noon = days_from_civil(2026, 10, 16) * 86400 + 10 * 3600;
+        let next = next(noon, 52.52, 13.405, false).unwrap();
+        assert_eq!((next.transition, local_time(next.at, 7200)), (Transition::Sunset, "18:11".to_string()));
+        let golden = super::next(noon, 52.52, 13.405, true).unwrap();
+        assert_eq!(golden.transition, Transition::GoldenHourStart);
+        let morning = super::next(next.at, 52.52, 13.405, true).unwrap();
+        assert_eq!((morning.transition, local_time(morning.at, 7200)), (Transition::Sunrise, "07:35".to_string()));
+
+        let polar_night = days_from_civil(2026, 12, 21) * 86400 + 11 * 3600;
+        assert_eq!(super::next(polar_night, 69.65, 18.96, false), None);
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("+0200"), Some(7200));
+        assert_eq!(parse_offset("-0530"), Some(-19800));
+        assert_eq!(parse_offset("0200"), None);
+        assert_eq!(parse_offset("+2"), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! Thermal state from NSProcessInfo
+//!
+//! macOS rates how hot the machine runs as nominal, fair, serious or critical;
+//! from serious on it slows the CPU down to cool off. `thermalState` reads the
+//! current rating, and NSProcessInfo posts a notification whenever it changes.
+//! The observer only sends on a channel; another thread runs the Rust
+//! callback. Outside macOS there is no rating.
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use sketchybartender::config::ThermalState;
+use sketchybartender::warn;
+
+/// Sending end of the event channel, set by `listen`
+static EVENTS: OnceLock<Mutex<Sender<()>>> = OnceLock::new();
+
+/// The state for an `NSProcessInfoThermalState` value
+fn state(value: isize) -> Option<ThermalState> {
+    match value {
+        0 => Some(ThermalState::Nominal),
+        1 => Some(ThermalState::Fair),
+        2 => Some(ThermalState::Serious),
+        3 => Some(ThermalState::Critical),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use crate::objc::Id;
+
+    #[link(name = "Foundation", kind = "framework")]
+    extern "C" {
+        pub static NSProcessInfoThermalStateDidChangeNotification: Id;
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::c_void;
+
+    use sketchybartender::error;
+
+    use super::{ffi, EVENTS};
+    use crate::objc::{self, send, Id, Sel};
+
+    /// `thermalStateChanged:` of the observer class
+    extern "C" fn thermal_state_changed(_this: Id, _cmd: Sel, _notification: Id) {
+        if let Some(Ok(events)) = EVENTS.get().map(|events| events.lock()) {
+            let _ = events.send(());
+        }
+    }
+
+    pub fn read() -> Option<isize> {
+        objc::autoreleasing(|| unsafe {
+            let process_info = send(objc::class(c"NSProcessInfo"), c"processInfo");
+            if process_info.is_null() {
+                return None;
+            }
+            let thermal_state = objc::msg_send::<unsafe extern "C" fn(Id, Sel) -> isize>();
+            Some(thermal_state(process_info, objc::selector(c"thermalState")))
+        })
+    }
+
+    /// Observe thermal state changes; NSProcessInfo posts them on a thread of its own
+    pub fn register() {
+        let methods: [objc::Method; 1] = [(c"thermalStateChanged:", thermal_state_changed as *const c_void, c"v@:@")];
+        let Some(class) = objc::define_class(c"SketchybartenderThermalObserver", &methods) else {
+            error!("Failed to create the thermal state observer class");
+            return;
+        };
+
+        unsafe {
+            let observer = send(send(class, c"alloc"), c"init");
+            let center = send(objc::class(c"NSNotificationCenter"), c"defaultCenter");
+            objc::msg_send::<unsafe extern "C" fn(Id, Sel, Id, Sel, Id, Id)>()(
+                center,
+                objc::selector(c"addObserver:selector:name:object:"),
+                observer,
+                objc::selector(c"thermalStateChanged:"),
+                ffi::NSProcessInfoThermalStateDidChangeNotification,
+                std::ptr::null_mut(),
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn read() -> Option<isize> {
+        None
+    }
+
+    pub fn register() {}
+}
+
+/// How hot the machine runs, or None without NSProcessInfo
+pub fn current() -> Option<ThermalState> {
+    native::read().and_then(state)
+}
+
+/// Run `callback` on a background thread whenever the thermal state changes
+pub fn listen(callback: impl Fn() + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+    if EVENTS.set(Mutex::new(sender)).is_err() {
+        warn!("Thermal state listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            callback();
+        }
+    });
+
+    native::register();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state() {
+        assert_eq!(state(0), Some(ThermalState::Nominal));
+        assert_eq!(state(2), Some(ThermalState::Serious));
+        assert_eq!(state(7), None);
+    }
+}
@@ -0,0 +1,438 @@
+//! Output volume, output devices and their change notifications from CoreAudio
+//!
+//! CoreAudio calls property listeners on its own notification thread, so no
+//! run loop is needed. Listeners on the system object report default output
+//! device changes; listeners on the device report volume and mute changes and
+//! are moved over when the default device changes. The C callback only sends
+//! on a channel; another thread runs the Rust callback.
+
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use sketchybartender::warn;
+
+use crate::providers::{AudioOutputInfo, VolumeInfo};
+
+/// Quiet period after the last event before the callback runs
+///
+/// Dragging the volume slider fires an event per step; this keeps the item
+/// from being redrawn for each of them while still feeling instant.
+const SETTLE_TIME: Duration = Duration::from_millis(30);
+
+/// Sending end of the event channel, set by `listen`
+static EVENTS: OnceLock<Mutex<Sender<()>>> = OnceLock::new();
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    /// Build a CoreAudio four-character code
+    const fn fourcc(code: &[u8; 4]) -> u32 {
+        u32::from_be_bytes(*code)
+    }
+
+    /// kAudioObjectSystemObject
+    pub const SYSTEM_OBJECT: u32 = 1;
+    /// kAudioObjectUnknown
+    pub const UNKNOWN_OBJECT: u32 = 0;
+    /// kAudioHardwarePropertyDefaultOutputDevice
+    pub const DEFAULT_OUTPUT_DEVICE: u32 = fourcc(b"dOut");
+    /// kAudioHardwarePropertyDevices
+    pub const DEVICES: u32 = fourcc(b"dev#");
+    /// kAudioObjectPropertyName
+    pub const NAME: u32 = fourcc(b"lnam");
+    /// kAudioDevicePropertyStreams
+    pub const STREAMS: u32 = fourcc(b"stm#");
+    /// kCFStringEncodingUTF8
+    pub const UTF8: u32 = 0x0800_0100;
+    /// kAudioHardwareServiceDeviceProperty_VirtualMainVolume
+    pub const VIRTUAL_MAIN_VOLUME: u32 = fourcc(b"vmvc");
+    /// kAudioDevicePropertyMute
+    pub const MUTE: u32 = fourcc(b"mute");
+    /// kAudioObjectPropertyScopeGlobal
+    pub const SCOPE_GLOBAL: u32 = fourcc(b"glob");
+    /// kAudioDevicePropertyScopeOutput
+    pub const SCOPE_OUTPUT: u32 = fourcc(b"outp");
+    /// kAudioObjectPropertyElementMain
+    pub const ELEMENT_MAIN: u32 = 0;
+
+    #[repr(C)]
+    pub struct PropertyAddress {
+        pub selector: u32,
+        pub scope: u32,
+        pub element: u32,
+    }
+
+    pub type PropertyListener = extern "C" fn(
+        object: u32,
+        count: u32,
+        addresses: *const PropertyAddress,
+        client_data: *mut c_void,
+    ) -> i32;
+
+    #[link(name = "CoreAudio", kind = "framework")]
+    extern "C" {
+        pub fn AudioObjectAddPropertyListener(
+            object: u32,
+            address: *const PropertyAddress,
+            listener: PropertyListener,
+            client_data: *mut c_void,
+        ) -> i32;
+
+        pub fn AudioObjectRemovePropertyListener(
+            object: u32,
+            address: *const PropertyAddress,
+            listener: PropertyListener,
+            client_data: *mut c_void,
+        ) -> i32;
+
+        pub fn AudioObjectGetPropertyData(
+            object: u32,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            data_size: *mut u32,
+            data: *mut c_void,
+        ) -> i32;
+
+        pub fn AudioObjectGetPropertyDataSize(
+            object: u32,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            data_size: *mut u32,
+        ) -> i32;
+
+        pub fn AudioObjectSetPropertyData(
+            object: u32,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            data_size: u32,
+            data: *const c_void,
+        ) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFStringGetCString(string: *const c_void, buffer: *mut c_char, size: isize, encoding: u32) -> u8;
+        pub fn CFRelease(object: *const c_void);
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use std::ffi::{c_char, c_void, CStr};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use sketchybartender::error;
+
+    use super::{ffi, EVENTS};
+
+    /// Device the volume and mute listeners are registered on
+    static WATCHED_DEVICE: AtomicU32 = AtomicU32::new(ffi::UNKNOWN_OBJECT);
+
+    const DEFAULT_DEVICE: ffi::PropertyAddress = ffi::PropertyAddress {
+        selector: ffi::DEFAULT_OUTPUT_DEVICE,
+        scope: ffi::SCOPE_GLOBAL,
+        element: ffi::ELEMENT_MAIN,
+    };
+    const VOLUME: ffi::PropertyAddress = ffi::PropertyAddress {
+        selector: ffi::VIRTUAL_MAIN_VOLUME,
+        scope: ffi::SCOPE_OUTPUT,
+        element: ffi::ELEMENT_MAIN,
+    };
+    const MUTE: ffi::PropertyAddress = ffi::PropertyAddress {
+        selector: ffi::MUTE,
+        scope: ffi::SCOPE_OUTPUT,
+        element: ffi::ELEMENT_MAIN,
+    };
+    const DEVICES: ffi::PropertyAddress = ffi::PropertyAddress {
+        selector: ffi::DEVICES,
+        scope: ffi::SCOPE_GLOBAL,
+        element: ffi::ELEMENT_MAIN,
+    };
+    const NAME: ffi::PropertyAddress = ffi::PropertyAddress {
+        selector: ffi::NAME,
+        scope: ffi::SCOPE_GLOBAL,
+        element: ffi::ELEMENT_MAIN,
+    };
+    /// Present on devices that can play sound, which input-only devices lack
+    const OUTPUT_STREAMS: ffi::PropertyAddress = ffi::PropertyAddress {
+        selector: ffi::STREAMS,
+        scope: ffi::SCOPE_OUTPUT,
+        element: ffi::ELEMENT_MAIN,
+    };
+
+    extern "C" fn on_property_change(
+        _object: u32,
+        _count: u32,
+        _addresses: *const ffi::PropertyAddress,
+        _client_data: *mut c_void,
+    ) -> i32 {
+        if let Some(Ok(events)) = EVENTS.get().map(|events| events.lock()) {
+            let _ = events.send(());
+        }
+        0
+    }
+
+    /// Read a fixed-size property, or None if the object doesn't have it
+    fn property<T: Default>(object: u32, address: &ffi::PropertyAddress) -> Option<T> {
+        let mut value = T::default();
+        let mut size = std::mem::size_of::<T>() as u32;
+        let error = unsafe {
+            ffi::AudioObjectGetPropertyData(
+                object,
+                address,
+                0,
+                std::ptr::null(),
+                &mut size,
+                &mut value as *mut T as *mut c_void,
+            )
+        };
+        (error == 0).then_some(value)
+    }
+
+    fn default_device() -> Option<u32> {
+        property::<u32>(ffi::SYSTEM_OBJECT, &DEFAULT_DEVICE).filter(|&device| device != ffi::UNKNOWN_OBJECT)
+    }
+
+    /// Size in bytes of a variable-length property, 0 if the object doesn't have it
+    fn property_size(object: u32, address: &ffi::PropertyAddress) -> u32 {
+        let mut size = 0;
+        let error = unsafe { ffi::AudioObjectGetPropertyDataSize(object, address, 0, std::ptr::null(), &mut size) };
+        if error == 0 {
+            size
+        } else {
+            0
+        }
+    }
+
+    fn device_name(device: u32) -> Option<String> {
+        let mut name: *const c_void = std::ptr::null();
+        let mut size = std::mem::size_of::<*const c_void>() as u32;
+        let error = unsafe {
+            ffi::AudioObjectGetPropertyData(
+                device,
+                &NAME,
+                0,
+                std::ptr::null(),
+                &mut size,
+                &mut name as *mut *const c_void as *mut c_void,
+            )
+        };
+        if error != 0 || name.is_null() {
+            return None;
+        }
+
+        let mut buffer = [0 as c_char; 256];
+        let copied = unsafe { ffi::CFStringGetCString(name, buffer.as_mut_ptr(), buffer.len() as isize, ffi::UTF8) };
+        unsafe { ffi::CFRelease(name) };
+        (copied != 0).then(|| unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().into_owned())
+    }
+
+    /// Every device with output streams, by id and name
+    pub fn output_devices() -> Vec<(u32, String)> {
+        let mut size = property_size(ffi::SYSTEM_OBJECT, &DEVICES);
+        let mut devices = vec![0u32; size as usize / std::mem::size_of::<u32>()];
+        let error = unsafe {
+            ffi::AudioObjectGetPropertyData(
+                ffi::SYSTEM_OBJECT,
+                &DEVICES,
+                0,
+                std::ptr::null(),
+                &mut size,
+                devices.as_mut_ptr() as *mut c_void,
+            )
+        };
+        if error != 0 {
+            return Vec::new();
+        }
+        devices.truncate(size as usize / std::mem::size_of::<u32>());
+
+        devices
+            .into_iter()
+            .filter(|&device| property_size(device, &OUTPUT_STREAMS) > 0)
+            .filter_map(|device| Some((device, device_name(device)?)))
+            .collect()
+    }
+
+    /// The default output device's id
+    pub fn default_output() -> Option<u32> {
+        default_device()
+    }
+
+    /// Make a device the default output, returning whether CoreAudio took it
+    pub fn set_default_output(device: u32) -> bool {
+        let error = unsafe {
+            ffi::AudioObjectSetPropertyData(
+                ffi::SYSTEM_OBJECT,
+                &DEFAULT_DEVICE,
+                0,
+                std::ptr::null(),
+                std::mem::size_of::<u32>() as u32,
+                &device as *const u32 as *const c_void,
+            )
+        };
+        error == 0
+    }
+
+    /// Volume (0.0 to 1.0) and mute state of the default output device
+    pub fn read() -> Option<(f32, bool)> {
+        let device = default_device()?;
+        let volume = property::<f32>(device, &VOLUME)?;
+        // Devices without a mute control are never muted
+        let muted = property::<u32>(device, &MUTE).is_some_and(|mute| mute != 0);
+        Some((volume, muted))
+    }
+
+    /// Move the volume and mute listeners to the current default output device
+    pub fn watch_default_device() {
+        let device = default_device().unwrap_or(ffi::UNKNOWN_OBJECT);
+        let previous = WATCHED_DEVICE.swap(device, Ordering::SeqCst);
+        if device == previous {
+            return;
+        }
+
+        for address in [&VOLUME, &MUTE] {
+            if previous != ffi::UNKNOWN_OBJECT {
+                unsafe {
+                    ffi::AudioObjectRemovePropertyListener(previous, address, on_property_change, std::ptr::null_mut());
+                }
+            }
+            if device != ffi::UNKNOWN_OBJECT {
+                // Devices without a volume or mute control (e.g. HDMI) refuse the listener
+                unsafe {
+                    ffi::AudioObjectAddPropertyListener(device, address, on_property_change, std::ptr::null_mut());
+                }
+            }
+        }
+    }
+
+    /// Register for default output device changes
+    pub fn register() {
+        let error = unsafe {
+            ffi::AudioObjectAddPropertyListener(
+                ffi::SYSTEM_OBJECT,
+                &DEFAULT_DEVICE,
+                on_property_change,
+                std::ptr::null_mut(),
+            )
+        };
+        if error != 0 {
+            error!("Failed to register default output device listener: {}", error);
+        }
+        watch_default_device();
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    pub fn read() -> Option<(f32, bool)> {
+        None
+    }
+
+    pub fn output_devices() -> Vec<(u32, String)> {
+        Vec::new()
+    }
+
+    pub fn default_output() -> Option<u32> {
+        None
+    }
+
+    pub fn set_default_output(_device: u32) -> bool {
+        false
+    }
+
+    pub fn watch_default_device() {}
+
+    pub fn register() {}
+}
+
+/// Volume of the default output device, or None if it has no volume control
+pub fn current() -> Option<VolumeInfo> {
+    native::read().map(|(volume, muted)| VolumeInfo {
+        percentage: (volume.clamp(0.0, 1.0) * 100.0).round() as u8,
+        muted,
+    })
+}
+
+/// Run an AppleScript `set volume` command, describing a failure as failing to `action`
+fn set_volume(script: &str, action: &str) -> Result<(), String> {
+    let output = Command::new("osascript")
+        .args(["-e", script])
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to {}: {}", action, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// Set the output volume, which the listener then redraws the item for
+pub fn set(percentage: u8) -> Result<(), String> {
+    set_volume(&format!("set volume output volume {}", percentage.min(100)), "set the volume")
+}
+
+/// Mute or unmute the output, which the listener then redraws the item for
+pub fn set_muted(muted: bool) -> Result<(), String> {
+    set_volume(&format!("set volume output muted {}", muted), if muted { "mute" } else { "unmute" })
+}
+
+/// Names of the output devices and the current default, or None without CoreAudio
+pub fn outputs() -> Option<AudioOutputInfo> {
+    let devices = native::output_devices();
+    if devices.is_empty() {
+        return None;
+    }
+    let current = native::default_output();
+    Some(AudioOutputInfo {
+        current: devices.iter().find(|(id, _)| Some(*id) == current).map(|(_, name)| name.clone()),
+        devices: devices.into_iter().map(|(_, name)| name).collect(),
+    })
+}
+
+/// Switch the default output to the device with this name, through CoreAudio or else `SwitchAudioSource`
+pub fn set_output(name: &str) -> Result<(), String> {
+    let device = native::output_devices().into_iter().find(|(_, device)| device == name);
+    if let Some((id, _)) = device {
+        if native::set_default_output(id) {
+            return Ok(());
+        }
+    }
+
+    let output = Command::new("SwitchAudioSource")
+        .args(["-t", "output", "-s", name])
+        .output()
+        .map_err(|e| format!("Failed to run SwitchAudioSource: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to switch the output to {}: {}", name, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// Run `callback` on a background thread when the output volume, mute state or device changes
+pub fn listen(callback: impl Fn() + Send + 'static) {
+    let (sender, receiver) = mpsc::channel();
+    if EVENTS.set(Mutex::new(sender)).is_err() {
+        warn!("Volume listener already running");
+        return;
+    }
+
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            while receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+            // A new default device needs its own volume and mute listeners
+            native::watch_default_device();
+            callback();
+        }
+    });
+
+    native::register();
+}
@@ -0,0 +1,37 @@
+//! Polling file watcher without pulling in a platform notification crate
+//!
+//! Files are compared by modification time and size, so creating, deleting,
+//! editing or atomically replacing a watched file all count as a change.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often watched files are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What identifies a version of a file (None if it doesn't exist)
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Run `callback` on a background thread whenever one of `paths` changes
+pub fn watch(paths: Vec<PathBuf>, callback: impl Fn() + Send + 'static) {
+    thread::spawn(move || {
+        let mut stamps: Vec<Stamp> = paths.iter().map(|p| stamp(p)).collect();
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let current: Vec<Stamp> = paths.iter().map(|p| stamp(p)).collect();
+            if current != stamps {
+                stamps = current;
+                callback();
+            }
+        }
+    });
+}
@@ -0,0 +1,423 @@
+//! Current weather and a short forecast from wttr.in or OpenWeatherMap
+//!
+//! Both services answer in JSON. The last report is cached, and while
+//! fetching fails it's shown until it is `max_age` old.
+
+use serde_json::{json, Value};
+use sketchybartender::config::{parse_coordinates, WeatherConfig, WeatherSource, WeatherUnits};
+use sketchybartender::warn;
+
+use crate::calendar;
+use crate::http::{self, encode};
+
+/// Days in the forecast, which is as many as wttr.in gives
+const FORECAST_DAYS: usize = 3;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// The weather, as coarse as the icons tell it apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Clear,
+    PartlyCloudy,
+    Cloudy,
+    Fog,
+    Rain,
+    /// Snow, sleet and freezing rain
+    Snow,
+    Thunder,
+}
+
+const CONDITIONS: [(Condition, &str); 7] = [
+    (Condition::Clear, "clear"),
+    (Condition::PartlyCloudy, "partly_cloudy"),
+    (Condition::Cloudy, "cloudy"),
+    (Condition::Fog, "fog"),
+    (Condition::Rain, "rain"),
+    (Condition::Snow, "snow"),
+    (Condition::Thunder, "thunder"),
+];
+
+impl Condition {
+    /// From a WorldWeatherOnline code, which wttr.in passes on
+    fn from_wwo(code: u64) -> Self {
+        match code {
+            113 => Condition::Clear,
+            116 => Condition::PartlyCloudy,
+            119 | 122 => Condition::Cloudy,
+            143 | 248 | 260 => Condition::Fog,
+            200 | 386 | 389 | 392 | 395 => Condition::Thunder,
+            179 | 182 | 185 | 227 | 230 | 281 | 284 | 311 | 314 | 317 | 320 | 323..=338 | 350 | 362..=377 => {
+                Condition::Snow
+            }
+            176 | 263 | 266 | 293..=308 | 353..=359 => Condition::Rain,
+            _ => Condition::Cloudy,
+        }
+    }
+
+    /// From an OpenWeatherMap condition ID
+    fn from_owm(id: u64) -> Self {
+        match id {
+            200..=299 => Condition::Thunder,
+            511 | 600..=699 => Condition::Snow,
+            300..=599 => Condition::Rain,
+            700..=799 => Condition::Fog,
+            800 => Condition::Clear,
+            801 | 802 => Condition::PartlyCloudy,
+            _ => Condition::Cloudy,
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Condition::Clear => "\u{f0599}",        // nf-md-weather_sunny
+            Condition::PartlyCloudy => "\u{f0595}", // nf-md-weather_partly_cloudy
+            Condition::Cloudy => "\u{f0590}",       // nf-md-weather_cloudy
+            Condition::Fog => "\u{f0591}",          // nf-md-weather_fog
+            Condition::Rain => "\u{f0597}",         // nf-md-weather_rainy
+            Condition::Snow => "\u{f0598}",         // nf-md-weather_snowy
+            Condition::Thunder => "\u{f0593}",      // nf-md-weather_lightning
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        CONDITIONS.iter().find(|(condition, _)| *condition == self).map_or("cloudy", |(_, name)| name)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        CONDITIONS.iter().find(|(_, n)| *n == name).map(|(condition, _)| *condition)
+    }
+}
+
+/// One day of the forecast
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Day {
+    /// `Today`, `Tomorrow` or the day of the week, e.g. `Sat`
+    pub name: String,
+    pub low: i32,
+    pub high: i32,
+    /// Around midday
+    pub condition: Condition,
+}
+
+/// The weather at one place, temperatures in the configured units
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Where the service placed the location, empty if it didn't say
+    pub place: String,
+    pub temperature: i32,
+    pub feels_like: i32,
+    pub condition: Condition,
+    /// The service's words for it, e.g. `Partly cloudy`
+    pub description: String,
+    pub forecast: Vec<Day>,
+}
+
+impl Report {
+    fn to_json(&self) -> Value {
+        let forecast: Vec<Value> = self
+            .forecast
+            .iter()
+            .map(|day| json!({"name": day.name, "low": day.low, "high": day.high, "condition": day.condition.name()}))
+            .collect();
+        json!({
+            "place": self.place,
+            "temperature": self.temperature,
+            "feels_like": self.feels_like,
+            "condition": self.condition.name(),
+            "description": self.description,
+            "forecast": forecast,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let number = |value: &Value| value.as_i64().and_then(|n| i32::try_from(n).ok());
+        let forecast = value["forecast"]
+            .as_array()?
+            .iter()
+            .map(|day| {
+                Some(Day {
+                    name: day["name"].as_str()?.to_string(),
+                    low: number(&day["low"])?,
+                    high: number(&day["high"])?,
+                    condition: Condition::from_name(day["condition"].as_str()?)?,
+                })
+            })
+            .collect::<Option<Vec<Day>>>()?;
+        Some(Report {
+            place: value["place"].as_str()?.to_string(),
+            temperature: number(&value["temperature"])?,
+            feels_like: number(&value["feels_like"])?,
+            condition: Condition::from_name(value["condition"].as_str()?)?,
+            description: value["description"].as_str()?.to_string(),
+            forecast,
+        })
+    }
+}
+
+/// A report and when it was fetched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Weather {
+    pub report: Report,
+    /// Local time of the fetch as `YYYY-MM-DD HH:MM`
+    pub updated: String,
+    /// Fetching failed just now, so this is the last report that came through
+    pub stale: bool,
+}
+
+/// `Today`, `Tomorrow` or the day of the week of a day counted from 1970-01-01
+fn day_name(day: i64, today: i64) -> String {
+    match day - today {
+        0 => "Today".to_string(),
+        1 => "Tomorrow".to_string(),
+        // 1970-01-01 was a Thursday
+        _ => WEEKDAYS[(day + 4).rem_euclid(7) as usize].to_string(),
+    }
+}
+
+fn wttr_url(location: &str) -> String {
+    format!("https://wttr.in/{}?format=j1", encode(location))
+}
+
+/// An OpenWeatherMap endpoint's URL for a place name, or for coordinates like `52.52,13.40`
+fn owm_url(endpoint: &str, location: &str, units: WeatherUnits, api_key: &str) -> String {
+    let place = match parse_coordinates(location) {
+        Some((lat, lon)) => format!("lat={}&lon={}", lat, lon),
+        None => format!("q={}", encode(location)),
+    };
+    format!(
+        "https://api.openweathermap.org/data/2.5/{}?{}&units={}&appid={}",
+        endpoint,
+        place,
+        units,
+        encode(api_key)
+    )
+}
+
+/// A wttr.in number, which it gives as a string, rounded
+fn wttr_number(value: &Value) -> Option<i32> {
+    value.as_str()?.trim().parse::<f64>().ok().map(|n| n.round() as i32)
+}
+
+/// wttr.in's `format=j1` reply
+fn parse_wttr(reply: &Value, units: WeatherUnits) -> Option<Report> {
+    let (temperature, feels_like, high, low) = match units {
+        WeatherUnits::Metric => ("temp_C", "FeelsLikeC", "maxtempC", "mintempC"),
+        WeatherUnits::Imperial => ("temp_F", "FeelsLikeF", "maxtempF", "mintempF"),
+    };
+    let code = |value: &Value| value["weatherCode"].as_str()?.parse::<u64>().ok().map(Condition::from_wwo);
+    let current = &reply["current_condition"][0];
+
+    let days = reply["weather"].as_array()?;
+    let date = |day: &Value| {
+        let mut parts = day["date"].as_str()?.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+        Some(calendar::days_from_civil(parts.next()??, parts.next()??, parts.next()??))
+    };
+    // wttr.in's first day is today where the location is
+    let today = date(days.first()?)?;
+    let forecast = days
+        .iter()
+        .take(FORECAST_DAYS)
+        .map(|day| {
+            let hourly = day["hourly"].as_array()?;
+            let midday = hourly.iter().find(|hour| hour["time"] == "1200").or(hourly.get(hourly.len() / 2))?;
+            Some(Day {
+                name: day_name(date(day)?, today),
+                low: wttr_number(&day[low])?,
+                high: wttr_number(&day[high])?,
+                condition: code(midday)?,
+            })
+        })
+        .collect::<Option<Vec<Day>>>()?;
+
+    Some(Report {
+        place: reply["nearest_area"][0]["areaName"][0]["value"].as_str().unwrap_or_default().to_string(),
+        temperature: wttr_number(&current[temperature])?,
+        feels_like: wttr_number(&current[feels_like])?,
+        condition: code(current)?,
+        description: current["weatherDesc"][0]["value"].as_str().unwrap_or_default().trim().to_string(),
+        forecast,
+    })
+}
+
+/// OpenWeatherMap's `weather` and `forecast` replies; the forecast comes in 3-hour steps
+fn parse_owm(current: &Value, forecast: &Value) -> Option<Report> {
+    let number = |value: &Value| value.as_f64().map(|n| n.round() as i32);
+    let condition = |value: &Value| value["weather"][0]["id"].as_u64().map(Condition::from_owm);
+    let local_day = |dt: &Value, offset: &Value| Some((dt.as_i64()? + offset.as_i64()?).div_euclid(86400));
+    let today = local_day(&current["dt"], &current["timezone"])?;
+
+    // Per local day: low, high, and the condition of the step nearest midday
+    let mut days: Vec<(i64, i32, i32, Condition, i64)> = Vec::new();
+    for step in forecast["list"].as_array()? {
+        let local = step["dt"].as_i64()? + forecast["city"]["timezone"].as_i64()?;
+        let day = local.div_euclid(86400);
+        let from_midday = (local.rem_euclid(86400) / 3600 - 12).abs();
+        let (low, high) = (number(&step["main"]["temp_min"])?, number(&step["main"]["temp_max"])?);
+        if let Some(last) = days.last_mut().filter(|last| last.0 == day) {
+            last.1 = last.1.min(low);
+            last.2 = last.2.max(high);
+            if from_midday < last.4 {
+                (last.3, last.4) = (condition(step)?, from_midday);
+            }
+        } else if days.len() == FORECAST_DAYS {
+            break;
+        } else {
+            days.push((day, low, high, condition(step)?, from_midday));
+        }
+    }
+
+    let mut description = current["weather"][0]["description"].as_str().unwrap_or_default().trim().to_string();
+    if let Some(first) = description.get(..1) {
+        description.replace_range(..1, &first.to_uppercase());
+    }
+    Some(Report {
+        place: current["name"].as_str().unwrap_or_default().to_string(),
+        temperature: number(&current["main"]["temp"])?,
+        feels_like: number(&current["main"]["feels_like"])?,
+        condition: condition(current)?,
+        description,
+        forecast: days
+            .into_iter()
+            .map(|(day, low, high, condition, _)| Day { name: day_name(day, today), low, high, condition })
+            .collect(),
+    })
+}
+
+/// Fetch the weather from the configured service
+fn fetch(config: &WeatherConfig) -> Result<Report, String> {
+    match config.source {
+        WeatherSource::Wttr => {
+            let reply = http::get("wttr.in", &wttr_url(&config.location))?;
+            parse_wttr(&reply, config.units).ok_or_else(|| "Unexpected weather from wttr.in".to_string())
+        }
+        WeatherSource::OpenWeatherMap => {
+            let api_key = config.api_key.as_deref().unwrap_or_default();
+            let url = |endpoint| owm_url(endpoint, &config.location, config.units, api_key);
+            let current = http::get("OpenWeatherMap", &url("weather"))?;
+            let forecast = http::get("OpenWeatherMap", &url("forecast"))?;
+            parse_owm(&current, &forecast).ok_or_else(|| "Unexpected weather from OpenWeatherMap".to_string())
+        }
+    }
+}
+
+/// What the cached report was fetched for, so changing any of it fetches again
+fn cache_key(config: &WeatherConfig) -> String {
+    format!("{} {} {}", config.source, config.units, config.location)
+}
+
+impl http::Cached for Weather {
+    fn from_cache(cache: &Value) -> Option<Self> {
+        Some(Weather {
+            report: Report::from_json(&cache["report"])?,
+            updated: cache["updated"].as_str()?.to_string(),
+            stale: false,
+        })
+    }
+
+    fn to_cache(&self) -> Value {
+        json!({"updated": self.updated, "report": self.report.to_json()})
+    }
+}
+
+/// The weather, from the cache when it was fetched less than `interval` seconds ago, and from the
+/// last report that came through while fetching fails
+pub fn current(config: &WeatherConfig, interval: u64) -> Result<Weather, String> {
+    config.check()?;
+    let fetched = http::cached("weather", &cache_key(config), interval, |age| config.usable(age), || {
+        let report = fetch(config)?;
+        Ok(Weather { report, updated: calendar::now().unwrap_or_default(), stale: false })
+    })?;
+    match fetched {
+        http::Fetched::Fresh(weather) => Ok(weather),
+        http::Fetched::Stale(weather, e) => {
+            warn!("[WEATHER] {}; showing the weather from {}", e, weather.updated);
+            Ok(Weather { stale: true, ..weather })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WTTR: &str = r#"{
+        "current_condition": [{"temp_C": "12", "temp_F": "54", "FeelsLikeC": "10", "FeelsLikeF": "50",
+            "weatherCode": "116", "weatherDesc": [{"value": "Partly cloudy "}]}],
+        "nearest_area": [{"areaName": [{"value": "Berlin"}]}],
+        "weather": [
+            {"date": "2026-10-16", "maxtempC": "15", "mintempC": "8", "maxtempF": "59", "mintempF": "46",
+             "hourly": [{"time": "0", "weatherCode": "113"}, {"time": "1200", "weatherCode": "296"}]},
+            {"date": "2026-10-17", "maxtempC": "13", "mintempC": "6", "maxtempF": "55", "mintempF": "43",
+             "hourly": [{"time": "1200", "weatherCode": "338"}]},
+            {"date": "2026-10-18", "maxtempC": "11", "mintempC": "4", "maxtempF": "52", "mintempF": "39",
+             "hourly": [{"time": "1200", "weatherCode": "389"}]}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_wttr() {
+        let report = parse_wttr(&serde_json::from_str(WTTR).unwrap(), WeatherUnits::Metric).unwrap();
+        assert_eq!((report.place.as_str(), report.temperature, report.feels_like), ("Berlin", 12, 10));
+        assert_eq!((report.condition, report.description.as_str()), (Condition::PartlyCloudy, "Partly cloudy"));
+        let days: Vec<(&str, i32, i32, Condition)> =
+            report.forecast.iter().map(|day| (day.name.as_str(), day.low, day.high, day.condition)).collect();
+        assert_eq!(days, [
+            ("Today", 8, 15, Condition::Rain),
+            ("Tomorrow", 6, 13, Condition::Snow),
+            ("Sun", 4, 11, Condition::Thunder),
+        ]);
+
+        let imperial = parse_wttr(&serde_json::from_str(WTTR).unwrap(), WeatherUnits::Imperial).unwrap();
+        assert_eq!((imperial.temperature, imperial.forecast[0].high), (54, 59));
+        assert_eq!(parse_wttr(&json!({"current_condition": []}), WeatherUnits::Metric), None);
+    }
+
+    #[test]
+    fn test_parse_owm() {
+        // 2026-10-16 12:00 in Berlin, at UTC+2
+        let current = json!({
+            "dt": 1_792_144_800, "timezone": 7200, "name": "Berlin",
+            "main": {"temp": 12.4, "feels_like": 9.6}, "weather": [{"id": 803, "description": "broken clouds"}],
+        });
+        let step = |dt: i64, low: f64, high: f64, id: u64| {
+            json!({"dt": dt, "main": {"temp_min": low, "temp_max": high}, "weather": [{"id": id}]})
+        };
+        let forecast = json!({"city": {"timezone": 7200}, "list": [
+            step(1_792_144_800, 11.0, 12.0, 500),
+            step(1_792_155_600, 13.2, 14.6, 800),
+            step(1_792_188_000, 7.9, 8.0, 800),
+            step(1_792_231_200, 9.0, 10.0, 601),
+            step(1_792_317_600, 5.0, 7.0, 211),
+            step(1_792_404_000, 1.0, 3.0, 741),
+        ]});
+        let report = parse_owm(&current, &forecast).unwrap();
+        assert_eq!((report.temperature, report.feels_like, report.condition), (12, 10, Condition::Cloudy));
+        assert_eq!(report.description, "Broken clouds");
+        let days: Vec<(&str, i32, i32, Condition)> =
+            report.forecast.iter().map(|day| (day.name.as_str(), day.low, day.high, day.condition)).collect();
+        assert_eq!(days, [
+            ("Today", 11, 15, Condition::Rain),
+            ("Tomorrow", 8, 10, Condition::Snow),
+            ("Sun", 5, 7, Condition::Thunder),
+        ]);
+    }
+
+    #[test]
+    fn test_report_json() {
+        let report = parse_wttr(&serde_json::from_str(WTTR).unwrap(), WeatherUnits::Metric).unwrap();
+        assert_eq!(Report::from_json(&report.to_json()), Some(report));
+        assert_eq!(Report::from_json(&json!({"place": "Berlin"})), None);
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(wttr_url(""), "https://wttr.in/?format=j1");
+        assert_eq!(wttr_url("São Paulo"), "https://wttr.in/S%C3%A3o%20Paulo?format=j1");
+        assert_eq!(
+            owm_url("weather", "52.52, 13.40", WeatherUnits::Metric, "k"),
+            "https://api.openweathermap.org/data/2.5/weather?lat=52.52&lon=13.4&units=metric&appid=k"
+        );
+        assert!(owm_url("forecast", "London,GB", WeatherUnits::Imperial, "k").contains("?q=London,GB&units=imperial"));
+    }
+}
@@ -0,0 +1,61 @@
+//! Wi-Fi network and signal strength from CoreWLAN
+//!
+//! CoreWLAN answers for the default Wi-Fi interface without a subprocess. Its
+//! SSID is nil unless the daemon may use Location Services, but the RSSI still
+//! tells whether the interface is associated. Macs without Wi-Fi hardware have
+//! no interface, and callers fall back to `ipconfig getsummary`.
+
+use crate::providers::WifiInfo;
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    // Loads the framework, so its classes are known to the Objective-C runtime
+    #[link(name = "CoreWLAN", kind = "framework")]
+    extern "C" {}
+}
+
+#[cfg(target_os = "macos")]
+mod native {
+    use crate::objc::{self, send, Id, Sel};
+    use crate::providers::WifiInfo;
+
+    pub fn read() -> Option<WifiInfo> {
+        objc::autoreleasing(|| unsafe {
+            let class = objc::class(c"CWWiFiClient");
+            if class.is_null() {
+                return None;
+            }
+            let interface = send(send(class, c"sharedWiFiClient"), c"interface");
+            if interface.is_null() {
+                return None;
+            }
+
+            let power_on = objc::msg_send::<unsafe extern "C" fn(Id, Sel) -> bool>();
+            let rssi_value = objc::msg_send::<unsafe extern "C" fn(Id, Sel) -> isize>();
+            // 0 while the interface isn't associated with a network
+            let rssi = match rssi_value(interface, objc::selector(c"rssiValue")) {
+                0 => None,
+                rssi => Some(rssi as i32),
+            };
+            Some(WifiInfo {
+                ssid: objc::string(send(interface, c"ssid")),
+                rssi,
+                connected: power_on(interface, objc::selector(c"powerOn")) && rssi.is_some(),
+            })
+        })
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod native {
+    use crate::providers::WifiInfo;
+
+    pub fn read() -> Option<WifiInfo> {
+        None
+    }
+}
+
+/// The Wi-Fi interface's network and signal, or None without CoreWLAN or Wi-Fi hardware
+pub fn current() -> Option<WifiInfo> {
+    native::read()
+}
@@ -0,0 +1,97 @@
+//! Zoom's meeting and microphone state from its menu bar
+//!
+//! Zoom has no scripting dictionary, but while a meeting runs its menu bar has
+//! a Meeting menu with a "Mute audio" item while the microphone is live and
+//! "Unmute audio" while it isn't. System Events reads that menu and clicks the
+//! item to toggle, which needs the Accessibility permission. The titles are
+//! Zoom's English ones.
+
+use std::process::Command;
+
+const STATE_SCRIPT: &str = r#"
+tell application "System Events" to tell process "zoom.us"
+    if not (exists menu bar item "Meeting" of menu bar 1) then return "none"
+    if exists menu item "Mute audio" of menu 1 of menu bar item "Meeting" of menu bar 1 then return "live"
+    return "muted"
+end tell
+"#;
+
+const TOGGLE_SCRIPT: &str = r#"
+tell application "System Events" to tell process "zoom.us"
+    tell menu 1 of menu bar item "Meeting" of menu bar 1
+        if exists menu item "Mute audio" then
+            click menu item "Mute audio"
+        else
+            click menu item "Unmute audio"
+        end if
+    end tell
+end tell
+"#;
+
+/// Zoom's meeting, if it's in one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Meeting {
+    /// Zoom isn't running or isn't in a meeting
+    #[default]
+    None,
+    /// In a meeting with the microphone off, or without joining audio
+    Muted,
+    /// In a meeting with the microphone live
+    Live,
+}
+
+/// The state `STATE_SCRIPT` printed
+fn parse_state(stdout: &str) -> Meeting {
+    match stdout.trim() {
+        "live" => Meeting::Live,
+        "muted" => Meeting::Muted,
+        _ => Meeting::None,
+    }
+}
+
+/// Run an AppleScript against Zoom's menu bar, returning what it printed
+fn run_script(script: &str) -> Result<String, String> {
+    let output = Command::new("osascript")
+        .args(["-e", script])
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read Zoom's menu bar: {}; give sketchybartender Accessibility access",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether Zoom is in a meeting and its microphone is live
+pub fn meeting() -> Result<Meeting, String> {
+    // Checked first so System Events isn't asked about a process that isn't there
+    let running = Command::new("pgrep")
+        .args(["-x", "zoom.us"])
+        .output()
+        .map(|o| o.status.success())
+        .map_err(|e| format!("Failed to run pgrep: {}", e))?;
+    if !running {
+        return Ok(Meeting::None);
+    }
+    run_script(STATE_SCRIPT).map(|stdout| parse_state(&stdout))
+}
+
+/// Mute or unmute the microphone in the running meeting
+pub fn toggle_mute() -> Result<(), String> {
+    run_script(TOGGLE_SCRIPT).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state() {
+        assert_eq!(parse_state("live\n"), Meeting::Live);
+        assert_eq!(parse_state("muted\n"), Meeting::Muted);
+        assert_eq!(parse_state("none\n"), Meeting::None);
+        assert_eq!(parse_state(""), Meeting::None);
+    }
+}
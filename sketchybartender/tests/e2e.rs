@@ -0,0 +1,505 @@
+//! End-to-end tests: the daemon against stub `sketchybar`, `aerospace` and system tools
+//!
+//! Every external command the daemon runs is replaced by a shell script on
+//! PATH that records its arguments and replays a canned reply, kept in a file
+//! so a test can change what aerospace reports while the daemon runs. The tests send
+//! messages over the Unix socket with `sync`, so the handler has finished
+//! when the reply arrives, and compare the recorded `sketchybar` and
+//! `aerospace` calls with the snapshots in `tests/snapshots`. Run with
+//! `UPDATE_SNAPSHOTS=1` to rewrite the snapshots after an intended change.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the command log has to stay unchanged before the daemon counts as idle
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Give up on the daemon after this long
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tools that get a stub, so nothing on the machine running the tests is touched or read
+const TOOLS: &[&str] = &[
+    "sketchybar", "aerospace", "swift", "pmset", "osascript", "brew", "top", "netstat", "pgrep", "ps", "date",
+    "ioreg", "defaults", "mdfind", "security", "sips", "brightness", "icalBuddy", "curl",
+];
+
+/// Canned replies of a stub: the first entry whose prefix matches the arguments wins
+struct Stub {
+    name: &'static str,
+    replies: Vec<(&'static str, String)>,
+    /// Exit status when no reply matches
+    status: i32,
+}
+
+impl Stub {
+    fn new(name: &'static str) -> Self {
+        Self { name, replies: Vec::new(), status: 0 }
+    }
+
+    fn reply(mut self, args: &'static str, output: impl Into<String>) -> Self {
+        self.replies.push((args, output.into()));
+        self
+    }
+
+    fn failing(mut self) -> Self {
+        self.status = 1;
+        self
+    }
+
+    /// File holding the reply to calls starting with `args`
+    fn reply_path(&self, replies: &Path, args: &str) -> Option<PathBuf> {
+        let index = self.replies.iter().position(|(prefix, _)| *prefix == args)?;
+        Some(replies.join(format!("{}.{}", self.name, index)))
+    }
+
+    /// Write the stub script and its replies: log the call, then print the matching reply
+    fn install(&self, bin: &Path, replies: &Path, log: &Path) {
+        let mut script = format!(
+            "#!/bin/sh\nprintf '%s\\n' \"{} $*\" >> '{}'\ncase \"$*\" in\n",
+            self.name,
+            log.display()
+        );
+        for (args, output) in &self.replies {
+            let path = self.reply_path(replies, args).unwrap();
+            fs::write(&path, format!("{}\n", output)).unwrap();
+            script.push_str(&format!("  '{}'*) cat '{}'; exit 0 ;;\n", args, path.display()));
+        }
+        script.push_str(&format!("  *) exit {} ;;\nesac\n", self.status));
+
+        let path = bin.join(self.name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+/// A single-display laptop with two aerospace workspaces
+fn laptop() -> Vec<Stub> {
+    vec![
+        Stub::new("swift").reply("-e", "1|1|BUILTIN|1512x982|1|Built-in Retina Display"),
+        Stub::new("sketchybar")
+            .reply("--query displays", r#"[{"arrangement-id":1,"DirectDisplayID":1,"UUID":"BUILTIN"}]"#),
+        Stub::new("aerospace")
+            .reply("list-monitors", "1|1|Built-in Retina Display")
+            .reply("list-workspaces --focused", "1")
+            .reply("list-workspaces --all", "1|1\n2|1\n3|1")
+            .reply("list-windows --all", "1|101|501|com.apple.Safari|Safari\n2|102|502|com.apple.Terminal|Terminal")
+            .reply("list-windows --focused", "501|com.apple.Safari|Safari"),
+        Stub::new("pmset").reply(
+            "-g batt",
+            concat!(
+                "Now drawing from 'Battery Power'\n",
+                " -InternalBattery-0 (id=1)\t64%; discharging; 3:12 remaining present: true",
+            ),
+        ),
+        Stub::new("ioreg").reply(
+            "-rn AppleSmartBattery",
+            "  \"AppleRawMaxCapacity\" = 4102\n  \"CycleCount\" = 312\n  \"DesignCapacity\" = 4563",
+        ),
+        Stub::new("date").reply("+", "16/10 09:30"),
+        // Neither Teams nor screen sharing is running
+        Stub::new("pgrep").failing(),
+    ]
+}
+
+/// The daemon sets up the bar, as with the shipped config, and long intervals keep the timers out of the way;
+/// the tests trigger refreshes themselves. The weather gets a location, without which it isn't fetched
+fn config(stubs: &Path) -> String {
+    format!(
+        "bootstrap_items = true\n\n\
+         [items.clock]\ninterval = 3600\nalign = false\n\n[items.battery]\ninterval = 3600\n\n\
+         [items.brew]\ninterval = 3600\npath = \"{}\"\n\n[items.teams]\ninterval = 3600\n\n\
+         [items.weather]\nlocation = \"Berlin\"\n",
+        stubs.join("brew").display()
+    )
+}
+
+/// A daemon running in its own directory, with stubs for every tool it calls
+struct Daemon {
+    root: PathBuf,
+    log: PathBuf,
+    stubs: Vec<Stub>,
+    child: Child,
+}
+
+impl Daemon {
+    fn start(stubs: Vec<Stub>) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "sketchybartender-e2e-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        let (bin, replies) = (root.join("bin"), root.join("replies"));
+        fs::create_dir_all(&bin).unwrap();
+        fs::create_dir_all(&replies).unwrap();
+        fs::create_dir_all(root.join("config/sketchybar")).unwrap();
+        let log = root.join("calls.log");
+        fs::write(&log, "").unwrap();
+
+        for tool in TOOLS {
+            match stubs.iter().find(|stub| stub.name == *tool) {
+                Some(stub) => stub.install(&bin, &replies, &log),
+                None => Stub::new(tool).install(&bin, &replies, &log),
+            }
+        }
+        fs::write(root.join("config/sketchybar/sketchybartender.toml"), config(&bin)).unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_sketchybartender"))
+            .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+            .env("HOME", &root)
+            .env("XDG_CONFIG_HOME", root.join("config"))
+            .env("XDG_CACHE_HOME", root.join("cache"))
+            .stdout(Stdio::null())
+            .stderr(fs::File::create(root.join("daemon.log")).unwrap())
+            .spawn()
+            .unwrap();
+
+        let daemon = Self { root, log, stubs, child };
+        let socket = daemon.socket();
+        let started = Instant::now();
+        while UnixStream::connect(&socket).is_err() {
+            assert!(started.elapsed() < TIMEOUT, "daemon didn't start:\n{}", daemon.stderr());
+            thread::sleep(Duration::from_millis(20));
+        }
+        daemon.settle();
+        daemon
+    }
+
+    /// Change the reply to calls of `tool` starting with `args`, which must have one already
+    fn set_reply(&self, tool: &str, args: &str, output: &str) {
+        let stub = self.stubs.iter().find(|stub| stub.name == tool).unwrap();
+        let path = stub.reply_path(&self.root.join("replies"), args).unwrap();
+        fs::write(path, format!("{}\n", output)).unwrap();
+    }
+
+    fn socket(&self) -> PathBuf {
+        self.root.join("cache/sketchybar/helper.sock")
+    }
+
+    fn stderr(&self) -> String {
+        fs::read_to_string(self.root.join("daemon.log")).unwrap_or_default()
+    }
+
+    /// Wait until no command has been run for a while, e.g. after the startup refresh
+    fn settle(&self) {
+        let started = Instant::now();
+        let mut last = fs::read_to_string(&self.log).unwrap_or_default();
+        let mut unchanged = Instant::now();
+        while unchanged.elapsed() < SETTLE_TIME {
+            assert!(started.elapsed() < TIMEOUT, "daemon never went idle:\n{}", self.stderr());
+            thread::sleep(Duration::from_millis(50));
+            let current = fs::read_to_string(&self.log).unwrap_or_default();
+            if current != last {
+                last = current;
+                unchanged = Instant::now();
+            }
+        }
+    }
+
+    /// Send a message, wait for its handler and anything it started, and return the reply
+    /// with the `sketchybar` and `aerospace` calls made meanwhile
+    fn send(&self, message: &str) -> (String, String) {
+        fs::write(&self.log, "").unwrap();
+
+        let mut stream = UnixStream::connect(self.socket()).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        writeln!(stream, "sync {}", message).unwrap();
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply).unwrap();
+        self.settle();
+
+        let calls = fs::read_to_string(&self.log).unwrap();
+        let calls: String = calls
+            .lines()
+            .filter(|line| line.starts_with("sketchybar ") || line.starts_with("aerospace "))
+            .map(|line| format!("{}\n", line.replace(&self.root.display().to_string(), "$ROOT")))
+            .collect();
+        (reply.trim().to_string(), calls)
+    }
+
+    /// Run sketchycli against this daemon, returning whether it succeeded and what it printed
+    fn sketchycli(&self, args: &[&str]) -> (bool, String, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_sketchycli"))
+            .args(args)
+            .env("HOME", &self.root)
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("XDG_CACHE_HOME", self.root.join("cache"))
+            .output()
+            .unwrap();
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).trim().to_string();
+        (output.status.success(), text(&output.stdout), text(&output.stderr))
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Compare with `tests/snapshots/<name>.txt`, or rewrite it with `UPDATE_SNAPSHOTS=1`
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("no snapshot {}; run with UPDATE_SNAPSHOTS=1 to create it", path.display()));
+    assert_eq!(actual, expected, "snapshot {} differs; run with UPDATE_SNAPSHOTS=1 if that's intended", name);
+}
+
+#[test]
+fn test_workspace_refresh() {
+    let daemon = Daemon::start(laptop());
+    let (reply, calls) = daemon.send("on-workspace-changed");
+    assert_eq!(reply, "ok");
+    assert_snapshot("workspace_refresh", &calls);
+}
+
+#[test]
+fn test_workspace_emptied() {
+    let daemon = Daemon::start(laptop());
+
+    // Terminal quits, and workspace 3 goes away
+    daemon.set_reply("aerospace", "list-windows --all", "1|101|501|com.apple.Safari|Safari");
+    daemon.set_reply("aerospace", "list-workspaces --all", "1|1\n2|1");
+    let (reply, calls) = daemon.send("on-workspace-changed");
+    assert_eq!(reply, "ok");
+    assert_snapshot("workspace_emptied", &calls);
+}
+
+#[test]
+fn test_power_source_change() {
+    let daemon = Daemon::start(laptop());
+    let (reply, calls) = daemon.send("on-power-source-changed battery");
+    assert_eq!(reply, "ok");
+    assert_snapshot("power_source_change", &calls);
+}
+
+#[test]
+fn test_volume_scroll_and_click() {
+    let mut stubs = laptop();
+    stubs.push(Stub::new("osascript").reply("-e output volume", "40").reply("-e output muted", "false"));
+    let daemon = Daemon::start(stubs);
+
+    // Drawn for the new level without waiting for the output to confirm it
+    let (reply, calls) = daemon.send("on-volume-scroll 2");
+    assert_eq!(reply, "ok");
+    assert_snapshot("volume_scroll", &calls);
+
+    let (reply, calls) = daemon.send("on-volume-clicked");
+    assert_eq!(reply, "ok");
+    assert_snapshot("volume_click", &calls);
+
+    let (_, calls) = daemon.send("on-volume-clicked alt");
+    assert_eq!(calls, "sketchybar --set volume popup.drawing=toggle\n");
+}
+
+#[test]
+fn test_focus_change_is_deduplicated() {
+    let daemon = Daemon::start(laptop());
+
+    // The startup refresh already showed Safari
+    let (reply, calls) = daemon.send("on-focus-changed");
+    assert_eq!(reply, "ok");
+    assert_snapshot("focus_unchanged", &calls);
+
+    let (_, calls) = daemon.send("reload-icons");
+    assert_snapshot("icons_reload", &calls);
+}
+
+#[test]
+fn test_unknown_message() {
+    let daemon = Daemon::start(laptop());
+    let (reply, calls) = daemon.send("on-nothing-happened");
+    assert_eq!(reply, "error Unknown message: on-nothing-happened");
+    assert!(calls.is_empty());
+}
+
+#[test]
+fn test_watch_streams_messages() {
+    let daemon = Daemon::start(laptop());
+    let mut watch = UnixStream::connect(daemon.socket()).unwrap();
+    writeln!(watch, "watch message").unwrap();
+    watch.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+    let mut events = BufReader::new(&watch);
+
+    // The watcher subscribes some time after connecting, so repeat the message until it shows up
+    let started = Instant::now();
+    let mut line = String::new();
+    while !line.contains("sync on-nothing-happened") {
+        assert!(started.elapsed() < TIMEOUT, "no event reached the watcher:\n{}", daemon.stderr());
+        daemon.send("on-nothing-happened");
+        line.clear();
+        let _ = events.read_line(&mut line);
+    }
+    assert!(line.contains("message    sync on-nothing-happened"), "{}", line);
+}
+
+#[test]
+fn test_refresh_provider() {
+    let daemon = Daemon::start(laptop());
+    let (reply, calls) = daemon.send("refresh clock");
+    assert_eq!(reply, "ok");
+    assert_snapshot("clock_refresh", &calls);
+
+    let (reply, _) = daemon.send("refresh front_app");
+    assert_eq!(reply, "error No provider for front_app");
+}
+
+#[test]
+fn test_refresh_weather() {
+    let mut stubs = laptop();
+    let wttr = r#"{"current_condition": [{"temp_C": "12", "FeelsLikeC": "10", "weatherCode": "116",
+        "weatherDesc": [{"value": "Partly cloudy"}]}], "nearest_area": [{"areaName": [{"value": "Berlin"}]}],
+        "weather": [{"date": "2026-10-16", "maxtempC": "15", "mintempC": "8",
+        "hourly": [{"time": "1200", "weatherCode": "296"}]}]}"#;
+    stubs.push(Stub::new("curl").reply("--silent", wttr));
+    let daemon = Daemon::start(stubs);
+
+    // Shown from the cache the startup refresh wrote
+    let (reply, calls) = daemon.send("refresh weather");
+    assert_eq!(reply, "ok");
+    assert_snapshot("weather_refresh", &calls);
+}
+
+#[test]
+fn test_json_requests() {
+    let daemon = Daemon::start(laptop());
+
+    let (ok, stdout, stderr) = daemon.sketchycli(&["status", "--json"]);
+    assert!(ok, "{}", stderr);
+    let status: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(status["front_app"], "Safari");
+    assert_eq!(status["workspace"], "1");
+    assert_eq!(status["battery"], serde_json::json!({"percent": 64, "charging": false}));
+    assert_eq!(status["quiet"], false);
+
+    // Errors come back to the client rather than only going to the daemon's log
+    let (ok, _, stderr) = daemon.sketchycli(&["--wait", "on-nothing-happened"]);
+    assert!(!ok);
+    assert_eq!(stderr, "Unknown message: on-nothing-happened");
+
+    // Raw requests echo their id, and a connection can carry several
+    let mut stream = UnixStream::connect(daemon.socket()).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    writeln!(stream, r#"{{"v":1,"id":41,"command":"quiet"}}"#).unwrap();
+    writeln!(stream, r#"{{"v":9,"id":42,"command":"quiet"}}"#).unwrap();
+    let mut replies = BufReader::new(&stream).lines();
+    let reply: serde_json::Value = serde_json::from_str(&replies.next().unwrap().unwrap()).unwrap();
+    assert_eq!((reply["id"].as_u64(), reply["ok"].as_bool()), (Some(41), Some(true)));
+    let reply: serde_json::Value = serde_json::from_str(&replies.next().unwrap().unwrap()).unwrap();
+    assert_eq!((reply["id"].as_u64(), reply["ok"].as_bool()), (Some(42), Some(false)));
+}
+
+#[test]
+fn test_query_item_state() {
+    let daemon = Daemon::start(laptop());
+
+    let (ok, stdout, stderr) = daemon.sketchycli(&["query", "battery", "--json"]);
+    assert!(ok, "{}", stderr);
+    let battery: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!((battery["percentage"].as_u64(), battery["charging"].as_bool()), (Some(64), Some(false)));
+    assert!(battery["refreshed_at"].as_u64().is_some());
+
+    let (_, stdout, _) = daemon.sketchycli(&["query", "workspaces", "--json"]);
+    let layout: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(layout["focused"], "1");
+    assert_eq!(layout["workspaces"]["2"]["apps"], serde_json::json!(["Terminal"]));
+
+    let (ok, stdout, _) = daemon.sketchycli(&["query", "front_app"]);
+    assert!(ok);
+    assert!(stdout.lines().any(|line| line.split_whitespace().eq(["name", "Safari"])), "{}", stdout);
+
+    let (ok, _, stderr) = daemon.sketchycli(&["query", "stocks"]);
+    assert!(!ok);
+    let known = "Nothing recorded for stocks; try one of: appearance, battery, brew, calendar";
+    assert!(stderr.starts_with(known), "{}", stderr);
+}
+
+#[test]
+fn test_config_reload() {
+    let daemon = Daemon::start(laptop());
+    fs::write(&daemon.log, "").unwrap();
+
+    // Turning the teams item off hides it without a restart
+    let path = daemon.root.join("config/sketchybar/sketchybartender.toml");
+    let config = fs::read_to_string(&path).unwrap();
+    fs::write(&path, format!("[items]\ndisabled = [\"teams\"]\n\n{}", config)).unwrap();
+
+    let started = Instant::now();
+    while !fs::read_to_string(&daemon.log).unwrap().contains("sketchybar --set teams drawing=off") {
+        assert!(started.elapsed() < TIMEOUT, "config wasn't reloaded:\n{}", daemon.stderr());
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert!(daemon.stderr().contains("[CONFIG] Reloading"));
+}
+
+#[test]
+fn test_sigterm_cleans_up() {
+    let mut daemon = Daemon::start(laptop());
+
+    let path = daemon.root.join("config/sketchybar/sketchybartender.toml");
+    let config = fs::read_to_string(&path).unwrap();
+    fs::write(&path, format!("clear_on_exit = true\n\n[history]\ngraphs = [\"battery\"]\n\n{}", config)).unwrap();
+    let started = Instant::now();
+    while !daemon.stderr().contains("[CONFIG] Reloading") {
+        assert!(started.elapsed() < TIMEOUT, "config wasn't reloaded:\n{}", daemon.stderr());
+        thread::sleep(Duration::from_millis(50));
+    }
+    daemon.settle();
+    fs::write(&daemon.log, "").unwrap();
+
+    let killed = Command::new("kill").args(["-TERM", &daemon.child.id().to_string()]).status().unwrap();
+    assert!(killed.success());
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = daemon.child.try_wait().unwrap() {
+            break status;
+        }
+        assert!(started.elapsed() < TIMEOUT, "daemon didn't exit:\n{}", daemon.stderr());
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    // The socket is gone and so is the graph item the daemon added
+    assert!(status.success(), "daemon exited with {}:\n{}", status, daemon.stderr());
+    assert!(!daemon.socket().exists());
+    assert!(fs::read_to_string(&daemon.log).unwrap().contains("sketchybar --remove battery_graph"));
+    assert!(daemon.stderr().contains("[SHUTDOWN] Stopped"));
+}
+
+#[test]
+fn test_failing_provider_is_degraded() {
+    let mut stubs = laptop();
+    stubs.push(Stub::new("brew").failing());
+    let daemon = Daemon::start(stubs);
+
+    // The startup refresh failed once already
+    for _ in 0..2 {
+        daemon.send("refresh brew");
+    }
+
+    let (ok, stdout, stderr) = daemon.sketchycli(&["status", "--json"]);
+    assert!(ok, "{}", stderr);
+    let status: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(status["degraded"], serde_json::json!(["brew"]));
+    assert_eq!(status["providers"]["brew"]["failures"], 3);
+    assert!(status["providers"]["brew"]["last_error"].as_str().unwrap().starts_with("brew outdated"));
+    assert_eq!(status["providers"]["battery"]["failures"], 0);
+    assert!(daemon.stderr().contains("[PROVIDER] brew failed 3 time(s) in a row, degraded"));
+
+    let (_, stdout, _) = daemon.sketchycli(&["status"]);
+    assert!(stdout.lines().any(|line| line.starts_with("degraded") && line.ends_with(" brew")));
+}